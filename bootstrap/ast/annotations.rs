@@ -1,2 +1,28 @@
+use crate::ast::expr::Expr;
+use crate::ast::types::TypeExpr;
+use crate::lexer::token::Span;
 
+// `@Name` or `@Name(args)` in front of a declaration
+#[derive(Debug, Clone)]
+pub struct Annotation {
+	pub name: String,
+	pub args: Vec<Expr>,
+	pub span: Span
+}
 
+// `annotation Name { field: Type, ?optional: Type }`
+#[derive(Debug, Clone)]
+pub struct AnnotationDecl {
+	pub name: String,
+	pub doc: Option<String>,
+	pub params: Vec<AnnotationParam>,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub struct AnnotationParam {
+	pub name: String,
+	pub ty: TypeExpr,
+	pub optional: bool,
+	pub span: Span
+}
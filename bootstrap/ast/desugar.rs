@@ -0,0 +1,45 @@
+use crate::ast::NodeId;
+use crate::ast::expr::{BinaryOp, Expr, ExprKind, InterpolationPart, Literal};
+use crate::lexer::token::Span;
+
+// Lowers "a ${b} c" to `"a " + b.toString() + " c"`. Every embedded
+// expression goes through `toString()` so the `+` chain is always string
+// concatenation, whatever the expression's type. `next_id` continues the
+// numbering of the program the parts came from.
+pub fn interpolation(parts: &[InterpolationPart], span: Span, next_id: &mut u32) -> Expr {
+	let mut fresh = |kind: ExprKind, span: Span| {
+		let id = NodeId(*next_id);
+		*next_id += 1;
+		Expr { id, kind, span }
+	};
+
+	let mut pieces = Vec::new();
+	for part in parts {
+		match part {
+			InterpolationPart::Text(text) if text.is_empty() => {}
+			InterpolationPart::Text(text) => pieces.push(fresh(ExprKind::Literal(Literal::String(text.clone())), span)),
+			InterpolationPart::Expr(expr) => {
+				let member = fresh(
+					ExprKind::Member {
+						object: Box::new(expr.clone()),
+						name: "toString".to_string(),
+						name_span: expr.span,
+						safe: false
+					},
+					expr.span
+				);
+				pieces.push(fresh(ExprKind::Call { callee: Box::new(member), args: Vec::new() }, expr.span));
+			}
+		}
+	}
+
+	let mut pieces = pieces.into_iter();
+	let Some(first) = pieces.next() else {
+		return fresh(ExprKind::Literal(Literal::String(String::new())), span);
+	};
+
+	pieces.fold(first, |left, right| {
+		let piece_span = left.span.to(right.span);
+		fresh(ExprKind::Binary { op: BinaryOp::Add, left: Box::new(left), right: Box::new(right) }, piece_span)
+	})
+}
@@ -0,0 +1,149 @@
+use crate::ast::NodeId;
+use crate::ast::stmt::{Block, Param};
+use crate::ast::types::TypeExpr;
+use crate::lexer::token::Span;
+
+#[derive(Debug, Clone)]
+pub struct Expr {
+	pub id: NodeId,
+	pub kind: ExprKind,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
+	Literal(Literal),
+	// "Hello ${name}!" as alternating text and embedded expressions
+	Interpolation(Vec<InterpolationPart>),
+	Identifier(String),
+	This,
+	Super,
+
+	Unary { op: UnaryOp, operand: Box<Expr> },
+	Binary { op: BinaryOp, left: Box<Expr>, right: Box<Expr> },
+	// `op` is set for compound assignments like `x += 1`
+	Assign { target: Box<Expr>, op: Option<BinaryOp>, value: Box<Expr> },
+	// `x++`, `x--`, `x!!`, `x?`
+	Postfix { op: PostfixOp, operand: Box<Expr> },
+	Is { operand: Box<Expr>, ty: TypeExpr, negated: bool },
+	// `if (cond) a else b` used as a value; `elif` nests in `else_branch`
+	If { cond: Box<Expr>, then_branch: Box<Expr>, else_branch: Box<Expr> },
+	// `{ stmts; value }` as an if-expression branch, valued by its last expression
+	Block(Block),
+
+	Call { callee: Box<Expr>, args: Vec<Expr> },
+	// `safe` for `a?.b`, which is null instead of failing when `a` is null
+	Member { object: Box<Expr>, name: String, name_span: Span, safe: bool },
+	Index { object: Box<Expr>, index: Box<Expr> },
+	New { class: TypeExpr, args: Vec<Expr> },
+
+	// Half-open `start..end`
+	Range { start: Box<Expr>, end: Box<Expr> },
+	Array(Vec<Expr>),
+	// `[key: value, ...]`, and `[:]` for an empty one
+	Map(Vec<(Expr, Expr)>),
+	Lambda { params: Vec<Param>, body: LambdaBody },
+	Await(Box<Expr>),
+	// `spawn { ... }`: the block run as a task of its own, valued by its last
+	// expression when awaited
+	Spawn(Block),
+	// `return` and `throw` used as values, as in `x ?? return`
+	Return(Option<Box<Expr>>),
+	Throw(Box<Expr>)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+	Int(i64),
+	Float(f64),
+	String(String),
+	Bool(bool),
+	Null
+}
+
+#[derive(Debug, Clone)]
+pub enum InterpolationPart {
+	Text(String),
+	Expr(Expr)
+}
+
+#[derive(Debug, Clone)]
+pub enum LambdaBody {
+	Expr(Box<Expr>),
+	Block(Block)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+	Negate,
+	Not,
+	PreIncrement,
+	PreDecrement,
+	Typeof
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostfixOp {
+	Increment,
+	Decrement,
+	NonNull,
+	// `?`: the value of an `Ok` or a `Some`, returning an `Err` or `None`
+	// from the function
+	Propagate
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+	Add,
+	Subtract,
+	Multiply,
+	Divide,
+	Modulo,
+
+	Equal,
+	NotEqual,
+	Less,
+	LessEqual,
+	Greater,
+	GreaterEqual,
+
+	And,
+	Or,
+	// `a ?? b` evaluates `b` only when `a` is null
+	Coalesce,
+
+	BitAnd,
+	BitOr,
+	BitXor,
+	ShiftLeft,
+	ShiftRight,
+
+	In
+}
+
+impl BinaryOp {
+	pub fn symbol(self) -> &'static str {
+		match self {
+			BinaryOp::Add => "+",
+			BinaryOp::Subtract => "-",
+			BinaryOp::Multiply => "*",
+			BinaryOp::Divide => "/",
+			BinaryOp::Modulo => "%",
+			BinaryOp::Equal => "==",
+			BinaryOp::NotEqual => "!=",
+			BinaryOp::Less => "<",
+			BinaryOp::LessEqual => "<=",
+			BinaryOp::Greater => ">",
+			BinaryOp::GreaterEqual => ">=",
+			BinaryOp::And => "&&",
+			BinaryOp::Or => "||",
+			BinaryOp::Coalesce => "??",
+			BinaryOp::BitAnd => "&",
+			BinaryOp::BitOr => "|",
+			BinaryOp::BitXor => "^",
+			BinaryOp::ShiftLeft => "<<",
+			BinaryOp::ShiftRight => ">>",
+			BinaryOp::In => "in"
+		}
+	}
+}
@@ -1,5 +1,21 @@
 pub mod annotations;
+pub mod desugar;
 pub mod expr;
+pub mod spans;
 pub mod stmt;
 pub mod types;
 pub mod visitor;
+
+use crate::ast::stmt::Stmt;
+
+// Every expression and declaration gets an id so later phases can keep
+// side tables (resolutions, types) without touching the tree.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct NodeId(pub u32);
+
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+	pub stmts: Vec<Stmt>,
+	// First id not used by the parser; passes that synthesize nodes continue from here
+	pub next_node_id: u32
+}
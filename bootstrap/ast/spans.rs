@@ -0,0 +1,348 @@
+use crate::ast::Program;
+use crate::ast::annotations::Annotation;
+use crate::ast::expr::{Expr, ExprKind, InterpolationPart, LambdaBody};
+use crate::ast::stmt::{Accessor, Block, FieldDecl, FnBody, FnDecl, ForPattern, Member, Param, Stmt, StmtKind};
+use crate::ast::types::{TypeExpr, TypeExprKind, TypeParam};
+use crate::lexer::token::Span;
+
+// Calls `f` on every span stored in the tree. Used to move nodes around
+// after an edit without reparsing them.
+pub fn for_each_span_mut(program: &mut Program, f: &mut dyn FnMut(&mut Span)) {
+	for stmt in &mut program.stmts {
+		stmt_spans(stmt, f);
+	}
+}
+
+pub fn stmt_spans(stmt: &mut Stmt, f: &mut dyn FnMut(&mut Span)) {
+	f(&mut stmt.span);
+	match &mut stmt.kind {
+		StmtKind::Expr(expr) | StmtKind::Yield(expr) | StmtKind::Throw(expr) => expr_spans(expr, f),
+		StmtKind::Var(decl) => {
+			f(&mut decl.name_span);
+			opt_type_spans(&mut decl.ty, f);
+			opt_expr_spans(&mut decl.init, f);
+		}
+		StmtKind::Block(block) | StmtKind::Loop { body: block } => block_spans(block, f),
+		StmtKind::If { cond, then_branch, else_branch } => {
+			expr_spans(cond, f);
+			block_spans(then_branch, f);
+			if let Some(else_branch) = else_branch {
+				stmt_spans(else_branch, f);
+			}
+		}
+		StmtKind::While { cond, body } => {
+			expr_spans(cond, f);
+			block_spans(body, f);
+		}
+		StmtKind::For(for_loop) => {
+			match &mut for_loop.pattern {
+				ForPattern::Single(binding) => f(&mut binding.span),
+				ForPattern::Tuple(bindings) => {
+					for binding in bindings {
+						f(&mut binding.span);
+					}
+				}
+			}
+			expr_spans(&mut for_loop.iterable, f);
+			block_spans(&mut for_loop.body, f);
+		}
+		StmtKind::Labeled { body, .. } | StmtKind::Defer(body) => stmt_spans(body, f),
+		StmtKind::Break(_) | StmtKind::Continue(_) | StmtKind::Package(_) => {}
+		StmtKind::Return(value) => opt_expr_spans(value, f),
+		StmtKind::Try { body, catches, finally } => {
+			block_spans(body, f);
+			for catch in catches {
+				f(&mut catch.span);
+				opt_type_spans(&mut catch.ty, f);
+				block_spans(&mut catch.body, f);
+			}
+			if let Some(finally) = finally {
+				block_spans(finally, f);
+			}
+		}
+		StmtKind::Switch { subject, cases, default } => {
+			expr_spans(subject, f);
+			for case in cases {
+				f(&mut case.span);
+				for pattern in &mut case.patterns {
+					expr_spans(pattern, f);
+				}
+				block_spans(&mut case.body, f);
+			}
+			if let Some(default) = default {
+				block_spans(default, f);
+			}
+		}
+		StmtKind::Function(decl) => fn_spans(decl, f),
+		StmtKind::Class(decl) => {
+			f(&mut decl.span);
+			f(&mut decl.name_span);
+			annotation_spans(&mut decl.annotations, f);
+			type_param_spans(&mut decl.type_params, f);
+			for ty in &mut decl.supertypes {
+				type_spans(ty, f);
+			}
+			member_spans(&mut decl.members, f);
+		}
+		StmtKind::Interface(decl) => {
+			f(&mut decl.span);
+			f(&mut decl.name_span);
+			annotation_spans(&mut decl.annotations, f);
+			type_param_spans(&mut decl.type_params, f);
+			for ty in &mut decl.supertypes {
+				type_spans(ty, f);
+			}
+			member_spans(&mut decl.members, f);
+		}
+		StmtKind::Enum(decl) => {
+			f(&mut decl.span);
+			f(&mut decl.name_span);
+			annotation_spans(&mut decl.annotations, f);
+			for ty in &mut decl.supertypes {
+				type_spans(ty, f);
+			}
+			for variant in &mut decl.variants {
+				f(&mut variant.span);
+				for arg in &mut variant.args {
+					expr_spans(arg, f);
+				}
+			}
+			member_spans(&mut decl.members, f);
+		}
+		StmtKind::Struct(decl) => {
+			f(&mut decl.span);
+			f(&mut decl.name_span);
+			annotation_spans(&mut decl.annotations, f);
+			type_param_spans(&mut decl.type_params, f);
+			for field in &mut decl.fields {
+				field_spans(field, f);
+			}
+		}
+		StmtKind::Annotation(decl) => {
+			f(&mut decl.span);
+			for param in &mut decl.params {
+				f(&mut param.span);
+				type_spans(&mut param.ty, f);
+			}
+		}
+		StmtKind::TypeAlias(decl) => {
+			f(&mut decl.span);
+			f(&mut decl.name_span);
+			type_param_spans(&mut decl.type_params, f);
+			type_spans(&mut decl.ty, f);
+		}
+		StmtKind::Import(decl) => f(&mut decl.span)
+	}
+}
+
+pub fn block_spans(block: &mut Block, f: &mut dyn FnMut(&mut Span)) {
+	f(&mut block.span);
+	for stmt in &mut block.stmts {
+		stmt_spans(stmt, f);
+	}
+}
+
+pub fn fn_spans(decl: &mut FnDecl, f: &mut dyn FnMut(&mut Span)) {
+	f(&mut decl.span);
+	f(&mut decl.name_span);
+	annotation_spans(&mut decl.annotations, f);
+	opt_type_spans(&mut decl.receiver, f);
+	type_param_spans(&mut decl.type_params, f);
+	param_spans(&mut decl.params, f);
+	opt_type_spans(&mut decl.return_type, f);
+	for ty in &mut decl.throws {
+		type_spans(ty, f);
+	}
+	if let Some(body) = &mut decl.body {
+		fn_body_spans(body, f);
+	}
+}
+
+pub fn member_spans(members: &mut [Member], f: &mut dyn FnMut(&mut Span)) {
+	for member in members {
+		match member {
+			Member::Field(field) => field_spans(field, f),
+			Member::Method(method) => fn_spans(method, f),
+			Member::Constructor(ctor) => {
+				f(&mut ctor.span);
+				param_spans(&mut ctor.params, f);
+				block_spans(&mut ctor.body, f);
+			}
+		}
+	}
+}
+
+fn field_spans(field: &mut FieldDecl, f: &mut dyn FnMut(&mut Span)) {
+	f(&mut field.span);
+	f(&mut field.name_span);
+	annotation_spans(&mut field.annotations, f);
+	opt_type_spans(&mut field.ty, f);
+	opt_expr_spans(&mut field.init, f);
+	if let Some(getter) = &mut field.getter {
+		accessor_spans(getter, f);
+	}
+	if let Some(setter) = &mut field.setter {
+		accessor_spans(setter, f);
+	}
+}
+
+fn accessor_spans(accessor: &mut Accessor, f: &mut dyn FnMut(&mut Span)) {
+	f(&mut accessor.span);
+	if let Some(param) = &mut accessor.param {
+		param_spans(std::slice::from_mut(param), f);
+	}
+	if let Some(body) = &mut accessor.body {
+		fn_body_spans(body, f);
+	}
+}
+
+fn fn_body_spans(body: &mut FnBody, f: &mut dyn FnMut(&mut Span)) {
+	match body {
+		FnBody::Block(block) => block_spans(block, f),
+		FnBody::Expr(expr) => expr_spans(expr, f)
+	}
+}
+
+fn param_spans(params: &mut [Param], f: &mut dyn FnMut(&mut Span)) {
+	for param in params {
+		f(&mut param.span);
+		opt_type_spans(&mut param.ty, f);
+		opt_expr_spans(&mut param.default, f);
+	}
+}
+
+fn annotation_spans(annotations: &mut [Annotation], f: &mut dyn FnMut(&mut Span)) {
+	for annotation in annotations {
+		f(&mut annotation.span);
+		for arg in &mut annotation.args {
+			expr_spans(arg, f);
+		}
+	}
+}
+
+fn type_param_spans(params: &mut [TypeParam], f: &mut dyn FnMut(&mut Span)) {
+	for param in params {
+		f(&mut param.span);
+		for bound in &mut param.bounds {
+			type_spans(bound, f);
+		}
+	}
+}
+
+fn opt_type_spans(ty: &mut Option<TypeExpr>, f: &mut dyn FnMut(&mut Span)) {
+	if let Some(ty) = ty {
+		type_spans(ty, f);
+	}
+}
+
+pub fn type_spans(ty: &mut TypeExpr, f: &mut dyn FnMut(&mut Span)) {
+	f(&mut ty.span);
+	match &mut ty.kind {
+		TypeExprKind::Named { args, .. } => {
+			for arg in args {
+				type_spans(arg, f);
+			}
+		}
+		TypeExprKind::Nullable(inner) | TypeExprKind::Array(inner) => type_spans(inner, f),
+		TypeExprKind::Function { params, ret } => {
+			for param in params {
+				type_spans(param, f);
+			}
+			type_spans(ret, f);
+		}
+		TypeExprKind::Union(members) => {
+			for member in members {
+				type_spans(member, f);
+			}
+		}
+	}
+}
+
+fn opt_expr_spans(expr: &mut Option<Expr>, f: &mut dyn FnMut(&mut Span)) {
+	if let Some(expr) = expr {
+		expr_spans(expr, f);
+	}
+}
+
+pub fn expr_spans(expr: &mut Expr, f: &mut dyn FnMut(&mut Span)) {
+	f(&mut expr.span);
+	match &mut expr.kind {
+		ExprKind::Literal(_) | ExprKind::Identifier(_) | ExprKind::This | ExprKind::Super => {}
+		ExprKind::Interpolation(parts) => {
+			for part in parts {
+				if let InterpolationPart::Expr(inner) = part {
+					expr_spans(inner, f);
+				}
+			}
+		}
+		ExprKind::Unary { operand, .. }
+		| ExprKind::Postfix { operand, .. }
+		| ExprKind::Await(operand)
+		| ExprKind::Throw(operand) => expr_spans(operand, f),
+		ExprKind::Return(value) => {
+			if let Some(value) = value {
+				expr_spans(value, f);
+			}
+		}
+		ExprKind::Binary { left, right, .. } => {
+			expr_spans(left, f);
+			expr_spans(right, f);
+		}
+		ExprKind::Assign { target, value, .. } => {
+			expr_spans(target, f);
+			expr_spans(value, f);
+		}
+		ExprKind::Is { operand, ty, .. } => {
+			expr_spans(operand, f);
+			type_spans(ty, f);
+		}
+		ExprKind::If { cond, then_branch, else_branch } => {
+			expr_spans(cond, f);
+			expr_spans(then_branch, f);
+			expr_spans(else_branch, f);
+		}
+		ExprKind::Block(block) | ExprKind::Spawn(block) => block_spans(block, f),
+		ExprKind::Call { callee, args } => {
+			expr_spans(callee, f);
+			for arg in args {
+				expr_spans(arg, f);
+			}
+		}
+		ExprKind::Member { object, name_span, .. } => {
+			expr_spans(object, f);
+			f(name_span);
+		}
+		ExprKind::Index { object, index } => {
+			expr_spans(object, f);
+			expr_spans(index, f);
+		}
+		ExprKind::New { class, args } => {
+			type_spans(class, f);
+			for arg in args {
+				expr_spans(arg, f);
+			}
+		}
+		ExprKind::Range { start, end } => {
+			expr_spans(start, f);
+			expr_spans(end, f);
+		}
+		ExprKind::Array(items) => {
+			for item in items {
+				expr_spans(item, f);
+			}
+		}
+		ExprKind::Map(entries) => {
+			for (key, value) in entries {
+				expr_spans(key, f);
+				expr_spans(value, f);
+			}
+		}
+		ExprKind::Lambda { params, body } => {
+			param_spans(params, f);
+			match body {
+				LambdaBody::Expr(body) => expr_spans(body, f),
+				LambdaBody::Block(block) => block_spans(block, f)
+			}
+		}
+	}
+}
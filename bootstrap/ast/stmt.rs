@@ -0,0 +1,416 @@
+use crate::ast::NodeId;
+use crate::ast::annotations::{Annotation, AnnotationDecl};
+use crate::ast::expr::Expr;
+use crate::ast::types::{TypeExpr, TypeParam};
+use crate::lexer::token::Span;
+
+#[derive(Debug, Clone)]
+pub struct Stmt {
+	pub kind: StmtKind,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub enum StmtKind {
+	Expr(Expr),
+	Var(VarDecl),
+	Block(Block),
+
+	// `elif` chains are stored as a nested `If` in `else_branch`
+	If { cond: Expr, then_branch: Block, else_branch: Option<Box<Stmt>> },
+	While { cond: Expr, body: Block },
+	Loop { body: Block },
+	For(ForLoop),
+	// `outer: for ...`, which `break outer` and `continue outer` refer to;
+	// `body` is always a loop
+	Labeled { label: String, body: Box<Stmt> },
+	// With the label of the loop to leave, when not the innermost one
+	Break(Option<String>),
+	Continue(Option<String>),
+	Return(Option<Expr>),
+	// `yield value`: the next element of the iterator that calling the
+	// function it is in makes
+	Yield(Expr),
+	Throw(Expr),
+	Try { body: Block, catches: Vec<CatchClause>, finally: Option<Block> },
+	// `defer file.close()`: run when the block it is in is left, however
+	// that happens, after what was deferred later in it; `body` is an
+	// expression statement or a block
+	Defer(Box<Stmt>),
+	Switch { subject: Expr, cases: Vec<SwitchCase>, default: Option<Block> },
+
+	Function(Box<FnDecl>),
+	Class(ClassDecl),
+	Interface(InterfaceDecl),
+	Enum(EnumDecl),
+	Struct(StructDecl),
+	Annotation(AnnotationDecl),
+	TypeAlias(TypeAliasDecl),
+	Import(ImportDecl),
+	Package(Vec<String>)
+}
+
+impl Stmt {
+	pub fn doc(&self) -> Option<&str> {
+		match &self.kind {
+			StmtKind::Var(decl) => decl.doc.as_deref(),
+			StmtKind::Function(decl) => decl.doc.as_deref(),
+			StmtKind::Class(decl) => decl.doc.as_deref(),
+			StmtKind::Interface(decl) => decl.doc.as_deref(),
+			StmtKind::Enum(decl) => decl.doc.as_deref(),
+			StmtKind::Struct(decl) => decl.doc.as_deref(),
+			StmtKind::Annotation(decl) => decl.doc.as_deref(),
+			StmtKind::TypeAlias(decl) => decl.doc.as_deref(),
+			_ => None
+		}
+	}
+
+	pub fn set_doc(&mut self, doc: Option<String>) {
+		match &mut self.kind {
+			StmtKind::Var(decl) => decl.doc = doc,
+			StmtKind::Function(decl) => decl.doc = doc,
+			StmtKind::Class(decl) => decl.doc = doc,
+			StmtKind::Interface(decl) => decl.doc = doc,
+			StmtKind::Enum(decl) => decl.doc = doc,
+			StmtKind::Struct(decl) => decl.doc = doc,
+			StmtKind::Annotation(decl) => decl.doc = doc,
+			StmtKind::TypeAlias(decl) => decl.doc = doc,
+			_ => {}
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Block {
+	pub stmts: Vec<Stmt>,
+	pub span: Span
+}
+
+// Ordered from least to most restrictive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Visibility {
+	#[default]
+	Public,
+	Protected,
+	Private
+}
+
+impl Visibility {
+	pub fn keyword(self) -> &'static str {
+		match self {
+			Visibility::Public => "public",
+			Visibility::Protected => "protected",
+			Visibility::Private => "private"
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Modifiers {
+	pub visibility: Visibility,
+	pub is_abstract: bool,
+	// Only subclasses declared in the same package can extend it, so a
+	// switch can know them all
+	pub is_sealed: bool,
+	pub is_override: bool,
+	pub is_async: bool,
+	pub is_data: bool,
+	// A member of the class itself rather than of its objects
+	pub is_static: bool,
+	// `extern "libm.so.6"`: a function of that shared library, written in C;
+	// boxed, since few declarations have one
+	pub library: Option<Box<str>>
+}
+
+#[derive(Debug, Clone)]
+pub struct VarDecl {
+	pub id: NodeId,
+	pub name: String,
+	pub name_span: Span,
+	pub mutable: bool,
+	// `const`: the initializer is worked out at compile time and uses of the
+	// name are replaced by its value
+	pub constant: bool,
+	pub ty: Option<TypeExpr>,
+	pub init: Option<Expr>,
+	pub doc: Option<String>
+}
+
+#[derive(Debug, Clone)]
+pub struct Param {
+	pub id: NodeId,
+	pub name: String,
+	pub ty: Option<TypeExpr>,
+	pub default: Option<Expr>,
+	pub variadic: bool,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub enum FnBody {
+	Block(Block),
+	// `= expr` shorthand
+	Expr(Expr)
+}
+
+#[derive(Debug, Clone)]
+pub struct FnDecl {
+	pub id: NodeId,
+	pub name: String,
+	pub name_span: Span,
+	// `fn String.shout()` extends `String`; `this` is the receiver inside the body
+	pub receiver: Option<TypeExpr>,
+	pub modifiers: Modifiers,
+	pub annotations: Vec<Annotation>,
+	// Text of the `///` or `/** */` comment in front of the declaration
+	pub doc: Option<String>,
+	pub type_params: Vec<TypeParam>,
+	pub params: Vec<Param>,
+	pub return_type: Option<TypeExpr>,
+	pub throws: Vec<TypeExpr>,
+	pub body: Option<FnBody>,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassDecl {
+	pub id: NodeId,
+	pub name: String,
+	pub name_span: Span,
+	pub modifiers: Modifiers,
+	pub annotations: Vec<Annotation>,
+	pub doc: Option<String>,
+	pub type_params: Vec<TypeParam>,
+	pub supertypes: Vec<TypeExpr>,
+	pub members: Vec<Member>,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub struct InterfaceDecl {
+	pub id: NodeId,
+	pub name: String,
+	pub name_span: Span,
+	pub modifiers: Modifiers,
+	pub annotations: Vec<Annotation>,
+	pub doc: Option<String>,
+	pub type_params: Vec<TypeParam>,
+	pub supertypes: Vec<TypeExpr>,
+	pub members: Vec<Member>,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumDecl {
+	pub id: NodeId,
+	pub name: String,
+	pub name_span: Span,
+	pub modifiers: Modifiers,
+	pub annotations: Vec<Annotation>,
+	pub doc: Option<String>,
+	pub supertypes: Vec<TypeExpr>,
+	pub variants: Vec<EnumVariant>,
+	pub members: Vec<Member>,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumVariant {
+	pub id: NodeId,
+	pub name: String,
+	pub doc: Option<String>,
+	pub args: Vec<Expr>,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub struct StructDecl {
+	pub id: NodeId,
+	pub name: String,
+	pub name_span: Span,
+	pub modifiers: Modifiers,
+	pub annotations: Vec<Annotation>,
+	pub doc: Option<String>,
+	pub type_params: Vec<TypeParam>,
+	pub fields: Vec<FieldDecl>,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub enum Member {
+	Field(FieldDecl),
+	Method(FnDecl),
+	Constructor(ConstructorDecl)
+}
+
+impl Member {
+	pub fn doc(&self) -> Option<&str> {
+		match self {
+			Member::Field(field) => field.doc.as_deref(),
+			Member::Method(method) => method.doc.as_deref(),
+			Member::Constructor(ctor) => ctor.doc.as_deref()
+		}
+	}
+
+	pub fn set_doc(&mut self, doc: Option<String>) {
+		match self {
+			Member::Field(field) => field.doc = doc,
+			Member::Method(method) => method.doc = doc,
+			Member::Constructor(ctor) => ctor.doc = doc
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldDecl {
+	pub id: NodeId,
+	pub name: String,
+	pub name_span: Span,
+	pub modifiers: Modifiers,
+	pub annotations: Vec<Annotation>,
+	pub doc: Option<String>,
+	pub mutable: bool,
+	pub ty: Option<TypeExpr>,
+	pub init: Option<Expr>,
+	pub getter: Option<Box<Accessor>>,
+	pub setter: Option<Box<Accessor>>,
+	pub span: Span
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessorKind {
+	Get,
+	Set
+}
+
+// `get() = expr`, `get { ... }` or `set(value) { ... }` following a field.
+// Inside the body `field` refers to the backing storage.
+#[derive(Debug, Clone)]
+pub struct Accessor {
+	pub id: NodeId,
+	pub kind: AccessorKind,
+	pub visibility: Visibility,
+	pub param: Option<Param>,
+	pub body: Option<FnBody>,
+	pub span: Span
+}
+
+impl Accessor {
+	// Name of the synthetic method an accessor lowers to, e.g. `get$name`.
+	pub fn method_name(&self, field: &str) -> String {
+		match self.kind {
+			AccessorKind::Get => format!("get${}", field),
+			AccessorKind::Set => format!("set${}", field)
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct ConstructorDecl {
+	pub id: NodeId,
+	pub modifiers: Modifiers,
+	pub doc: Option<String>,
+	pub params: Vec<Param>,
+	pub body: Block,
+	pub span: Span
+}
+
+// Loops run over the iterator protocol:
+//
+//   for x in items { body }
+//     => val it = items.iterator(); while (it.hasNext()) { val x = it.next(); body }
+//
+// `of` walks `items.entries()` instead, whose elements are (key, value) pairs
+// for maps and (index, value) pairs for lists, and destructures each one.
+//
+// `iterator()` can also give a built-in `Iterator<T>`, which has the same
+// `hasNext()` and `next()`, like the one every list, map, set and range
+// makes with its own `iterator()`.
+pub const ITERATOR_METHOD: &str = "iterator";
+pub const ENTRIES_METHOD: &str = "entries";
+pub const HAS_NEXT_METHOD: &str = "hasNext";
+pub const NEXT_METHOD: &str = "next";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForKind {
+	In,
+	Of
+}
+
+#[derive(Debug, Clone)]
+pub struct Binding {
+	pub id: NodeId,
+	pub name: String,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub enum ForPattern {
+	Single(Binding),
+	Tuple(Vec<Binding>)
+}
+
+impl ForPattern {
+	pub fn bindings(&self) -> &[Binding] {
+		match self {
+			ForPattern::Single(binding) => std::slice::from_ref(binding),
+			ForPattern::Tuple(bindings) => bindings
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct ForLoop {
+	pub kind: ForKind,
+	pub pattern: ForPattern,
+	pub iterable: Expr,
+	pub body: Block
+}
+
+impl ForLoop {
+	pub fn iterator_method(&self) -> &'static str {
+		match self.kind {
+			ForKind::In => ITERATOR_METHOD,
+			ForKind::Of => ENTRIES_METHOD
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct CatchClause {
+	pub id: NodeId,
+	pub name: String,
+	pub ty: Option<TypeExpr>,
+	pub body: Block,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub struct SwitchCase {
+	pub patterns: Vec<Expr>,
+	pub body: Block,
+	pub span: Span
+}
+
+// `type Name<T> = SomeType<T>`
+#[derive(Debug, Clone)]
+pub struct TypeAliasDecl {
+	pub id: NodeId,
+	pub name: String,
+	pub name_span: Span,
+	pub modifiers: Modifiers,
+	pub doc: Option<String>,
+	pub type_params: Vec<TypeParam>,
+	pub ty: TypeExpr,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportDecl {
+	pub path: Vec<String>,
+	pub wildcard: bool,
+	pub alias: Option<String>,
+	// Of the last name of the path, the one imported
+	pub name_span: Span,
+	pub span: Span
+}
@@ -0,0 +1,84 @@
+use std::fmt;
+
+use crate::ast::NodeId;
+use crate::lexer::token::Span;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeExprKind {
+	// `Int`, `a.b.Thing`, `Map<String, Int>`
+	Named { name: String, args: Vec<TypeExpr> },
+	// `String?`
+	Nullable(Box<TypeExpr>),
+	// `Int[]`
+	Array(Box<TypeExpr>),
+	// `(Int, String) -> Bool`
+	Function { params: Vec<TypeExpr>, ret: Box<TypeExpr> },
+	// `Int | String`
+	Union(Vec<TypeExpr>)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeExpr {
+	pub kind: TypeExprKind,
+	pub span: Span
+}
+
+// `T` or `T: Comparable & Hashable` in a declaration's `<...>` list
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeParam {
+	pub id: NodeId,
+	pub name: String,
+	pub bounds: Vec<TypeExpr>,
+	pub span: Span
+}
+
+impl fmt::Display for TypeExpr {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match &self.kind {
+			TypeExprKind::Named { name, args } => {
+				write!(f, "{}", name)?;
+				if !args.is_empty() {
+					write!(f, "<")?;
+					for (i, arg) in args.iter().enumerate() {
+						if i > 0 {
+							write!(f, ", ")?;
+						}
+						write!(f, "{}", arg)?;
+					}
+					write!(f, ">")?;
+				}
+				Ok(())
+			}
+			TypeExprKind::Nullable(inner) => match inner.kind {
+				TypeExprKind::Function { .. } | TypeExprKind::Union(_) => write!(f, "({})?", inner),
+				_ => write!(f, "{}?", inner)
+			},
+			TypeExprKind::Array(inner) => match inner.kind {
+				TypeExprKind::Function { .. } | TypeExprKind::Union(_) => write!(f, "({})[]", inner),
+				_ => write!(f, "{}[]", inner)
+			},
+			TypeExprKind::Function { params, ret } => {
+				write!(f, "(")?;
+				for (i, param) in params.iter().enumerate() {
+					if i > 0 {
+						write!(f, ", ")?;
+					}
+					write!(f, "{}", param)?;
+				}
+				write!(f, ") -> {}", ret)
+			}
+			TypeExprKind::Union(members) => {
+				for (i, member) in members.iter().enumerate() {
+					if i > 0 {
+						write!(f, " | ")?;
+					}
+					match member.kind {
+						TypeExprKind::Function { .. } => write!(f, "({})", member)?,
+						_ => write!(f, "{}", member)?
+					}
+				}
+				Ok(())
+			}
+		}
+	}
+}
@@ -0,0 +1,233 @@
+use crate::ast::Program;
+use crate::ast::expr::{Expr, ExprKind, InterpolationPart, LambdaBody};
+use crate::ast::stmt::{Accessor, Block, FnBody, FnDecl, Member, Stmt, StmtKind};
+
+// Read-only traversal. Override the hooks you care about and call the
+// matching `walk_*` function to keep descending.
+pub trait Visitor: Sized {
+	fn visit_stmt(&mut self, stmt: &Stmt) {
+		walk_stmt(self, stmt);
+	}
+
+	fn visit_expr(&mut self, expr: &Expr) {
+		walk_expr(self, expr);
+	}
+
+	fn visit_block(&mut self, block: &Block) {
+		walk_block(self, block);
+	}
+
+	fn visit_fn(&mut self, decl: &FnDecl) {
+		walk_fn(self, decl);
+	}
+}
+
+pub fn walk_program<V: Visitor>(v: &mut V, program: &Program) {
+	for stmt in &program.stmts {
+		v.visit_stmt(stmt);
+	}
+}
+
+pub fn walk_block<V: Visitor>(v: &mut V, block: &Block) {
+	for stmt in &block.stmts {
+		v.visit_stmt(stmt);
+	}
+}
+
+pub fn walk_fn<V: Visitor>(v: &mut V, decl: &FnDecl) {
+	for param in &decl.params {
+		if let Some(default) = &param.default {
+			v.visit_expr(default);
+		}
+	}
+	if let Some(body) = &decl.body {
+		walk_fn_body(v, body);
+	}
+}
+
+pub fn walk_fn_body<V: Visitor>(v: &mut V, body: &FnBody) {
+	match body {
+		FnBody::Block(block) => v.visit_block(block),
+		FnBody::Expr(expr) => v.visit_expr(expr)
+	}
+}
+
+fn walk_accessor<V: Visitor>(v: &mut V, accessor: &Accessor) {
+	if let Some(body) = &accessor.body {
+		walk_fn_body(v, body);
+	}
+}
+
+pub fn walk_members<V: Visitor>(v: &mut V, members: &[Member]) {
+	for member in members {
+		match member {
+			Member::Field(field) => {
+				if let Some(init) = &field.init {
+					v.visit_expr(init);
+				}
+				if let Some(getter) = &field.getter {
+					walk_accessor(v, getter);
+				}
+				if let Some(setter) = &field.setter {
+					walk_accessor(v, setter);
+				}
+			}
+			Member::Method(method) => v.visit_fn(method),
+			Member::Constructor(ctor) => v.visit_block(&ctor.body)
+		}
+	}
+}
+
+pub fn walk_stmt<V: Visitor>(v: &mut V, stmt: &Stmt) {
+	match &stmt.kind {
+		StmtKind::Expr(expr) => v.visit_expr(expr),
+		StmtKind::Var(decl) => {
+			if let Some(init) = &decl.init {
+				v.visit_expr(init);
+			}
+		}
+		StmtKind::Block(block) => v.visit_block(block),
+		StmtKind::If { cond, then_branch, else_branch } => {
+			v.visit_expr(cond);
+			v.visit_block(then_branch);
+			if let Some(else_branch) = else_branch {
+				v.visit_stmt(else_branch);
+			}
+		}
+		StmtKind::While { cond, body } => {
+			v.visit_expr(cond);
+			v.visit_block(body);
+		}
+		StmtKind::Loop { body } => v.visit_block(body),
+		StmtKind::For(for_loop) => {
+			v.visit_expr(&for_loop.iterable);
+			v.visit_block(&for_loop.body);
+		}
+		StmtKind::Labeled { body, .. } | StmtKind::Defer(body) => v.visit_stmt(body),
+		StmtKind::Break(_) | StmtKind::Continue(_) => {}
+		StmtKind::Return(value) => {
+			if let Some(value) = value {
+				v.visit_expr(value);
+			}
+		}
+		StmtKind::Yield(value) | StmtKind::Throw(value) => v.visit_expr(value),
+		StmtKind::Try { body, catches, finally } => {
+			v.visit_block(body);
+			for catch in catches {
+				v.visit_block(&catch.body);
+			}
+			if let Some(finally) = finally {
+				v.visit_block(finally);
+			}
+		}
+		StmtKind::Switch { subject, cases, default } => {
+			v.visit_expr(subject);
+			for case in cases {
+				for pattern in &case.patterns {
+					v.visit_expr(pattern);
+				}
+				v.visit_block(&case.body);
+			}
+			if let Some(default) = default {
+				v.visit_block(default);
+			}
+		}
+		StmtKind::Function(decl) => v.visit_fn(decl),
+		StmtKind::Class(decl) => walk_members(v, &decl.members),
+		StmtKind::Interface(decl) => walk_members(v, &decl.members),
+		StmtKind::Enum(decl) => {
+			for variant in &decl.variants {
+				for arg in &variant.args {
+					v.visit_expr(arg);
+				}
+			}
+			walk_members(v, &decl.members);
+		}
+		StmtKind::Struct(decl) => {
+			for field in &decl.fields {
+				if let Some(init) = &field.init {
+					v.visit_expr(init);
+				}
+			}
+		}
+		StmtKind::Annotation(_) | StmtKind::TypeAlias(_) | StmtKind::Import(_) | StmtKind::Package(_) => {}
+	}
+}
+
+pub fn walk_expr<V: Visitor>(v: &mut V, expr: &Expr) {
+	match &expr.kind {
+		ExprKind::Literal(_) | ExprKind::Identifier(_) | ExprKind::This | ExprKind::Super => {}
+		ExprKind::Interpolation(parts) => {
+			for part in parts {
+				if let InterpolationPart::Expr(inner) = part {
+					v.visit_expr(inner);
+				}
+			}
+		}
+		ExprKind::Unary { operand, .. } => v.visit_expr(operand),
+		ExprKind::Binary { left, right, .. } => {
+			v.visit_expr(left);
+			v.visit_expr(right);
+		}
+		ExprKind::Assign { target, value, .. } => {
+			v.visit_expr(target);
+			v.visit_expr(value);
+		}
+		ExprKind::Postfix { operand, .. } => v.visit_expr(operand),
+		ExprKind::Is { operand, .. } => v.visit_expr(operand),
+		ExprKind::If { cond, then_branch, else_branch } => {
+			v.visit_expr(cond);
+			v.visit_expr(then_branch);
+			v.visit_expr(else_branch);
+		}
+		ExprKind::Block(block) | ExprKind::Spawn(block) => v.visit_block(block),
+		ExprKind::Call { callee, args } => {
+			v.visit_expr(callee);
+			for arg in args {
+				v.visit_expr(arg);
+			}
+		}
+		ExprKind::Member { object, .. } => v.visit_expr(object),
+		ExprKind::Index { object, index } => {
+			v.visit_expr(object);
+			v.visit_expr(index);
+		}
+		ExprKind::New { args, .. } => {
+			for arg in args {
+				v.visit_expr(arg);
+			}
+		}
+		ExprKind::Range { start, end } => {
+			v.visit_expr(start);
+			v.visit_expr(end);
+		}
+		ExprKind::Array(items) => {
+			for item in items {
+				v.visit_expr(item);
+			}
+		}
+		ExprKind::Map(entries) => {
+			for (key, value) in entries {
+				v.visit_expr(key);
+				v.visit_expr(value);
+			}
+		}
+		ExprKind::Lambda { params, body } => {
+			for param in params {
+				if let Some(default) = &param.default {
+					v.visit_expr(default);
+				}
+			}
+			match body {
+				LambdaBody::Expr(body) => v.visit_expr(body),
+				LambdaBody::Block(block) => v.visit_block(block)
+			}
+		}
+		ExprKind::Await(inner) | ExprKind::Throw(inner) => v.visit_expr(inner),
+		ExprKind::Return(value) => {
+			if let Some(value) = value {
+				v.visit_expr(value);
+			}
+		}
+	}
+}
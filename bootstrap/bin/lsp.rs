@@ -0,0 +1,18 @@
+use std::io;
+use std::process::ExitCode;
+
+use glee::diagnostics::ice;
+use glee::driver::{self, lsp};
+use glee::interpreter;
+
+// The language server, which editors start and speak to on stdin and stdout;
+// see `driver::lsp`. It takes no arguments.
+fn main() -> ExitCode {
+	match ice::catch(|| interpreter::with_stack(|| lsp::serve(Box::new(io::stdin().lock()), Box::new(io::stdout())))) {
+		Ok(code) => ExitCode::from(code),
+		Err(ice) => {
+			eprint!("{}", ice);
+			ExitCode::from(driver::ICE)
+		}
+	}
+}
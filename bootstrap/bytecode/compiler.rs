@@ -0,0 +1,1272 @@
+use std::collections::HashMap;
+
+use crate::ast::expr::{BinaryOp, Literal, PostfixOp, UnaryOp};
+use crate::ast::stmt::ForKind;
+use crate::bytecode::{ClassCode, Code, Constant, Module, NONE, Op, TypeTest};
+use crate::interpreter::captures::Captures;
+use crate::interpreter::{self, accessor, inherited_constructor, layout};
+use crate::lexer::token::Span;
+use crate::sema::builtins::Builtin;
+use crate::sema::hir::{Block, Case, Catch, ClassKind, Coercion, Expr, ExprKind, Field, FnKind, MemberTarget, Pattern, Program, Stmt, StmtKind, Variable};
+use crate::sema::types::{FnId, LocalId, Type, TypeId};
+use crate::stdlib;
+
+// Compiles a checked program to bytecode that runs as the interpreter would
+// run the program. `entry` is the file `main` is in, when the project names
+// one.
+//
+// Locals get slots in their function's frame, the ones its closures capture
+// first; boxed locals get a fresh cell where the interpreter would bind them
+// afresh. `finally` blocks are compiled in again at every way out of their
+// `try`: falling off the end, `break`, `continue` and `return`, and after a
+// `catch` or when nothing catches.
+pub fn compile(program: &Program, entry: Option<usize>) -> Module {
+	build(program, entry, false)
+}
+
+// Compiles as `compile` does, with a `Count` before each statement, so the
+// VM counts how many times each one runs, for coverage.
+pub fn instrument(program: &Program, entry: Option<usize>) -> Module {
+	build(program, entry, true)
+}
+
+fn build(program: &Program, entry: Option<usize>, counting: bool) -> Module {
+	let mut compiler = Compiler {
+		program,
+		captures: Captures::new(program),
+		module: Module::default(),
+		constants: HashMap::new(),
+		emitter: Emitter::new(0, Span::default()),
+		counting
+	};
+	compiler.module.std = stdlib::VERSION;
+	compiler.module.files = program.files.iter().map(|file| file.path.clone()).collect();
+	compiler.module.natives = program.natives.iter().map(|native| native.name.clone()).collect();
+	compiler.module.globals = program.globals.iter().map(|global| global.name.clone()).collect();
+	// Every function is there before any is compiled, for the closures that
+	// note in them what they capture
+	for id in 0..program.functions.len() {
+		let code = compiler.header(FnId(id as u32));
+		compiler.module.functions.push(code);
+	}
+	for id in 0..program.functions.len() {
+		compiler.function(FnId(id as u32));
+	}
+	for id in 0..program.classes.len() {
+		let class = compiler.class(TypeId(id as u32));
+		compiler.module.classes.push(class);
+	}
+	for &file in &program.init_order {
+		let code = compiler.top_level(file);
+		compiler.module.init.push(code);
+	}
+	compiler.module.main = interpreter::main(program, entry).map(|main| main.0);
+	compiler.module
+}
+
+struct Compiler<'a> {
+	program: &'a Program,
+	captures: Captures,
+	module: Module,
+	// Each constant once, by what it is
+	constants: HashMap<String, u16>,
+	// The function being compiled now
+	emitter: Emitter<'a>,
+	// Whether statements are counted as they run
+	counting: bool
+}
+
+// The code of a function as it is compiled, and what compiling it needs to
+// keep track of.
+struct Emitter<'a> {
+	code: Vec<u8>,
+	spans: Vec<(u32, Span)>,
+	file: usize,
+	// Where what is compiled now is in the source
+	at: Span,
+	slots: HashMap<LocalId, u16>,
+	count: u16,
+	// The loops and `try`s around what is compiled now, innermost last
+	scopes: Vec<Scope<'a>>
+}
+
+impl Emitter<'_> {
+	fn new(file: usize, at: Span) -> Self {
+		Emitter { code: Vec::new(), spans: Vec::new(), file, at, slots: HashMap::new(), count: 0, scopes: Vec::new() }
+	}
+}
+
+enum Scope<'a> {
+	// A loop, or a labeled statement that `break` can leave. `next` is where
+	// `continue` goes, and `breaks` the jumps to patch to where it ends.
+	Loop { label: Option<&'a str>, looping: bool, cursor: bool, next: u32, breaks: Vec<usize> },
+	// The body of a `try`, or a `catch` of one with a `finally`: what is
+	// thrown goes to its handler, and leaving runs the `finally`
+	Try { finally: Option<&'a Block> }
+}
+
+// Where the value of an assignment or `++` goes, with the object and index
+// in temporary slots.
+enum Place {
+	Local(u16),
+	Global(u16),
+	Member(u16, u16),
+	Index(u16, u16),
+	Backing(u16)
+}
+
+impl<'a> Compiler<'a> {
+	// ---------------------------------------------------------------------
+	// Functions and classes
+	// ---------------------------------------------------------------------
+
+	fn header(&self, id: FnId) -> Code {
+		let function = self.program.function(id);
+		let class = match function.kind {
+			FnKind::Method(class) | FnKind::Constructor(class) | FnKind::Getter(class) | FnKind::Setter(class) | FnKind::Static(class) => Some(class.0),
+			_ => None
+		};
+		Code {
+			name: function.name.clone(),
+			class,
+			file: function.file as u32,
+			span: function.span,
+			params: function.params.len() as u16,
+			variadic: function.params.last().is_some_and(|param| param.variadic),
+			is_async: function.modifiers.is_async,
+			generator: function.generator,
+			foreign: function.foreign.clone(),
+			slots: 0,
+			captures: Vec::new(),
+			code: None,
+			spans: Vec::new(),
+			locals: Vec::new()
+		}
+	}
+
+	// Parameters take their default when the call left them out, in order,
+	// so a default can use the parameters before it.
+	fn function(&mut self, id: FnId) {
+		let program = self.program;
+		let function = program.function(id);
+		let Some(body) = &function.body else {
+			return;
+		};
+		self.begin(function.file, function.span);
+		for local in self.captures.of(id).to_vec() {
+			self.slot(local);
+		}
+		for (index, param) in function.params.iter().enumerate() {
+			let slot = self.slot(param.local);
+			if let Some(default) = &param.default {
+				self.op(Op::JumpIfGiven);
+				self.u16(index as u16);
+				let skip = self.placeholder();
+				self.expr(default);
+				self.local(Op::SetLocal, slot);
+				self.patch(skip);
+			}
+			if self.captures.boxed(param.local) {
+				self.local(Op::Box, slot);
+			}
+		}
+		self.stmts(&body.stmts, None);
+		self.emitter.at = function.span;
+		self.op(Op::Null);
+		self.op(Op::Return);
+		let emitter = self.finish();
+		let locals = self.names(&emitter);
+		let code = &mut self.module.functions[id.0 as usize];
+		code.slots = emitter.count;
+		code.code = Some(emitter.code);
+		code.spans = emitter.spans;
+		code.locals = locals;
+	}
+
+	fn top_level(&mut self, file: usize) -> u32 {
+		let program = self.program;
+		self.begin(file, Span::default());
+		self.stmts(&program.files[file].body, None);
+		self.op(Op::Null);
+		self.op(Op::Return);
+		self.add("<top level>", None, 0)
+	}
+
+	fn class(&mut self, id: TypeId) -> ClassCode {
+		let program = self.program;
+		let class = program.class(id);
+		let header = interpreter::class(program, id);
+		let positional = class.kind == ClassKind::Struct && class.constructors.is_empty();
+		let mut methods: Vec<(String, u16, u32)> = header
+			.methods
+			.iter()
+			.flat_map(|(name, overloads)| overloads.iter().map(|(arity, method)| (name.clone(), *arity as u16, method.0)))
+			.collect();
+		methods.sort();
+		let accessors = |pick: fn(&Field) -> Option<FnId>| {
+			header.fields.iter().filter_map(|name| Some((name.clone(), accessor(program, id, name, pick)?.0))).collect()
+		};
+		let (getters, setters) = (accessors(|field| field.getter), accessors(|field| field.setter));
+		let init = self.initializer(id, positional);
+		let variant_code = (0..class.variants.len()).map(|index| self.variant(id, index)).collect();
+		ClassCode {
+			name: header.name,
+			by_value: header.by_value,
+			fields: header.fields,
+			variants: header.variants,
+			supertypes: header.supertypes.iter().map(|supertype| supertype.0).collect(),
+			methods,
+			superclass: interpreter::superclass(program, id).map(|superclass| superclass.0),
+			init,
+			positional,
+			constructors: class.constructors.iter().map(|constructor| constructor.0).collect(),
+			inherited: if positional { None } else { inherited_constructor(program, id).map(|constructor| constructor.0) },
+			getters,
+			setters,
+			variant_code
+		}
+	}
+
+	// Sets the fields the class declares to their initializers. A struct
+	// without constructors takes its fields as parameters, and only what is
+	// left out gets its initializer.
+	fn initializer(&mut self, id: TypeId, positional: bool) -> Option<u32> {
+		let class = self.program.class(id);
+		if !positional && class.fields.iter().all(|field| field.init.is_none()) {
+			return None;
+		}
+		let first = layout(self.program, id).len() - class.fields.len();
+		self.begin(class.file, class.span);
+		if positional {
+			self.emitter.count = class.fields.len() as u16;
+		}
+		for (index, field) in class.fields.iter().enumerate() {
+			if positional {
+				self.op(Op::JumpIfGiven);
+				self.u16(index as u16);
+				let skip = self.placeholder();
+				match &field.init {
+					Some(init) => self.expr(init),
+					None => self.op(Op::Null)
+				}
+				self.local(Op::SetLocal, index as u16);
+				self.patch(skip);
+				self.local(Op::GetLocal, index as u16);
+			} else if let Some(init) = &field.init {
+				self.expr(init);
+			} else {
+				continue;
+			}
+			self.op(Op::InitField);
+			self.u16((first + index) as u16);
+		}
+		self.op(Op::Null);
+		self.op(Op::Return);
+		let params = if positional { class.fields.len() } else { 0 };
+		Some(self.add("<init>", Some(id), params))
+	}
+
+	// Makes an enum variant with the constructor its arguments fit.
+	fn variant(&mut self, id: TypeId, index: usize) -> u32 {
+		let program = self.program;
+		let enumeration = program.class(id);
+		let variant = &enumeration.variants[index];
+		self.begin(enumeration.file, variant.span);
+		for arg in &variant.args {
+			self.expr(arg);
+		}
+		let count = variant.args.len();
+		let constructor = enumeration.constructors.iter().copied().find(|&constructor| {
+			let params = &program.function(constructor).params;
+			let required = params.iter().filter(|param| param.default.is_none() && !param.variadic).count();
+			count >= required && (count <= params.len() || params.last().is_some_and(|param| param.variadic))
+		});
+		self.emitter.at = variant.span;
+		self.op(Op::NewVariant);
+		self.u16(id.0 as u16);
+		self.u32(constructor.map_or(NONE, |constructor| constructor.0));
+		self.u16(index as u16);
+		self.u8(count as u8);
+		self.op(Op::Return);
+		self.add(&variant.name, Some(id), 0)
+	}
+
+	fn begin(&mut self, file: usize, at: Span) {
+		self.emitter = Emitter::new(file, at);
+	}
+
+	fn finish(&mut self) -> Emitter<'a> {
+		std::mem::replace(&mut self.emitter, Emitter::new(0, Span::default()))
+	}
+
+	// The locals of what was compiled, by slot.
+	fn names(&self, emitter: &Emitter) -> Vec<(u16, String)> {
+		let mut locals: Vec<(u16, String)> = emitter.slots.iter().map(|(&local, &slot)| (slot, self.program.local(local).name.clone())).collect();
+		locals.sort();
+		locals
+	}
+
+	// Adds what was compiled as code of its own, after the functions.
+	fn add(&mut self, name: &str, class: Option<TypeId>, params: usize) -> u32 {
+		let emitter = self.finish();
+		let span = emitter.spans.first().map_or(Span::default(), |(_, span)| *span);
+		let locals = self.names(&emitter);
+		self.module.functions.push(Code {
+			name: name.to_string(),
+			class: class.map(|class| class.0),
+			file: emitter.file as u32,
+			span,
+			params: params as u16,
+			variadic: false,
+			is_async: false,
+			generator: false,
+			foreign: None,
+			slots: emitter.count,
+			captures: Vec::new(),
+			code: Some(emitter.code),
+			spans: emitter.spans,
+			locals
+		});
+		self.module.functions.len() as u32 - 1
+	}
+
+	// ---------------------------------------------------------------------
+	// Emitting
+	// ---------------------------------------------------------------------
+
+	fn op(&mut self, op: Op) {
+		let emitter = &mut self.emitter;
+		if emitter.spans.last().is_none_or(|(_, span)| *span != emitter.at) {
+			emitter.spans.push((emitter.code.len() as u32, emitter.at));
+		}
+		emitter.code.push(op as u8);
+	}
+
+	fn u8(&mut self, value: u8) {
+		self.emitter.code.push(value);
+	}
+
+	fn u16(&mut self, value: u16) {
+		self.emitter.code.extend_from_slice(&value.to_le_bytes());
+	}
+
+	fn u32(&mut self, value: u32) {
+		self.emitter.code.extend_from_slice(&value.to_le_bytes());
+	}
+
+	fn here(&self) -> u32 {
+		self.emitter.code.len() as u32
+	}
+
+	// A jump to somewhere not compiled yet, to `patch` once it is.
+	fn jump(&mut self, op: Op) -> usize {
+		self.op(op);
+		self.placeholder()
+	}
+
+	fn placeholder(&mut self) -> usize {
+		let at = self.emitter.code.len();
+		self.u32(0);
+		at
+	}
+
+	fn patch(&mut self, at: usize) {
+		let here = self.here();
+		self.emitter.code[at..at + 4].copy_from_slice(&here.to_le_bytes());
+	}
+
+	fn jump_to(&mut self, op: Op, target: u32) {
+		self.op(op);
+		self.u32(target);
+	}
+
+	fn local(&mut self, op: Op, slot: u16) {
+		self.op(op);
+		self.u16(slot);
+	}
+
+	fn slot(&mut self, local: LocalId) -> u16 {
+		if let Some(&slot) = self.emitter.slots.get(&local) {
+			return slot;
+		}
+		let slot = self.temp();
+		self.emitter.slots.insert(local, slot);
+		slot
+	}
+
+	// A slot of its own for something the code keeps aside.
+	fn temp(&mut self) -> u16 {
+		self.emitter.count += 1;
+		self.emitter.count - 1
+	}
+
+	fn constant(&mut self, constant: Constant) -> u16 {
+		let key = format!("{:?}", constant);
+		if let Some(&index) = self.constants.get(&key) {
+			return index;
+		}
+		self.module.constants.push(constant);
+		let index = self.module.constants.len() as u16 - 1;
+		self.constants.insert(key, index);
+		index
+	}
+
+	fn name(&mut self, name: &str) -> u16 {
+		self.constant(Constant::String(name.to_string()))
+	}
+
+	fn type_test(&mut self, ty: &Type) -> u16 {
+		self.constant(Constant::Type(type_test(ty)))
+	}
+
+	// ---------------------------------------------------------------------
+	// Statements
+	// ---------------------------------------------------------------------
+
+	// Compiles statements in order, and with `value` leaves what the last one
+	// is when it is an expression, compiled by `value`, or else null. Boxed
+	// locals get their cells first, and local functions are made next, as
+	// the interpreter does.
+	fn stmts(&mut self, stmts: &'a [Stmt], value: Option<fn(&mut Self, &'a Expr)>) {
+		for stmt in stmts {
+			if let StmtKind::Var { var: Variable::Local(local), .. } | StmtKind::Function { local, .. } = &stmt.kind
+				&& self.captures.boxed(*local)
+			{
+				let slot = self.slot(*local);
+				self.local(Op::Box, slot);
+			}
+		}
+		for stmt in stmts {
+			if let StmtKind::Function { local, function } = &stmt.kind {
+				self.emitter.at = stmt.span;
+				self.closure(*function);
+				let slot = self.slot(*local);
+				self.local(Op::SetLocal, slot);
+			}
+		}
+		for (index, stmt) in stmts.iter().enumerate() {
+			self.count(stmt);
+			if let Some(value) = value
+				&& index + 1 == stmts.len()
+				&& let StmtKind::Expr(expr) = &stmt.kind
+			{
+				value(self, expr);
+				return;
+			}
+			self.stmt(stmt);
+		}
+		if value.is_some() {
+			self.op(Op::Null);
+		}
+	}
+
+	// When counting, counts the statement as it starts, unless it only
+	// declares a local function, which the statements before made.
+	fn count(&mut self, stmt: &Stmt) {
+		if !self.counting || matches!(stmt.kind, StmtKind::Function { .. }) {
+			return;
+		}
+		let counter = self.module.counters.len() as u32;
+		self.module.counters.push((self.emitter.file as u32, stmt.span));
+		let outer = std::mem::replace(&mut self.emitter.at, stmt.span);
+		self.op(Op::Count);
+		self.u32(counter);
+		self.emitter.at = outer;
+	}
+
+	fn block(&mut self, block: &'a Block) {
+		self.stmts(&block.stmts, None);
+	}
+
+	fn stmt(&mut self, stmt: &'a Stmt) {
+		let outer = std::mem::replace(&mut self.emitter.at, stmt.span);
+		match &stmt.kind {
+			StmtKind::Expr(expr) => {
+				self.expr(expr);
+				self.op(Op::Pop);
+			}
+			StmtKind::Var { var, init } => {
+				match init {
+					Some(init) => self.expr(init),
+					None => self.op(Op::Null)
+				}
+				self.emitter.at = stmt.span;
+				self.store_var(*var);
+			}
+			StmtKind::Block(block) => self.block(block),
+			StmtKind::If { cond, then_branch, else_branch } => {
+				self.expr(cond);
+				let otherwise = self.jump(Op::JumpIfFalse);
+				self.block(then_branch);
+				match else_branch {
+					Some(else_branch) => {
+						let end = self.jump(Op::Jump);
+						self.patch(otherwise);
+						self.stmt(else_branch);
+						self.patch(end);
+					}
+					None => self.patch(otherwise)
+				}
+			}
+			StmtKind::While { .. } | StmtKind::Loop { .. } | StmtKind::For { .. } => self.looped(stmt, None),
+			StmtKind::Labeled { label, body } => match &body.kind {
+				StmtKind::While { .. } | StmtKind::Loop { .. } | StmtKind::For { .. } => self.looped(body, Some(label.as_str())),
+				// A label on something else only gives `break` a name to leave it by
+				_ => {
+					self.emitter.scopes.push(Scope::Loop { label: Some(label.as_str()), looping: false, cursor: false, next: 0, breaks: Vec::new() });
+					self.stmt(body);
+					self.end_loop();
+				}
+			},
+			StmtKind::Break(label) => {
+				let target = self.target(label.as_deref());
+				self.leave(target + 1);
+				let at = self.jump(Op::Jump);
+				if let Scope::Loop { breaks, .. } = &mut self.emitter.scopes[target] {
+					breaks.push(at);
+				}
+			}
+			StmtKind::Continue(label) => {
+				let target = self.target(label.as_deref());
+				self.leave(target + 1);
+				if let Scope::Loop { next, .. } = self.emitter.scopes[target] {
+					self.jump_to(Op::Jump, next);
+				}
+			}
+			StmtKind::Return(value) => self.ret(value.as_ref()),
+			StmtKind::Yield(value) => {
+				self.expr(value);
+				self.op(Op::Yield);
+			}
+			StmtKind::Throw(value) => {
+				self.expr(value);
+				self.op(Op::Throw);
+			}
+			StmtKind::Try { body, catches, finally } => self.try_catch(body, catches, finally.as_ref()),
+			StmtKind::Switch { subject, cases, default, .. } => self.switch(subject, cases, default.as_ref()),
+			// Made by `stmts` before the block runs
+			StmtKind::Function { .. } => {}
+		}
+		self.emitter.at = outer;
+	}
+
+	// A loop, labeled `label` or not. `for` walks its iterable with a
+	// cursor, which `break` ends on the way out.
+	fn looped(&mut self, stmt: &'a Stmt, label: Option<&'a str>) {
+		match &stmt.kind {
+			StmtKind::While { cond, body } => {
+				let start = self.here();
+				self.expr(cond);
+				let end = self.jump(Op::JumpIfFalse);
+				self.emitter.scopes.push(Scope::Loop { label, looping: true, cursor: false, next: start, breaks: vec![end] });
+				self.block(body);
+				self.jump_to(Op::Jump, start);
+				self.end_loop();
+			}
+			StmtKind::Loop { body } => {
+				let start = self.here();
+				self.emitter.scopes.push(Scope::Loop { label, looping: true, cursor: false, next: start, breaks: Vec::new() });
+				self.block(body);
+				self.jump_to(Op::Jump, start);
+				self.end_loop();
+			}
+			StmtKind::For { kind, bindings, iterable, body } => {
+				self.expr(iterable);
+				self.emitter.at = iterable.span;
+				self.op(Op::Iterate);
+				self.u8((*kind == ForKind::Of) as u8);
+				let start = self.here();
+				let done = self.jump(Op::Next);
+				if let [binding] = bindings.as_slice() {
+					self.define(*binding);
+				} else {
+					self.op(Op::Unpack);
+					self.u8(bindings.len() as u8);
+					for binding in bindings.iter().rev() {
+						self.define(*binding);
+					}
+				}
+				self.emitter.at = stmt.span;
+				self.emitter.scopes.push(Scope::Loop { label, looping: true, cursor: true, next: start, breaks: vec![done] });
+				self.block(body);
+				self.jump_to(Op::Jump, start);
+				let Some(Scope::Loop { breaks, .. }) = self.emitter.scopes.pop() else {
+					unreachable!("the loop's scope is the innermost");
+				};
+				for at in breaks {
+					self.patch(at);
+				}
+				self.op(Op::EndIterate);
+			}
+			_ => unreachable!("only loops are looped")
+		}
+	}
+
+	fn end_loop(&mut self) {
+		let Some(Scope::Loop { breaks, .. }) = self.emitter.scopes.pop() else {
+			unreachable!("the loop's scope is the innermost");
+		};
+		for at in breaks {
+			self.patch(at);
+		}
+	}
+
+	// The scope `break` or `continue` with `label` goes to.
+	fn target(&self, label: Option<&str>) -> usize {
+		self.emitter
+			.scopes
+			.iter()
+			.rposition(|scope| match scope {
+				Scope::Loop { label: name, looping, .. } => match label {
+					Some(label) => *name == Some(label),
+					None => *looping
+				},
+				Scope::Try { .. } => false
+			})
+			.expect("checked programs only break out of loops")
+	}
+
+	// Ends what is left of the scopes from `depth` in, innermost first:
+	// cursors of loops, and handlers of `try`s, running their `finally`.
+	fn leave(&mut self, depth: usize) {
+		for index in (depth..self.emitter.scopes.len()).rev() {
+			match &self.emitter.scopes[index] {
+				Scope::Loop { cursor: true, .. } => self.op(Op::EndIterate),
+				Scope::Loop { .. } => {}
+				Scope::Try { finally } => {
+					let finally = *finally;
+					self.op(Op::EndTry);
+					if let Some(finally) = finally {
+						let inner = self.emitter.scopes.split_off(index);
+						self.block(finally);
+						self.emitter.scopes.extend(inner);
+					}
+				}
+			}
+		}
+	}
+
+	fn ret(&mut self, value: Option<&'a Expr>) {
+		match value {
+			// Only loops are left on the way out of a `try`-less return, and
+			// the frame's cursors go with it, so a call can take its place
+			Some(value) if self.emitter.scopes.iter().all(|scope| matches!(scope, Scope::Loop { .. })) => self.tail(value),
+			Some(value) => self.expr(value),
+			None => self.op(Op::Null)
+		}
+		self.leave(0);
+		self.op(Op::Return);
+	}
+
+	// What is returned, with the calls in tail position marked: the call
+	// that is the whole value, or that a branch of an `if` or the end of a
+	// block is, so recursion through them runs in the frame it started in,
+	// however deep it goes.
+	fn tail(&mut self, value: &'a Expr) {
+		let outer = std::mem::replace(&mut self.emitter.at, value.span);
+		match &value.kind {
+			ExprKind::Call { callee, args } => self.call(callee, args, true),
+			ExprKind::If { cond, then_branch, else_branch } => {
+				self.expr(cond);
+				let otherwise = self.jump(Op::JumpIfFalse);
+				self.tail(then_branch);
+				let end = self.jump(Op::Jump);
+				self.patch(otherwise);
+				self.tail(else_branch);
+				self.patch(end);
+			}
+			ExprKind::Block(block) => self.stmts(&block.stmts, Some(Self::tail)),
+			_ => self.expr(value)
+		}
+		self.emitter.at = outer;
+	}
+
+	// Binds a local afresh to the value on the stack, as a loop variable or
+	// what a `catch` caught.
+	fn define(&mut self, local: LocalId) {
+		let slot = self.slot(local);
+		if self.captures.boxed(local) {
+			self.local(Op::Box, slot);
+		}
+		self.local(Op::SetLocal, slot);
+	}
+
+	// The body runs with a handler that goes to the catches, which test
+	// what was thrown in order. With a `finally`, the catches run with a
+	// handler of their own, so the `finally` runs however they end too.
+	fn try_catch(&mut self, body: &'a Block, catches: &'a [Catch], finally: Option<&'a Block>) {
+		let handler = self.jump(Op::Try);
+		self.emitter.scopes.push(Scope::Try { finally });
+		self.block(body);
+		self.emitter.scopes.pop();
+		self.op(Op::EndTry);
+		if let Some(finally) = finally {
+			self.block(finally);
+		}
+		let mut ends = vec![self.jump(Op::Jump)];
+		self.patch(handler);
+		let protected = finally.is_some() && !catches.is_empty();
+		let inner = if protected { Some(self.jump(Op::Try)) } else { None };
+		for catch in catches {
+			self.emitter.at = catch.span;
+			let next = catch.ty.as_ref().map(|ty| {
+				self.op(Op::Dup);
+				let ty = self.type_test(ty);
+				self.local(Op::Is, ty);
+				self.jump(Op::JumpIfFalse)
+			});
+			self.op(Op::Caught);
+			self.define(catch.local);
+			if protected {
+				self.emitter.scopes.push(Scope::Try { finally });
+			}
+			self.block(&catch.body);
+			if protected {
+				self.emitter.scopes.pop();
+				self.op(Op::EndTry);
+			}
+			if let Some(finally) = finally {
+				self.block(finally);
+			}
+			ends.push(self.jump(Op::Jump));
+			if let Some(next) = next {
+				self.patch(next);
+			}
+		}
+		if protected {
+			self.op(Op::EndTry);
+		}
+		// Nothing caught it, or a catch threw
+		let rethrow = |compiler: &mut Self| {
+			compiler.op(Op::Pop);
+			if let Some(finally) = finally {
+				compiler.block(finally);
+			}
+			compiler.op(Op::Rethrow);
+		};
+		rethrow(self);
+		if let Some(inner) = inner {
+			self.patch(inner);
+			rethrow(self);
+		}
+		for end in ends {
+			self.patch(end);
+		}
+	}
+
+	// The subject is kept aside and tested against each pattern in order.
+	fn switch(&mut self, subject: &'a Expr, cases: &'a [Case], default: Option<&'a Block>) {
+		self.expr(subject);
+		let kept = self.temp();
+		self.local(Op::SetLocal, kept);
+		let mut bodies = Vec::new();
+		for case in cases {
+			let mut matched = Vec::new();
+			for pattern in &case.patterns {
+				self.local(Op::GetLocal, kept);
+				match pattern {
+					Pattern::Value(value) => {
+						self.expr(value);
+						self.op(Op::Equal);
+					}
+					Pattern::Type { ty, .. } => {
+						let ty = self.type_test(ty);
+						self.local(Op::Is, ty);
+					}
+				}
+				matched.push(self.jump(Op::JumpIfTrue));
+			}
+			bodies.push(matched);
+		}
+		if let Some(default) = default {
+			self.block(default);
+		}
+		let mut ends = vec![self.jump(Op::Jump)];
+		for (case, matched) in cases.iter().zip(bodies) {
+			for at in matched {
+				self.patch(at);
+			}
+			self.block(&case.body);
+			ends.push(self.jump(Op::Jump));
+		}
+		for end in ends {
+			self.patch(end);
+		}
+	}
+
+	// ---------------------------------------------------------------------
+	// Expressions
+	// ---------------------------------------------------------------------
+
+	// Leaves the value of `expr` on the stack.
+	fn expr(&mut self, expr: &'a Expr) {
+		let outer = std::mem::replace(&mut self.emitter.at, expr.span);
+		self.expr_kind(expr);
+		self.emitter.at = outer;
+	}
+
+	fn expr_kind(&mut self, expr: &'a Expr) {
+		let span = expr.span;
+		match &expr.kind {
+			ExprKind::Literal(literal) => match literal {
+				Literal::Int(value) => self.push_constant(Constant::Int(*value)),
+				Literal::Float(value) => self.push_constant(Constant::Float(*value)),
+				Literal::String(text) => self.push_constant(Constant::String(text.clone())),
+				Literal::Bool(true) => self.op(Op::True),
+				Literal::Bool(false) => self.op(Op::False),
+				Literal::Null => self.op(Op::Null)
+			},
+			ExprKind::Var(Variable::Local(local)) => {
+				let slot = self.slot(*local);
+				self.local(Op::GetLocal, slot);
+			}
+			ExprKind::Var(Variable::Global(global)) => self.local(Op::GetGlobal, global.0 as u16),
+			ExprKind::Function(function) => self.jump_to(Op::Function, function.0),
+			ExprKind::Builtin(builtin) => {
+				self.op(Op::Builtin);
+				self.u8(Builtin::ALL.iter().position(|known| known == builtin).unwrap_or(0) as u8);
+			}
+			ExprKind::Native(native) => self.local(Op::Native, *native as u16),
+			ExprKind::This | ExprKind::Super => self.op(Op::This),
+			ExprKind::BackingField(name) => {
+				let name = self.name(name);
+				self.local(Op::GetBacking, name);
+			}
+
+			ExprKind::Unary { op, operand } => match op {
+				UnaryOp::Negate | UnaryOp::Not | UnaryOp::Typeof => {
+					self.expr(operand);
+					self.op(match op {
+						UnaryOp::Negate => Op::Negate,
+						UnaryOp::Not => Op::Not,
+						_ => Op::Typeof
+					});
+				}
+				UnaryOp::PreIncrement => self.update(operand, Op::Increment, false),
+				UnaryOp::PreDecrement => self.update(operand, Op::Decrement, false)
+			},
+			ExprKind::Binary { op, left, right } => {
+				self.expr(left);
+				match op {
+					BinaryOp::And | BinaryOp::Or => {
+						let (test, short) = if *op == BinaryOp::And { (Op::JumpIfFalse, Op::False) } else { (Op::JumpIfTrue, Op::True) };
+						let first = self.jump(test);
+						self.expr(right);
+						let second = self.jump(test);
+						self.op(if short == Op::True { Op::False } else { Op::True });
+						let end = self.jump(Op::Jump);
+						self.patch(first);
+						self.patch(second);
+						self.op(short);
+						self.patch(end);
+					}
+					BinaryOp::Coalesce => {
+						let end = self.jump(Op::JumpIfNotNull);
+						self.op(Op::Pop);
+						self.expr(right);
+						self.patch(end);
+					}
+					_ => {
+						self.expr(right);
+						self.op(binary(*op));
+					}
+				}
+			}
+			ExprKind::Assign { target, op, value } => self.assign(target, *op, value),
+			ExprKind::Postfix { op, operand } => match op {
+				PostfixOp::Increment => self.update(operand, Op::Increment, true),
+				PostfixOp::Decrement => self.update(operand, Op::Decrement, true),
+				PostfixOp::NonNull => {
+					self.expr(operand);
+					self.op(Op::NonNull);
+				}
+				// An `Err` or `None` is returned as it is
+				PostfixOp::Propagate => {
+					self.expr(operand);
+					let end = self.jump(Op::Unwrap);
+					self.leave(0);
+					self.op(Op::Return);
+					self.patch(end);
+				}
+			},
+			ExprKind::Is { operand, ty, negated } => {
+				self.expr(operand);
+				let ty = self.type_test(ty);
+				self.local(Op::Is, ty);
+				if *negated {
+					self.op(Op::Not);
+				}
+			}
+			ExprKind::Coerce { value, coercion } => {
+				self.expr(value);
+				match coercion {
+					Coercion::IntToFloat => self.op(Op::ToFloat),
+					Coercion::ToString => self.op(Op::ToString),
+					Coercion::Cast => {
+						let ty = self.type_test(&expr.ty);
+						let name = self.name(&expr.ty.to_string());
+						self.local(Op::Cast, ty);
+						self.u16(name);
+					}
+				}
+			}
+			ExprKind::If { cond, then_branch, else_branch } => {
+				self.expr(cond);
+				let otherwise = self.jump(Op::JumpIfFalse);
+				self.expr(then_branch);
+				let end = self.jump(Op::Jump);
+				self.patch(otherwise);
+				self.expr(else_branch);
+				self.patch(end);
+			}
+			ExprKind::Block(block) => self.stmts(&block.stmts, Some(Self::expr)),
+
+			ExprKind::Call { callee, args } => self.call(callee, args, false),
+			ExprKind::SuperCall { constructor, args } => {
+				self.exprs(args);
+				self.emitter.at = span;
+				self.op(Op::SuperCall);
+				self.u32(constructor.map_or(NONE, |constructor| constructor.0));
+				self.u8(args.len() as u8);
+			}
+			ExprKind::New { class, constructor, args } => {
+				self.exprs(args);
+				self.op(Op::New);
+				self.u16(class.0 as u16);
+				self.u32(constructor.map_or(NONE, |constructor| constructor.0));
+				self.u8(args.len() as u8);
+			}
+			ExprKind::Member { object, name, target, safe, .. } => {
+				self.expr(object);
+				let end = safe.then(|| self.jump(Op::JumpIfNull));
+				match target {
+					MemberTarget::Field(_) | MemberTarget::Dynamic => {
+						let name = self.name(name);
+						self.local(Op::GetField, name);
+					}
+					MemberTarget::Method(method) if matches!(object.kind, ExprKind::Super) => self.jump_to(Op::Bind, method.0),
+					MemberTarget::Method(method) => self.jump_to(Op::GetMethod, method.0),
+					MemberTarget::Extension(function) => self.jump_to(Op::Bind, function.0),
+					MemberTarget::Builtin => {
+						let name = self.name(name);
+						self.local(Op::GetProperty, name);
+					}
+				}
+				if let Some(end) = end {
+					self.patch(end);
+				}
+			}
+			ExprKind::Variant { class, index } => {
+				self.op(Op::Variant);
+				self.u16(class.0 as u16);
+				self.u16(*index as u16);
+			}
+			ExprKind::Index { object, index } => {
+				self.expr(object);
+				self.expr(index);
+				self.op(Op::GetIndex);
+			}
+
+			ExprKind::Range { start, end } => {
+				self.expr(start);
+				self.expr(end);
+				self.op(Op::Range);
+			}
+			ExprKind::Array(items) => {
+				self.exprs(items);
+				self.local(Op::Array, items.len() as u16);
+			}
+			ExprKind::Map(entries) => {
+				for (key, value) in entries {
+					self.expr(key);
+					self.expr(value);
+				}
+				self.local(Op::Map, entries.len() as u16);
+			}
+			ExprKind::Set(elements) => {
+				self.exprs(elements);
+				self.local(Op::Set, elements.len() as u16);
+			}
+			ExprKind::Lambda(function) => self.closure(*function),
+			ExprKind::Await(value) => {
+				self.expr(value);
+				self.op(Op::Await);
+			}
+			ExprKind::Return(value) => self.ret(value.as_deref()),
+			ExprKind::Throw(value) => {
+				self.expr(value);
+				self.op(Op::Throw);
+			}
+		}
+	}
+
+	fn exprs(&mut self, exprs: &'a [Expr]) {
+		for expr in exprs {
+			self.expr(expr);
+		}
+	}
+
+	fn push_constant(&mut self, constant: Constant) {
+		let index = self.constant(constant);
+		self.local(Op::Constant, index);
+	}
+
+	// A lambda or local function, noting in its code which slots here it
+	// captures.
+	fn closure(&mut self, function: FnId) {
+		let captured = self.captures.of(function).to_vec();
+		let slots = captured.into_iter().map(|local| self.slot(local)).collect();
+		self.module.functions[function.0 as usize].captures = slots;
+		self.jump_to(Op::Closure, function.0);
+	}
+
+	// Methods are called on the object, without making a function value of
+	// them first. A `tail` call is marked as one, unless it is of a method
+	// found by name, which is not called in the loop.
+	fn call(&mut self, callee: &'a Expr, args: &'a [Expr], tail: bool) {
+		let span = self.emitter.at;
+		let argc = args.len() as u8;
+		match &callee.kind {
+			ExprKind::Member { object, name, target, safe, .. } if !matches!(target, MemberTarget::Field(_)) => {
+				self.expr(object);
+				let end = safe.then(|| self.jump(Op::JumpIfNull));
+				self.exprs(args);
+				self.emitter.at = span;
+				if tail && !matches!(target, MemberTarget::Dynamic | MemberTarget::Builtin) {
+					self.op(Op::Tail);
+				}
+				match target {
+					MemberTarget::Method(method) if matches!(object.kind, ExprKind::Super) => self.jump_to(Op::CallWith, method.0),
+					MemberTarget::Method(method) => self.jump_to(Op::CallMethod, method.0),
+					MemberTarget::Extension(function) => self.jump_to(Op::CallWith, function.0),
+					_ => {
+						let name = self.name(name);
+						self.local(Op::Invoke, name);
+					}
+				}
+				self.u8(argc);
+				if let Some(end) = end {
+					self.patch(end);
+				}
+			}
+			ExprKind::Function(function) => {
+				self.exprs(args);
+				self.emitter.at = span;
+				if tail {
+					self.op(Op::Tail);
+				}
+				self.jump_to(Op::CallFunction, function.0);
+				self.u8(argc);
+			}
+			_ => {
+				self.expr(callee);
+				self.exprs(args);
+				self.emitter.at = span;
+				if tail {
+					self.op(Op::Tail);
+				}
+				self.op(Op::Call);
+				self.u8(argc);
+			}
+		}
+	}
+
+	// ---------------------------------------------------------------------
+	// Assignment
+	// ---------------------------------------------------------------------
+
+	fn store_var(&mut self, var: Variable) {
+		match var {
+			Variable::Local(local) => {
+				let slot = self.slot(local);
+				self.local(Op::SetLocal, slot);
+			}
+			Variable::Global(global) => self.local(Op::SetGlobal, global.0 as u16)
+		}
+	}
+
+	// `target = value`, or with `op`, `target = target op value`, leaving the
+	// value assigned. The object and index of the target are worked out once.
+	fn assign(&mut self, target: &'a Expr, op: Option<BinaryOp>, value: &'a Expr) {
+		let Some(place) = self.place(target) else {
+			self.expr(value);
+			return;
+		};
+		let span = self.emitter.at;
+		let end = match op {
+			Some(op) => {
+				self.emitter.at = target.span;
+				self.load(&place);
+				let end = (op == BinaryOp::Coalesce).then(|| {
+					let end = self.jump(Op::JumpIfNotNull);
+					self.op(Op::Pop);
+					end
+				});
+				self.expr(value);
+				self.emitter.at = target.span;
+				if op != BinaryOp::Coalesce {
+					self.op(binary(op));
+				}
+				end
+			}
+			None => {
+				self.expr(value);
+				None
+			}
+		};
+		self.emitter.at = target.span;
+		self.store(&place);
+		self.emitter.at = span;
+		if let Some(end) = end {
+			self.patch(end);
+		}
+	}
+
+	// `++` and `--` with `step`, leaving the new value or with `postfix`
+	// the old one.
+	fn update(&mut self, target: &'a Expr, step: Op, postfix: bool) {
+		let Some(place) = self.place(target) else {
+			self.expr(target);
+			return;
+		};
+		self.emitter.at = target.span;
+		self.load(&place);
+		let old = postfix.then(|| {
+			let old = self.temp();
+			self.op(Op::Dup);
+			self.local(Op::SetLocal, old);
+			old
+		});
+		self.op(step);
+		self.store(&place);
+		if let Some(old) = old {
+			self.op(Op::Pop);
+			self.local(Op::GetLocal, old);
+		}
+	}
+
+	// Works out the object and index of the target, keeping them aside.
+	fn place(&mut self, target: &'a Expr) -> Option<Place> {
+		Some(match &target.kind {
+			ExprKind::Var(Variable::Local(local)) => Place::Local(self.slot(*local)),
+			ExprKind::Var(Variable::Global(global)) => Place::Global(global.0 as u16),
+			ExprKind::Member { object, name, .. } => {
+				self.expr(object);
+				let kept = self.temp();
+				self.local(Op::SetLocal, kept);
+				Place::Member(kept, self.name(name))
+			}
+			ExprKind::Index { object, index } => {
+				self.expr(object);
+				let object = self.temp();
+				self.local(Op::SetLocal, object);
+				self.expr(index);
+				let index = self.temp();
+				self.local(Op::SetLocal, index);
+				Place::Index(object, index)
+			}
+			ExprKind::BackingField(name) => Place::Backing(self.name(name)),
+			_ => return None
+		})
+	}
+
+	fn load(&mut self, place: &Place) {
+		match *place {
+			Place::Local(slot) => self.local(Op::GetLocal, slot),
+			Place::Global(global) => self.local(Op::GetGlobal, global),
+			Place::Member(object, name) => {
+				self.local(Op::GetLocal, object);
+				self.local(Op::GetField, name);
+			}
+			Place::Index(object, index) => {
+				self.local(Op::GetLocal, object);
+				self.local(Op::GetLocal, index);
+				self.op(Op::GetIndex);
+			}
+			Place::Backing(name) => self.local(Op::GetBacking, name)
+		}
+	}
+
+	// Stores the value on the stack, leaving it there.
+	fn store(&mut self, place: &Place) {
+		match *place {
+			Place::Local(slot) => {
+				self.op(Op::Dup);
+				self.local(Op::SetLocal, slot);
+			}
+			Place::Global(global) => {
+				self.op(Op::Dup);
+				self.local(Op::SetGlobal, global);
+			}
+			Place::Member(object, name) => {
+				let value = self.temp();
+				self.local(Op::SetLocal, value);
+				self.local(Op::GetLocal, object);
+				self.local(Op::GetLocal, value);
+				self.local(Op::SetField, name);
+			}
+			Place::Index(object, index) => {
+				let value = self.temp();
+				self.local(Op::SetLocal, value);
+				self.local(Op::GetLocal, object);
+				self.local(Op::GetLocal, index);
+				self.local(Op::GetLocal, value);
+				self.op(Op::SetIndex);
+			}
+			Place::Backing(name) => {
+				self.op(Op::Dup);
+				self.local(Op::SetBacking, name);
+			}
+		}
+	}
+}
+
+fn binary(op: BinaryOp) -> Op {
+	match op {
+		BinaryOp::Add => Op::Add,
+		BinaryOp::Subtract => Op::Subtract,
+		BinaryOp::Multiply => Op::Multiply,
+		BinaryOp::Divide => Op::Divide,
+		BinaryOp::Modulo => Op::Modulo,
+		BinaryOp::Equal => Op::Equal,
+		BinaryOp::NotEqual => Op::NotEqual,
+		BinaryOp::Less => Op::Less,
+		BinaryOp::LessEqual => Op::LessEqual,
+		BinaryOp::Greater => Op::Greater,
+		BinaryOp::GreaterEqual => Op::GreaterEqual,
+		BinaryOp::BitAnd => Op::BitAnd,
+		BinaryOp::BitOr => Op::BitOr,
+		BinaryOp::BitXor => Op::BitXor,
+		BinaryOp::ShiftLeft => Op::ShiftLeft,
+		BinaryOp::ShiftRight => Op::ShiftRight,
+		BinaryOp::In => Op::In,
+		// Short-circuiting, compiled to jumps
+		BinaryOp::And | BinaryOp::Or | BinaryOp::Coalesce => unreachable!("'{}' is compiled to jumps", op.symbol())
+	}
+}
+
+fn type_test(ty: &Type) -> TypeTest {
+	match ty {
+		Type::Any | Type::Param { .. } | Type::Error => TypeTest::Any,
+		Type::Void | Type::Never => TypeTest::Never,
+		Type::Null => TypeTest::Null,
+		Type::Int => TypeTest::Int,
+		Type::Float => TypeTest::Float,
+		Type::Bool => TypeTest::Bool,
+		Type::String => TypeTest::String,
+		Type::Range => TypeTest::Range,
+		Type::Array(_) => TypeTest::Array,
+		Type::Map(..) => TypeTest::Map,
+		Type::Set(_) => TypeTest::Set,
+		Type::Iterator(_) => TypeTest::Iterator,
+		Type::Result(..) => TypeTest::Result,
+		Type::Option(_) => TypeTest::Option,
+		Type::Random => TypeTest::Random,
+		Type::Pointer => TypeTest::Pointer,
+		Type::Channel(_) => TypeTest::Channel,
+		Type::Function { .. } => TypeTest::Function,
+		Type::Class { id, .. } => TypeTest::Class(id.0),
+		Type::Nullable(inner) => TypeTest::Nullable(Box::new(type_test(inner))),
+		Type::Union(members) => TypeTest::Union(members.iter().map(type_test).collect())
+	}
+}
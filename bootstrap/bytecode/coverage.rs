@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::bytecode::Module;
+
+// What ran of a program compiled for coverage, by file and line: of each
+// line a statement starts on, how many times the statements starting there
+// ran. Code that `finally` blocks repeat counts on the lines of the block,
+// whichever way out of the `try` ran it.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Coverage {
+	// Those with statements, in the order of the module's files
+	pub files: Vec<FileCoverage>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileCoverage {
+	pub path: String,
+	pub lines: BTreeMap<i64, u64>
+}
+
+impl Coverage {
+	// From the `counts` of a VM that ran `module`, which `instrument` compiled.
+	pub fn new(module: &Module, counts: &[u64]) -> Coverage {
+		let mut files: Vec<FileCoverage> = module.files.iter().map(|path| FileCoverage { path: path.clone(), lines: BTreeMap::new() }).collect();
+		for (&(file, span), &count) in module.counters.iter().zip(counts) {
+			*files[file as usize].lines.entry(span.line).or_default() += count;
+		}
+		files.retain(|file| !file.lines.is_empty());
+		Coverage { files }
+	}
+
+	// The lcov tracefile of it, as `genhtml` and coverage services read it:
+	// a record of each file, with how many times each line ran.
+	pub fn lcov(&self) -> String {
+		let mut lcov = String::new();
+		for file in &self.files {
+			let _ = writeln!(lcov, "TN:\nSF:{}", file.path);
+			for (line, count) in &file.lines {
+				let _ = writeln!(lcov, "DA:{},{}", line, count);
+			}
+			let _ = writeln!(lcov, "LF:{}\nLH:{}\nend_of_record", file.lines.len(), file.run());
+		}
+		lcov
+	}
+
+	// A table of the files: how many lines have statements, how many of
+	// them ran, as a share too, and which did not, then the same of them all.
+	pub fn summary(&self) -> String {
+		let mut summary = format!("{:<30} {:>7} {:>7} {:>7}  {}\n", "file", "lines", "run", "cover", "not run");
+		for file in &self.files {
+			let (lines, run) = (file.lines.len(), file.run());
+			let _ = writeln!(summary, "{:<30} {:>7} {:>7} {:>6.1}%  {}", file.path, lines, run, percent(run, lines), file.missed());
+		}
+		let lines = self.files.iter().map(|file| file.lines.len()).sum();
+		let run = self.files.iter().map(FileCoverage::run).sum();
+		let _ = write!(summary, "{:<30} {:>7} {:>7} {:>6.1}%", "total", lines, run, percent(run, lines));
+		summary
+	}
+}
+
+impl FileCoverage {
+	// How many of its lines ran.
+	pub fn run(&self) -> usize {
+		self.lines.values().filter(|&&count| count > 0).count()
+	}
+
+	// The lines that did not run, with those next to each other among the
+	// lines with statements given as a range, like `4, 7-9`.
+	pub fn missed(&self) -> String {
+		let mut ranges: Vec<(i64, i64)> = Vec::new();
+		let mut after_missed = false;
+		for (&line, &count) in &self.lines {
+			match ranges.last_mut() {
+				Some((_, end)) if count == 0 && after_missed => *end = line,
+				_ if count == 0 => ranges.push((line, line)),
+				_ => {}
+			}
+			after_missed = count == 0;
+		}
+		let ranges: Vec<String> = ranges.iter().map(|&(start, end)| if start == end { start.to_string() } else { format!("{}-{}", start, end) }).collect();
+		ranges.join(", ")
+	}
+}
+
+fn percent(run: usize, lines: usize) -> f64 {
+	if lines == 0 { 100.0 } else { run as f64 / lines as f64 * 100.0 }
+}
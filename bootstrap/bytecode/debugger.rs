@@ -0,0 +1,236 @@
+use std::collections::BTreeSet;
+
+use crate::ast::expr::{BinaryOp, Expr, ExprKind, InterpolationPart, Literal, UnaryOp};
+use crate::ast::stmt::StmtKind;
+use crate::bytecode::vm::Vm;
+use crate::interpreter::ops;
+use crate::interpreter::value::{Tag, Value};
+use crate::lexer::lexer::Lexer;
+use crate::parser::parser::Parser;
+
+// Stops a program running on the VM where its user asks: at breakpoints,
+// set by file and line, and after each step. While it is stopped, a
+// frontend, like `dotfun debug` on the console or an editor, looks at the
+// VM through what it shows of itself, and says how to go on.
+//
+// The VM asks it before every instruction whether to stop. Where each one
+// is comes from the code's spans, so it only stops when the line, or the
+// call the line is in, changes: a line is one step, however many
+// instructions it takes.
+pub struct Debugger<'a> {
+	// By file and line
+	breakpoints: BTreeSet<(usize, i64)>,
+	resume: Resume,
+	// Where it last stopped, for steps to go from
+	from: Option<Location>,
+	// Where the last instruction was
+	last: Option<Location>,
+	frontend: Box<dyn Frontend + 'a>
+}
+
+// Where the program is: the file and line of the next instruction, and how
+// many calls deep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+	pub file: usize,
+	pub line: i64,
+	pub depth: usize
+}
+
+// Why the program stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+	// Before its first line
+	Entry,
+	Breakpoint,
+	Step
+}
+
+// How to go on after stopping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+	// Until a breakpoint
+	Continue,
+	// To the next line of the same call, or the caller when it returns
+	StepOver,
+	// To the next line, in a call it makes if it makes one
+	StepInto,
+	// Until the call returns
+	StepOut
+}
+
+// What the user debugs with. It is told each time the program stops, and
+// returns when the user says how to go on; the breakpoints are its to
+// change meanwhile.
+pub trait Frontend {
+	fn paused(&mut self, vm: &Vm, breakpoints: &mut BTreeSet<(usize, i64)>, reason: Reason) -> Resume;
+}
+
+impl<'a> Debugger<'a> {
+	// A debugger that stops before the first line when `stop` is set, and
+	// otherwise at the first breakpoint.
+	pub fn new(frontend: Box<dyn Frontend + 'a>, breakpoints: BTreeSet<(usize, i64)>, stop: bool) -> Debugger<'a> {
+		let resume = if stop { Resume::StepInto } else { Resume::Continue };
+		Debugger { breakpoints, resume, from: None, last: None, frontend }
+	}
+
+	// Called by the VM before each instruction, stopping there if it has to.
+	pub fn check(&mut self, vm: &Vm) {
+		let Some(here) = vm.location() else {
+			return;
+		};
+		let last = self.last.replace(here);
+		if last == Some(here) || here.line == 0 {
+			return;
+		}
+		let returned = last.is_some_and(|last| last.depth > here.depth);
+		let reason = match self.from {
+			_ if !returned && self.breakpoints.contains(&(here.file, here.line)) => Reason::Breakpoint,
+			None if self.resume == Resume::StepInto => Reason::Entry,
+			Some(from) if self.stops(from, here) => Reason::Step,
+			_ => return
+		};
+		self.from = Some(here);
+		self.resume = self.frontend.paused(vm, &mut self.breakpoints, reason);
+	}
+
+	fn stops(&self, from: Location, here: Location) -> bool {
+		match self.resume {
+			Resume::Continue => false,
+			Resume::StepInto => true,
+			Resume::StepOver => here.depth < from.depth || here.depth == from.depth && here.line != from.line,
+			Resume::StepOut => here.depth < from.depth
+		}
+	}
+}
+
+// What `text`, an expression, is in the call `depth` calls out from the one
+// the program is stopped in. Names are its locals, then the fields of
+// `this`, then globals. Nothing it does can change the program, so it calls
+// nothing, not even getters.
+pub fn evaluate(vm: &Vm, depth: usize, text: &str) -> Result<Value, String> {
+	let tokens = Lexer::new(text.to_string()).lex().map_err(|err| err.message.clone())?.clone();
+	let program = Parser::new(tokens).parse().map_err(|errors| errors[0].message.clone())?;
+	match program.stmts.as_slice() {
+		[stmt] if let StmtKind::Expr(expr) = &stmt.kind => Evaluator { vm, depth }.expr(expr),
+		_ => Err("Expected an expression".to_string())
+	}
+}
+
+// How values look while debugging: as the program prints them, but for
+// strings, which are quoted, and objects, which show their fields.
+pub fn show(value: &Value) -> String {
+	let mut custom = |value: &Value| {
+		Ok::<_, ()>(match value {
+			Value::String(text) => Some(format!("{:?}", text)),
+			Value::Object(object) if object.variant.is_none() => {
+				let fields = object.fields.borrow().clone();
+				let fields: Vec<String> = object.class.fields.iter().zip(&fields).map(|(name, value)| format!("{}: {}", name, show(value))).collect();
+				Some(format!("{}({})", object.class.name, fields.join(", ")))
+			}
+			_ => None
+		})
+	};
+	ops::stringify(value, &mut custom).unwrap_or_default()
+}
+
+// What a value is made of, named, for frontends to show under it: the
+// elements of arrays and sets, the entries of maps, the fields of objects
+// and what results and options hold.
+pub fn members(value: &Value) -> Vec<(String, Value)> {
+	match value {
+		Value::Array(items) => items.borrow().iter().enumerate().map(|(index, item)| (format!("[{}]", index), item.clone())).collect(),
+		Value::Map(map) => map.borrow().iter().map(|(key, value)| (show(key), value.clone())).collect(),
+		Value::Set(set) => set.borrow().keys().enumerate().map(|(index, element)| (format!("[{}]", index), element.clone())).collect(),
+		Value::Object(object) => object.class.fields.iter().cloned().zip(object.fields.borrow().iter().cloned()).collect(),
+		Value::Tagged(Tag::Ok | Tag::Some, value) => vec![("value".to_string(), (**value).clone())],
+		Value::Tagged(Tag::Err, error) => vec![("error".to_string(), (**error).clone())],
+		_ => Vec::new()
+	}
+}
+
+struct Evaluator<'a, 'b> {
+	vm: &'b Vm<'a>,
+	depth: usize
+}
+
+impl Evaluator<'_, '_> {
+	fn expr(&self, expr: &Expr) -> Result<Value, String> {
+		Ok(match &expr.kind {
+			ExprKind::Literal(literal) => match literal {
+				Literal::Int(value) => Value::Int(*value),
+				Literal::Float(value) => Value::Float(*value),
+				Literal::String(text) => Value::string(text.as_str()),
+				Literal::Bool(value) => Value::Bool(*value),
+				Literal::Null => Value::Null
+			},
+			ExprKind::Interpolation(parts) => {
+				let mut text = String::new();
+				for part in parts {
+					match part {
+						InterpolationPart::Text(part) => text.push_str(part),
+						InterpolationPart::Expr(part) => text.push_str(&string(&self.expr(part)?))
+					}
+				}
+				Value::string(text)
+			}
+			ExprKind::Identifier(name) => self.name(name)?,
+			ExprKind::This => self.vm.this(self.depth).ok_or("There is no 'this' here")?,
+			ExprKind::Unary { op: UnaryOp::Negate, operand } => ops::negate(self.expr(operand)?)?,
+			ExprKind::Unary { op: UnaryOp::Not, operand } => Value::Bool(!self.expr(operand)?.truthy()),
+			ExprKind::Unary { op: UnaryOp::Typeof, operand } => Value::string(self.expr(operand)?.type_name()),
+			ExprKind::Binary { op, left, right } => {
+				let left = self.expr(left)?;
+				match (op, &left) {
+					(BinaryOp::And, _) if !left.truthy() => Value::Bool(false),
+					(BinaryOp::Or, _) if left.truthy() => Value::Bool(true),
+					(BinaryOp::Coalesce, Value::Null) => self.expr(right)?,
+					(BinaryOp::Coalesce, _) => left,
+					(BinaryOp::Add, Value::String(left)) => Value::string(format!("{}{}", left, string(&self.expr(right)?))),
+					_ => match (op, self.expr(right)?) {
+						(BinaryOp::Add, Value::String(right)) => Value::string(format!("{}{}", string(&left), right)),
+						(op, right) => ops::binary(*op, left, right)?
+					}
+				}
+			}
+			ExprKind::If { cond, then_branch, else_branch } => match self.expr(cond)?.truthy() {
+				true => self.expr(then_branch)?,
+				false => self.expr(else_branch)?
+			},
+			ExprKind::Member { object, name, safe, .. } => match self.expr(object)? {
+				Value::Null if *safe => Value::Null,
+				Value::Object(object) => match object.class.field(name) {
+					Some(field) => object.fields.borrow()[field].clone(),
+					None => return Err(format!("'{}' has no field '{}'", object.class.name, name))
+				},
+				receiver => ops::property(&receiver, name)?
+			},
+			ExprKind::Index { object, index } => ops::index(&self.expr(object)?, &self.expr(index)?)?,
+			ExprKind::Range { start, end } => ops::range(self.expr(start)?, self.expr(end)?)?,
+			ExprKind::Array(items) => Value::array(items.iter().map(|item| self.expr(item)).collect::<Result<_, _>>()?),
+			ExprKind::Call { .. } | ExprKind::New { .. } => return Err("The debugger cannot call functions".to_string()),
+			ExprKind::Assign { .. } | ExprKind::Postfix { .. } | ExprKind::Unary { .. } => return Err("The debugger cannot change the program".to_string()),
+			_ => return Err("The debugger cannot evaluate this".to_string())
+		})
+	}
+
+	fn name(&self, name: &str) -> Result<Value, String> {
+		if let Some((_, value)) = self.vm.locals(self.depth).into_iter().rev().find(|(local, _)| local == name) {
+			return Ok(value);
+		}
+		if let Some(Value::Object(object)) = self.vm.this(self.depth)
+			&& let Some(field) = object.class.field(name)
+		{
+			return Ok(object.fields.borrow()[field].clone());
+		}
+		match self.vm.globals().into_iter().find(|(global, _)| global == name) {
+			Some((_, value)) => Ok(value),
+			None => Err(format!("Cannot find '{}' here", name))
+		}
+	}
+}
+
+// A value as the program would print it, for joining to strings.
+fn string(value: &Value) -> String {
+	ops::stringify(value, &mut |_| Ok::<_, ()>(None)).unwrap_or_default()
+}
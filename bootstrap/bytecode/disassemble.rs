@@ -0,0 +1,96 @@
+use std::fmt::Write;
+
+use crate::bytecode::{Constant, Module, NONE, Op, TypeTest, decode};
+use crate::sema::builtins::Builtin;
+
+// What `--emit bytecode` prints: each function's code, one instruction a
+// line, with its offset, where in the source it came from when that changes,
+// and its operands, with the constants, functions and classes they stand for.
+pub fn disassemble(module: &Module) -> String {
+	let mut out = String::new();
+	for (index, code) in module.functions.iter().enumerate() {
+		let name = function(module, index as u32);
+		if let Some(foreign) = &code.foreign {
+			let _ = writeln!(out, "extern {:?} fn {}\n", foreign.library, name);
+			continue;
+		}
+		let Some(bytes) = &code.code else {
+			let _ = writeln!(out, "fn {} has no body\n", name);
+			continue;
+		};
+		let keyword = if code.is_async { "async fn" } else { "fn" };
+		let _ = write!(out, "{} {} ({}:{}:{}), {} params, {} slots", keyword, name, module.files[code.file as usize], code.span.line, code.span.column, code.params, code.slots);
+		if !code.captures.is_empty() {
+			let _ = write!(out, ", captures {:?}", code.captures);
+		}
+		out.push('\n');
+		let mut offset = 0;
+		let mut spans = code.spans.iter().peekable();
+		while let Some((op, operands)) = decode(bytes, offset) {
+			let mut at = String::new();
+			if let Some((_, span)) = spans.next_if(|(start, _)| *start as usize == offset) {
+				at = format!("{}:{}", span.line, span.column);
+			}
+			let _ = writeln!(out, "  {:04}  {:<7} {:?}{}", offset, at, op, operand_text(module, op, &operands));
+			offset += op.size();
+		}
+		out.push('\n');
+	}
+	out.truncate(out.trim_end().len());
+	out
+}
+
+fn operand_text(module: &Module, op: Op, operands: &[u32]) -> String {
+	let text = match (op, operands) {
+		(Op::Constant | Op::GetBacking | Op::SetBacking | Op::GetField | Op::SetField | Op::GetProperty | Op::Is, [constant])
+		| (Op::Cast, [constant, _]) => self::constant(module, *constant),
+		(Op::Invoke, [name, argc]) => format!("{} {}", constant(module, *name), argc),
+		(Op::Jump | Op::JumpIfFalse | Op::JumpIfTrue | Op::JumpIfNull | Op::JumpIfNotNull | Op::Unwrap | Op::Next | Op::Try, [target]) => format!("{:04}", target),
+		(Op::JumpIfGiven, [param, target]) => format!("{} {:04}", param, target),
+		(Op::CallFunction | Op::CallMethod | Op::CallWith | Op::SuperCall, [function, argc]) => format!("{} {}", self::function(module, *function), argc),
+		(Op::GetMethod | Op::Bind | Op::Function | Op::Closure, [function]) => self::function(module, *function),
+		(Op::New, [class, constructor, argc]) => format!("{} {} {}", module.classes[*class as usize].name, self::function(module, *constructor), argc),
+		(Op::NewVariant, [class, constructor, variant, argc]) => {
+			let class = &module.classes[*class as usize];
+			format!("{}.{} {} {}", class.name, class.variants[*variant as usize], self::function(module, *constructor), argc)
+		}
+		(Op::Variant, [class, variant]) => {
+			let class = &module.classes[*class as usize];
+			format!("{}.{}", class.name, class.variants[*variant as usize])
+		}
+		(Op::Builtin, [builtin]) => Builtin::ALL[*builtin as usize].name().to_string(),
+		(Op::Native, [native]) => module.natives[*native as usize].clone(),
+		(_, operands) => operands.iter().map(u32::to_string).collect::<Vec<_>>().join(" ")
+	};
+	if text.is_empty() { text } else { format!(" {}", text) }
+}
+
+// A function by what stack traces call it.
+fn function(module: &Module, function: u32) -> String {
+	if function == NONE {
+		return "-".to_string();
+	}
+	let code = &module.functions[function as usize];
+	match code.class {
+		Some(class) => format!("{}.{}", module.classes[class as usize].name, code.name),
+		None => code.name.clone()
+	}
+}
+
+fn constant(module: &Module, index: u32) -> String {
+	match &module.constants[index as usize] {
+		Constant::Int(value) => value.to_string(),
+		Constant::Float(value) => format!("{:?}", value),
+		Constant::String(text) => format!("{:?}", text),
+		Constant::Type(ty) => type_test(module, ty)
+	}
+}
+
+fn type_test(module: &Module, ty: &TypeTest) -> String {
+	match ty {
+		TypeTest::Class(id) => module.classes[*id as usize].name.clone(),
+		TypeTest::Nullable(inner) => format!("{}?", type_test(module, inner)),
+		TypeTest::Union(members) => members.iter().map(|member| type_test(module, member)).collect::<Vec<_>>().join(" | "),
+		ty => format!("{:?}", ty)
+	}
+}
@@ -0,0 +1,385 @@
+use crate::bytecode::verify::verify;
+use crate::bytecode::{ClassCode, Code, Constant, Module, NONE, TypeTest, decode};
+use crate::lexer::token::Span;
+use crate::sema::hir::{CType, Foreign};
+use crate::stdlib;
+
+// What `dotfun build` writes and `dotfun run` runs without compiling again.
+pub const EXTENSION: &str = "dfc";
+
+// The first bytes of every `.dfc` file.
+pub const MAGIC: &[u8; 4] = b"DFC\0";
+
+// Goes up whenever the layout of a file or the instructions change, since a
+// file written for one VM means nothing to another.
+pub const VERSION: u32 = 15;
+
+// A module as a `.dfc` file: the magic and version, then the module with
+// numbers little-endian, and strings and lists after their length. The
+// version of the standard library comes first of it, to be checked before
+// anything else is read.
+pub fn save(module: &Module) -> Vec<u8> {
+	let mut out = Writer { bytes: MAGIC.to_vec() };
+	out.u32(VERSION);
+	out.u32(module.std);
+	out.list(&module.files, |out, file| out.string(file));
+	out.list(&module.constants, Writer::constant);
+	out.list(&module.functions, Writer::code);
+	out.list(&module.classes, Writer::class);
+	out.list(&module.natives, |out, native| out.string(native));
+	out.list(&module.globals, |out, global| out.string(global));
+	out.list(&module.init, |out, code| out.u32(*code));
+	out.option(module.main);
+	out.list(&module.counters, |out, (file, span)| {
+		out.u32(*file);
+		out.span(span);
+	});
+	out.bytes
+}
+
+// Reads back what `save` wrote, or says why it cannot.
+pub fn load(bytes: &[u8]) -> Result<Module, String> {
+	if !bytes.starts_with(MAGIC) {
+		return Err("not a compiled dotfun program".to_string());
+	}
+	let mut reader = Reader { bytes, at: MAGIC.len() };
+	let version = reader.u32()?;
+	if version != VERSION {
+		return Err(format!("compiled for bytecode version {}, but this dotfun runs version {}; build it again", version, VERSION));
+	}
+	let std = reader.u32()?;
+	if std > stdlib::VERSION {
+		return Err(format!("needs version {} of the standard library, but this dotfun has version {}", std, stdlib::VERSION));
+	}
+	let module = Module {
+		std,
+		files: reader.list(Reader::string)?,
+		constants: reader.list(Reader::constant)?,
+		functions: reader.list(Reader::code)?,
+		classes: reader.list(Reader::class)?,
+		natives: reader.list(Reader::string)?,
+		globals: reader.list(Reader::string)?,
+		init: reader.list(Reader::u32)?,
+		main: reader.option()?,
+		counters: reader.list(|reader| Ok((reader.u32()?, reader.span()?)))?
+	};
+	if reader.at != bytes.len() {
+		return Err("corrupt: there is more after the program".to_string());
+	}
+	verify(&module)?;
+	Ok(module)
+}
+
+struct Writer {
+	bytes: Vec<u8>
+}
+
+impl Writer {
+	fn u8(&mut self, value: u8) {
+		self.bytes.push(value);
+	}
+
+	fn u16(&mut self, value: u16) {
+		self.bytes.extend_from_slice(&value.to_le_bytes());
+	}
+
+	fn u32(&mut self, value: u32) {
+		self.bytes.extend_from_slice(&value.to_le_bytes());
+	}
+
+	fn u64(&mut self, value: u64) {
+		self.bytes.extend_from_slice(&value.to_le_bytes());
+	}
+
+	fn bool(&mut self, value: bool) {
+		self.u8(value as u8);
+	}
+
+	fn string(&mut self, text: &str) {
+		self.u32(text.len() as u32);
+		self.bytes.extend_from_slice(text.as_bytes());
+	}
+
+	fn list<T>(&mut self, items: &[T], mut write: impl FnMut(&mut Writer, &T)) {
+		self.u32(items.len() as u32);
+		for item in items {
+			write(self, item);
+		}
+	}
+
+	// None is written as the operand that stands for nothing.
+	fn option(&mut self, value: Option<u32>) {
+		self.u32(value.unwrap_or(NONE));
+	}
+
+	fn span(&mut self, span: &Span) {
+		self.u64(span.start as u64);
+		self.u64(span.end as u64);
+		self.u64(span.line as u64);
+		self.u64(span.column as u64);
+	}
+
+	fn constant(&mut self, constant: &Constant) {
+		match constant {
+			Constant::Int(value) => {
+				self.u8(0);
+				self.u64(*value as u64);
+			}
+			Constant::Float(value) => {
+				self.u8(1);
+				self.u64(value.to_bits());
+			}
+			Constant::String(text) => {
+				self.u8(2);
+				self.string(text);
+			}
+			Constant::Type(ty) => {
+				self.u8(3);
+				self.type_test(ty);
+			}
+		}
+	}
+
+	fn type_test(&mut self, ty: &TypeTest) {
+		match ty {
+			TypeTest::Class(class) => {
+				self.u8(TYPE_CLASS);
+				self.u32(*class);
+			}
+			TypeTest::Nullable(inner) => {
+				self.u8(TYPE_NULLABLE);
+				self.type_test(inner);
+			}
+			TypeTest::Union(members) => {
+				self.u8(TYPE_UNION);
+				self.list(members, Writer::type_test);
+			}
+			simple => self.u8(SIMPLE_TYPES.iter().position(|ty| ty == simple).unwrap() as u8)
+		}
+	}
+
+	fn ctype(&mut self, ctype: CType) {
+		self.u8(CType::ALL.iter().position(|known| *known == ctype).unwrap() as u8);
+	}
+
+	fn code(&mut self, code: &Code) {
+		self.string(&code.name);
+		self.option(code.class);
+		self.u32(code.file);
+		self.span(&code.span);
+		self.u16(code.params);
+		self.bool(code.variadic);
+		self.bool(code.is_async);
+		self.bool(code.generator);
+		self.bool(code.foreign.is_some());
+		if let Some(foreign) = &code.foreign {
+			self.string(&foreign.library);
+			self.list(&foreign.params, |out, ctype| out.ctype(*ctype));
+			self.ctype(foreign.ret);
+		}
+		self.u16(code.slots);
+		self.list(&code.captures, |out, slot| out.u16(*slot));
+		self.bool(code.code.is_some());
+		if let Some(bytes) = &code.code {
+			self.list(bytes, |out, byte| out.u8(*byte));
+		}
+		self.list(&code.spans, |out, (offset, span)| {
+			out.u32(*offset);
+			out.span(span);
+		});
+		self.list(&code.locals, |out, (slot, name)| {
+			out.u16(*slot);
+			out.string(name);
+		});
+	}
+
+	fn class(&mut self, class: &ClassCode) {
+		self.string(&class.name);
+		self.bool(class.by_value);
+		self.list(&class.fields, |out, field| out.string(field));
+		self.list(&class.variants, |out, variant| out.string(variant));
+		self.list(&class.supertypes, |out, class| out.u32(*class));
+		self.list(&class.methods, |out, (name, params, function)| {
+			out.string(name);
+			out.u16(*params);
+			out.u32(*function);
+		});
+		self.option(class.superclass);
+		self.option(class.init);
+		self.bool(class.positional);
+		self.list(&class.constructors, |out, function| out.u32(*function));
+		self.option(class.inherited);
+		self.list(&class.getters, Writer::accessor);
+		self.list(&class.setters, Writer::accessor);
+		self.list(&class.variant_code, |out, function| out.u32(*function));
+	}
+
+	fn accessor(&mut self, (field, function): &(String, u32)) {
+		self.string(field);
+		self.u32(*function);
+	}
+}
+
+// Type tests are written as their index here, or one of the tags after.
+const SIMPLE_TYPES: [TypeTest; 18] = [
+	TypeTest::Any,
+	TypeTest::Never,
+	TypeTest::Null,
+	TypeTest::Int,
+	TypeTest::Float,
+	TypeTest::Bool,
+	TypeTest::String,
+	TypeTest::Range,
+	TypeTest::Array,
+	TypeTest::Function,
+	TypeTest::Map,
+	TypeTest::Set,
+	TypeTest::Iterator,
+	TypeTest::Result,
+	TypeTest::Option,
+	TypeTest::Random,
+	TypeTest::Channel,
+	TypeTest::Pointer
+];
+const TYPE_CLASS: u8 = 18;
+const TYPE_NULLABLE: u8 = 19;
+const TYPE_UNION: u8 = 20;
+
+struct Reader<'a> {
+	bytes: &'a [u8],
+	at: usize
+}
+
+impl Reader<'_> {
+	fn take<const N: usize>(&mut self) -> Result<[u8; N], String> {
+		let bytes = self.bytes.get(self.at..self.at + N).ok_or("corrupt: it ends too soon")?;
+		self.at += N;
+		Ok(bytes.try_into().unwrap())
+	}
+
+	fn u8(&mut self) -> Result<u8, String> {
+		Ok(self.take::<1>()?[0])
+	}
+
+	fn u16(&mut self) -> Result<u16, String> {
+		Ok(u16::from_le_bytes(self.take()?))
+	}
+
+	fn u32(&mut self) -> Result<u32, String> {
+		Ok(u32::from_le_bytes(self.take()?))
+	}
+
+	fn u64(&mut self) -> Result<u64, String> {
+		Ok(u64::from_le_bytes(self.take()?))
+	}
+
+	fn bool(&mut self) -> Result<bool, String> {
+		match self.u8()? {
+			0 => Ok(false),
+			1 => Ok(true),
+			byte => Err(format!("corrupt: {} where a bool should be", byte))
+		}
+	}
+
+	fn string(&mut self) -> Result<String, String> {
+		let len = self.u32()? as usize;
+		let bytes = self.bytes.get(self.at..self.at + len).ok_or("corrupt: it ends too soon")?;
+		self.at += len;
+		String::from_utf8(bytes.to_vec()).map_err(|_| "corrupt: a string is not UTF-8".to_string())
+	}
+
+	fn list<T>(&mut self, mut read: impl FnMut(&mut Self) -> Result<T, String>) -> Result<Vec<T>, String> {
+		let len = self.u32()? as usize;
+		// Every item takes a byte at least, which keeps a bad length from
+		// asking for more memory than the file could fill
+		if len > self.bytes.len() - self.at {
+			return Err("corrupt: it ends too soon".to_string());
+		}
+		(0..len).map(|_| read(self)).collect()
+	}
+
+	fn option(&mut self) -> Result<Option<u32>, String> {
+		let value = self.u32()?;
+		Ok((value != NONE).then_some(value))
+	}
+
+	fn span(&mut self) -> Result<Span, String> {
+		Ok(Span { start: self.u64()? as usize, end: self.u64()? as usize, line: self.u64()? as i64, column: self.u64()? as i64 })
+	}
+
+	fn constant(&mut self) -> Result<Constant, String> {
+		match self.u8()? {
+			0 => Ok(Constant::Int(self.u64()? as i64)),
+			1 => Ok(Constant::Float(f64::from_bits(self.u64()?))),
+			2 => Ok(Constant::String(self.string()?)),
+			3 => Ok(Constant::Type(self.type_test()?)),
+			tag => Err(format!("corrupt: no constant is tagged {}", tag))
+		}
+	}
+
+	fn type_test(&mut self) -> Result<TypeTest, String> {
+		match self.u8()? {
+			TYPE_CLASS => Ok(TypeTest::Class(self.u32()?)),
+			TYPE_NULLABLE => Ok(TypeTest::Nullable(Box::new(self.type_test()?))),
+			TYPE_UNION => Ok(TypeTest::Union(self.list(Reader::type_test)?)),
+			tag => SIMPLE_TYPES.get(tag as usize).cloned().ok_or_else(|| format!("corrupt: no type is tagged {}", tag))
+		}
+	}
+
+	fn ctype(&mut self) -> Result<CType, String> {
+		let tag = self.u8()?;
+		CType::ALL.get(tag as usize).copied().ok_or_else(|| format!("corrupt: no C type is tagged {}", tag))
+	}
+
+	fn code(&mut self) -> Result<Code, String> {
+		let name = self.string()?;
+		let class = self.option()?;
+		let file = self.u32()?;
+		let span = self.span()?;
+		let params = self.u16()?;
+		let variadic = self.bool()?;
+		let is_async = self.bool()?;
+		let generator = self.bool()?;
+		let foreign = match self.bool()? {
+			true => Some(Foreign { library: self.string()?, params: self.list(Reader::ctype)?, ret: self.ctype()? }),
+			false => None
+		};
+		let slots = self.u16()?;
+		let captures = self.list(Reader::u16)?;
+		let code = if self.bool()? { Some(self.list(Reader::u8)?) } else { None };
+		// The VM trusts instructions to be whole
+		if let Some(bytes) = &code {
+			let mut offset = 0;
+			while offset < bytes.len() {
+				let (op, _) = decode(bytes, offset).ok_or_else(|| format!("corrupt: bad instruction at {} in {}", offset, name))?;
+				offset += op.size();
+			}
+		}
+		let spans = self.list(|reader| Ok((reader.u32()?, reader.span()?)))?;
+		let locals = self.list(|reader| Ok((reader.u16()?, reader.string()?)))?;
+		Ok(Code { name, class, file, span, params, variadic, is_async, generator, foreign, slots, captures, code, spans, locals })
+	}
+
+	fn class(&mut self) -> Result<ClassCode, String> {
+		Ok(ClassCode {
+			name: self.string()?,
+			by_value: self.bool()?,
+			fields: self.list(Reader::string)?,
+			variants: self.list(Reader::string)?,
+			supertypes: self.list(Reader::u32)?,
+			methods: self.list(|reader| Ok((reader.string()?, reader.u16()?, reader.u32()?)))?,
+			superclass: self.option()?,
+			init: self.option()?,
+			positional: self.bool()?,
+			constructors: self.list(Reader::u32)?,
+			inherited: self.option()?,
+			getters: self.list(Reader::accessor)?,
+			setters: self.list(Reader::accessor)?,
+			variant_code: self.list(Reader::u32)?
+		})
+	}
+
+	fn accessor(&mut self) -> Result<(String, u32), String> {
+		Ok((self.string()?, self.u32()?))
+	}
+}
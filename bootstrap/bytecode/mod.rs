@@ -0,0 +1,445 @@
+pub mod compiler;
+pub mod coverage;
+pub mod debugger;
+pub mod disassemble;
+pub mod file;
+pub mod profiler;
+pub mod verify;
+pub mod vm;
+
+use crate::lexer::token::Span;
+use crate::sema::hir::Foreign;
+
+// A compiled program, ready for the VM: everything it needs to run without
+// the checked program it came from.
+//
+// Functions are numbered as in the checked program, so a `FnId` is also the
+// index of its code here; after them come the code the compiler adds, for
+// each file's top-level code, each class's field initializers and each enum
+// variant. Classes are numbered as in the checked program too.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Module {
+	// The version of the standard library it was compiled with, whose
+	// files it has the code of and whose natives it calls
+	pub std: u32,
+	// Of each file, for positions in errors
+	pub files: Vec<String>,
+	pub constants: Vec<Constant>,
+	pub functions: Vec<Code>,
+	pub classes: Vec<ClassCode>,
+	// The natives it calls, by the names the runtime has them under
+	pub natives: Vec<String>,
+	// The name of each global
+	pub globals: Vec<String>,
+	// The top-level code of each file, in the order it runs
+	pub init: Vec<u32>,
+	pub main: Option<u32>,
+	// When compiled for coverage, the statements `Count` counts, by the
+	// file each is in and where
+	pub counters: Vec<(u32, Span)>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+	Int(i64),
+	Float(f64),
+	String(String),
+	Type(TypeTest)
+}
+
+// A type as `is`, `catch` and casts test values against it at run time,
+// where only classes are left of type arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeTest {
+	Any,
+	Never,
+	Null,
+	Int,
+	Float,
+	Bool,
+	String,
+	Range,
+	Array,
+	Map,
+	Set,
+	Iterator,
+	Result,
+	Option,
+	Random,
+	Channel,
+	Function,
+	Pointer,
+	Class(u32),
+	Nullable(Box<TypeTest>),
+	Union(Vec<TypeTest>)
+}
+
+// The code of one function, or of what the compiler adds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Code {
+	pub name: String,
+	// The class of methods, constructors and accessors
+	pub class: Option<u32>,
+	pub file: u32,
+	pub span: Span,
+	// `...rest` included, which is the last one
+	pub params: u16,
+	pub variadic: bool,
+	// Calls run as tasks of their own, giving the task
+	pub is_async: bool,
+	// Calls run on stacks of their own, a `Yield` at a time, giving an
+	// iterator of what they yield
+	pub generator: bool,
+	// For `extern` functions, the C function to call instead
+	pub foreign: Option<Foreign>,
+	// Every local of a call: what it captured, its parameters, then the rest
+	pub slots: u16,
+	// For lambdas and local functions, the slots of the function making them
+	// whose locals they capture, into their first slots
+	pub captures: Vec<u16>,
+	// None for abstract and interface methods
+	pub code: Option<Vec<u8>>,
+	// Where each run of instructions came from: the offset of the first one
+	// and its span, in order
+	pub spans: Vec<(u32, Span)>,
+	// The slots of named locals, in order, with their names, for debuggers
+	pub locals: Vec<(u16, String)>
+}
+
+impl Code {
+	// The span of the instruction at `offset`.
+	pub fn span_at(&self, offset: usize) -> Span {
+		let index = self.spans.partition_point(|(start, _)| *start as usize <= offset);
+		index.checked_sub(1).map_or(self.span, |index| self.spans[index].1)
+	}
+}
+
+// What the VM needs to know of a class to make its objects and run their
+// methods.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassCode {
+	pub name: String,
+	pub by_value: bool,
+	// Of its objects, inherited ones first
+	pub fields: Vec<String>,
+	pub variants: Vec<String>,
+	pub supertypes: Vec<u32>,
+	// The method table: name, number of parameters and function
+	pub methods: Vec<(String, u16, u32)>,
+	pub superclass: Option<u32>,
+	// Sets the fields the class declares, with `this` the new object
+	pub init: Option<u32>,
+	// A struct without constructors, which takes its fields in order
+	pub positional: bool,
+	pub constructors: Vec<u32>,
+	// Run when `new` names no constructor: the nearest inherited one that
+	// takes no arguments
+	pub inherited: Option<u32>,
+	// Of its fields with accessors, the nearest ones, by field name
+	pub getters: Vec<(String, u32)>,
+	pub setters: Vec<(String, u32)>,
+	// For an enum, the code that makes each variant
+	pub variant_code: Vec<u32>
+}
+
+// An operand that stands for nothing, like no constructor.
+pub const NONE: u32 = u32::MAX;
+
+// The instructions. Each is a byte, followed by its operands in the widths
+// `operands` gives, little-endian. Values are worked on on a stack; jumps go
+// to offsets in the function's code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Op {
+	Null,
+	True,
+	False,
+	// constant
+	Constant,
+	Pop,
+	Dup,
+
+	// slot; `SetLocal` writes into the local's cell if it has one
+	GetLocal,
+	SetLocal,
+	// slot: puts the local in a cell of its own, holding what it holds
+	Box,
+	// global
+	GetGlobal,
+	SetGlobal,
+	This,
+	// name constant: the storage behind a property, in its accessors
+	GetBacking,
+	SetBacking,
+	// field index: sets a field of `this`, for field initializers
+	InitField,
+
+	Add,
+	Subtract,
+	Multiply,
+	Divide,
+	Modulo,
+	Equal,
+	NotEqual,
+	Less,
+	LessEqual,
+	Greater,
+	GreaterEqual,
+	BitAnd,
+	BitOr,
+	BitXor,
+	ShiftLeft,
+	ShiftRight,
+	In,
+	Negate,
+	Not,
+	Typeof,
+	NonNull,
+	Increment,
+	Decrement,
+	ToFloat,
+	ToString,
+	// type constant, and for `Cast` the name of the type for its error
+	Cast,
+	Is,
+
+	// offset
+	Jump,
+	// offset: pops the condition
+	JumpIfFalse,
+	JumpIfTrue,
+	// offset: keeps the value
+	JumpIfNull,
+	JumpIfNotNull,
+	// offset: for `?`, unwraps an `Ok` or `Some` and jumps, keeping an
+	// `Err` or `None` to return
+	Unwrap,
+	// parameter, offset: jumps when the call gave that many arguments or
+	// more; how parameters skip their default
+	JumpIfGiven,
+
+	// argument count: calls the value under the arguments
+	Call,
+	// function, argument count
+	CallFunction,
+	// function, argument count: on the object under the arguments, its
+	// class's override of the function
+	CallMethod,
+	// function, argument count: on the object under the arguments, the
+	// function itself, for `super.f()` and extensions
+	CallWith,
+	// The call after it is what the function returns: one of the program's
+	// functions is run in place of the frame running, rather than on top
+	Tail,
+	// name constant, argument count: the method found by name at run time
+	Invoke,
+	// constructor or `NONE`, argument count: `super(...)`
+	SuperCall,
+	// class, constructor or `NONE`, argument count
+	New,
+	// class, constructor or `NONE`, variant, argument count
+	NewVariant,
+	// class, variant
+	Variant,
+
+	// name constant
+	GetField,
+	SetField,
+	GetProperty,
+	// function: the method bound to the object, its class's override
+	GetMethod,
+	// function: the function bound to the object as is
+	Bind,
+	GetIndex,
+	SetIndex,
+	// count
+	Array,
+	// count: of entries, each a key pushed and then its value
+	Map,
+	// count
+	Set,
+	Range,
+	// function
+	Function,
+	Closure,
+	// builtin
+	Builtin,
+	// native: of the module's
+	Native,
+
+	// 1 for `for of`: starts walking the value popped
+	Iterate,
+	// offset: pushes the next item, or jumps at the end
+	Next,
+	EndIterate,
+	// count: an entry taken apart into that many values
+	Unpack,
+
+	// offset: where to go with what is thrown until `EndTry`
+	Try,
+	EndTry,
+	// What was caught is handled: forgets it
+	Caught,
+	// Throws again what was caught
+	Rethrow,
+	Throw,
+	Return,
+	// Waits for the task popped to finish, pushing its result or throwing
+	// what it threw; anything else is pushed back as is
+	Await,
+	// Gives the value popped as the next element of the generator running,
+	// which stops there until the one after is asked for
+	Yield,
+	// counter: the statement after it runs, for coverage
+	Count
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+	U8,
+	U16,
+	U32
+}
+
+impl Op {
+	pub const ALL: &[Op] = &[
+		Op::Null,
+		Op::True,
+		Op::False,
+		Op::Constant,
+		Op::Pop,
+		Op::Dup,
+		Op::GetLocal,
+		Op::SetLocal,
+		Op::Box,
+		Op::GetGlobal,
+		Op::SetGlobal,
+		Op::This,
+		Op::GetBacking,
+		Op::SetBacking,
+		Op::InitField,
+		Op::Add,
+		Op::Subtract,
+		Op::Multiply,
+		Op::Divide,
+		Op::Modulo,
+		Op::Equal,
+		Op::NotEqual,
+		Op::Less,
+		Op::LessEqual,
+		Op::Greater,
+		Op::GreaterEqual,
+		Op::BitAnd,
+		Op::BitOr,
+		Op::BitXor,
+		Op::ShiftLeft,
+		Op::ShiftRight,
+		Op::In,
+		Op::Negate,
+		Op::Not,
+		Op::Typeof,
+		Op::NonNull,
+		Op::Increment,
+		Op::Decrement,
+		Op::ToFloat,
+		Op::ToString,
+		Op::Cast,
+		Op::Is,
+		Op::Jump,
+		Op::JumpIfFalse,
+		Op::JumpIfTrue,
+		Op::JumpIfNull,
+		Op::JumpIfNotNull,
+		Op::Unwrap,
+		Op::JumpIfGiven,
+		Op::Call,
+		Op::CallFunction,
+		Op::CallMethod,
+		Op::CallWith,
+		Op::Tail,
+		Op::Invoke,
+		Op::SuperCall,
+		Op::New,
+		Op::NewVariant,
+		Op::Variant,
+		Op::GetField,
+		Op::SetField,
+		Op::GetProperty,
+		Op::GetMethod,
+		Op::Bind,
+		Op::GetIndex,
+		Op::SetIndex,
+		Op::Array,
+		Op::Map,
+		Op::Set,
+		Op::Range,
+		Op::Function,
+		Op::Closure,
+		Op::Builtin,
+		Op::Native,
+		Op::Iterate,
+		Op::Next,
+		Op::EndIterate,
+		Op::Unpack,
+		Op::Try,
+		Op::EndTry,
+		Op::Caught,
+		Op::Rethrow,
+		Op::Throw,
+		Op::Return,
+		Op::Await,
+		Op::Yield,
+		Op::Count
+	];
+
+	pub fn from_byte(byte: u8) -> Option<Op> {
+		Op::ALL.get(byte as usize).copied()
+	}
+
+	pub fn operands(self) -> &'static [Operand] {
+		use Operand::*;
+		match self {
+			Op::Constant | Op::GetLocal | Op::SetLocal | Op::Box | Op::GetGlobal | Op::SetGlobal => &[U16],
+			Op::GetBacking | Op::SetBacking | Op::InitField | Op::Is => &[U16],
+			Op::GetField | Op::SetField | Op::GetProperty | Op::Array | Op::Map | Op::Set | Op::Native => &[U16],
+			Op::Jump | Op::JumpIfFalse | Op::JumpIfTrue | Op::JumpIfNull | Op::JumpIfNotNull | Op::Unwrap | Op::Next | Op::Try => &[U32],
+			Op::GetMethod | Op::Bind | Op::Function | Op::Closure | Op::Count => &[U32],
+			Op::JumpIfGiven => &[U16, U32],
+			Op::Call | Op::Builtin | Op::Iterate | Op::Unpack => &[U8],
+			Op::CallFunction | Op::CallMethod | Op::CallWith | Op::SuperCall => &[U32, U8],
+			Op::Invoke => &[U16, U8],
+			Op::New => &[U16, U32, U8],
+			Op::NewVariant => &[U16, U32, U16, U8],
+			Op::Cast | Op::Variant => &[U16, U16],
+			_ => &[]
+		}
+	}
+
+	// How many bytes the instruction takes, operands included.
+	pub fn size(self) -> usize {
+		1 + self.operands().iter().map(|operand| operand.size()).sum::<usize>()
+	}
+}
+
+impl Operand {
+	pub fn size(self) -> usize {
+		match self {
+			Operand::U8 => 1,
+			Operand::U16 => 2,
+			Operand::U32 => 4
+		}
+	}
+}
+
+// The instruction at `offset` and its operands.
+pub fn decode(code: &[u8], offset: usize) -> Option<(Op, Vec<u32>)> {
+	let op = Op::from_byte(*code.get(offset)?)?;
+	let mut at = offset + 1;
+	let mut operands = Vec::new();
+	for operand in op.operands() {
+		let bytes = code.get(at..at + operand.size())?;
+		operands.push(bytes.iter().rev().fold(0, |value, byte| value << 8 | *byte as u32));
+		at += operand.size();
+	}
+	Some((op, operands))
+}
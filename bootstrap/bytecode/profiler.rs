@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::bytecode::vm::Vm;
+use crate::interpreter::gc;
+
+// How often `dotfun run --profile` samples the calls in progress.
+pub const INTERVAL: Duration = Duration::from_millis(1);
+
+// Finds where a program spends its time by sampling: a timer thread says
+// when a sample is due, every `interval`, and the VM takes it at its next
+// instruction, noting the calls in progress. Each sample stands for one
+// interval of the time of the calls it found, so the more often a function
+// is found running, the longer it took. Time spent waiting on the event
+// loop, with no code running, is not sampled.
+//
+// What each function makes is counted exactly instead: the arrays, maps,
+// objects and cells made while it was the call running.
+pub struct Profiler {
+	interval: Duration,
+	due: Arc<AtomicBool>,
+	stop: Arc<AtomicBool>,
+	timer: Option<JoinHandle<()>>,
+	// Of each function seen, by number, what it is called
+	names: HashMap<u32, String>,
+	// How many samples found each stack of calls, outermost first
+	stacks: HashMap<Vec<u32>, u64>,
+	// What each function made
+	made: HashMap<u32, u64>,
+	// What had been made at the last instruction, once one has run
+	last: Option<u64>
+}
+
+// What the profile says of a function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+	pub name: String,
+	// Sampled running its own code
+	pub own: Duration,
+	// Sampled anywhere in a call of it, the calls it makes included
+	pub total: Duration,
+	pub made: u64
+}
+
+impl Profiler {
+	pub fn new(interval: Duration) -> Profiler {
+		let due = Arc::new(AtomicBool::new(false));
+		let stop = Arc::new(AtomicBool::new(false));
+		let timer = {
+			let (due, stop) = (due.clone(), stop.clone());
+			thread::spawn(move || {
+				while !stop.load(Ordering::Relaxed) {
+					thread::sleep(interval);
+					due.store(true, Ordering::Relaxed);
+				}
+			})
+		};
+		Profiler { interval, due, stop, timer: Some(timer), names: HashMap::new(), stacks: HashMap::new(), made: HashMap::new(), last: None }
+	}
+
+	// Called before each instruction: counts what was made by the one
+	// before, and takes a sample when one is due.
+	pub fn check(&mut self, vm: &Vm) {
+		let made = gc::made();
+		let last = self.last.replace(made).unwrap_or(made);
+		if made != last
+			&& let Some(function) = vm.calls().next_back()
+		{
+			*self.made.entry(function).or_default() += made - last;
+			self.name(vm, function);
+		}
+		if self.due.swap(false, Ordering::Relaxed) {
+			let stack: Vec<u32> = vm.calls().collect();
+			for &function in &stack {
+				self.name(vm, function);
+			}
+			*self.stacks.entry(stack).or_default() += 1;
+		}
+	}
+
+	fn name(&mut self, vm: &Vm, function: u32) {
+		self.names.entry(function).or_insert_with(|| vm.name(function));
+	}
+
+	// Every function sampled or that made anything, those that took
+	// longest of their own time first.
+	pub fn functions(&self) -> Vec<Function> {
+		let mut functions: HashMap<u32, Function> = HashMap::new();
+		let blank = |id: &u32| Function { name: self.names[id].clone(), own: Duration::ZERO, total: Duration::ZERO, made: 0 };
+		for (stack, &samples) in &self.stacks {
+			let time = self.interval * samples as u32;
+			for (index, &id) in stack.iter().enumerate() {
+				let function = functions.entry(id).or_insert_with(|| blank(&id));
+				// Recursive calls are in the stack more than once, but took
+				// the time once
+				if !stack[..index].contains(&id) {
+					function.total += time;
+				}
+				if index == stack.len() - 1 {
+					function.own += time;
+				}
+			}
+		}
+		for (&id, &made) in &self.made {
+			functions.entry(id).or_insert_with(|| blank(&id)).made = made;
+		}
+		let mut functions: Vec<Function> = functions.into_values().collect();
+		functions.sort_by(|a, b| b.own.cmp(&a.own).then(b.total.cmp(&a.total)).then(a.name.cmp(&b.name)));
+		functions
+	}
+
+	// A table of the functions: the time of their own and in all, each also
+	// as a share of the time sampled, and what they made.
+	pub fn table(&self) -> String {
+		let sampled = self.interval * self.stacks.values().sum::<u64>() as u32;
+		let share = |time: Duration| time.as_secs_f64() / sampled.as_secs_f64().max(f64::EPSILON) * 100.0;
+		let mut table = format!("{:<30} {:>10} {:>7} {:>10} {:>7} {:>8}\n", "function", "own", "share", "total", "share", "made");
+		for function in self.functions() {
+			let _ = writeln!(
+				table,
+				"{:<30} {:>10} {:>6.1}% {:>10} {:>6.1}% {:>8}",
+				function.name,
+				milliseconds(function.own),
+				share(function.own),
+				milliseconds(function.total),
+				share(function.total),
+				function.made
+			);
+		}
+		let _ = write!(table, "{} samples, one every {}", self.stacks.values().sum::<u64>(), milliseconds(self.interval));
+		table
+	}
+
+	// The stacks sampled in the folded format flame graph tools read: on
+	// each line, the calls outermost first, separated by `;`, and how many
+	// samples found them.
+	pub fn folded(&self) -> String {
+		let mut lines: Vec<String> = self
+			.stacks
+			.iter()
+			.map(|(stack, samples)| {
+				let names: Vec<&str> = stack.iter().map(|id| self.names[id].as_str()).collect();
+				format!("{} {}\n", names.join(";"), samples)
+			})
+			.collect();
+		lines.sort();
+		lines.concat()
+	}
+}
+
+impl Drop for Profiler {
+	fn drop(&mut self) {
+		self.stop.store(true, Ordering::Relaxed);
+		if let Some(timer) = self.timer.take() {
+			let _ = timer.join();
+		}
+	}
+}
+
+fn milliseconds(duration: Duration) -> String {
+	format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+}
@@ -0,0 +1,282 @@
+use crate::bytecode::{Code, Constant, Module, NONE, Op, TypeTest, decode};
+use crate::sema::builtins::Builtin;
+
+// Checks that a module read from a file is one the VM can run without going
+// wrong itself. The compiler only makes such modules, but a file could have
+// been written by anything, and the VM trusts what it runs: that every index
+// is of something the module has, that jumps go to instructions of their
+// function, and that on every path through a function, instructions only pop
+// what was pushed before them and code never runs off its end.
+pub fn verify(module: &Module) -> Result<(), String> {
+	let functions = module.functions.len();
+	for class in &module.classes {
+		let place = || format!("class '{}'", class.name);
+		for &supertype in &class.supertypes {
+			within(supertype, module.classes.len(), "class", &place)?;
+		}
+		let accessors = class.getters.iter().chain(&class.setters).map(|(_, function)| *function);
+		let methods = class.methods.iter().map(|(_, _, function)| *function);
+		let made = class.init.iter().chain(&class.inherited).chain(&class.constructors).chain(&class.variant_code).copied();
+		for function in methods.chain(accessors).chain(made) {
+			within(function, functions, "function", &place)?;
+		}
+		// The VM goes up the superclasses to the top when making an object
+		let mut superclass = class.superclass;
+		for _ in 0..module.classes.len() {
+			let Some(above) = superclass else {
+				break;
+			};
+			within(above, module.classes.len(), "class", &place)?;
+			superclass = module.classes[above as usize].superclass;
+		}
+		if superclass.is_some() {
+			return Err(format!("corrupt: class '{}' is its own superclass", class.name));
+		}
+	}
+	for constant in &module.constants {
+		if let Constant::Type(ty) = constant {
+			type_test(module, ty)?;
+		}
+	}
+	for &function in module.init.iter().chain(&module.main) {
+		within(function, functions, "function", &|| "the program".to_string())?;
+	}
+	for (file, _) in &module.counters {
+		within(*file, module.files.len(), "file", &|| "a counter".to_string())?;
+	}
+	for code in &module.functions {
+		let place = || format!("'{}'", code.name);
+		within(code.file, module.files.len(), "file", &place)?;
+		if let Some(class) = code.class {
+			within(class, module.classes.len(), "class", &place)?;
+		}
+		if let Some(bytes) = &code.code {
+			Verifier::new(module, code, bytes).run()?;
+		}
+	}
+	Ok(())
+}
+
+fn type_test(module: &Module, ty: &TypeTest) -> Result<(), String> {
+	match ty {
+		TypeTest::Class(class) => within(*class, module.classes.len(), "class", &|| "a type".to_string()),
+		TypeTest::Nullable(inner) => type_test(module, inner),
+		TypeTest::Union(members) => members.iter().try_for_each(|member| type_test(module, member)),
+		_ => Ok(())
+	}
+}
+
+// That `index` is of one of the `count` of `what` there are, for `place`,
+// which refers to it.
+fn within(index: impl Into<u64>, count: usize, what: &str, place: &dyn Fn() -> String) -> Result<(), String> {
+	let index = index.into();
+	match index < count as u64 {
+		true => Ok(()),
+		false => Err(format!("corrupt: {} refers to {} {}, which is not there", place(), what, index))
+	}
+}
+
+// How many values are on the stack of a call as an instruction starts, at
+// the least, counted from where the call started, and for each `try` it is
+// in, innermost last, where its `catch` starts and how many there were at
+// the `try`.
+#[derive(Debug, Clone, PartialEq)]
+struct State {
+	values: usize,
+	handlers: Vec<(usize, usize)>
+}
+
+// Goes through every path of a function's code, an instruction at a time,
+// with what is on the stack as each starts, going through an instruction
+// again when a path to it is found with fewer values.
+struct Verifier<'a> {
+	module: &'a Module,
+	code: &'a Code,
+	bytes: &'a [u8],
+	// By offset, whether an instruction starts there
+	starts: Vec<bool>,
+	states: Vec<Option<State>>,
+	work: Vec<usize>
+}
+
+impl<'a> Verifier<'a> {
+	fn new(module: &'a Module, code: &'a Code, bytes: &'a [u8]) -> Verifier<'a> {
+		let mut starts = vec![false; bytes.len()];
+		let mut offset = 0;
+		while let Some((op, _)) = decode(bytes, offset) {
+			starts[offset] = true;
+			offset += op.size();
+		}
+		Verifier { module, code, bytes, starts, states: vec![None; bytes.len()], work: Vec::new() }
+	}
+
+	fn run(mut self) -> Result<(), String> {
+		let start = State { values: 0, handlers: Vec::new() };
+		self.reach(0, start, &|| format!("'{}'", self.code.name))?;
+		while let Some(offset) = self.work.pop() {
+			let state = self.states[offset].clone().expect("only what was reached is gone through");
+			self.instruction(offset, state)?;
+		}
+		Ok(())
+	}
+
+	// Goes on at `offset` with `state`, from `place`.
+	fn reach(&mut self, offset: usize, state: State, place: &dyn Fn() -> String) -> Result<(), String> {
+		if offset >= self.bytes.len() {
+			return Err(format!("corrupt: {} runs off the end of the code of '{}'", place(), self.code.name));
+		}
+		if !self.starts[offset] {
+			return Err(format!("corrupt: {} goes to {}, where no instruction of '{}' starts", place(), offset, self.code.name));
+		}
+		match &self.states[offset] {
+			None => {
+				self.states[offset] = Some(state);
+				self.work.push(offset);
+				Ok(())
+			}
+			Some(reached) if reached.handlers.len() != state.handlers.len() || reached.handlers.iter().zip(&state.handlers).any(|(a, b)| a.0 != b.0) => {
+				Err(format!("corrupt: the instruction at {} in '{}' is reached inside different tries", offset, self.code.name))
+			}
+			Some(reached) => {
+				let fewer = State {
+					values: reached.values.min(state.values),
+					handlers: reached.handlers.iter().zip(&state.handlers).map(|(a, b)| (a.0, a.1.min(b.1))).collect()
+				};
+				if fewer != *reached {
+					self.states[offset] = Some(fewer);
+					self.work.push(offset);
+				}
+				Ok(())
+			}
+		}
+	}
+
+	fn instruction(&mut self, offset: usize, state: State) -> Result<(), String> {
+		let (module, code) = (self.module, self.code);
+		let (op, operands) = decode(self.bytes, offset).expect("instructions start where one was decoded");
+		let place = || format!("the instruction at {} in '{}'", offset, code.name);
+		let operand = |index: usize| operands[index] as usize;
+		let constant = |index: usize| within(operands[index], module.constants.len(), "constant", &place);
+		let function = |index: usize| within(operands[index], module.functions.len(), "function", &place);
+		match op {
+			Op::Constant | Op::GetBacking | Op::SetBacking | Op::GetField | Op::SetField | Op::GetProperty | Op::Is | Op::Invoke => constant(0)?,
+			Op::Cast => {
+				constant(0)?;
+				constant(1)?;
+			}
+			Op::GetLocal | Op::SetLocal | Op::Box => within(operands[0], code.slots as usize, "local", &place)?,
+			Op::GetGlobal | Op::SetGlobal => within(operands[0], module.globals.len(), "global", &place)?,
+			Op::InitField => {
+				let fields = code.class.map_or(0, |class| module.classes[class as usize].fields.len());
+				within(operands[0], fields, "field", &place)?;
+			}
+			Op::CallFunction | Op::CallMethod | Op::CallWith | Op::GetMethod | Op::Bind | Op::Function => function(0)?,
+			Op::Closure => {
+				function(0)?;
+				for &slot in &module.functions[operand(0)].captures {
+					within(slot, code.slots as usize, "local", &place)?;
+				}
+			}
+			Op::SuperCall if operands[0] != NONE => function(0)?,
+			Op::New | Op::NewVariant | Op::Variant => {
+				within(operands[0], module.classes.len(), "class", &place)?;
+				let class = &module.classes[operand(0)];
+				if op != Op::Variant && operands[1] != NONE {
+					function(1)?;
+				}
+				match op {
+					Op::NewVariant => within(operands[2], class.variants.len(), "variant", &place)?,
+					Op::Variant => within(operands[1], class.variant_code.len(), "variant", &place)?,
+					_ => {}
+				}
+			}
+			Op::Builtin => within(operands[0], Builtin::ALL.len(), "builtin", &place)?,
+			Op::Native => within(operands[0], module.natives.len(), "native", &place)?,
+			Op::Count => within(operands[0], module.counters.len(), "counter", &place)?,
+			_ => {}
+		}
+
+		let (pops, pushes) = match op {
+			Op::Null | Op::True | Op::False | Op::Constant | Op::GetLocal | Op::GetGlobal | Op::This | Op::GetBacking => (0, 1),
+			Op::Variant | Op::Function | Op::Closure | Op::Builtin | Op::Native => (0, 1),
+			Op::Pop | Op::SetLocal | Op::SetGlobal | Op::SetBacking | Op::InitField | Op::JumpIfFalse | Op::JumpIfTrue => (1, 0),
+			Op::Iterate | Op::Throw | Op::Return | Op::Yield => (1, 0),
+			Op::Dup => (1, 2),
+			Op::Add
+			| Op::Subtract
+			| Op::Multiply
+			| Op::Divide
+			| Op::Modulo
+			| Op::Equal
+			| Op::NotEqual
+			| Op::Less
+			| Op::LessEqual
+			| Op::Greater
+			| Op::GreaterEqual
+			| Op::BitAnd
+			| Op::BitOr
+			| Op::BitXor
+			| Op::ShiftLeft
+			| Op::ShiftRight
+			| Op::In
+			| Op::SetField
+			| Op::GetIndex
+			| Op::Range => (2, 1),
+			Op::SetIndex => (3, 1),
+			// What only looks at the value on top, or replaces it
+			Op::Negate | Op::Not | Op::Typeof | Op::NonNull | Op::Increment | Op::Decrement | Op::ToFloat | Op::ToString | Op::Cast => (1, 1),
+			Op::Is | Op::JumpIfNull | Op::JumpIfNotNull | Op::Unwrap | Op::GetField | Op::GetProperty | Op::GetMethod | Op::Bind | Op::Await => (1, 1),
+			Op::Call => (operand(0) + 1, 1),
+			Op::CallMethod | Op::CallWith | Op::Invoke => (operand(1) + 1, 1),
+			Op::CallFunction | Op::SuperCall => (operand(1), 1),
+			Op::New => (operand(2), 1),
+			Op::NewVariant => (operand(3), 1),
+			Op::Array | Op::Set => (operand(0), 1),
+			Op::Map => (operand(0) * 2, 1),
+			Op::Unpack => (1, operand(0)),
+			Op::Box | Op::Jump | Op::JumpIfGiven | Op::Tail | Op::Next | Op::EndIterate => (0, 0),
+			Op::Try | Op::EndTry | Op::Caught | Op::Rethrow | Op::Count => (0, 0)
+		};
+		if state.values < pops {
+			return Err(format!("corrupt: {} pops more than was pushed", place()));
+		}
+		// What it throws is caught by the innermost `catch`, with the stack
+		// cut back to what it was at the `try`, or to what is left of that
+		let values = state.values - pops;
+		if let Some((&(catch, at), outer)) = state.handlers.split_last() {
+			self.reach(catch, State { values: values.min(at) + 1, handlers: outer.to_vec() }, &place)?;
+		}
+		let mut next = State { values: values + pushes, handlers: state.handlers.clone() };
+		let after = offset + op.size();
+		match op {
+			Op::Return | Op::Throw | Op::Rethrow => {}
+			Op::Jump => self.reach(operand(0), next, &place)?,
+			Op::JumpIfFalse | Op::JumpIfTrue | Op::JumpIfNull | Op::JumpIfNotNull | Op::Unwrap => {
+				self.reach(operand(0), next.clone(), &place)?;
+				self.reach(after, next, &place)?;
+			}
+			Op::JumpIfGiven => {
+				self.reach(operand(1), next.clone(), &place)?;
+				self.reach(after, next, &place)?;
+			}
+			// The next item, or at the end, nothing
+			Op::Next => {
+				self.reach(operand(0), next.clone(), &place)?;
+				next.values += 1;
+				self.reach(after, next, &place)?;
+			}
+			Op::Try => {
+				next.handlers.push((operand(0), values));
+				self.reach(after, next, &place)?;
+			}
+			Op::EndTry => {
+				if next.handlers.pop().is_none() {
+					return Err(format!("corrupt: {} is not in a 'try'", place()));
+				}
+				self.reach(after, next, &place)?;
+			}
+			_ => self.reach(after, next, &place)?
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,1862 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::mem;
+use std::rc::{Rc, Weak};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Instant;
+
+use crate::ast::expr::BinaryOp;
+use crate::ast::stmt::{ENTRIES_METHOD, HAS_NEXT_METHOD, ITERATOR_METHOD, NEXT_METHOD};
+use crate::bytecode::debugger::{Debugger, Location};
+use crate::bytecode::profiler::Profiler;
+use crate::bytecode::{Constant, Module, NONE, Op, TypeTest};
+use crate::diagnostics::timings;
+use crate::interpreter::ffi::Libraries;
+use crate::interpreter::gc::{self, Marker, Settings};
+use crate::interpreter::limits::{self, Limit, Meter};
+use crate::interpreter::native::{self, Done, Runtime, Work};
+use crate::interpreter::ops::{self, Combine, Cursor, Sequence, Source, Stage};
+use crate::interpreter::value::{Channel, Class, Closure, Generator, Map, Native, Object, Slot, Tag, Task, TaskState, Thrown, Value};
+use crate::interpreter::{MAX_DEPTH, MAX_TRACE, StackFrame, Uncaught};
+use crate::sema::builtins::Builtin;
+use crate::sema::types::{FnId, TypeId};
+use crate::stdlib;
+
+// Runs a compiled program: the top-level code of every file, in order, then
+// `main` with `args` if there is one, then the tasks they started that are
+// still going, collecting garbage as `gc` says. The natives it calls are
+// `runtime`'s, and what the program prints goes to `out`.
+pub fn run(module: &Module, runtime: &Runtime, args: &[String], gc: Settings, out: &mut dyn Write) -> Result<(), Uncaught> {
+	start(Vm::new(module, runtime, gc, out), args)
+}
+
+// Runs a compiled program as `run` does, stopping where `debugger` says.
+pub fn debug<'a>(module: &'a Module, runtime: &Runtime, args: &[String], debugger: Debugger<'a>, out: &'a mut dyn Write) -> Result<(), Uncaught> {
+	let mut vm = Vm::new(module, runtime, Settings::default(), out);
+	vm.attach(debugger);
+	start(vm, args)
+}
+
+// Runs a compiled program as `run` does, with `profiler` sampling it.
+pub fn profile<'a>(module: &'a Module, runtime: &Runtime, args: &[String], gc: Settings, profiler: &'a mut Profiler, out: &'a mut dyn Write) -> Result<(), Uncaught> {
+	let mut vm = Vm::new(module, runtime, gc, out);
+	vm.profiler = Some(profiler);
+	start(vm, args)
+}
+
+fn start(mut vm: Vm, args: &[String]) -> Result<(), Uncaught> {
+	let module = vm.module;
+	vm.init()?;
+	if let Some(main) = module.main {
+		let args = match module.functions[main as usize].params {
+			0 => Vec::new(),
+			_ => vec![Value::array(args.iter().map(|arg| Value::string(arg.as_str())).collect())]
+		};
+		vm.call(main, args)?;
+	}
+	vm.finish();
+	// Going over a limit in a task nothing waited for stops the program too
+	if let Some(limit) = vm.meter.exceeded() {
+		let thrown = vm.exceeded(limit);
+		return Err(vm.uncaught(thrown));
+	}
+	Ok(())
+}
+
+type Run<T> = Result<T, Thrown>;
+
+// A call in progress. Its locals, the values it works on, and the cursors,
+// handlers and exceptions it has are on the VM's stacks of each, from where
+// it notes.
+struct Frame {
+	function: u32,
+	// Of the next instruction
+	ip: usize,
+	slots: usize,
+	stack: usize,
+	cursors: usize,
+	handlers: usize,
+	caught: usize,
+	this: Option<Value>,
+	// How many arguments the call gave
+	given: usize,
+	// Called from Rust, which gets what it returns, rather than from code
+	// running in the same loop
+	exit: bool
+}
+
+// What a call of one of the program's functions returns to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Caller {
+	// The code that made it, in the same loop
+	Code,
+	// Rust, which gets what it returns: it runs in an `exit` frame
+	Rust,
+	// What the frame running returns to, which it replaces: a tail call
+	Tail
+}
+
+// Where what is thrown goes from `Try` to `EndTry`, and what is left on the
+// VM's stacks when it gets there.
+struct Handler {
+	frame: usize,
+	target: usize,
+	stack: usize,
+	cursors: usize,
+	caught: usize
+}
+
+// The calls in progress of a task, on stacks of their own, which are the
+// VM's while the task runs.
+#[derive(Default)]
+struct Context {
+	stack: Vec<Value>,
+	slots: Vec<Slot>,
+	frames: Vec<Frame>,
+	cursors: Vec<Cursor>,
+	handlers: Vec<Handler>,
+	caught: Vec<Thrown>
+}
+
+// A task that is not running, and where it stopped.
+struct Suspended {
+	task: Rc<Task>,
+	context: Context
+}
+
+// What a thread reading for `readFile` or `readLine`, or working for a
+// native, sends back: the number of the read, and what makes its value.
+type Read = (u64, Done);
+
+// Runs a compiled program. Calls from one function to another run in the
+// same loop; those from Rust, for constructors, accessors, `toString()` and
+// iterators, start a loop of their own.
+//
+// Async calls run as tasks, each on stacks of its own, taking turns on one
+// thread: one runs until it finishes or waits for another with `await`,
+// then the next ready one goes on. When none is ready, the event loop waits
+// for the next timer of `sleep` to be up, or read to be done.
+//
+// A call of a generator function runs the same way, on stacks of its own,
+// from where it stopped to its next `yield` each time its iterator is asked
+// for an element, in a loop of its own.
+//
+// While it is around, what it makes is tracked by a heap of its own, which
+// it collects between instructions of the outermost loop: everything the
+// program can reach is then on the stacks of its tasks or in its globals.
+// Collecting is also how it measures the heap against the runtime's limits.
+pub struct Vm<'a> {
+	module: &'a Module,
+	out: &'a mut dyn Write,
+	// The values of the module's constants, types as null
+	constants: Vec<Value>,
+	globals: Vec<Value>,
+	stack: Vec<Value>,
+	slots: Vec<Slot>,
+	frames: Vec<Frame>,
+	cursors: Vec<Cursor>,
+	handlers: Vec<Handler>,
+	// What the catches running now caught, for `Rethrow`
+	caught: Vec<Thrown>,
+	// The native function being called, for stack traces
+	native: Option<Rc<Native>>,
+	// Of the module's natives, what the runtime has
+	natives: Vec<Option<Rc<Native>>>,
+	libraries: Libraries,
+	// By id, what objects of each class point to
+	classes: Vec<Rc<Class>>,
+	// Each enum variant is made once, when it is first used
+	variants: HashMap<(usize, usize), Value>,
+	// How many loops are running, one inside the other
+	loops: usize,
+	// The task running, if any
+	task: Option<Rc<Task>>,
+	// Set by `Await` when the task running has to wait for this one
+	awaiting: Option<Rc<Task>>,
+	// The task the event loop runs until it is done
+	driving: Option<Rc<Task>>,
+	// The loops running when the event loop was started, which the tasks it
+	// goes on with run inside of
+	base: usize,
+	// Tasks to go on with, in order, and what to go on with: nothing for
+	// one not started, or how the task it waited for finished
+	ready: VecDeque<(Suspended, Option<Run<Value>>)>,
+	// Tasks waiting, each with the one it waits for
+	waiting: Vec<(Rc<Task>, Suspended)>,
+	// The tasks of `sleep`, with when they are up
+	timers: Vec<(Instant, Rc<Task>)>,
+	// The tasks of `receive()`, with the channel each waits for a value of,
+	// in the order they asked
+	receivers: Vec<(Rc<Channel>, Rc<Task>)>,
+	// The tasks of reads, by their number, each with what to throw if it
+	// fails but the message
+	reads: HashMap<u64, (Rc<Task>, Thrown)>,
+	next_read: u64,
+	// The generators not finished, by id, each with where it stopped, kept
+	// while anything can still ask their iterator for an element
+	generators: HashMap<u64, (Weak<Generator>, Context)>,
+	next_generator: u64,
+	// The loop the generator running runs in, which its `yield` leaves
+	yielding: Option<usize>,
+	// Set by `Yield`, for the iterator to give
+	yielded: Option<Value>,
+	events: (Sender<Read>, Receiver<Read>),
+	// Asked before each instruction whether to stop there
+	debugger: Option<Debugger<'a>>,
+	// Told of each instruction, to sample the calls running and count what
+	// they make
+	profiler: Option<&'a mut Profiler>,
+	// What the program has used of the runtime's limits
+	meter: Meter,
+	// How many times each of the module's counters was counted
+	counts: Vec<u64>
+}
+
+impl<'a> Vm<'a> {
+	pub fn new(module: &'a Module, runtime: &Runtime, gc: Settings, out: &'a mut dyn Write) -> Vm<'a> {
+		gc::install(gc);
+		stdlib::env::clear();
+		let classes = module
+			.classes
+			.iter()
+			.enumerate()
+			.map(|(id, class)| {
+				let mut methods: HashMap<String, Vec<(usize, FnId)>> = HashMap::new();
+				for (name, arity, method) in &class.methods {
+					methods.entry(name.clone()).or_default().push((*arity as usize, FnId(*method)));
+				}
+				Rc::new(Class {
+					id: TypeId(id as u32),
+					name: class.name.clone(),
+					by_value: class.by_value,
+					fields: class.fields.clone(),
+					variants: class.variants.clone(),
+					supertypes: class.supertypes.iter().map(|&supertype| TypeId(supertype)).collect(),
+					methods
+				})
+			})
+			.collect();
+		let constants = module
+			.constants
+			.iter()
+			.map(|constant| match constant {
+				Constant::Int(value) => Value::Int(*value),
+				Constant::Float(value) => Value::Float(*value),
+				Constant::String(text) => Value::string(text.as_str()),
+				Constant::Type(_) => Value::Null
+			})
+			.collect();
+		Vm {
+			module,
+			out,
+			constants,
+			globals: vec![Value::Null; module.globals.len()],
+			stack: Vec::new(),
+			slots: Vec::new(),
+			frames: Vec::new(),
+			cursors: Vec::new(),
+			handlers: Vec::new(),
+			caught: Vec::new(),
+			native: None,
+			natives: runtime.bind(module.natives.iter().map(String::as_str)),
+			libraries: Libraries::new(),
+			classes,
+			variants: HashMap::new(),
+			loops: 0,
+			task: None,
+			awaiting: None,
+			driving: None,
+			ready: VecDeque::new(),
+			waiting: Vec::new(),
+			timers: Vec::new(),
+			receivers: Vec::new(),
+			reads: HashMap::new(),
+			next_read: 0,
+			generators: HashMap::new(),
+			next_generator: 0,
+			yielding: None,
+			yielded: None,
+			base: 0,
+			events: mpsc::channel(),
+			debugger: None,
+			profiler: None,
+			meter: Meter::new(runtime.limits),
+			counts: vec![0; module.counters.len()]
+		}
+	}
+
+	// Runs the top-level code of every file, setting the globals.
+	pub fn init(&mut self) -> Result<(), Uncaught> {
+		for &code in &self.module.init {
+			self.call(code, Vec::new())?;
+		}
+		Ok(())
+	}
+
+	// Calls a top-level function, like `main` or a test, and when it is
+	// async, runs the event loop until it is done.
+	pub fn call(&mut self, function: u32, args: Vec<Value>) -> Result<Value, Uncaught> {
+		let result = match self.call_code(function, None, args) {
+			Ok(Value::Task(task)) if self.module.functions[function as usize].is_async => self.wait(function, task),
+			result => result
+		};
+		result.map_err(|thrown| self.uncaught(thrown))
+	}
+
+	// Runs what is left to do: tasks started and never waited for, and the
+	// timers and reads they wait on. What they throw is lost, as nothing
+	// waits for it.
+	pub fn finish(&mut self) {
+		self.drive(None);
+	}
+
+	fn uncaught(&mut self, thrown: Thrown) -> Uncaught {
+		let message = match thrown.value {
+			Value::String(message) if thrown.error => message.to_string(),
+			value => format!("Uncaught exception: {}", self.stringify(&value).unwrap_or_else(|_| value.type_name().to_string()))
+		};
+		let StackFrame { file, span, .. } = thrown.trace[0];
+		Uncaught { message, file, span, trace: thrown.trace, hidden: thrown.hidden, limit: thrown.limit }
+	}
+
+	// A run-time error, thrown like an exception so `catch` can handle it.
+	fn error(&self, message: impl Into<String>) -> Thrown {
+		self.thrown(Value::string(message.into()), true)
+	}
+
+	// Going over a limit, which is thrown as an error nothing catches.
+	fn exceeded(&self, limit: Limit) -> Thrown {
+		Thrown { limit: Some(limit), ..self.error(limit.to_string()) }
+	}
+
+	// What is thrown now, with the calls it is thrown through, each where
+	// the instruction it is at came from.
+	fn thrown(&self, value: Value, error: bool) -> Thrown {
+		let module = self.module;
+		let mut trace = Vec::new();
+		let mut frames = self.frames.iter().rev().map(|frame| {
+			let code = &module.functions[frame.function as usize];
+			StackFrame { function: self.name(frame.function), file: code.file as usize, span: code.span_at(frame.ip.saturating_sub(1)), native: false }
+		});
+		if let Some(native) = &self.native
+			&& let Some(caller) = frames.next()
+		{
+			trace.push(StackFrame { function: native.name.clone(), file: caller.file, span: caller.span, native: true });
+			trace.push(caller);
+		}
+		trace.extend(frames.take(MAX_TRACE - trace.len()));
+		if trace.is_empty() {
+			trace.push(StackFrame { function: "<top level>".to_string(), file: 0, span: Default::default(), native: false });
+		}
+		let hidden = (self.frames.len() + self.native.is_some() as usize).saturating_sub(MAX_TRACE);
+		// What went over the limit on memory asking for it cannot be caught
+		let limit = if error { limits::over() } else { None };
+		Thrown { value, trace, hidden, error, limit }
+	}
+
+	// What a stack trace calls a function: methods and accessors with their
+	// class.
+	pub fn name(&self, function: u32) -> String {
+		let code = &self.module.functions[function as usize];
+		match code.class {
+			Some(class) => format!("{}.{}", self.module.classes[class as usize].name, code.name),
+			None => code.name.clone()
+		}
+	}
+
+	// ---------------------------------------------------------------------
+	// Debugging
+	// ---------------------------------------------------------------------
+
+	// Runs what is left under `debugger`, which stops it where it says.
+	pub fn attach(&mut self, debugger: Debugger<'a>) {
+		self.debugger = Some(debugger);
+	}
+
+	pub fn module(&self) -> &'a Module {
+		self.module
+	}
+
+	// For a module compiled for coverage, how many times each statement it
+	// counts has run so far, in the order of its counters.
+	pub fn counts(&self) -> &[u64] {
+		&self.counts
+	}
+
+	// Where the next instruction is, if code is running.
+	pub fn location(&self) -> Option<Location> {
+		let frame = self.frames.last()?;
+		let code = &self.module.functions[frame.function as usize];
+		Some(Location { file: code.file as usize, line: code.span_at(frame.ip).line, depth: self.frames.len() })
+	}
+
+	// The calls in progress, innermost first, each where it is: the one
+	// running at its next instruction, the others at their call.
+	pub fn backtrace(&self) -> Vec<StackFrame> {
+		let module = self.module;
+		self.frames
+			.iter()
+			.rev()
+			.enumerate()
+			.map(|(depth, frame)| {
+				let code = &module.functions[frame.function as usize];
+				let ip = if depth == 0 { frame.ip } else { frame.ip.saturating_sub(1) };
+				StackFrame { function: self.name(frame.function), file: code.file as usize, span: code.span_at(ip), native: false }
+			})
+			.collect()
+	}
+
+	// The functions of the calls in progress, outermost first.
+	pub fn calls(&self) -> impl DoubleEndedIterator<Item = u32> + '_ {
+		self.frames.iter().map(|frame| frame.function)
+	}
+
+	// The named locals of the call `depth` calls out from the innermost, in
+	// the order of their slots, with what they are now. Those not set yet
+	// are null.
+	pub fn locals(&self, depth: usize) -> Vec<(String, Value)> {
+		let Some(frame) = self.frames.len().checked_sub(depth + 1).map(|index| &self.frames[index]) else {
+			return Vec::new();
+		};
+		let code = &self.module.functions[frame.function as usize];
+		let value = |slot: &Slot| match slot {
+			Slot::Value(value) => value.clone(),
+			Slot::Cell(cell) => cell.borrow().clone()
+		};
+		code.locals.iter().filter_map(|(slot, name)| Some((name.clone(), value(self.slots.get(frame.slots + *slot as usize)?)))).collect()
+	}
+
+	// What `this` is in the call `depth` calls out from the innermost.
+	pub fn this(&self, depth: usize) -> Option<Value> {
+		self.frames.len().checked_sub(depth + 1).and_then(|index| self.frames[index].this.clone())
+	}
+
+	pub fn globals(&self) -> Vec<(String, Value)> {
+		self.module.globals.iter().cloned().zip(self.globals.iter().cloned()).collect()
+	}
+
+	fn debug(&mut self) {
+		if let Some(mut debugger) = self.debugger.take() {
+			debugger.check(self);
+			self.debugger = Some(debugger);
+		}
+	}
+
+	// ---------------------------------------------------------------------
+	// Calls
+	// ---------------------------------------------------------------------
+
+	// Starts a call of `function` with the `argc` arguments on top of the
+	// stack, filling in null for those not given, to be replaced by their
+	// defaults, and gathering the rest for `...rest`.
+	// An async function is started as a task instead.
+	fn enter(&mut self, function: u32, this: Option<Value>, captures: &[Slot], argc: usize, caller: Caller) -> Run<()> {
+		let code = &self.module.functions[function as usize];
+		if let Some(foreign) = &code.foreign {
+			let args = self.stack.split_off(self.stack.len() - argc);
+			let value = self.libraries.call(&code.name, foreign, &args).map_err(|message| self.error(message))?;
+			self.stack.push(value);
+			return Ok(());
+		}
+		if code.code.is_none() {
+			return Err(self.error(format!("'{}' has no body to run", code.name)));
+		}
+		if code.is_async {
+			let synchronous = caller != Caller::Rust && self.synchronous();
+			self.spawn(function, this, captures, argc);
+			if synchronous {
+				let task = self.pop();
+				let value = self.complete(task)?;
+				self.stack.push(value);
+			}
+			return Ok(());
+		}
+		if code.generator {
+			self.generator(function, this, captures, argc);
+			return Ok(());
+		}
+		if caller == Caller::Tail {
+			let exit = self.vacate(argc);
+			self.push_frame(function, this, captures, argc, exit);
+			return Ok(());
+		}
+		if self.frames.len() == MAX_DEPTH {
+			return Err(self.error(format!("Stack overflow: more than {} calls deep", MAX_DEPTH)));
+		}
+		self.meter.depth(self.frames.len() + 1).map_err(|limit| self.exceeded(limit))?;
+		self.push_frame(function, this, captures, argc, caller == Caller::Rust);
+		Ok(())
+	}
+
+	// Ends the frame running for the call taking its place, which gets the
+	// `argc` arguments on top of the stack, and whether it was an `exit`
+	// one, as that call is then. Stack traces leave out the frames ended so.
+	fn vacate(&mut self, argc: usize) -> bool {
+		let frame = self.frames.pop().expect("code runs in a frame");
+		let args = self.stack.split_off(self.stack.len() - argc);
+		self.stack.truncate(frame.stack);
+		self.stack.extend(args);
+		self.slots.truncate(frame.slots);
+		self.cursors.truncate(frame.cursors);
+		self.handlers.truncate(frame.handlers);
+		self.caught.truncate(frame.caught);
+		frame.exit
+	}
+
+	fn push_frame(&mut self, function: u32, this: Option<Value>, captures: &[Slot], argc: usize, exit: bool) {
+		let code = &self.module.functions[function as usize];
+		let start = self.stack.len() - argc;
+		let slots = self.slots.len();
+		self.slots.extend(captures.iter().cloned());
+		let params = code.params as usize;
+		let mut args = self.stack.drain(start..);
+		for index in 0..params {
+			let value = match code.variadic && index + 1 == params {
+				true => Value::array(args.by_ref().collect()),
+				false => args.next().unwrap_or(Value::Null)
+			};
+			self.slots.push(Slot::Value(value));
+		}
+		drop(args);
+		self.slots.resize(slots + code.slots as usize, Slot::Value(Value::Null));
+		self.frames.push(Frame {
+			function,
+			ip: 0,
+			slots,
+			stack: start,
+			cursors: self.cursors.len(),
+			handlers: self.handlers.len(),
+			caught: self.caught.len(),
+			this,
+			given: argc.min(params),
+			exit
+		});
+	}
+
+	// Starts an async call with the `argc` arguments on top of the stack as
+	// a task, on stacks of its own, leaving the task in their place. Its
+	// frame is an `exit` one, returning to the event loop.
+	fn spawn(&mut self, function: u32, this: Option<Value>, captures: &[Slot], argc: usize) {
+		let args = self.stack.split_off(self.stack.len() - argc);
+		let mut context = Context { stack: args, ..Context::default() };
+		self.switch(&mut context);
+		self.push_frame(function, this, captures, argc, true);
+		self.switch(&mut context);
+		let task = Rc::new(Task::default());
+		self.ready.push_back((Suspended { task: task.clone(), context }, None));
+		self.stack.push(Value::Task(task));
+	}
+
+	// A generator's call only starts on stacks of its own, for its iterator
+	// to run a `yield` at a time.
+	fn generator(&mut self, function: u32, this: Option<Value>, captures: &[Slot], argc: usize) {
+		let args = self.stack.split_off(self.stack.len() - argc);
+		let mut context = Context { stack: args, ..Context::default() };
+		self.switch(&mut context);
+		self.push_frame(function, this, captures, argc, true);
+		self.switch(&mut context);
+		let generator = Rc::new(Generator { id: self.next_generator });
+		self.next_generator += 1;
+		self.generators.insert(generator.id, (Rc::downgrade(&generator), context));
+		self.stack.push(Value::Iterator(Sequence::new(Source::Generator(generator))));
+	}
+
+	// Goes on with a generator to its next `yield`, giving what it yields,
+	// or none once it has returned. Once it throws, it is finished too.
+	fn next_yielded(&mut self, generator: &Generator) -> Run<Option<Value>> {
+		let Some((weak, mut context)) = self.generators.remove(&generator.id) else {
+			return Ok(None);
+		};
+		self.switch(&mut context);
+		let yielding = self.yielding.replace(self.loops + 1);
+		let result = self.run();
+		self.yielding = yielding;
+		self.switch(&mut context);
+		result?;
+		Ok(self.yielded.take().inspect(|_| {
+			self.generators.insert(generator.id, (weak, context));
+		}))
+	}
+
+	// Calls `function` from Rust and runs it to the end, or for an async
+	// one, returns its task.
+	fn call_code(&mut self, function: u32, this: Option<Value>, args: Vec<Value>) -> Run<Value> {
+		let (argc, depth) = (args.len(), self.frames.len());
+		self.stack.extend(args);
+		self.enter(function, this, &[], argc, Caller::Rust)?;
+		if self.frames.len() > depth { self.run() } else { Ok(self.pop()) }
+	}
+
+	// Calls the value `callee` with the `argc` arguments on top of the stack.
+	// A function of the program is only started, to run in the loop, unless
+	// it is an `exit` call; anything else is done with here, leaving what it
+	// returns on the stack.
+	fn call_value(&mut self, callee: Value, argc: usize, caller: Caller) -> Run<()> {
+		match callee {
+			Value::Function(closure) => self.enter(closure.function.0, closure.this.clone(), &closure.captures, argc, caller),
+			Value::Builtin(builtin) => {
+				let args = self.stack.split_off(self.stack.len() - argc);
+				let mut value = self.builtin(builtin, args)?;
+				if builtin.is_async() && self.synchronous() {
+					value = self.complete(value)?;
+				}
+				self.stack.push(value);
+				Ok(())
+			}
+			Value::Native(native) => {
+				let args = self.stack.split_off(self.stack.len() - argc);
+				ops::arity(&native, args.len()).map_err(|message| self.error(message))?;
+				self.native = Some(native.clone());
+				let result = (native.function)(&args).map_err(|message| self.error(message));
+				let result = self.meter.native().map_err(|limit| self.exceeded(limit)).and(result);
+				for (task, work) in native::started() {
+					self.background(task, work);
+				}
+				self.native = None;
+				self.stack.push(result?);
+				Ok(())
+			}
+			Value::Null => Err(self.error("Cannot call null")),
+			callee => Err(self.error(format!("'{}' is not a function", callee.type_name())))
+		}
+	}
+
+	// The async ones start a task, which the event loop finishes: `sleep`
+	// once its time is up, and reads once a thread of their own has read
+	// what they were asked for.
+	fn builtin(&mut self, builtin: Builtin, args: Vec<Value>) -> Run<Value> {
+		let arg = args.first();
+		match builtin {
+			Builtin::Print | Builtin::Println => {
+				let text = match arg {
+					Some(value) => self.stringify(value)?,
+					None => String::new()
+				};
+				let written = match builtin {
+					Builtin::Print => write!(self.out, "{}", text),
+					_ => writeln!(self.out, "{}", text)
+				};
+				written.map_err(|err| self.error(format!("Cannot write output: {}", err)))?;
+				Ok(Value::Null)
+			}
+			Builtin::Sleep => {
+				let duration = ops::millis(arg.unwrap_or(&Value::Null)).map_err(|message| self.error(message))?;
+				let task = Rc::new(Task::default());
+				self.timers.push((Instant::now() + duration, task.clone()));
+				Ok(Value::Task(task))
+			}
+			Builtin::ReadFile | Builtin::ReadLine => {
+				let path = match (builtin, arg) {
+					(Builtin::ReadFile, Some(Value::String(path))) => Some(path.to_string()),
+					(Builtin::ReadFile, _) => return Err(self.error("'readFile' takes the path of a file")),
+					_ => None
+				};
+				let task = Rc::new(Task::default());
+				self.background(
+					task.clone(),
+					Box::new(move || {
+						let text = match path {
+							Some(path) => ops::read_file(&path).map(Some),
+							None => ops::read_line()
+						};
+						Box::new(move || text.map(|text| text.map_or(Value::Null, Value::string)))
+					})
+				);
+				Ok(Value::Task(task))
+			}
+			Builtin::Channel => Ok(Value::Channel(Rc::new(Channel::default()))),
+			Builtin::Ok => Ok(Value::tagged(Tag::Ok, arg.cloned().unwrap_or(Value::Null))),
+			Builtin::Err => Ok(Value::tagged(Tag::Err, arg.cloned().unwrap_or(Value::Null))),
+			Builtin::Some => Ok(Value::tagged(Tag::Some, arg.cloned().unwrap_or(Value::Null))),
+			Builtin::None => Ok(Value::tagged(Tag::None, Value::Null))
+		}
+	}
+
+	// Does `work` on a thread of its own, for the event loop to finish `task`
+	// with once it is done.
+	fn background(&mut self, task: Rc<Task>, work: Work) {
+		let (read, events) = (self.next_read, self.events.0.clone());
+		self.next_read += 1;
+		thread::spawn(move || {
+			let done = work();
+			// The VM may be gone, with no one left to tell
+			let _ = events.send((read, done));
+		});
+		self.reads.insert(read, (task, self.thrown(Value::Null, true)));
+	}
+
+	// Calls the value `callee` from Rust and runs it to the end.
+	fn apply(&mut self, callee: Value, args: Vec<Value>) -> Run<Value> {
+		let (argc, depth) = (args.len(), self.frames.len());
+		self.stack.extend(args);
+		self.call_value(callee, argc, Caller::Rust)?;
+		if self.frames.len() > depth { self.run() } else { Ok(self.pop()) }
+	}
+
+	// The override of `method` in the class of `receiver`, or `method`
+	// itself when it has none.
+	fn dispatch(&self, receiver: &Value, method: u32) -> Run<u32> {
+		let code = &self.module.functions[method as usize];
+		match receiver {
+			Value::Object(object) => Ok(object.class.method(&code.name, Some(code.params as usize)).map_or(method, |method| method.0)),
+			Value::Null => Err(self.error(format!("Cannot call '{}' on null", code.name))),
+			_ => Ok(method)
+		}
+	}
+
+	// Calls the method `name` of `receiver`, looked up by name at run time:
+	// for `Any` values, built-in members, and the iterator protocol.
+	fn invoke(&mut self, receiver: Value, name: &str, args: Vec<Value>) -> Run<Value> {
+		if let Value::Object(object) = &receiver {
+			if let Some(method) = object.class.method(name, None) {
+				return self.call_code(method.0, Some(receiver), args);
+			}
+			if object.class.field(name).is_some() {
+				let value = self.get(receiver, name)?;
+				return self.apply(value, args);
+			}
+		}
+		if name == "toString" && args.is_empty() {
+			return Ok(Value::string(self.stringify(&receiver)?));
+		}
+		if let (Value::String(template), "format", [Value::Array(items)]) = (&receiver, name, args.as_slice()) {
+			let items = items.borrow().clone();
+			let args = items.iter().map(|item| self.stringify(item)).collect::<Run<Vec<String>>>()?;
+			return ops::format(template, &args).map(Value::string).map_err(|message| self.error(message));
+		}
+		if let Value::Tagged(tag, value) = &receiver
+			&& let Some(combine) = ops::combinator(*tag, value, name, &args)
+		{
+			return match combine {
+				Combine::Call(inputs, tag) => {
+					let result = self.apply(args[0].clone(), inputs)?;
+					Ok(match tag {
+						Some(tag) => Value::tagged(tag, result),
+						None => result
+					})
+				}
+				Combine::Keep => Ok(receiver)
+			};
+		}
+		if let Value::Channel(channel) = &receiver {
+			let value = self.channel_method(channel.clone(), name, &args)?;
+			return match name == "receive" && self.synchronous() {
+				true => self.complete(value),
+				false => Ok(value)
+			};
+		}
+		if let Some(value) = self.sequence_method(&receiver, name, &args)? {
+			return Ok(value);
+		}
+		ops::method(&receiver, name, &args).map_err(|message| self.error(message))
+	}
+
+	// The methods of sequences, for what has them (see `ops::Sequence`), and
+	// the iterator protocol of `Iterator`s. None for any other method.
+	fn sequence_method(&mut self, receiver: &Value, name: &str, args: &[Value]) -> Run<Option<Value>> {
+		if let Value::Iterator(sequence) = receiver {
+			match (name, args) {
+				(HAS_NEXT_METHOD, []) => {
+					let next = self.pull(sequence)?;
+					let more = next.is_some();
+					sequence.borrow_mut().peeked(next);
+					return Ok(Some(Value::Bool(more)));
+				}
+				(NEXT_METHOD, []) => match self.pull(sequence)? {
+					Some(next) => return Ok(Some(next)),
+					None => return Err(self.error("The iterator has no elements left".to_string()))
+				},
+				_ => {}
+			}
+		}
+		if !ops::is_sequence_method(receiver, name, args.len()) {
+			return Ok(None);
+		}
+		let sequence = self.sequence(receiver.clone())?;
+		Ok(Some(match (name, args) {
+			(ITERATOR_METHOD, []) => Value::Iterator(sequence),
+			("map", [function]) => Value::Iterator(Sequence::new(Source::Map(sequence, function.clone()))),
+			("filter", [function]) => Value::Iterator(Sequence::new(Source::Filter(sequence, function.clone()))),
+			("zip", [other]) => Value::Iterator(Sequence::new(Source::Zip(sequence, self.sequence(other.clone())?))),
+			("enumerate", []) => Value::Iterator(Sequence::new(Source::Enumerate(sequence, 0))),
+			("toArray", []) => {
+				let mut items = Vec::new();
+				while let Some(element) = self.pull(&sequence)? {
+					ops::push(&mut items, element).map_err(|message| self.error(message))?;
+				}
+				Value::array(items)
+			}
+			("reduce", [initial, function]) => {
+				let mut total = initial.clone();
+				while let Some(element) = self.pull(&sequence)? {
+					total = self.apply(function.clone(), vec![total, element])?;
+				}
+				total
+			}
+			// As soon as one element decides it
+			("any" | "all", [function]) => {
+				let any = name == "any";
+				loop {
+					let Some(element) = self.pull(&sequence)? else {
+						break Value::Bool(!any);
+					};
+					if self.apply(function.clone(), vec![element])?.truthy() == any {
+						break Value::Bool(any);
+					}
+				}
+			}
+			_ => return Ok(None)
+		}))
+	}
+
+	// What a `for` loop would go through in `value`, as a sequence;
+	// iterators are one already.
+	fn sequence(&mut self, value: Value) -> Run<Rc<RefCell<Sequence>>> {
+		if let Value::Iterator(sequence) = value {
+			return Ok(sequence);
+		}
+		let cursor = self.cursor(value, false)?;
+		Ok(Sequence::new(Source::Cursor(cursor)))
+	}
+
+	// The next element of a sequence, or none at the end.
+	fn pull(&mut self, sequence: &Rc<RefCell<Sequence>>) -> Run<Option<Value>> {
+		let stage = sequence.borrow_mut().stage().map_err(|message| self.error(message))?;
+		Ok(match stage {
+			Stage::Ready(next) => next,
+			Stage::Protocol(iterator) => {
+				if !self.invoke(iterator.clone(), HAS_NEXT_METHOD, Vec::new())?.truthy() {
+					return Ok(None);
+				}
+				Some(self.invoke(iterator, NEXT_METHOD, Vec::new())?)
+			}
+			Stage::Map(inner, function) => match self.pull(&inner)? {
+				Some(element) => Some(self.apply(function, vec![element])?),
+				None => None
+			},
+			Stage::Filter(inner, test) => loop {
+				match self.pull(&inner)? {
+					Some(element) if self.apply(test.clone(), vec![element.clone()])?.truthy() => break Some(element),
+					Some(_) => {}
+					None => break None
+				}
+			},
+			// Done at the end of either
+			Stage::Zip(first, second) => match self.pull(&first)? {
+				Some(element) => self.pull(&second)?.map(|other| Value::array(vec![element, other])),
+				None => None
+			},
+			Stage::Enumerate(inner, index) => self.pull(&inner)?.map(|element| Value::array(vec![Value::Int(index), element])),
+			Stage::Resume(generator) => self.next_yielded(&generator)?
+		})
+	}
+
+	// ---------------------------------------------------------------------
+	// Tasks
+	// ---------------------------------------------------------------------
+
+	// `receive()` starts a task that a value sent to the channel finishes.
+	// Sending and closing then go on with the tasks waiting for one.
+	fn channel_method(&mut self, channel: Rc<Channel>, name: &str, args: &[Value]) -> Run<Value> {
+		let value = match (name, args) {
+			("receive", []) => {
+				let task = Rc::new(Task::default());
+				self.receivers.push((channel.clone(), task.clone()));
+				Value::Task(task)
+			}
+			_ => ops::method(&Value::Channel(channel.clone()), name, args).map_err(|message| self.error(message))?
+		};
+		self.deliver(&channel);
+		Ok(value)
+	}
+
+	// Gives what `channel` has to the tasks receiving from it, in the order
+	// they asked.
+	fn deliver(&mut self, channel: &Rc<Channel>) {
+		while let Some(index) = self.receivers.iter().position(|(waiting, _)| Rc::ptr_eq(waiting, channel))
+			&& let Some(value) = channel.receive()
+		{
+			let (_, task) = self.receivers.remove(index);
+			self.settle(&task, Ok(value));
+		}
+	}
+
+	// Runs the event loop until the task of a call of `function` is done,
+	// and gives how it finished.
+	fn wait(&mut self, function: u32, task: Rc<Task>) -> Run<Value> {
+		self.drive(Some(task.clone()));
+		let state = task.state.borrow().clone();
+		match state {
+			TaskState::Done(value) => Ok(value),
+			TaskState::Failed(thrown) => Err(thrown),
+			// Nothing is running, so the error is put on the function called
+			TaskState::Running => {
+				let code = &self.module.functions[function as usize];
+				let frame = StackFrame { function: self.name(function), file: code.file as usize, span: code.span, native: false };
+				let (message, limit) = match self.meter.exceeded() {
+					Some(limit) => (limit.to_string(), Some(limit)),
+					None => ("Every task left is waiting for another, so none of them can finish".to_string(), None)
+				};
+				Err(Thrown { value: Value::string(message), trace: vec![frame], hidden: 0, error: true, limit })
+			}
+		}
+	}
+
+	// Code that is not async cannot wait for the task of a call it makes, so
+	// the call runs to its end there and then instead, as the interpreter's
+	// do, and gives what it returns rather than its task.
+	fn synchronous(&self) -> bool {
+		self.frames.last().is_some_and(|frame| !self.module.functions[frame.function as usize].is_async)
+	}
+
+	// Runs the event loop from inside a call until `task` is done, and gives
+	// how it finished. The task running, if any, is left as it is meanwhile,
+	// and the tasks gone on with run in loops of their own.
+	fn complete(&mut self, task: Value) -> Run<Value> {
+		let Value::Task(task) = task else {
+			return Ok(task);
+		};
+		let (running, driving) = (self.task.take(), self.driving.take());
+		let base = mem::replace(&mut self.base, self.loops);
+		self.drive(Some(task.clone()));
+		(self.task, self.driving, self.base) = (running, driving, base);
+		let state = task.state.borrow().clone();
+		match state {
+			TaskState::Done(value) => Ok(value),
+			TaskState::Failed(thrown) => Err(thrown),
+			TaskState::Running => Err(match self.meter.exceeded() {
+				Some(limit) => self.exceeded(limit),
+				None => self.error("Every task left is waiting for another, so none of them can finish")
+			})
+		}
+	}
+
+	// Runs tasks until `until` is done, or until there is nothing left to do:
+	// no task ready, and no timer or read to wait for, or a limit gone over.
+	// Called from Rust between calls, or by `complete` inside one.
+	fn drive(&mut self, until: Option<Rc<Task>>) {
+		self.driving = until;
+		loop {
+			if let Some(task) = &self.driving
+				&& !matches!(*task.state.borrow(), TaskState::Running)
+			{
+				break;
+			}
+			if self.meter.check().is_err() {
+				break;
+			}
+			self.poll(false);
+			if let Some((suspended, resume)) = self.ready.pop_front() {
+				self.resume(suspended, resume);
+			} else if !self.poll(true) {
+				break;
+			}
+		}
+		self.driving = None;
+	}
+
+	// Goes on with a task where it stopped, until it finishes or waits for
+	// another.
+	fn resume(&mut self, suspended: Suspended, resume: Option<Run<Value>>) {
+		let Suspended { task, mut context } = suspended;
+		self.switch(&mut context);
+		self.task = Some(task.clone());
+		let result = match resume {
+			None => self.run(),
+			Some(Ok(value)) => {
+				self.stack.push(value);
+				self.run()
+			}
+			Some(Err(thrown)) => self.catch(thrown, 0).and_then(|()| self.run())
+		};
+		self.task = None;
+		self.switch(&mut context);
+		match self.awaiting.take() {
+			Some(awaited) => self.waiting.push((awaited, Suspended { task, context })),
+			None => self.settle(&task, result)
+		}
+	}
+
+	// Finishes a task, readying those that wait for it.
+	fn settle(&mut self, task: &Rc<Task>, result: Run<Value>) {
+		*task.state.borrow_mut() = match &result {
+			Ok(value) => TaskState::Done(value.clone()),
+			Err(thrown) => TaskState::Failed(thrown.clone())
+		};
+		for (awaited, suspended) in mem::take(&mut self.waiting) {
+			if Rc::ptr_eq(&awaited, task) {
+				self.ready.push_back((suspended, Some(result.clone())));
+			} else {
+				self.waiting.push((awaited, suspended));
+			}
+		}
+	}
+
+	// Finishes the tasks of timers that are up and reads that are done,
+	// first waiting for one when `block` is set and none is yet, but not past
+	// when the program has to stop. Whether there was any timer or read at
+	// all.
+	fn poll(&mut self, block: bool) -> bool {
+		if self.timers.is_empty() && self.reads.is_empty() {
+			return false;
+		}
+		let next = self.timers.iter().map(|(deadline, _)| *deadline).min();
+		let now = Instant::now();
+		if block && next.is_none_or(|deadline| deadline > now) {
+			// The VM keeps a sender, so this only stops for a read or the
+			// next timer
+			let read = match next.into_iter().chain(self.meter.deadline()).min() {
+				Some(deadline) => self.events.1.recv_timeout(deadline.saturating_duration_since(now)).ok(),
+				None => self.events.1.recv().ok()
+			};
+			if let Some(read) = read {
+				self.finish_read(read);
+			}
+		}
+		let now = Instant::now();
+		// Those up at once in the order they were set
+		while let Some(index) = self.timers.iter().position(|(deadline, _)| *deadline <= now) {
+			let (_, task) = self.timers.remove(index);
+			self.settle(&task, Ok(Value::Null));
+		}
+		while let Ok(read) = self.events.1.try_recv() {
+			self.finish_read(read);
+		}
+		true
+	}
+
+	fn finish_read(&mut self, (read, done): Read) {
+		let Some((task, thrown)) = self.reads.remove(&read) else {
+			return;
+		};
+		let result = done().map_err(|message| Thrown { value: Value::string(message), ..thrown });
+		self.settle(&task, result);
+	}
+
+	// Swaps the stacks of the code running for those of `context`.
+	fn switch(&mut self, context: &mut Context) {
+		mem::swap(&mut self.stack, &mut context.stack);
+		mem::swap(&mut self.slots, &mut context.slots);
+		mem::swap(&mut self.frames, &mut context.frames);
+		mem::swap(&mut self.cursors, &mut context.cursors);
+		mem::swap(&mut self.handlers, &mut context.handlers);
+		mem::swap(&mut self.caught, &mut context.caught);
+	}
+
+	// ---------------------------------------------------------------------
+	// The loop
+	// ---------------------------------------------------------------------
+
+	// Runs the frame on top until it returns. What is thrown in it, or in
+	// the calls it makes in the loop, goes to the innermost handler of those
+	// frames, or out of here when they have none.
+	fn run(&mut self) -> Run<Value> {
+		let base = self.frames.len() - 1;
+		self.loops += 1;
+		let result = loop {
+			match self.execute() {
+				Ok(value) => break Ok(value),
+				Err(thrown) => {
+					if let Err(thrown) = self.catch(thrown, base) {
+						break Err(thrown);
+					}
+				}
+			}
+		};
+		self.loops -= 1;
+		result
+	}
+
+	// Going over a limit unwinds past every handler.
+	fn catch(&mut self, thrown: Thrown, base: usize) -> Run<()> {
+		let handler = match thrown.limit {
+			Some(_) => None,
+			None => self.handlers.pop_if(|handler| handler.frame >= base)
+		};
+		let Some(handler) = handler else {
+			let frame = &self.frames[base];
+			self.stack.truncate(frame.stack);
+			self.slots.truncate(frame.slots);
+			self.cursors.truncate(frame.cursors);
+			self.handlers.truncate(frame.handlers);
+			self.caught.truncate(frame.caught);
+			self.frames.truncate(base);
+			return Err(thrown);
+		};
+		if let Some(above) = self.frames.get(handler.frame + 1) {
+			self.slots.truncate(above.slots);
+		}
+		self.frames.truncate(handler.frame + 1);
+		self.stack.truncate(handler.stack);
+		self.cursors.truncate(handler.cursors);
+		self.caught.truncate(handler.caught);
+		self.stack.push(thrown.value.clone());
+		self.caught.push(thrown);
+		self.frame_mut().ip = handler.target;
+		Ok(())
+	}
+
+	fn frame(&self) -> &Frame {
+		self.frames.last().expect("code runs in a frame")
+	}
+
+	fn frame_mut(&mut self) -> &mut Frame {
+		self.frames.last_mut().expect("code runs in a frame")
+	}
+
+	fn pop(&mut self) -> Value {
+		self.stack.pop().expect("instructions only pop what was pushed")
+	}
+
+	fn peek(&self) -> &Value {
+		self.stack.last().expect("instructions only look at what was pushed")
+	}
+
+	// The next operand of the instruction running, `size` bytes of it.
+	fn operand(&mut self, size: usize) -> usize {
+		let module = self.module;
+		let frame = self.frames.last_mut().expect("code runs in a frame");
+		let code = module.functions[frame.function as usize].code.as_deref().unwrap_or_default();
+		let bytes = &code[frame.ip..frame.ip + size];
+		frame.ip += size;
+		bytes.iter().rev().fold(0, |value, byte| value << 8 | *byte as usize)
+	}
+
+	fn u8(&mut self) -> usize {
+		self.operand(1)
+	}
+
+	fn u16(&mut self) -> usize {
+		self.operand(2)
+	}
+
+	fn u32(&mut self) -> usize {
+		self.operand(4)
+	}
+
+	fn jump(&mut self, target: usize) {
+		self.frame_mut().ip = target;
+	}
+
+	fn slot(&self, slot: usize) -> Value {
+		match &self.slots[self.frame().slots + slot] {
+			Slot::Value(value) => value.clone(),
+			Slot::Cell(cell) => cell.borrow().clone()
+		}
+	}
+
+	fn this_object(&self) -> Option<Rc<Object>> {
+		match &self.frame().this {
+			Some(Value::Object(object)) => Some(object.clone()),
+			_ => None
+		}
+	}
+
+	fn string_constant(&self, index: usize) -> &'a str {
+		match &self.module.constants[index] {
+			Constant::String(text) => text,
+			_ => ""
+		}
+	}
+
+	// Runs instructions until a frame called from Rust returns, or something
+	// is thrown.
+	fn execute(&mut self) -> Run<Value> {
+		let module = self.module;
+		// `Tail` for the call after it
+		let mut caller = Caller::Code;
+		loop {
+			if (gc::is_due() || self.meter.measure_due()) && self.loops == 1 {
+				let bytes = timings::time("gc", "", || self.collect());
+				self.meter.measured(bytes).map_err(|limit| self.exceeded(limit))?;
+			}
+			if self.meter.budget == 0 {
+				self.meter.check().map_err(|limit| self.exceeded(limit))?;
+			}
+			self.meter.budget -= 1;
+			if let Some(profiler) = self.profiler.take() {
+				profiler.check(self);
+				self.profiler = Some(profiler);
+			}
+			if self.debugger.is_some() {
+				self.debug();
+			}
+			let frame = self.frames.last_mut().expect("code runs in a frame");
+			let code = module.functions[frame.function as usize].code.as_deref().unwrap_or_default();
+			let op = Op::from_byte(code[frame.ip]).expect("compiled code has only known instructions");
+			frame.ip += 1;
+			match op {
+				Op::Null => self.stack.push(Value::Null),
+				Op::True => self.stack.push(Value::Bool(true)),
+				Op::False => self.stack.push(Value::Bool(false)),
+				Op::Constant => {
+					let constant = self.u16();
+					self.stack.push(self.constants[constant].clone());
+				}
+				Op::Pop => {
+					self.pop();
+				}
+				Op::Dup => self.stack.push(self.peek().clone()),
+
+				Op::GetLocal => {
+					let value = self.u16();
+					let value = self.slot(value);
+					self.stack.push(value);
+				}
+				Op::SetLocal => {
+					let index = self.frame().slots + self.u16();
+					let value = self.pop();
+					match &mut self.slots[index] {
+						Slot::Cell(cell) => *cell.borrow_mut() = value,
+						slot => *slot = Slot::Value(value)
+					}
+				}
+				Op::Box => {
+					let slot = self.u16();
+					let value = self.slot(slot);
+					let index = self.frame().slots + slot;
+					self.slots[index] = Slot::cell(value);
+				}
+				Op::GetGlobal => {
+					let global = self.u16();
+					let value = self.globals[global].clone();
+					self.stack.push(value);
+				}
+				Op::SetGlobal => {
+					let global = self.u16();
+					self.globals[global] = self.pop();
+				}
+				Op::This => self.stack.push(self.frame().this.clone().unwrap_or(Value::Null)),
+				Op::GetBacking => {
+					let name = self.u16();
+					let name = self.string_constant(name);
+					let value = self.this_object().and_then(|object| Some(object.fields.borrow()[object.class.field(name)?].clone()));
+					self.stack.push(value.unwrap_or(Value::Null));
+				}
+				Op::SetBacking => {
+					let name = self.u16();
+					let name = self.string_constant(name);
+					let value = self.pop();
+					if let Some(object) = self.this_object()
+						&& let Some(field) = object.class.field(name)
+					{
+						object.fields.borrow_mut()[field] = value;
+					}
+				}
+				Op::InitField => {
+					let field = self.u16();
+					let value = self.pop();
+					if let Some(object) = self.this_object() {
+						object.fields.borrow_mut()[field] = value;
+					}
+				}
+
+				Op::Add
+				| Op::Subtract
+				| Op::Multiply
+				| Op::Divide
+				| Op::Modulo
+				| Op::Equal
+				| Op::NotEqual
+				| Op::Less
+				| Op::LessEqual
+				| Op::Greater
+				| Op::GreaterEqual
+				| Op::BitAnd
+				| Op::BitOr
+				| Op::BitXor
+				| Op::ShiftLeft
+				| Op::ShiftRight
+				| Op::In => {
+					let right = self.pop();
+					let left = self.pop();
+					let value = self.binary(operator(op), left, right)?;
+					self.stack.push(value);
+				}
+				Op::Negate => {
+					let value = self.pop();
+					let value = ops::negate(value).map_err(|message| self.error(message))?;
+					self.stack.push(value);
+				}
+				Op::Not => {
+					let value = self.pop();
+					self.stack.push(Value::Bool(!value.truthy()));
+				}
+				Op::Typeof => {
+					let value = self.pop();
+					self.stack.push(Value::string(value.type_name()));
+				}
+				Op::NonNull => {
+					if self.peek().is_null() {
+						return Err(self.error("Used '!!' on a value that is null"));
+					}
+				}
+				Op::Increment | Op::Decrement => {
+					let value = self.pop();
+					let delta = if op == Op::Increment { 1 } else { -1 };
+					let value = ops::step(&value, delta).map_err(|message| self.error(message))?;
+					self.stack.push(value);
+				}
+				Op::ToFloat => {
+					if let Value::Int(value) = self.peek() {
+						let value = Value::Float(*value as f64);
+						*self.stack.last_mut().expect("instructions only look at what was pushed") = value;
+					}
+				}
+				Op::ToString => {
+					let value = self.pop();
+					let text = self.stringify(&value)?;
+					self.stack.push(Value::string(text));
+				}
+				Op::Cast => {
+					let ty = self.u16();
+					let name = self.u16();
+					let name = self.string_constant(name);
+					if !self.instance_of(self.peek(), ty) {
+						return Err(self.error(format!("Cannot use a '{}' as a '{}'", self.peek().type_name(), name)));
+					}
+				}
+				Op::Is => {
+					let ty = self.u16();
+					let value = self.pop();
+					self.stack.push(Value::Bool(self.instance_of(&value, ty)));
+				}
+
+				Op::Jump => {
+					let target = self.u32();
+					self.jump(target);
+				}
+				Op::JumpIfFalse | Op::JumpIfTrue => {
+					let target = self.u32();
+					if self.pop().truthy() == (op == Op::JumpIfTrue) {
+						self.jump(target);
+					}
+				}
+				Op::JumpIfNull | Op::JumpIfNotNull => {
+					let target = self.u32();
+					if self.peek().is_null() == (op == Op::JumpIfNull) {
+						self.jump(target);
+					}
+				}
+				Op::Unwrap => {
+					let target = self.u32();
+					match self.peek() {
+						Value::Tagged(tag, value) if tag.has_value() => {
+							let value = (**value).clone();
+							*self.stack.last_mut().unwrap() = value;
+							self.jump(target);
+						}
+						Value::Tagged(..) => {}
+						value => return Err(self.error(format!("'?' needs a Result or an Option, not a '{}'", value.type_name())))
+					}
+				}
+				Op::JumpIfGiven => {
+					let param = self.u16();
+					let target = self.u32();
+					if param < self.frame().given {
+						self.jump(target);
+					}
+				}
+
+				Op::Call => {
+					let argc = self.u8();
+					let callee = self.stack.remove(self.stack.len() - argc - 1);
+					self.call_value(callee, argc, mem::replace(&mut caller, Caller::Code))?;
+				}
+				Op::CallFunction => {
+					let function = self.u32() as u32;
+					let argc = self.u8();
+					self.enter(function, None, &[], argc, mem::replace(&mut caller, Caller::Code))?;
+				}
+				Op::CallMethod | Op::CallWith => {
+					let function = self.u32() as u32;
+					let argc = self.u8();
+					let receiver = self.stack.remove(self.stack.len() - argc - 1);
+					let function = if op == Op::CallMethod { self.dispatch(&receiver, function)? } else { function };
+					self.enter(function, Some(receiver), &[], argc, mem::replace(&mut caller, Caller::Code))?;
+				}
+				Op::Tail => caller = Caller::Tail,
+				Op::Invoke => {
+					let name = self.u16();
+					let name = self.string_constant(name);
+					let argc = self.u8();
+					let args = self.stack.split_off(self.stack.len() - argc);
+					let receiver = self.pop();
+					let value = self.invoke(receiver, name, args)?;
+					self.stack.push(value);
+				}
+				Op::SuperCall => {
+					let constructor = self.u32() as u32;
+					let argc = self.u8();
+					if constructor == NONE {
+						self.stack.truncate(self.stack.len() - argc);
+						self.stack.push(Value::Null);
+					} else {
+						let this = self.frame().this.clone();
+						self.enter(constructor, this, &[], argc, Caller::Code)?;
+					}
+				}
+				Op::New | Op::NewVariant => {
+					let class = self.u16();
+					let constructor = self.u32() as u32;
+					let variant = if op == Op::NewVariant { Some(self.u16()) } else { None };
+					let argc = self.u8();
+					let args = self.stack.split_off(self.stack.len() - argc);
+					let value = self.construct(class, constructor, args, variant)?;
+					self.stack.push(value);
+				}
+				Op::Variant => {
+					let class = self.u16();
+					let index = self.u16();
+					let value = self.variant(class, index)?;
+					self.stack.push(value);
+				}
+
+				Op::GetField => {
+					let name = self.u16();
+					let name = self.string_constant(name);
+					let receiver = self.pop();
+					let value = self.get(receiver, name)?;
+					self.stack.push(value);
+				}
+				Op::SetField => {
+					let name = self.u16();
+					let name = self.string_constant(name);
+					let value = self.pop();
+					let receiver = self.pop();
+					self.set(receiver, name, value.clone())?;
+					self.stack.push(value);
+				}
+				Op::GetProperty => {
+					let name = self.u16();
+					let name = self.string_constant(name);
+					let receiver = self.pop();
+					let value = ops::property(&receiver, name).map_err(|message| self.error(message))?;
+					self.stack.push(value);
+				}
+				Op::GetMethod | Op::Bind => {
+					let function = self.u32() as u32;
+					let receiver = self.pop();
+					let function = if op == Op::GetMethod { self.dispatch(&receiver, function)? } else { function };
+					self.stack.push(Value::Function(Rc::new(Closure { function: FnId(function), captures: Vec::new(), this: Some(receiver) })));
+				}
+				Op::GetIndex => {
+					let index = self.pop();
+					let object = self.pop();
+					let value = ops::index(&object, &index).map_err(|message| self.error(message))?;
+					self.stack.push(value);
+				}
+				Op::SetIndex => {
+					let value = self.pop();
+					let index = self.pop();
+					let object = self.pop();
+					ops::set_index(&object, &index, value.clone()).map_err(|message| self.error(message))?;
+					self.stack.push(value);
+				}
+				Op::Array => {
+					let count = self.u16();
+					let items = self.stack.split_off(self.stack.len() - count);
+					self.stack.push(Value::array(items));
+				}
+				Op::Map => {
+					let count = self.u16() * 2;
+					let mut map = Map::new();
+					for entry in self.stack.split_off(self.stack.len() - count).chunks(2) {
+						map.insert(entry[0].clone(), entry[1].clone());
+					}
+					self.stack.push(Value::map(map));
+				}
+				Op::Set => {
+					let count = self.u16();
+					let elements = self.stack.split_off(self.stack.len() - count);
+					self.stack.push(Value::set(elements));
+				}
+				Op::Range => {
+					let end = self.pop();
+					let start = self.pop();
+					let value = ops::range(start, end).map_err(|message| self.error(message))?;
+					self.stack.push(value);
+				}
+				Op::Function => {
+					let function = FnId(self.u32() as u32);
+					self.stack.push(Value::Function(Rc::new(Closure { function, captures: Vec::new(), this: None })));
+				}
+				Op::Closure => {
+					let function = self.u32();
+					let base = self.frame().slots;
+					let captures = module.functions[function].captures.iter().map(|&slot| self.slots[base + slot as usize].clone()).collect();
+					let this = self.frame().this.clone();
+					self.stack.push(Value::Function(Rc::new(Closure { function: FnId(function as u32), captures, this })));
+				}
+				Op::Builtin => {
+					let builtin = self.u8();
+					let builtin = Builtin::ALL[builtin];
+					self.stack.push(Value::Builtin(builtin));
+				}
+				Op::Native => {
+					let native = self.u16();
+					match &self.natives[native] {
+						Some(function) => self.stack.push(Value::Native(function.clone())),
+						None => return Err(self.error(native::missing(&module.natives[native])))
+					}
+				}
+
+				Op::Iterate => {
+					let entries = self.u8() == 1;
+					let value = self.pop();
+					let cursor = self.cursor(value, entries)?;
+					self.cursors.push(cursor);
+				}
+				Op::Next => {
+					let target = self.u32();
+					match self.advance()? {
+						Some(item) => self.stack.push(item),
+						None => self.jump(target)
+					}
+				}
+				Op::EndIterate => {
+					self.cursors.pop();
+				}
+				Op::Unpack => {
+					let count = self.u8();
+					let item = self.pop();
+					let parts = ops::unpack(&item, count).map_err(|message| self.error(message))?;
+					self.stack.extend(parts);
+				}
+
+				Op::Try => {
+					let target = self.u32();
+					self.handlers.push(Handler {
+						frame: self.frames.len() - 1,
+						target,
+						stack: self.stack.len(),
+						cursors: self.cursors.len(),
+						caught: self.caught.len()
+					});
+				}
+				Op::EndTry => {
+					self.handlers.pop();
+				}
+				Op::Caught => {
+					self.caught.pop();
+				}
+				Op::Rethrow => return Err(self.caught.pop().unwrap_or_else(|| self.error("Nothing was caught to throw again"))),
+				Op::Throw => {
+					let value = self.pop();
+					return Err(self.thrown(value, false));
+				}
+				Op::Count => {
+					let counter = self.u32();
+					self.counts[counter] += 1;
+				}
+				Op::Await => {
+					let value = self.pop();
+					let Value::Task(task) = value else {
+						self.stack.push(value);
+						continue;
+					};
+					let state = task.state.borrow().clone();
+					match state {
+						TaskState::Done(value) => self.stack.push(value),
+						TaskState::Failed(thrown) => return Err(thrown),
+						// Only an async function awaits, and it is the first
+						// call of its task, so returning here leaves the task
+						// as it is for the event loop to go on with
+						TaskState::Running => {
+							if self.task.is_none() || self.frames.len() != 1 || self.loops != self.base + 1 {
+								return Err(self.error("Cannot wait for a task here"));
+							}
+							self.awaiting = Some(task);
+							return Ok(Value::Null);
+						}
+					}
+				}
+				// Only a generator yields, and its call is the first of its
+				// stacks, so returning here leaves it where it stopped
+				Op::Yield => {
+					let value = self.pop();
+					if self.frames.len() != 1 || self.yielding != Some(self.loops) {
+						return Err(self.error("Cannot yield here"));
+					}
+					self.yielded = Some(value);
+					return Ok(Value::Null);
+				}
+				Op::Return => {
+					let value = self.pop();
+					let frame = self.frames.pop().expect("code runs in a frame");
+					self.stack.truncate(frame.stack);
+					self.slots.truncate(frame.slots);
+					self.cursors.truncate(frame.cursors);
+					self.handlers.truncate(frame.handlers);
+					self.caught.truncate(frame.caught);
+					if frame.exit {
+						return Ok(value);
+					}
+					self.stack.push(value);
+				}
+			}
+		}
+	}
+
+	// Empties what the program cannot reach any more: everything but what
+	// the globals, cached variants and tasks lead to, the running one's
+	// stacks and those of the others included. Gives roughly how many bytes
+	// what it can reach takes up.
+	fn collect(&mut self) -> usize {
+		let mut marker = Marker::new();
+		for value in self.globals.iter().chain(self.variants.values()) {
+			marker.value(value);
+		}
+		let mut running = Context::default();
+		self.switch(&mut running);
+		mark(&mut marker, &running);
+		self.switch(&mut running);
+		for (suspended, resume) in &self.ready {
+			marker.value(&Value::Task(suspended.task.clone()));
+			mark(&mut marker, &suspended.context);
+			match resume {
+				Some(Ok(value)) => marker.value(value),
+				Some(Err(thrown)) => marker.value(&thrown.value),
+				None => {}
+			}
+		}
+		for (awaited, suspended) in &self.waiting {
+			marker.value(&Value::Task(awaited.clone()));
+			marker.value(&Value::Task(suspended.task.clone()));
+			mark(&mut marker, &suspended.context);
+		}
+		for (channel, task) in &self.receivers {
+			marker.value(&Value::Channel(channel.clone()));
+			marker.value(&Value::Task(task.clone()));
+		}
+		self.generators.retain(|_, (generator, _)| generator.strong_count() > 0);
+		for (_, context) in self.generators.values() {
+			mark(&mut marker, context);
+		}
+		let timers = self.timers.iter().map(|(_, task)| task);
+		let reads = self.reads.values().map(|(task, _)| task);
+		for task in timers.chain(reads).chain(&self.task).chain(&self.awaiting).chain(&self.driving) {
+			marker.value(&Value::Task(task.clone()));
+		}
+		let bytes = marker.bytes();
+		gc::sweep(marker);
+		bytes
+	}
+
+	// ---------------------------------------------------------------------
+	// Values
+	// ---------------------------------------------------------------------
+
+	fn binary(&mut self, op: BinaryOp, left: Value, right: Value) -> Run<Value> {
+		match (op, left, right) {
+			(BinaryOp::Add, Value::String(left), right) => ops::concat(&left, &self.stringify(&right)?).map_err(|message| self.error(message)),
+			(BinaryOp::Add, left, Value::String(right)) => ops::concat(&self.stringify(&left)?, &right).map_err(|message| self.error(message)),
+			(op, left, right) => ops::binary(op, left, right).map_err(|message| self.error(message))
+		}
+	}
+
+	fn instance_of(&self, value: &Value, ty: usize) -> bool {
+		match &self.module.constants[ty] {
+			Constant::Type(ty) => test(value, ty),
+			_ => false
+		}
+	}
+
+	// What `value` looks like printed or added to a string, through its
+	// class's `toString()` when it has one.
+	fn stringify(&mut self, value: &Value) -> Run<String> {
+		ops::stringify(value, &mut |value| self.custom_string(value))
+	}
+
+	fn custom_string(&mut self, value: &Value) -> Run<Option<String>> {
+		Ok(match value {
+			Value::Object(object) => match object.class.method("toString", Some(0)) {
+				Some(method) => Some(match self.call_code(method.0, Some(value.clone()), Vec::new())? {
+					Value::String(text) => text.to_string(),
+					text => self.stringify(&text)?
+				}),
+				None => None
+			},
+			Value::Function(closure) => Some(format!("<fn {}>", self.module.functions[closure.function.0 as usize].name)),
+			_ => None
+		})
+	}
+
+	// Arrays, ranges, maps and strings are walked directly; objects through
+	// their `iterator()`, or for `of` their `entries()`.
+	fn cursor(&mut self, value: Value, entries: bool) -> Run<Cursor> {
+		match Cursor::of(value.clone(), entries).map_err(|message| self.error(message))? {
+			Some(cursor) => Ok(cursor),
+			None => {
+				let method = if entries { ENTRIES_METHOD } else { ITERATOR_METHOD };
+				Ok(Cursor::Iterator(self.invoke(value, method, Vec::new())?))
+			}
+		}
+	}
+
+	fn advance(&mut self) -> Run<Option<Value>> {
+		let Some(cursor) = self.cursors.last_mut() else {
+			return Err(self.error("Nothing is being iterated"));
+		};
+		let Cursor::Iterator(iterator) = cursor else {
+			return cursor.step().map_err(|message| self.error(message));
+		};
+		let iterator = iterator.clone();
+		if !self.invoke(iterator.clone(), HAS_NEXT_METHOD, Vec::new())?.truthy() {
+			return Ok(None);
+		}
+		Ok(Some(self.invoke(iterator, NEXT_METHOD, Vec::new())?))
+	}
+
+	// ---------------------------------------------------------------------
+	// Objects
+	// ---------------------------------------------------------------------
+
+	// `new C(...)`: an object with its fields set by the initializers of its
+	// class and those it inherits from, superclasses' first, then the
+	// constructor run on it, or the one it inherits that takes no arguments.
+	fn construct(&mut self, class: usize, constructor: u32, args: Vec<Value>, variant: Option<usize>) -> Run<Value> {
+		let module = self.module;
+		let header = &module.classes[class];
+		let receiver = Value::Object(Object::new(self.classes[class].clone(), vec![Value::Null; header.fields.len()], variant));
+		let mut chain = vec![class];
+		while let Some(superclass) = module.classes[*chain.last().expect("the chain starts with the class")].superclass {
+			chain.push(superclass as usize);
+		}
+		for &owner in chain.iter().rev() {
+			if let Some(init) = module.classes[owner].init {
+				let args = if header.positional && owner == class { args.clone() } else { Vec::new() };
+				self.call_code(init, Some(receiver.clone()), args)?;
+			}
+		}
+		let constructor = if constructor == NONE { header.inherited } else { Some(constructor) };
+		if let Some(constructor) = constructor {
+			let args = if header.constructors.contains(&constructor) { args } else { Vec::new() };
+			self.call_code(constructor, Some(receiver.clone()), args)?;
+		}
+		Ok(receiver)
+	}
+
+	// `receiver.name`, through the field's getter when it has one. Methods
+	// read this way come with `receiver` as `this`.
+	fn get(&mut self, receiver: Value, name: &str) -> Run<Value> {
+		let Value::Object(object) = &receiver else {
+			return ops::property(&receiver, name).map_err(|message| self.error(message));
+		};
+		let class = &self.module.classes[object.class.id.0 as usize];
+		if let Some((_, getter)) = class.getters.iter().find(|(field, _)| field == name) {
+			return self.call_code(*getter, Some(receiver), Vec::new());
+		}
+		if let Some(field) = object.class.field(name) {
+			return Ok(object.fields.borrow()[field].clone());
+		}
+		if let Some(method) = object.class.method(name, None) {
+			return Ok(Value::Function(Rc::new(Closure { function: method, captures: Vec::new(), this: Some(receiver.clone()) })));
+		}
+		Err(self.error(format!("'{}' has no member '{}'", receiver.type_name(), name)))
+	}
+
+	// `receiver.name = value`, through the field's setter when it has one.
+	fn set(&mut self, receiver: Value, name: &str, value: Value) -> Run<()> {
+		let Value::Object(object) = &receiver else {
+			return Err(self.error(format!("Cannot set '{}' of '{}'", name, receiver.type_name())));
+		};
+		let class = &self.module.classes[object.class.id.0 as usize];
+		if let Some((_, setter)) = class.setters.iter().find(|(field, _)| field == name) {
+			self.call_code(*setter, Some(receiver), vec![value])?;
+			return Ok(());
+		}
+		match object.class.field(name) {
+			Some(field) => object.fields.borrow_mut()[field] = value,
+			None => return Err(self.error(format!("'{}' has no field '{}'", receiver.type_name(), name)))
+		}
+		Ok(())
+	}
+
+	// An enum variant, made the first time it is used.
+	fn variant(&mut self, class: usize, index: usize) -> Run<Value> {
+		if let Some(value) = self.variants.get(&(class, index)) {
+			return Ok(value.clone());
+		}
+		let value = self.call_code(self.module.classes[class].variant_code[index], None, Vec::new())?;
+		self.variants.insert((class, index), value.clone());
+		Ok(value)
+	}
+}
+
+impl Drop for Vm<'_> {
+	fn drop(&mut self) {
+		gc::uninstall();
+	}
+}
+
+// Marks what the calls in progress on `context` can reach.
+fn mark(marker: &mut Marker, context: &Context) {
+	for value in &context.stack {
+		marker.value(value);
+	}
+	for slot in &context.slots {
+		marker.slot(slot);
+	}
+	for this in context.frames.iter().filter_map(|frame| frame.this.as_ref()) {
+		marker.value(this);
+	}
+	for thrown in &context.caught {
+		marker.value(&thrown.value);
+	}
+	for walking in context.cursors.iter().filter_map(Cursor::walking) {
+		marker.value(&walking);
+	}
+}
+
+// Whether `value` has type `ty`, for `is`, `catch`, `case` and casts.
+fn test(value: &Value, ty: &TypeTest) -> bool {
+	match ty {
+		TypeTest::Any => true,
+		TypeTest::Never => false,
+		TypeTest::Null => value.is_null(),
+		TypeTest::Nullable(inner) => value.is_null() || test(value, inner),
+		TypeTest::Union(members) => members.iter().any(|member| test(value, member)),
+		TypeTest::Int => matches!(value, Value::Int(_)),
+		TypeTest::Float => matches!(value, Value::Float(_)),
+		TypeTest::Bool => matches!(value, Value::Bool(_)),
+		TypeTest::String => matches!(value, Value::String(_)),
+		TypeTest::Range => matches!(value, Value::Range(..)),
+		TypeTest::Array => matches!(value, Value::Array(_)),
+		TypeTest::Map => matches!(value, Value::Map(_)),
+		TypeTest::Set => matches!(value, Value::Set(_)),
+		TypeTest::Iterator => matches!(value, Value::Iterator(_)),
+		TypeTest::Result => matches!(value, Value::Tagged(Tag::Ok | Tag::Err, _)),
+		TypeTest::Option => matches!(value, Value::Tagged(Tag::Some | Tag::None, _)),
+		TypeTest::Random => matches!(value, Value::Random(_)),
+		TypeTest::Channel => matches!(value, Value::Channel(_)),
+		TypeTest::Pointer => matches!(value, Value::Pointer(_)),
+		TypeTest::Function => matches!(value, Value::Function(_) | Value::Native(_) | Value::Builtin(_)),
+		TypeTest::Class(id) => matches!(value, Value::Object(object) if object.class.is(TypeId(*id)))
+	}
+}
+
+fn operator(op: Op) -> BinaryOp {
+	match op {
+		Op::Add => BinaryOp::Add,
+		Op::Subtract => BinaryOp::Subtract,
+		Op::Multiply => BinaryOp::Multiply,
+		Op::Divide => BinaryOp::Divide,
+		Op::Modulo => BinaryOp::Modulo,
+		Op::Equal => BinaryOp::Equal,
+		Op::NotEqual => BinaryOp::NotEqual,
+		Op::Less => BinaryOp::Less,
+		Op::LessEqual => BinaryOp::LessEqual,
+		Op::Greater => BinaryOp::Greater,
+		Op::GreaterEqual => BinaryOp::GreaterEqual,
+		Op::BitAnd => BinaryOp::BitAnd,
+		Op::BitOr => BinaryOp::BitOr,
+		Op::BitXor => BinaryOp::BitXor,
+		Op::ShiftLeft => BinaryOp::ShiftLeft,
+		Op::ShiftRight => BinaryOp::ShiftRight,
+		Op::In => BinaryOp::In,
+		op => unreachable!("{:?} is not a binary operator", op)
+	}
+}
@@ -0,0 +1,827 @@
+use std::fmt;
+
+use crate::diagnostics::lints;
+
+// What each diagnostic code means, for `--explain`. Codes are stable: once a
+// kind of problem has one it keeps it, and a code that is no longer reported
+// is not given to anything else. The first digit groups them: E00 are from
+// the lexer, E01 the parser, E02 names and imports, E03 declarations, E04
+// types, E05 control flow and E06 annotations; W are warnings.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Explanation {
+	pub code: &'static str,
+	pub title: &'static str,
+	// What the rule is and how to fix code that breaks it
+	pub text: &'static str,
+	// A program that gets the diagnostic. Examples of several files start
+	// each with a `// file: name.gl` line.
+	pub example: &'static str
+}
+
+pub fn explain(code: &str) -> Option<&'static Explanation> {
+	CATALOG.iter().find(|explanation| explanation.code.eq_ignore_ascii_case(code))
+}
+
+impl fmt::Display for Explanation {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "{}: {}\n", self.code, self.title)?;
+		writeln!(f, "{}\n", self.text)?;
+		if let Some(lint) = lints::LINTS.iter().find(|lint| lint.code == self.code) {
+			writeln!(f, "This warning is the '{}' lint. Turn it off with '@Suppress(\"{}\")' or '-A {}'.\n", lint.name, lint.name, lint.name)?;
+		}
+		writeln!(f, "For example:\n")?;
+		for line in self.example.lines() {
+			if line.is_empty() { writeln!(f)? } else { writeln!(f, "    {}", line)? }
+		}
+		Ok(())
+	}
+}
+
+pub const CATALOG: &[Explanation] = &[
+	Explanation {
+		code: "E0001",
+		title: "Unexpected character",
+		text: "The source has a character that is not part of any token, outside of a string or comment.",
+		example: r#"val total = 3 # 4"#
+	},
+	Explanation {
+		code: "E0002",
+		title: "Unterminated string",
+		text: "A string literal, or an interpolation `${...}` inside one, is still open at the end of the \
+			line or file. Close it with `\"` or `}`; strings cannot span lines.",
+		example: r#"val greeting = "hello
+fn main() {}"#
+	},
+	Explanation {
+		code: "E0003",
+		title: "Unterminated block comment",
+		text: "A `/*` comment is never closed with `*/`, so it runs to the end of the file.",
+		example: r#"/* a comment
+fn main() {}"#
+	},
+	Explanation {
+		code: "E0100",
+		title: "Unexpected token",
+		text: "The parser found something other than what the grammar allows at this point, like a \
+			missing closing parenthesis or a keyword where a name should be. The message says what it \
+			expected.",
+		example: r#"fn main() {
+	val x = (1 + 2
+}"#
+	},
+	Explanation {
+		code: "E0101",
+		title: "Type parameters declared twice",
+		text: "A generic function declares its type parameters before its name, as in \
+			`fn <T> first(xs: T[]): T`. They cannot also follow the name.",
+		example: r#"fn <T> first<U>(xs: T[]): T = xs[0]"#
+	},
+	Explanation {
+		code: "E0102",
+		title: "Invalid accessor",
+		text: "A field has at most one getter and one setter. A getter takes no parameters, a setter \
+			takes one and names it when it has a body, and only `mut` fields can have a setter.",
+		example: r#"class Temperature {
+	val celsius: Float = 0.0
+		set(value) { field = value }
+}"#
+	},
+	Explanation {
+		code: "E0103",
+		title: "Const without a value",
+		text: "A `const` is worked out when the program is compiled, so it needs its value where it is \
+			declared.",
+		example: r#"const LIMIT: Int"#
+	},
+	Explanation {
+		code: "E0104",
+		title: "Label on something other than a loop",
+		text: "Only loops can be labeled, since labels are only used by `break` and `continue`.",
+		example: r#"fn main() {
+	outer: println("hi")
+}"#
+	},
+	Explanation {
+		code: "E0105",
+		title: "Destructuring loop without 'of'",
+		text: "`for (x in xs)` loops over the values of a collection. To take a pair apart, like the \
+			keys and values of a map, loop with `of`: `for (key, value) of map`.",
+		example: r#"fn main() {
+	val ages = ["ann": 31]
+	for (name, age) in ages {}
+}"#
+	},
+	Explanation {
+		code: "E0106",
+		title: "Duplicate default case",
+		text: "A switch can have only one `default` case, since only one of them could ever run.",
+		example: r#"fn describe(n: Int): String {
+	switch (n) {
+		case 0: return "none"
+		default: return "some"
+		default: return "many"
+	}
+}"#
+	},
+	Explanation {
+		code: "E0107",
+		title: "Invalid assignment target",
+		text: "Only variables, fields and indexed elements can be assigned to or incremented. Names of \
+			functions and types, and other expressions like calls, cannot.",
+		example: r#"fn count(): Int = 1
+fn main() {
+	count() = 2
+}"#
+	},
+	Explanation {
+		code: "E0108",
+		title: "Chained range",
+		text: "Range operators do not chain, since `a..b..c` has no single meaning. Add parentheses or \
+			split the range up.",
+		example: r#"val r = 0..5..10"#
+	},
+	Explanation {
+		code: "E0109",
+		title: "'if' value without 'else'",
+		text: "An `if` used as a value has to have a value whichever way the condition goes, so it \
+			needs an `else` branch.",
+		example: r#"fn sign(n: Int): Int {
+	val s = if (n < 0) -1
+	return s
+}"#
+	},
+	Explanation {
+		code: "E0110",
+		title: "Type already nullable",
+		text: "`?` makes a type nullable, and a nullable type cannot be made nullable again. Use a \
+			single `?`.",
+		example: r#"val name: String? ? = null"#
+	},
+	Explanation {
+		code: "E0111",
+		title: "Nested too deeply",
+		text: "The parser gives up on expressions and blocks nested more than 200 deep, rather than \
+			running out of stack. Break the code up with variables or functions.",
+		example: r#"val x = ((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((1"#
+	},
+	Explanation {
+		code: "E0112",
+		title: "Number literal out of range",
+		text: "Integer literals have to fit in an `Int`, a signed 64-bit number, and float literals in \
+			a `Float`.",
+		example: r#"val big = 99999999999999999999"#
+	},
+	Explanation {
+		code: "E0113",
+		title: "Unknown escape sequence",
+		text: "Strings can use the escapes `\\n`, `\\t`, `\\r`, `\\0`, `\\\\`, `\\\"`, `\\'` and `\\$`. \
+			A backslash before anything else is an error; write `\\\\` for a backslash itself.",
+		example: r#"val path = "C:\users""#
+	},
+	Explanation {
+		code: "E0114",
+		title: "Missing ';' between statements",
+		text: "Statements on the same line have to be separated by `;`. A line break ends a \
+			statement too, so putting the second one on its own line works as well. \
+			`dotfun fix` adds the `;`.",
+		example: r#"val a = 1 val b = 2"#
+	},
+	Explanation {
+		code: "E0200",
+		title: "Cannot find a name",
+		text: "The name is not declared in this scope, imported, or built in. Check the spelling, \
+			declare it before using it in a block, or import it from the package that has it.",
+		example: r#"fn main() {
+	val total = 1
+	println(totl)
+}"#
+	},
+	Explanation {
+		code: "E0201",
+		title: "Unknown type",
+		text: "A type annotation names something that is not a type: a misspelled or missing type, or \
+			a function or value.",
+		example: r#"fn area(r: Flaot): Float = r * r"#
+	},
+	Explanation {
+		code: "E0202",
+		title: "Cannot find package",
+		text: "An import names a package that none of the compiled files declare with `package`.",
+		example: r#"import geometry.Circle"#
+	},
+	Explanation {
+		code: "E0203",
+		title: "Package has no such declaration",
+		text: "The package exists but declares nothing by this name, or nothing it lets other \
+			packages import.",
+		example: r#"// file: shapes.gl
+package shapes
+
+class Circle {}
+
+// file: main.gl
+import shapes.Circel"#
+	},
+	Explanation {
+		code: "E0204",
+		title: "Conflicting import",
+		text: "An import brings in a name that the file already declares or imports from somewhere \
+			else. Import it under another name with `as`.",
+		example: r#"// file: shapes.gl
+package shapes
+
+class Circle {}
+
+// file: main.gl
+import shapes.Circle
+
+class Circle {}"#
+	},
+	Explanation {
+		code: "E0205",
+		title: "Ambiguous name",
+		text: "Two wildcard imports both bring in the name, so it is not clear which one is meant. \
+			Import the one you want by name.",
+		example: r#"// file: a.gl
+package a
+
+fn helper(): Int = 1
+
+// file: b.gl
+package b
+
+fn helper(): Int = 2
+
+// file: main.gl
+import a.*
+import b.*
+
+val x = helper()"#
+	},
+	Explanation {
+		code: "E0206",
+		title: "Misplaced package or import",
+		text: "A file declares its package at most once, and package declarations and imports have to \
+			be at the top level of the file, not inside functions or classes.",
+		example: r#"package one
+package two"#
+	},
+	Explanation {
+		code: "E0207",
+		title: "Import cycle",
+		text: "Packages import each other in a circle, so none of them can be initialized first. Move \
+			what they share into a package of its own.",
+		example: r#"// file: a.gl
+package a
+
+import b.second
+
+fn first(): Int = 1
+
+// file: b.gl
+package b
+
+import a.first
+
+fn second(): Int = 2"#
+	},
+	Explanation {
+		code: "E0208",
+		title: "Not visible",
+		text: "A `private` declaration can only be used in its own file, or its own class for \
+			members, and a `protected` member only in its class and the classes that extend it.",
+		example: r#"class Account {
+	private mut balance: Int = 0
+}
+
+fn main() {
+	Account().balance = 5
+}"#
+	},
+	Explanation {
+		code: "E0209",
+		title: "Duplicate declaration",
+		text: "The same name is declared twice in one scope. Functions and methods can share a name \
+			only when their parameters differ.",
+		example: r#"class Point {
+	val x: Int = 0
+	val x: Int = 1
+}"#
+	},
+	Explanation {
+		code: "E0210",
+		title: "Not a value",
+		text: "The name is a type, a package or an extension function, none of which can be used as \
+			a value on its own. Construct the type, or use a member of the package.",
+		example: r#"class Point {}
+
+val p = Point"#
+	},
+	Explanation {
+		code: "E0211",
+		title: "Unknown member",
+		text: "The type has no field, method or enum variant by this name, and no extension function \
+			adds one.",
+		example: r#"enum Color { RED, GREEN }
+
+val c = Color.BLUE"#
+	},
+	Explanation {
+		code: "E0212",
+		title: "Package named like the standard library",
+		text: "The packages in `std` are the standard library's, which every program can import, so no \
+			other package can be named `std` or be in it.",
+		example: r#"package std.json
+
+fn parse(text: String): Any = text"#
+	},
+	Explanation {
+		code: "E0300",
+		title: "Modifier not allowed here",
+		text: "The modifier does not apply to this kind of declaration: only classes and interfaces \
+			can be `sealed`, and only members can be `protected` or `static`.",
+		example: r#"sealed fn helper() {}"#
+	},
+	Explanation {
+		code: "E0301",
+		title: "Extending a sealed type from another package",
+		text: "The types that extend a `sealed` class or interface all have to be in its package, so a \
+			switch over them can know it has covered every one.",
+		example: r#"// file: shapes.gl
+package shapes
+
+sealed interface Shape {}
+
+// file: main.gl
+import shapes.Shape
+
+class Hexagon : Shape {}"#
+	},
+	Explanation {
+		code: "E0302",
+		title: "Invalid supertype",
+		text: "A class extends at most one class, plus any number of interfaces, and an interface \
+			extends only interfaces. Built-in types cannot be extended.",
+		example: r#"class A {}
+class B {}
+class C : A, B {}"#
+	},
+	Explanation {
+		code: "E0303",
+		title: "Interface with state",
+		text: "Interfaces describe behavior, so they cannot store values in fields or have \
+			constructors. Declare the field without a value, or use an abstract class.",
+		example: r#"interface Named {
+	val name: String = "anonymous"
+}"#
+	},
+	Explanation {
+		code: "E0304",
+		title: "Override marking",
+		text: "A method that replaces an inherited one has to be marked `override`, and a method \
+			marked `override` has to replace one. Methods cannot override fields.",
+		example: r#"class Animal {
+	fn sound(): String = "..."
+}
+
+class Dog : Animal {
+	fn sound(): String = "woof"
+}"#
+	},
+	Explanation {
+		code: "E0305",
+		title: "Incompatible override",
+		text: "An override has to be usable wherever the method it overrides is: the same parameter \
+			types, a return type that fits, the same visibility or wider, and `async` only if the \
+			original is.",
+		example: r#"class Animal {
+	fn sound(): String = "..."
+}
+
+class Dog : Animal {
+	override fn sound(): Int = 1
+}"#
+	},
+	Explanation {
+		code: "E0306",
+		title: "Abstract method in a concrete class",
+		text: "A method without a body can only be declared in an interface or an `abstract` class. \
+			Give it a body, or mark the class `abstract`.",
+		example: r#"class Shape {
+	abstract fn area(): Float
+}"#
+	},
+	Explanation {
+		code: "E0307",
+		title: "Missing implementation",
+		text: "A class that is not `abstract` has to implement every abstract method and property it \
+			inherits, with the type the interface or class declares.",
+		example: r#"interface Shape {
+	fn area(): Float
+}
+
+class Square : Shape {}"#
+	},
+	Explanation {
+		code: "E0308",
+		title: "Conflicting inherited bodies",
+		text: "Two supertypes both give the method a body, so it is not clear which one the class \
+			should use. Override it and choose.",
+		example: r#"interface Greeter {
+	fn hello(): String = "hi"
+}
+
+interface Welcomer {
+	fn hello(): String = "welcome"
+}
+
+class Host : Greeter, Welcomer {}"#
+	},
+	Explanation {
+		code: "E0309",
+		title: "Cannot instantiate",
+		text: "Only concrete classes can be constructed. Interfaces and `abstract` classes need a \
+			class that extends them, and enums only have their variants.",
+		example: r#"abstract class Shape {}
+
+val s = Shape()"#
+	},
+	Explanation {
+		code: "E0310",
+		title: "Const not known at compile time",
+		text: "The value of a `const` has to be worked out when the program is compiled, so it can \
+			only use literals, operators and other consts. Use `val` for anything else.",
+		example: r#"fn seed(): Int = 4
+const START = seed()"#
+	},
+	Explanation {
+		code: "E0311",
+		title: "Initializer refers to itself",
+		text: "A top-level value or const is used while it is being initialized, directly or through \
+			others, so it has no value yet.",
+		example: r#"const A = B + 1
+const B = A"#
+	},
+	Explanation {
+		code: "E0312",
+		title: "Invalid extern function",
+		text: "An `extern` function is a C function in the shared library it names, so it has no \
+			body, is not generic or a method, and only takes and returns what C can: Int, Float, \
+			Bool, String and String?, or Void as the result.",
+		example: r#"extern "libc.so.6" fn labs(n: Int): Int = n"#
+	},
+	Explanation {
+		code: "E0313",
+		title: "Invalid static member",
+		text: "A `static` member belongs to its class rather than to its objects, and is used as \
+			`Class.name`. Static fields are set as the top-level code gets to the class, in the \
+			order they are declared, so each needs an initializer, which cannot read those declared \
+			after it, and only top-level types can have them. Static functions have a body, as \
+			nothing overrides them.",
+		example: r#"class Config {
+	static val path = directory + "/config"
+	static val directory = "/etc"
+}"#
+	},
+	Explanation {
+		code: "E0400",
+		title: "Type mismatch",
+		text: "The value has a different type from the one expected here, like a `String` where an \
+			`Int` is declared, or a condition that is not a `Bool`.",
+		example: r#"val count: Int = "three""#
+	},
+	Explanation {
+		code: "E0401",
+		title: "Operator not applicable",
+		text: "The operator is not defined for these types. Convert one of the operands, or give the \
+			class an operator method.",
+		example: r#"val x = true - 1"#
+	},
+	Explanation {
+		code: "E0402",
+		title: "Not callable, indexable or iterable",
+		text: "Only functions can be called, only arrays, maps and strings indexed, and only \
+			collections and ranges looped over.",
+		example: r#"fn main() {
+	val n = 3
+	n()
+}"#
+	},
+	Explanation {
+		code: "E0403",
+		title: "Wrong number of arguments",
+		text: "The call gives a different number of arguments from the number of parameters the \
+			function or constructor has.",
+		example: r#"fn add(a: Int, b: Int): Int = a + b
+
+val x = add(1)"#
+	},
+	Explanation {
+		code: "E0404",
+		title: "No matching overload",
+		text: "The function has several overloads, and none of them takes arguments of these types.",
+		example: r#"fn show(n: Int): String = "int"
+fn show(b: Bool): String = "bool"
+
+val s = show("text")"#
+	},
+	Explanation {
+		code: "E0405",
+		title: "Cannot infer a type argument",
+		text: "The type parameter could not be worked out from the arguments, or they make it two \
+			different types. Declare the type of the result, or pass arguments of one type.",
+		example: r#"fn <T> pick(a: T, b: T): T = a
+
+val x = pick(1, "one")"#
+	},
+	Explanation {
+		code: "E0406",
+		title: "Type bound not met",
+		text: "The type argument does not extend or implement the bound its type parameter declares.",
+		example: r#"interface Shape {
+	fn area(): Float
+}
+
+fn <T : Shape> total(shapes: T[]): Float = 0.0
+
+val x = total([1, 2])"#
+	},
+	Explanation {
+		code: "E0407",
+		title: "Wrong number of type arguments",
+		text: "A generic type or alias is given a different number of type arguments from the number \
+			of type parameters it has, or a type without any is given some.",
+		example: r#"class Box<T> {}
+
+val b: Box<Int, String>? = null"#
+	},
+	Explanation {
+		code: "E0408",
+		title: "Recursive type alias",
+		text: "A type alias refers to itself, directly or through other aliases, so it never ends. \
+			Use a class for recursive data.",
+		example: r#"type Left = Right
+type Right = Left
+
+val x: Left? = null"#
+	},
+	Explanation {
+		code: "E0409",
+		title: "Possibly null",
+		text: "The value has a nullable type, and the operation needs a value. Check it against \
+			`null` first, or use `?.`, `??` or `!!`.",
+		example: r#"fn length(s: String?): Int = s.length"#
+	},
+	Explanation {
+		code: "E0410",
+		title: "Void in a union",
+		text: "`Void` means there is no value, so it cannot be one of the types a value might have. \
+			Use a nullable type for something that might be missing.",
+		example: r#"fn f(x: Int | Void) {}"#
+	},
+	Explanation {
+		code: "E0411",
+		title: "Impossible case",
+		text: "A switch case can never match a value of the type being switched on, so it never \
+			runs.",
+		example: r#"class Cat {}
+class Dog {}
+
+fn name(pet: Cat): String {
+	switch (pet) {
+		case Dog: return "dog"
+		default: return "cat"
+	}
+}"#
+	},
+	Explanation {
+		code: "E0412",
+		title: "Constant arithmetic error",
+		text: "Arithmetic on constants would divide by zero or overflow, so it would fail every time \
+			the program runs.",
+		example: r#"val x = 10 / 0"#
+	},
+	Explanation {
+		code: "E0500",
+		title: "Invalid return",
+		text: "`return` is only allowed in functions, returns a value only from functions that have a \
+			return type, and has to return one from those.",
+		example: r#"fn count(): Int {
+	return
+}"#
+	},
+	Explanation {
+		code: "E0501",
+		title: "Invalid break or continue",
+		text: "`break` and `continue` only work inside loops, and a label they name has to be on a \
+			loop around them. Nested loops need different labels.",
+		example: r#"fn main() {
+	break
+}"#
+	},
+	Explanation {
+		code: "E0502",
+		title: "'this' or 'super' outside a class",
+		text: "`this` is only available in classes and extension functions, but not in their \
+			`static` members, and `super` in classes that extend another. `super(...)` can only be \
+			called from a constructor.",
+		example: r#"fn main() {
+	println(this)
+}"#
+	},
+	Explanation {
+		code: "E0503",
+		title: "'await' outside an async function",
+		text: "Only `async` functions can wait for a result. Mark the function `async`, or keep the \
+			result and wait for it somewhere that is.",
+		example: r#"async fn fetch(): Int = 1
+
+fn main() {
+	val x = await fetch()
+}"#
+	},
+	Explanation {
+		code: "E0504",
+		title: "Switch cases",
+		text: "A switch over an enum, a sealed type or a `Bool` has to handle every value or have a \
+			`default`. A case already handled, and a `default` when every value is, never runs.",
+		example: r#"enum Color { RED, GREEN, BLUE }
+
+fn name(c: Color): String {
+	switch (c) {
+		case Color.RED: return "red"
+		case Color.GREEN: return "green"
+	}
+	return ""
+}"#
+	},
+	Explanation {
+		code: "E0505",
+		title: "Missing return",
+		text: "The function has a return type, and some way through its body ends without returning \
+			a value.",
+		example: r#"fn sign(n: Int): Int {
+	if (n < 0) {
+		return -1
+	}
+}"#
+	},
+	Explanation {
+		code: "E0506",
+		title: "Unreachable code",
+		text: "The statement comes after a `return`, `throw`, `break` or `continue` that always runs, \
+			so it never does.",
+		example: r#"fn one(): Int {
+	return 1
+	println("done")
+}"#
+	},
+	Explanation {
+		code: "E0507",
+		title: "Used before assigned",
+		text: "A variable declared without a value is read on a path where nothing has been assigned \
+			to it yet.",
+		example: r#"fn main() {
+	val x: Int
+	println(x)
+}"#
+	},
+	Explanation {
+		code: "E0508",
+		title: "Assignment to an immutable",
+		text: "A `val` is assigned only once and a `const` never. Declare it `mut` to change it.",
+		example: r#"fn main() {
+	val n = 1
+	n = 2
+}"#
+	},
+	Explanation {
+		code: "E0509",
+		title: "Constructor does not initialize",
+		text: "A constructor has to assign every field without a value, and call `super(...)` once \
+			when the superclass needs arguments, before it uses `this`.",
+		example: r#"class Point {
+	val x: Int
+	constructor() {}
+}"#
+	},
+	Explanation {
+		code: "E0510",
+		title: "Invalid '?'",
+		text: "`?` takes the value out of a `Result` or an `Option`, and returns an `Err` or `None` \
+			from the function it is in. So it only works on those, in a function that returns the \
+			same kind, whose errors can hold the ones it returns.",
+		example: r#"fn half(n: Int): Option<Int> = if (n % 2 == 0) Some(n / 2) else None
+
+fn quarter(n: Int): Int = half(half(n)?)?"#
+	},
+	Explanation {
+		code: "E0511",
+		title: "Invalid defer",
+		text: "`defer` runs a statement when the block it is in is left, so it only works in a block, \
+			and what it runs cannot leave that block again with `return`, `?`, or a `break` or \
+			`continue` of a loop around it.",
+		example: r#"fn first(lines: String[]): String {
+	defer {
+		return ""
+	}
+	return lines[0]
+}"#
+	},
+	Explanation {
+		code: "E0512",
+		title: "Invalid yield",
+		text: "A function that uses `yield` is a generator: calling it makes an `Iterator` of what it \
+			yields, running a `yield` at a time as the iterator is gone through. So `yield` only works \
+			in a function declared to return an `Iterator` that is not `async`, and `return` in it \
+			ends the iterator without a value.",
+		example: r#"fn countdown(from: Int): Int[] {
+	for n in from..0 {
+		yield n
+	}
+}"#
+	},
+	Explanation {
+		code: "E0600",
+		title: "Annotation arguments must be literals",
+		text: "Annotations are read when the program is compiled, so their arguments can only be \
+			literals.",
+		example: r#"val reason = "old"
+
+@Deprecated(reason)
+fn legacy() {}"#
+	},
+	Explanation {
+		code: "E0601",
+		title: "Invalid @Test",
+		text: "Tests are functions that take no parameters, have no type parameters and return \
+			nothing.",
+		example: r#"@Test
+fn adds(a: Int) {}"#
+	},
+	Explanation {
+		code: "W0001",
+		title: "Unused",
+		text: "A variable, parameter or private declaration is never used. Remove it, or start its \
+			name with `_` to show it is unused on purpose.",
+		example: r#"fn main() {
+	val unused = 1
+}"#
+	},
+	Explanation {
+		code: "W0002",
+		title: "Unused import",
+		text: "Nothing the import brings in is used in the file. Remove it.",
+		example: r#"// file: shapes.gl
+package shapes
+
+class Circle {}
+
+// file: main.gl
+import shapes.Circle
+
+fn main() {}"#
+	},
+	Explanation {
+		code: "W0003",
+		title: "Dropped async result",
+		text: "Calling an `async` function, or a builtin like `sleep`, starts it, and its result is \
+			lost unless it is kept or awaited, along with any error it throws.",
+		example: r#"async fn save() {}
+
+async fn main() {
+	save()
+}"#
+	},
+	Explanation {
+		code: "W0004",
+		title: "Useless await",
+		text: "`await` only waits for the result of an `async` call. Awaiting anything else does \
+			nothing.",
+		example: r#"fn one(): Int = 1
+
+async fn main() {
+	val x = await one()
+}"#
+	},
+	Explanation {
+		code: "W0005",
+		title: "Deprecated",
+		text: "The declaration is marked `@Deprecated`, which usually says what to use instead. Add \
+			`@Suppress(\"deprecation\")` to keep using it without the warning.",
+		example: r#"@Deprecated("use add")
+fn plus(a: Int, b: Int): Int = a + b
+
+val x = plus(1, 2)"#
+	},
+	Explanation {
+		code: "W0006",
+		title: "Unknown lint",
+		text: "A name given to `@Suppress` is not the name of a lint, so it turns nothing off. \
+			`warnings` turns off every lint.",
+		example: r#"@Suppress("unusd")
+fn main() {
+	val x = 1
+}"#
+	}
+];
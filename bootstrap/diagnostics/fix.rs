@@ -0,0 +1,57 @@
+use crate::diagnostics::source_map::SourceMap;
+use crate::diagnostics::{Applicability, Diagnostic, Edit};
+
+// A file with fixes made to it.
+#[derive(Debug)]
+pub struct Fixed {
+	pub file: usize,
+	pub text: String,
+	// How many suggestions were made
+	pub fixes: usize
+}
+
+// Makes the safe suggestions of `diagnostics`, for `dotfun fix`. A suggestion
+// is made whole or not at all, and one with an edit touching where an
+// earlier one edits is left out, so fixes never step on each other; checking
+// the result again finds what is left. Only files that change are returned.
+pub fn fix(diagnostics: &[Diagnostic], sources: &SourceMap) -> Vec<Fixed> {
+	let mut accepted: Vec<&Edit> = Vec::new();
+	// Suggestions editing several files count toward the first
+	let mut counted: Vec<usize> = Vec::new();
+	let safe = diagnostics.iter().flat_map(|diagnostic| &diagnostic.suggestions).filter(|suggestion| suggestion.applicability == Applicability::Safe);
+	for suggestion in safe {
+		let fits = suggestion.edits.iter().all(|edit| {
+			sources.get(edit.file).is_some_and(|source| edit.span.end <= source.text.len())
+				&& !accepted.iter().any(|other| other.file == edit.file && touches(other, edit))
+		});
+		if let Some(first) = suggestion.edits.first()
+			&& fits
+		{
+			accepted.extend(&suggestion.edits);
+			counted.push(first.file);
+		}
+	}
+
+	let mut files: Vec<usize> = accepted.iter().map(|edit| edit.file).collect();
+	files.sort();
+	files.dedup();
+	files
+		.into_iter()
+		.map(|file| {
+			let mut edits: Vec<&Edit> = accepted.iter().copied().filter(|edit| edit.file == file).collect();
+			// From the end, so earlier offsets stay where they are
+			edits.sort_by_key(|edit| std::cmp::Reverse(edit.span.start));
+			let mut text = sources.get(file).unwrap().text.clone();
+			for edit in edits {
+				text.replace_range(edit.span.start..edit.span.end, &edit.replacement);
+			}
+			let fixes = counted.iter().filter(|counted| **counted == file).count();
+			Fixed { file, text, fixes }
+		})
+		.collect()
+}
+
+// Whether two edits overlap or meet, in which case which goes first matters.
+fn touches(a: &Edit, b: &Edit) -> bool {
+	a.span.start <= b.span.end && b.span.start <= a.span.end
+}
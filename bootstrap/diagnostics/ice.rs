@@ -0,0 +1,167 @@
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe, PanicHookInfo};
+use std::sync::Once;
+
+// Where to report compiler bugs.
+pub const ISSUES: &str = "https://github.com/dotfun-lang/dotFun/issues";
+
+// Backtraces are cut down to this many frames of the compiler's own code.
+const FRAMES: usize = 12;
+
+// What the compiler was doing, for telling which part of it broke.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+	Lexing,
+	Parsing,
+	Checking,
+	Compiling,
+	Fixing,
+	Formatting,
+	Running
+}
+
+impl fmt::Display for Phase {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Phase::Lexing => write!(f, "lexing"),
+			Phase::Parsing => write!(f, "parsing"),
+			Phase::Checking => write!(f, "checking"),
+			Phase::Compiling => write!(f, "compiling"),
+			Phase::Fixing => write!(f, "fixing"),
+			Phase::Formatting => write!(f, "formatting"),
+			Phase::Running => write!(f, "running")
+		}
+	}
+}
+
+// An internal compiler error: a panic inside the compiler, which is a bug in
+// it and never the fault of the program being compiled.
+#[derive(Debug, Clone)]
+pub struct Ice {
+	pub message: String,
+	// Where in the compiler it panicked, as `file:line:column`
+	pub location: Option<String>,
+	pub phase: Option<Phase>,
+	// The file or files being worked on
+	pub file: Option<String>,
+	// Frames of the compiler's own functions, innermost first
+	pub frames: Vec<String>
+}
+
+thread_local! {
+	// How many `catch` calls the current thread is inside
+	static CATCHING: Cell<usize> = const { Cell::new(0) };
+	static PHASE: RefCell<Option<(Phase, String)>> = const { RefCell::new(None) };
+	static CAUGHT: RefCell<Option<Ice>> = const { RefCell::new(None) };
+}
+
+// Says what the compiler is about to do, so a panic while doing it can say so.
+pub fn enter(phase: Phase, file: impl Into<String>) {
+	PHASE.with(|current| *current.borrow_mut() = Some((phase, file.into())));
+}
+
+// Runs `f`, turning a panic inside it into an `Ice` rather than letting the
+// raw panic message reach the terminal. Panics outside of `catch` are
+// reported the usual way.
+pub fn catch<T>(f: impl FnOnce() -> T) -> Result<T, Ice> {
+	static HOOK: Once = Once::new();
+	HOOK.call_once(|| {
+		let previous = panic::take_hook();
+		panic::set_hook(Box::new(move |info| {
+			if CATCHING.with(Cell::get) == 0 {
+				return previous(info);
+			}
+			let ice = report(info);
+			CAUGHT.with(|caught| *caught.borrow_mut() = Some(ice));
+		}));
+	});
+
+	CATCHING.with(|catching| catching.set(catching.get() + 1));
+	let result = panic::catch_unwind(AssertUnwindSafe(f));
+	CATCHING.with(|catching| catching.set(catching.get() - 1));
+	result.map_err(|payload| {
+		let caught = CAUGHT.with(|caught| caught.borrow_mut().take());
+		caught.unwrap_or_else(|| Ice {
+			message: payload_message(payload.as_ref()),
+			location: None,
+			phase: None,
+			file: None,
+			frames: Vec::new()
+		})
+	})
+}
+
+// Panics with an `Ice` caught on another thread, so that the `catch` this
+// thread is in reports it as it was.
+pub fn resume(ice: Ice) -> ! {
+	CAUGHT.with(|caught| *caught.borrow_mut() = Some(ice.clone()));
+	panic::resume_unwind(Box::new(ice.message))
+}
+
+fn report(info: &PanicHookInfo) -> Ice {
+	let (phase, file) = PHASE.with(|current| current.borrow().clone()).unzip();
+	Ice {
+		message: payload_message(info.payload()),
+		location: info.location().map(|location| location.to_string()),
+		phase,
+		file,
+		frames: frames(&Backtrace::force_capture().to_string())
+	}
+}
+
+fn payload_message(payload: &(dyn std::any::Any + Send)) -> String {
+	if let Some(message) = payload.downcast_ref::<&str>() {
+		message.to_string()
+	} else if let Some(message) = payload.downcast_ref::<String>() {
+		message.clone()
+	} else {
+		"unknown panic".to_string()
+	}
+}
+
+// Keeps the frames of a backtrace that are the compiler's own, leaving out
+// the standard library and the panic machinery, with where each one is.
+fn frames(backtrace: &str) -> Vec<String> {
+	let mut frames: Vec<String> = Vec::new();
+	let mut keep = false;
+	for line in backtrace.lines() {
+		let line = line.trim();
+		if let Some((index, symbol)) = line.split_once(": ")
+			&& index.chars().all(|c| c.is_ascii_digit())
+		{
+			keep = symbol.starts_with("glee::") && !symbol.starts_with(module_path!()) && frames.len() < FRAMES;
+			if keep {
+				frames.push(symbol.to_string());
+			}
+		} else if keep && let Some(at) = line.strip_prefix("at ") {
+			let last = frames.last_mut().unwrap();
+			last.push_str(" at ");
+			last.push_str(at);
+		}
+	}
+	frames
+}
+
+impl fmt::Display for Ice {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "error: internal compiler error: {}", self.message)?;
+		match (&self.phase, &self.file) {
+			(Some(phase), Some(file)) if !file.is_empty() => writeln!(f, " --> while {} {}", phase, file)?,
+			(Some(phase), _) => writeln!(f, " --> while {}", phase)?,
+			_ => {}
+		}
+		if let Some(location) = &self.location {
+			writeln!(f, "  = panicked at {}", location)?;
+		}
+		if !self.frames.is_empty() {
+			writeln!(f, "\nbacktrace:")?;
+			for (index, frame) in self.frames.iter().enumerate() {
+				writeln!(f, "  {:>2}: {}", index, frame)?;
+			}
+		}
+		writeln!(f, "\nThis is a bug in the compiler, not in your code. Please file an issue at")?;
+		writeln!(f, "{} with this report and, if you can, the code that caused it.", ISSUES)
+	}
+}
@@ -0,0 +1,156 @@
+use crate::diagnostics::source_map::{Source, SourceMap, floor_char_boundary};
+use crate::diagnostics::{Applicability, Diagnostic, Label, Severity, Suggestion};
+use crate::lexer::token::Span;
+
+// Diagnostics for tools, one JSON object per line:
+//
+//   {"severity": "error", "code": "E0001", "message": "...",
+//    "file": "src/main.gl", "range": {...}, "labels": [...],
+//    "notes": ["..."], "suggestions": [...]}
+//
+// A range is `{"start": position, "end": position}`, and a position is
+// `{"line": 1, "column": 1, "offset": 0}`: lines and columns count from 1,
+// columns in characters, and offsets are bytes from the start of the file.
+// The end is just past the last character. Labels have a `file`, `range` and
+// `message`; suggestions have a `message`, an `applicability` of "safe" or
+// "maybe-incorrect", and the `edits` they make, each with a `file`, `range`
+// and `replacement`. `code` is null for diagnostics without one, and `file`
+// for files missing from the source map, whose ranges then only have the
+// line and column of their start.
+pub fn to_json(diagnostic: &Diagnostic, sources: &SourceMap) -> String {
+	let code = diagnostic.code.map_or("null".to_string(), string);
+	let labels: Vec<String> = diagnostic.secondary_labels.iter().map(|label| self::label(label, sources)).collect();
+	let notes: Vec<String> = diagnostic.notes.iter().map(|note| string(note)).collect();
+	let suggestions: Vec<String> = diagnostic.suggestions.iter().map(|suggestion| self::suggestion(suggestion, sources)).collect();
+	format!(
+		"{{\"severity\":{},\"code\":{},\"message\":{}{},\"labels\":[{}],\"notes\":[{}],\"suggestions\":[{}]}}",
+		string(&diagnostic.severity.to_string()),
+		code,
+		string(&diagnostic.message),
+		located(sources, diagnostic.file, diagnostic.primary_span),
+		labels.join(","),
+		notes.join(","),
+		suggestions.join(",")
+	)
+}
+
+fn label(label: &Label, sources: &SourceMap) -> String {
+	format!("{{\"message\":{}{}}}", string(&label.message), located(sources, label.file, label.span))
+}
+
+fn suggestion(suggestion: &Suggestion, sources: &SourceMap) -> String {
+	let applicability = match suggestion.applicability {
+		Applicability::Safe => "safe",
+		Applicability::MaybeIncorrect => "maybe-incorrect"
+	};
+	let edits: Vec<String> = suggestion
+		.edits
+		.iter()
+		.map(|edit| format!("{{\"replacement\":{}{}}}", string(&edit.replacement), located(sources, edit.file, edit.span)))
+		.collect();
+	format!("{{\"message\":{},\"applicability\":{},\"edits\":[{}]}}", string(&suggestion.message), string(applicability), edits.join(","))
+}
+
+// The diagnostic's suggestions as Language Server Protocol code actions: a
+// JSON array of quick fixes, each with the diagnostic it fixes and its edits
+// as a workspace edit. Safe fixes are the preferred ones. Suggestions that
+// edit files missing from the source map are left out.
+pub fn code_actions(diagnostic: &Diagnostic, sources: &SourceMap) -> String {
+	let Some(source) = sources.get(diagnostic.file) else {
+		return "[]".to_string();
+	};
+	let severity = match diagnostic.severity {
+		Severity::Error => 1,
+		Severity::Warning => 2
+	};
+	let code = diagnostic.code.map_or(String::new(), |code| format!(",\"code\":{}", string(code)));
+	let fixed = format!(
+		"{{\"range\":{},\"severity\":{}{},\"source\":\"dotfun\",\"message\":{}}}",
+		lsp_range(source, diagnostic.primary_span),
+		severity,
+		code,
+		string(&diagnostic.message)
+	);
+
+	let mut actions = Vec::new();
+	'suggestions: for suggestion in &diagnostic.suggestions {
+		// Edits grouped by the file they are in, in the order files first come up
+		let mut changes: Vec<(usize, Vec<String>)> = Vec::new();
+		for edit in &suggestion.edits {
+			let Some(source) = sources.get(edit.file) else {
+				continue 'suggestions;
+			};
+			let text_edit = format!("{{\"range\":{},\"newText\":{}}}", lsp_range(source, edit.span), string(&edit.replacement));
+			match changes.iter_mut().find(|(file, _)| *file == edit.file) {
+				Some((_, edits)) => edits.push(text_edit),
+				None => changes.push((edit.file, vec![text_edit]))
+			}
+		}
+		let changes: Vec<String> = changes
+			.iter()
+			.map(|(file, edits)| format!("{}:[{}]", string(&file_uri(&sources.get(*file).unwrap().path)), edits.join(",")))
+			.collect();
+		actions.push(format!(
+			"{{\"title\":{},\"kind\":\"quickfix\",\"isPreferred\":{},\"diagnostics\":[{}],\"edit\":{{\"changes\":{{{}}}}}}}",
+			string(&suggestion.message),
+			suggestion.applicability == Applicability::Safe,
+			fixed,
+			changes.join(",")
+		));
+	}
+	format!("[{}]", actions.join(","))
+}
+
+// An LSP range: lines from 0, and characters in UTF-16 code units.
+fn lsp_range(source: &Source, span: Span) -> String {
+	let position = |offset: usize| {
+		let (line, character) = lsp_position(source, offset);
+		format!("{{\"line\":{},\"character\":{}}}", line, character)
+	};
+	format!("{{\"start\":{},\"end\":{}}}", position(span.start), position(span.end))
+}
+
+// The line and character of an offset, as LSP positions have them.
+pub fn lsp_position(source: &Source, offset: usize) -> (usize, usize) {
+	let offset = floor_char_boundary(&source.text, offset.min(source.text.len()));
+	let line = source.line_of(offset);
+	let start = source.line_start(line);
+	(line - 1, source.text[start..offset.max(start)].encode_utf16().count())
+}
+
+pub fn file_uri(path: &str) -> String {
+	if path.contains("://") { path.to_string() } else { format!("file://{}", path) }
+}
+
+// `,"file":...,"range":...` for a span in `file`.
+fn located(sources: &SourceMap, file: usize, span: Span) -> String {
+	let Some(source) = sources.get(file) else {
+		let start = format!("{{\"line\":{},\"column\":{},\"offset\":{}}}", span.line, span.column, span.start);
+		let end = format!("{{\"line\":null,\"column\":null,\"offset\":{}}}", span.end);
+		return format!(",\"file\":null,\"range\":{{\"start\":{},\"end\":{}}}", start, end);
+	};
+	let position = |offset: usize| {
+		let (line, column) = source.position(offset);
+		format!("{{\"line\":{},\"column\":{},\"offset\":{}}}", line, column, offset)
+	};
+	format!(",\"file\":{},\"range\":{{\"start\":{},\"end\":{}}}", string(&source.path), position(span.start), position(span.end))
+}
+
+// A JSON string literal.
+pub fn string(text: &str) -> String {
+	let mut out = String::with_capacity(text.len() + 2);
+	out.push('"');
+	for c in text.chars() {
+		match c {
+			'"' => out += "\\\"",
+			'\\' => out += "\\\\",
+			'\n' => out += "\\n",
+			'\r' => out += "\\r",
+			'\t' => out += "\\t",
+			c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+			c => out.push(c)
+		}
+	}
+	out.push('"');
+	out
+}
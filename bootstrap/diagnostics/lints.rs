@@ -0,0 +1,140 @@
+use crate::diagnostics::suggest;
+use crate::diagnostics::{Diagnostic, Severity};
+
+// Every warning belongs to a lint, named so it can be turned off in source
+// with `@Suppress("name")` or set to a level with `-W name`, `-D name` and
+// `-A name`. `warnings` stands for all of them at once.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Lint {
+	pub name: &'static str,
+	pub code: &'static str,
+	pub description: &'static str
+}
+
+pub const ALL: &str = "warnings";
+
+pub const LINTS: &[Lint] = &[
+	Lint { name: "unused", code: "W0001", description: "variables, parameters and private declarations that are never used" },
+	Lint { name: "unused-import", code: "W0002", description: "imports nothing is used from" },
+	Lint { name: "unawaited", code: "W0003", description: "async calls whose result is dropped" },
+	Lint { name: "useless-await", code: "W0004", description: "'await' on calls that are not async" },
+	Lint { name: "deprecation", code: "W0005", description: "uses of declarations marked '@Deprecated'" },
+	Lint { name: "unknown-lint", code: "W0006", description: "names in '@Suppress' that are not lints" }
+];
+
+pub fn lint(name: &str) -> Option<&'static Lint> {
+	LINTS.iter().find(|lint| lint.name == name)
+}
+
+// The lint a diagnostic belongs to, if it is a warning that has one.
+pub fn of(diagnostic: &Diagnostic) -> Option<&'static Lint> {
+	let code = diagnostic.code?;
+	LINTS.iter().find(|lint| lint.code == code)
+}
+
+// Whether `names`, as given to `@Suppress`, turn off `diagnostic`.
+pub fn suppresses(names: &[String], diagnostic: &Diagnostic) -> bool {
+	of(diagnostic).is_some_and(|lint| names.iter().any(|name| name == lint.name || name == ALL))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+	// Not reported
+	Allow,
+	Warn,
+	// Reported as an error
+	Deny
+}
+
+impl Level {
+	pub fn flag(self) -> &'static str {
+		match self {
+			Level::Allow => "-A",
+			Level::Warn => "-W",
+			Level::Deny => "-D"
+		}
+	}
+}
+
+// The level of each lint, from `-W`, `-D` and `-A` flags. Later flags win
+// over earlier ones, so `-D warnings -A unused` makes every warning an error
+// except those about unused code. Lints nothing is said about warn.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LintLevels {
+	settings: Vec<(&'static str, Level)>
+}
+
+impl LintLevels {
+	pub fn new() -> Self {
+		LintLevels::default()
+	}
+
+	// Takes `-W name`, `-D name` and `-A name`, or `-Wname` and the like,
+	// out of `args`, leaving everything else in order.
+	pub fn from_args(args: &mut Vec<String>) -> Result<Self, String> {
+		let mut levels = LintLevels::new();
+		let mut given = std::mem::take(args).into_iter();
+		while let Some(arg) = given.next() {
+			let level = match arg.get(..2) {
+				Some("-W") => Level::Warn,
+				Some("-D") => Level::Deny,
+				Some("-A") => Level::Allow,
+				_ => {
+					args.push(arg);
+					continue;
+				}
+			};
+			let name = match &arg[2..] {
+				"" => given.next().ok_or_else(|| format!("'{}' needs the name of a lint", level.flag()))?,
+				name => name.to_string()
+			};
+			levels.set(&name, level)?;
+		}
+		Ok(levels)
+	}
+
+	pub fn set(&mut self, name: &str, level: Level) -> Result<(), String> {
+		let name = match lint(name) {
+			Some(lint) => lint.name,
+			None if name == ALL => ALL,
+			None => {
+				let names = LINTS.iter().map(|lint| lint.name).chain([ALL]);
+				return Err(match suggest::closest(name, names) {
+					Some(closest) => format!("Unknown lint '{}'; did you mean '{}'?", name, closest),
+					None => format!("Unknown lint '{}'", name)
+				});
+			}
+		};
+		self.settings.push((name, level));
+		Ok(())
+	}
+
+	pub fn level(&self, lint: &Lint) -> Level {
+		self.setting(lint).map_or(Level::Warn, |(_, level)| level)
+	}
+
+	fn setting(&self, lint: &Lint) -> Option<(&'static str, Level)> {
+		self.settings.iter().rev().find(|(name, _)| *name == lint.name || *name == ALL).copied()
+	}
+
+	// Drops the warnings that are allowed and makes the denied ones errors,
+	// saying which flag did it. Errors are left as they are.
+	pub fn apply(&self, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+		diagnostics
+			.into_iter()
+			.filter_map(|mut diagnostic| {
+				let Some(lint) = of(&diagnostic).filter(|_| diagnostic.severity == Severity::Warning) else {
+					return Some(diagnostic);
+				};
+				match self.setting(lint) {
+					Some((_, Level::Allow)) => None,
+					Some((name, Level::Deny)) => {
+						diagnostic.severity = Severity::Error;
+						Some(diagnostic.with_note(format!("'-D {}' makes this an error", name)))
+					}
+					Some((_, Level::Warn)) | None => Some(diagnostic)
+				}
+			})
+			.collect()
+	}
+}
@@ -0,0 +1,189 @@
+pub mod codes;
+pub mod fix;
+pub mod ice;
+pub mod json;
+pub mod lints;
+pub mod render;
+pub mod source_map;
+pub mod suggest;
+pub mod timings;
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::diagnostics::source_map::SourceMap;
+use crate::lexer::token::Span;
+
+// Everything the compiler has to say about a program, from the lexer, the
+// parser and the checker alike: errors that stop it from running, and
+// warnings about code that runs but is probably a mistake.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+	pub severity: Severity,
+	// Names the kind of problem, like `E0001`, for ones that have a code
+	pub code: Option<&'static str>,
+	pub message: String,
+	// The file the problem is in, by its index among those compiled together
+	pub file: usize,
+	// Where the problem is
+	pub primary_span: Span,
+	// Other places that explain it, like an earlier declaration
+	pub secondary_labels: Vec<Label>,
+	// Shown after the message, to explain the rule or what to do
+	pub notes: Vec<String>,
+	pub suggestions: Vec<Suggestion>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+	Error,
+	Warning
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+	pub file: usize,
+	pub span: Span,
+	pub message: String
+}
+
+// A change that fixes the problem, as edits to the source. `dotfun fix`
+// only makes the safe ones; the rest are guesses, like the name a
+// misspelled one was probably meant to be.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+	pub message: String,
+	pub edits: Vec<Edit>,
+	pub applicability: Applicability
+}
+
+// The source at `span` replaced by `replacement`. An empty span inserts it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edit {
+	pub file: usize,
+	pub span: Span,
+	pub replacement: String
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Applicability {
+	// Fixes the problem without changing what the program means otherwise
+	Safe,
+	MaybeIncorrect
+}
+
+impl Diagnostic {
+	pub fn new(severity: Severity, message: impl Into<String>, span: Span) -> Self {
+		Diagnostic {
+			severity,
+			code: None,
+			message: message.into(),
+			file: 0,
+			primary_span: span,
+			secondary_labels: Vec::new(),
+			notes: Vec::new(),
+			suggestions: Vec::new()
+		}
+	}
+
+	pub fn error(message: impl Into<String>, span: Span) -> Self {
+		Diagnostic::new(Severity::Error, message, span)
+	}
+
+	pub fn warning(message: impl Into<String>, span: Span) -> Self {
+		Diagnostic::new(Severity::Warning, message, span)
+	}
+
+	// Labels added after this are in the same file.
+	pub fn in_file(mut self, file: usize) -> Self {
+		self.file = file;
+		self
+	}
+
+	pub fn with_code(mut self, code: &'static str) -> Self {
+		self.code = Some(code);
+		self
+	}
+
+	pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+		self.secondary_labels.push(Label { file: self.file, span, message: message.into() });
+		self
+	}
+
+	pub fn with_note(mut self, note: impl Into<String>) -> Self {
+		self.notes.push(note.into());
+		self
+	}
+
+	pub fn with_suggestion(self, message: impl Into<String>, span: Span, replacement: impl Into<String>) -> Self {
+		self.with_edit(message, span, replacement, Applicability::MaybeIncorrect)
+	}
+
+	// A suggestion safe enough for `dotfun fix` to make.
+	pub fn with_fix(self, message: impl Into<String>, span: Span, replacement: impl Into<String>) -> Self {
+		self.with_edit(message, span, replacement, Applicability::Safe)
+	}
+
+	fn with_edit(mut self, message: impl Into<String>, span: Span, replacement: impl Into<String>, applicability: Applicability) -> Self {
+		let edit = Edit { file: self.file, span, replacement: replacement.into() };
+		self.suggestions.push(Suggestion { message: message.into(), edits: vec![edit], applicability });
+		self
+	}
+
+	pub fn is_error(&self) -> bool {
+		self.severity == Severity::Error
+	}
+}
+
+impl fmt::Display for Severity {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Severity::Error => write!(f, "error"),
+			Severity::Warning => write!(f, "warning")
+		}
+	}
+}
+
+// How diagnostics are printed: laid out for people to read, or as JSON
+// lines for tools. Chosen with `--error-format=human` or `--error-format=json`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+	#[default]
+	Human,
+	Json
+}
+
+impl ErrorFormat {
+	// The diagnostic as it should be printed, ending in a line break. Color
+	// only applies to the human format.
+	pub fn emit(self, diagnostic: &Diagnostic, sources: &SourceMap, color: bool) -> String {
+		match self {
+			ErrorFormat::Human => render::render(diagnostic, sources, color),
+			ErrorFormat::Json => json::to_json(diagnostic, sources) + "\n"
+		}
+	}
+}
+
+impl FromStr for ErrorFormat {
+	type Err = String;
+
+	fn from_str(name: &str) -> Result<Self, Self::Err> {
+		match name {
+			"human" => Ok(ErrorFormat::Human),
+			"json" => Ok(ErrorFormat::Json),
+			_ => Err(format!("Unknown error format '{}'; expected 'human' or 'json'", name))
+		}
+	}
+}
+
+// On one line, with notes and suggestions after the message, for output
+// that is not a terminal; `render` lays it out with the code it is about.
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.message)?;
+		for hint in self.notes.iter().chain(self.suggestions.iter().map(|suggestion| &suggestion.message)) {
+			write!(f, "; {}", hint)?;
+		}
+		write!(f, " at line {} column {}", self.primary_span.line, self.primary_span.column)
+	}
+}
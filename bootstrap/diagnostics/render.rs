@@ -0,0 +1,164 @@
+use std::collections::BTreeSet;
+
+use crate::diagnostics::source_map::{Source, SourceMap, floor_char_boundary};
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::lexer::token::Span;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+// Columns a tab takes up when source lines are shown
+const TAB_WIDTH: usize = 4;
+
+// Lays a diagnostic out for a terminal: the message, then the lines of code
+// it is about with its span underlined by `^` and each label's by `-`, then
+// its notes and suggestions. Labels in other files get a snippet of their
+// own. Spans over many lines show only their first and last two, and lines
+// between ones worth showing are left out. With `color`, severities, gutters
+// and underlines are colored with ANSI escapes.
+pub fn render(diagnostic: &Diagnostic, sources: &SourceMap, color: bool) -> String {
+	let style = Style { color };
+	let accent = match diagnostic.severity {
+		Severity::Error => RED,
+		Severity::Warning => YELLOW
+	};
+	let severity = match diagnostic.code {
+		Some(code) => format!("{}[{}]", diagnostic.severity, code),
+		None => diagnostic.severity.to_string()
+	};
+
+	// The primary span's file comes first, then those of labels elsewhere
+	let mut snippets = vec![Snippet { file: diagnostic.file, marks: Vec::new() }];
+	snippets[0].marks.push(Mark { span: diagnostic.primary_span, primary: true, message: None });
+	for label in &diagnostic.secondary_labels {
+		let mark = Mark { span: label.span, primary: false, message: Some(&label.message) };
+		match snippets.iter_mut().find(|snippet| snippet.file == label.file) {
+			Some(snippet) => snippet.marks.push(mark),
+			None => snippets.push(Snippet { file: label.file, marks: vec![mark] })
+		}
+	}
+	let width = snippets
+		.iter()
+		.filter_map(|snippet| Some(snippet.lines(sources.get(snippet.file)?).last()?.to_string().len()))
+		.max()
+		.unwrap_or(1);
+	let gutter = " ".repeat(width);
+
+	let mut out = format!("{}{}\n", style.paint(accent, &severity), style.paint(BOLD, &format!(": {}", diagnostic.message)));
+	for (index, snippet) in snippets.iter().enumerate() {
+		let arrow = if index == 0 { "-->" } else { ":::" };
+		let span = snippet.marks[0].span;
+		let Some(source) = sources.get(snippet.file) else {
+			let place = format!("line {} column {}", span.line, span.column);
+			out += &format!("{}{} {}\n", gutter, style.paint(BLUE, arrow), place);
+			continue;
+		};
+		let (line, column) = source.position(span.start);
+		out += &format!("{}{} {}:{}:{}\n", gutter, style.paint(BLUE, arrow), source.path, line, column);
+		out += &format!("{} {}\n", gutter, style.paint(BLUE, "|"));
+		snippet.render(source, accent, width, &style, &mut out);
+	}
+
+	if !diagnostic.notes.is_empty() || !diagnostic.suggestions.is_empty() {
+		out += &format!("{} {}\n", gutter, style.paint(BLUE, "|"));
+	}
+	for note in &diagnostic.notes {
+		out += &format!("{} {} {} {}\n", gutter, style.paint(BLUE, "="), style.paint(BOLD, "note:"), note);
+	}
+	for suggestion in &diagnostic.suggestions {
+		out += &format!("{} {} {} {}\n", gutter, style.paint(BLUE, "="), style.paint(BOLD, "help:"), suggestion.message);
+	}
+	out
+}
+
+struct Style {
+	color: bool
+}
+
+impl Style {
+	fn paint(&self, color: &str, text: &str) -> String {
+		if self.color { format!("{}{}{}", color, text, RESET) } else { text.to_string() }
+	}
+}
+
+// The marks in one file.
+struct Snippet<'a> {
+	file: usize,
+	marks: Vec<Mark<'a>>
+}
+
+struct Mark<'a> {
+	span: Span,
+	primary: bool,
+	message: Option<&'a str>
+}
+
+impl Mark<'_> {
+	// The first and last line the span covers. An empty span still marks
+	// the place it is at.
+	fn lines(&self, source: &Source) -> (usize, usize) {
+		let last = if self.span.end > self.span.start { self.span.end - 1 } else { self.span.start };
+		(source.line_of(self.span.start), source.line_of(last))
+	}
+}
+
+impl Snippet<'_> {
+	fn lines(&self, source: &Source) -> BTreeSet<usize> {
+		let mut lines = BTreeSet::new();
+		for mark in &self.marks {
+			let (first, last) = mark.lines(source);
+			if last - first < 4 {
+				lines.extend(first..=last);
+			} else {
+				lines.extend([first, first + 1, last - 1, last]);
+			}
+		}
+		lines
+	}
+
+	fn render(&self, source: &Source, accent: &str, width: usize, style: &Style, out: &mut String) {
+		let mut marks: Vec<&Mark> = self.marks.iter().collect();
+		marks.sort_by_key(|mark| (mark.span.start, !mark.primary));
+		let mut previous = None;
+		for line in self.lines(source) {
+			if previous.is_some_and(|previous| line > previous + 1) {
+				out.push_str(&format!("{}\n", style.paint(BLUE, "...")));
+			}
+			previous = Some(line);
+
+			let text = source.line(line);
+			let number = format!("{:>width$} |", line);
+			out.push_str(&format!("{} {}\n", style.paint(BLUE, &number), text.replace('\t', &" ".repeat(TAB_WIDTH))));
+
+			for mark in &marks {
+				let (first, last) = mark.lines(source);
+				if line < first || line > last {
+					continue;
+				}
+				let start = source.line_start(line);
+				let from = if line == first {
+					columns(text, mark.span.start - start)
+				} else {
+					columns(text, text.len() - text.trim_start().len())
+				};
+				let to = if line == last { columns(text, mark.span.end.saturating_sub(start)) } else { columns(text, text.len()) };
+				let (underline, color) = if mark.primary { ("^", accent) } else { ("-", BLUE) };
+				let mut row = underline.repeat(to.saturating_sub(from).max(1));
+				if line == last && let Some(message) = mark.message {
+					row = format!("{} {}", row, message);
+				}
+				let gutter = format!("{:width$} |", "");
+				out.push_str(&format!("{} {}{}\n", style.paint(BLUE, &gutter), " ".repeat(from), style.paint(color, &row)));
+			}
+		}
+	}
+}
+
+// How many columns the first `bytes` of a line take up when it is shown.
+fn columns(text: &str, bytes: usize) -> usize {
+	let end = floor_char_boundary(text, bytes.min(text.len()));
+	text[..end].chars().map(|c| if c == '\t' { TAB_WIDTH } else { 1 }).sum()
+}
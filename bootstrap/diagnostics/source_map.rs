@@ -0,0 +1,77 @@
+// The text of every file being compiled, so diagnostics can show the code
+// they are about. Files are numbered in the order they are added, which has
+// to be the order they are given to the checker for `Diagnostic::file` to
+// find the right one.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+	files: Vec<Source>
+}
+
+impl SourceMap {
+	pub fn new() -> Self {
+		SourceMap::default()
+	}
+
+	pub fn add(&mut self, path: impl Into<String>, text: impl Into<String>) -> usize {
+		self.files.push(Source::new(path, text));
+		self.files.len() - 1
+	}
+
+	pub fn get(&self, file: usize) -> Option<&Source> {
+		self.files.get(file)
+	}
+}
+
+#[derive(Debug)]
+pub struct Source {
+	pub path: String,
+	pub text: String,
+	// Byte offset of the start of each line
+	line_starts: Vec<usize>
+}
+
+impl Source {
+	pub fn new(path: impl Into<String>, text: impl Into<String>) -> Self {
+		let text = text.into();
+		let mut line_starts = vec![0];
+		line_starts.extend(text.match_indices('\n').map(|(index, _)| index + 1));
+		Source { path: path.into(), text, line_starts }
+	}
+
+	pub fn line_count(&self) -> usize {
+		self.line_starts.len()
+	}
+
+	// The line `offset` is on, counting from 1 like `Span::line`. Offsets
+	// past the end are on the last line.
+	pub fn line_of(&self, offset: usize) -> usize {
+		self.line_starts.partition_point(|&start| start <= offset)
+	}
+
+	pub fn line_start(&self, line: usize) -> usize {
+		self.line_starts[line - 1]
+	}
+
+	// The text of a line, without its line break.
+	pub fn line(&self, line: usize) -> &str {
+		let start = self.line_start(line);
+		let end = self.line_starts.get(line).map_or(self.text.len(), |next| next - 1);
+		self.text[start..end].trim_end_matches('\r')
+	}
+
+	// The line and column `offset` is at, both counting from 1 and columns
+	// in characters, like `Span::line` and `Span::column`.
+	pub fn position(&self, offset: usize) -> (usize, usize) {
+		let line = self.line_of(offset);
+		let start = self.line_start(line);
+		let offset = floor_char_boundary(&self.text, offset.min(self.text.len()));
+		(line, self.text[start..offset.max(start)].chars().count() + 1)
+	}
+}
+
+pub fn floor_char_boundary(text: &str, mut offset: usize) -> usize {
+	while !text.is_char_boundary(offset) {
+		offset -= 1;
+	}
+	offset
+}
@@ -0,0 +1,65 @@
+use crate::diagnostics::Diagnostic;
+use crate::lexer::lexer::KEYWORDS;
+use crate::lexer::token::Span;
+
+// "Did you mean" support: picks the candidate closest to a misspelled name,
+// as long as it is close enough to plausibly be a typo.
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+	let length = name.chars().count();
+	let limit = (length / 3).max(1);
+	candidates
+		.into_iter()
+		.filter(|candidate| *candidate != name)
+		// Names that differ this much in length cannot be close
+		.filter(|candidate| candidate.chars().count().abs_diff(length) <= limit)
+		.map(|candidate| (distance(name, candidate), candidate))
+		.filter(|(distance, _)| *distance <= limit)
+		.min_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(b.1)))
+		.map(|(_, candidate)| candidate)
+}
+
+// Adds "did you mean 'x'?" to a diagnostic about `name` when there is a
+// plausible candidate. When `span` is just the misspelled name it is offered
+// as a replacement for it; otherwise the hint is only a note.
+pub fn did_you_mean<'a>(diagnostic: Diagnostic, name: &str, span: Span, candidates: impl IntoIterator<Item = &'a str>) -> Diagnostic {
+	let Some(candidate) = closest(name, candidates) else {
+		return diagnostic;
+	};
+	let hint = format!("did you mean '{}'?", candidate);
+	if span.end - span.start == name.len() {
+		diagnostic.with_suggestion(hint, span, candidate)
+	} else {
+		diagnostic.with_note(hint)
+	}
+}
+
+// The keywords worth suggesting in place of `name`. Keywords are lowercase,
+// and names of a letter or two are close to too many of them.
+pub fn keywords(name: &str) -> impl Iterator<Item = &'static str> {
+	let plausible = name.len() > 2 && name.starts_with(|c: char| c.is_ascii_lowercase());
+	KEYWORDS.iter().filter(move |_| plausible).map(|(keyword, _)| *keyword)
+}
+
+// Edit distance counting swapped neighbours as one edit, case-insensitive so
+// `string` suggests `String` and `shuot` suggests `shout`.
+fn distance(a: &str, b: &str) -> usize {
+	let a: Vec<char> = a.to_lowercase().chars().collect();
+	let b: Vec<char> = b.to_lowercase().chars().collect();
+	let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+	for (i, row) in rows.iter_mut().enumerate() {
+		row[0] = i;
+	}
+	rows[0] = (0..=b.len()).collect();
+
+	for i in 1..=a.len() {
+		for j in 1..=b.len() {
+			let substitution = rows[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+			let mut best = substitution.min(rows[i - 1][j] + 1).min(rows[i][j - 1] + 1);
+			if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+				best = best.min(rows[i - 2][j - 2] + 1);
+			}
+			rows[i][j] = best;
+		}
+	}
+	rows[a.len()][b.len()]
+}
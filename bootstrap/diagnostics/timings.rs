@@ -0,0 +1,207 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::diagnostics::json::string;
+
+// What `--timings` shows: how long each phase of the compiler took and how
+// much memory it allocated, on every thread it ran on.
+//
+// Phases are timed with `time`, which costs next to nothing unless timings
+// were asked for. Memory is only counted in a program that installs
+// `Counting` as its global allocator, as `dotfun` does; elsewhere it shows
+// as nothing allocated.
+
+// A phase, on one thread, working on one file or all of them.
+#[derive(Debug, Clone)]
+pub struct Event {
+	pub phase: &'static str,
+	// Empty when the phase works on every file at once
+	pub file: String,
+	// Since timing started
+	pub start: Duration,
+	pub duration: Duration,
+	pub thread: u64,
+	// Bytes allocated on the thread during the phase
+	pub allocated: u64
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static START: Mutex<Option<Instant>> = Mutex::new(None);
+static EVENTS: Mutex<Vec<Event>> = Mutex::new(Vec::new());
+static THREADS: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+	// Set up without allocating, so the allocator can use it
+	static ALLOCATED: Cell<u64> = const { Cell::new(0) };
+	static THREAD: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+// The system allocator, counting the bytes each thread allocates.
+pub struct Counting;
+
+unsafe impl GlobalAlloc for Counting {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		count(layout.size());
+		unsafe { System.alloc(layout) }
+	}
+
+	unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+		count(layout.size());
+		unsafe { System.alloc_zeroed(layout) }
+	}
+
+	unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+		count(new_size.saturating_sub(layout.size()));
+		unsafe { System.realloc(ptr, layout, new_size) }
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		unsafe { System.dealloc(ptr, layout) }
+	}
+}
+
+fn count(size: usize) {
+	// Threads being torn down have no thread-locals left to count in
+	let _ = ALLOCATED.try_with(|allocated| allocated.set(allocated.get() + size as u64));
+}
+
+fn allocated() -> u64 {
+	ALLOCATED.with(Cell::get)
+}
+
+// Starts timing, from now, forgetting what was timed before. Watch mode
+// starts again for every build.
+pub fn start() {
+	*START.lock().unwrap() = Some(Instant::now());
+	EVENTS.lock().unwrap().clear();
+	ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+	ENABLED.load(Ordering::Relaxed)
+}
+
+fn since_start(instant: Instant) -> Duration {
+	START.lock().unwrap().map_or(Duration::ZERO, |start| instant.saturating_duration_since(start))
+}
+
+// Numbers the threads in the order they first time something.
+fn thread() -> u64 {
+	THREAD.with(|thread| {
+		let id = thread.get().unwrap_or_else(|| THREADS.fetch_add(1, Ordering::Relaxed));
+		thread.set(Some(id));
+		id
+	})
+}
+
+// Runs `f` as `phase` of the work on `file`, timing it when timings are on.
+pub fn time<T>(phase: &'static str, file: &str, f: impl FnOnce() -> T) -> T {
+	if !enabled() {
+		return f();
+	}
+	let (begun, before) = (Instant::now(), allocated());
+	let result = f();
+	let event = Event {
+		phase,
+		file: file.to_string(),
+		start: since_start(begun),
+		duration: begun.elapsed(),
+		thread: thread(),
+		allocated: allocated() - before
+	};
+	EVENTS.lock().unwrap().push(event);
+	result
+}
+
+// The phases timed since timing started, in the order they started, and the
+// time since then.
+pub fn take() -> (Vec<Event>, Duration) {
+	let mut events = std::mem::take(&mut *EVENTS.lock().unwrap());
+	events.sort_by_key(|event| event.start);
+	(events, since_start(Instant::now()))
+}
+
+// A table of each phase, in the order they first ran: how many times it ran,
+// for how long in all, and how much it allocated, with the wall-clock time of
+// the whole build. Phases on several threads at once can add up to more
+// than that.
+pub fn table(events: &[Event], total: Duration) -> String {
+	let mut phases: Vec<(&str, usize, Duration, u64)> = Vec::new();
+	for event in events {
+		match phases.iter_mut().find(|(phase, ..)| *phase == event.phase) {
+			Some((_, runs, duration, allocated)) => {
+				*runs += 1;
+				*duration += event.duration;
+				*allocated += event.allocated;
+			}
+			None => phases.push((event.phase, 1, event.duration, event.allocated))
+		}
+	}
+	let threads = events.iter().map(|event| event.thread).collect::<BTreeSet<_>>().len();
+	let mut table = format!("{:<10} {:>6} {:>10} {:>7} {:>11}\n", "phase", "runs", "time", "share", "allocated");
+	for (phase, runs, duration, allocated) in phases {
+		let share = duration.as_secs_f64() / total.as_secs_f64().max(f64::EPSILON) * 100.0;
+		let _ = writeln!(table, "{:<10} {:>6} {:>10} {:>6.1}% {:>11}", phase, runs, milliseconds(duration), share, bytes(allocated));
+	}
+	let _ = writeln!(table, "{:<10} {:>6} {:>10}", "total", "", milliseconds(total));
+	let _ = write!(table, "on {} thread{}", threads, if threads == 1 { "" } else { "s" });
+	table
+}
+
+// A duration as milliseconds, to a hundredth of one.
+pub fn milliseconds(duration: Duration) -> String {
+	format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+}
+
+fn bytes(count: u64) -> String {
+	match count {
+		0..1024 => format!("{} B", count),
+		1024..1048576 => format!("{:.1} KiB", count as f64 / 1024.0),
+		_ => format!("{:.1} MiB", count as f64 / 1048576.0)
+	}
+}
+
+// The events in the Chrome trace format, for chrome://tracing or Perfetto:
+// a complete ("X") event for each, in microseconds, with the file and bytes
+// allocated as its arguments.
+pub fn trace(events: &[Event]) -> String {
+	let mut entries: Vec<String> = events
+		.iter()
+		.map(|event| {
+			format!(
+				"{{\"name\":{},\"cat\":\"compiler\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":{},\"args\":{{\"file\":{},\"allocated\":{}}}}}",
+				string(event.phase),
+				event.start.as_micros(),
+				event.duration.as_micros(),
+				event.thread,
+				string(&event.file),
+				event.allocated
+			)
+		})
+		.collect();
+	let threads: BTreeSet<u64> = events.iter().map(|event| event.thread).collect();
+	entries.extend(threads.into_iter().map(|thread| {
+		format!("{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{},\"args\":{{\"name\":\"thread {}\"}}}}", thread, thread)
+	}));
+	format!("{{\"traceEvents\":[\n{}\n]}}\n", entries.join(",\n"))
+}
+
+// Reports the phases timed so far: as a table on stderr, or as a trace
+// written to `path`.
+pub fn report(path: Option<&str>) {
+	let (events, total) = take();
+	match path {
+		None => eprintln!("{}", table(&events, total)),
+		Some(path) => {
+			if let Err(err) = fs::write(path, trace(&events)) {
+				eprintln!("error: Cannot write '{}': {}", path, err);
+			}
+		}
+	}
+}
@@ -0,0 +1,210 @@
+use crate::diagnostics::json::string;
+use crate::doc::{Docs, Item, Link, Package};
+
+const STYLE: &str = "body { font-family: sans-serif; max-width: 60em; margin: 2em auto; padding: 0 1em; line-height: 1.5; }
+pre, code { font-family: monospace; background: #f4f4f4; }
+pre { padding: 0.5em; overflow-x: auto; }
+.signature { font-weight: bold; }
+.item { margin: 1.5em 0; }
+.members { margin-left: 2em; }
+.source { color: #777; font-size: 0.85em; }
+a { color: #1a5fb4; text-decoration: none; }
+#results li span { color: #777; }";
+
+// Filters the search index as the query is typed, matching names and the
+// packages they are in.
+const SEARCH: &str = "const input = document.getElementById('search');
+const results = document.getElementById('results');
+input.addEventListener('input', () => {
+	const query = input.value.trim().toLowerCase();
+	results.innerHTML = '';
+	if (!query) return;
+	for (const entry of SEARCH_INDEX) {
+		if (!(entry.package + '.' + entry.name).toLowerCase().includes(query)) continue;
+		const item = document.createElement('li');
+		const link = document.createElement('a');
+		link.href = entry.url;
+		link.textContent = entry.name;
+		const kind = document.createElement('span');
+		kind.textContent = ' ' + entry.kind + (entry.package ? ' in ' + entry.package : '');
+		item.append(link, kind);
+		results.append(item);
+	}
+});";
+
+// The pages of the docs, each as its file name and contents: `index.html`
+// listing the packages, a page for each package, and `search-index.js`.
+pub fn pages(docs: &Docs, title: &str) -> Vec<(String, String)> {
+	let mut pages = Vec::new();
+
+	let mut body = format!("<h1>{}</h1>\n", escape(title));
+	body += "<input id=\"search\" type=\"search\" placeholder=\"Search\" autofocus>\n<ul id=\"results\"></ul>\n";
+	body += "<h2>Packages</h2>\n<ul>\n";
+	for package in &docs.packages {
+		let count = package.items.len();
+		body += &format!(
+			"<li><a href=\"{}\">{}</a> <span class=\"source\">{} declaration{}</span></li>\n",
+			escape(&page(&package.name)),
+			escape(display_name(&package.name)),
+			count,
+			if count == 1 { "" } else { "s" }
+		);
+	}
+	body += "</ul>\n<script src=\"search-index.js\"></script>\n";
+	body += &format!("<script>\n{}\n</script>\n", SEARCH);
+	pages.push(("index.html".to_string(), document(title, &body)));
+
+	for package in &docs.packages {
+		pages.push((page(&package.name), package_page(docs, package, title)));
+	}
+
+	let entries: Vec<String> = docs
+		.all()
+		.map(|(package, item)| {
+			format!(
+				"{{\"name\":{},\"kind\":{},\"package\":{},\"url\":{}}}",
+				string(&item.anchor),
+				string(item.kind),
+				string(&package.name),
+				string(&url(&Link { package: package.name.clone(), anchor: item.anchor.clone() }))
+			)
+		})
+		.collect();
+	pages.push(("search-index.js".to_string(), format!("const SEARCH_INDEX = [\n{}\n];\n", entries.join(",\n"))));
+	pages
+}
+
+// The root package has no name, so its page needs one that no package can
+// have.
+fn page(package: &str) -> String {
+	if package.is_empty() { "_root.html".to_string() } else { format!("{}.html", package) }
+}
+
+fn display_name(package: &str) -> &str {
+	if package.is_empty() { "(root)" } else { package }
+}
+
+fn url(link: &Link) -> String {
+	format!("{}#{}", page(&link.package), link.anchor)
+}
+
+fn document(title: &str, body: &str) -> String {
+	format!(
+		"<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+		escape(title),
+		STYLE,
+		body
+	)
+}
+
+fn package_page(docs: &Docs, package: &Package, title: &str) -> String {
+	let mut body = format!("<p><a href=\"index.html\">{}</a></p>\n", escape(title));
+	body += &format!("<h1>Package {}</h1>\n", escape(display_name(&package.name)));
+	for item in &package.items {
+		body += &self::item(docs, package, item);
+	}
+	document(&format!("{} - {}", display_name(&package.name), title), &body)
+}
+
+fn item(docs: &Docs, package: &Package, item: &Item) -> String {
+	let mut html = format!("<div class=\"item\" id=\"{}\">\n<pre class=\"signature\">", escape(&item.anchor));
+	for segment in &item.signature {
+		match &segment.link {
+			Some(link) => html += &format!("<a href=\"{}\">{}</a>", escape(&url(link)), escape(&segment.text)),
+			None => html += &escape(&segment.text)
+		}
+	}
+	html += "</pre>\n";
+	html += &format!("<div class=\"source\">{}:{}</div>\n", escape(&item.path), item.line);
+	if let Some(doc) = &item.doc {
+		html += &markup(docs, &package.name, doc);
+	}
+	if !item.members.is_empty() {
+		html += "<div class=\"members\">\n";
+		for member in &item.members {
+			html += &self::item(docs, package, member);
+		}
+		html += "</div>\n";
+	}
+	html += "</div>\n";
+	html
+}
+
+// Doc comment text as HTML: paragraphs split by blank lines, ``` fenced code
+// blocks, `code` spans, and `[Name]` links to the docs of what it names.
+fn markup(docs: &Docs, package: &str, text: &str) -> String {
+	let mut html = String::new();
+	let mut paragraph: Vec<&str> = Vec::new();
+	let mut code: Option<Vec<&str>> = None;
+	for line in text.lines() {
+		if line.trim_start().starts_with("```") {
+			match code.take() {
+				Some(lines) => html += &format!("<pre><code>{}</code></pre>\n", escape(&lines.join("\n"))),
+				None => {
+					html += &self::paragraph(docs, package, &std::mem::take(&mut paragraph));
+					code = Some(Vec::new());
+				}
+			}
+		} else if let Some(lines) = &mut code {
+			lines.push(line);
+		} else if line.trim().is_empty() {
+			html += &self::paragraph(docs, package, &std::mem::take(&mut paragraph));
+		} else {
+			paragraph.push(line.trim());
+		}
+	}
+	// An unclosed block runs to the end
+	if let Some(lines) = code {
+		html += &format!("<pre><code>{}</code></pre>\n", escape(&lines.join("\n")));
+	}
+	html += &self::paragraph(docs, package, &paragraph);
+	html
+}
+
+fn paragraph(docs: &Docs, package: &str, lines: &[&str]) -> String {
+	if lines.is_empty() {
+		return String::new();
+	}
+	let text = lines.join(" ");
+	let mut html = String::from("<p>");
+	let mut rest = text.as_str();
+	while let Some(start) = rest.find(['`', '[']) {
+		html += &escape(&rest[..start]);
+		let (open, after) = (&rest[start..start + 1], &rest[start + 1..]);
+		let close = if open == "`" { '`' } else { ']' };
+		let Some(end) = after.find(close) else {
+			html += &escape(open);
+			rest = after;
+			continue;
+		};
+		let inner = &after[..end];
+		if open == "`" {
+			html += &format!("<code>{}</code>", escape(inner));
+		} else {
+			// Names that are not documented stay as they were written
+			match docs.resolve(package, inner) {
+				Some(link) => html += &format!("<a href=\"{}\"><code>{}</code></a>", escape(&url(&link)), escape(inner)),
+				None => html += &escape(&format!("[{}]", inner))
+			}
+		}
+		rest = &after[end + 1..];
+	}
+	html += &escape(rest);
+	html += "</p>\n";
+	html
+}
+
+fn escape(text: &str) -> String {
+	let mut out = String::with_capacity(text.len());
+	for c in text.chars() {
+		match c {
+			'&' => out += "&amp;",
+			'<' => out += "&lt;",
+			'>' => out += "&gt;",
+			'"' => out += "&quot;",
+			'\'' => out += "&#39;",
+			c => out.push(c)
+		}
+	}
+	out
+}
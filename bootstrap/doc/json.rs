@@ -0,0 +1,58 @@
+use crate::diagnostics::json::string;
+use crate::doc::{Docs, Item, Segment};
+
+// The docs for tools, as one JSON object:
+//
+//   {"packages": [{"name": "", "items": [item, ...]}, ...]}
+//
+// An item is `{"name": "Dog", "kind": "class", "signature": "class Dog :
+// Animal", "links": [...], "doc": "...", "file": "src/main.gl", "line": 3,
+// "anchor": "Dog", "members": [item, ...]}`. `links` are the parts of the
+// signature that name documented types: each has the `text` it is, the
+// `offset` in characters it starts at, and the `package` and `anchor` of its
+// docs. `doc` is null for declarations without a doc comment.
+pub fn to_json(docs: &Docs) -> String {
+	let packages: Vec<String> = docs
+		.packages
+		.iter()
+		.map(|package| {
+			let items: Vec<String> = package.items.iter().map(item).collect();
+			format!("{{\"name\":{},\"items\":[{}]}}", string(&package.name), items.join(","))
+		})
+		.collect();
+	format!("{{\"packages\":[{}]}}\n", packages.join(","))
+}
+
+fn item(item: &Item) -> String {
+	let members: Vec<String> = item.members.iter().map(self::item).collect();
+	format!(
+		"{{\"name\":{},\"kind\":{},\"signature\":{},\"links\":[{}],\"doc\":{},\"file\":{},\"line\":{},\"anchor\":{},\"members\":[{}]}}",
+		string(&item.name),
+		string(item.kind),
+		string(&item.signature_text()),
+		links(&item.signature).join(","),
+		item.doc.as_deref().map_or("null".to_string(), string),
+		string(&item.path),
+		item.line,
+		string(&item.anchor),
+		members.join(",")
+	)
+}
+
+fn links(signature: &[Segment]) -> Vec<String> {
+	let mut offset = 0;
+	let mut links = Vec::new();
+	for segment in signature {
+		if let Some(link) = &segment.link {
+			links.push(format!(
+				"{{\"text\":{},\"offset\":{},\"package\":{},\"anchor\":{}}}",
+				string(&segment.text),
+				offset,
+				string(&link.package),
+				string(&link.anchor)
+			));
+		}
+		offset += segment.text.chars().count();
+	}
+	links
+}
@@ -0,0 +1,436 @@
+pub mod html;
+pub mod json;
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::ast::stmt::Visibility;
+use crate::sema::hir::{ClassKind, FnKind, Function, Program, StaticTarget};
+use crate::sema::query::Symbol;
+use crate::sema::types::{FnId, Type, TypeId, TypeParamId};
+
+// What `dotfun doc` documents: the declarations other code can use, by
+// package, each with its signature and doc comment. Private declarations and
+// those of files left out, like dependencies', are not in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Docs {
+	// In order by name; the root package's name is empty
+	pub packages: Vec<Package>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Package {
+	pub name: String,
+	pub items: Vec<Item>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+	pub name: String,
+	// The keyword that declares it, like `class` or `fn`, or for members of
+	// enums `variant`
+	pub kind: &'static str,
+	// Its declaration, without the body, with type names linked to their docs
+	pub signature: Vec<Segment>,
+	pub doc: Option<String>,
+	// Fields, constructors, methods and variants of types
+	pub members: Vec<Item>,
+	// Unique within its package: `Name` or `Name.member`
+	pub anchor: String,
+	pub path: String,
+	pub line: i64
+}
+
+// Part of a signature, and where to find the docs of what it names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+	pub text: String,
+	pub link: Option<Link>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Link {
+	pub package: String,
+	pub anchor: String
+}
+
+impl Item {
+	pub fn signature_text(&self) -> String {
+		self.signature.iter().map(|segment| segment.text.as_str()).collect()
+	}
+}
+
+impl Docs {
+	// Documents the declarations of the files `documented` says to.
+	pub fn new(program: &Program, documented: impl Fn(usize) -> bool) -> Docs {
+		let mut builder = Builder { program, classes: HashMap::new(), segments: Vec::new() };
+		let mut packages: BTreeMap<String, Vec<(usize, i64, Item)>> = BTreeMap::new();
+
+		let classes: Vec<TypeId> = (0..program.classes.len() as u32)
+			.map(TypeId)
+			.filter(|&id| {
+				let class = program.class(id);
+				documented(class.file) && class.modifiers.visibility != Visibility::Private
+			})
+			.collect();
+		for &id in &classes {
+			let class = program.class(id);
+			builder.classes.insert(id, Link { package: class.package.clone(), anchor: class.name.clone() });
+		}
+		for id in classes {
+			let class = program.class(id);
+			let item = builder.class(id);
+			packages.entry(class.package.clone()).or_default().push((class.file, class.span.line, item));
+		}
+		for (index, function) in program.functions.iter().enumerate() {
+			let top_level = matches!(function.kind, FnKind::Function | FnKind::Extension);
+			if top_level && documented(function.file) && function.modifiers.visibility != Visibility::Private {
+				let package = program.files[function.file].package.clone();
+				let item = builder.function(FnId(index as u32), None);
+				packages.entry(package).or_default().push((function.file, function.span.line, item));
+			}
+		}
+		for global in program.globals.iter().filter(|global| documented(global.file) && global.class.is_none()) {
+			let kind = if global.constant { "const" } else if global.mutable { "mut" } else { "val" };
+			builder.variable(kind, &global.name, &global.ty);
+			let item = builder.item(&global.name, kind, &global.doc, global.name.clone(), global.file, global.span.line);
+			packages.entry(program.files[global.file].package.clone()).or_default().push((global.file, global.span.line, item));
+		}
+
+		let packages = packages
+			.into_iter()
+			.map(|(name, mut items)| {
+				items.sort_by_key(|(file, line, _)| (*file, *line));
+				Package { name, items: items.into_iter().map(|(_, _, item)| item).collect() }
+			})
+			.collect();
+		Docs { packages }
+	}
+
+	// Every item and member, with the package it is in.
+	pub fn all(&self) -> impl Iterator<Item = (&Package, &Item)> {
+		self.packages.iter().flat_map(|package| {
+			package.items.iter().flat_map(move |item| std::iter::once((package, item)).chain(item.members.iter().map(move |member| (package, member))))
+		})
+	}
+
+	// Where `[name]` in a doc comment of package `from` points: `Name` or
+	// `Name.member`, in the same package first, or `package.Name`.
+	pub fn resolve(&self, from: &str, name: &str) -> Option<Link> {
+		let find = |package: &Package, anchor: &str| package.items.iter().chain(package.items.iter().flat_map(|item| &item.members)).any(|item| item.anchor == anchor);
+		let link = |package: &Package, anchor: &str| Link { package: package.name.clone(), anchor: anchor.to_string() };
+		if let Some(package) = self.packages.iter().find(|package| package.name == from)
+			&& find(package, name)
+		{
+			return Some(link(package, name));
+		}
+		for package in &self.packages {
+			if let Some(anchor) = name.strip_prefix(package.name.as_str()).and_then(|rest| rest.strip_prefix('.'))
+				&& find(package, anchor)
+			{
+				return Some(link(package, anchor));
+			}
+		}
+		let mut found = self.packages.iter().filter(|package| find(package, name));
+		let package = found.next()?;
+		found.next().is_none().then(|| link(package, name))
+	}
+}
+
+// The signature of what `symbol` declares, written as in the docs but
+// without links, and its doc comment.
+pub fn describe(program: &Program, symbol: Symbol) -> (String, Option<String>) {
+	let mut builder = Builder { program, classes: HashMap::new(), segments: Vec::new() };
+	let doc = match symbol {
+		Symbol::Class(id) => {
+			let item = builder.class(id);
+			builder.segments = item.signature;
+			item.doc
+		}
+		Symbol::Function(id) => {
+			let item = builder.function(id, None);
+			builder.segments = item.signature;
+			item.doc
+		}
+		Symbol::Field(class, index) => {
+			let field = &program.class(class).fields[index];
+			builder.visibility(field.visibility);
+			builder.variable(if field.mutable { "mut" } else { "val" }, &field.name, &field.ty);
+			field.doc.clone()
+		}
+		Symbol::Variant(class, index) => {
+			let variant = &program.class(class).variants[index];
+			builder.text(&variant.name);
+			variant.doc.clone()
+		}
+		Symbol::Global(id) => {
+			let global = program.global(id);
+			let kind = if global.constant { "const" } else if global.mutable { "mut" } else { "val" };
+			builder.variable(&format!("{}{}", if global.class.is_some() { "static " } else { "" }, kind), &global.name, &global.ty);
+			global.doc.clone()
+		}
+		Symbol::Local(id) => {
+			let local = program.local(id);
+			builder.variable(if local.mutable { "mut" } else { "val" }, &local.name, &local.ty);
+			None
+		}
+	};
+	(builder.segments.iter().map(|segment| segment.text.as_str()).collect(), doc)
+}
+
+struct Builder<'p> {
+	program: &'p Program,
+	// The classes documented, and so linked to
+	classes: HashMap<TypeId, Link>,
+	// The signature being written
+	segments: Vec<Segment>
+}
+
+impl<'p> Builder<'p> {
+	fn item(&mut self, name: &str, kind: &'static str, doc: &Option<String>, anchor: String, file: usize, line: i64) -> Item {
+		Item {
+			name: name.to_string(),
+			kind,
+			signature: std::mem::take(&mut self.segments),
+			doc: doc.clone(),
+			members: Vec::new(),
+			anchor,
+			path: self.program.files[file].path.clone(),
+			line
+		}
+	}
+
+	fn text(&mut self, text: &str) {
+		match self.segments.last_mut() {
+			Some(last) if last.link.is_none() => last.text.push_str(text),
+			_ => self.segments.push(Segment { text: text.to_string(), link: None })
+		}
+	}
+
+	fn class(&mut self, id: TypeId) -> Item {
+		let program = self.program;
+		let class = program.class(id);
+		let modifiers = &class.modifiers;
+		let kind = match class.kind {
+			ClassKind::Class => "class",
+			ClassKind::Interface => "interface",
+			ClassKind::Enum => "enum",
+			ClassKind::Struct => "struct"
+		};
+		if modifiers.is_sealed {
+			self.text("sealed ");
+		} else if modifiers.is_abstract && class.kind == ClassKind::Class {
+			self.text("abstract ");
+		}
+		if modifiers.is_data {
+			self.text("data ");
+		}
+		self.text(&format!("{} {}", kind, class.name));
+		self.type_params(&class.type_params);
+		for (index, supertype) in class.supertypes.iter().enumerate() {
+			self.text(if index == 0 { " : " } else { ", " });
+			self.ty(supertype);
+		}
+		let mut item = self.item(&class.name, kind, &class.doc, class.name.clone(), class.file, class.span.line);
+
+		for variant in &class.variants {
+			self.text(&variant.name);
+			let anchor = format!("{}.{}", class.name, variant.name);
+			item.members.push(self.item(&variant.name, "variant", &variant.doc, anchor, class.file, variant.span.line));
+		}
+		for field in class.fields.iter().filter(|field| field.visibility != Visibility::Private) {
+			self.visibility(field.visibility);
+			self.variable(if field.mutable { "mut" } else { "val" }, &field.name, &field.ty);
+			let anchor = format!("{}.{}", class.name, field.name);
+			item.members.push(self.item(&field.name, "field", &field.doc, anchor, class.file, field.span.line));
+		}
+		for (index, &constructor) in class.constructors.iter().enumerate() {
+			if program.function(constructor).modifiers.visibility != Visibility::Private {
+				// Overloads each get an anchor of their own
+				let anchor = if index == 0 { format!("{}.constructor", class.name) } else { format!("{}.constructor-{}", class.name, index + 1) };
+				item.members.push(self.function(constructor, Some(anchor)));
+			}
+		}
+		for &method in &class.methods {
+			if program.function(method).modifiers.visibility != Visibility::Private {
+				let anchor = format!("{}.{}", class.name, program.function(method).name);
+				item.members.push(self.function(method, Some(anchor)));
+			}
+		}
+		for member in class.statics.iter().filter(|member| member.visibility != Visibility::Private) {
+			let anchor = format!("{}.{}", class.name, member.name);
+			match member.target {
+				StaticTarget::Field(global) => {
+					let global = program.global(global);
+					self.visibility(member.visibility);
+					self.variable(if global.mutable { "static mut" } else { "static val" }, &global.name, &global.ty);
+					item.members.push(self.item(&member.name, "field", &global.doc, anchor, class.file, member.span.line));
+				}
+				StaticTarget::Method(method) => item.members.push(self.function(method, Some(anchor)))
+			}
+		}
+		item
+	}
+
+	fn function(&mut self, id: FnId, anchor: Option<String>) -> Item {
+		let function: &Function = self.program.function(id);
+		let modifiers = &function.modifiers;
+		self.visibility(modifiers.visibility);
+		if modifiers.is_override {
+			self.text("override ");
+		}
+		if modifiers.is_static {
+			self.text("static ");
+		}
+		if modifiers.is_abstract && matches!(function.kind, FnKind::Method(_)) {
+			self.text("abstract ");
+		}
+		if modifiers.is_async {
+			self.text("async ");
+		}
+		if let Some(library) = &modifiers.library {
+			self.text(&format!("extern {:?} ", library));
+		}
+		let kind = match function.kind {
+			FnKind::Constructor(_) => {
+				self.text("constructor");
+				"constructor"
+			}
+			_ => {
+				self.text("fn ");
+				// As written: before an extension's receiver, which may use
+				// them, and after the name otherwise
+				if let Some(receiver) = &function.receiver {
+					if !function.type_params.is_empty() {
+						self.type_params(&function.type_params);
+						self.text(" ");
+					}
+					self.ty(receiver);
+					self.text(".");
+					self.text(&function.name);
+				} else {
+					self.text(&function.name);
+					self.type_params(&function.type_params);
+				}
+				if matches!(function.kind, FnKind::Method(_) | FnKind::Static(_)) { "method" } else { "fn" }
+			}
+		};
+		self.text("(");
+		for (index, param) in function.params.iter().enumerate() {
+			if index > 0 {
+				self.text(", ");
+			}
+			let name = &self.program.local(param.local).name;
+			self.text(&format!("{}{}: ", if param.variadic { "..." } else { "" }, name));
+			// `...rest: T` is an array of T, but is written as what each
+			// argument is
+			match &param.ty {
+				Type::Array(each) if param.variadic => self.ty(each),
+				ty => self.ty(ty)
+			}
+			if param.default.is_some() {
+				self.text(" = ...");
+			}
+		}
+		self.text(")");
+		// Functions without a return type return `Any`, so it goes unwritten
+		if !matches!(function.kind, FnKind::Constructor(_)) && !matches!(function.ret, Type::Void | Type::Any) {
+			self.text(": ");
+			self.ty(&function.ret);
+		}
+		for (index, thrown) in function.throws.iter().enumerate() {
+			self.text(if index == 0 { " throws " } else { ", " });
+			self.ty(thrown);
+		}
+		let anchor = anchor.unwrap_or_else(|| function.name.clone());
+		self.item(&function.name, kind, &function.doc, anchor, function.file, function.span.line)
+	}
+
+	fn variable(&mut self, kind: &str, name: &str, ty: &Type) {
+		self.text(&format!("{} {}: ", kind, name));
+		self.ty(ty);
+	}
+
+	fn visibility(&mut self, visibility: Visibility) {
+		if visibility == Visibility::Protected {
+			self.text("protected ");
+		}
+	}
+
+	fn type_params(&mut self, params: &[TypeParamId]) {
+		if params.is_empty() {
+			return;
+		}
+		self.text("<");
+		for (index, &id) in params.iter().enumerate() {
+			if index > 0 {
+				self.text(", ");
+			}
+			let param = self.program.type_param(id);
+			self.text(&param.name);
+			for (index, bound) in param.bounds.iter().enumerate() {
+				self.text(if index == 0 { ": " } else { " & " });
+				self.ty(bound);
+			}
+		}
+		self.text(">");
+	}
+
+	// Writes a type as `Display` does, linking the classes in it.
+	fn ty(&mut self, ty: &Type) {
+		let parenthesized = |inner: &Type| matches!(inner, Type::Function { .. } | Type::Union(_));
+		match ty {
+			Type::Nullable(inner) | Type::Array(inner) => {
+				if parenthesized(inner) {
+					self.text("(");
+					self.ty(inner);
+					self.text(")");
+				} else {
+					self.ty(inner);
+				}
+				self.text(if matches!(ty, Type::Nullable(_)) { "?" } else { "[]" });
+			}
+			Type::Function { params, ret } => {
+				self.text("(");
+				for (index, param) in params.iter().enumerate() {
+					if index > 0 {
+						self.text(", ");
+					}
+					self.ty(param);
+				}
+				self.text(") -> ");
+				self.ty(ret);
+			}
+			Type::Class { id, name, args } => {
+				match self.classes.get(id) {
+					Some(link) => self.segments.push(Segment { text: name.clone(), link: Some(link.clone()) }),
+					None => self.text(name)
+				}
+				if !args.is_empty() {
+					self.text("<");
+					for (index, arg) in args.iter().enumerate() {
+						if index > 0 {
+							self.text(", ");
+						}
+						self.ty(arg);
+					}
+					self.text(">");
+				}
+			}
+			Type::Union(members) => {
+				for (index, member) in members.iter().enumerate() {
+					if index > 0 {
+						self.text(" | ");
+					}
+					if matches!(member, Type::Function { .. }) {
+						self.text("(");
+						self.ty(member);
+						self.text(")");
+					} else {
+						self.ty(member);
+					}
+				}
+			}
+			_ => self.text(&ty.to_string())
+		}
+	}
+}
@@ -0,0 +1,438 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::bytecode::file::EXTENSION;
+use crate::diagnostics::ErrorFormat;
+use crate::diagnostics::lints::LintLevels;
+use crate::interpreter::gc::Settings;
+
+pub const USAGE: &str = "usage: dotfun <command> [options] [<file>...]
+
+commands:
+    run [<file>...]       check a program and run it, or run a .dfc file
+                          that build made
+    build [<file>...]     check a program and compile it to a .dfc file, which
+                          run can run without compiling it again, or with
+                          --target native to an executable
+    check [<file>...]     check a program without running it, printing only
+                          diagnostics; exits with 1 if there are errors
+    test [<file>...]      run the functions marked @Test
+    debug [<file>...]     run a program in the debugger, which stops before
+                          the first line and at breakpoints to step through
+                          it and look at its variables
+    dap                   serve the Debug Adapter Protocol on stdin and
+                          stdout, for editors to debug programs with
+    tokens [<file>...]    print the tokens of each file
+    ast [<file>...]       print the syntax tree of each file
+    fix [<file>...]       make the fixes diagnostics are sure of
+    fmt [<file>...]       format files in place, or stdin to stdout
+    doc [<file>...]       write HTML and JSON docs of a program's public
+                          declarations
+    new <dir>             make a project in a new directory, ready to run
+    init [<dir>]          make a project of a directory, by default this one
+    add <source>          add a dependency to the project, from a directory or
+                          a git URL
+    explain <code>        explain a diagnostic code, like E0200
+
+options:
+    --emit <what>         also print tokens, ast, hir or bytecode while
+                          compiling, or with <what>=<file> write them to the
+                          file; may be a list, like --emit tokens,hir=program.hir
+    --error-format <f>    print diagnostics as human (the default) or json
+    --color <when>        color diagnostics: auto (the default), always or never
+    --watch               with run, check or test, build again whenever a file
+                          changes, until stopped with Ctrl-C; changes are
+                          noticed through inotify on Linux, and elsewhere by
+                          looking at the files every 200ms
+    --rev <rev>           with add, the branch, tag or commit of a git
+                          dependency to use
+    --filter <text>       with test, only run tests whose names contain this
+    --list                with test, list the tests instead of running them
+    --out <dir>           with doc, the directory to write to; by default doc
+                          in the project, or in the current directory; with
+                          build, the file to write, by default named after
+                          the project or the first file
+    --target <target>     with build, what to compile to: bytecode (the
+                          default), or native for an executable, made with
+                          llc and the C compiler
+    --lib                 with new or init, make a library rather than a
+                          program
+    --timings[=<file>]    print how long each phase of the compiler took and
+                          what it allocated, or write it to the file as a
+                          Chrome trace
+    --profile[=<file>]    with run, sample where the program spends its time
+                          and count what each function makes, printing a
+                          table of them, or write the stacks sampled to the
+                          file in the folded format flame graph tools read
+    --coverage[=<file>]   with test, count which lines the tests run,
+                          printing how much of each file ran and write an
+                          lcov report to the file, by default lcov.info
+    --gc-threshold <n>    with run or test, collect garbage once the program
+                          has made this many arrays, maps and objects, and
+                          after that once what is left has doubled; by
+                          default 10000
+    --gc-stress           with run or test, collect garbage whenever anything
+                          was made since the last time, to find values the
+                          collector loses
+    --check               with fmt, change nothing but list the files that are
+                          not formatted; exits with 1 if there are any
+    -W <lint>             make a lint warn; 'warnings' stands for every lint
+    -D <lint>             make a lint an error
+    -A <lint>             turn a lint off
+    -h, --help            print this
+    -V, --version         print the version
+
+A directory stands for every .gl file in it, and '-' for source read from
+stdin. With no files, the project is built whose dotfun.toml is in the
+current directory or one above it; a directory with a dotfun.toml stands for
+that project. Arguments after '--' are passed to the program 'run' or 'debug'
+runs.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+	Run,
+	Build,
+	Check,
+	Test,
+	Debug,
+	Dap,
+	Tokens,
+	Ast,
+	Fix,
+	Fmt,
+	Doc,
+	New,
+	Init,
+	Add,
+	Explain,
+	Help,
+	Version
+}
+
+impl Command {
+	fn named(name: &str) -> Option<Command> {
+		match name {
+			"run" => Some(Command::Run),
+			"build" => Some(Command::Build),
+			"check" => Some(Command::Check),
+			"test" => Some(Command::Test),
+			"debug" => Some(Command::Debug),
+			"dap" => Some(Command::Dap),
+			"tokens" => Some(Command::Tokens),
+			"ast" => Some(Command::Ast),
+			"fix" => Some(Command::Fix),
+			"fmt" => Some(Command::Fmt),
+			"doc" => Some(Command::Doc),
+			"new" => Some(Command::New),
+			"init" => Some(Command::Init),
+			"add" => Some(Command::Add),
+			"explain" => Some(Command::Explain),
+			"help" => Some(Command::Help),
+			"version" => Some(Command::Version),
+			_ => None
+		}
+	}
+}
+
+// Something to print on the way, for looking at what the compiler makes of
+// a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+	Tokens,
+	Ast,
+	Hir,
+	Bytecode
+}
+
+impl FromStr for Emit {
+	type Err = String;
+
+	fn from_str(name: &str) -> Result<Self, Self::Err> {
+		match name {
+			"tokens" => Ok(Emit::Tokens),
+			"ast" => Ok(Emit::Ast),
+			"hir" => Ok(Emit::Hir),
+			"bytecode" => Ok(Emit::Bytecode),
+			_ => Err(format!("Unknown --emit '{}'; expected 'tokens', 'ast', 'hir' or 'bytecode'", name))
+		}
+	}
+}
+
+// What `build` compiles a program to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Target {
+	// A .dfc file for the VM
+	#[default]
+	Bytecode,
+	// An executable, through LLVM
+	Native
+}
+
+impl FromStr for Target {
+	type Err = String;
+
+	fn from_str(name: &str) -> Result<Self, Self::Err> {
+		match name {
+			"bytecode" => Ok(Target::Bytecode),
+			"native" => Ok(Target::Native),
+			_ => Err(format!("Unknown --target '{}'; expected 'bytecode' or 'native'", name))
+		}
+	}
+}
+
+// How `--timings` reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Timings {
+	// A table on stderr
+	Table,
+	// Chrome trace JSON written to the file
+	Trace(String)
+}
+
+// How `--profile` reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Profile {
+	// A table of the functions on stderr
+	Table,
+	// The stacks sampled, folded, written to the file
+	Folded(String)
+}
+
+// Where `--coverage` writes its report, when it does not name a file.
+pub const LCOV: &str = "lcov.info";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Color {
+	// When printing to a terminal and `NO_COLOR` is not set
+	#[default]
+	Auto,
+	Always,
+	Never
+}
+
+impl FromStr for Color {
+	type Err = String;
+
+	fn from_str(name: &str) -> Result<Self, Self::Err> {
+		match name {
+			"auto" => Ok(Color::Auto),
+			"always" => Ok(Color::Always),
+			"never" => Ok(Color::Never),
+			_ => Err(format!("Unknown --color '{}'; expected 'auto', 'always' or 'never'", name))
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+	pub command: Command,
+	// The files to work on, or for `explain` the code
+	pub inputs: Vec<String>,
+	// What to print on the way, each to stdout or to the file given
+	pub emit: Vec<(Emit, Option<String>)>,
+	pub error_format: ErrorFormat,
+	pub color: Color,
+	pub lints: LintLevels,
+	// For `fmt`: report files that are not formatted rather than format them
+	pub check: bool,
+	// For `run` and `check`: build again every time a file changes
+	pub watch: bool,
+	// For `add`: the branch, tag or commit of a git dependency
+	pub rev: Option<String>,
+	// For `test`: which tests to run, by part of their name
+	pub filter: Option<String>,
+	// For `test`: list the tests rather than run them
+	pub list: bool,
+	// For `doc`: where to write the docs; for `build`, the file to write
+	pub out: Option<String>,
+	// For `build`: what to compile to
+	pub target: Target,
+	// For `new` and `init`: make a library rather than a program
+	pub lib: bool,
+	pub timings: Option<Timings>,
+	// For `run`: how to report where the program spent its time
+	pub profile: Option<Profile>,
+	// For `test`: where to write the lcov report of the lines the tests ran,
+	// when counting them
+	pub coverage: Option<String>,
+	// For `run` and `test`: when to collect garbage
+	pub gc: Settings,
+	// Everything after `--`, for the program `run` runs
+	pub program_args: Vec<String>
+}
+
+impl Options {
+	pub fn emits(&self, emit: Emit) -> bool {
+		self.emit.iter().any(|(what, _)| *what == emit)
+	}
+
+	// The file `emit` is written to, when it is not stdout.
+	pub fn emit_path(&self, emit: Emit) -> Option<&str> {
+		self.emit.iter().find(|(what, _)| *what == emit).and_then(|(_, path)| path.as_deref())
+	}
+}
+
+// Reads the command line, without the name of the program. Options can go
+// before or after the command, in `--name value` or `--name=value` form.
+pub fn parse(mut args: Vec<String>) -> Result<Options, String> {
+	let program_args = match args.iter().position(|arg| arg == "--") {
+		Some(split) => {
+			let rest = args.split_off(split + 1);
+			args.pop();
+			rest
+		}
+		None => Vec::new()
+	};
+	let lints = LintLevels::from_args(&mut args)?;
+
+	let mut command = None;
+	let mut inputs = Vec::new();
+	let mut emit = Vec::new();
+	let mut error_format = ErrorFormat::default();
+	let mut color = Color::default();
+	let mut check = false;
+	let mut watch = false;
+	let mut rev = None;
+	let mut filter = None;
+	let mut list = false;
+	let mut out = None;
+	let mut target = Target::default();
+	let mut lib = false;
+	let mut timings = None;
+	let mut profile = None;
+	let mut coverage = None;
+	let mut gc = Settings::default();
+	let mut args = args.into_iter();
+	while let Some(arg) = args.next() {
+		let (name, inline) = match arg.split_once('=') {
+			Some((name, value)) if name.starts_with("--") => (name.to_string(), Some(value.to_string())),
+			_ => (arg.clone(), None)
+		};
+		let mut value = || inline.clone().or_else(|| args.next()).ok_or_else(|| format!("'{}' needs a value", name));
+		match name.as_str() {
+			"-h" | "--help" => command = Some(Command::Help),
+			"-V" | "--version" => command = Some(Command::Version),
+			"--explain" => {
+				command = Some(Command::Explain);
+				inputs.push(value()?);
+			}
+			"--emit" => {
+				for name in value()?.split(',') {
+					let (name, path) = match name.split_once('=') {
+						Some((name, path)) if !path.is_empty() => (name, Some(path.to_string())),
+						Some((name, _)) => return Err(format!("'--emit {}=' needs a file", name.trim())),
+						None => (name, None)
+					};
+					let what: Emit = name.trim().parse()?;
+					if emit.iter().any(|(seen, _)| *seen == what) {
+						return Err(format!("'--emit {}' is given twice", name.trim()));
+					}
+					emit.push((what, path));
+				}
+			}
+			"--error-format" => error_format = value()?.parse()?,
+			"--color" => color = value()?.parse()?,
+			"--check" => check = true,
+			"--watch" => watch = true,
+			"--rev" => rev = Some(value()?),
+			"--filter" => filter = Some(value()?),
+			"--list" => list = true,
+			"--out" => out = Some(value()?),
+			"--target" => target = value()?.parse()?,
+			"--lib" => lib = true,
+			"--gc-threshold" => {
+				let count = value()?;
+				gc.threshold = count.parse().ok().filter(|&count| count > 0).ok_or_else(|| format!("'--gc-threshold {}' needs a count above 0", count))?;
+			}
+			"--gc-stress" => gc.stress = true,
+			// Only `--timings=<file>` names a file, so that inputs can follow
+			"--timings" => {
+				timings = Some(match inline.as_deref() {
+					Some("") => return Err("'--timings=' needs a file".to_string()),
+					Some(path) => Timings::Trace(path.to_string()),
+					None => Timings::Table
+				})
+			}
+			// Like `--timings`, only `--profile=<file>` names a file
+			"--profile" => {
+				profile = Some(match inline.as_deref() {
+					Some("") => return Err("'--profile=' needs a file".to_string()),
+					Some(path) => Profile::Folded(path.to_string()),
+					None => Profile::Table
+				})
+			}
+			// As does `--coverage=<file>`, which is otherwise written to `LCOV`
+			"--coverage" => {
+				coverage = Some(match inline.as_deref() {
+					Some("") => return Err("'--coverage=' needs a file".to_string()),
+					Some(path) => path.to_string(),
+					None => LCOV.to_string()
+				})
+			}
+			_ if name.starts_with('-') && name != "-" => return Err(format!("Unknown option '{}'", name)),
+			_ if command.is_none() => {
+				command = Some(Command::named(&arg).ok_or_else(|| format!("Unknown command '{}'", arg))?);
+			}
+			_ => inputs.push(arg)
+		}
+	}
+
+	let command = command.ok_or("Expected a command")?;
+	if command == Command::Explain && inputs.len() != 1 {
+		return Err("'explain' takes one diagnostic code".to_string());
+	}
+	if command == Command::Add && inputs.len() != 1 {
+		return Err("'add' takes one directory or git URL".to_string());
+	}
+	if command == Command::New && inputs.len() != 1 {
+		return Err("'new' takes the directory to make".to_string());
+	}
+	if command == Command::Dap && !inputs.is_empty() {
+		return Err("'dap' takes no files; the editor says what to debug".to_string());
+	}
+	if command == Command::Init && inputs.len() > 1 {
+		return Err("'init' takes at most one directory".to_string());
+	}
+	if lib && !matches!(command, Command::New | Command::Init) {
+		return Err("'--lib' only goes with 'new' or 'init'".to_string());
+	}
+	if rev.is_some() && command != Command::Add {
+		return Err("'--rev' only goes with 'add'".to_string());
+	}
+	if (filter.is_some() || list) && command != Command::Test {
+		return Err(format!("'{}' only goes with 'test'", if list { "--list" } else { "--filter" }));
+	}
+	if out.is_some() && !matches!(command, Command::Doc | Command::Build) {
+		return Err("'--out' only goes with 'doc' or 'build'".to_string());
+	}
+	if target != Target::default() && command != Command::Build {
+		return Err("'--target' only goes with 'build'".to_string());
+	}
+	if gc != Settings::default() && !matches!(command, Command::Run | Command::Test) {
+		return Err(format!("'{}' only goes with 'run' or 'test'", if gc.stress { "--gc-stress" } else { "--gc-threshold" }));
+	}
+	if profile.is_some() && command != Command::Run {
+		return Err("'--profile' only goes with 'run'".to_string());
+	}
+	if coverage.is_some() && command != Command::Test {
+		return Err("'--coverage' only goes with 'test'".to_string());
+	}
+	if check && command != Command::Fmt {
+		return Err("'--check' only goes with 'fmt'".to_string());
+	}
+	if watch && !matches!(command, Command::Run | Command::Check | Command::Test) {
+		return Err("'--watch' only goes with 'run', 'check' or 'test'".to_string());
+	}
+	if watch && inputs.iter().any(|input| input == "-") {
+		return Err("Cannot watch stdin".to_string());
+	}
+	if command == Command::Debug && inputs.iter().any(|input| input == "-") {
+		return Err("Cannot debug stdin, which the debugger reads its commands from".to_string());
+	}
+	let compiled = inputs.iter().any(|input| Path::new(input).extension().is_some_and(|extension| extension == EXTENSION));
+	let takes_files = !matches!(command, Command::New | Command::Init | Command::Add | Command::Explain);
+	if compiled && takes_files && (command != Command::Run || inputs.len() > 1 || watch) {
+		return Err(format!("A .{} file can only be run, on its own", EXTENSION));
+	}
+	Ok(Options { command, inputs, emit, error_format, color, lints, check, watch, rev, filter, list, out, target, lib, timings, profile, coverage, gc, program_args })
+}
@@ -0,0 +1,356 @@
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::mem;
+use std::path::PathBuf;
+use std::process;
+use std::rc::Rc;
+
+use crate::bytecode::Module;
+use crate::bytecode::debugger::{self, Debugger, Frontend, Reason, Resume};
+use crate::bytecode::vm::{self, Vm};
+use crate::diagnostics::ErrorFormat;
+use crate::diagnostics::ice::{self, Phase};
+use crate::diagnostics::source_map::SourceMap;
+use crate::driver::json::{self, Json};
+use crate::driver::{FAILURE, SUCCESS, Session, args, running};
+use crate::interpreter::value::Value;
+
+// `dotfun dap`: the debugger, for editors, over the Debug Adapter Protocol.
+// The editor starts it and sends it requests on stdin, and it answers and
+// tells of events on stdout, each message a JSON object after a
+// `Content-Length` header.
+//
+// A session goes: `initialize`, then `launch` with the `program` to debug,
+// which is checked and compiled, its `args` and whether to `stopOnEntry`,
+// then the breakpoints, then `configurationDone`, after which the program
+// runs. What it prints is sent as `output` events. When it stops, the
+// editor asks for the stack, the scopes of a frame and their variables, and
+// to evaluate expressions, until it says to go on. There is one thread, and
+// requests are only read while the program is stopped, so it cannot be
+// paused while it runs.
+
+// The id of the only thread
+const THREAD: i64 = 1;
+
+// Serves one debugging session, until the editor disconnects or its input
+// ends.
+pub fn serve<'a>(input: Box<dyn BufRead + 'a>, output: Box<dyn Write + 'a>) -> u8 {
+	let connection = Rc::new(RefCell::new(Connection { input, output, seq: 0 }));
+	// Of each file by its path, the lines of its breakpoints, until the
+	// program runs and the debugger has them
+	let mut breakpoints: HashMap<PathBuf, Vec<i64>> = HashMap::new();
+	let mut launched = None;
+	let mut configured = false;
+	loop {
+		let Some(request) = connection.borrow_mut().read() else {
+			return SUCCESS;
+		};
+		let arguments = request.get("arguments");
+		let command = request.get("command").as_str().unwrap_or_default();
+		let result = match command {
+			"initialize" => {
+				let capabilities = Json::object([("supportsConfigurationDoneRequest", true.into()), ("supportsEvaluateForHovers", true.into())]);
+				connection.borrow_mut().respond(&request, capabilities);
+				connection.borrow_mut().event("initialized", Json::object([]));
+				continue;
+			}
+			"launch" => launch(&connection, arguments).map(|launch| {
+				launched = Some(launch);
+				Json::object([])
+			}),
+			"setBreakpoints" => {
+				let lines = lines(arguments);
+				let body = verified(&lines, true);
+				breakpoints.insert(canonical(arguments.get("source").get("path").as_str().unwrap_or_default()), lines);
+				Ok(body)
+			}
+			"configurationDone" => {
+				configured = true;
+				Ok(Json::object([]))
+			}
+			"threads" => Ok(threads()),
+			"disconnect" | "terminate" => {
+				connection.borrow_mut().respond(&request, Json::object([]));
+				return SUCCESS;
+			}
+			_ => Err(format!("dotfun cannot do '{}' now", command))
+		};
+		connection.borrow_mut().reply(&request, result);
+		if configured && let Some(launch) = launched.take() {
+			run(&connection, launch, &breakpoints);
+		}
+	}
+}
+
+// A program ready to debug.
+struct Launch {
+	module: Module,
+	sources: SourceMap,
+	args: Vec<String>,
+	stop: bool
+}
+
+// Checks and compiles the program `launch` names, sending its diagnostics
+// to the editor as output.
+fn launch(connection: &Rc<RefCell<Connection>>, arguments: &Json) -> Result<Launch, String> {
+	let program = arguments.get("program").as_str().ok_or("'launch' needs the 'program' to debug")?;
+	let mut line = vec!["debug".to_string(), program.to_string()];
+	let args: Vec<String> = arguments.get("args").items().iter().filter_map(|arg| arg.as_str().map(str::to_string)).collect();
+	if !args.is_empty() {
+		line.push("--".to_string());
+		line.extend(args);
+	}
+	let options = args::parse(line)?;
+	let mut session = Session::load(&options)?;
+	let program = session.check();
+	for diagnostic in mem::take(&mut session.diagnostics) {
+		output(connection, "stderr", ErrorFormat::Human.emit(&diagnostic, &session.sources, false));
+	}
+	let program = program.ok_or("The program has errors")?;
+	let module = session.compile(&program);
+	Ok(Launch { module, sources: mem::take(&mut session.sources), args: options.program_args, stop: arguments.get("stopOnEntry").as_bool().unwrap_or(false) })
+}
+
+// Runs the program under the debugger, then tells the editor it has ended.
+fn run(connection: &Rc<RefCell<Connection>>, launch: Launch, breakpoints: &HashMap<PathBuf, Vec<i64>>) {
+	let module = &launch.module;
+	let files: Vec<PathBuf> = module.files.iter().map(|path| canonical(path)).collect();
+	let set = files.iter().enumerate().flat_map(|(file, path)| breakpoints.get(path).into_iter().flatten().map(move |&line| (file, line))).collect();
+	let editor = Editor { connection: connection.clone(), files, references: Vec::new() };
+	let debugger = Debugger::new(Box::new(editor), set, launch.stop);
+	ice::enter(Phase::Running, module.files.join(", "));
+	let mut out = Output(connection.clone());
+	let code = match vm::debug(module, &running(&launch.args), &launch.args, debugger, &mut out) {
+		Ok(()) => SUCCESS,
+		Err(uncaught) => {
+			output(connection, "stderr", ErrorFormat::Human.emit(&uncaught.diagnostic(&module.files), &launch.sources, false));
+			FAILURE
+		}
+	};
+	connection.borrow_mut().event("exited", Json::object([("exitCode", i64::from(code).into())]));
+	connection.borrow_mut().event("terminated", Json::object([]));
+}
+
+// What a value is to the editor: a reference to what to list when it asks
+// for the variables under it, valid until the program goes on.
+#[derive(Clone)]
+enum Reference {
+	Locals(usize),
+	Globals,
+	Value(Value)
+}
+
+// The debugger's frontend: tells the editor the program stopped, and
+// answers it until it says how to go on.
+struct Editor<'a> {
+	connection: Rc<RefCell<Connection<'a>>>,
+	// Of each file of the module, its path as the editor has it
+	files: Vec<PathBuf>,
+	// By their number less one
+	references: Vec<Reference>
+}
+
+impl Frontend for Editor<'_> {
+	fn paused(&mut self, vm: &Vm, breakpoints: &mut BTreeSet<(usize, i64)>, reason: Reason) -> Resume {
+		self.references.clear();
+		let reason = match reason {
+			Reason::Entry => "entry",
+			Reason::Breakpoint => "breakpoint",
+			Reason::Step => "step"
+		};
+		let stopped = Json::object([("reason", reason.into()), ("threadId", THREAD.into()), ("allThreadsStopped", true.into())]);
+		self.connection.borrow_mut().event("stopped", stopped);
+		loop {
+			// With the editor gone, there is no one to debug for
+			let Some(request) = self.connection.borrow_mut().read() else {
+				process::exit(SUCCESS.into());
+			};
+			let resume = match request.get("command").as_str().unwrap_or_default() {
+				"continue" => Resume::Continue,
+				"next" => Resume::StepOver,
+				"stepIn" => Resume::StepInto,
+				"stepOut" => Resume::StepOut,
+				"disconnect" | "terminate" => {
+					self.connection.borrow_mut().respond(&request, Json::object([]));
+					process::exit(SUCCESS.into());
+				}
+				_ => {
+					let result = self.answer(vm, breakpoints, &request);
+					self.connection.borrow_mut().reply(&request, result);
+					continue;
+				}
+			};
+			self.connection.borrow_mut().respond(&request, Json::object([("allThreadsContinued", true.into())]));
+			return resume;
+		}
+	}
+}
+
+impl Editor<'_> {
+	fn answer(&mut self, vm: &Vm, breakpoints: &mut BTreeSet<(usize, i64)>, request: &Json) -> Result<Json, String> {
+		let arguments = request.get("arguments");
+		Ok(match request.get("command").as_str().unwrap_or_default() {
+			"threads" => threads(),
+			"stackTrace" => {
+				let backtrace = vm.backtrace();
+				let start = arguments.get("startFrame").as_i64().unwrap_or(0).max(0) as usize;
+				let levels = arguments.get("levels").as_i64().filter(|&levels| levels > 0).map_or(backtrace.len(), |levels| levels as usize);
+				let frames = backtrace
+					.iter()
+					.enumerate()
+					.skip(start)
+					.take(levels)
+					.map(|(depth, frame)| {
+						let source = Json::object([("path", self.files[frame.file].display().to_string().into())]);
+						Json::object([
+							("id", depth.into()),
+							("name", frame.function.as_str().into()),
+							("source", source),
+							("line", frame.span.line.into()),
+							("column", frame.span.column.max(1).into())
+						])
+					})
+					.collect();
+				Json::object([("stackFrames", Json::Array(frames)), ("totalFrames", backtrace.len().into())])
+			}
+			"scopes" => {
+				let depth = arguments.get("frameId").as_i64().unwrap_or(0) as usize;
+				let scopes = [("Locals", Reference::Locals(depth)), ("Globals", Reference::Globals)].map(|(name, reference)| {
+					let reference = self.reference(reference);
+					Json::object([("name", name.into()), ("variablesReference", reference.into()), ("expensive", false.into())])
+				});
+				Json::object([("scopes", Json::Array(scopes.to_vec()))])
+			}
+			"variables" => {
+				let number = arguments.get("variablesReference").as_i64().unwrap_or(0);
+				let reference = usize::try_from(number - 1).ok().and_then(|index| self.references.get(index));
+				let variables = match reference.cloned().ok_or_else(|| format!("There are no variables {}", number))? {
+					Reference::Locals(depth) => vm.locals(depth),
+					Reference::Globals => vm.globals(),
+					Reference::Value(value) => debugger::members(&value)
+				};
+				let variables = variables
+					.into_iter()
+					.map(|(name, value)| {
+						Json::object([
+							("name", name.into()),
+							("value", debugger::show(&value).into()),
+							("type", value.type_name().into()),
+							("variablesReference", self.expand(value).into())
+						])
+					})
+					.collect();
+				Json::object([("variables", Json::Array(variables))])
+			}
+			"evaluate" => {
+				let depth = arguments.get("frameId").as_i64().unwrap_or(0) as usize;
+				let value = debugger::evaluate(vm, depth, arguments.get("expression").as_str().unwrap_or_default())?;
+				Json::object([("result", debugger::show(&value).into()), ("variablesReference", self.expand(value).into())])
+			}
+			"setBreakpoints" => {
+				let path = canonical(arguments.get("source").get("path").as_str().unwrap_or_default());
+				let lines = lines(arguments);
+				let file = self.files.iter().position(|file| *file == path);
+				if let Some(file) = file {
+					breakpoints.retain(|&(at, _)| at != file);
+					breakpoints.extend(lines.iter().map(|&line| (file, line)));
+				}
+				verified(&lines, file.is_some())
+			}
+			command => return Err(format!("dotfun cannot do '{}' while the program is stopped", command))
+		})
+	}
+
+	fn reference(&mut self, reference: Reference) -> usize {
+		self.references.push(reference);
+		self.references.len()
+	}
+
+	// The reference for what a value is made of, or 0 when it is made of
+	// nothing.
+	fn expand(&mut self, value: Value) -> usize {
+		match debugger::members(&value).is_empty() {
+			true => 0,
+			false => self.reference(Reference::Value(value))
+		}
+	}
+}
+
+// What the program prints, sent to the editor.
+struct Output<'a>(Rc<RefCell<Connection<'a>>>);
+
+impl Write for Output<'_> {
+	fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+		output(&self.0, "stdout", String::from_utf8_lossy(bytes).into_owned());
+		Ok(bytes.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+fn output(connection: &Rc<RefCell<Connection>>, category: &str, text: String) {
+	connection.borrow_mut().event("output", Json::object([("category", category.into()), ("output", text.into())]));
+}
+
+fn threads() -> Json {
+	Json::object([("threads", Json::Array(vec![Json::object([("id", THREAD.into()), ("name", "main".into())])]))])
+}
+
+// The lines of the breakpoints `setBreakpoints` sets.
+fn lines(arguments: &Json) -> Vec<i64> {
+	arguments.get("breakpoints").items().iter().filter_map(|breakpoint| breakpoint.get("line").as_i64()).collect()
+}
+
+fn verified(lines: &[i64], verified: bool) -> Json {
+	let breakpoints = lines.iter().map(|&line| Json::object([("verified", verified.into()), ("line", line.into())])).collect();
+	Json::object([("breakpoints", Json::Array(breakpoints))])
+}
+
+// Paths as the editor and the module may name the same file differently.
+fn canonical(path: &str) -> PathBuf {
+	fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
+}
+
+// Messages to and from the editor.
+struct Connection<'a> {
+	input: Box<dyn BufRead + 'a>,
+	output: Box<dyn Write + 'a>,
+	// Of the last message sent
+	seq: i64
+}
+
+impl Connection<'_> {
+	fn read(&mut self) -> Option<Json> {
+		json::read_message(&mut self.input)
+	}
+
+	fn send(&mut self, kind: &str, mut message: Json) {
+		self.seq += 1;
+		if let Json::Object(object) = &mut message {
+			object.insert("seq".to_string(), self.seq.into());
+			object.insert("type".to_string(), kind.into());
+		}
+		json::write_message(&mut self.output, &message);
+	}
+
+	fn respond(&mut self, request: &Json, body: Json) {
+		self.reply(request, Ok(body));
+	}
+
+	fn reply(&mut self, request: &Json, result: Result<Json, String>) {
+		let (success, key, value) = match result {
+			Ok(body) => (true, "body", body),
+			Err(message) => (false, "message", message.into())
+		};
+		let response = Json::object([("request_seq", request.get("seq").clone()), ("command", request.get("command").clone()), ("success", success.into()), (key, value)]);
+		self.send("response", response);
+	}
+
+	fn event(&mut self, event: &str, body: Json) {
+		self.send("event", Json::object([("event", event.into()), ("body", body)]));
+	}
+}
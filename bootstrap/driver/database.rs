@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use crate::ast::Program;
+use crate::diagnostics::Diagnostic;
+use crate::driver::parallel;
+use crate::sema::check::Bodies;
+use crate::sema::hir;
+
+// What the compiler has worked out about a set of files, kept between builds
+// so that only what a change affects is worked out again. Watch mode keeps
+// one for as long as it runs, and anything else that builds the same files
+// over and over can too.
+//
+// Each source file is an input, set with `set_source`. Every time an input
+// changes the database moves to a new revision, and the input remembers that
+// revision. Queries, like parsing a file, remember the revision they were
+// worked out in and the inputs they read, and are answered from memory as
+// long as none of those inputs has changed since. Checking, which reads every
+// file, is also kept apart for each package, so a change only has the
+// packages it could affect checked again.
+#[derive(Debug, Default)]
+pub struct Database {
+	revision: u64,
+	// By path: its text and the revision it last changed in
+	sources: HashMap<String, (String, u64)>,
+	// By path: what the file parsed to
+	parses: HashMap<String, Memo<Parsed>>,
+	// What the files checked to, with the files it was for and the package
+	// each is in without a `package` statement
+	checked: Option<(Files, Memo<Checked>)>,
+	// What the bodies of each package checked to
+	bodies: Bodies
+}
+
+pub type Parsed = Result<Program, Vec<Diagnostic>>;
+pub type Checked = Result<hir::Program, Vec<Diagnostic>>;
+type Files = Vec<(String, Option<String>)>;
+
+// The answer to a query, and the revision it was worked out in.
+#[derive(Debug)]
+struct Memo<T> {
+	value: T,
+	revision: u64
+}
+
+impl Database {
+	pub fn new() -> Database {
+		Database::default()
+	}
+
+	pub fn revision(&self) -> u64 {
+		self.revision
+	}
+
+	// Sets the text of a file, moving to a new revision if it is not what
+	// the file had.
+	pub fn set_source(&mut self, path: &str, text: &str) {
+		if self.sources.get(path).is_some_and(|(old, _)| old == text) {
+			return;
+		}
+		self.revision += 1;
+		self.sources.insert(path.to_string(), (text.to_string(), self.revision));
+	}
+
+	// Sets the text of a file along with what it parses to, as worked out
+	// elsewhere, like by an editor's incremental parser, so that it is not
+	// parsed again.
+	pub fn set_parsed(&mut self, path: &str, text: &str, parsed: Parsed) {
+		self.set_source(path, text);
+		self.parses.insert(path.to_string(), Memo { value: parsed, revision: self.revision });
+	}
+
+	pub fn source(&self, path: &str) -> Option<&str> {
+		self.sources.get(path).map(|(text, _)| text.as_str())
+	}
+
+	// Forgets the files not in `paths`, and what was worked out from them.
+	pub fn retain_sources(&mut self, paths: &[String]) {
+		let before = self.sources.len();
+		self.sources.retain(|path, _| paths.contains(path));
+		self.parses.retain(|path, _| paths.contains(path));
+		if self.sources.len() != before {
+			self.revision += 1;
+		}
+	}
+
+	// The revision the file last changed in.
+	fn changed(&self, path: &str) -> u64 {
+		self.sources.get(path).map_or(u64::MAX, |(_, revision)| *revision)
+	}
+
+	// What each file parses to, parsing with `parse` the ones that changed
+	// since they were last parsed, or were never parsed, on as many threads
+	// as there are cores. With `memoize` off every file is parsed again, for
+	// when parsing does more than work out the answer, like printing it.
+	pub fn parse(&mut self, paths: &[String], memoize: bool, parse: impl Fn(usize, &str) -> Parsed + Sync) -> Vec<Parsed> {
+		let stale: Vec<usize> = (0..paths.len())
+			.filter(|&file| !memoize || self.parses.get(&paths[file]).is_none_or(|memo| memo.revision < self.changed(&paths[file])))
+			.collect();
+		let texts: Vec<&str> = paths.iter().map(|path| self.source(path).unwrap_or("")).collect();
+		let fresh = if memoize {
+			parallel::map(&stale, |&file| parse(file, texts[file]))
+		} else {
+			stale.iter().map(|&file| parse(file, texts[file])).collect()
+		};
+		for (file, parsed) in stale.into_iter().zip(fresh) {
+			self.parses.insert(paths[file].clone(), Memo { value: parsed, revision: self.revision });
+		}
+		paths.iter().map(|path| self.parses[path].value.clone()).collect()
+	}
+
+	// What the files check to, checking them with `check` unless none of
+	// them has changed since they were last checked. `files` are the paths
+	// with the package each is in without a `package` statement. `check` is
+	// given what was kept of each package's bodies, to check only those of
+	// packages a change could affect again.
+	pub fn check(&mut self, files: &[(String, Option<String>)], check: impl FnOnce(&mut Bodies) -> Checked) -> Checked {
+		if let Some((checked_files, memo)) = &self.checked
+			&& checked_files == files
+			&& files.iter().all(|(path, _)| self.changed(path) <= memo.revision)
+		{
+			return memo.value.clone();
+		}
+		let checked = check(&mut self.bodies);
+		self.checked = Some((files.to_vec(), Memo { value: checked.clone(), revision: self.revision }));
+		checked
+	}
+}
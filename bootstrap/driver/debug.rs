@@ -0,0 +1,147 @@
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+use std::process;
+
+use crate::bytecode::debugger::{self, Frontend, Reason, Resume};
+use crate::bytecode::vm::Vm;
+use crate::diagnostics::source_map::SourceMap;
+use crate::driver::SUCCESS;
+
+pub const HELP: &str = "commands:
+    break <file>:<line>   stop whenever the line is reached; b for short, and
+                          the file can be left out for the one stopped in
+    delete <file>:<line>  stop at the line no more
+    continue, c           go on until a breakpoint
+    next, n               go on to the next line, over the calls it makes
+    step, s               go on to the next line, into the calls it makes
+    finish                go on until the call stopped in returns
+    backtrace, bt         print the calls in progress, innermost first
+    frame <n>             look at the call n out from the innermost
+    locals                print the locals of the call looked at
+    print <expr>, p       print what an expression is in the call looked at
+    quit, q               stop the program and the debugger";
+
+// `dotfun debug` on the console: each time the program stops, where it is
+// and the line it is on are printed, then commands are read, one a line,
+// until one says how to go on. At the end of input, the program runs to
+// the end without stopping.
+pub struct Console<'a> {
+	sources: &'a SourceMap,
+	input: Box<dyn BufRead + 'a>,
+	output: Box<dyn Write + 'a>,
+	// The call looked at, as calls out from the innermost
+	frame: usize
+}
+
+impl<'a> Console<'a> {
+	pub fn new(sources: &'a SourceMap, input: Box<dyn BufRead + 'a>, output: Box<dyn Write + 'a>) -> Console<'a> {
+		Console { sources, input, output, frame: 0 }
+	}
+
+	// Does one command, returning how to go on if it says.
+	fn command(&mut self, vm: &Vm, breakpoints: &mut BTreeSet<(usize, i64)>, line: &str) -> Result<Option<Resume>, String> {
+		let (command, rest) = line.split_once(char::is_whitespace).map_or((line, ""), |(command, rest)| (command, rest.trim()));
+		match command {
+			"continue" | "c" => return Ok(Some(Resume::Continue)),
+			"next" | "n" => return Ok(Some(Resume::StepOver)),
+			"step" | "s" => return Ok(Some(Resume::StepInto)),
+			"finish" => return Ok(Some(Resume::StepOut)),
+			"break" | "b" | "delete" => {
+				let (file, line) = self.breakpoint(vm, rest)?;
+				let path = &vm.module().files[file];
+				match command {
+					"delete" if !breakpoints.remove(&(file, line)) => return Err(format!("There is no breakpoint at {}:{}", path, line)),
+					"delete" => self.say(format!("Deleted the breakpoint at {}:{}", path, line)),
+					_ => {
+						breakpoints.insert((file, line));
+						self.say(format!("Breakpoint at {}:{}", path, line));
+					}
+				}
+			}
+			"backtrace" | "bt" => {
+				for (depth, frame) in vm.backtrace().iter().enumerate() {
+					let marker = if depth == self.frame { '>' } else { ' ' };
+					self.say(format!("{} #{} {} at {}:{}", marker, depth, frame.function, vm.module().files[frame.file], frame.span.line));
+				}
+			}
+			"frame" => {
+				let frame = rest.parse().ok().filter(|&frame| frame < vm.backtrace().len()).ok_or_else(|| format!("There is no frame '{}'", rest))?;
+				self.frame = frame;
+				self.locate(vm);
+			}
+			"locals" => {
+				for (name, value) in vm.locals(self.frame) {
+					self.say(format!("{} = {}", name, debugger::show(&value)));
+				}
+			}
+			"print" | "p" => {
+				let value = debugger::evaluate(vm, self.frame, rest)?;
+				self.say(debugger::show(&value));
+			}
+			"help" | "h" => self.say(HELP),
+			"quit" | "q" => process::exit(SUCCESS as i32),
+			"" => {}
+			_ => return Err(format!("Unknown command '{}'; 'help' lists them", command))
+		}
+		Ok(None)
+	}
+
+	// The file and line of a breakpoint, as `file:line` or only the line,
+	// in the file stopped in. A file is found by the end of its path.
+	fn breakpoint(&self, vm: &Vm, text: &str) -> Result<(usize, i64), String> {
+		let (file, line) = match text.rsplit_once(':') {
+			Some((name, line)) => {
+				let files = &vm.module().files;
+				let file = files.iter().position(|path| path == name || path.ends_with(&format!("/{}", name)));
+				(file.ok_or_else(|| format!("There is no file '{}'", name))?, line)
+			}
+			None => (vm.location().map_or(0, |location| location.file), text)
+		};
+		let line = line.parse().ok().filter(|&line: &i64| line > 0).ok_or_else(|| format!("Expected a line, not '{}'", line))?;
+		Ok((file, line))
+	}
+
+	// Prints where the call looked at is, and its line.
+	fn locate(&mut self, vm: &Vm) {
+		let Some(frame) = vm.backtrace().into_iter().nth(self.frame) else {
+			return;
+		};
+		self.say(format!("{} at {}:{}", frame.function, vm.module().files[frame.file], frame.span.line));
+		if let Some(source) = self.sources.get(frame.file)
+			&& frame.span.line >= 1
+			&& (frame.span.line as usize) <= source.line_count()
+		{
+			self.say(format!("{:>5} | {}", frame.span.line, source.line(frame.span.line as usize)));
+		}
+	}
+
+	fn say(&mut self, text: impl AsRef<str>) {
+		let _ = writeln!(self.output, "{}", text.as_ref());
+	}
+}
+
+impl Frontend for Console<'_> {
+	fn paused(&mut self, vm: &Vm, breakpoints: &mut BTreeSet<(usize, i64)>, reason: Reason) -> Resume {
+		self.frame = 0;
+		match reason {
+			Reason::Entry => self.say("Stopped before the first line; 'help' lists the commands"),
+			Reason::Breakpoint => self.say("Stopped at a breakpoint"),
+			Reason::Step => {}
+		}
+		self.locate(vm);
+		loop {
+			let _ = write!(self.output, "(debug) ");
+			let _ = self.output.flush();
+			let mut line = String::new();
+			if self.input.read_line(&mut line).unwrap_or(0) == 0 {
+				breakpoints.clear();
+				return Resume::Continue;
+			}
+			match self.command(vm, breakpoints, line.trim()) {
+				Ok(Some(resume)) => return resume,
+				Ok(None) => {}
+				Err(message) => self.say(format!("error: {}", message))
+			}
+		}
+	}
+}
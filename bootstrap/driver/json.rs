@@ -0,0 +1,325 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{BufRead, Write};
+
+use crate::diagnostics::json::string;
+
+// JSON, for the protocols editors speak to dotfun in: what they send is read
+// into a `Json`, and what is sent back is made of one. Programs' `json.parse`
+// reads with it too.
+
+pub type Object = BTreeMap<String, Json>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Json>),
+	Object(Object)
+}
+
+impl Json {
+	// An object of the entries given.
+	pub fn object<'a>(entries: impl IntoIterator<Item = (&'a str, Json)>) -> Json {
+		Json::Object(entries.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+	}
+
+	// The value of an object's `key`, null when there is none.
+	pub fn get(&self, key: &str) -> &Json {
+		match self {
+			Json::Object(object) => object.get(key).unwrap_or(&Json::Null),
+			_ => &Json::Null
+		}
+	}
+
+	pub fn is_null(&self) -> bool {
+		matches!(self, Json::Null)
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match self {
+			Json::String(text) => Some(text),
+			_ => None
+		}
+	}
+
+	// Numbers that are whole, as they are when they count or number things.
+	pub fn as_i64(&self) -> Option<i64> {
+		match self {
+			Json::Number(number) if number.fract() == 0.0 => Some(*number as i64),
+			_ => None
+		}
+	}
+
+	pub fn as_bool(&self) -> Option<bool> {
+		match self {
+			Json::Bool(value) => Some(*value),
+			_ => None
+		}
+	}
+
+	// The items of an array; anything else has none.
+	pub fn items(&self) -> &[Json] {
+		match self {
+			Json::Array(items) => items,
+			_ => &[]
+		}
+	}
+}
+
+impl From<&str> for Json {
+	fn from(text: &str) -> Json {
+		Json::String(text.to_string())
+	}
+}
+
+impl From<String> for Json {
+	fn from(text: String) -> Json {
+		Json::String(text)
+	}
+}
+
+impl From<i64> for Json {
+	fn from(number: i64) -> Json {
+		Json::Number(number as f64)
+	}
+}
+
+impl From<usize> for Json {
+	fn from(number: usize) -> Json {
+		Json::Number(number as f64)
+	}
+}
+
+impl From<bool> for Json {
+	fn from(value: bool) -> Json {
+		Json::Bool(value)
+	}
+}
+
+impl From<Vec<Json>> for Json {
+	fn from(items: Vec<Json>) -> Json {
+		Json::Array(items)
+	}
+}
+
+// On one line, without spaces.
+impl fmt::Display for Json {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Json::Null => write!(f, "null"),
+			Json::Bool(value) => write!(f, "{}", value),
+			Json::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => write!(f, "{}", *number as i64),
+			Json::Number(number) if number.is_finite() => write!(f, "{}", number),
+			Json::Number(_) => write!(f, "null"),
+			Json::String(text) => write!(f, "{}", string(text)),
+			Json::Array(items) => {
+				write!(f, "[")?;
+				for (index, item) in items.iter().enumerate() {
+					if index > 0 {
+						write!(f, ",")?;
+					}
+					write!(f, "{}", item)?;
+				}
+				write!(f, "]")
+			}
+			Json::Object(object) => {
+				write!(f, "{{")?;
+				for (index, (key, value)) in object.iter().enumerate() {
+					if index > 0 {
+						write!(f, ",")?;
+					}
+					write!(f, "{}:{}", string(key), value)?;
+				}
+				write!(f, "}}")
+			}
+		}
+	}
+}
+
+// The next message of a protocol that sends each after a `Content-Length`
+// header, as the Debug Adapter and Language Server Protocols do, or None at
+// the end of input or when what comes is not one.
+pub fn read_message(input: &mut dyn BufRead) -> Option<Json> {
+	let mut length = None;
+	loop {
+		let mut line = String::new();
+		if input.read_line(&mut line).ok()? == 0 {
+			return None;
+		}
+		let line = line.trim_end();
+		if line.is_empty() {
+			break;
+		}
+		if let Some((name, value)) = line.split_once(':')
+			&& name.eq_ignore_ascii_case("Content-Length")
+		{
+			length = value.trim().parse().ok();
+		}
+	}
+	let mut body = vec![0; length?];
+	input.read_exact(&mut body).ok()?;
+	parse(&String::from_utf8_lossy(&body)).ok()
+}
+
+pub fn write_message(output: &mut dyn Write, message: &Json) {
+	let text = message.to_string();
+	let _ = write!(output, "Content-Length: {}\r\n\r\n{}", text.len(), text);
+	let _ = output.flush();
+}
+
+pub fn parse(text: &str) -> Result<Json, String> {
+	let mut reader = Reader { chars: text.chars().collect(), at: 0 };
+	let value = reader.value()?;
+	reader.skip_spaces();
+	match reader.peek() {
+		None => Ok(value),
+		Some(c) => Err(format!("Expected the end of the JSON, found '{}'", c))
+	}
+}
+
+struct Reader {
+	chars: Vec<char>,
+	at: usize
+}
+
+impl Reader {
+	fn peek(&self) -> Option<char> {
+		self.chars.get(self.at).copied()
+	}
+
+	fn next(&mut self) -> Option<char> {
+		let c = self.peek()?;
+		self.at += 1;
+		Some(c)
+	}
+
+	fn skip_spaces(&mut self) {
+		while matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+			self.at += 1;
+		}
+	}
+
+	fn expect(&mut self, expected: char) -> Result<(), String> {
+		self.skip_spaces();
+		match self.next() {
+			Some(c) if c == expected => Ok(()),
+			Some(c) => Err(format!("Expected '{}', found '{}'", expected, c)),
+			None => Err(format!("Expected '{}', found the end of the JSON", expected))
+		}
+	}
+
+	fn value(&mut self) -> Result<Json, String> {
+		self.skip_spaces();
+		match self.peek() {
+			Some('"') => Ok(Json::String(self.string()?)),
+			Some('[') => {
+				self.at += 1;
+				let mut items = Vec::new();
+				self.skip_spaces();
+				if self.peek() == Some(']') {
+					self.at += 1;
+					return Ok(Json::Array(items));
+				}
+				loop {
+					items.push(self.value()?);
+					self.skip_spaces();
+					match self.next() {
+						Some(',') => {}
+						Some(']') => return Ok(Json::Array(items)),
+						_ => return Err("Expected ',' or ']' in an array".to_string())
+					}
+				}
+			}
+			Some('{') => {
+				self.at += 1;
+				let mut object = Object::new();
+				self.skip_spaces();
+				if self.peek() == Some('}') {
+					self.at += 1;
+					return Ok(Json::Object(object));
+				}
+				loop {
+					self.skip_spaces();
+					if self.peek() != Some('"') {
+						return Err("Expected a key in an object".to_string());
+					}
+					let key = self.string()?;
+					self.expect(':')?;
+					let value = self.value()?;
+					object.insert(key, value);
+					self.skip_spaces();
+					match self.next() {
+						Some(',') => {}
+						Some('}') => return Ok(Json::Object(object)),
+						_ => return Err("Expected ',' or '}' in an object".to_string())
+					}
+				}
+			}
+			Some(c) if c.is_ascii_alphanumeric() || c == '-' => {
+				let start = self.at;
+				while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.')) {
+					self.at += 1;
+				}
+				let word: String = self.chars[start..self.at].iter().collect();
+				match word.as_str() {
+					"null" => Ok(Json::Null),
+					"true" => Ok(Json::Bool(true)),
+					"false" => Ok(Json::Bool(false)),
+					_ => word.parse().map(Json::Number).map_err(|_| format!("'{}' is not a JSON value", word))
+				}
+			}
+			Some(c) => Err(format!("Expected a value, found '{}'", c)),
+			None => Err("Expected a value, found the end of the JSON".to_string())
+		}
+	}
+
+	fn string(&mut self) -> Result<String, String> {
+		self.next();
+		let mut text = String::new();
+		loop {
+			match self.next() {
+				None => return Err("Unterminated string".to_string()),
+				Some('"') => return Ok(text),
+				Some('\\') => {
+					let escaped = match self.next() {
+						Some('n') => '\n',
+						Some('t') => '\t',
+						Some('r') => '\r',
+						Some('b') => '\u{8}',
+						Some('f') => '\u{c}',
+						Some(c @ ('"' | '\\' | '/')) => c,
+						Some('u') => self.unicode()?,
+						Some(c) => return Err(format!("Unknown escape '\\{}'", c)),
+						None => return Err("Unterminated string".to_string())
+					};
+					text.push(escaped);
+				}
+				Some(c) => text.push(c)
+			}
+		}
+	}
+
+	// After `\u`: four hex digits, or two sets for a character outside the
+	// Basic Multilingual Plane, as a surrogate pair.
+	fn unicode(&mut self) -> Result<char, String> {
+		let high = self.hex()?;
+		let code = match high {
+			0xD800..=0xDBFF if self.chars.get(self.at..self.at + 2) == Some(&['\\', 'u']) => {
+				self.at += 2;
+				let low = self.hex()?;
+				0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF)
+			}
+			code => code
+		};
+		Ok(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER))
+	}
+
+	fn hex(&mut self) -> Result<u32, String> {
+		let digits: String = self.chars.get(self.at..self.at + 4).ok_or("Expected four hex digits after '\\u'")?.iter().collect();
+		self.at += 4;
+		u32::from_str_radix(&digits, 16).map_err(|_| format!("'{}' is not four hex digits", digits))
+	}
+}
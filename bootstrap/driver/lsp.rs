@@ -0,0 +1,507 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::mem;
+use std::path::Path;
+
+use crate::diagnostics::json::{file_uri, lsp_position};
+use crate::diagnostics::source_map::Source;
+use crate::diagnostics::{Diagnostic, Severity, ice};
+use crate::doc;
+use crate::driver::database::Database;
+use crate::driver::json::{self, Json};
+use crate::driver::manifest::MANIFEST;
+use crate::driver::{FAILURE, SUCCESS, Session, args};
+use crate::lexer::lexer::{KEYWORDS, Lexer};
+use crate::lexer::token::Span;
+use crate::parser::incremental::{IncrementalParser, TextEdit};
+use crate::sema::hir::{self, ClassKind, FnKind};
+use crate::sema::query::{Index, Location, Symbol};
+use crate::sema::types::LocalId;
+
+// `dotfun-lsp`: the language server, for editors, over the Language Server
+// Protocol. The editor starts it and sends it JSON-RPC messages on stdin,
+// framed as `dotfun dap`'s are, and it answers and tells of what it found on
+// stdout.
+//
+// It keeps the text of each document the editor has open as the editor has
+// it, saved or not, changing it range by range as the editor says it
+// changed, and reparsing only the body, member or declaration each change
+// falls in. After each change the document is checked again, with the rest of
+// its project when it is in one, and what is wrong with each of the files is
+// published. What did not change since the last check is not worked out
+// again; see `Database`. Questions about a document, like how to highlight
+// it, are answered from what it last checked to without errors, for as long
+// as it is still what was checked.
+
+// JSON-RPC's error for a method the server does not have
+const METHOD_NOT_FOUND: i64 = -32601;
+
+// The semantic tokens it tells names apart by, which it gives the editor on
+// `initialize`. A token's type is an index into `TOKEN_TYPES`, and its
+// modifiers bits in the order of `TOKEN_MODIFIERS`.
+const TOKEN_TYPES: &[&str] = &["keyword", "class", "enum", "interface", "struct", "enumMember", "function", "method", "property", "parameter", "variable"];
+const TOKEN_MODIFIERS: &[&str] = &["declaration", "readonly", "static"];
+const DECLARATION: usize = 1;
+const READONLY: usize = 2;
+const STATIC: usize = 4;
+
+// Serves one editor, until it says to exit or its input ends. Exits with
+// success only when the editor asked to shut down first.
+pub fn serve<'a>(input: Box<dyn BufRead + 'a>, output: Box<dyn Write + 'a>) -> u8 {
+	let mut server = Server {
+		input,
+		output,
+		documents: HashMap::new(),
+		databases: HashMap::new(),
+		checked: HashMap::new(),
+		published: HashSet::new(),
+		shutdown: false
+	};
+	loop {
+		let Some(message) = json::read_message(&mut server.input) else {
+			return if server.shutdown { SUCCESS } else { FAILURE };
+		};
+		let params = message.get("params");
+		match message.get("method").as_str().unwrap_or_default() {
+			"initialize" => {
+				let sync = Json::object([("openClose", true.into()), ("change", 2i64.into())]);
+				let legend = Json::object([("tokenTypes", names(TOKEN_TYPES)), ("tokenModifiers", names(TOKEN_MODIFIERS))]);
+				let tokens = Json::object([("legend", legend), ("full", true.into())]);
+				let capabilities = Json::object([
+					("textDocumentSync", sync),
+					("semanticTokensProvider", tokens),
+					("hoverProvider", true.into()),
+					("definitionProvider", true.into()),
+					("referencesProvider", true.into())
+				]);
+				let info = Json::object([("name", "dotfun-lsp".into()), ("version", env!("CARGO_PKG_VERSION").into())]);
+				server.respond(&message, Json::object([("capabilities", capabilities), ("serverInfo", info)]));
+			}
+			"shutdown" => {
+				server.shutdown = true;
+				server.respond(&message, Json::Null);
+			}
+			"exit" => return if server.shutdown { SUCCESS } else { FAILURE },
+			"textDocument/didOpen" => {
+				let document = params.get("textDocument");
+				let uri = document.get("uri").as_str().unwrap_or_default().to_string();
+				server.documents.insert(uri.clone(), IncrementalParser::new(document.get("text").as_str().unwrap_or_default().to_string()));
+				server.diagnose(&uri);
+			}
+			"textDocument/didChange" => {
+				let uri = params.get("textDocument").get("uri").as_str().unwrap_or_default().to_string();
+				let Some(document) = server.documents.get_mut(&uri) else {
+					continue;
+				};
+				for change in params.get("contentChanges").items() {
+					edit(document, change);
+				}
+				server.diagnose(&uri);
+			}
+			"textDocument/didClose" => {
+				let uri = params.get("textDocument").get("uri").as_str().unwrap_or_default().to_string();
+				server.documents.remove(&uri);
+				server.published.remove(&uri);
+				server.publish(&uri, Vec::new());
+			}
+			"textDocument/semanticTokens/full" => {
+				let data = server.semantic_tokens(params.get("textDocument").get("uri").as_str().unwrap_or_default());
+				server.respond(&message, Json::object([("data", data.into())]));
+			}
+			"textDocument/hover" => {
+				let hover = server.hover(params);
+				server.respond(&message, hover.unwrap_or(Json::Null));
+			}
+			"textDocument/definition" => {
+				let definitions = server.definitions(params);
+				server.respond(&message, definitions.into());
+			}
+			"textDocument/references" => {
+				let references = server.references(params);
+				server.respond(&message, references.into());
+			}
+			method if !message.get("id").is_null() => {
+				let error = Json::object([("code", METHOD_NOT_FOUND.into()), ("message", format!("dotfun-lsp cannot do '{}'", method).into())]);
+				server.send(Json::object([("id", message.get("id").clone()), ("error", error)]));
+			}
+			// Notifications it has no use for, like `initialized`
+			_ => {}
+		}
+	}
+}
+
+struct Server<'a> {
+	input: Box<dyn BufRead + 'a>,
+	output: Box<dyn Write + 'a>,
+	// By URI, the text of each open document and what it parses to, which
+	// each change reparses only as much of as it has to
+	documents: HashMap<String, IncrementalParser>,
+	// By the project or file checked, what was worked out about it
+	databases: HashMap<String, Database>,
+	// By the project or file checked, what it last checked to without errors
+	checked: HashMap<String, Checked>,
+	// The URIs last published with diagnostics, which are cleared once
+	// there are none
+	published: HashSet<String>,
+	shutdown: bool
+}
+
+// A program that checked without errors, and the path and text of each of
+// its files but the standard library's.
+struct Checked {
+	program: hir::Program,
+	index: Index,
+	texts: Vec<(String, String)>
+}
+
+impl Server<'_> {
+	// Checks the document at `uri` and publishes the diagnostics of every
+	// file checked with it.
+	fn diagnose(&mut self, uri: &str) {
+		let path = path(uri);
+		// Files in a project are checked with the rest of it
+		let input = match uri.starts_with("file://") {
+			true => Path::new(&path).ancestors().skip(1).find(|dir| dir.join(MANIFEST).is_file()).map_or(path.clone(), |dir| dir.display().to_string()),
+			false => path
+		};
+		let options = match args::parse(vec!["check".to_string(), input.clone()]) {
+			Ok(options) => options,
+			Err(message) => return self.show(message)
+		};
+		let open: HashMap<String, String> = self.documents.iter().map(|(uri, document)| (self::path(uri), document.source().to_string())).collect();
+		let mut session = match Session::editing(&options, &open) {
+			Ok(session) => session,
+			Err(message) => return self.show(message)
+		};
+		session.database = self.databases.remove(&input).unwrap_or_default();
+		// Open documents are parsed as they change, so checking uses those trees
+		for (uri, document) in &self.documents {
+			let path = self::path(uri);
+			if session.paths.contains(&path) {
+				session.database.set_parsed(&path, document.source(), document.parsed());
+			}
+		}
+		// A compiler bug in one check leaves the server to serve the next
+		let program = ice::catch(|| session.check()).unwrap_or_else(|ice| {
+			session.database = Database::new();
+			self.show(ice.to_string());
+			None
+		});
+		self.databases.insert(input.clone(), mem::take(&mut session.database));
+		if let Some(program) = program {
+			let text = |file| session.sources.get(file).map(|source| source.text.clone()).unwrap_or_default();
+			let texts = session.paths.iter().enumerate().map(|(file, path)| (path.clone(), text(file))).collect();
+			self.checked.insert(input, Checked { index: Index::new(&program), program, texts });
+		}
+
+		let mut diagnostics: Vec<Vec<Json>> = vec![Vec::new(); session.paths.len()];
+		for diagnostic in &session.diagnostics {
+			if let Some(source) = session.sources.get(diagnostic.file)
+				&& let Some(file) = diagnostics.get_mut(diagnostic.file)
+			{
+				file.push(self::diagnostic(diagnostic, source));
+			}
+		}
+		let published = mem::take(&mut self.published);
+		for (path, diagnostics) in session.paths.iter().zip(diagnostics) {
+			let uri = self.uri(path);
+			if !diagnostics.is_empty() {
+				self.published.insert(uri.clone());
+			} else if !published.contains(&uri) && !self.documents.contains_key(&uri) {
+				continue;
+			}
+			self.publish(&uri, diagnostics);
+		}
+		// Files the check no longer covers keep what they had
+		self.published.extend(published.into_iter().filter(|uri| !session.paths.contains(&self::path(uri))));
+	}
+
+	// On a name, the signature of what it refers to and its doc comment, and
+	// elsewhere in an expression, the type of the innermost one.
+	fn hover(&self, params: &Json) -> Option<Json> {
+		let (checked, file, offset) = self.at(params)?;
+		let (path, text) = &checked.texts[file];
+		let fenced = |code: &str| format!("```dotfun\n{}\n```", code);
+		let (contents, span) = match checked.index.name_at(file, offset) {
+			Some((span, symbols)) => {
+				let mut sections: Vec<String> = Vec::new();
+				for &symbol in &symbols {
+					let (signature, doc) = doc::describe(&checked.program, symbol);
+					sections.push(fenced(&signature));
+					sections.extend(doc);
+				}
+				let span = named(text, span, symbols[0].name(&checked.program)).map_or(span, |(start, end)| Span { start, end, ..span });
+				(sections.join("\n\n"), span)
+			}
+			None => {
+				let (ty, span) = checked.index.type_at(file, offset)?;
+				(fenced(&ty.to_string()), span)
+			}
+		};
+		let source = Source::new(path.as_str(), text.as_str());
+		let contents = Json::object([("kind", "markdown".into()), ("value", contents.into())]);
+		Some(Json::object([("contents", contents), ("range", range(&source, span))]))
+	}
+
+	// Where what the name at a position refers to is declared. Names in
+	// imports of overloaded functions refer to every overload.
+	fn definitions(&self, params: &Json) -> Vec<Json> {
+		let Some((checked, file, offset)) = self.at(params) else {
+			return Vec::new();
+		};
+		let mut locations: Vec<Json> = Vec::new();
+		for symbol in checked.index.symbols_at(file, offset) {
+			if let Some(location) = checked.index.definition(symbol).and_then(|location| self.location(checked, symbol, location))
+				&& !locations.contains(&location)
+			{
+				locations.push(location);
+			}
+		}
+		locations
+	}
+
+	// Everywhere what the name at a position refers to is used, in any file
+	// checked with it, and where it is declared if the editor asks for that.
+	fn references(&self, params: &Json) -> Vec<Json> {
+		let Some((checked, file, offset)) = self.at(params) else {
+			return Vec::new();
+		};
+		let Some(symbol) = checked.index.symbol_at(file, offset) else {
+			return Vec::new();
+		};
+		let declaration = checked.index.definition(symbol).filter(|_| params.get("context").get("includeDeclaration").as_bool() == Some(true));
+		let locations = declaration.into_iter().chain(checked.index.references_of(symbol));
+		locations.filter_map(|location| self.location(checked, symbol, location)).collect()
+	}
+
+	// What the document of a position last checked to, which file of it the
+	// document is and the offset of the position in it.
+	fn at(&self, params: &Json) -> Option<(&Checked, usize, usize)> {
+		let uri = params.get("textDocument").get("uri").as_str()?;
+		let (checked, file) = self.checked(uri)?;
+		Some((checked, file, offset(&checked.texts[file].1, params.get("position"))))
+	}
+
+	// An LSP location of a name referring to `symbol`, at the name alone
+	// when it can be found in the span. The standard library's files have
+	// no location.
+	fn location(&self, checked: &Checked, symbol: Symbol, location: Location) -> Option<Json> {
+		let (path, text) = checked.texts.get(location.file)?;
+		let source = Source::new(path.as_str(), text.as_str());
+		let span = named(text, location.span, symbol.name(&checked.program)).map_or(location.span, |(start, end)| Span { start, end, ..location.span });
+		Some(Json::object([("uri", self.uri(path).into()), ("range", range(&source, span))]))
+	}
+
+	// The URI of the file at `path`: the one the editor has it open under,
+	// if it does.
+	fn uri(&self, path: &str) -> String {
+		self.documents.keys().find(|uri| self::path(uri) == path).cloned().unwrap_or_else(|| file_uri(path))
+	}
+
+	// What the document at `uri` last checked to without errors, and which
+	// file of it the document is, while it is still as it was then.
+	fn checked(&self, uri: &str) -> Option<(&Checked, usize)> {
+		let (path, text) = (self::path(uri), self.documents.get(uri)?.source());
+		self.checked.values().find_map(|checked| Some((checked, checked.texts.iter().position(|(at, was)| *at == path && was == text)?)))
+	}
+
+	// The keywords of a document and its names, by what each refers to, as
+	// LSP encodes semantic tokens: five numbers for each, in order, of its
+	// line and start, both relative to the token before, its length, type
+	// and modifiers.
+	fn semantic_tokens(&self, uri: &str) -> Vec<Json> {
+		let Some(text) = self.documents.get(uri).map(IncrementalParser::source) else {
+			return Vec::new();
+		};
+		// Of each token, where it starts and ends, its type and its modifiers
+		let mut tokens: Vec<(usize, usize, usize, usize)> = Vec::new();
+		if let Ok(lexed) = Lexer::new(text.to_string()).lex() {
+			let keywords = lexed.iter().filter(|token| KEYWORDS.iter().any(|(_, keyword)| *keyword == token.token_type));
+			tokens.extend(keywords.map(|token| (token.span.start, token.span.end, token_type("keyword"), 0)));
+		}
+		if let Some((checked, file)) = self.checked(uri) {
+			let program = &checked.program;
+			let parameters: HashSet<LocalId> = program.functions.iter().flat_map(|function| function.params.iter().map(|param| param.local)).collect();
+			for (symbol, span, declared) in checked.index.names(file) {
+				let Some((start, end)) = named(text, span, symbol.name(program)) else {
+					continue;
+				};
+				let (ty, modifiers) = classify(program, symbol, &parameters);
+				tokens.push((start, end, ty, if declared { modifiers | DECLARATION } else { modifiers }));
+			}
+		}
+		tokens.sort_by_key(|&(start, ..)| start);
+		tokens.dedup_by_key(|&mut (start, ..)| start);
+
+		let source = Source::new(path(uri), text);
+		let mut data = Vec::new();
+		let (mut line, mut character) = (0, 0);
+		for (start, end, ty, modifiers) in tokens {
+			let (at_line, at_character) = lsp_position(&source, start);
+			let relative = if at_line == line { at_character - character } else { at_character };
+			data.extend([at_line - line, relative, text[start..end].encode_utf16().count(), ty, modifiers].map(Json::from));
+			(line, character) = (at_line, at_character);
+		}
+		data
+	}
+
+	fn publish(&mut self, uri: &str, diagnostics: Vec<Json>) {
+		let params = Json::object([("uri", uri.into()), ("diagnostics", diagnostics.into())]);
+		self.notify("textDocument/publishDiagnostics", params);
+	}
+
+	// Tells the editor's user what kept a document from being checked.
+	fn show(&mut self, message: String) {
+		self.notify("window/showMessage", Json::object([("type", 1i64.into()), ("message", message.into())]));
+	}
+
+	fn respond(&mut self, request: &Json, result: Json) {
+		self.send(Json::object([("id", request.get("id").clone()), ("result", result)]));
+	}
+
+	fn notify(&mut self, method: &str, params: Json) {
+		self.send(Json::object([("method", method.into()), ("params", params)]));
+	}
+
+	fn send(&mut self, mut message: Json) {
+		if let Json::Object(object) = &mut message {
+			object.insert("jsonrpc".to_string(), "2.0".into());
+		}
+		json::write_message(&mut self.output, &message);
+	}
+}
+
+// Where in `span` of `text` the name is: all of it, or its start or end for
+// spans like `Color.Red`'s, when it is there.
+fn named(text: &str, span: Span, name: &str) -> Option<(usize, usize)> {
+	let spanned = text.get(span.start..span.end).filter(|_| !name.is_empty())?;
+	match (spanned.starts_with(name), spanned.ends_with(name)) {
+		(true, _) => Some((span.start, span.start + name.len())),
+		(false, true) => Some((span.end - name.len(), span.end)),
+		(false, false) => None
+	}
+}
+
+// The semantic token type and modifiers of a name referring to `symbol`.
+fn classify(program: &hir::Program, symbol: Symbol, parameters: &HashSet<LocalId>) -> (usize, usize) {
+	let readonly = |mutable: bool| if mutable { 0 } else { READONLY };
+	match symbol {
+		Symbol::Class(id) => match program.class(id).kind {
+			ClassKind::Class => (token_type("class"), 0),
+			ClassKind::Interface => (token_type("interface"), 0),
+			ClassKind::Enum => (token_type("enum"), 0),
+			ClassKind::Struct => (token_type("struct"), 0)
+		},
+		Symbol::Function(id) => match program.function(id).kind {
+			FnKind::Constructor(_) => (token_type("class"), 0),
+			FnKind::Method(_) | FnKind::Getter(_) | FnKind::Setter(_) => (token_type("method"), 0),
+			FnKind::Static(_) => (token_type("method"), STATIC),
+			FnKind::Function | FnKind::Extension | FnKind::Lambda => (token_type("function"), 0)
+		},
+		Symbol::Field(class, index) => (token_type("property"), readonly(program.class(class).fields[index].mutable)),
+		Symbol::Variant(..) => (token_type("enumMember"), 0),
+		Symbol::Global(id) => match program.global(id) {
+			global if global.class.is_some() => (token_type("property"), readonly(global.mutable) | STATIC),
+			global => (token_type("variable"), readonly(global.mutable))
+		},
+		Symbol::Local(id) if parameters.contains(&id) => (token_type("parameter"), readonly(program.local(id).mutable)),
+		Symbol::Local(id) => (token_type("variable"), readonly(program.local(id).mutable))
+	}
+}
+
+fn token_type(name: &str) -> usize {
+	TOKEN_TYPES.iter().position(|ty| *ty == name).unwrap_or_default()
+}
+
+fn names(names: &[&str]) -> Json {
+	Json::Array(names.iter().map(|&name| name.into()).collect())
+}
+
+// Applies a change the editor made to a document: to a range of it, or
+// without one, to all of it.
+fn edit(document: &mut IncrementalParser, change: &Json) {
+	let new = change.get("text").as_str().unwrap_or_default();
+	let range = change.get("range");
+	if range.is_null() {
+		*document = IncrementalParser::new(new.to_string());
+		return;
+	}
+	let start = offset(document.source(), range.get("start"));
+	let end = offset(document.source(), range.get("end")).max(start);
+	// Offsets fall on characters of the text, so the edit always applies
+	let _ = document.apply(TextEdit { start, end, text: new.to_string() });
+}
+
+// The byte offset of an LSP position: a line from 0, and a character in
+// UTF-16 code units. Positions past the end of a line are at its end, and
+// past the last line at the end of the text.
+fn offset(text: &str, position: &Json) -> usize {
+	let line = position.get("line").as_i64().unwrap_or(0).max(0) as usize;
+	let character = position.get("character").as_i64().unwrap_or(0).max(0) as usize;
+	let Some(start) = (0..line).try_fold(0, |start, _| text[start..].find('\n').map(|end| start + end + 1)) else {
+		return text.len();
+	};
+	let mut units = 0;
+	for (at, c) in text[start..].char_indices() {
+		if units >= character || c == '\n' {
+			return start + at;
+		}
+		units += c.len_utf16();
+	}
+	text.len()
+}
+
+fn range(source: &Source, span: Span) -> Json {
+	let position = |offset: usize| {
+		let (line, character) = lsp_position(source, offset);
+		Json::object([("line", line.into()), ("character", character.into())])
+	};
+	Json::object([("start", position(span.start)), ("end", position(span.end))])
+}
+
+fn diagnostic(diagnostic: &Diagnostic, source: &Source) -> Json {
+	let severity: i64 = match diagnostic.severity {
+		Severity::Error => 1,
+		Severity::Warning => 2
+	};
+	let mut message = diagnostic.message.clone();
+	for note in &diagnostic.notes {
+		message.push('\n');
+		message.push_str(note);
+	}
+	let mut json = Json::object([
+		("range", range(source, diagnostic.primary_span)),
+		("severity", severity.into()),
+		("source", "dotfun".into()),
+		("message", message.into())
+	]);
+	if let (Json::Object(object), Some(code)) = (&mut json, diagnostic.code) {
+		object.insert("code".to_string(), code.into());
+	}
+	json
+}
+
+// The path of a `file://` URI, with its escapes undone; other URIs, like
+// those of documents not yet saved, stand for themselves.
+fn path(uri: &str) -> String {
+	let Some(path) = uri.strip_prefix("file://") else {
+		return uri.to_string();
+	};
+	let bytes = path.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut at = 0;
+	while at < bytes.len() {
+		let escaped = (bytes[at] == b'%').then(|| path.get(at + 1..at + 3)).flatten().and_then(|hex| u8::from_str_radix(hex, 16).ok());
+		match escaped {
+			Some(byte) => {
+				decoded.push(byte);
+				at += 3;
+			}
+			None => {
+				decoded.push(bytes[at]);
+				at += 1;
+			}
+		}
+	}
+	String::from_utf8_lossy(&decoded).into_owned()
+}
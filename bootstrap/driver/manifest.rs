@@ -0,0 +1,268 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::driver::packages::Resolver;
+use crate::driver::toml::{self, Table, Value};
+use crate::stdlib;
+
+// The file that makes a directory a project.
+pub const MANIFEST: &str = "dotfun.toml";
+
+// Where a project's code is when its manifest does not say.
+const SOURCES: &str = "src";
+const ENTRY: &str = "src/main.gl";
+
+// Source files only `dotfun test` builds, with the project's own.
+pub const TESTS: &str = "tests";
+
+// What a project's `dotfun.toml` says about it:
+//
+//   [package]
+//   name = "hello"
+//   version = "0.1.0"
+//   entry = "src/main.gl"
+//   sources = ["src"]
+//   std = 1
+//
+//   [dependencies]
+//   util = { path = "../util" }
+//   json = { git = "https://example.com/json.git", rev = "v1.2" }
+//
+// Paths are relative to the directory the manifest is in. `entry` and
+// `sources` can be left out for the defaults above, and `std`, the version
+// of the standard library the project needs at least, for any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+	pub name: String,
+	pub version: String,
+	// The file with `main`, if the manifest names one
+	pub entry: Option<String>,
+	// Directories whose source files, and those of the directories in them,
+	// make up the project
+	pub sources: Vec<String>,
+	pub std: Option<u32>,
+	pub dependencies: Vec<Dependency>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+	pub name: String,
+	pub source: Source
+}
+
+// Where a dependency comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+	// A project directory on this machine
+	Path(String),
+	// A git repository, at a branch, tag or commit if `rev` is given
+	Git { url: String, rev: Option<String> }
+}
+
+impl Manifest {
+	pub fn parse(text: &str) -> Result<Manifest, String> {
+		let mut table = toml::parse(text).map_err(|err| format!("line {}: {}", err.line, err.message))?;
+		let (package, dependencies) = (table.remove("package"), table.remove("dependencies"));
+		if let Some(key) = table.keys().next() {
+			return Err(format!("Unknown key '{}'", key));
+		}
+		let mut package = match package {
+			Some(Value::Table(package)) => package,
+			Some(other) => return Err(format!("'package' must be a table, not {}", other.kind())),
+			None => return Err("Missing the [package] table".to_string())
+		};
+		let dependencies = match dependencies {
+			Some(Value::Table(dependencies)) => dependencies,
+			Some(other) => return Err(format!("'dependencies' must be a table, not {}", other.kind())),
+			None => Table::new()
+		};
+
+		let name = string(&mut package, "package", "name")?.ok_or("Missing 'package.name'")?;
+		if !is_package_name(&name) {
+			return Err(format!("'{}' is not a package name; use letters, digits, '_' and '-'", name));
+		}
+		let version = string(&mut package, "package", "version")?.ok_or("Missing 'package.version'")?;
+		let entry = string(&mut package, "package", "entry")?;
+		let sources = match package.remove("sources") {
+			Some(Value::Array(values)) => values
+				.into_iter()
+				.map(|value| match value {
+					Value::String(source) => Ok(source),
+					other => Err(format!("'package.sources' must list strings, not {}", other.kind()))
+				})
+				.collect::<Result<_, _>>()?,
+			Some(other) => return Err(format!("'package.sources' must be an array, not {}", other.kind())),
+			None => vec![SOURCES.to_string()]
+		};
+		let std = match package.remove("std") {
+			Some(Value::Integer(version)) => match u32::try_from(version) {
+				Ok(version) if version >= 1 => Some(version),
+				_ => return Err(format!("'package.std' must be a version from 1 up, not {}", version))
+			},
+			Some(other) => return Err(format!("'package.std' must be an integer, not {}", other.kind())),
+			None => None
+		};
+		if let Some(key) = package.keys().next() {
+			return Err(format!("Unknown key 'package.{}'", key));
+		}
+
+		let mut parsed = Vec::new();
+		for (name, value) in dependencies {
+			let Value::Table(mut table) = value else {
+				return Err(format!("Dependency '{}' must be a table like {{ path = \"../{}\" }}", name, name));
+			};
+			let key = format!("dependencies.{}", name);
+			let path = string(&mut table, &key, "path")?;
+			let git = string(&mut table, &key, "git")?;
+			let rev = string(&mut table, &key, "rev")?;
+			if let Some(other) = table.keys().next() {
+				return Err(format!("Unknown key '{}.{}'", key, other));
+			}
+			let source = match (path, git) {
+				(Some(path), None) if rev.is_none() => Source::Path(path),
+				(Some(_), None) => return Err(format!("'{}.rev' only goes with 'git'", key)),
+				(None, Some(url)) => Source::Git { url, rev },
+				(Some(_), Some(_)) => return Err(format!("Dependency '{}' has both a 'path' and a 'git'; give one", name)),
+				(None, None) => return Err(format!("Dependency '{}' needs a 'path' or a 'git'", name))
+			};
+			parsed.push(Dependency { name, source });
+		}
+		Ok(Manifest { name, version, entry, sources, std, dependencies: parsed })
+	}
+}
+
+pub fn is_package_name(name: &str) -> bool {
+	!name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn string(table: &mut Table, within: &str, key: &str) -> Result<Option<String>, String> {
+	match table.remove(key) {
+		Some(Value::String(value)) => Ok(Some(value)),
+		Some(other) => Err(format!("'{}.{}' must be a string, not {}", within, key, other.kind())),
+		None => Ok(None)
+	}
+}
+
+// A project and the projects it depends on, loaded from their manifests.
+#[derive(Debug, Clone)]
+pub struct Project {
+	// The directory the manifest is in, relative to the current directory
+	// when it is in it or above it
+	pub root: PathBuf,
+	pub manifest: Manifest,
+	pub dependencies: Vec<Project>
+}
+
+impl Project {
+	// The project `dir` is in: the nearest directory with a manifest, looking
+	// in `dir` and then the directories above it.
+	pub fn find(dir: &Path) -> Result<Option<Project>, String> {
+		let absolute = dir.canonicalize().map_err(|err| format!("Cannot read '{}': {}", dir.display(), err))?;
+		for (up, ancestor) in absolute.ancestors().enumerate() {
+			if ancestor.join(MANIFEST).is_file() {
+				// Paths in the current directory are best left without `./`
+				let dir = if dir == Path::new(".") { Path::new("") } else { dir };
+				let root = (0..up).fold(dir.to_path_buf(), |path, _| path.join(".."));
+				return Project::load(&root).map(Some);
+			}
+		}
+		Ok(None)
+	}
+
+	// Reads the project whose manifest is in `root`, and those it depends on,
+	// fetching git dependencies that are not in the cache and updating the
+	// lockfile if they have changed.
+	pub fn load(root: &Path) -> Result<Project, String> {
+		let mut resolver = Resolver::new(root)?;
+		let project = Project::load_within(root, &mut Vec::new(), &mut resolver)?;
+		if resolver.new != resolver.old {
+			resolver.new.write(root)?;
+		}
+		Ok(project)
+	}
+
+	fn load_within(root: &Path, chain: &mut Vec<(PathBuf, String)>, resolver: &mut Resolver) -> Result<Project, String> {
+		let file = root.join(MANIFEST);
+		let text = fs::read_to_string(&file).map_err(|err| format!("Cannot read '{}': {}", file.display(), err))?;
+		let manifest = Manifest::parse(&text).map_err(|err| format!("{}: {}", file.display(), err))?;
+		if let Some(std) = manifest.std
+			&& std > stdlib::VERSION
+		{
+			let (name, version) = (&manifest.name, stdlib::VERSION);
+			return Err(format!("{}: '{}' needs version {} of the standard library, but this dotfun has version {}", file.display(), name, std, version));
+		}
+
+		let identity = identity(root);
+		if let Some(start) = chain.iter().position(|(seen, _)| *seen == identity) {
+			let mut names: Vec<&str> = chain[start..].iter().map(|(_, name)| name.as_str()).collect();
+			names.push(&manifest.name);
+			return Err(format!("Projects depend on each other in a cycle: {}", names.join(" -> ")));
+		}
+		chain.push((identity, manifest.name.clone()));
+		let mut dependencies = Vec::new();
+		for dependency in &manifest.dependencies {
+			let dir = match &dependency.source {
+				Source::Path(path) => root.join(path),
+				Source::Git { url, rev } => {
+					let fetched = resolver.resolve(&dependency.name, url, rev.as_deref());
+					fetched.map_err(|err| format!("{}: cannot fetch dependency '{}': {}", file.display(), dependency.name, err))?
+				}
+			};
+			let project = Project::load_within(&dir, chain, resolver)?;
+			if project.manifest.name != dependency.name {
+				let (name, actual) = (&dependency.name, &project.manifest.name);
+				return Err(format!("{}: dependency '{}' is the project '{}'; name it that", file.display(), name, actual));
+			}
+			dependencies.push(project);
+		}
+		chain.pop();
+		Ok(Project { root: root.to_path_buf(), manifest, dependencies })
+	}
+
+	pub fn manifest_path(&self) -> PathBuf {
+		self.root.join(MANIFEST)
+	}
+
+	// The file with `main`: the one the manifest names, or else the default
+	// one if there is such a file. Libraries need not have one.
+	pub fn entry(&self) -> Option<PathBuf> {
+		match &self.manifest.entry {
+			Some(entry) => Some(self.root.join(entry)),
+			None => Some(self.root.join(ENTRY)).filter(|entry| entry.is_file())
+		}
+	}
+
+	// The directory of tests, if the project has one. Dependencies' tests are
+	// left out.
+	pub fn tests(&self) -> Option<PathBuf> {
+		Some(self.root.join(TESTS)).filter(|dir| dir.is_dir())
+	}
+
+	// The source directories of the projects this one depends on, then its
+	// own, each once, with the package files in them are in unless they say.
+	// That is None for this project's own, and the name of the dependency,
+	// with `-` made `_`, for dependencies'. Projects that several others
+	// depend on come up first.
+	pub fn source_dirs(&self) -> Vec<(PathBuf, Option<String>)> {
+		let mut dirs = Vec::new();
+		self.collect_dirs(None, &mut dirs, &mut HashSet::new());
+		dirs
+	}
+
+	fn collect_dirs(&self, package: Option<String>, dirs: &mut Vec<(PathBuf, Option<String>)>, seen: &mut HashSet<PathBuf>) {
+		if !seen.insert(identity(&self.root)) {
+			return;
+		}
+		for dependency in &self.dependencies {
+			dependency.collect_dirs(Some(dependency.manifest.name.replace('-', "_")), dirs, seen);
+		}
+		dirs.extend(self.manifest.sources.iter().map(|source| (self.root.join(source), package.clone())));
+	}
+}
+
+// What tells projects apart however their roots are written.
+fn identity(root: &Path) -> PathBuf {
+	let dir = if root.as_os_str().is_empty() { Path::new(".") } else { root };
+	dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf())
+}
@@ -0,0 +1,887 @@
+pub mod args;
+pub mod dap;
+pub mod database;
+pub mod debug;
+pub mod json;
+pub mod lsp;
+pub mod manifest;
+pub mod packages;
+pub mod parallel;
+pub mod scaffold;
+pub mod toml;
+pub mod watch;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Instant;
+
+use crate::ast::Program;
+use crate::bytecode::Module;
+use crate::bytecode::compiler::{compile, instrument};
+use crate::bytecode::coverage::Coverage;
+use crate::bytecode::debugger::Debugger;
+use crate::bytecode::disassemble::disassemble;
+use crate::bytecode::file;
+use crate::bytecode::profiler::{self, Profiler};
+use crate::bytecode::vm::{self, Vm};
+use crate::diagnostics::ice::{self, Phase};
+use crate::diagnostics::source_map::SourceMap;
+use crate::diagnostics::{Diagnostic, ErrorFormat, codes, fix, timings};
+use crate::doc::{self, Docs};
+use crate::driver::args::{Color, Command, Emit, Options, Profile, Target, Timings, USAGE};
+use crate::driver::database::Database;
+use crate::driver::manifest::{MANIFEST, Project};
+use crate::driver::scaffold::Template;
+use crate::format::format;
+use crate::interpreter::native::Runtime;
+use crate::interpreter::{self, Uncaught};
+use crate::lexer::lexer::Lexer;
+use crate::lexer::token::Token;
+use crate::llvm;
+use crate::llvm::lower::{Lowered, lower};
+use crate::parser::parser::Parser;
+use crate::sema::hir;
+use crate::sema::modules::SourceFile;
+use crate::sema::types::FnId;
+use crate::stdlib;
+
+// What `dotfun` exits with. Anything but success means nothing ran, or the
+// program had problems.
+pub const SUCCESS: u8 = 0;
+// The program has errors, or a file could not be read or written
+pub const FAILURE: u8 = 1;
+// The command line made no sense
+pub const USAGE_ERROR: u8 = 2;
+// The compiler panicked; see `ice`
+pub const ICE: u8 = 101;
+
+// Files with this extension are the ones found in directories given as inputs.
+pub const EXTENSION: &str = "gl";
+
+// An input of `-` is source read from stdin, for editors checking what has
+// not been saved; diagnostics call it this.
+pub const STDIN: &str = "<stdin>";
+
+// Fixes can make room for others, so `dotfun fix` checks again after making
+// them, up to this many times.
+const FIX_ROUNDS: usize = 10;
+
+// The natives the programs dotfun checks and runs can call.
+pub fn runtime() -> Runtime {
+	let mut runtime = Runtime::new();
+	stdlib::register(&mut runtime);
+	runtime
+}
+
+// The same, for running a program with `args`, which `os.args()` returns.
+pub fn running(args: &[String]) -> Runtime {
+	let mut runtime = runtime();
+	stdlib::os::args(&mut runtime, args);
+	runtime
+}
+
+// Does what `options` say, printing diagnostics to stderr and what was asked
+// for to stdout, and returns the exit code.
+pub fn run(options: &Options) -> u8 {
+	if options.timings.is_some() {
+		timings::start();
+	}
+	let code = match options.command {
+		Command::Run => run_program(options),
+		Command::Build => build_program(options),
+		Command::Check => check_program(options),
+		Command::Test => build(options, test_session),
+		Command::Debug => build(options, debug_session),
+		Command::Dap => interpreter::with_stack(|| dap::serve(Box::new(io::stdin().lock()), Box::new(io::stdout()))),
+		Command::Tokens | Command::Ast => dump(options),
+		Command::Fix => fix_files(options),
+		Command::Fmt => format_files(options),
+		Command::Doc => document(options),
+		Command::New | Command::Init => {
+			let template = if options.lib { Template::Lib } else { Template::Bin };
+			let created = match options.command {
+				Command::New => scaffold::new(Path::new(&options.inputs[0]), template),
+				_ => scaffold::init(Path::new(options.inputs.first().map_or(".", String::as_str)), template)
+			};
+			match created {
+				Ok(created) => {
+					out(created);
+					SUCCESS
+				}
+				Err(message) => {
+					eprintln!("error: {}", message);
+					FAILURE
+				}
+			}
+		}
+		Command::Add => match packages::add(&options.inputs[0], options.rev.as_deref()) {
+			Ok(added) => {
+				out(added);
+				SUCCESS
+			}
+			Err(message) => {
+				eprintln!("error: {}", message);
+				FAILURE
+			}
+		},
+		Command::Explain => explain(&options.inputs[0]),
+		Command::Help => {
+			out(USAGE);
+			SUCCESS
+		}
+		Command::Version => {
+			out(format!("dotfun {} (standard library {})", env!("CARGO_PKG_VERSION"), stdlib::VERSION));
+			SUCCESS
+		}
+	};
+	report_timings(options);
+	code
+}
+
+// Reports what `--timings` asked for, if it did, and starts timing again.
+pub fn report_timings(options: &Options) {
+	match &options.timings {
+		Some(Timings::Table) => timings::report(None),
+		Some(Timings::Trace(path)) => timings::report(Some(path)),
+		None => return
+	}
+	timings::start();
+}
+
+// Files being compiled together, and what was found wrong with them so far.
+pub struct Session<'a> {
+	options: &'a Options,
+	// Of each file, in the order of `sources`
+	pub paths: Vec<String>,
+	// Of each file, the package it is in without a `package` statement
+	pub packages: Vec<Option<String>>,
+	pub sources: SourceMap,
+	// With lint levels applied, in the order they were found
+	pub diagnostics: Vec<Diagnostic>,
+	// The project being built, when it is one rather than loose files
+	pub project: Option<Project>,
+	// Which of `paths` has `main`, if the project says
+	pub entry: Option<usize>,
+	// What was worked out about the files, which watch mode keeps from one
+	// build to the next so that what has not changed is not worked out again
+	pub database: Database
+}
+
+impl<'a> Session<'a> {
+	// Reads the files named in `options`, and the source files anywhere in
+	// the directories named there, or with no files named, in the project.
+	pub fn load(options: &'a Options) -> Result<Session<'a>, String> {
+		Session::editing(options, &HashMap::new())
+	}
+
+	// The same, but with the files in `open` as an editor has them, saved or
+	// not, rather than as they are on disk.
+	pub fn editing(options: &'a Options, open: &HashMap<String, String>) -> Result<Session<'a>, String> {
+		let Inputs { mut paths, packages, project } = inputs(options)?;
+		let mut entry = None;
+		if let Some(project) = &project
+			&& let Some(file) = project.entry()
+		{
+			let position = paths.iter().position(|path| Path::new(path) == file);
+			entry = Some(position.ok_or_else(|| {
+				let dirs: Vec<String> = project.manifest.sources.iter().map(|dir| format!("'{}'", dir)).collect();
+				format!("The entry point '{}' is not in the project's sources, {}", file.display(), dirs.join(", "))
+			})?);
+		}
+		// Each build starts the files `--emit` writes afresh
+		for (_, path) in &options.emit {
+			if let Some(path) = path {
+				fs::write(path, "").map_err(|err| format!("Cannot write '{}': {}", path, err))?;
+			}
+		}
+		let mut sources = SourceMap::new();
+		for path in &mut paths {
+			let text = if path == "-" {
+				*path = STDIN.to_string();
+				io::read_to_string(io::stdin()).map_err(|err| format!("Cannot read stdin: {}", err))?
+			} else if let Some(text) = open.get(path.as_str()) {
+				text.clone()
+			} else {
+				timings::time("read", path, || fs::read_to_string(&path)).map_err(|err| format!("Cannot read '{}': {}", path, err))?
+			};
+			sources.add(path.clone(), text);
+		}
+		// The standard library's files come after the program's, as checking
+		// numbers them, for diagnostics and the debugger to show
+		for source in runtime().sources() {
+			sources.add(source.path.clone(), source.text.clone());
+		}
+		Ok(Session { options, paths, packages, sources, diagnostics: Vec::new(), project, entry, database: Database::new() })
+	}
+
+	fn path(&self, file: usize) -> &str {
+		&self.paths[file]
+	}
+
+	pub fn has_errors(&self) -> bool {
+		self.diagnostics.iter().any(Diagnostic::is_error)
+	}
+
+	pub fn report(&mut self, diagnostics: Vec<Diagnostic>) {
+		self.diagnostics.extend(self.options.lints.apply(diagnostics));
+	}
+
+	pub fn tokens(&mut self, file: usize) -> Option<Vec<Token>> {
+		ice::enter(Phase::Lexing, self.path(file));
+		let text = self.sources.get(file)?.text.clone();
+		match Lexer::new(text).lex() {
+			Ok(tokens) => Some(tokens.clone()),
+			Err(err) => {
+				self.report(vec![err.in_file(file)]);
+				None
+			}
+		}
+	}
+
+	// Parses every file, each on whichever thread is free, except those
+	// parsed before and unchanged since. Diagnostics are reported in the
+	// order of the files however the threads finish.
+	pub fn parse(&mut self) -> Vec<Option<SourceFile>> {
+		for (file, path) in self.paths.iter().enumerate() {
+			let text = self.sources.get(file).map_or("", |source| source.text.as_str());
+			self.database.set_source(path, text);
+		}
+		self.database.retain_sources(&self.paths);
+		// Files are parsed again for what `--emit` prints on the way
+		let memoize = self.options.emit.is_empty();
+		let mut database = std::mem::take(&mut self.database);
+		let parsed = database.parse(&self.paths, memoize, |file, text| self.parse_text(file, text));
+		self.database = database;
+		parsed
+			.into_iter()
+			.enumerate()
+			.map(|(file, parsed)| match parsed {
+				Ok(program) => Some(SourceFile { path: self.paths[file].clone(), program, package: self.packages[file].clone() }),
+				Err(errors) => {
+					self.report(errors.into_iter().map(|err| err.in_file(file)).collect());
+					None
+				}
+			})
+			.collect()
+	}
+
+	fn parse_text(&self, file: usize, text: &str) -> Result<Program, Vec<Diagnostic>> {
+		let path = self.path(file);
+		ice::enter(Phase::Lexing, path);
+		let tokens = timings::time("lex", path, || Lexer::new(text.to_string()).lex().map_err(|err| vec![*err]).cloned())?;
+		if self.options.emits(Emit::Tokens) {
+			let lines: Vec<String> = tokens.iter().map(Token::to_string).collect();
+			self.emit(Emit::Tokens, lines.join("\n"));
+		}
+		ice::enter(Phase::Parsing, path);
+		let program = timings::time("parse", path, || Parser::new(tokens).parse())?;
+		if self.options.emits(Emit::Ast) {
+			self.emit(Emit::Ast, format!("{:#?}", program));
+		}
+		Ok(program)
+	}
+
+	// Prints what `--emit` asked for, or adds it to the file it named.
+	fn emit(&self, what: Emit, text: String) {
+		let Some(path) = self.options.emit_path(what) else {
+			out(text);
+			return;
+		};
+		let written = fs::OpenOptions::new().append(true).open(path).and_then(|mut file| writeln!(file, "{}", text));
+		if let Err(err) = written {
+			eprintln!("error: Cannot write '{}': {}", path, err);
+		}
+	}
+
+	// Parses every file, then checks them together if they all parsed.
+	pub fn check(&mut self) -> Option<hir::Program> {
+		let files: Vec<SourceFile> = self.parse().into_iter().collect::<Option<_>>()?;
+		ice::enter(Phase::Checking, self.paths.join(", "));
+		let key: Vec<(String, Option<String>)> = self.paths.iter().cloned().zip(self.packages.iter().cloned()).collect();
+		match self.database.check(&key, |kept| runtime().recheck(&files, kept)) {
+			Ok(mut program) => {
+				if self.options.emits(Emit::Hir) {
+					self.emit(Emit::Hir, format!("{:#?}", program));
+				}
+				// Dependencies' warnings are for their authors to fix
+				let mut warnings = std::mem::take(&mut program.warnings);
+				warnings.retain(|warning| self.packages.get(warning.file).is_some_and(Option::is_none));
+				self.report(warnings);
+				(!self.has_errors()).then_some(program)
+			}
+			Err(errors) => {
+				self.report(errors);
+				None
+			}
+		}
+	}
+
+	// Compiles a checked program to bytecode for the VM, counting what runs
+	// of it with `--coverage`.
+	pub fn compile(&self, program: &hir::Program) -> Module {
+		ice::enter(Phase::Compiling, self.paths.join(", "));
+		let compile = if self.options.coverage.is_some() { instrument } else { compile };
+		let module = timings::time("compile", &self.paths.join(", "), || compile(program, self.entry));
+		if self.options.emits(Emit::Bytecode) {
+			self.emit(Emit::Bytecode, disassemble(&module));
+		}
+		module
+	}
+
+	// The program as LLVM IR, or None when it uses what the native target
+	// cannot compile yet, which is reported.
+	pub fn lower(&mut self, program: &hir::Program) -> Option<Lowered> {
+		ice::enter(Phase::Compiling, self.paths.join(", "));
+		match timings::time("lower", &self.paths.join(", "), || lower(program, self.entry)) {
+			Ok(lowered) => Some(lowered),
+			Err(errors) => {
+				self.report(errors);
+				self.flush();
+				None
+			}
+		}
+	}
+
+	// Prints the diagnostics found so far, and forgets them.
+	pub fn flush(&mut self) {
+		let color = color(self.options);
+		for diagnostic in std::mem::take(&mut self.diagnostics) {
+			eprint!("{}", self.options.error_format.emit(&diagnostic, &self.sources, color));
+		}
+	}
+}
+
+// Whether diagnostics on stderr are colored.
+fn color(options: &Options) -> bool {
+	match options.color {
+		Color::Always => true,
+		Color::Never => false,
+		Color::Auto => options.error_format == ErrorFormat::Human && io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+	}
+}
+
+// Prints a line to stdout, stopping quietly once nothing reads it any more,
+// as when piped into `head`, where `println!` would panic.
+fn out(line: impl fmt::Display) {
+	if let Err(err) = writeln!(io::stdout(), "{}", line)
+		&& err.kind() == io::ErrorKind::BrokenPipe
+	{
+		process::exit(SUCCESS.into());
+	}
+}
+
+// The files to build and the project they are, if they are one.
+pub struct Inputs {
+	pub paths: Vec<String>,
+	// Of each file, the package of the dependency it is from
+	pub packages: Vec<Option<String>>,
+	pub project: Option<Project>
+}
+
+// The files named in `options`, with directories replaced by the source files
+// in them. A directory with a manifest is a project, and stands for the
+// source directories of it and its dependencies, and for `dotfun test` its
+// tests; naming no files at all means the project the current directory is
+// in.
+pub fn inputs(options: &Options) -> Result<Inputs, String> {
+	let mut paths = Vec::new();
+	let mut project = None;
+	if options.inputs.is_empty() {
+		let found = Project::find(Path::new("."))?;
+		project = Some(found.ok_or_else(|| format!("Expected a file, or a {} in this directory or one above it", MANIFEST))?);
+	}
+	for input in &options.inputs {
+		let dir = Path::new(input);
+		if dir.join(MANIFEST).is_file() {
+			if project.is_some() {
+				return Err("Only one project can be built at a time".to_string());
+			}
+			project = Some(Project::load(dir)?);
+		} else if dir.is_dir() {
+			let found = paths.len();
+			source_files(dir, &mut paths).map_err(|err| format!("Cannot read '{}': {}", input, err))?;
+			if paths.len() == found {
+				return Err(format!("No .{} files in '{}'", EXTENSION, input));
+			}
+		} else {
+			paths.push(input.clone());
+		}
+	}
+	let mut packages = vec![None; paths.len()];
+	if let Some(project) = &project {
+		let mut seen: HashSet<String> = paths.iter().cloned().collect();
+		let mut dirs = project.source_dirs();
+		if options.command == Command::Test {
+			dirs.extend(project.tests().map(|dir| (dir, None)));
+		}
+		for (dir, package) in dirs {
+			let mut found = Vec::new();
+			source_files(&dir, &mut found).map_err(|err| format!("Cannot read '{}': {}", dir.display(), err))?;
+			for path in found {
+				if seen.insert(path.clone()) {
+					paths.push(path);
+					packages.push(package.clone());
+				}
+			}
+		}
+	}
+	Ok(Inputs { paths, packages, project })
+}
+
+// Adds the source files in `dir` and the directories in it to `paths`, in
+// order by name. Hidden directories, like `.git`, are left out.
+fn source_files(dir: &Path, paths: &mut Vec<String>) -> io::Result<()> {
+	let mut entries: Vec<PathBuf> = fs::read_dir(dir)?.map(|entry| entry.map(|entry| entry.path())).collect::<io::Result<_>>()?;
+	entries.sort();
+	for entry in entries {
+		let hidden = entry.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+		if entry.is_dir() && !hidden {
+			source_files(&entry, paths)?;
+		} else if entry.is_file() && entry.extension().is_some_and(|extension| extension == EXTENSION) {
+			paths.push(entry.to_string_lossy().into_owned());
+		}
+	}
+	Ok(())
+}
+
+fn print_tokens(tokens: &[Token]) {
+	for token in tokens {
+		out(token);
+	}
+}
+
+fn load(options: &Options) -> Result<Session<'_>, u8> {
+	Session::load(options).map_err(|message| {
+		eprintln!("error: {}", message);
+		FAILURE
+	})
+}
+
+// Loads the files `options` name and builds them, or with `--watch` builds
+// them again every time they change.
+fn build(options: &Options, build: fn(&mut Session) -> u8) -> u8 {
+	if options.watch {
+		return watch::watch(options, build);
+	}
+	match load(options) {
+		Ok(mut session) => build(&mut session),
+		Err(code) => code
+	}
+}
+
+// Lexes, parses and checks, and stops there: nothing runs, and nothing is
+// printed but diagnostics, so editors and CI can call this after every change.
+fn check_program(options: &Options) -> u8 {
+	build(options, |session| {
+		let program = session.check();
+		session.flush();
+		if let Some(program) = &program
+			&& session.options.emits(Emit::Bytecode)
+		{
+			session.compile(program);
+		}
+		if program.is_some() { SUCCESS } else { FAILURE }
+	})
+}
+
+fn run_program(options: &Options) -> u8 {
+	match options.inputs.as_slice() {
+		[path] if Path::new(path).extension().is_some_and(|extension| extension == file::EXTENSION) => run_compiled(options, path),
+		_ => build(options, run_session)
+	}
+}
+
+// Checks the program, then runs it: the top-level code of every file, then
+// `main` with the arguments after `--`.
+fn run_session(session: &mut Session) -> u8 {
+	let program = session.check();
+	session.flush();
+	let Some(program) = program else {
+		return FAILURE;
+	};
+	let module = session.compile(&program);
+	let name = session.entry.map_or_else(|| session.paths.join(", "), |entry| session.paths[entry].clone());
+	match execute(&module, session.options, &name) {
+		Ok(()) => SUCCESS,
+		Err(uncaught) => {
+			session.report(vec![uncaught.diagnostic(&module.files)]);
+			session.flush();
+			FAILURE
+		}
+	}
+}
+
+// Checks the program, then runs it as `run` does in the debugger, which
+// reads its commands from stdin and prints to stderr, leaving stdout to the
+// program.
+fn debug_session(session: &mut Session) -> u8 {
+	let program = session.check();
+	session.flush();
+	let Some(program) = program else {
+		return FAILURE;
+	};
+	let module = session.compile(&program);
+	let name = session.entry.map_or_else(|| session.paths.join(", "), |entry| session.paths[entry].clone());
+	let (sources, args) = (&session.sources, &session.options.program_args);
+	let result = interpreter::with_stack(|| {
+		ice::enter(Phase::Running, &name);
+		let console = debug::Console::new(sources, Box::new(io::stdin().lock()), Box::new(io::stderr()));
+		let debugger = Debugger::new(Box::new(console), Default::default(), true);
+		vm::debug(&module, &running(args), args, debugger, &mut io::stdout())
+	});
+	match result {
+		Ok(()) => SUCCESS,
+		Err(uncaught) => {
+			session.report(vec![uncaught.diagnostic(&module.files)]);
+			session.flush();
+			FAILURE
+		}
+	}
+}
+
+// `dotfun run app.dfc`: runs what `dotfun build` compiled. Its source may be
+// gone, so errors in it say where they are without showing the code.
+fn run_compiled(options: &Options, path: &str) -> u8 {
+	let bytes = match fs::read(path) {
+		Ok(bytes) => bytes,
+		Err(err) => {
+			eprintln!("error: Cannot read '{}': {}", path, err);
+			return FAILURE;
+		}
+	};
+	let module = match timings::time("load", path, || file::load(&bytes)) {
+		Ok(module) => module,
+		Err(err) => {
+			eprintln!("error: '{}' is {}", path, err);
+			return FAILURE;
+		}
+	};
+	match execute(&module, options, path) {
+		Ok(()) => SUCCESS,
+		Err(uncaught) => {
+			eprint!("{}", options.error_format.emit(&uncaught.diagnostic(&module.files), &SourceMap::new(), color(options)));
+			FAILURE
+		}
+	}
+}
+
+// Runs a compiled program: the top-level code of every file, then `main`
+// with the arguments after `--`. `name` is what an ICE says was running.
+// With `--profile`, what the profiler found is reported once it stops,
+// however it does.
+fn execute(module: &Module, options: &Options, name: &str) -> Result<(), Uncaught> {
+	interpreter::with_stack(|| {
+		ice::enter(Phase::Running, name);
+		timings::time("run", name, || match &options.profile {
+			None => vm::run(module, &running(&options.program_args), &options.program_args, options.gc, &mut io::stdout()),
+			Some(profile) => {
+				let mut profiler = Profiler::new(profiler::INTERVAL);
+				let result = vm::profile(module, &running(&options.program_args), &options.program_args, options.gc, &mut profiler, &mut io::stdout());
+				match profile {
+					Profile::Table => eprintln!("{}", profiler.table()),
+					Profile::Folded(path) => {
+						if let Err(err) = fs::write(path, profiler.folded()) {
+							eprintln!("error: Cannot write '{}': {}", path, err);
+						}
+					}
+				}
+				result
+			}
+		})
+	})
+}
+
+// `dotfun build`: checks and compiles the program, then writes it where
+// `--out` says, or by default next to the project's manifest, named after
+// it, or else next to the first file.
+fn build_program(options: &Options) -> u8 {
+	let mut session = match load(options) {
+		Ok(session) => session,
+		Err(code) => return code
+	};
+	let program = session.check();
+	session.flush();
+	let Some(program) = program else {
+		return FAILURE;
+	};
+	let extension = match options.target {
+		Target::Bytecode => file::EXTENSION,
+		Target::Native => ""
+	};
+	let path = match (&options.out, &session.project, session.paths.first()) {
+		(Some(out), _, _) => PathBuf::from(out),
+		(None, Some(project), _) => project.root.join(&project.manifest.name).with_extension(extension),
+		(None, None, Some(first)) if first != STDIN => Path::new(first).with_extension(extension),
+		(None, None, _) => {
+			eprintln!("error: Source read from stdin needs '--out' to say where to build it");
+			return FAILURE;
+		}
+	};
+	let built = match options.target {
+		Target::Bytecode => {
+			let module = session.compile(&program);
+			fs::write(&path, file::save(&module)).map_err(|err| format!("Cannot write '{}': {}", path.display(), err))
+		}
+		Target::Native => match session.lower(&program) {
+			Some(lowered) => llvm::build(&lowered, &path),
+			None => return FAILURE
+		}
+	};
+	if let Err(err) = built {
+		eprintln!("error: {}", err);
+		return FAILURE;
+	}
+	out(format!("Built {}", path.display()));
+	SUCCESS
+}
+
+// The tests of a checked program that `--filter` lets through, by name, in
+// the order of their files and then of where they are in them.
+fn tests(session: &Session, program: &hir::Program) -> Vec<(String, FnId)> {
+	let mut tests = program.tests.clone();
+	tests.sort_by_key(|&id| (program.function(id).file, program.function(id).span.start));
+	tests
+		.into_iter()
+		.map(|id| {
+			let function = program.function(id);
+			let package = &program.files[function.file].package;
+			let name = if package.is_empty() { function.name.clone() } else { format!("{}.{}", package, function.name) };
+			(name, id)
+		})
+		.filter(|(name, _)| session.options.filter.as_ref().is_none_or(|filter| name.contains(filter.as_str())))
+		.collect()
+}
+
+// Runs each test after the top-level code of every file, printing whether
+// it passed and how long it took, and why not when it threw. With `--coverage`, then reports
+// what ran of each file, and writes the lcov report.
+fn test_session(session: &mut Session) -> u8 {
+	let program = session.check();
+	session.flush();
+	let Some(program) = program else {
+		return FAILURE;
+	};
+	let tests = tests(session, &program);
+	if session.options.list {
+		for (name, _) in &tests {
+			out(name);
+		}
+		return SUCCESS;
+	}
+	let module = session.compile(&program);
+	let name = session.paths.join(", ");
+	let failures = interpreter::with_stack(|| {
+		ice::enter(Phase::Running, name.as_str());
+		let mut stdout = io::stdout();
+		let runtime = runtime();
+		let begun = Instant::now();
+		let mut vm = Vm::new(&module, &runtime, session.options.gc, &mut stdout);
+		vm.init()?;
+		let mut failures = Vec::new();
+		for (name, id) in &tests {
+			let started = Instant::now();
+			let result = timings::time("test", name, || vm.call(id.0, Vec::new()));
+			let took = timings::milliseconds(started.elapsed());
+			match result {
+				Ok(_) => out(format!("test {} ... ok ({})", name, took)),
+				Err(uncaught) => {
+					out(format!("test {} ... FAILED ({})", name, took));
+					failures.push(uncaught);
+				}
+			}
+		}
+		vm.finish();
+		Ok::<_, Uncaught>((failures, vm.counts().to_vec(), begun.elapsed()))
+	});
+	let (failures, counts, took) = match failures {
+		Ok(failures) => failures,
+		Err(uncaught) => {
+			session.report(vec![uncaught.diagnostic(&module.files)]);
+			session.flush();
+			return FAILURE;
+		}
+	};
+	let failed = failures.len();
+	session.report(failures.iter().map(|uncaught| uncaught.diagnostic(&module.files)).collect());
+	session.flush();
+	let result = if failed == 0 { "ok" } else { "FAILED" };
+	out(format!("\ntest result: {}. {} passed; {} failed; finished in {}", result, tests.len() - failed, failed, timings::milliseconds(took)));
+	if let Some(path) = &session.options.coverage {
+		// Of the program, not of the standard library it was compiled with
+		let mut coverage = Coverage::new(&module, &counts);
+		coverage.files.retain(|file| session.paths.contains(&file.path));
+		out(format!("\n{}", coverage.summary()));
+		if let Err(err) = fs::write(path, coverage.lcov()) {
+			eprintln!("error: Cannot write '{}': {}", path, err);
+			return FAILURE;
+		}
+	}
+	if failed == 0 { SUCCESS } else { FAILURE }
+}
+
+// `dotfun doc`: checks the program, then writes the docs of its own
+// declarations, not its dependencies', as HTML pages and `docs.json`.
+fn document(options: &Options) -> u8 {
+	let mut session = match load(options) {
+		Ok(session) => session,
+		Err(code) => return code
+	};
+	let program = session.check();
+	session.flush();
+	let Some(program) = program else {
+		return FAILURE;
+	};
+	let docs = Docs::new(&program, |file| session.packages.get(file).is_some_and(Option::is_none));
+	let dir = match (&options.out, &session.project) {
+		(Some(out), _) => PathBuf::from(out),
+		(None, Some(project)) => project.root.join("doc"),
+		(None, None) => PathBuf::from("doc")
+	};
+	let title = session.project.as_ref().map_or("Documentation".to_string(), |project| project.manifest.name.clone());
+	let mut files = doc::html::pages(&docs, &title);
+	files.push(("docs.json".to_string(), doc::json::to_json(&docs)));
+	let written = fs::create_dir_all(&dir).and_then(|()| files.iter().try_for_each(|(name, text)| fs::write(dir.join(name), text)));
+	if let Err(err) = written {
+		eprintln!("error: Cannot write the docs to '{}': {}", dir.display(), err);
+		return FAILURE;
+	}
+	let (count, packages) = (docs.all().count(), docs.packages.len());
+	out(format!(
+		"Documented {} declaration{} in {} package{} to {}",
+		count,
+		if count == 1 { "" } else { "s" },
+		packages,
+		if packages == 1 { "" } else { "s" },
+		dir.join("index.html").display()
+	));
+	SUCCESS
+}
+
+// `dotfun tokens` and `dotfun ast`: each file on its own, without checking.
+fn dump(options: &Options) -> u8 {
+	let mut session = match load(options) {
+		Ok(session) => session,
+		Err(code) => return code
+	};
+	for file in 0..session.paths.len() {
+		if session.paths.len() > 1 {
+			out(format!("// {}", session.paths[file]));
+		}
+		match options.command {
+			Command::Tokens => {
+				if let Some(tokens) = session.tokens(file) {
+					print_tokens(&tokens);
+				}
+			}
+			_ => {
+				if let Some(tokens) = session.tokens(file) {
+					ice::enter(Phase::Parsing, session.path(file));
+					match Parser::new(tokens).parse() {
+						Ok(program) => out(format!("{:#?}", program)),
+						Err(errors) => session.report(errors.into_iter().map(|err| err.in_file(file)).collect())
+					}
+				}
+			}
+		}
+	}
+	let failed = session.has_errors();
+	session.flush();
+	if failed { FAILURE } else { SUCCESS }
+}
+
+// Makes the safe suggestions of every diagnostic, checking again after each
+// round since fixes can uncover more.
+fn fix_files(options: &Options) -> u8 {
+	if options.inputs.iter().any(|input| input == "-") {
+		eprintln!("error: Cannot fix stdin; 'dotfun fix' changes files in place");
+		return FAILURE;
+	}
+	let mut paths = Vec::new();
+	let mut fixed = Vec::new();
+	for _ in 0..FIX_ROUNDS {
+		let mut session = match load(options) {
+			Ok(session) => session,
+			Err(code) => return code
+		};
+		paths.clone_from(&session.paths);
+		fixed.resize(paths.len(), 0);
+		session.check();
+		ice::enter(Phase::Fixing, paths.join(", "));
+		let changes = fix::fix(&session.diagnostics, &session.sources);
+		if changes.is_empty() {
+			break;
+		}
+		for change in changes {
+			let path = &paths[change.file];
+			if let Err(err) = fs::write(path, &change.text) {
+				eprintln!("error: Cannot write '{}': {}", path, err);
+				return FAILURE;
+			}
+			fixed[change.file] += change.fixes;
+		}
+	}
+	for (path, count) in paths.iter().zip(fixed) {
+		if count > 0 {
+			out(format!("Fixed {} problem{} in {}", count, if count == 1 { "" } else { "s" }, path));
+		}
+	}
+	SUCCESS
+}
+
+// Formats each file in place, or with `--check` only lists the ones that
+// would change. Stdin is formatted to stdout. Files that do not parse are left
+// alone and their errors reported.
+fn format_files(options: &Options) -> u8 {
+	let mut session = match load(options) {
+		Ok(session) => session,
+		Err(code) => return code
+	};
+	let mut unformatted = false;
+	for file in 0..session.paths.len() {
+		ice::enter(Phase::Formatting, session.path(file));
+		let source = &session.sources.get(file).unwrap().text;
+		let formatted = match timings::time("format", session.path(file), || format(source)) {
+			Ok(formatted) => formatted,
+			Err(errors) => {
+				session.report(errors.into_iter().map(|err| err.in_file(file)).collect());
+				continue;
+			}
+		};
+		let path = &session.paths[file];
+		let changed = formatted != *source;
+		if options.check {
+			if changed {
+				unformatted = true;
+				out(path);
+			}
+		} else if path == STDIN {
+			out(formatted.strip_suffix('\n').unwrap_or(&formatted));
+		} else if changed {
+			if let Err(err) = fs::write(path, &formatted) {
+				eprintln!("error: Cannot write '{}': {}", path, err);
+				return FAILURE;
+			}
+			out(format!("Formatted {}", path));
+		}
+	}
+	let failed = session.has_errors();
+	session.flush();
+	if failed || unformatted { FAILURE } else { SUCCESS }
+}
+
+fn explain(code: &str) -> u8 {
+	match codes::explain(code) {
+		Some(explanation) => {
+			out(explanation.to_string().trim_end());
+			SUCCESS
+		}
+		None => {
+			eprintln!("error: '{}' is not a diagnostic code", code);
+			FAILURE
+		}
+	}
+}
@@ -0,0 +1,278 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::driver::manifest::{MANIFEST, Manifest, Project};
+use crate::driver::toml::{self, Value};
+
+// The file next to a project's manifest that pins its git dependencies, and
+// theirs, to the commits they were first resolved to, so that every build
+// of the project uses the same code until a dependency is added again.
+pub const LOCKFILE: &str = "dotfun.lock";
+
+// Where fetched packages are kept, when `DOTFUN_HOME` does not say.
+const HOME: &str = ".dotfun";
+
+// A git dependency as pinned in the lockfile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locked {
+	pub url: String,
+	pub rev: Option<String>,
+	pub commit: String
+}
+
+// The lockfile, by dependency name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lock {
+	pub packages: BTreeMap<String, Locked>
+}
+
+impl Lock {
+	pub fn parse(text: &str) -> Result<Lock, String> {
+		let table = toml::parse(text).map_err(|err| format!("line {}: {}", err.line, err.message))?;
+		let mut lock = Lock::default();
+		for (name, value) in table {
+			let Value::Table(mut entry) = value else {
+				return Err(format!("'{}' must be a table", name));
+			};
+			let mut field = |key: &str| match entry.remove(key) {
+				Some(Value::String(value)) => Ok(Some(value)),
+				Some(_) => Err(format!("'{}.{}' must be a string", name, key)),
+				None => Ok(None)
+			};
+			let url = field("git")?.ok_or_else(|| format!("Missing '{}.git'", name))?;
+			let rev = field("rev")?;
+			let commit = field("commit")?.ok_or_else(|| format!("Missing '{}.commit'", name))?;
+			// It is given to git as is, so it cannot be anything but a commit
+			if commit.len() != 40 || !commit.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+				return Err(format!("'{}.commit' must be the 40 hex digits of a commit, not '{}'", name, commit));
+			}
+			lock.packages.insert(name, Locked { url, rev, commit });
+		}
+		Ok(lock)
+	}
+
+	pub fn read(root: &Path) -> Result<Lock, String> {
+		let path = root.join(LOCKFILE);
+		match fs::read_to_string(&path) {
+			Ok(text) => Lock::parse(&text).map_err(|err| format!("{}: {}", path.display(), err)),
+			Err(_) if !path.exists() => Ok(Lock::default()),
+			Err(err) => Err(format!("Cannot read '{}': {}", path.display(), err))
+		}
+	}
+
+	pub fn write(&self, root: &Path) -> Result<(), String> {
+		let path = root.join(LOCKFILE);
+		if self.packages.is_empty() {
+			return match fs::remove_file(&path) {
+				Err(err) if path.exists() => Err(format!("Cannot remove '{}': {}", path.display(), err)),
+				_ => Ok(())
+			};
+		}
+		fs::write(&path, self.to_string()).map_err(|err| format!("Cannot write '{}': {}", path.display(), err))
+	}
+}
+
+impl fmt::Display for Lock {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "# Written by dotfun to pin git dependencies to commits; not meant to be edited.")?;
+		for (name, locked) in &self.packages {
+			writeln!(f, "\n[{}]", name)?;
+			writeln!(f, "git = {}", toml::quote(&locked.url))?;
+			if let Some(rev) = &locked.rev {
+				writeln!(f, "rev = {}", toml::quote(rev))?;
+			}
+			writeln!(f, "commit = {}", toml::quote(&locked.commit))?;
+		}
+		Ok(())
+	}
+}
+
+// Finds the code of git dependencies: at the commit the lockfile pins them
+// to, or for new ones, at the commit their `rev` is now, which is then
+// pinned. Repositories are cloned once into the cache, and each commit used
+// is checked out there of its own.
+#[derive(Debug)]
+pub struct Resolver {
+	cache: PathBuf,
+	pub old: Lock,
+	// What the lockfile should now say: the dependencies resolved so far
+	pub new: Lock
+}
+
+impl Resolver {
+	pub fn new(root: &Path) -> Result<Resolver, String> {
+		Ok(Resolver { cache: cache_dir(), old: Lock::read(root)?, new: Lock::default() })
+	}
+
+	// The directory with the code of dependency `name`.
+	pub fn resolve(&mut self, name: &str, url: &str, rev: Option<&str>) -> Result<PathBuf, String> {
+		if let Some(locked) = self.new.packages.get(name) {
+			if locked.url != url || locked.rev.as_deref() != rev {
+				return Err(format!("Two different dependencies are named '{}', from {} and {}", name, locked.url, url));
+			}
+			return Ok(self.checkout_dir(url, &locked.commit));
+		}
+		let pinned = self.old.packages.get(name).filter(|locked| locked.url == url && locked.rev.as_deref() == rev);
+		let commit = match pinned {
+			Some(locked) => locked.commit.clone(),
+			None => self.fetch(url, rev)?
+		};
+		let dir = self.checkout(url, &commit)?;
+		self.new.packages.insert(name.to_string(), Locked { url: url.to_string(), rev: rev.map(str::to_string), commit });
+		Ok(dir)
+	}
+
+	// Brings the clone of `url` up to date, and returns the commit `rev` is.
+	// Neither can start with '-', where git would take them for options.
+	pub fn fetch(&self, url: &str, rev: Option<&str>) -> Result<String, String> {
+		if url.starts_with('-') {
+			return Err(format!("'{}' is not a git URL", url));
+		}
+		if let Some(rev) = rev
+			&& rev.starts_with('-')
+		{
+			return Err(format!("'{}' is not a branch, tag or commit", rev));
+		}
+		let db = self.db_dir(url);
+		if db.exists() {
+			git(&db, &["fetch", "--quiet", "--force", "--", url, "+refs/heads/*:refs/heads/*", "+refs/tags/*:refs/tags/*"])?;
+		} else {
+			fs::create_dir_all(db.parent().unwrap()).map_err(|err| format!("Cannot create '{}': {}", self.cache.display(), err))?;
+			git(&self.cache, &["clone", "--quiet", "--bare", "--", url, &db.to_string_lossy()])?;
+		}
+		let rev = format!("{}^{{commit}}", rev.unwrap_or("HEAD"));
+		git(&db, &["rev-parse", "--verify", "--quiet", &rev])
+			.map_err(|_| format!("{} has no branch, tag or commit '{}'", url, rev.trim_end_matches("^{commit}")))
+	}
+
+	// The directory with `url` checked out at `commit`, made if need be.
+	pub fn checkout(&self, url: &str, commit: &str) -> Result<PathBuf, String> {
+		let dir = self.checkout_dir(url, commit);
+		if dir.exists() {
+			return Ok(dir);
+		}
+		let db = self.db_dir(url);
+		let known = db.exists() && git(&db, &["cat-file", "-e", &format!("{}^{{commit}}", commit)]).is_ok();
+		if !known {
+			self.fetch(url, None)?;
+		}
+		// Checked out beside where it goes, so an interrupted checkout is never
+		// mistaken for a finished one
+		let partial = dir.with_extension("partial");
+		let _ = fs::remove_dir_all(&partial);
+		git(&self.cache, &["clone", "--quiet", "--no-checkout", "--", &db.to_string_lossy(), &partial.to_string_lossy()])?;
+		git(&partial, &["checkout", "--quiet", "--detach", commit])?;
+		fs::rename(&partial, &dir).map_err(|err| format!("Cannot create '{}': {}", dir.display(), err))?;
+		Ok(dir)
+	}
+
+	fn db_dir(&self, url: &str) -> PathBuf {
+		self.cache.join("db").join(repository_key(url))
+	}
+
+	fn checkout_dir(&self, url: &str, commit: &str) -> PathBuf {
+		self.cache.join("checkouts").join(repository_key(url)).join(&commit[..commit.len().min(12)])
+	}
+}
+
+// Adds the project at `source`, a git URL or a directory, to the dependencies
+// of the project the current directory is in, and returns what was added.
+// For git, `rev` is the branch, tag or commit to use.
+pub fn add(source: &str, rev: Option<&str>) -> Result<String, String> {
+	let project = Project::find(Path::new("."))?.ok_or_else(|| format!("No {} in this directory or one above it", MANIFEST))?;
+	let from_git = source.contains("://") || source.starts_with("git@") || source.ends_with(".git");
+	let (dir, entry) = if from_git {
+		let resolver = Resolver::new(&project.root)?;
+		let commit = resolver.fetch(source, rev)?;
+		let dir = resolver.checkout(source, &commit)?;
+		let rev = rev.map(|rev| format!(", rev = {}", toml::quote(rev))).unwrap_or_default();
+		(dir, format!("{{ git = {}{} }}", toml::quote(source), rev))
+	} else if rev.is_some() {
+		return Err("'--rev' only goes with git dependencies".to_string());
+	} else {
+		let path = relative(&project.root, Path::new(source))?;
+		(PathBuf::from(source), format!("{{ path = {} }}", toml::quote(&path)))
+	};
+
+	let file = dir.join(MANIFEST);
+	let text = fs::read_to_string(&file).map_err(|err| format!("Cannot read '{}': {}", file.display(), err))?;
+	let added = Manifest::parse(&text).map_err(|err| format!("{}: {}", file.display(), err))?;
+	if added.name == project.manifest.name {
+		return Err(format!("A project cannot depend on itself, '{}'", added.name));
+	}
+	if project.manifest.dependencies.iter().any(|dependency| dependency.name == added.name) {
+		return Err(format!("'{}' is already a dependency", added.name));
+	}
+
+	let path = project.manifest_path();
+	let original = fs::read_to_string(&path).map_err(|err| format!("Cannot read '{}': {}", path.display(), err))?;
+	let changed = with_dependency(&original, &format!("{} = {}", added.name, entry));
+	fs::write(&path, changed).map_err(|err| format!("Cannot write '{}': {}", path.display(), err))?;
+	// Loading the project checks what was added, and pins it in the lockfile
+	if let Err(err) = Project::load(&project.root) {
+		let _ = fs::write(&path, original);
+		return Err(err);
+	}
+	Ok(format!("Added {} {} from {}", added.name, added.version, source))
+}
+
+// A manifest with a line added to the end of its [dependencies] table, which
+// is added if there is none.
+fn with_dependency(manifest: &str, line: &str) -> String {
+	let lines: Vec<&str> = manifest.lines().collect();
+	let Some(header) = lines.iter().position(|line| line.trim() == "[dependencies]") else {
+		let separator = if manifest.is_empty() || manifest.ends_with("\n\n") { "" } else if manifest.ends_with('\n') { "\n" } else { "\n\n" };
+		return format!("{}{}[dependencies]\n{}\n", manifest, separator, line);
+	};
+	let end = lines[header + 1..].iter().position(|line| line.trim_start().starts_with('[')).map_or(lines.len(), |at| header + 1 + at);
+	// After the last entry, not after the blank lines before the next table
+	let at = (header + 1..end).rev().find(|&index| !lines[index].trim().is_empty()).map_or(header + 1, |index| index + 1);
+	let mut changed: Vec<&str> = lines[..at].to_vec();
+	changed.push(line);
+	changed.extend(&lines[at..]);
+	changed.join("\n") + "\n"
+}
+
+// How to get to `to` from `from`, as a manifest would write it.
+fn relative(from: &Path, to: &Path) -> Result<String, String> {
+	let absolute = |path: &Path| {
+		let path = if path.as_os_str().is_empty() { Path::new(".") } else { path };
+		path.canonicalize().map_err(|err| format!("Cannot read '{}': {}", path.display(), err))
+	};
+	let (from, to) = (absolute(from)?, absolute(to)?);
+	let common = from.components().zip(to.components()).take_while(|(a, b)| a == b).count();
+	let mut parts: Vec<String> = from.components().skip(common).map(|_| "..".to_string()).collect();
+	parts.extend(to.components().skip(common).map(|part| part.as_os_str().to_string_lossy().into_owned()));
+	Ok(if parts.is_empty() { ".".to_string() } else { parts.join("/") })
+}
+
+// `$DOTFUN_HOME/git`, or `~/.dotfun/git`.
+fn cache_dir() -> PathBuf {
+	let home = env::var_os("DOTFUN_HOME").map(PathBuf::from).or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(HOME)));
+	home.unwrap_or_else(|| PathBuf::from(HOME)).join("git")
+}
+
+// A name for the clone of `url`: its last part, for people looking in the
+// cache, and a hash of all of it, so that different repositories of the same
+// name do not meet.
+fn repository_key(url: &str) -> String {
+	let name = url.trim_end_matches('/').rsplit(['/', ':']).next().unwrap_or("");
+	let name: String = name.trim_end_matches(".git").chars().filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_')).collect();
+	// FNV-1a, which unlike the standard library's hasher is the same everywhere
+	let hash = url.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x100_0000_01b3));
+	format!("{}-{:016x}", name, hash)
+}
+
+// Runs git in `dir`, returning what it printed, trimmed.
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+	let output = Command::new("git").args(args).current_dir(dir).output().map_err(|err| format!("Cannot run git: {}", err))?;
+	if !output.status.success() {
+		let message = String::from_utf8_lossy(&output.stderr);
+		return Err(format!("'git {}' failed: {}", args[0], message.trim()));
+	}
+	Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
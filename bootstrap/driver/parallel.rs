@@ -0,0 +1,53 @@
+use std::num::NonZero;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::diagnostics::ice;
+
+// How many threads `map` uses, or 0 for as many as there are cores
+static THREADS: AtomicUsize = AtomicUsize::new(0);
+
+// Sets how many threads `map` uses from now on, or with 0 to use as many as
+// there are cores. What it works out is the same however many there are.
+pub fn set_threads(threads: usize) {
+	THREADS.store(threads, Ordering::Relaxed);
+}
+
+// `f` of each item, in the order of `items`, worked out on as many threads as
+// the machine has cores, or as `set_threads` says. Items are handed out one at a time, so a few large
+// files do not hold up the rest. A panic on any thread is an internal
+// compiler error, reported by the `ice::catch` the caller is in.
+pub fn map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+	let threads = match THREADS.load(Ordering::Relaxed) {
+		0 => thread::available_parallelism().map_or(1, NonZero::get),
+		threads => threads
+	};
+	let threads = threads.min(items.len());
+	if threads <= 1 {
+		return items.iter().map(f).collect();
+	}
+	let next = AtomicUsize::new(0);
+	let results: Mutex<Vec<Option<R>>> = Mutex::new((0..items.len()).map(|_| None).collect());
+	let failed = thread::scope(|scope| {
+		let workers: Vec<_> = (0..threads)
+			.map(|_| {
+				scope.spawn(|| {
+					ice::catch(|| {
+						loop {
+							let index = next.fetch_add(1, Ordering::Relaxed);
+							let Some(item) = items.get(index) else { break };
+							let result = f(item);
+							results.lock().unwrap()[index] = Some(result);
+						}
+					})
+				})
+			})
+			.collect();
+		workers.into_iter().find_map(|worker| worker.join().ok()?.err())
+	});
+	if let Some(ice) = failed {
+		ice::resume(ice);
+	}
+	results.into_inner().unwrap().into_iter().map(Option::unwrap).collect()
+}
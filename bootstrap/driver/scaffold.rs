@@ -0,0 +1,108 @@
+use std::fs;
+use std::path::Path;
+
+use crate::driver::manifest::{self, MANIFEST, TESTS};
+use crate::driver::toml;
+
+// What a new project starts as: a program to run, or a library for other
+// projects to depend on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Template {
+	Bin,
+	Lib
+}
+
+impl Template {
+	fn describe(self) -> &'static str {
+		match self {
+			Template::Bin => "binary",
+			Template::Lib => "library"
+		}
+	}
+}
+
+const BIN_MAIN: &str = "/// What `main` prints
+fn greeting(name: String): String = \"Hello, \" + name + \"!\"
+
+fn main() {
+	println(greeting(\"world\"))
+}
+";
+
+const BIN_TEST: &str = "@Test
+fn greets_by_name() {
+	val greeting = greeting(\"dotfun\")
+	if greeting != \"Hello, dotfun!\" {
+		throw \"Expected 'Hello, dotfun!', got '\" + greeting + \"'\"
+	}
+}
+";
+
+const LIB_MAIN: &str = "/// The sum of `a` and `b`
+fn add(a: Int, b: Int): Int = a + b
+";
+
+const LIB_TEST: &str = "@Test
+fn adds() {
+	if add(2, 3) != 5 {
+		throw \"Expected add(2, 3) to be 5\"
+	}
+}
+";
+
+// `dotfun doc` writes here
+const GITIGNORE: &str = "/doc/\n";
+
+// `dotfun new`: a project in a new directory, named after it.
+pub fn new(dir: &Path, template: Template) -> Result<String, String> {
+	if dir.exists() {
+		return Err(format!("'{}' already exists; use 'dotfun init' to make a project of a directory", dir.display()));
+	}
+	let name = name(dir)?;
+	write(dir, &name, template)?;
+	Ok(format!("Created {} project '{}' in {}", template.describe(), name, dir.display()))
+}
+
+// `dotfun init`: a project of a directory that already exists, named after
+// it. Files already there are kept.
+pub fn init(dir: &Path, template: Template) -> Result<String, String> {
+	if dir.join(MANIFEST).exists() {
+		return Err(format!("'{}' is already a project", dir.display()));
+	}
+	let absolute = dir.canonicalize().map_err(|err| format!("Cannot read '{}': {}", dir.display(), err))?;
+	let name = name(&absolute)?;
+	write(dir, &name, template)?;
+	Ok(format!("Created {} project '{}'", template.describe(), name))
+}
+
+fn name(dir: &Path) -> Result<String, String> {
+	let name = dir.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+	if !manifest::is_package_name(&name) {
+		return Err(format!("'{}' is not a package name; use letters, digits, '_' and '-'", name));
+	}
+	Ok(name)
+}
+
+fn write(dir: &Path, name: &str, template: Template) -> Result<(), String> {
+	// Libraries have no `main`, so the file is named for what it is
+	let (source, main, test) = match template {
+		Template::Bin => ("main.gl", BIN_MAIN, BIN_TEST),
+		Template::Lib => ("lib.gl", LIB_MAIN, LIB_TEST)
+	};
+	let manifest = format!("[package]\nname = {}\nversion = \"0.1.0\"\n\n[dependencies]\n", toml::quote(name));
+	let files = [
+		(MANIFEST.to_string(), manifest),
+		(format!("src/{}", source), main.to_string()),
+		(format!("{}/{}", TESTS, source.replace(".gl", "_test.gl")), test.to_string()),
+		(".gitignore".to_string(), GITIGNORE.to_string())
+	];
+	for (path, text) in files {
+		let path = dir.join(path);
+		if path.exists() {
+			continue;
+		}
+		let written = path.parent().map_or(Ok(()), fs::create_dir_all).and_then(|()| fs::write(&path, text));
+		written.map_err(|err| format!("Cannot write '{}': {}", path.display(), err))?;
+	}
+	Ok(())
+}
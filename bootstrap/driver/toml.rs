@@ -0,0 +1,283 @@
+use std::collections::{BTreeMap, HashSet};
+
+// The part of TOML that `dotfun.toml` needs: `[table]` headers, `key = value`
+// pairs, strings, integers, booleans, arrays and inline tables, and `#`
+// comments. Dates, floats and arrays of tables are left out.
+
+pub type Table = BTreeMap<String, Value>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+	String(String),
+	Integer(i64),
+	Boolean(bool),
+	Array(Vec<Value>),
+	Table(Table)
+}
+
+impl Value {
+	pub fn kind(&self) -> &'static str {
+		match self {
+			Value::String(_) => "a string",
+			Value::Integer(_) => "an integer",
+			Value::Boolean(_) => "a boolean",
+			Value::Array(_) => "an array",
+			Value::Table(_) => "a table"
+		}
+	}
+}
+
+// `text` as a TOML string.
+pub fn quote(text: &str) -> String {
+	let mut quoted = String::from('"');
+	for c in text.chars() {
+		match c {
+			'"' => quoted.push_str("\\\""),
+			'\\' => quoted.push_str("\\\\"),
+			'\n' => quoted.push_str("\\n"),
+			'\t' => quoted.push_str("\\t"),
+			'\r' => quoted.push_str("\\r"),
+			c => quoted.push(c)
+		}
+	}
+	quoted.push('"');
+	quoted
+}
+
+// A problem in a TOML file, at a line counted from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+	pub line: usize,
+	pub message: String
+}
+
+pub fn parse(text: &str) -> Result<Table, Error> {
+	let mut reader = Reader { chars: text.chars().collect(), at: 0, line: 1 };
+	let mut root = Table::new();
+	let mut current: Vec<String> = Vec::new();
+	let mut headers = HashSet::new();
+	loop {
+		reader.skip_blank();
+		let Some(c) = reader.peek() else {
+			return Ok(root);
+		};
+		if c == '[' {
+			reader.at += 1;
+			current = reader.key()?;
+			reader.expect(']')?;
+			reader.table_at(&mut root, &current)?;
+			if !headers.insert(current.clone()) {
+				return Err(reader.error(format!("Table '{}' is defined twice", current.join("."))));
+			}
+		} else {
+			let key = reader.key()?;
+			reader.expect('=')?;
+			let value = reader.value()?;
+			let table = reader.table_at(&mut root, &current)?;
+			reader.insert(table, &key, value)?;
+		}
+		reader.skip_spaces();
+		match reader.peek() {
+			None | Some('\n') | Some('#') => {}
+			Some(c) => return Err(reader.error(format!("Expected a line break, found '{}'", c)))
+		}
+	}
+}
+
+struct Reader {
+	chars: Vec<char>,
+	at: usize,
+	line: usize
+}
+
+impl Reader {
+	fn error(&self, message: impl Into<String>) -> Error {
+		Error { line: self.line, message: message.into() }
+	}
+
+	fn peek(&self) -> Option<char> {
+		self.chars.get(self.at).copied()
+	}
+
+	fn next(&mut self) -> Option<char> {
+		let c = self.peek()?;
+		self.at += 1;
+		if c == '\n' {
+			self.line += 1;
+		}
+		Some(c)
+	}
+
+	fn skip_spaces(&mut self) {
+		while matches!(self.peek(), Some(' ' | '\t' | '\r')) {
+			self.at += 1;
+		}
+	}
+
+	// Spaces, line breaks and comments
+	fn skip_blank(&mut self) {
+		loop {
+			self.skip_spaces();
+			match self.peek() {
+				Some('\n') => {
+					self.next();
+				}
+				Some('#') => {
+					while !matches!(self.peek(), None | Some('\n')) {
+						self.at += 1;
+					}
+				}
+				_ => return
+			}
+		}
+	}
+
+	fn expect(&mut self, expected: char) -> Result<(), Error> {
+		self.skip_spaces();
+		match self.peek() {
+			Some(c) if c == expected => {
+				self.at += 1;
+				Ok(())
+			}
+			Some(c) => Err(self.error(format!("Expected '{}', found '{}'", expected, c))),
+			None => Err(self.error(format!("Expected '{}', found the end of the file", expected)))
+		}
+	}
+
+	// A dotted key, like `a.b` or `"a b".c`
+	fn key(&mut self) -> Result<Vec<String>, Error> {
+		let mut parts = Vec::new();
+		loop {
+			self.skip_spaces();
+			let part = match self.peek() {
+				Some('"' | '\'') => self.string()?,
+				_ => {
+					let start = self.at;
+					while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+						self.at += 1;
+					}
+					if self.at == start {
+						return Err(self.error("Expected a key"));
+					}
+					self.chars[start..self.at].iter().collect()
+				}
+			};
+			parts.push(part);
+			self.skip_spaces();
+			if self.peek() != Some('.') {
+				return Ok(parts);
+			}
+			self.at += 1;
+		}
+	}
+
+	fn value(&mut self) -> Result<Value, Error> {
+		self.skip_spaces();
+		match self.peek() {
+			Some('"' | '\'') => Ok(Value::String(self.string()?)),
+			Some('[') => {
+				self.at += 1;
+				let mut values = Vec::new();
+				loop {
+					self.skip_blank();
+					if self.peek() == Some(']') {
+						self.at += 1;
+						return Ok(Value::Array(values));
+					}
+					values.push(self.value()?);
+					self.skip_blank();
+					match self.peek() {
+						Some(',') => self.at += 1,
+						Some(']') => {}
+						_ => return Err(self.error("Expected ',' or ']' in an array"))
+					}
+				}
+			}
+			Some('{') => {
+				self.at += 1;
+				let mut table = Table::new();
+				self.skip_spaces();
+				if self.peek() == Some('}') {
+					self.at += 1;
+					return Ok(Value::Table(table));
+				}
+				loop {
+					let key = self.key()?;
+					self.expect('=')?;
+					let value = self.value()?;
+					self.insert(&mut table, &key, value)?;
+					self.skip_spaces();
+					match self.next() {
+						Some(',') => {}
+						Some('}') => return Ok(Value::Table(table)),
+						_ => return Err(self.error("Expected ',' or '}' in an inline table"))
+					}
+				}
+			}
+			Some(c) if c.is_ascii_alphanumeric() || c == '-' || c == '+' => {
+				let start = self.at;
+				while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '_' | '.' | ':')) {
+					self.at += 1;
+				}
+				let word: String = self.chars[start..self.at].iter().collect();
+				match word.as_str() {
+					"true" => Ok(Value::Boolean(true)),
+					"false" => Ok(Value::Boolean(false)),
+					_ => {
+						let value = word.replace('_', "").parse();
+						value.map(Value::Integer).map_err(|_| self.error(format!("'{}' is not a value dotfun.toml can have", word)))
+					}
+				}
+			}
+			Some(c) => Err(self.error(format!("Expected a value, found '{}'", c))),
+			None => Err(self.error("Expected a value, found the end of the file"))
+		}
+	}
+
+	fn string(&mut self) -> Result<String, Error> {
+		let quote = self.next().unwrap();
+		let mut text = String::new();
+		loop {
+			match self.next() {
+				None | Some('\n') => return Err(self.error("Unterminated string")),
+				Some(c) if c == quote => return Ok(text),
+				Some('\\') if quote == '"' => {
+					let escaped = match self.next() {
+						Some('n') => '\n',
+						Some('t') => '\t',
+						Some('r') => '\r',
+						Some('"') => '"',
+						Some('\\') => '\\',
+						Some(c) => return Err(self.error(format!("Unknown escape '\\{}'", c))),
+						None => return Err(self.error("Unterminated string"))
+					};
+					text.push(escaped);
+				}
+				Some(c) => text.push(c)
+			}
+		}
+	}
+
+	// The table a dotted key names, made along with any tables above it.
+	fn table_at<'t>(&self, root: &'t mut Table, path: &[String]) -> Result<&'t mut Table, Error> {
+		let mut table = root;
+		for part in path {
+			let entry = table.entry(part.clone()).or_insert_with(|| Value::Table(Table::new()));
+			table = match entry {
+				Value::Table(inner) => inner,
+				other => return Err(self.error(format!("'{}' is {}, not a table", part, other.kind())))
+			};
+		}
+		Ok(table)
+	}
+
+	fn insert(&self, table: &mut Table, key: &[String], value: Value) -> Result<(), Error> {
+		let (last, path) = key.split_last().unwrap();
+		let table = self.table_at(table, path)?;
+		if table.contains_key(last) {
+			return Err(self.error(format!("'{}' is set twice", key.join("."))));
+		}
+		table.insert(last.clone(), value);
+		Ok(())
+	}
+}
@@ -0,0 +1,181 @@
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::driver::args::Options;
+use crate::driver::database::Database;
+use crate::driver::{Session, inputs, report_timings};
+
+// How often the files are looked at where the system cannot tell when they
+// change, or will not tell about as many directories as there are. Either
+// way, what changed is found by comparing modification times.
+const POLL: Duration = Duration::from_millis(200);
+
+// Editors can save a file in more than one write, and save several files at
+// once, so a build waits until nothing has changed for this long.
+const SETTLE: Duration = Duration::from_millis(100);
+
+// Each input and source file, with when it was last modified and its size,
+// or None when it is not there.
+type Snapshot = Vec<(String, Option<(SystemTime, u64)>)>;
+
+// Builds the files `options` name, then again each time one changes or one is
+// added to or removed from a directory, clearing the terminal first. What
+// was worked out about files that have not changed is kept from one build to
+// the next, so only the packages a change could affect are checked again.
+// Only returns when stopped, by Ctrl-C, which ends the process.
+pub fn watch(options: &Options, build: fn(&mut Session) -> u8) -> u8 {
+	let mut database = Database::new();
+	loop {
+		let snapshot = snapshot(options);
+		clear();
+		match Session::load(options) {
+			Ok(mut session) => {
+				session.database = database;
+				build(&mut session);
+				database = std::mem::take(&mut session.database);
+				report_timings(options);
+				let count = session.paths.len();
+				eprintln!("\nWatching {} file{} for changes; press Ctrl-C to stop", count, if count == 1 { "" } else { "s" });
+			}
+			Err(message) => {
+				eprintln!("error: {}", message);
+				eprintln!("\nWatching for changes; press Ctrl-C to stop");
+			}
+		}
+		wait(options, snapshot);
+	}
+}
+
+fn snapshot(options: &Options) -> Snapshot {
+	let mut paths = options.inputs.clone();
+	if let Ok(inputs) = inputs(options) {
+		paths.extend(inputs.project.iter().map(|project| project.manifest_path().display().to_string()));
+		paths.extend(inputs.paths);
+	}
+	paths
+		.into_iter()
+		.map(|path| {
+			let modified = fs::metadata(&path).ok().and_then(|metadata| Some((metadata.modified().ok()?, metadata.len())));
+			(path, modified)
+		})
+		.collect()
+}
+
+// Waits for the files to change from `last`, and then to stop changing.
+fn wait(options: &Options, last: Snapshot) {
+	// Watched before looking, so nothing that changes in between is missed
+	let mut events = notify::Events::new(&directories(&last));
+	let mut current = snapshot(options);
+	while current == last {
+		match &events {
+			Some(watched) if watched.wait() => {}
+			_ => {
+				events = None;
+				thread::sleep(POLL);
+			}
+		}
+		current = snapshot(options);
+	}
+	loop {
+		thread::sleep(SETTLE);
+		let settled = snapshot(options);
+		if settled == current {
+			return;
+		}
+		current = settled;
+	}
+}
+
+// The directories files can change or be added in: those named, and those
+// the files are in.
+fn directories(snapshot: &Snapshot) -> Vec<PathBuf> {
+	let mut directories: Vec<PathBuf> = snapshot
+		.iter()
+		.map(|(path, _)| Path::new(path))
+		.map(|path| match path.parent() {
+			_ if path.is_dir() => path.to_path_buf(),
+			Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+			_ => PathBuf::from(".")
+		})
+		.collect();
+	directories.sort();
+	directories.dedup();
+	directories
+}
+
+// Clears the terminal and its scrollback, so only the latest build shows.
+fn clear() {
+	if io::stderr().is_terminal() {
+		eprint!("\x1b[2J\x1b[3J\x1b[H");
+		let _ = io::stderr().flush();
+	}
+}
+
+#[cfg(target_os = "linux")]
+mod notify {
+	use std::ffi::{CString, c_char, c_int};
+	use std::fs::File;
+	use std::io::Read;
+	use std::os::fd::FromRawFd;
+	use std::os::unix::ffi::OsStrExt;
+	use std::path::PathBuf;
+
+	const IN_CLOEXEC: c_int = 0o2000000;
+	// A file in the directory modified, its attributes changed, closed after
+	// writing, moved out or in, created or deleted, or the directory itself
+	// deleted or moved
+	const CHANGES: u32 = 0x2 | 0x4 | 0x8 | 0x40 | 0x80 | 0x100 | 0x200 | 0x400 | 0x800;
+
+	unsafe extern "C" {
+		fn inotify_init1(flags: c_int) -> c_int;
+		fn inotify_add_watch(fd: c_int, pathname: *const c_char, mask: u32) -> c_int;
+	}
+
+	// What inotify tells about changes in some directories, until dropped.
+	pub struct Events(File);
+
+	impl Events {
+		// None when the directories cannot all be watched, like when there
+		// are more than the system allows.
+		pub fn new(directories: &[PathBuf]) -> Option<Events> {
+			let fd = unsafe { inotify_init1(IN_CLOEXEC) };
+			if fd < 0 {
+				return None;
+			}
+			let events = Events(unsafe { File::from_raw_fd(fd) });
+			for directory in directories {
+				let path = CString::new(directory.as_os_str().as_bytes()).ok()?;
+				if unsafe { inotify_add_watch(fd, path.as_ptr(), CHANGES) } < 0 {
+					return None;
+				}
+			}
+			Some(events)
+		}
+
+		// Blocks until something changes, or returns false if it cannot.
+		pub fn wait(&self) -> bool {
+			let mut buffer = [0; 4096];
+			(&self.0).read(&mut buffer).is_ok_and(|read| read > 0)
+		}
+	}
+}
+
+#[cfg(not(target_os = "linux"))]
+mod notify {
+	use std::path::PathBuf;
+
+	pub struct Events;
+
+	impl Events {
+		pub fn new(_: &[PathBuf]) -> Option<Events> {
+			None
+		}
+
+		pub fn wait(&self) -> bool {
+			false
+		}
+	}
+}
@@ -0,0 +1,295 @@
+pub mod spacing;
+
+use crate::diagnostics::Diagnostic;
+use crate::format::spacing::{carries_on, continues, spaced};
+use crate::lexer::lexer::Lexer;
+use crate::lexer::token::{Token, TokenType};
+use crate::parser::parser::Parser;
+
+// Lines longer than this have their argument lists wrapped.
+pub const MAX_WIDTH: usize = 100;
+
+// Columns a tab counts for when measuring lines.
+const TAB_WIDTH: usize = 4;
+
+// Lays out a file the one way `dotfun fmt` accepts: a tab per level of
+// indentation, single spaces between tokens where spacing.rs says, no more
+// than one blank line in a row and none just inside braces, and argument
+// lists too long for a line put one argument per line. Line breaks are where
+// the source has them, since they end statements; so are comments, which
+// the lexer drops and are found again in the source between tokens.
+//
+// Only files that parse are formatted, so a formatting mistake cannot make a
+// broken file worse; their errors are returned instead.
+pub fn format(source: &str) -> Result<String, Vec<Diagnostic>> {
+	let tokens = Lexer::new(source.to_string()).lex().map_err(|err| vec![*err])?.clone();
+	Parser::new(tokens.clone()).parse()?;
+
+	let mut layout = Layout { source, lines: Vec::new(), frames: Vec::new(), breaks: 0, last: None, header: false };
+	layout.tokens(&tokens);
+
+	let mut text = String::new();
+	for line in layout.lines.into_iter().flat_map(wrap) {
+		text.push_str(&render(&line));
+		text.push('\n');
+	}
+	Ok(text)
+}
+
+// A token, run of string literal, or comment, as it goes on a line.
+#[derive(Debug, Clone)]
+pub struct Piece {
+	pub text: String,
+	// None for comments
+	pub kind: Option<TokenType>,
+	// Whether the source has whitespace before it, or for colons whether
+	// one goes there: in headers like `class Dog : Animal` and nowhere else
+	pub spaced: bool,
+	// The token before it, on this line or an earlier one
+	pub before: Option<TokenType>
+}
+
+// A line of output; blank when it has no pieces.
+#[derive(Debug, Clone, Default)]
+struct Line {
+	indent: usize,
+	pieces: Vec<Piece>
+}
+
+// A bracket not yet closed.
+struct Frame {
+	kind: TokenType,
+	// Indentation of the line it is on
+	base: usize,
+	// For braces of a `switch`: whether a `case` has been seen, after which
+	// lines other than cases are the bodies of cases
+	cases: bool
+}
+
+struct Layout<'a> {
+	source: &'a str,
+	lines: Vec<Line>,
+	frames: Vec<Frame>,
+	// Line breaks in the source since the last piece
+	breaks: usize,
+	// The kind of the last token
+	last: Option<TokenType>,
+	// Between `class`, `interface`, `struct` or `enum` and the `{` after it
+	header: bool
+}
+
+impl<'a> Layout<'a> {
+	fn tokens(&mut self, tokens: &[Token]) {
+		let mut end = 0;
+		let mut index = 0;
+		while index < tokens.len() && tokens[index].token_type != TokenType::Eof {
+			let token = &tokens[index];
+			self.trivia(end, token.span.start, Some(token.token_type));
+			let mut kind = token.token_type;
+			// Strings and what is interpolated into them stay as written
+			if kind == TokenType::StringStart {
+				let mut depth = 0;
+				while index < tokens.len() {
+					match tokens[index].token_type {
+						TokenType::StringStart => depth += 1,
+						TokenType::StringEnd => depth -= 1,
+						_ => {}
+					}
+					if depth == 0 {
+						break;
+					}
+					index += 1;
+				}
+				kind = TokenType::StringLiteral;
+			}
+			let span_end = tokens[index.min(tokens.len() - 1)].span.end;
+			let spaced = match kind {
+				TokenType::Colon => self.header,
+				_ => self.source[..token.span.start].ends_with(char::is_whitespace)
+			};
+			match kind {
+				TokenType::Class | TokenType::Interface | TokenType::Struct | TokenType::Enum => self.header = true,
+				TokenType::LeftBrace => self.header = false,
+				_ => {}
+			}
+			self.push(Piece { text: self.source[token.span.start..span_end].to_string(), kind: Some(kind), spaced, before: self.last });
+			self.last = Some(kind);
+			end = span_end;
+			index += 1;
+		}
+		self.trivia(end, self.source.len(), None);
+	}
+
+	// Finds the line breaks and comments in the source between tokens.
+	// Comment lines are indented like the token after them.
+	fn trivia(&mut self, start: usize, end: usize, next: Option<TokenType>) {
+		let gap = &self.source[start..end];
+		let mut at = 0;
+		while at < gap.len() {
+			let rest = &gap[at..];
+			let length = if rest.starts_with("//") {
+				rest.find('\n').unwrap_or(rest.len())
+			} else if rest.starts_with("/*") {
+				rest.find("*/").map_or(rest.len(), |close| close + 2)
+			} else {
+				if rest.starts_with('\n') {
+					self.breaks += 1;
+				}
+				at += rest.chars().next().map_or(1, char::len_utf8);
+				continue;
+			};
+			let text = rest[..length].trim_end().to_string();
+			let spaced = self.source[..start + at].ends_with(char::is_whitespace);
+			let kind = next.filter(|kind| !closes(*kind));
+			self.place(kind);
+			self.lines.last_mut().unwrap().pieces.push(Piece { text, kind: None, spaced, before: self.last });
+			at += length;
+		}
+	}
+
+	fn push(&mut self, piece: Piece) {
+		let kind = piece.kind.unwrap();
+		self.place(Some(kind));
+		if closes(kind) {
+			self.frames.pop();
+		}
+		let line = self.lines.last_mut().unwrap();
+		line.pieces.push(piece);
+		if opens(kind) {
+			let base = line.indent;
+			self.frames.push(Frame { kind, base, cases: false });
+		}
+	}
+
+	// Starts a new line for what comes next if the source has a line break
+	// before it, along with a blank line for two or more.
+	fn place(&mut self, kind: Option<TokenType>) {
+		if !self.lines.is_empty() && self.breaks == 0 {
+			return;
+		}
+		let previous = self.lines.last().and_then(|line| line.pieces.last());
+		let after_open = previous.and_then(|piece| piece.kind).is_some_and(opens);
+		let before_close = kind.is_some_and(closes) && self.lines.last().is_some_and(|line| !line.pieces.is_empty());
+		if self.breaks >= 2 && previous.is_some() && !after_open && !before_close {
+			self.lines.push(Line::default());
+		}
+		let indent = self.indent(kind);
+		self.lines.push(Line { indent, pieces: Vec::new() });
+		self.breaks = 0;
+	}
+
+	// The indentation of a line starting with `kind`, or with a comment for
+	// None: one more than the line of the innermost open bracket, and one more
+	// again for the bodies of cases and for lines carrying on an expression.
+	fn indent(&mut self, kind: Option<TokenType>) -> usize {
+		if let Some(kind) = kind
+			&& closes(kind)
+		{
+			return self.frames.last().map_or(0, |frame| frame.base);
+		}
+		let Some(frame) = self.frames.last_mut() else {
+			return usize::from(self.carries_on(kind));
+		};
+		let inner = frame.base + 1;
+		if frame.kind != TokenType::LeftBrace {
+			return inner;
+		}
+		if matches!(kind, Some(TokenType::Case | TokenType::Default)) {
+			frame.cases = true;
+			return inner;
+		}
+		let cases = frame.cases;
+		inner + usize::from(cases) + usize::from(self.carries_on(kind))
+	}
+
+	fn carries_on(&self, kind: Option<TokenType>) -> bool {
+		let ended = self.lines.iter().rev().flat_map(|line| line.pieces.iter().rev()).find_map(|piece| piece.kind);
+		kind.is_some_and(carries_on) || ended.is_some_and(continues)
+	}
+}
+
+fn opens(kind: TokenType) -> bool {
+	matches!(kind, TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace)
+}
+
+fn closes(kind: TokenType) -> bool {
+	matches!(kind, TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace)
+}
+
+fn render(line: &Line) -> String {
+	if line.pieces.is_empty() {
+		return String::new();
+	}
+	let mut text = "\t".repeat(line.indent);
+	for (index, piece) in line.pieces.iter().enumerate() {
+		if index > 0 && spaced(&line.pieces[index - 1], piece) {
+			text.push(' ');
+		}
+		text.push_str(&piece.text);
+	}
+	text
+}
+
+fn width(text: &str) -> usize {
+	let first = text.lines().next().unwrap_or("");
+	first.chars().map(|c| if c == '\t' { TAB_WIDTH } else { 1 }).sum()
+}
+
+// Puts the arguments of the first call or parameter list on a line too long
+// for it one per line, then does the same to the lines that makes.
+fn wrap(line: Line) -> Vec<Line> {
+	if width(&render(&line)) <= MAX_WIDTH {
+		return vec![line];
+	}
+	let Some((open, close)) = arguments(&line.pieces) else {
+		return vec![line];
+	};
+
+	let indent = line.indent;
+	let mut pieces = line.pieces;
+	let tail = pieces.split_off(close);
+	let inside = pieces.split_off(open + 1);
+	let mut lines = vec![Line { indent, pieces }];
+	let mut argument = Vec::new();
+	let mut depth = 0;
+	for piece in inside {
+		match piece.kind {
+			Some(kind) if opens(kind) => depth += 1,
+			Some(kind) if closes(kind) => depth -= 1,
+			_ => {}
+		}
+		let comma = depth == 0 && piece.kind == Some(TokenType::Comma);
+		argument.push(piece);
+		if comma {
+			lines.push(Line { indent: indent + 1, pieces: std::mem::take(&mut argument) });
+		}
+	}
+	if !argument.is_empty() {
+		lines.push(Line { indent: indent + 1, pieces: argument });
+	}
+	lines.push(Line { indent, pieces: tail });
+	lines.into_iter().flat_map(wrap).collect()
+}
+
+// The first `(` that starts a call's arguments or a function's parameters,
+// with something in them, and its `)`, both on this line.
+fn arguments(pieces: &[Piece]) -> Option<(usize, usize)> {
+	let call = |piece: &Piece| piece.before.is_some_and(|kind| spacing::ends_value(kind) || kind == TokenType::Function);
+	pieces.iter().enumerate().find_map(|(open, piece)| {
+		if piece.kind != Some(TokenType::LeftParen) || !call(piece) {
+			return None;
+		}
+		let mut depth = 0;
+		for (index, piece) in pieces.iter().enumerate().skip(open) {
+			match piece.kind {
+				Some(kind) if opens(kind) => depth += 1,
+				Some(kind) if closes(kind) => depth -= 1,
+				_ => {}
+			}
+			if depth == 0 {
+				return (index > open + 1).then_some((open, index));
+			}
+		}
+		None
+	})
+}
@@ -0,0 +1,120 @@
+use crate::format::Piece;
+use crate::lexer::token::TokenType;
+
+// Whether a space goes between two pieces on the same line. Operators get one
+// on each side, punctuation hugs what it belongs to, and `<`, `>` and `>>`,
+// which close type arguments as often as they compare, keep whether the
+// source had one. Colons are spaced as the layout says.
+pub fn spaced(prev: &Piece, next: &Piece) -> bool {
+	let (Some(before), Some(after)) = (prev.kind, next.kind) else {
+		// Comments keep their distance, except right inside brackets
+		let inside = matches!(prev.kind, Some(TokenType::LeftParen | TokenType::LeftBracket))
+			|| matches!(next.kind, Some(TokenType::RightParen | TokenType::RightBracket));
+		return !inside;
+	};
+	let value = ends_value(before);
+	let closes_type = |kind: TokenType| matches!(kind, TokenType::Greater | TokenType::ShiftRight);
+
+	// What the next token wants on its left
+	match after {
+		TokenType::Comma | TokenType::Semicolon | TokenType::Dot | TokenType::ColonColon | TokenType::DotDot => return false,
+		TokenType::Colon => return next.spaced,
+		TokenType::RightParen | TokenType::RightBracket | TokenType::BangBang => return false,
+		TokenType::RightBrace => return before != TokenType::LeftBrace,
+		TokenType::Question if value || closes_type(before) => return false,
+		TokenType::PlusPlus | TokenType::MinusMinus if value => return false,
+		TokenType::LeftParen if value => return false,
+		TokenType::LeftParen if before == TokenType::Function => return false,
+		TokenType::LeftBracket if value || before == TokenType::Question || closes_type(before) => return false,
+		TokenType::Less | TokenType::Greater | TokenType::ShiftRight => return next.spaced,
+		_ => {}
+	}
+
+	// What the previous token wants on its right
+	match before {
+		TokenType::LeftParen | TokenType::LeftBracket | TokenType::Dot | TokenType::ColonColon | TokenType::DotDot => false,
+		TokenType::AT | TokenType::Dollar | TokenType::Ellipsis | TokenType::NotBang => false,
+		TokenType::LeftBrace => true,
+		// A prefix, as in `?name` in annotations, when no type comes before
+		TokenType::Question => after != TokenType::Dot && prev.before.is_some_and(|kind| ends_value(kind) || closes_type(kind)),
+		// Unary when nothing with a value comes before
+		TokenType::Minus | TokenType::Plus | TokenType::PlusPlus | TokenType::MinusMinus => prev.before.is_some_and(ends_value),
+		TokenType::Less | TokenType::Greater | TokenType::ShiftRight => next.spaced,
+		_ => true
+	}
+}
+
+// Tokens that can end an operand, after which `-` subtracts and `(` calls.
+pub fn ends_value(kind: TokenType) -> bool {
+	matches!(
+		kind,
+		TokenType::Identifier
+			| TokenType::NumberLiteral
+			| TokenType::StringLiteral
+			| TokenType::True
+			| TokenType::False
+			| TokenType::Null
+			| TokenType::This
+			| TokenType::Super
+			| TokenType::Constructor
+			| TokenType::RightParen
+			| TokenType::RightBracket
+			| TokenType::BangBang
+	)
+}
+
+// Tokens at the end of a line that leave the expression unfinished, so the
+// next line carries it on.
+pub fn continues(kind: TokenType) -> bool {
+	matches!(
+		kind,
+		TokenType::Plus
+			| TokenType::Minus
+			| TokenType::Star
+			| TokenType::Slash
+			| TokenType::Percent
+			| TokenType::AndAnd
+			| TokenType::OrOr
+			| TokenType::And
+			| TokenType::Or
+			| TokenType::EqualEqual
+			| TokenType::NotEqual
+			| TokenType::LessEqual
+			| TokenType::GreaterEqual
+			| TokenType::Equal
+			| TokenType::PlusEqual
+			| TokenType::MinusEqual
+			| TokenType::StarEqual
+			| TokenType::SlashEqual
+			| TokenType::PercentEqual
+			| TokenType::Arrow
+			| TokenType::FatArrow
+			| TokenType::QuestionQuestion
+			| TokenType::QuestionColon
+			| TokenType::BitAnd
+			| TokenType::BitOr
+			| TokenType::BitXor
+			| TokenType::ShiftLeft
+			| TokenType::Dot
+	)
+}
+
+// Tokens at the start of a line that carry on the line before.
+pub fn carries_on(kind: TokenType) -> bool {
+	matches!(
+		kind,
+		TokenType::Dot
+			| TokenType::Question
+			| TokenType::QuestionQuestion
+			| TokenType::QuestionColon
+			| TokenType::AndAnd
+			| TokenType::OrOr
+			| TokenType::And
+			| TokenType::Or
+			| TokenType::Star
+			| TokenType::Slash
+			| TokenType::Percent
+			| TokenType::Arrow
+			| TokenType::FatArrow
+	)
+}
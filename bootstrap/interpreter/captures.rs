@@ -0,0 +1,251 @@
+use std::collections::HashSet;
+
+use crate::sema::hir::{Block, Expr, ExprKind, Pattern, Program, Stmt, StmtKind, Variable};
+use crate::sema::types::{FnId, LocalId};
+
+// What each lambda and local function captures from the functions around
+// it, worked out once before a program runs.
+//
+// A closure copies the locals it captures when it is made. A lambda copies a
+// `val` by value, since it never changes once set. Anything else is boxed:
+// it lives in a cell the closure shares with the function that declared it,
+// so a `mut` changed on either side is seen on the other, and stays alive as
+// long as the closure does. Local functions are made when the block they are
+// in starts, before its `val`s are set, so what they capture is boxed too,
+// and so are they, to call themselves and each other.
+pub struct Captures {
+	// By function id, the locals it uses from outside, for nested closures
+	// too, in the order they are first used
+	captured: Vec<Vec<LocalId>>,
+	// By local id, whether it lives in a cell
+	boxed: Vec<bool>
+}
+
+impl Captures {
+	pub fn new(program: &Program) -> Captures {
+		let mut walk = Walk {
+			program,
+			done: vec![None; program.functions.len()],
+			captured: HashSet::new(),
+			shared: HashSet::new()
+		};
+		for id in 0..program.functions.len() {
+			walk.function(FnId(id as u32));
+		}
+		for file in &program.files {
+			walk.stmts(&file.body, &mut Uses::default());
+		}
+		let boxed = (0..program.locals.len())
+			.map(|id| {
+				let local = LocalId(id as u32);
+				walk.captured.contains(&local) && (program.local(local).mutable || walk.shared.contains(&local))
+			})
+			.collect();
+		Captures { captured: walk.done.into_iter().map(Option::unwrap_or_default).collect(), boxed }
+	}
+
+	pub fn of(&self, function: FnId) -> &[LocalId] {
+		&self.captured[function.0 as usize]
+	}
+
+	pub fn boxed(&self, local: LocalId) -> bool {
+		self.boxed[local.0 as usize]
+	}
+}
+
+// The locals a function body uses and those it declares itself.
+#[derive(Default)]
+struct Uses {
+	used: Vec<LocalId>,
+	declared: HashSet<LocalId>
+}
+
+impl Uses {
+	fn add(&mut self, local: LocalId) {
+		if !self.used.contains(&local) {
+			self.used.push(local);
+		}
+	}
+}
+
+struct Walk<'a> {
+	program: &'a Program,
+	done: Vec<Option<Vec<LocalId>>>,
+	// Every local some closure captures
+	captured: HashSet<LocalId>,
+	// Locals bound to local functions and those they capture
+	shared: HashSet<LocalId>
+}
+
+impl<'a> Walk<'a> {
+	// Closures only nest, so this never comes back to a function it is
+	// still working out.
+	fn function(&mut self, id: FnId) -> Vec<LocalId> {
+		if let Some(captured) = &self.done[id.0 as usize] {
+			return captured.clone();
+		}
+		let function = self.program.function(id);
+		let mut uses = Uses::default();
+		for param in &function.params {
+			uses.declared.insert(param.local);
+			if let Some(default) = &param.default {
+				self.expr(default, &mut uses);
+			}
+		}
+		if let Some(body) = &function.body {
+			self.block(body, &mut uses);
+		}
+		let captured: Vec<LocalId> = uses.used.into_iter().filter(|local| !uses.declared.contains(local)).collect();
+		self.done[id.0 as usize] = Some(captured.clone());
+		captured
+	}
+
+	// Whatever the closure captures, the function making it uses.
+	fn closure(&mut self, id: FnId, uses: &mut Uses) -> Vec<LocalId> {
+		let captured = self.function(id);
+		for &local in &captured {
+			self.captured.insert(local);
+			uses.add(local);
+		}
+		captured
+	}
+
+	fn block(&mut self, block: &Block, uses: &mut Uses) {
+		self.stmts(&block.stmts, uses);
+	}
+
+	fn stmts(&mut self, stmts: &[Stmt], uses: &mut Uses) {
+		for stmt in stmts {
+			self.stmt(stmt, uses);
+		}
+	}
+
+	fn stmt(&mut self, stmt: &Stmt, uses: &mut Uses) {
+		match &stmt.kind {
+			StmtKind::Expr(expr) | StmtKind::Yield(expr) | StmtKind::Throw(expr) => self.expr(expr, uses),
+			StmtKind::Var { var, init } => {
+				if let Variable::Local(local) = var {
+					uses.declared.insert(*local);
+				}
+				if let Some(init) = init {
+					self.expr(init, uses);
+				}
+			}
+			StmtKind::Block(block) | StmtKind::Loop { body: block } => self.block(block, uses),
+			StmtKind::If { cond, then_branch, else_branch } => {
+				self.expr(cond, uses);
+				self.block(then_branch, uses);
+				if let Some(else_branch) = else_branch {
+					self.stmt(else_branch, uses);
+				}
+			}
+			StmtKind::While { cond, body } => {
+				self.expr(cond, uses);
+				self.block(body, uses);
+			}
+			StmtKind::For { bindings, iterable, body, .. } => {
+				uses.declared.extend(bindings.iter().copied());
+				self.expr(iterable, uses);
+				self.block(body, uses);
+			}
+			StmtKind::Labeled { body, .. } => self.stmt(body, uses),
+			StmtKind::Break(_) | StmtKind::Continue(_) => {}
+			StmtKind::Return(value) => {
+				if let Some(value) = value {
+					self.expr(value, uses);
+				}
+			}
+			StmtKind::Try { body, catches, finally } => {
+				self.block(body, uses);
+				for catch in catches {
+					uses.declared.insert(catch.local);
+					self.block(&catch.body, uses);
+				}
+				if let Some(finally) = finally {
+					self.block(finally, uses);
+				}
+			}
+			StmtKind::Switch { subject, cases, default, .. } => {
+				self.expr(subject, uses);
+				for case in cases {
+					for pattern in &case.patterns {
+						if let Pattern::Value(value) = pattern {
+							self.expr(value, uses);
+						}
+					}
+					self.block(&case.body, uses);
+				}
+				if let Some(default) = default {
+					self.block(default, uses);
+				}
+			}
+			StmtKind::Function { local, function } => {
+				uses.declared.insert(*local);
+				self.shared.insert(*local);
+				let captured = self.closure(*function, uses);
+				self.shared.extend(captured);
+			}
+		}
+	}
+
+	fn expr(&mut self, expr: &Expr, uses: &mut Uses) {
+		match &expr.kind {
+			ExprKind::Var(Variable::Local(local)) => uses.add(*local),
+			ExprKind::Lambda(function) => {
+				self.closure(*function, uses);
+			}
+			ExprKind::Literal(_)
+			| ExprKind::Var(Variable::Global(_))
+			| ExprKind::Function(_)
+			| ExprKind::Builtin(_)
+			| ExprKind::Native(_)
+			| ExprKind::This
+			| ExprKind::Super
+			| ExprKind::BackingField(_)
+			| ExprKind::Variant { .. } => {}
+			ExprKind::Unary { operand, .. } | ExprKind::Postfix { operand, .. } | ExprKind::Is { operand, .. } => self.expr(operand, uses),
+			ExprKind::Coerce { value, .. } | ExprKind::Await(value) | ExprKind::Throw(value) => self.expr(value, uses),
+			ExprKind::Member { object, .. } => self.expr(object, uses),
+			ExprKind::Binary { left, right, .. } | ExprKind::Range { start: left, end: right } => {
+				self.expr(left, uses);
+				self.expr(right, uses);
+			}
+			ExprKind::Assign { target, value, .. } => {
+				self.expr(target, uses);
+				self.expr(value, uses);
+			}
+			ExprKind::Index { object, index } => {
+				self.expr(object, uses);
+				self.expr(index, uses);
+			}
+			ExprKind::If { cond, then_branch, else_branch } => {
+				self.expr(cond, uses);
+				self.expr(then_branch, uses);
+				self.expr(else_branch, uses);
+			}
+			ExprKind::Block(block) => self.block(block, uses),
+			ExprKind::Call { callee, args } => {
+				self.expr(callee, uses);
+				self.exprs(args, uses);
+			}
+			ExprKind::SuperCall { args, .. } | ExprKind::New { args, .. } | ExprKind::Array(args) | ExprKind::Set(args) => self.exprs(args, uses),
+			ExprKind::Map(entries) => {
+				for (key, value) in entries {
+					self.expr(key, uses);
+					self.expr(value, uses);
+				}
+			}
+			ExprKind::Return(value) => {
+				if let Some(value) = value {
+					self.expr(value, uses);
+				}
+			}
+		}
+	}
+
+	fn exprs(&mut self, exprs: &[Expr], uses: &mut Uses) {
+		for expr in exprs {
+			self.expr(expr, uses);
+		}
+	}
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::ffi::{CStr, CString, c_char, c_void};
+
+use crate::interpreter::value::Value;
+use crate::sema::hir::{CType, Foreign};
+
+// The shared libraries `extern` functions are in, opened when one of them is
+// first called, and the functions looked up in them. They stay open for as
+// long as the program runs.
+#[derive(Default)]
+pub struct Libraries {
+	handles: HashMap<String, *mut c_void>,
+	symbols: HashMap<(String, String), *mut c_void>
+}
+
+impl Libraries {
+	pub fn new() -> Libraries {
+		Libraries::default()
+	}
+
+	// Calls the C function `name` with `args`, which code checked against
+	// what `foreign` says it takes.
+	pub fn call(&mut self, name: &str, foreign: &Foreign, args: &[Value]) -> Result<Value, String> {
+		if args.len() != foreign.params.len() {
+			let plural = if foreign.params.len() == 1 { "" } else { "s" };
+			return Err(format!("'{}' expects {} argument{} but {} were given", name, foreign.params.len(), plural, args.len()));
+		}
+		let symbol = self.symbol(&foreign.library, name)?;
+		// Strings go as copies that live until the call is over
+		let mut strings = Vec::new();
+		let (mut ints, mut floats) = ([0u64; CType::MAX_INTS], [0f64; CType::MAX_FLOATS]);
+		let (mut int, mut float) = (0, 0);
+		for (ctype, arg) in foreign.params.iter().zip(args) {
+			let register = match (ctype, arg) {
+				(CType::Float, Value::Float(value)) => Register::Float(*value),
+				(CType::Float, Value::Int(value)) => Register::Float(*value as f64),
+				(CType::Int, Value::Int(value)) => Register::Int(*value as u64),
+				(CType::Bool, Value::Bool(value)) => Register::Int(*value as u64),
+				(CType::NullableString | CType::NullablePointer, Value::Null) => Register::Int(0),
+				(CType::Pointer | CType::NullablePointer, Value::Pointer(address)) => Register::Int(*address as u64),
+				(CType::String | CType::NullableString, Value::String(text)) => {
+					let text = CString::new(text.to_string()).map_err(|_| format!("'{}' cannot pass a string with a NUL in it to C", name))?;
+					strings.push(text);
+					Register::Int(strings[strings.len() - 1].as_ptr() as u64)
+				}
+				(_, arg) => return Err(format!("'{}' cannot pass a '{}' to C as a {:?}", name, arg.type_name(), ctype))
+			};
+			match register {
+				Register::Int(bits) => {
+					ints[int] = bits;
+					int += 1;
+				}
+				Register::Float(value) => {
+					floats[float] = value;
+					float += 1;
+				}
+			}
+		}
+		let value = match foreign.ret {
+			CType::Float => Value::Float(unsafe { call_float(symbol, ints, floats) }),
+			ret => {
+				let result = unsafe { call_int(symbol, ints, floats) };
+				match ret {
+					CType::Int => Value::Int(result as i64),
+					CType::Bool => Value::Bool(result as u32 != 0),
+					CType::String | CType::NullableString if result != 0 => {
+						let text = unsafe { CStr::from_ptr(result as *const c_char) };
+						Value::string(text.to_string_lossy().into_owned())
+					}
+					CType::String => return Err(format!("'{}' returned NULL for a String", name)),
+					CType::Pointer | CType::NullablePointer if result != 0 => Value::Pointer(result as usize),
+					CType::Pointer => return Err(format!("'{}' returned NULL for a Pointer", name)),
+					_ => Value::Null
+				}
+			}
+		};
+		drop(strings);
+		Ok(value)
+	}
+
+	fn symbol(&mut self, library: &str, name: &str) -> Result<*mut c_void, String> {
+		let key = (library.to_string(), name.to_string());
+		if let Some(symbol) = self.symbols.get(&key) {
+			return Ok(*symbol);
+		}
+		let handle = match self.handles.get(library) {
+			Some(handle) => *handle,
+			None => {
+				let handle = sys::open(library).map_err(|err| format!("Cannot load '{}': {}", library, err))?;
+				self.handles.insert(library.to_string(), handle);
+				handle
+			}
+		};
+		let symbol = sys::symbol(handle, name).map_err(|err| format!("Cannot find '{}' in '{}': {}", name, library, err))?;
+		self.symbols.insert(key, symbol);
+		Ok(symbol)
+	}
+}
+
+// Where an argument goes in a call.
+enum Register {
+	Int(u64),
+	Float(f64)
+}
+
+// Every C function is called as one taking as many arguments of each kind
+// as fit in registers, whatever it takes: the calling conventions of the
+// platforms below pass integers and pointers, and doubles, in registers of
+// their own, in order, and a function reads only those it declares. That
+// rules out functions taking `...`, and more arguments than fit.
+type IntFn = unsafe extern "C" fn(u64, u64, u64, u64, u64, u64, f64, f64, f64, f64, f64, f64, f64, f64) -> u64;
+type FloatFn = unsafe extern "C" fn(u64, u64, u64, u64, u64, u64, f64, f64, f64, f64, f64, f64, f64, f64) -> f64;
+
+unsafe fn call_int(symbol: *mut c_void, i: [u64; CType::MAX_INTS], f: [f64; CType::MAX_FLOATS]) -> u64 {
+	let function: IntFn = unsafe { std::mem::transmute::<*mut c_void, IntFn>(symbol) };
+	unsafe { function(i[0], i[1], i[2], i[3], i[4], i[5], f[0], f[1], f[2], f[3], f[4], f[5], f[6], f[7]) }
+}
+
+unsafe fn call_float(symbol: *mut c_void, i: [u64; CType::MAX_INTS], f: [f64; CType::MAX_FLOATS]) -> f64 {
+	let function: FloatFn = unsafe { std::mem::transmute::<*mut c_void, FloatFn>(symbol) };
+	unsafe { function(i[0], i[1], i[2], i[3], i[4], i[5], f[0], f[1], f[2], f[3], f[4], f[5], f[6], f[7]) }
+}
+
+#[cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod sys {
+	use std::ffi::{CStr, CString, c_char, c_int, c_void};
+
+	const RTLD_NOW: c_int = 2;
+
+	unsafe extern "C" {
+		fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+		fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+		fn dlerror() -> *mut c_char;
+	}
+
+	pub fn open(library: &str) -> Result<*mut c_void, String> {
+		let library = CString::new(library).map_err(|_| "the name has a NUL in it".to_string())?;
+		let handle = unsafe { dlopen(library.as_ptr(), RTLD_NOW) };
+		if handle.is_null() { Err(error()) } else { Ok(handle) }
+	}
+
+	pub fn symbol(handle: *mut c_void, name: &str) -> Result<*mut c_void, String> {
+		let name = CString::new(name).map_err(|_| "the name has a NUL in it".to_string())?;
+		let symbol = unsafe { dlsym(handle, name.as_ptr()) };
+		if symbol.is_null() { Err(error()) } else { Ok(symbol) }
+	}
+
+	fn error() -> String {
+		let message = unsafe { dlerror() };
+		match message.is_null() {
+			true => "unknown error".to_string(),
+			false => unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned()
+		}
+	}
+}
+
+#[cfg(not(all(unix, any(target_arch = "x86_64", target_arch = "aarch64"))))]
+mod sys {
+	use std::ffi::c_void;
+
+	pub fn open(_: &str) -> Result<*mut c_void, String> {
+		Err("calling C is not supported on this platform".to_string())
+	}
+
+	pub fn symbol(_: *mut c_void, _: &str) -> Result<*mut c_void, String> {
+		Err("calling C is not supported on this platform".to_string())
+	}
+}
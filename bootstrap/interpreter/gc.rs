@@ -0,0 +1,442 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::rc::{Rc, Weak};
+
+use crate::interpreter::ops::Sequence;
+use crate::interpreter::value::{Channel, Closure, Map, Object, Random, Slot, TaskState, Value};
+
+// Values are freed by reference counting as soon as nothing points to them,
+// except where they point to each other in a cycle: an object whose field
+// holds a closure that captured it, or arrays that hold each other. The heap
+// keeps track of everything a cycle can go through, the arrays, maps,
+// objects and cells of captured locals made while it is installed, and
+// collecting finds those nothing can reach any more and empties them, which
+// breaks their cycles so counting frees them.
+//
+// This is a hybrid on purpose: counting stays what frees almost everything,
+// and values stay `Rc`s Rust code can hold, clone and hand around as it
+// likes. So the roots a back end gives only narrow down what is looked at.
+// Of what they do not reach, anything counted more often than the rest of
+// it points to is held from outside, by Rust code, a native's captures or a
+// call in progress, and is kept along with all it reaches. The VM gives its
+// stacks and globals as roots; the interpreter, whose locals are all on the
+// Rust stack, gives none and leaves every value to the counts.
+
+// When to collect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+	// How much the heap has to hold before the first collection; after one,
+	// the next comes once it holds `GROWTH` times what was left, or this
+	// much if that is more
+	pub threshold: usize,
+	// Collect whenever anything was made since the last collection, to find
+	// values the collector loses sooner
+	pub stress: bool
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		Settings { threshold: DEFAULT_THRESHOLD, stress: false }
+	}
+}
+
+pub const DEFAULT_THRESHOLD: usize = 10_000;
+const GROWTH: usize = 2;
+
+// What collecting did, over a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+	pub collections: usize,
+	// Of arrays, maps, objects and cells, those emptied as unreachable
+	pub freed: usize,
+	// Still tracked after the last collection
+	pub live: usize
+}
+
+enum Tracked {
+	Array(Weak<RefCell<Vec<Value>>>),
+	Map(Weak<RefCell<Map>>),
+	Object(Weak<Object>),
+	Cell(Weak<RefCell<Value>>)
+}
+
+struct Heap {
+	settings: Settings,
+	tracked: Vec<Tracked>,
+	// Collect once `tracked` is this long
+	next: usize,
+	stats: Stats
+}
+
+thread_local! {
+	static HEAP: RefCell<Option<Heap>> = const { RefCell::new(None) };
+	// Whether to collect at the next chance, which the VM looks at before
+	// every instruction, and the interpreter before every statement and
+	// expression
+	static DUE: Cell<bool> = const { Cell::new(false) };
+	// Of what a heap would track, how much has been made on this thread
+	static MADE: Cell<u64> = const { Cell::new(0) };
+}
+
+// Starts tracking what is made on this thread, in a heap of its own.
+pub fn install(settings: Settings) {
+	HEAP.with_borrow_mut(|heap| *heap = Some(Heap { settings, tracked: Vec::new(), next: settings.threshold, stats: Stats::default() }));
+	DUE.set(false);
+}
+
+// Stops tracking, and returns what collecting did.
+pub fn uninstall() -> Stats {
+	DUE.set(false);
+	HEAP.with_borrow_mut(|heap| heap.take().map(|heap| heap.stats).unwrap_or_default())
+}
+
+// What collecting has done since the heap was installed.
+pub fn stats() -> Stats {
+	HEAP.with_borrow(|heap| heap.as_ref().map(|heap| heap.stats).unwrap_or_default())
+}
+
+pub fn is_due() -> bool {
+	DUE.get()
+}
+
+// How many arrays, maps, objects and cells have been made on this thread,
+// with a heap installed or not, for the profiler to count what each
+// function makes.
+pub fn made() -> u64 {
+	MADE.get()
+}
+
+fn track(tracked: Tracked) {
+	MADE.set(MADE.get() + 1);
+	HEAP.with_borrow_mut(|heap| {
+		if let Some(heap) = heap {
+			heap.tracked.push(tracked);
+			if heap.settings.stress || heap.tracked.len() >= heap.next {
+				DUE.set(true);
+			}
+		}
+	});
+}
+
+pub fn track_array(array: &Rc<RefCell<Vec<Value>>>) {
+	track(Tracked::Array(Rc::downgrade(array)));
+}
+
+pub fn track_map(map: &Rc<RefCell<Map>>) {
+	track(Tracked::Map(Rc::downgrade(map)));
+}
+
+pub fn track_object(object: &Rc<Object>) {
+	track(Tracked::Object(Rc::downgrade(object)));
+}
+
+pub fn track_cell(cell: &Rc<RefCell<Value>>) {
+	track(Tracked::Cell(Rc::downgrade(cell)));
+}
+
+// Finds what the roots reach. Values are marked by their address, and what
+// they point to is followed from a list rather than by recursing, however
+// deep it goes.
+#[derive(Default)]
+pub struct Marker {
+	marked: HashSet<usize>,
+	pending: Vec<Value>,
+	// What the values reached take up
+	bytes: usize
+}
+
+impl Marker {
+	pub fn new() -> Marker {
+		Marker::default()
+	}
+
+	// Roughly how many bytes the values marked take up: those of the
+	// arrays, maps, objects, closures, cells and strings reached, each
+	// counted once however many point to it.
+	pub fn bytes(&self) -> usize {
+		self.bytes
+	}
+
+	pub fn value(&mut self, value: &Value) {
+		self.pending.push(value.clone());
+		while let Some(value) = self.pending.pop() {
+			self.follow(&value);
+		}
+	}
+
+	pub fn slot(&mut self, slot: &Slot) {
+		match slot {
+			Slot::Value(value) => self.value(value),
+			Slot::Cell(cell) => {
+				if self.mark(Rc::as_ptr(cell).cast()) {
+					self.bytes += size_of::<RefCell<Value>>();
+					self.value(&cell.borrow());
+				}
+			}
+		}
+	}
+
+	// Whether the address was not marked before.
+	fn mark(&mut self, address: *const ()) -> bool {
+		self.marked.insert(address as usize)
+	}
+
+	fn follow(&mut self, value: &Value) {
+		match value {
+			Value::Array(items) => {
+				if self.mark(Rc::as_ptr(items).cast()) {
+					self.bytes += size_of::<RefCell<Vec<Value>>>() + items.borrow().capacity() * size_of::<Value>();
+					self.pending.extend(items.borrow().iter().cloned());
+				}
+			}
+			Value::Map(map) | Value::Set(map) => {
+				if self.mark(Rc::as_ptr(map).cast()) {
+					// An entry, and its place in the index
+					self.bytes += size_of::<RefCell<Map>>() + map.borrow().len() * (3 * size_of::<Value>() + size_of::<usize>());
+					for (key, value) in map.borrow().iter() {
+						self.pending.push(key.clone());
+						self.pending.push(value.clone());
+					}
+				}
+			}
+			Value::Object(object) => {
+				if self.mark(Rc::as_ptr(object).cast()) {
+					self.bytes += size_of::<Object>() + object.fields.borrow().len() * size_of::<Value>();
+					self.pending.extend(object.fields.borrow().iter().cloned());
+				}
+			}
+			Value::Function(closure) => {
+				if self.mark(Rc::as_ptr(closure).cast()) {
+					self.bytes += size_of::<Closure>() + closure.captures.len() * size_of::<Slot>();
+					for capture in &closure.captures {
+						match capture {
+							Slot::Value(value) => self.pending.push(value.clone()),
+							Slot::Cell(cell) => {
+								if self.mark(Rc::as_ptr(cell).cast()) {
+									self.bytes += size_of::<RefCell<Value>>();
+									self.pending.push(cell.borrow().clone());
+								}
+							}
+						}
+					}
+					self.pending.extend(closure.this.clone());
+				}
+			}
+			Value::Iterator(sequence) => {
+				if self.mark(Rc::as_ptr(sequence).cast()) {
+					self.bytes += size_of::<RefCell<Sequence>>();
+					self.pending.extend(sequence.borrow().values());
+				}
+			}
+			Value::Task(task) => {
+				if self.mark(Rc::as_ptr(task).cast()) {
+					match &*task.state.borrow() {
+						TaskState::Running => {}
+						TaskState::Done(value) => self.pending.push(value.clone()),
+						TaskState::Failed(thrown) => self.pending.push(thrown.value.clone())
+					}
+				}
+			}
+			Value::Tagged(_, value) => {
+				if self.mark(Rc::as_ptr(value).cast()) {
+					self.bytes += 2 * size_of::<usize>() + size_of::<Value>();
+					self.pending.push((**value).clone());
+				}
+			}
+			// With the counts `Rc` keeps before it
+			Value::String(text) => {
+				if self.mark(Rc::as_ptr(text).cast()) {
+					self.bytes += 2 * size_of::<usize>() + text.len();
+				}
+			}
+			Value::Random(random) => {
+				if self.mark(Rc::as_ptr(random).cast()) {
+					self.bytes += 2 * size_of::<usize>() + size_of::<Random>();
+				}
+			}
+			Value::Channel(channel) => {
+				if self.mark(Rc::as_ptr(channel).cast()) {
+					self.bytes += 2 * size_of::<usize>() + size_of::<Channel>() + channel.items.borrow().len() * size_of::<Value>();
+					self.pending.extend(channel.items.borrow().iter().cloned());
+				}
+			}
+			Value::Null | Value::Bool(_) | Value::Int(_) | Value::Float(_) | Value::Range(..) | Value::Native(_) | Value::Builtin(_) | Value::Pointer(_) => {}
+		}
+	}
+}
+
+// Empties everything tracked that `marker` did not reach and nothing holds
+// from outside, and forgets what has been freed.
+pub fn sweep(marker: Marker) {
+	let mut garbage = Vec::new();
+	let mut graph = Graph::default();
+	HEAP.with_borrow_mut(|heap| {
+		let Some(heap) = heap else {
+			return;
+		};
+		heap.tracked.retain(|tracked| match tracked.upgrade() {
+			Some(node) => {
+				if !marker.marked.contains(&node.address()) {
+					graph.add(node);
+				}
+				true
+			}
+			None => false
+		});
+		let unreachable = graph.unreachable(&marker);
+		heap.tracked.retain(|tracked| match tracked.upgrade() {
+			Some(node) if unreachable.contains(&node.address()) => {
+				node.empty(&mut garbage);
+				heap.stats.freed += 1;
+				false
+			}
+			alive => alive.is_some()
+		});
+		heap.stats.collections += 1;
+		heap.stats.live = heap.tracked.len();
+		heap.next = heap.settings.threshold.max(heap.tracked.len() * GROWTH);
+	});
+	DUE.set(false);
+	// Dropped once the heap is not borrowed, as dropping them frees more
+	drop(graph);
+	drop(garbage);
+}
+
+impl Tracked {
+	fn upgrade(&self) -> Option<Node> {
+		match self {
+			Tracked::Array(array) => array.upgrade().map(|array| Node::Value(Value::Array(array))),
+			Tracked::Map(map) => map.upgrade().map(|map| Node::Value(Value::Map(map))),
+			Tracked::Object(object) => object.upgrade().map(|object| Node::Value(Value::Object(object))),
+			Tracked::Cell(cell) => cell.upgrade().map(Node::Cell)
+		}
+	}
+}
+
+// What a cycle can go through: what is tracked, and the closures and tagged
+// values between them. Iterators, tasks and channels are not looked into,
+// so what they point to counts as held from outside.
+enum Node {
+	Value(Value),
+	Cell(Rc<RefCell<Value>>)
+}
+
+impl Node {
+	fn of(value: &Value) -> Option<Node> {
+		match value {
+			Value::Array(_) | Value::Map(_) | Value::Set(_) | Value::Object(_) | Value::Function(_) | Value::Tagged(..) => Some(Node::Value(value.clone())),
+			_ => None
+		}
+	}
+
+	fn address(&self) -> usize {
+		let address: *const () = match self {
+			Node::Value(Value::Array(items)) => Rc::as_ptr(items).cast(),
+			Node::Value(Value::Map(map) | Value::Set(map)) => Rc::as_ptr(map).cast(),
+			Node::Value(Value::Object(object)) => Rc::as_ptr(object).cast(),
+			Node::Value(Value::Function(closure)) => Rc::as_ptr(closure).cast(),
+			Node::Value(Value::Tagged(_, value)) => Rc::as_ptr(value).cast(),
+			Node::Cell(cell) => Rc::as_ptr(cell).cast(),
+			Node::Value(_) => unreachable!("not a node")
+		};
+		address as usize
+	}
+
+	// How many point to it.
+	fn count(&self) -> usize {
+		match self {
+			Node::Value(Value::Array(items)) => Rc::strong_count(items),
+			Node::Value(Value::Map(map) | Value::Set(map)) => Rc::strong_count(map),
+			Node::Value(Value::Object(object)) => Rc::strong_count(object),
+			Node::Value(Value::Function(closure)) => Rc::strong_count(closure),
+			Node::Value(Value::Tagged(_, value)) => Rc::strong_count(value),
+			Node::Cell(cell) => Rc::strong_count(cell),
+			Node::Value(_) => unreachable!("not a node")
+		}
+	}
+
+	// What it points to that is a node, once for each time it does, or None
+	// while Rust code has it borrowed, and so is using it.
+	fn children(&self) -> Option<Vec<Node>> {
+		let children = match self {
+			Node::Value(Value::Array(items)) => items.try_borrow().ok()?.iter().filter_map(Node::of).collect(),
+			Node::Value(Value::Map(map) | Value::Set(map)) => {
+				map.try_borrow().ok()?.iter().flat_map(|(key, value)| [Node::of(key), Node::of(value)]).flatten().collect()
+			}
+			Node::Value(Value::Object(object)) => object.fields.try_borrow().ok()?.iter().filter_map(Node::of).collect(),
+			Node::Value(Value::Function(closure)) => {
+				let captures = closure.captures.iter().filter_map(|capture| match capture {
+					Slot::Value(value) => Node::of(value),
+					Slot::Cell(cell) => Some(Node::Cell(cell.clone()))
+				});
+				captures.chain(closure.this.as_ref().and_then(Node::of)).collect()
+			}
+			Node::Value(Value::Tagged(_, value)) => Node::of(value).into_iter().collect(),
+			Node::Cell(cell) => Node::of(&*cell.try_borrow().ok()?).into_iter().collect(),
+			Node::Value(_) => Vec::new()
+		};
+		Some(children)
+	}
+
+	fn empty(&self, garbage: &mut Vec<Value>) {
+		match self {
+			Node::Value(Value::Array(items)) => garbage.extend(items.take()),
+			Node::Value(Value::Map(map) | Value::Set(map)) => garbage.extend(map.take().iter().flat_map(|(key, value)| [key.clone(), value.clone()])),
+			Node::Value(Value::Object(object)) => garbage.extend(object.fields.take()),
+			Node::Cell(cell) => garbage.push(cell.replace(Value::Null)),
+			Node::Value(_) => {}
+		}
+	}
+}
+
+// What the roots did not reach, to find which of it is held from outside.
+#[derive(Default)]
+struct Graph {
+	nodes: Vec<Node>,
+	index: HashMap<usize, usize>
+}
+
+impl Graph {
+	fn add(&mut self, node: Node) {
+		self.index.insert(node.address(), self.nodes.len());
+		self.nodes.push(node);
+	}
+
+	// The addresses of what nothing outside the graph holds or reaches.
+	fn unreachable(&mut self, marker: &Marker) -> HashSet<usize> {
+		// The closures and tagged values between what is tracked
+		let mut at = 0;
+		while at < self.nodes.len() {
+			for child in self.nodes[at].children().into_iter().flatten() {
+				let address = child.address();
+				let between = matches!(child, Node::Value(Value::Function(_) | Value::Tagged(..)));
+				if between && !marker.marked.contains(&address) && !self.index.contains_key(&address) {
+					self.add(child);
+				}
+			}
+			at += 1;
+		}
+		// Taken before anything else is cloned: one count of each is the
+		// graph's own, and one for each time another node points to it
+		let mut outside: Vec<usize> = self.nodes.iter().map(|node| node.count() - 1).collect();
+		let children: Vec<Option<Vec<usize>>> = self
+			.nodes
+			.iter()
+			.map(|node| node.children().map(|children| children.iter().filter_map(|child| self.index.get(&child.address()).copied()).collect()))
+			.collect();
+		for child in children.iter().flatten().flatten() {
+			outside[*child] = outside[*child].saturating_sub(1);
+		}
+		let mut held: Vec<bool> = outside.iter().zip(&children).map(|(&outside, children)| outside > 0 || children.is_none()).collect();
+		let mut pending: Vec<usize> = (0..held.len()).filter(|&node| held[node]).collect();
+		while let Some(node) = pending.pop() {
+			for &child in children[node].iter().flatten() {
+				if !held[child] {
+					held[child] = true;
+					pending.push(child);
+				}
+			}
+		}
+		self.nodes.iter().zip(held).filter(|(_, held)| !held).map(|(node, _)| node.address()).collect()
+	}
+}
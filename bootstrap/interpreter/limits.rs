@@ -0,0 +1,196 @@
+use std::cell::Cell;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+// What a program may use of the machine it runs on, for hosts that run code
+// they do not trust. Each is set on the `Runtime` the program runs with, and
+// none when it is not to be limited:
+//
+//     let mut runtime = Runtime::new();
+//     runtime.limits = RuntimeLimits { max_instructions: Some(1_000_000), wall_clock: Some(Duration::from_secs(1)), ..Default::default() };
+//
+// A program that goes over one is stopped there, with an `Uncaught` whose
+// `limit` says which. No `catch` can handle it, and no `finally` runs, so
+// nothing the program does can keep it going.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RuntimeLimits {
+	// Of the VM's instructions; the interpreter counts each statement and
+	// expression as one
+	pub max_instructions: Option<u64>,
+	// Of the values the program can reach, roughly, as measured now and
+	// then by the VM's collector, along with what natives ask for to work
+	// with as they run and the strings and arrays that grow the most at
+	// once as they are made. The interpreter has no heap to measure, so it
+	// only limits those
+	pub max_heap_bytes: Option<usize>,
+	// Calls in progress at once, which can be fewer than `MAX_DEPTH` but
+	// not more
+	pub max_call_depth: Option<usize>,
+	// How long the program runs, waiting for timers and reads included
+	pub wall_clock: Option<Duration>
+}
+
+// The limit a program went over, and what it was.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+	Instructions(u64),
+	HeapBytes(usize),
+	CallDepth(usize),
+	WallClock(Duration)
+}
+
+impl fmt::Display for Limit {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Limit::Instructions(max) => write!(f, "Limit exceeded: more than {} instructions", max),
+			Limit::HeapBytes(max) => write!(f, "Limit exceeded: more than {} bytes of memory", max),
+			Limit::CallDepth(max) => write!(f, "Limit exceeded: more than {} calls deep", max),
+			Limit::WallClock(max) => write!(f, "Limit exceeded: running for more than {:?}", max)
+		}
+	}
+}
+
+thread_local! {
+	// Of the running program, the limit on memory and what its heap was last
+	// measured to hold, for natives to ask for memory within
+	static MEMORY: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+	// A limit a native went over, for the engine to stop the program with
+	static OVER: Cell<Option<Limit>> = const { Cell::new(None) };
+}
+
+// Whether a native can use `bytes` of memory of its own while it runs, like
+// a regex search's, or the program can make a value that big, along with
+// what its heap holds. If not, it returns the error this gives; the engine
+// stops the program with the limit rather than throw it.
+pub fn reserve(bytes: usize) -> Result<(), String> {
+	match MEMORY.get() {
+		Some((max, heap)) if heap.saturating_add(bytes) > max => {
+			OVER.set(Some(Limit::HeapBytes(max)));
+			Err(Limit::HeapBytes(max).to_string())
+		}
+		_ => Ok(())
+	}
+}
+
+// The limit the running program went over asking for memory, if it did.
+pub fn over() -> Option<Limit> {
+	OVER.get()
+}
+
+// Instructions between looks at the clock.
+const CHECK: u64 = 1024;
+
+// Instructions between measures of the heap, at the least.
+const MEASURE: u64 = 10_000;
+
+// Counts what a program uses against its limits. The engine takes one from
+// `budget` for each instruction, and calls `check` when there is none left,
+// so the clock is looked at only now and then. Once a limit is gone over,
+// every check fails with it.
+pub struct Meter {
+	pub limits: RuntimeLimits,
+	pub budget: u64,
+	started: Instant,
+	// Run before the budget was last given, and how much that was
+	ran: u64,
+	given: u64,
+	// When to measure the heap next, in instructions
+	measure: u64,
+	exceeded: Option<Limit>
+}
+
+impl Meter {
+	pub fn new(limits: RuntimeLimits) -> Meter {
+		MEMORY.set(limits.max_heap_bytes.map(|max| (max, 0)));
+		OVER.set(None);
+		Meter { limits, budget: 0, started: Instant::now(), ran: 0, given: 0, measure: MEASURE, exceeded: None }
+	}
+
+	// Instructions run so far.
+	pub fn instructions(&self) -> u64 {
+		self.ran + self.given - self.budget
+	}
+
+	// Counts the instructions run since the last check against the limit,
+	// and looks at the clock, giving the budget to run until the next.
+	pub fn check(&mut self) -> Result<(), Limit> {
+		self.ran = self.instructions();
+		self.given = 0;
+		self.budget = 0;
+		if let Some(limit) = self.exceeded {
+			return Err(limit);
+		}
+		if let Some(limit) = OVER.take() {
+			return self.exceed(limit);
+		}
+		if let Some(max) = self.limits.max_instructions
+			&& self.ran >= max
+		{
+			return self.exceed(Limit::Instructions(max));
+		}
+		if let Some(max) = self.limits.wall_clock
+			&& self.started.elapsed() >= max
+		{
+			return self.exceed(Limit::WallClock(max));
+		}
+		self.given = self.limits.max_instructions.map_or(CHECK, |max| CHECK.min(max - self.ran));
+		self.budget = self.given;
+		Ok(())
+	}
+
+	// Whether the calls in progress can be `depth` deep.
+	pub fn depth(&mut self, depth: usize) -> Result<(), Limit> {
+		match self.limits.max_call_depth {
+			Some(max) if depth > max => self.exceed(Limit::CallDepth(max)),
+			_ => Ok(())
+		}
+	}
+
+	// Whether the heap is to be measured now.
+	pub fn measure_due(&self) -> bool {
+		self.limits.max_heap_bytes.is_some() && self.instructions() >= self.measure
+	}
+
+	// Whether what the heap was measured to hold is within the limit. The
+	// bigger it is, the longer until it is measured again, so measuring
+	// takes about the same share of the time however much it holds.
+	pub fn measured(&mut self, bytes: usize) -> Result<(), Limit> {
+		self.measure = self.instructions() + MEASURE.max(bytes as u64 / 16);
+		MEMORY.set(self.limits.max_heap_bytes.map(|max| (max, bytes)));
+		match self.limits.max_heap_bytes {
+			Some(max) if bytes > max => self.exceed(Limit::HeapBytes(max)),
+			_ => Ok(())
+		}
+	}
+
+	// Whether the native that just returned went over a limit asking for
+	// memory.
+	pub fn native(&mut self) -> Result<(), Limit> {
+		match OVER.take() {
+			Some(limit) => self.exceed(limit),
+			None => Ok(())
+		}
+	}
+
+	// When the clock runs out, for waiting no longer than that.
+	pub fn deadline(&self) -> Option<Instant> {
+		self.limits.wall_clock.map(|max| self.started + max)
+	}
+
+	pub fn exceeded(&self) -> Option<Limit> {
+		self.exceeded
+	}
+
+	fn exceed(&mut self, limit: Limit) -> Result<(), Limit> {
+		self.ran = self.instructions();
+		self.given = 0;
+		self.budget = 0;
+		Err(*self.exceeded.get_or_insert(limit))
+	}
+}
+
+impl Drop for Meter {
+	fn drop(&mut self) {
+		MEMORY.set(None);
+	}
+}
@@ -0,0 +1,1365 @@
+pub mod captures;
+pub mod ffi;
+pub mod gc;
+pub mod limits;
+pub mod native;
+pub mod ops;
+pub mod value;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+use std::panic;
+use std::rc::Rc;
+use std::thread;
+use std::time::Instant;
+
+use crate::ast::expr::{BinaryOp, Literal, PostfixOp, UnaryOp};
+use crate::ast::stmt::{ENTRIES_METHOD, ForKind, HAS_NEXT_METHOD, ITERATOR_METHOD, NEXT_METHOD};
+use crate::diagnostics::Diagnostic;
+use crate::diagnostics::ice;
+use crate::interpreter::captures::Captures;
+use crate::interpreter::ffi::Libraries;
+use crate::interpreter::gc::{Marker, Settings};
+use crate::interpreter::limits::{Limit, Meter};
+use crate::interpreter::native::{Runtime, started};
+use crate::interpreter::ops::{Combine, Cursor, Sequence, Source, Stage};
+use crate::interpreter::value::{Channel, Class, Closure, Frame, Map, Native, Object, Slot, Tag, Value};
+use crate::lexer::token::Span;
+use crate::sema::builtins::Builtin;
+use crate::sema::hir::{Block, Case, Catch, ClassKind, Coercion, Expr, ExprKind, Field, FnKind, MemberTarget, Pattern, Program, Stmt, StmtKind, Variable};
+use crate::sema::types::{FnId, LocalId, Type, TypeId};
+use crate::stdlib;
+
+// Calls can nest this deep before the program is stopped with a stack
+// overflow, rather than the interpreter running out of stack itself.
+pub const MAX_DEPTH: usize = 10_000;
+// How many calls an uncaught exception's trace shows, the innermost ones
+pub const MAX_TRACE: usize = 64;
+// Enough for `MAX_DEPTH` calls, each of which is a few frames of `expr`
+const STACK_SIZE: usize = 1 << 30;
+
+// Runs a checked program: the top-level code of every file, each package's
+// after the packages it imports, then `main` with `args` if there is one.
+// `entry` is the file `main` is in, when the project names one. The natives
+// it calls are `runtime`'s, and what the program prints goes to `out`.
+pub fn run(program: &Program, runtime: &Runtime, entry: Option<usize>, args: &[String], out: &mut dyn Write) -> Result<(), Uncaught> {
+	let mut interpreter = Interpreter::new(program, runtime, Settings::default(), out);
+	interpreter.init()?;
+	if let Some(main) = main(program, entry) {
+		let args = match program.function(main).params.len() {
+			0 => Vec::new(),
+			_ => vec![Value::array(args.iter().map(|arg| Value::string(arg.as_str())).collect())]
+		};
+		interpreter.call(main, args)?;
+	}
+	Ok(())
+}
+
+// The function to run after the top-level code: `main` in the entry file
+// when there is one, or else at the top level of the root package.
+pub fn main(program: &Program, entry: Option<usize>) -> Option<FnId> {
+	(0..program.functions.len()).map(|id| FnId(id as u32)).find(|&id| {
+		let function = program.function(id);
+		let placed = match entry {
+			Some(entry) => function.file == entry,
+			None => program.files[function.file].package.is_empty()
+		};
+		function.name == "main" && function.kind == FnKind::Function && placed
+	})
+}
+
+// Runs `f` on a thread with room for `MAX_DEPTH` nested calls, which the
+// thread the driver is on may not have. A panic on it is an internal
+// compiler error, reported by the `ice::catch` the caller is in.
+pub fn with_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+	thread::scope(|scope| {
+		let thread = thread::Builder::new().stack_size(STACK_SIZE).spawn_scoped(scope, || ice::catch(f));
+		match thread.expect("cannot start a thread to run the program on").join() {
+			Ok(Ok(result)) => result,
+			Ok(Err(ice)) => ice::resume(ice),
+			Err(payload) => panic::resume_unwind(payload)
+		}
+	})
+}
+
+// An exception nothing caught, which stops the program: one it threw, or an
+// error like dividing by zero.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Uncaught {
+	pub message: String,
+	pub file: usize,
+	// Where it was thrown
+	pub span: Span,
+	// The calls it was thrown through, innermost first
+	pub trace: Vec<StackFrame>,
+	// How many outer calls the trace leaves out
+	pub hidden: usize,
+	// The limit the program went over, when that is what stopped it
+	pub limit: Option<Limit>
+}
+
+// A call running when an exception was thrown: the function, and where in
+// it it was, at the `throw` or at the call to the next one in. Native
+// functions have no source, so theirs is where they were called.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+	pub function: String,
+	pub file: usize,
+	pub span: Span,
+	pub native: bool
+}
+
+impl Uncaught {
+	// Points at where it was thrown, with the stack trace after: each call
+	// by the function and where in it it was, innermost first, and calls
+	// made again and again from the same place once.
+	// `files` are the paths of the program's files.
+	pub fn diagnostic(&self, files: &[String]) -> Diagnostic {
+		let mut diagnostic = Diagnostic::error(self.message.clone(), self.span).in_file(self.file);
+		for run in self.trace.chunk_by(|frame, next| frame == next) {
+			let frame = &run[0];
+			let mut note = match frame.native {
+				true => format!("at {} (native)", frame.function),
+				false => format!("at {} ({}:{}:{})", frame.function, files[frame.file], frame.span.line, frame.span.column)
+			};
+			if run.len() > 1 {
+				note += &format!(", {} times", run.len());
+			}
+			diagnostic = diagnostic.with_note(note);
+		}
+		if self.hidden > 0 {
+			diagnostic = diagnostic.with_note(format!("... and {} more calls", self.hidden));
+		}
+		diagnostic
+	}
+}
+
+// How running a statement or expression can end other than by finishing.
+enum Control {
+	Return(Value),
+	Break(Option<String>),
+	Continue(Option<String>),
+	Throw(Thrown)
+}
+
+struct Thrown {
+	value: Value,
+	trace: Vec<StackFrame>,
+	hidden: usize,
+	// Thrown by the interpreter for an error, with the message as the value,
+	// rather than by `throw`
+	error: bool,
+	// Thrown for going over a limit, which nothing catches
+	limit: Option<Limit>
+}
+
+type Run<T> = Result<T, Control>;
+
+// A call in progress, with where it was made from.
+struct Call {
+	callee: Callee,
+	file: usize,
+	span: Span
+}
+
+enum Callee {
+	Function(FnId),
+	Native(Rc<Native>)
+}
+
+// Somewhere a value can be stored, with the object it is in, and the index
+// for an element, already worked out.
+enum Place {
+	Var(Variable),
+	Member(Value, String),
+	Index(Value, Value),
+	// `field` in an accessor of `this`
+	Backing(String)
+}
+
+// Walks the checked program's tree, statement by statement.
+pub struct Interpreter<'a> {
+	program: &'a Program,
+	out: &'a mut dyn Write,
+	globals: Vec<Value>,
+	// The locals of the call running now
+	frame: Frame,
+	captures: Captures,
+	this: Option<Value>,
+	// The file of the function running now, for where errors are
+	file: usize,
+	// The calls in progress, outermost first
+	calls: Vec<Call>,
+	// Running the top-level code of files, which is below every call
+	initializing: bool,
+	// By id, what objects of each class point to
+	classes: Vec<Rc<Class>>,
+	// Each enum variant is made once, when it is first used
+	variants: HashMap<(TypeId, usize), Value>,
+	// Of the program's natives, what the runtime has
+	natives: Vec<Option<Rc<Native>>>,
+	libraries: Libraries,
+	meter: Meter,
+	// What the generator calls running have yielded, innermost last
+	yielded: Vec<Vec<Value>>
+}
+
+impl<'a> Interpreter<'a> {
+	pub fn new(program: &'a Program, runtime: &Runtime, gc: Settings, out: &'a mut dyn Write) -> Interpreter<'a> {
+		gc::install(gc);
+		stdlib::env::clear();
+		Interpreter {
+			program,
+			out,
+			globals: vec![Value::Null; program.globals.len()],
+			frame: Frame::new(),
+			captures: Captures::new(program),
+			this: None,
+			file: 0,
+			calls: Vec::new(),
+			initializing: false,
+			classes: (0..program.classes.len()).map(|id| Rc::new(class(program, TypeId(id as u32)))).collect(),
+			variants: HashMap::new(),
+			natives: runtime.bind(program.natives.iter().map(|native| native.name.as_str())),
+			libraries: Libraries::new(),
+			meter: Meter::new(runtime.limits),
+			yielded: Vec::new()
+		}
+	}
+
+	// Runs the top-level code of every file, each package's after the
+	// packages it imports, setting the globals.
+	pub fn init(&mut self) -> Result<(), Uncaught> {
+		let program = self.program;
+		self.initializing = true;
+		let mut result = Ok(());
+		for &file in &program.init_order {
+			self.file = file;
+			match self.stmts(&program.files[file].body) {
+				Ok(_) => {}
+				Err(Control::Throw(thrown)) => {
+					result = Err(self.uncaught(thrown));
+					break;
+				}
+				Err(_) => break
+			}
+		}
+		self.initializing = false;
+		result
+	}
+
+	// Calls a top-level function, like `main` or a test.
+	pub fn call(&mut self, function: FnId, args: Vec<Value>) -> Result<Value, Uncaught> {
+		let span = self.program.function(function).span;
+		self.file = self.program.function(function).file;
+		match self.call_function(function, None, &[], args, span) {
+			Ok(value) => Ok(value),
+			Err(Control::Throw(thrown)) => Err(self.uncaught(thrown)),
+			Err(_) => Ok(Value::Null)
+		}
+	}
+
+	fn uncaught(&mut self, thrown: Thrown) -> Uncaught {
+		let message = match thrown.value {
+			Value::String(message) if thrown.error => message.to_string(),
+			value => format!("Uncaught exception: {}", self.stringify(&value).unwrap_or_else(|_| value.type_name().to_string()))
+		};
+		let StackFrame { file, span, .. } = thrown.trace[0];
+		Uncaught { message, file, span, trace: thrown.trace, hidden: thrown.hidden, limit: thrown.limit }
+	}
+
+	// A run-time error, thrown like an exception so `catch` can handle it.
+	fn error(&self, message: impl Into<String>, span: Span) -> Control {
+		self.thrown(Value::string(message.into()), span, true)
+	}
+
+	// Going over a limit, which is thrown as an error nothing catches.
+	fn exceeded(&self, limit: Limit, span: Span) -> Control {
+		match self.thrown(Value::string(limit.to_string()), span, true) {
+			Control::Throw(thrown) => Control::Throw(Thrown { limit: Some(limit), ..thrown }),
+			control => control
+		}
+	}
+
+	// Counts a statement or expression run against the limits, and collects
+	// garbage when it is due. Everything the calls in progress use is held
+	// on the Rust stack, so there are no roots to give.
+	fn tick(&mut self, span: Span) -> Run<()> {
+		if gc::is_due() {
+			gc::sweep(Marker::new());
+		}
+		if self.meter.budget == 0 {
+			self.meter.check().map_err(|limit| self.exceeded(limit, span))?;
+		}
+		self.meter.budget -= 1;
+		Ok(())
+	}
+
+	fn throw(&self, value: Value, span: Span) -> Control {
+		self.thrown(value, span, false)
+	}
+
+	// What is thrown at `span`, with the calls it is thrown through.
+	fn thrown(&self, value: Value, span: Span, error: bool) -> Control {
+		let mut trace = Vec::new();
+		let (mut file, mut span) = (self.file, span);
+		for call in self.calls.iter().rev().take(MAX_TRACE) {
+			let (function, native) = match &call.callee {
+				Callee::Function(function) => (name(self.program, *function), false),
+				Callee::Native(native) => (native.name.clone(), true)
+			};
+			trace.push(StackFrame { function, file, span, native });
+			(file, span) = (call.file, call.span);
+		}
+		let hidden = self.calls.len().saturating_sub(MAX_TRACE);
+		if trace.is_empty() || (self.initializing && hidden == 0) {
+			trace.push(StackFrame { function: "<top level>".to_string(), file, span, native: false });
+		}
+		// What went over the limit on memory asking for it cannot be caught
+		let limit = if error { limits::over() } else { None };
+		Control::Throw(Thrown { value, trace, hidden, error, limit })
+	}
+
+	// ---------------------------------------------------------------------
+	// Statements
+	// ---------------------------------------------------------------------
+
+	// Runs statements in order, and is what the last one is when it is an
+	// expression, which is the value of a block used as one. Boxed locals
+	// get their cells first, each time the block starts, and local functions
+	// are made next, so they can be called from above where they are.
+	fn stmts(&mut self, stmts: &'a [Stmt]) -> Run<Value> {
+		for stmt in stmts {
+			if let StmtKind::Var { var: Variable::Local(local), .. } | StmtKind::Function { local, .. } = &stmt.kind {
+				self.define(*local, Value::Null);
+			}
+		}
+		for stmt in stmts {
+			if let StmtKind::Function { local, function } = &stmt.kind {
+				let closure = self.closure(*function);
+				self.frame.set(*local, closure);
+			}
+		}
+		let mut value = Value::Null;
+		for stmt in stmts {
+			value = match &stmt.kind {
+				StmtKind::Expr(expr) => self.expr(expr)?,
+				_ => {
+					self.stmt(stmt)?;
+					Value::Null
+				}
+			};
+		}
+		Ok(value)
+	}
+
+	fn block(&mut self, block: &'a Block) -> Run<()> {
+		self.stmts(&block.stmts).map(drop)
+	}
+
+	fn stmt(&mut self, stmt: &'a Stmt) -> Run<()> {
+		self.tick(stmt.span)?;
+		match &stmt.kind {
+			StmtKind::Expr(expr) => {
+				self.expr(expr)?;
+			}
+			StmtKind::Var { var, init } => {
+				let value = match init {
+					Some(init) => self.expr(init)?,
+					None => Value::Null
+				};
+				match var {
+					Variable::Local(local) => self.frame.set(*local, value),
+					Variable::Global(global) => self.globals[global.0 as usize] = value
+				}
+			}
+			StmtKind::Block(block) => self.block(block)?,
+			StmtKind::If { cond, then_branch, else_branch } => {
+				if self.expr(cond)?.truthy() {
+					self.block(then_branch)?;
+				} else if let Some(else_branch) = else_branch {
+					self.stmt(else_branch)?;
+				}
+			}
+			StmtKind::While { .. } | StmtKind::Loop { .. } | StmtKind::For { .. } => self.looped(stmt, None)?,
+			StmtKind::Labeled { label, body } => self.looped(body, Some(label))?,
+			StmtKind::Break(label) => return Err(Control::Break(label.clone())),
+			StmtKind::Continue(label) => return Err(Control::Continue(label.clone())),
+			StmtKind::Return(value) => {
+				let value = match value {
+					Some(value) => self.expr(value)?,
+					None => Value::Null
+				};
+				return Err(Control::Return(value));
+			}
+			StmtKind::Yield(value) => {
+				let value = self.expr(value)?;
+				self.yielded.last_mut().expect("only a generator yields").push(value);
+			}
+			StmtKind::Throw(value) => {
+				let value = self.expr(value)?;
+				return Err(self.throw(value, stmt.span));
+			}
+			StmtKind::Try { body, catches, finally } => self.try_catch(body, catches, finally.as_ref())?,
+			StmtKind::Switch { subject, cases, default, .. } => self.switch(subject, cases, default.as_ref())?,
+			// Bound by `stmts` before the block runs
+			StmtKind::Function { .. } => {}
+		}
+		Ok(())
+	}
+
+	// Runs a loop, labeled `label` or not, until it ends or is broken out of.
+	fn looped(&mut self, stmt: &'a Stmt, label: Option<&String>) -> Run<()> {
+		match &stmt.kind {
+			StmtKind::While { cond, body } => {
+				while self.expr(cond)?.truthy() {
+					if self.iteration(body, label)? {
+						break;
+					}
+				}
+			}
+			StmtKind::Loop { body } => {
+				while !self.iteration(body, label)? {}
+			}
+			StmtKind::For { kind, bindings, iterable, body } => {
+				let value = self.expr(iterable)?;
+				let mut cursor = self.cursor(value, *kind, iterable.span)?;
+				while let Some(item) = self.advance(&mut cursor, iterable.span)? {
+					self.bind(bindings, item, iterable.span)?;
+					if self.iteration(body, label)? {
+						break;
+					}
+				}
+			}
+			// A label on something else only gives `break` a name to leave it by
+			_ => match self.stmt(stmt) {
+				Err(Control::Break(Some(name))) if Some(&name) == label => {}
+				result => result?
+			}
+		}
+		Ok(())
+	}
+
+	// Runs the body of a loop once, and says whether to stop looping.
+	fn iteration(&mut self, body: &'a Block, label: Option<&String>) -> Run<bool> {
+		match self.block(body) {
+			Ok(()) => Ok(false),
+			Err(Control::Break(name)) if name.is_none() || name.as_ref() == label => Ok(true),
+			Err(Control::Continue(name)) if name.is_none() || name.as_ref() == label => Ok(false),
+			Err(control) => Err(control)
+		}
+	}
+
+	// Arrays, ranges, maps and strings are walked directly; objects through
+	// their `iterator()`, or for `of` their `entries()`.
+	fn cursor(&mut self, value: Value, kind: ForKind, span: Span) -> Run<Cursor> {
+		let entries = kind == ForKind::Of;
+		match Cursor::of(value.clone(), entries).map_err(|message| self.error(message, span))? {
+			Some(cursor) => Ok(cursor),
+			None => {
+				let method = if entries { ENTRIES_METHOD } else { ITERATOR_METHOD };
+				Ok(Cursor::Iterator(self.invoke(value, method, Vec::new(), span)?))
+			}
+		}
+	}
+
+	fn advance(&mut self, cursor: &mut Cursor, span: Span) -> Run<Option<Value>> {
+		let Cursor::Iterator(iterator) = cursor else {
+			return cursor.step().map_err(|message| self.error(message, span));
+		};
+		if !self.invoke(iterator.clone(), HAS_NEXT_METHOD, Vec::new(), span)?.truthy() {
+			return Ok(None);
+		}
+		Ok(Some(self.invoke(iterator.clone(), NEXT_METHOD, Vec::new(), span)?))
+	}
+
+	// Binds a loop's variables to an item, taking an entry apart for
+	// `for (key, value) of`: an array by index, an object by field.
+	fn bind(&mut self, bindings: &[LocalId], item: Value, span: Span) -> Run<()> {
+		if let [binding] = bindings {
+			self.define(*binding, item);
+			return Ok(());
+		}
+		let parts = ops::unpack(&item, bindings.len()).map_err(|message| self.error(message, span))?;
+		for (binding, part) in bindings.iter().zip(parts) {
+			self.define(*binding, part);
+		}
+		Ok(())
+	}
+
+	// The first `catch` that takes what the body threw handles it, and
+	// `finally` runs however the rest ended; if it ends early itself, that
+	// wins. Going over a limit ends the program, with nothing run on the way.
+	fn try_catch(&mut self, body: &'a Block, catches: &'a [Catch], finally: Option<&'a Block>) -> Run<()> {
+		let result = match self.block(body) {
+			Err(Control::Throw(thrown)) if thrown.limit.is_some() => return Err(Control::Throw(thrown)),
+			Err(Control::Throw(thrown)) => {
+				match catches.iter().find(|catch| catch.ty.as_ref().is_none_or(|ty| self.instance_of(&thrown.value, ty))) {
+					Some(catch) => {
+						self.define(catch.local, thrown.value);
+						self.block(&catch.body)
+					}
+					None => Err(Control::Throw(thrown))
+				}
+			}
+			result => result
+		};
+		if let Some(finally) = finally {
+			self.block(finally)?;
+		}
+		result
+	}
+
+	fn switch(&mut self, subject: &'a Expr, cases: &'a [Case], default: Option<&'a Block>) -> Run<()> {
+		let subject = self.expr(subject)?;
+		for case in cases {
+			for pattern in &case.patterns {
+				let matched = match pattern {
+					Pattern::Value(value) => {
+						let value = self.expr(value)?;
+						subject == value
+					}
+					Pattern::Type { ty, .. } => self.instance_of(&subject, ty)
+				};
+				if matched {
+					return self.block(&case.body);
+				}
+			}
+		}
+		match default {
+			Some(default) => self.block(default),
+			None => Ok(())
+		}
+	}
+
+	// ---------------------------------------------------------------------
+	// Expressions
+	// ---------------------------------------------------------------------
+
+	fn expr(&mut self, expr: &'a Expr) -> Run<Value> {
+		let span = expr.span;
+		self.tick(span)?;
+		Ok(match &expr.kind {
+			ExprKind::Literal(literal) => match literal {
+				Literal::Int(value) => Value::Int(*value),
+				Literal::Float(value) => Value::Float(*value),
+				Literal::String(text) => Value::string(text.as_str()),
+				Literal::Bool(value) => Value::Bool(*value),
+				Literal::Null => Value::Null
+			},
+			ExprKind::Var(var) => self.load(&Place::Var(*var), span)?,
+			ExprKind::Function(function) => Value::Function(Rc::new(Closure { function: *function, captures: Vec::new(), this: None })),
+			ExprKind::Builtin(builtin) => Value::Builtin(*builtin),
+			ExprKind::Native(index) => match &self.natives[*index] {
+				Some(native) => Value::Native(native.clone()),
+				None => return Err(self.error(native::missing(&self.program.natives[*index].name), span))
+			},
+			ExprKind::This | ExprKind::Super => self.this.clone().unwrap_or(Value::Null),
+			ExprKind::BackingField(name) => self.load(&Place::Backing(name.clone()), span)?,
+
+			ExprKind::Unary { op, operand } => match op {
+				UnaryOp::Negate => {
+					let value = self.expr(operand)?;
+					ops::negate(value).map_err(|message| self.error(message, span))?
+				}
+				UnaryOp::Not => Value::Bool(!self.expr(operand)?.truthy()),
+				UnaryOp::PreIncrement => self.update(operand, 1)?.1,
+				UnaryOp::PreDecrement => self.update(operand, -1)?.1,
+				UnaryOp::Typeof => {
+					let value = self.expr(operand)?;
+					Value::string(value.type_name())
+				}
+			},
+			ExprKind::Binary { op, left, right } => {
+				let left = self.expr(left)?;
+				match op {
+					BinaryOp::And if !left.truthy() => Value::Bool(false),
+					BinaryOp::Or if left.truthy() => Value::Bool(true),
+					BinaryOp::And | BinaryOp::Or => Value::Bool(self.expr(right)?.truthy()),
+					BinaryOp::Coalesce if !left.is_null() => left,
+					BinaryOp::Coalesce => self.expr(right)?,
+					_ => {
+						let right = self.expr(right)?;
+						self.binary(*op, left, right, span)?
+					}
+				}
+			}
+			ExprKind::Assign { target, op, value } => self.assign(target, *op, value)?,
+			ExprKind::Postfix { op, operand } => match op {
+				PostfixOp::Increment => self.update(operand, 1)?.0,
+				PostfixOp::Decrement => self.update(operand, -1)?.0,
+				PostfixOp::NonNull => match self.expr(operand)? {
+					Value::Null => return Err(self.error("Used '!!' on a value that is null", span)),
+					value => value
+				},
+				PostfixOp::Propagate => match self.expr(operand)? {
+					Value::Tagged(tag, value) if tag.has_value() => (*value).clone(),
+					value @ Value::Tagged(..) => return Err(Control::Return(value)),
+					value => return Err(self.error(format!("'?' needs a Result or an Option, not a '{}'", value.type_name()), span))
+				}
+			},
+			ExprKind::Is { operand, ty, negated } => {
+				let value = self.expr(operand)?;
+				Value::Bool(self.instance_of(&value, ty) != *negated)
+			}
+			ExprKind::Coerce { value, coercion } => {
+				let value = self.expr(value)?;
+				match (coercion, value) {
+					(Coercion::IntToFloat, Value::Int(value)) => Value::Float(value as f64),
+					(Coercion::ToString, value) => Value::string(self.stringify(&value)?),
+					(Coercion::Cast, value) if !self.instance_of(&value, &expr.ty) => {
+						return Err(self.error(format!("Cannot use a '{}' as a '{}'", value.type_name(), expr.ty), span));
+					}
+					(_, value) => value
+				}
+			}
+			ExprKind::If { cond, then_branch, else_branch } => {
+				let branch = if self.expr(cond)?.truthy() { then_branch } else { else_branch };
+				self.expr(branch)?
+			}
+			ExprKind::Block(block) => self.stmts(&block.stmts)?,
+
+			ExprKind::Call { callee, args } => self.call_expr(callee, args, span)?,
+			ExprKind::SuperCall { constructor, args } => {
+				let args = self.args(args)?;
+				if let Some(constructor) = constructor {
+					self.call_function(*constructor, self.this.clone(), &[], args, span)?;
+				}
+				Value::Null
+			}
+			ExprKind::New { class, constructor, args } => {
+				let args = self.args(args)?;
+				self.construct(*class, *constructor, args, None, span)?
+			}
+			ExprKind::Member { object, name, target, safe, .. } => {
+				let receiver = self.expr(object)?;
+				if receiver.is_null() && *safe {
+					return Ok(Value::Null);
+				}
+				match target {
+					MemberTarget::Field(_) | MemberTarget::Dynamic => self.get(receiver, name, span)?,
+					MemberTarget::Method(method) => {
+						let method = if matches!(object.kind, ExprKind::Super) { *method } else { self.dispatch(&receiver, *method, span)? };
+						Value::Function(Rc::new(Closure { function: method, captures: Vec::new(), this: Some(receiver) }))
+					}
+					MemberTarget::Extension(function) => Value::Function(Rc::new(Closure { function: *function, captures: Vec::new(), this: Some(receiver) })),
+					MemberTarget::Builtin => ops::property(&receiver, name).map_err(|message| self.error(message, span))?
+				}
+			}
+			ExprKind::Variant { class, index } => self.variant(*class, *index, span)?,
+			ExprKind::Index { object, index } => {
+				let object = self.expr(object)?;
+				let index = self.expr(index)?;
+				self.load(&Place::Index(object, index), span)?
+			}
+
+			ExprKind::Range { start, end } => {
+				let (start, end) = (self.expr(start)?, self.expr(end)?);
+				ops::range(start, end).map_err(|message| self.error(message, span))?
+			}
+			ExprKind::Array(items) => Value::array(self.args(items)?),
+			ExprKind::Map(entries) => {
+				let mut map = Map::new();
+				for (key, value) in entries {
+					let key = self.expr(key)?;
+					map.insert(key, self.expr(value)?);
+				}
+				Value::map(map)
+			}
+			ExprKind::Set(elements) => Value::set(self.args(elements)?),
+			ExprKind::Lambda(function) => self.closure(*function),
+			// Without an event loop, async functions and builtins finish before
+			// they return, so there is nothing to wait for
+			ExprKind::Await(value) => self.expr(value)?,
+			ExprKind::Return(value) => {
+				let value = match value {
+					Some(value) => self.expr(value)?,
+					None => Value::Null
+				};
+				return Err(Control::Return(value));
+			}
+			ExprKind::Throw(value) => {
+				let value = self.expr(value)?;
+				return Err(self.throw(value, span));
+			}
+		})
+	}
+
+	fn args(&mut self, args: &'a [Expr]) -> Run<Vec<Value>> {
+		args.iter().map(|arg| self.expr(arg)).collect()
+	}
+
+	// A lambda or local function, with the locals it captures and `this`.
+	fn closure(&self, function: FnId) -> Value {
+		let captures = self.captures.of(function).iter().map(|&local| self.frame.capture(local).unwrap_or(Slot::Value(Value::Null))).collect();
+		Value::Function(Rc::new(Closure { function, captures, this: self.this.clone() }))
+	}
+
+	fn define(&mut self, local: LocalId, value: Value) {
+		self.frame.define(local, value, self.captures.boxed(local));
+	}
+
+	// `target = value`, or with `op`, `target = target op value`. The object
+	// and index of the target are worked out once.
+	fn assign(&mut self, target: &'a Expr, op: Option<BinaryOp>, value: &'a Expr) -> Run<Value> {
+		let place = self.place(target)?;
+		let value = match op {
+			Some(op) => {
+				let old = self.load(&place, target.span)?;
+				if op == BinaryOp::Coalesce && !old.is_null() {
+					return Ok(old);
+				}
+				let value = self.expr(value)?;
+				self.binary(op, old, value, target.span)?
+			}
+			None => self.expr(value)?
+		};
+		self.store(&place, value.clone(), target.span)?;
+		Ok(value)
+	}
+
+	// `++` and `--`, before and after: the target's old and new values.
+	fn update(&mut self, target: &'a Expr, delta: i64) -> Run<(Value, Value)> {
+		let place = self.place(target)?;
+		let old = self.load(&place, target.span)?;
+		let new = ops::step(&old, delta).map_err(|message| self.error(message, target.span))?;
+		self.store(&place, new.clone(), target.span)?;
+		Ok((old, new))
+	}
+
+	fn place(&mut self, target: &'a Expr) -> Run<Place> {
+		Ok(match &target.kind {
+			ExprKind::Var(var) => Place::Var(*var),
+			ExprKind::Member { object, name, .. } => Place::Member(self.expr(object)?, name.clone()),
+			ExprKind::Index { object, index } => Place::Index(self.expr(object)?, self.expr(index)?),
+			ExprKind::BackingField(name) => Place::Backing(name.clone()),
+			_ => return Err(self.error("Cannot assign to this", target.span))
+		})
+	}
+
+	fn load(&mut self, place: &Place, span: Span) -> Run<Value> {
+		match place {
+			Place::Var(Variable::Local(local)) => Ok(self.frame.get(*local).unwrap_or(Value::Null)),
+			Place::Var(Variable::Global(global)) => Ok(self.globals[global.0 as usize].clone()),
+			Place::Member(object, name) => self.get(object.clone(), name, span),
+			Place::Index(object, index) => ops::index(object, index).map_err(|message| self.error(message, span)),
+			Place::Backing(name) => match &self.this {
+				Some(Value::Object(object)) => Ok(object.class.field(name).map_or(Value::Null, |field| object.fields.borrow()[field].clone())),
+				_ => Ok(Value::Null)
+			}
+		}
+	}
+
+	fn store(&mut self, place: &Place, value: Value, span: Span) -> Run<()> {
+		match place {
+			Place::Var(Variable::Local(local)) => self.frame.set(*local, value),
+			Place::Var(Variable::Global(global)) => self.globals[global.0 as usize] = value,
+			Place::Member(object, name) => self.set(object.clone(), name, value, span)?,
+			Place::Index(object, index) => ops::set_index(object, index, value).map_err(|message| self.error(message, span))?,
+			Place::Backing(name) => {
+				if let Some(Value::Object(object)) = &self.this
+					&& let Some(field) = object.class.field(name)
+				{
+					object.fields.borrow_mut()[field] = value;
+				}
+			}
+		}
+		Ok(())
+	}
+
+	fn binary(&mut self, op: BinaryOp, left: Value, right: Value, span: Span) -> Run<Value> {
+		match (op, left, right) {
+			(BinaryOp::Add, Value::String(left), right) => ops::concat(&left, &self.stringify(&right)?).map_err(|message| self.error(message, span)),
+			(BinaryOp::Add, left, Value::String(right)) => ops::concat(&self.stringify(&left)?, &right).map_err(|message| self.error(message, span)),
+			(op, left, right) => ops::binary(op, left, right).map_err(|message| self.error(message, span))
+		}
+	}
+
+	// Whether `value` has type `ty`, for `is`, `catch`, `case` and casts.
+	// Type arguments are not kept at run time, so only the class is tested.
+	fn instance_of(&self, value: &Value, ty: &Type) -> bool {
+		match ty {
+			Type::Any | Type::Param { .. } | Type::Error => true,
+			Type::Void | Type::Never => false,
+			Type::Null => value.is_null(),
+			Type::Nullable(inner) => value.is_null() || self.instance_of(value, inner),
+			Type::Union(members) => members.iter().any(|member| self.instance_of(value, member)),
+			Type::Int => matches!(value, Value::Int(_)),
+			Type::Float => matches!(value, Value::Float(_)),
+			Type::Bool => matches!(value, Value::Bool(_)),
+			Type::String => matches!(value, Value::String(_)),
+			Type::Range => matches!(value, Value::Range(..)),
+			Type::Array(_) => matches!(value, Value::Array(_)),
+			Type::Map(..) => matches!(value, Value::Map(_)),
+			Type::Set(_) => matches!(value, Value::Set(_)),
+			Type::Iterator(_) => matches!(value, Value::Iterator(_)),
+			Type::Result(..) => matches!(value, Value::Tagged(Tag::Ok | Tag::Err, _)),
+			Type::Option(_) => matches!(value, Value::Tagged(Tag::Some | Tag::None, _)),
+			Type::Random => matches!(value, Value::Random(_)),
+			Type::Channel(_) => matches!(value, Value::Channel(_)),
+			Type::Pointer => matches!(value, Value::Pointer(_)),
+			Type::Function { .. } => matches!(value, Value::Function(_) | Value::Native(_) | Value::Builtin(_)),
+			Type::Class { id, .. } => matches!(value, Value::Object(object) if object.class.is(*id))
+		}
+	}
+
+	// What `value` looks like printed or added to a string, through its
+	// class's `toString()` when it has one.
+	fn stringify(&mut self, value: &Value) -> Run<String> {
+		ops::stringify(value, &mut |value| self.custom_string(value))
+	}
+
+	fn custom_string(&mut self, value: &Value) -> Run<Option<String>> {
+		Ok(match value {
+			Value::Object(object) => match object.class.method("toString", Some(0)) {
+				Some(method) => {
+					let span = self.program.function(method).span;
+					Some(match self.call_function(method, Some(value.clone()), &[], Vec::new(), span)? {
+						Value::String(text) => text.to_string(),
+						text => self.stringify(&text)?
+					})
+				}
+				None => None
+			},
+			Value::Function(closure) => Some(format!("<fn {}>", self.program.function(closure.function).name)),
+			_ => None
+		})
+	}
+
+	// ---------------------------------------------------------------------
+	// Calls
+	// ---------------------------------------------------------------------
+
+	// Methods are called on the object without making a function value of
+	// them first, and found on its class at run time.
+	fn call_expr(&mut self, callee: &'a Expr, args: &'a [Expr], span: Span) -> Run<Value> {
+		match &callee.kind {
+			ExprKind::Member { object, name, target, safe, .. } if !matches!(target, MemberTarget::Field(_)) => {
+				let receiver = self.expr(object)?;
+				if receiver.is_null() && *safe {
+					return Ok(Value::Null);
+				}
+				let args = self.args(args)?;
+				match target {
+					MemberTarget::Method(method) if matches!(object.kind, ExprKind::Super) => self.call_function(*method, Some(receiver), &[], args, span),
+					MemberTarget::Method(method) => {
+						let method = self.dispatch(&receiver, *method, span)?;
+						self.call_function(method, Some(receiver), &[], args, span)
+					}
+					MemberTarget::Extension(function) => self.call_function(*function, Some(receiver), &[], args, span),
+					_ => self.invoke(receiver, name, args, span)
+				}
+			}
+			ExprKind::Function(function) => {
+				let args = self.args(args)?;
+				self.call_function(*function, None, &[], args, span)
+			}
+			_ => {
+				let callee = self.expr(callee)?;
+				let args = self.args(args)?;
+				self.call_value(callee, args, span)
+			}
+		}
+	}
+
+	fn call_value(&mut self, callee: Value, args: Vec<Value>, span: Span) -> Run<Value> {
+		match callee {
+			Value::Function(closure) => self.call_function(closure.function, closure.this.clone(), &closure.captures, args, span),
+			Value::Builtin(builtin) => self.builtin(builtin, args, span),
+			Value::Native(native) => {
+				ops::arity(&native, args.len()).map_err(|message| self.error(message, span))?;
+				self.calls.push(Call { callee: Callee::Native(native.clone()), file: self.file, span });
+				let mut result = (native.function)(&args);
+				// Work the VM would do meanwhile is done here and now
+				for (_, work) in started() {
+					result = work()();
+				}
+				let result = result.map_err(|message| self.error(message, span));
+				let result = self.meter.native().map_err(|limit| self.exceeded(limit, span)).and(result);
+				self.calls.pop();
+				result
+			}
+			Value::Null => Err(self.error("Cannot call null", span)),
+			callee => Err(self.error(format!("'{}' is not a function", callee.type_name()), span))
+		}
+	}
+
+	// Builtins the VM runs as tasks, like `sleep`, are done here before they
+	// return, as there is nothing else to run meanwhile.
+	fn builtin(&mut self, builtin: Builtin, args: Vec<Value>, span: Span) -> Run<Value> {
+		let arg = args.first();
+		match builtin {
+			Builtin::Print | Builtin::Println => {
+				let text = match arg {
+					Some(value) => self.stringify(value)?,
+					None => String::new()
+				};
+				let written = match builtin {
+					Builtin::Print => write!(self.out, "{}", text),
+					_ => writeln!(self.out, "{}", text)
+				};
+				written.map_err(|err| self.error(format!("Cannot write output: {}", err), span))?;
+				Ok(Value::Null)
+			}
+			Builtin::Sleep => {
+				let mut duration = ops::millis(arg.unwrap_or(&Value::Null)).map_err(|message| self.error(message, span))?;
+				// No longer than the program may run, when that ends first
+				if let Some(deadline) = self.meter.deadline() {
+					duration = duration.min(deadline.saturating_duration_since(Instant::now()));
+				}
+				thread::sleep(duration);
+				self.meter.check().map_err(|limit| self.exceeded(limit, span))?;
+				Ok(Value::Null)
+			}
+			Builtin::ReadFile => {
+				let path = match arg {
+					Some(Value::String(path)) => path.clone(),
+					_ => return Err(self.error("'readFile' takes the path of a file", span))
+				};
+				ops::read_file(&path).map(Value::string).map_err(|message| self.error(message, span))
+			}
+			Builtin::ReadLine => Ok(ops::read_line().map_err(|message| self.error(message, span))?.map_or(Value::Null, Value::string)),
+			Builtin::Channel => Ok(Value::Channel(Rc::new(Channel::default()))),
+			Builtin::Ok => Ok(Value::tagged(Tag::Ok, arg.cloned().unwrap_or(Value::Null))),
+			Builtin::Err => Ok(Value::tagged(Tag::Err, arg.cloned().unwrap_or(Value::Null))),
+			Builtin::Some => Ok(Value::tagged(Tag::Some, arg.cloned().unwrap_or(Value::Null))),
+			Builtin::None => Ok(Value::tagged(Tag::None, Value::Null))
+		}
+	}
+
+	// Runs a function with its parameters bound to `args`, filling in
+	// defaults for those not given and gathering the rest for `...rest`.
+	fn call_function(&mut self, id: FnId, this: Option<Value>, captures: &[Slot], mut args: Vec<Value>, span: Span) -> Run<Value> {
+		let program = self.program;
+		let function = program.function(id);
+		if let Some(foreign) = &function.foreign {
+			return self.libraries.call(&function.name, foreign, &args).map_err(|message| self.error(message, span));
+		}
+		let Some(body) = &function.body else {
+			return Err(self.error(format!("'{}' has no body to run", function.name), span));
+		};
+		if self.calls.len() == MAX_DEPTH {
+			return Err(self.error(format!("Stack overflow: more than {} calls deep", MAX_DEPTH), span));
+		}
+		self.meter.depth(self.calls.len() + 1).map_err(|limit| self.exceeded(limit, span))?;
+		let saved = (
+			std::mem::replace(&mut self.frame, Frame::captured(self.captures.of(id), captures)),
+			std::mem::replace(&mut self.this, this),
+			std::mem::replace(&mut self.file, function.file)
+		);
+		self.calls.push(Call { callee: Callee::Function(id), file: saved.2, span });
+		let mut result = Ok(());
+		for (index, param) in function.params.iter().enumerate() {
+			let value = if param.variadic {
+				Value::array(args.split_off(index.min(args.len())))
+			} else if let Some(arg) = args.get(index) {
+				arg.clone()
+			} else if let Some(default) = &param.default {
+				match self.expr(default) {
+					Ok(value) => value,
+					Err(control) => {
+						result = Err(control);
+						break;
+					}
+				}
+			} else {
+				Value::Null
+			};
+			self.define(param.local, value);
+		}
+		// A generator runs to its end as it is called, and its iterator
+		// goes through what it yielded, rather than running it a `yield` at
+		// a time as the VM does
+		if function.generator {
+			self.yielded.push(Vec::new());
+		}
+		if result.is_ok() {
+			result = self.block(body);
+		}
+		self.calls.pop();
+		(self.frame, self.this, self.file) = saved;
+		if function.generator {
+			let yielded = self.yielded.pop().unwrap_or_default();
+			return match result {
+				Ok(()) | Err(Control::Return(_)) => Ok(Value::Iterator(self.sequence(Value::array(yielded), span)?)),
+				Err(control) => Err(control)
+			};
+		}
+		match result {
+			Ok(()) => Ok(Value::Null),
+			Err(Control::Return(value)) => Ok(value),
+			Err(control) => Err(control)
+		}
+	}
+
+	// The override of `method` in the class of `receiver`, or `method`
+	// itself when it has none.
+	fn dispatch(&self, receiver: &Value, method: FnId, span: Span) -> Run<FnId> {
+		let function = self.program.function(method);
+		match receiver {
+			Value::Object(object) => Ok(object.class.method(&function.name, Some(function.params.len())).unwrap_or(method)),
+			Value::Null => Err(self.error(format!("Cannot call '{}' on null", function.name), span)),
+			_ => Ok(method)
+		}
+	}
+
+	// Calls the method `name` of `receiver`, looked up by name at run time:
+	// for `Any` values, built-in members, and the iterator protocol.
+	fn invoke(&mut self, receiver: Value, name: &str, args: Vec<Value>, span: Span) -> Run<Value> {
+		if let Value::Object(object) = &receiver {
+			if let Some(method) = object.class.method(name, None) {
+				return self.call_function(method, Some(receiver), &[], args, span);
+			}
+			if object.class.field(name).is_some() {
+				let value = self.get(receiver, name, span)?;
+				return self.call_value(value, args, span);
+			}
+		}
+		if name == "toString" && args.is_empty() {
+			return Ok(Value::string(self.stringify(&receiver)?));
+		}
+		if let (Value::String(template), "format", [Value::Array(items)]) = (&receiver, name, args.as_slice()) {
+			let items = items.borrow().clone();
+			let args = items.iter().map(|item| self.stringify(item)).collect::<Run<Vec<String>>>()?;
+			return ops::format(template, &args).map(Value::string).map_err(|message| self.error(message, span));
+		}
+		if let Value::Tagged(tag, value) = &receiver
+			&& let Some(combine) = ops::combinator(*tag, value, name, &args)
+		{
+			return match combine {
+				Combine::Call(inputs, tag) => {
+					let result = self.call_value(args[0].clone(), inputs, span)?;
+					Ok(match tag {
+						Some(tag) => Value::tagged(tag, result),
+						None => result
+					})
+				}
+				Combine::Keep => Ok(receiver)
+			};
+		}
+		// Whatever would send a value runs after this, or ran before it
+		if let (Value::Channel(channel), "receive", []) = (&receiver, name, args.as_slice()) {
+			let message = "The channel is empty, and nothing can send to it while this waits, as the interpreter runs each task to the end";
+			return channel.receive().ok_or_else(|| self.error(message, span));
+		}
+		if let Some(value) = self.sequence_method(&receiver, name, &args, span)? {
+			return Ok(value);
+		}
+		ops::method(&receiver, name, &args).map_err(|message| self.error(message, span))
+	}
+
+	// The methods of sequences, for what has them (see `ops::Sequence`), and
+	// the iterator protocol of `Iterator`s. None for any other method.
+	fn sequence_method(&mut self, receiver: &Value, name: &str, args: &[Value], span: Span) -> Run<Option<Value>> {
+		if let Value::Iterator(sequence) = receiver {
+			match (name, args) {
+				(HAS_NEXT_METHOD, []) => {
+					let next = self.pull(sequence, span)?;
+					let more = next.is_some();
+					sequence.borrow_mut().peeked(next);
+					return Ok(Some(Value::Bool(more)));
+				}
+				(NEXT_METHOD, []) => match self.pull(sequence, span)? {
+					Some(next) => return Ok(Some(next)),
+					None => return Err(self.error("The iterator has no elements left".to_string(), span))
+				},
+				_ => {}
+			}
+		}
+		if !ops::is_sequence_method(receiver, name, args.len()) {
+			return Ok(None);
+		}
+		let sequence = self.sequence(receiver.clone(), span)?;
+		Ok(Some(match (name, args) {
+			(ITERATOR_METHOD, []) => Value::Iterator(sequence),
+			("map", [function]) => Value::Iterator(Sequence::new(Source::Map(sequence, function.clone()))),
+			("filter", [function]) => Value::Iterator(Sequence::new(Source::Filter(sequence, function.clone()))),
+			("zip", [other]) => Value::Iterator(Sequence::new(Source::Zip(sequence, self.sequence(other.clone(), span)?))),
+			("enumerate", []) => Value::Iterator(Sequence::new(Source::Enumerate(sequence, 0))),
+			("toArray", []) => {
+				let mut items = Vec::new();
+				while let Some(element) = self.pull(&sequence, span)? {
+					ops::push(&mut items, element).map_err(|message| self.error(message, span))?;
+				}
+				Value::array(items)
+			}
+			("reduce", [initial, function]) => {
+				let mut total = initial.clone();
+				while let Some(element) = self.pull(&sequence, span)? {
+					total = self.call_value(function.clone(), vec![total, element], span)?;
+				}
+				total
+			}
+			// As soon as one element decides it
+			("any" | "all", [function]) => {
+				let any = name == "any";
+				loop {
+					let Some(element) = self.pull(&sequence, span)? else {
+						break Value::Bool(!any);
+					};
+					if self.call_value(function.clone(), vec![element], span)?.truthy() == any {
+						break Value::Bool(any);
+					}
+				}
+			}
+			_ => return Ok(None)
+		}))
+	}
+
+	// What a `for` loop would go through in `value`, as a sequence;
+	// iterators are one already.
+	fn sequence(&mut self, value: Value, span: Span) -> Run<Rc<RefCell<Sequence>>> {
+		if let Value::Iterator(sequence) = value {
+			return Ok(sequence);
+		}
+		let cursor = self.cursor(value, ForKind::In, span)?;
+		Ok(Sequence::new(Source::Cursor(cursor)))
+	}
+
+	// The next element of a sequence, or none at the end.
+	fn pull(&mut self, sequence: &Rc<RefCell<Sequence>>, span: Span) -> Run<Option<Value>> {
+		let stage = sequence.borrow_mut().stage().map_err(|message| self.error(message, span))?;
+		Ok(match stage {
+			Stage::Ready(next) => next,
+			Stage::Protocol(iterator) => self.advance(&mut Cursor::Iterator(iterator), span)?,
+			Stage::Map(inner, function) => match self.pull(&inner, span)? {
+				Some(element) => Some(self.call_value(function, vec![element], span)?),
+				None => None
+			},
+			Stage::Filter(inner, test) => loop {
+				match self.pull(&inner, span)? {
+					Some(element) if self.call_value(test.clone(), vec![element.clone()], span)?.truthy() => break Some(element),
+					Some(_) => {}
+					None => break None
+				}
+			},
+			// Done at the end of either
+			Stage::Zip(first, second) => match self.pull(&first, span)? {
+				Some(element) => self.pull(&second, span)?.map(|other| Value::array(vec![element, other])),
+				None => None
+			},
+			Stage::Enumerate(inner, index) => self.pull(&inner, span)?.map(|element| Value::array(vec![Value::Int(index), element])),
+			// Generators make iterators of arrays here
+			Stage::Resume(_) => unreachable!("the interpreter runs generators as they are called")
+		})
+	}
+
+	// ---------------------------------------------------------------------
+	// Objects
+	// ---------------------------------------------------------------------
+
+	// `new C(...)`: an object with every field set to its initializer, or
+	// for a struct without a constructor to the arguments in order, then
+	// the constructor run on it. A class without constructors runs the one
+	// it inherits that takes no arguments, if there is one.
+	fn construct(&mut self, class: TypeId, constructor: Option<FnId>, mut args: Vec<Value>, variant: Option<usize>, span: Span) -> Run<Value> {
+		let program = self.program;
+		let header = &self.classes[class.0 as usize];
+		let object = Object::new(header.clone(), vec![Value::Null; header.fields.len()], variant);
+		let receiver = Value::Object(object.clone());
+		let positional = program.class(class).kind == ClassKind::Struct && program.class(class).constructors.is_empty();
+		let saved = (
+			std::mem::take(&mut self.frame),
+			self.this.replace(receiver.clone()),
+			self.file
+		);
+		let mut result = Ok(());
+		for (index, (owner, field)) in layout(program, class).into_iter().enumerate() {
+			let value = if positional && index < args.len() {
+				std::mem::replace(&mut args[index], Value::Null)
+			} else if let Some(init) = &field.init {
+				self.file = program.class(owner).file;
+				match self.expr(init) {
+					Ok(value) => value,
+					Err(control) => {
+						result = Err(control);
+						break;
+					}
+				}
+			} else {
+				Value::Null
+			};
+			object.fields.borrow_mut()[index] = value;
+		}
+		(self.frame, self.this, self.file) = saved;
+		result?;
+		let constructor = constructor.or_else(|| if positional { None } else { inherited_constructor(program, class) });
+		if let Some(constructor) = constructor {
+			let args = if program.class(class).constructors.contains(&constructor) { args } else { Vec::new() };
+			self.call_function(constructor, Some(receiver.clone()), &[], args, span)?;
+		}
+		Ok(receiver)
+	}
+
+	// `receiver.name`, through the field's getter when it has one. Methods
+	// read this way come with `receiver` as `this`.
+	fn get(&mut self, receiver: Value, name: &str, span: Span) -> Run<Value> {
+		let Value::Object(object) = &receiver else {
+			return ops::property(&receiver, name).map_err(|message| self.error(message, span));
+		};
+		if let Some(getter) = accessor(self.program, object.class.id, name, |field| field.getter) {
+			return self.call_function(getter, Some(receiver), &[], Vec::new(), span);
+		}
+		if let Some(field) = object.class.field(name) {
+			return Ok(object.fields.borrow()[field].clone());
+		}
+		if let Some(method) = object.class.method(name, None) {
+			return Ok(Value::Function(Rc::new(Closure { function: method, captures: Vec::new(), this: Some(receiver.clone()) })));
+		}
+		Err(self.error(format!("'{}' has no member '{}'", receiver.type_name(), name), span))
+	}
+
+	// `receiver.name = value`, through the field's setter when it has one.
+	fn set(&mut self, receiver: Value, name: &str, value: Value, span: Span) -> Run<()> {
+		let Value::Object(object) = &receiver else {
+			return Err(self.error(format!("Cannot set '{}' of '{}'", name, receiver.type_name()), span));
+		};
+		if let Some(setter) = accessor(self.program, object.class.id, name, |field| field.setter) {
+			self.call_function(setter, Some(receiver), &[], vec![value], span)?;
+			return Ok(());
+		}
+		match object.class.field(name) {
+			Some(field) => object.fields.borrow_mut()[field] = value,
+			None => return Err(self.error(format!("'{}' has no field '{}'", receiver.type_name(), name), span))
+		}
+		Ok(())
+	}
+
+	// An enum variant, made with the enum's constructor the first time it is used.
+	fn variant(&mut self, class: TypeId, index: usize, span: Span) -> Run<Value> {
+		if let Some(value) = self.variants.get(&(class, index)) {
+			return Ok(value.clone());
+		}
+		let program = self.program;
+		let enumeration = program.class(class);
+		let file = std::mem::replace(&mut self.file, enumeration.file);
+		let args = self.args(&enumeration.variants[index].args);
+		self.file = file;
+		let args = args?;
+		let constructor = enumeration.constructors.iter().copied().find(|&constructor| {
+			let params = &program.function(constructor).params;
+			let required = params.iter().filter(|param| param.default.is_none() && !param.variadic).count();
+			args.len() >= required && (args.len() <= params.len() || params.last().is_some_and(|param| param.variadic))
+		});
+		let value = self.construct(class, constructor, args, Some(index), span)?;
+		self.variants.insert((class, index), value.clone());
+		Ok(value)
+	}
+}
+
+impl Drop for Interpreter<'_> {
+	fn drop(&mut self) {
+		gc::uninstall();
+	}
+}
+
+pub fn class(program: &Program, id: TypeId) -> Class {
+	let class = program.class(id);
+	Class {
+		id,
+		name: class.name.clone(),
+		by_value: class.kind == ClassKind::Struct || class.modifiers.is_data,
+		fields: layout(program, id).into_iter().map(|(_, field)| field.name.clone()).collect(),
+		variants: class.variants.iter().map(|variant| variant.name.clone()).collect(),
+		supertypes: supertypes(program, id),
+		methods: methods(program, id)
+	}
+}
+
+fn supertypes(program: &Program, class: TypeId) -> Vec<TypeId> {
+	let mut supertypes = Vec::new();
+	for ty in &program.class(class).supertypes {
+		if let Type::Class { id, .. } = ty {
+			for supertype in std::iter::once(*id).chain(self::supertypes(program, *id)) {
+				if !supertypes.contains(&supertype) {
+					supertypes.push(supertype);
+				}
+			}
+		}
+	}
+	supertypes
+}
+
+// The class's own methods with bodies, then what it inherits that they do
+// not override: its superclass's first, then its interfaces' in order.
+fn methods(program: &Program, class: TypeId) -> HashMap<String, Vec<(usize, FnId)>> {
+	let mut methods: HashMap<String, Vec<(usize, FnId)>> = HashMap::new();
+	let mut add = |name: &str, arity: usize, method: FnId| {
+		let overloads = methods.entry(name.to_string()).or_default();
+		if !overloads.iter().any(|(params, _)| *params == arity) {
+			overloads.push((arity, method));
+		}
+	};
+	for &method in &program.class(class).methods {
+		let function = program.function(method);
+		if function.body.is_some() {
+			add(&function.name, function.params.len(), method);
+		}
+	}
+	for ty in &program.class(class).supertypes {
+		if let Type::Class { id, .. } = ty {
+			for (name, overloads) in self::methods(program, *id) {
+				for (arity, method) in overloads {
+					add(&name, arity, method);
+				}
+			}
+		}
+	}
+	methods
+}
+
+// What a stack trace calls a function: methods and accessors with their
+// class.
+pub fn name(program: &Program, function: FnId) -> String {
+	let function = program.function(function);
+	match function.kind {
+		FnKind::Method(class) | FnKind::Constructor(class) | FnKind::Getter(class) | FnKind::Setter(class) | FnKind::Static(class) => {
+			format!("{}.{}", program.class(class).name, function.name)
+		}
+		FnKind::Function | FnKind::Extension | FnKind::Lambda => function.name.clone()
+	}
+}
+
+// For a class without constructors, the nearest one it inherits that takes
+// no arguments.
+pub fn inherited_constructor(program: &Program, class: TypeId) -> Option<FnId> {
+	let mut current = superclass(program, class)?;
+	while program.class(current).constructors.is_empty() {
+		current = superclass(program, current)?;
+	}
+	program.class(current).constructors.iter().copied().find(|&constructor| {
+		program.function(constructor).params.iter().all(|param| param.default.is_some() || param.variadic)
+	})
+}
+
+// The getter or setter, picked by `pick`, of the field `name` declared in
+// `class` or a class it inherits from.
+pub fn accessor(program: &Program, class: TypeId, name: &str, pick: impl Fn(&Field) -> Option<FnId>) -> Option<FnId> {
+	let mut current = Some(class);
+	while let Some(class) = current {
+		if let Some(field) = program.class(class).field(name) {
+			return pick(field);
+		}
+		current = superclass(program, class);
+	}
+	None
+}
+
+pub fn superclass(program: &Program, class: TypeId) -> Option<TypeId> {
+	match program.class(class).supertypes.first() {
+		Some(Type::Class { id, .. }) if program.class(*id).kind != ClassKind::Interface => Some(*id),
+		_ => None
+	}
+}
+
+// The fields an object of `class` has, with the class declaring each, those
+// of its superclasses first.
+pub fn layout(program: &Program, class: TypeId) -> Vec<(TypeId, &Field)> {
+	let mut fields = match superclass(program, class) {
+		Some(superclass) => layout(program, superclass),
+		None => Vec::new()
+	};
+	fields.extend(program.class(class).fields.iter().map(|field| (class, field)));
+	fields
+}
@@ -0,0 +1,471 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::diagnostics::Diagnostic;
+use crate::interpreter::limits::RuntimeLimits;
+use crate::interpreter::value::{self, Task, Value};
+use crate::lexer::lexer::Lexer;
+use crate::parser::parser::Parser;
+use crate::sema::check::{Bodies, check_with};
+use crate::sema::hir::{self, Program};
+use crate::sema::modules::SourceFile;
+use crate::sema::processors;
+use crate::sema::types::Type;
+
+// What a program runs with besides its own code: the functions written in
+// Rust that the host, or the standard library, registers for it to call.
+//
+//     let mut runtime = Runtime::new();
+//     runtime.register("math.sqrt", |x: f64| x.sqrt());
+//     runtime.register("shout", |text: String| if text.is_empty() { Err("nothing to shout".to_string()) } else { Ok(text.to_uppercase()) });
+//
+// Code calls them by their name, `math.sqrt(2.0)`, checked against the
+// types of the closure's parameters and what it returns. The arguments are
+// turned into those types, and what it returns back into a value; an error
+// it returns is thrown, for `catch` to handle like any other.
+//
+// A program is checked for a runtime, which gives it the natives it can
+// call, and is run with one, which gives it what they do. Compiled programs
+// name the natives they call, so they can run with any runtime that has
+// them.
+//
+// A runtime can also have code written in dotFun, which is checked and
+// compiled with every program as files of their own, after the program's:
+//
+//     runtime.source("std.testing", include_str!("testing.gl"));
+#[derive(Clone, Default)]
+pub struct Runtime {
+	natives: Vec<Registered>,
+	sources: Vec<Source>,
+	// What programs run with it may use, none limited by default
+	pub limits: RuntimeLimits
+}
+
+#[derive(Clone)]
+struct Registered {
+	declaration: hir::Native,
+	function: Arc<HostFn>
+}
+
+// A file of the runtime's, in `package`, and named after it.
+#[derive(Clone)]
+pub struct Source {
+	pub package: String,
+	pub path: String,
+	pub text: String
+}
+
+// A native as registered: it gets arguments of the types it was declared
+// with, the right number of them, and can be shared between threads, as a
+// runtime can be.
+pub type HostFn = dyn Fn(&[Value]) -> Result<Value, String> + Send + Sync;
+
+impl Runtime {
+	pub fn new() -> Runtime {
+		Runtime::default()
+	}
+
+	// Gives code `function` to call as `name`, in place of any native
+	// registered as that before.
+	pub fn register<Args>(&mut self, name: &str, function: impl IntoNative<Args>) -> &mut Runtime {
+		let declaration = hir::Native { name: name.to_string(), params: function.params(), ret: function.ret() };
+		let registered = Registered { declaration, function: function.into_host(name) };
+		match self.natives.iter_mut().find(|native| native.declaration.name == name) {
+			Some(native) => *native = registered,
+			None => self.natives.push(registered)
+		}
+		self
+	}
+
+	// Adds `text` to every program as a file of `package`, which it needs no
+	// `package` statement to be in.
+	pub fn source(&mut self, package: &str, text: &str) -> &mut Runtime {
+		let path = format!("<{}>", package);
+		self.sources.push(Source { package: package.to_string(), path, text: text.to_string() });
+		self
+	}
+
+	// The files added to every program, in the order they come after its own.
+	pub fn sources(&self) -> &[Source] {
+		&self.sources
+	}
+
+	// What code sees of the natives, for checking it.
+	pub fn declarations(&self) -> Vec<hir::Native> {
+		self.natives.iter().map(|native| native.declaration.clone()).collect()
+	}
+
+	// Checks a program for this runtime, with the default annotation
+	// processors. Warnings about the runtime's own files are left out, as
+	// they are not the program's to fix.
+	pub fn check(&self, files: &[SourceFile]) -> Result<Program, Vec<Diagnostic>> {
+		self.checked(files, None)
+	}
+
+	// The same, again, for files checked before with `kept`: packages that
+	// have not changed since are not checked again.
+	pub fn recheck(&self, files: &[SourceFile], kept: &mut Bodies) -> Result<Program, Vec<Diagnostic>> {
+		self.checked(files, Some(kept))
+	}
+
+	fn checked(&self, files: &[SourceFile], kept: Option<&mut Bodies>) -> Result<Program, Vec<Diagnostic>> {
+		let mut all = files.to_vec();
+		for source in &self.sources {
+			let file = all.len();
+			all.push(source.parse().map_err(|errors| errors.into_iter().map(|error| error.in_file(file)).collect::<Vec<Diagnostic>>())?);
+		}
+		let mut program = check_with(&all, &mut processors::defaults(), &self.declarations(), kept)?;
+		program.warnings.retain(|warning| warning.file < files.len());
+		Ok(program)
+	}
+
+	// The native registered as `name`, as a value for code to call.
+	pub fn native(&self, name: &str) -> Option<Rc<value::Native>> {
+		let native = self.natives.iter().find(|native| native.declaration.name == name)?;
+		let function = native.function.clone();
+		let arity = Some(native.declaration.params.len());
+		Some(Rc::new(value::Native { name: name.to_string(), arity, function: Box::new(move |args| function(args)) }))
+	}
+
+	// The natives a program calls, in the order it numbers them, as values;
+	// none for those this runtime does not have.
+	pub fn bind<'a>(&self, names: impl IntoIterator<Item = &'a str>) -> Vec<Option<Rc<value::Native>>> {
+		names.into_iter().map(|name| self.native(name)).collect()
+	}
+}
+
+impl Source {
+	fn parse(&self) -> Result<SourceFile, Vec<Diagnostic>> {
+		let tokens = Lexer::new(self.text.clone()).lex().map_err(|err| vec![*err])?.clone();
+		let program = Parser::new(tokens).parse()?;
+		Ok(SourceFile { path: self.path.clone(), program, package: Some(self.package.clone()) })
+	}
+}
+
+// What calling a native the runtime does not have throws.
+pub fn missing(name: &str) -> String {
+	format!("'{}' is a native function this runtime does not have", name)
+}
+
+// A Rust type natives take or return, standing for a dotFun type.
+pub trait Marshal: Sized {
+	fn ty() -> Type;
+
+	// None when the value is not of the type.
+	fn from_value(value: &Value) -> Option<Self>;
+
+	fn into_value(self) -> Value;
+}
+
+impl Marshal for i64 {
+	fn ty() -> Type {
+		Type::Int
+	}
+
+	fn from_value(value: &Value) -> Option<i64> {
+		match value {
+			Value::Int(value) => Some(*value),
+			_ => None
+		}
+	}
+
+	fn into_value(self) -> Value {
+		Value::Int(self)
+	}
+}
+
+// An `Int` passed for a `Float` is widened, as code does.
+impl Marshal for f64 {
+	fn ty() -> Type {
+		Type::Float
+	}
+
+	fn from_value(value: &Value) -> Option<f64> {
+		match value {
+			Value::Float(value) => Some(*value),
+			Value::Int(value) => Some(*value as f64),
+			_ => None
+		}
+	}
+
+	fn into_value(self) -> Value {
+		Value::Float(self)
+	}
+}
+
+impl Marshal for bool {
+	fn ty() -> Type {
+		Type::Bool
+	}
+
+	fn from_value(value: &Value) -> Option<bool> {
+		match value {
+			Value::Bool(value) => Some(*value),
+			_ => None
+		}
+	}
+
+	fn into_value(self) -> Value {
+		Value::Bool(self)
+	}
+}
+
+impl Marshal for String {
+	fn ty() -> Type {
+		Type::String
+	}
+
+	fn from_value(value: &Value) -> Option<String> {
+		match value {
+			Value::String(text) => Some(text.to_string()),
+			_ => None
+		}
+	}
+
+	fn into_value(self) -> Value {
+		Value::string(self)
+	}
+}
+
+// Returning nothing, for natives called for what they do.
+impl Marshal for () {
+	fn ty() -> Type {
+		Type::Void
+	}
+
+	fn from_value(value: &Value) -> Option<()> {
+		value.is_null().then_some(())
+	}
+
+	fn into_value(self) -> Value {
+		Value::Null
+	}
+}
+
+// Any value at all, as it is.
+impl Marshal for Value {
+	fn ty() -> Type {
+		Type::Any
+	}
+
+	fn from_value(value: &Value) -> Option<Value> {
+		Some(value.clone())
+	}
+
+	fn into_value(self) -> Value {
+		self
+	}
+}
+
+impl<T: Marshal> Marshal for Option<T> {
+	fn ty() -> Type {
+		T::ty().nullable()
+	}
+
+	fn from_value(value: &Value) -> Option<Option<T>> {
+		match value {
+			Value::Null => Some(None),
+			value => T::from_value(value).map(Some)
+		}
+	}
+
+	fn into_value(self) -> Value {
+		self.map_or(Value::Null, T::into_value)
+	}
+}
+
+// Copied in and out: changes a native makes to the array it gets are not
+// seen by code.
+impl<T: Marshal> Marshal for Vec<T> {
+	fn ty() -> Type {
+		Type::Array(Box::new(T::ty()))
+	}
+
+	fn from_value(value: &Value) -> Option<Vec<T>> {
+		match value {
+			Value::Array(items) => items.borrow().iter().map(T::from_value).collect(),
+			_ => None
+		}
+	}
+
+	fn into_value(self) -> Value {
+		Value::array(self.into_iter().map(T::into_value).collect())
+	}
+}
+
+// Work a native leaves to be done off the thread running the program, as
+// the async builtins do: the VM does it on a thread of its own and returns a
+// task for `await`, and the interpreter does it at once. `T` is made into a
+// value back on the program's thread, once the work is done.
+//
+//     runtime.register("slowly", |n: i64| Background::new(move || Ok(n * 2)));
+pub struct Background<T>(Box<dyn FnOnce() -> Result<T, String> + Send>);
+
+impl<T> Background<T> {
+	pub fn new(work: impl FnOnce() -> Result<T, String> + Send + 'static) -> Background<T> {
+		Background(Box::new(work))
+	}
+}
+
+// Work started, and what makes its value once it is done.
+pub type Work = Box<dyn FnOnce() -> Done + Send>;
+pub type Done = Box<dyn FnOnce() -> Result<Value, String> + Send>;
+
+thread_local! {
+	// Returned by natives for the engine calling them to do
+	static STARTED: RefCell<Vec<(Rc<Task>, Work)>> = RefCell::default();
+}
+
+// The work natives returned since this was last asked, with the tasks that
+// stand for it.
+pub fn started() -> Vec<(Rc<Task>, Work)> {
+	STARTED.with(|started| started.take())
+}
+
+impl<T: Marshal + Send + 'static> Marshal for Background<T> {
+	fn ty() -> Type {
+		T::ty()
+	}
+
+	// Only ever returned
+	fn from_value(_: &Value) -> Option<Background<T>> {
+		None
+	}
+
+	fn into_value(self) -> Value {
+		let task = Rc::new(Task::default());
+		let work: Work = Box::new(move || {
+			let result = (self.0)();
+			Box::new(move || result.map(T::into_value))
+		});
+		STARTED.with(|started| started.borrow_mut().push((task.clone(), work)));
+		Value::Task(task)
+	}
+}
+
+// What a native returns: a value, or a result whose error is the message
+// to throw.
+pub trait Returns {
+	fn ty() -> Type;
+
+	fn into_result(self) -> Result<Value, String>;
+}
+
+impl<T: Marshal> Returns for T {
+	fn ty() -> Type {
+		T::ty()
+	}
+
+	fn into_result(self) -> Result<Value, String> {
+		Ok(self.into_value())
+	}
+}
+
+impl<T: Marshal> Returns for Result<T, String> {
+	fn ty() -> Type {
+		T::ty()
+	}
+
+	fn into_result(self) -> Result<Value, String> {
+		self.map(T::into_value)
+	}
+}
+
+// A closure that can be registered: one taking up to three arguments whose
+// types marshal, and returning what does. `Args` tells the impls apart, as
+// the tuple of its parameters' types.
+pub trait IntoNative<Args> {
+	fn params(&self) -> Vec<Type>;
+
+	fn ret(&self) -> Type;
+
+	fn into_host(self, name: &str) -> Arc<HostFn>;
+}
+
+// The `index`th argument of a call to `name`, as a `T`.
+fn arg<T: Marshal>(name: &str, args: &[Value], index: usize) -> Result<T, String> {
+	let value = args.get(index).unwrap_or(&Value::Null);
+	T::from_value(value).ok_or_else(|| format!("'{}' takes a '{}' as argument {}, not a '{}'", name, T::ty(), index + 1, value.type_name()))
+}
+
+impl<F, R> IntoNative<()> for F
+where
+	F: Fn() -> R + Send + Sync + 'static,
+	R: Returns
+{
+	fn params(&self) -> Vec<Type> {
+		Vec::new()
+	}
+
+	fn ret(&self) -> Type {
+		R::ty()
+	}
+
+	fn into_host(self, _: &str) -> Arc<HostFn> {
+		Arc::new(move |_| self().into_result())
+	}
+}
+
+impl<F, A, R> IntoNative<(A,)> for F
+where
+	F: Fn(A) -> R + Send + Sync + 'static,
+	A: Marshal,
+	R: Returns
+{
+	fn params(&self) -> Vec<Type> {
+		vec![A::ty()]
+	}
+
+	fn ret(&self) -> Type {
+		R::ty()
+	}
+
+	fn into_host(self, name: &str) -> Arc<HostFn> {
+		let name = name.to_string();
+		Arc::new(move |args| self(arg(&name, args, 0)?).into_result())
+	}
+}
+
+impl<F, A, B, R> IntoNative<(A, B)> for F
+where
+	F: Fn(A, B) -> R + Send + Sync + 'static,
+	A: Marshal,
+	B: Marshal,
+	R: Returns
+{
+	fn params(&self) -> Vec<Type> {
+		vec![A::ty(), B::ty()]
+	}
+
+	fn ret(&self) -> Type {
+		R::ty()
+	}
+
+	fn into_host(self, name: &str) -> Arc<HostFn> {
+		let name = name.to_string();
+		Arc::new(move |args| self(arg(&name, args, 0)?, arg(&name, args, 1)?).into_result())
+	}
+}
+
+impl<F, A, B, C, R> IntoNative<(A, B, C)> for F
+where
+	F: Fn(A, B, C) -> R + Send + Sync + 'static,
+	A: Marshal,
+	B: Marshal,
+	C: Marshal,
+	R: Returns
+{
+	fn params(&self) -> Vec<Type> {
+		vec![A::ty(), B::ty(), C::ty()]
+	}
+
+	fn ret(&self) -> Type {
+		R::ty()
+	}
+
+	fn into_host(self, name: &str) -> Arc<HostFn> {
+		let name = name.to_string();
+		Arc::new(move |args| self(arg(&name, args, 0)?, arg(&name, args, 1)?, arg(&name, args, 2)?).into_result())
+	}
+}
@@ -0,0 +1,822 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::ast::expr::BinaryOp;
+use crate::ast::stmt::ITERATOR_METHOD;
+use crate::interpreter::limits;
+use crate::interpreter::value::{Channel, Generator, Map, Native, Random, Tag, Value};
+
+// What running a program does to values, the same whichever way it runs:
+// walking the tree, or on the bytecode VM. What can call back into the
+// program, like a class's `toString()` or an iterator, is left to each of
+// them; errors are the message to throw.
+
+// `left op right` for every operator but `&&`, `||` and `??`, which only
+// evaluate their right side sometimes, and `+` with a string, which turns
+// the other side into one.
+pub fn binary(op: BinaryOp, left: Value, right: Value) -> Result<Value, String> {
+	let float = |value: &Value| match value {
+		Value::Int(value) => Some(*value as f64),
+		Value::Float(value) => Some(*value),
+		_ => None
+	};
+	Ok(match (op, left, right) {
+		(BinaryOp::Equal, left, right) => Value::Bool(left == right),
+		(BinaryOp::NotEqual, left, right) => Value::Bool(left != right),
+		(_, Value::Int(left), Value::Int(right)) if !matches!(op, BinaryOp::In | BinaryOp::And | BinaryOp::Or | BinaryOp::Coalesce) => {
+			integer(op, left, right)?
+		}
+		(BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo, left, right)
+			if let (Some(left), Some(right)) = (float(&left), float(&right)) =>
+		{
+			Value::Float(match op {
+				BinaryOp::Add => left + right,
+				BinaryOp::Subtract => left - right,
+				BinaryOp::Multiply => left * right,
+				BinaryOp::Divide => left / right,
+				_ => left % right
+			})
+		}
+		(BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual, left, right)
+			if let Some(ordering) = match (&left, &right) {
+				(Value::String(left), Value::String(right)) => Some(Some(left.cmp(right))),
+				_ => float(&left).zip(float(&right)).map(|(left, right)| left.partial_cmp(&right))
+			} =>
+		{
+			Value::Bool(compare(op, ordering))
+		}
+		(BinaryOp::And, left, right) => Value::Bool(left.truthy() && right.truthy()),
+		(BinaryOp::Or, left, right) => Value::Bool(left.truthy() || right.truthy()),
+		(BinaryOp::Coalesce, Value::Null, right) => right,
+		(BinaryOp::Coalesce, left, _) => left,
+		(BinaryOp::In, Value::Int(item), Value::Range(start, end)) => Value::Bool(start <= item && item < end),
+		(BinaryOp::In, item, Value::Array(items)) => Value::Bool(items.borrow().contains(&item)),
+		(BinaryOp::In, key, Value::Map(map) | Value::Set(map)) => Value::Bool(map.borrow().contains_key(&key)),
+		(BinaryOp::In, Value::String(item), Value::String(text)) => Value::Bool(text.contains(&*item)),
+		(op, left, right) => {
+			return Err(format!("Operator '{}' cannot be applied to '{}' and '{}'", op.symbol(), left.type_name(), right.type_name()));
+		}
+	})
+}
+
+// `left + right` once either side is a string, and the other turned into
+// one.
+pub fn concat(left: &str, right: &str) -> Result<Value, String> {
+	let mut text = allocate(left.len().saturating_add(right.len()))?;
+	text.push_str(left);
+	text.push_str(right);
+	Ok(Value::string(text))
+}
+
+// An empty string with room for `bytes`, asked for within the program's
+// limit on memory, so that making one too long to keep is an error rather
+// than the end of the process. The heap is only measured now and then, so
+// what grows the most at once is asked for as it is made.
+fn allocate(bytes: usize) -> Result<String, String> {
+	limits::reserve(bytes)?;
+	let mut text = String::new();
+	text.try_reserve_exact(bytes).map_err(|_| format!("Cannot make a String of {} bytes", bytes))?;
+	Ok(text)
+}
+
+// Adds `item` to the end of `items`, asking for the memory first when they
+// have to grow, as `allocate` does.
+pub fn push(items: &mut Vec<Value>, item: Value) -> Result<(), String> {
+	if items.len() == items.capacity() {
+		let length = items.len().max(2).saturating_mul(2);
+		limits::reserve(length.saturating_mul(size_of::<Value>()))?;
+		items.try_reserve(1).map_err(|_| format!("Cannot make an Array of {} elements", length))?;
+	}
+	items.push(item);
+	Ok(())
+}
+
+// Arithmetic on `Int`s is checked: overflowing is an error rather than
+// wrapping around.
+fn integer(op: BinaryOp, left: i64, right: i64) -> Result<Value, String> {
+	let shift = |right: i64| u32::try_from(right).ok().filter(|&right| right < i64::BITS);
+	let result = match op {
+		BinaryOp::Add => left.checked_add(right),
+		BinaryOp::Subtract => left.checked_sub(right),
+		BinaryOp::Multiply => left.checked_mul(right),
+		BinaryOp::Divide | BinaryOp::Modulo if right == 0 => return Err("Division by zero".to_string()),
+		BinaryOp::Divide => left.checked_div(right),
+		BinaryOp::Modulo => left.checked_rem(right),
+		BinaryOp::BitAnd => Some(left & right),
+		BinaryOp::BitOr => Some(left | right),
+		BinaryOp::BitXor => Some(left ^ right),
+		BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+			let Some(by) = shift(right) else {
+				return Err(format!("Cannot shift by {}; shifts go from 0 to 63", right));
+			};
+			Some(if op == BinaryOp::ShiftLeft { left << by } else { left >> by })
+		}
+		_ => return Ok(Value::Bool(compare(op, Some(left.cmp(&right)))))
+	};
+	result.map(Value::Int).ok_or_else(|| "Integer overflow".to_string())
+}
+
+fn compare(op: BinaryOp, ordering: Option<Ordering>) -> bool {
+	let Some(ordering) = ordering else {
+		return false;
+	};
+	match op {
+		BinaryOp::Less => ordering.is_lt(),
+		BinaryOp::LessEqual => ordering.is_le(),
+		BinaryOp::Greater => ordering.is_gt(),
+		BinaryOp::GreaterEqual => ordering.is_ge(),
+		_ => false
+	}
+}
+
+pub fn negate(value: Value) -> Result<Value, String> {
+	match value {
+		Value::Int(value) => value.checked_neg().map(Value::Int).ok_or_else(|| "Integer overflow".to_string()),
+		Value::Float(value) => Ok(Value::Float(-value)),
+		value => Err(format!("Cannot negate '{}'", value.type_name()))
+	}
+}
+
+// What `++` and `--` make of a value: one more or one less.
+pub fn step(value: &Value, delta: i64) -> Result<Value, String> {
+	match value {
+		Value::Int(value) => value.checked_add(delta).map(Value::Int).ok_or_else(|| "Integer overflow".to_string()),
+		Value::Float(value) => Ok(Value::Float(value + delta as f64)),
+		_ => Err(format!("Cannot increment or decrement '{}'", value.type_name()))
+	}
+}
+
+pub fn range(start: Value, end: Value) -> Result<Value, String> {
+	match (start, end) {
+		(Value::Int(start), Value::Int(end)) => Ok(Value::Range(start, end)),
+		(start, end) => Err(format!("A range goes between two 'Int's, not '{}' and '{}'", start.type_name(), end.type_name()))
+	}
+}
+
+// `object[index]`: an array's element, a map's value, or a string's
+// character. A key a map does not have is null.
+pub fn index(object: &Value, index: &Value) -> Result<Value, String> {
+	match (object, index) {
+		(Value::Array(items), Value::Int(index)) => {
+			let items = items.borrow();
+			match usize::try_from(*index).ok().and_then(|at| items.get(at)) {
+				Some(item) => Ok(item.clone()),
+				None => Err(format!("Index {} is out of bounds for length {}", index, items.len()))
+			}
+		}
+		(Value::Map(map), key) => Ok(map.borrow().get(key).cloned().unwrap_or(Value::Null)),
+		(Value::String(text), Value::Int(index)) => match usize::try_from(*index).ok().and_then(|at| text.chars().nth(at)) {
+			Some(c) => Ok(Value::string(c.to_string())),
+			None => Err(format!("Index {} is out of bounds for length {}", index, text.chars().count()))
+		},
+		(object, index) => Err(format!("Cannot index '{}' with '{}'", object.type_name(), index.type_name()))
+	}
+}
+
+pub fn set_index(object: &Value, index: &Value, value: Value) -> Result<(), String> {
+	match (object, index) {
+		(Value::Array(items), Value::Int(index)) => {
+			let mut items = items.borrow_mut();
+			let length = items.len();
+			match usize::try_from(*index).ok().and_then(|at| items.get_mut(at)) {
+				Some(item) => *item = value,
+				None => return Err(format!("Index {} is out of bounds for length {}", index, length))
+			}
+		}
+		(Value::Map(map), key) => {
+			map.borrow_mut().insert(key.clone(), value);
+		}
+		(object, _) => return Err(format!("Cannot set an element of '{}'", object.type_name()))
+	}
+	Ok(())
+}
+
+// The members values of built-in types come with that are not methods.
+pub fn property(receiver: &Value, name: &str) -> Result<Value, String> {
+	Ok(match (receiver, name) {
+		(Value::String(text), "length") => Value::Int(text.chars().count() as i64),
+		(Value::Array(items), "size") => Value::Int(items.borrow().len() as i64),
+		(Value::Map(map) | Value::Set(map), "size") => Value::Int(map.borrow().len() as i64),
+		(Value::Range(start, _), "start") => Value::Int(*start),
+		(Value::Range(_, end), "end") => Value::Int(*end),
+		(Value::Object(object), "name") if let Some(variant) = object.variant => Value::string(object.class.variants[variant].as_str()),
+		(Value::Null, _) => return Err(format!("Cannot read '{}' of null", name)),
+		_ => return Err(format!("'{}' has no member '{}'", receiver.type_name(), name))
+	})
+}
+
+// The methods values of built-in types come with, but `toString()`, which
+// can call back into the program.
+pub fn method(receiver: &Value, name: &str, args: &[Value]) -> Result<Value, String> {
+	Ok(match (receiver, name, args.len()) {
+		(Value::Int(value), "toFloat", 0) => Value::Float(*value as f64),
+		(Value::Float(value), "toInt", 0) => {
+			if !value.is_finite() || value.abs() >= i64::MAX as f64 {
+				return Err(format!("{:?} is too large to be an 'Int'", value));
+			}
+			Value::Int(value.trunc() as i64)
+		}
+		(Value::String(text), "isEmpty", 0) => Value::Bool(text.is_empty()),
+		(Value::String(text), _, _) if let Some(value) = string_method(text, name, args)? => value,
+		(Value::Array(items), "isEmpty", 0) => Value::Bool(items.borrow().is_empty()),
+		(Value::Map(map) | Value::Set(map), "isEmpty", 0) => Value::Bool(map.borrow().is_empty()),
+		(Value::Array(items), _, _) if let Some(value) = array_method(items, name, args)? => value,
+		(Value::Map(map), _, _) if let Some(value) = map_method(map, name, args) => value,
+		(Value::Set(set), _, _) if let Some(value) = set_method(set, name, args) => value,
+		(Value::Tagged(tag, value), _, _) if let Some(value) = wrapper_method(*tag, value, name, args)? => value,
+		(Value::Random(random), _, _) if let Some(value) = random_method(random, name, args)? => value,
+		(Value::Channel(channel), _, _) if let Some(value) = channel_method(channel, name, args)? => value,
+		(Value::Null, _, _) => return Err(format!("Cannot call '{}' on null", name)),
+		_ => return Err(format!("'{}' has no method '{}'", receiver.type_name(), name))
+	})
+}
+
+// The methods of results and options but those taking a function (see
+// `combinator`).
+fn wrapper_method(tag: Tag, value: &Value, name: &str, args: &[Value]) -> Result<Option<Value>, String> {
+	let value = value.clone();
+	Ok(Some(match (name, args) {
+		("isOk", []) => Value::Bool(tag == Tag::Ok),
+		("isErr", []) => Value::Bool(tag == Tag::Err),
+		("isSome", []) => Value::Bool(tag == Tag::Some),
+		("isNone", []) => Value::Bool(tag == Tag::None),
+		("unwrap", []) if tag.has_value() => value,
+		("unwrapErr", []) if tag == Tag::Err => value,
+		("unwrap" | "unwrapErr", []) => {
+			let what = match tag {
+				Tag::None => "None".to_string(),
+				tag => format!("{}({})", tag.name(), plain(&value))
+			};
+			return Err(format!("Called '{}()' on {}", name, what));
+		}
+		("unwrapOr", [default]) => if tag.has_value() { value } else { default.clone() },
+		("ok", []) => if tag == Tag::Ok { Value::tagged(Tag::Some, value) } else { Value::tagged(Tag::None, Value::Null) },
+		("err", []) => if tag == Tag::Err { Value::tagged(Tag::Some, value) } else { Value::tagged(Tag::None, Value::Null) },
+		("okOr", [error]) => if tag == Tag::Some { Value::tagged(Tag::Ok, value) } else { Value::tagged(Tag::Err, error.clone()) },
+		("orNull", []) => value,
+		_ => return Ok(None)
+	}))
+}
+
+// What `map`, `mapErr`, `andThen` and `orElse` of a result or option do,
+// which the engines carry out, as only they can call the function. None for
+// any other method.
+pub enum Combine {
+	// Calls the function with these arguments, wrapping what it returns in
+	// the tag when there is one
+	Call(Vec<Value>, Option<Tag>),
+	// Returns the receiver as it is
+	Keep
+}
+
+pub fn combinator(tag: Tag, value: &Value, name: &str, args: &[Value]) -> Option<Combine> {
+	if args.len() != 1 {
+		return None;
+	}
+	let value = value.clone();
+	Some(match (name, tag) {
+		("map", Tag::Ok | Tag::Some) => Combine::Call(vec![value], Some(tag)),
+		("mapErr", Tag::Err) => Combine::Call(vec![value], Some(tag)),
+		("andThen", Tag::Ok | Tag::Some) | ("orElse", Tag::Err) => Combine::Call(vec![value], None),
+		("orElse", Tag::None) => Combine::Call(Vec::new(), None),
+		("map" | "mapErr" | "andThen" | "orElse", _) => Combine::Keep,
+		_ => return None
+	})
+}
+
+// The methods of channels but `receive`, which the engines carry out, as
+// only they know what else can run while it waits.
+fn channel_method(channel: &Channel, name: &str, args: &[Value]) -> Result<Option<Value>, String> {
+	Ok(Some(match (name, args) {
+		("send", [value]) => {
+			channel.send(value.clone())?;
+			Value::Null
+		}
+		("close", []) => {
+			channel.closed.set(true);
+			Value::Null
+		}
+		("isClosed", []) => Value::Bool(channel.closed.get()),
+		_ => return Ok(None)
+	}))
+}
+
+fn random_method(random: &Random, name: &str, args: &[Value]) -> Result<Option<Value>, String> {
+	Ok(Some(match (name, args) {
+		("int", [Value::Int(low), Value::Int(high)]) => Value::Int(random.int(*low, *high)?),
+		("float", []) => Value::Float(random.float()),
+		("choice", [Value::Array(items)]) => random.choice(&items.borrow())?,
+		("shuffle", [Value::Array(items)]) => {
+			random.shuffle(&mut items.borrow_mut());
+			Value::Null
+		}
+		_ => return Ok(None)
+	}))
+}
+
+// The methods of strings but `format`, which turns its arguments into
+// strings first. None for a method strings do not have.
+fn string_method(text: &str, name: &str, args: &[Value]) -> Result<Option<Value>, String> {
+	let string = |index: usize| match args.get(index) {
+		Some(Value::String(arg)) => Ok(arg.clone()),
+		arg => Err(format!("'{}' takes a String, not '{}'", name, arg.map_or("nothing", Value::type_name)))
+	};
+	let int = |index: usize| match args.get(index) {
+		Some(Value::Int(arg)) => Ok(*arg),
+		arg => Err(format!("'{}' takes an Int, not '{}'", name, arg.map_or("nothing", Value::type_name)))
+	};
+	let strings = |parts: Vec<&str>| Value::array(parts.into_iter().map(Value::string).collect());
+	let length = || text.chars().count() as i64;
+	Ok(Some(match (name, args.len()) {
+		("trim", 0) => Value::string(text.trim()),
+		("trimStart", 0) => Value::string(text.trim_start()),
+		("trimEnd", 0) => Value::string(text.trim_end()),
+		("toUpperCase", 0) => Value::string(text.to_uppercase()),
+		("toLowerCase", 0) => Value::string(text.to_lowercase()),
+		("chars", 0) => Value::array(text.chars().map(|char| Value::string(char.to_string())).collect()),
+		("contains", 1) => Value::Bool(text.contains(&*string(0)?)),
+		("startsWith", 1) => Value::Bool(text.starts_with(&*string(0)?)),
+		("endsWith", 1) => Value::Bool(text.ends_with(&*string(0)?)),
+		("indexOf", 1) => Value::Int(text.find(&*string(0)?).map_or(-1, |at| text[..at].chars().count() as i64)),
+		// Into characters when split on nothing, as `chars()` does
+		("split", 1) => match &*string(0)? {
+			"" => strings(text.char_indices().map(|(at, char)| &text[at..at + char.len_utf8()]).collect()),
+			separator => strings(text.split(separator).collect())
+		},
+		("slice", 2) => {
+			let (start, end) = (int(0)?, int(1)?);
+			if start < 0 || end < start || end > length() {
+				return Err(format!("Cannot slice {}..{} of a String of length {}", start, end, length()));
+			}
+			Value::string(text.chars().skip(start as usize).take((end - start) as usize).collect::<String>())
+		}
+		("replace", 2) => {
+			let from = string(0)?;
+			if from.is_empty() {
+				return Err("Cannot replace an empty String".to_string());
+			}
+			Value::string(text.replace(&*from, &string(1)?))
+		}
+		// Up to the width in characters, with as much of `fill` repeated as
+		// it takes
+		("padStart" | "padEnd", 2) => {
+			let (width, fill) = (int(0)?, string(1)?);
+			if fill.is_empty() {
+				return Err("Cannot pad with an empty String".to_string());
+			}
+			let count = width.saturating_sub(length()).max(0) as usize;
+			let chars = fill.chars().count();
+			let rest = fill.char_indices().nth(count % chars).map_or(fill.len(), |(at, _)| at);
+			let Some(bytes) = (count / chars).checked_mul(fill.len()).and_then(|bytes| bytes.checked_add(rest + text.len())) else {
+				return Err(format!("Cannot pad a String to {} characters", width));
+			};
+			let mut padded = allocate(bytes)?;
+			if name == "padEnd" {
+				padded.push_str(text);
+			}
+			padded.extend(fill.chars().cycle().take(count));
+			if name == "padStart" {
+				padded.push_str(text);
+			}
+			Value::string(padded)
+		}
+		("repeat", 1) => {
+			let times = int(0)?;
+			let Some((times, bytes)) = usize::try_from(times).ok().and_then(|times| Some((times, text.len().checked_mul(times)?))) else {
+				return Err(format!("Cannot repeat a String {} times", times));
+			};
+			let mut repeated = allocate(bytes)?;
+			for _ in 0..times {
+				repeated.push_str(text);
+			}
+			Value::string(repeated)
+		}
+		_ => return Ok(None)
+	}))
+}
+
+// The methods that grow, shrink and search arrays. None for a method arrays
+// do not have.
+fn array_method(items: &RefCell<Vec<Value>>, name: &str, args: &[Value]) -> Result<Option<Value>, String> {
+	let position = |index: &Value, length: usize| match index {
+		Value::Int(index) => usize::try_from(*index).ok().filter(|&at| at <= length).ok_or_else(|| format!("Index {} is out of bounds for length {}", index, length)),
+		index => Err(format!("'{}' takes an Int, not '{}'", name, index.type_name()))
+	};
+	let mut items = items.borrow_mut();
+	let length = items.len();
+	Ok(Some(match (name, args) {
+		("clear", []) => {
+			items.clear();
+			Value::Null
+		}
+		("push", [item]) => {
+			push(&mut items, item.clone())?;
+			Value::Null
+		}
+		// At the end too, which is a push
+		("insert", [index, item]) => {
+			let at = position(index, length)?;
+			push(&mut items, item.clone())?;
+			items[at..].rotate_right(1);
+			Value::Null
+		}
+		("pop", []) => items.pop().unwrap_or(Value::Null),
+		("removeAt", [index]) => match position(index, length)? {
+			// One past the end, where only inserting can go
+			at if at == length => return Err(format!("Index {} is out of bounds for length {}", at, length)),
+			at => items.remove(at)
+		},
+		("remove", [item]) => match items.iter().position(|other| other == item) {
+			Some(at) => {
+				items.remove(at);
+				Value::Bool(true)
+			}
+			None => Value::Bool(false)
+		},
+		("contains", [item]) => Value::Bool(items.contains(item)),
+		("indexOf", [item]) => Value::Int(items.iter().position(|other| other == item).map_or(-1, |at| at as i64)),
+		_ => return Ok(None)
+	}))
+}
+
+fn map_method(map: &RefCell<Map>, name: &str, args: &[Value]) -> Option<Value> {
+	let mut map = map.borrow_mut();
+	Some(match (name, args) {
+		("clear", []) => {
+			map.clear();
+			Value::Null
+		}
+		("get", [key]) => map.get(key).cloned().unwrap_or(Value::Null),
+		("set", [key, value]) => {
+			map.insert(key.clone(), value.clone());
+			Value::Null
+		}
+		("remove", [key]) => map.remove(key).unwrap_or(Value::Null),
+		("containsKey", [key]) => Value::Bool(map.contains_key(key)),
+		("keys", []) => Value::array(map.keys().cloned().collect()),
+		("values", []) => Value::array(map.iter().map(|(_, value)| value.clone()).collect()),
+		_ => return None
+	})
+}
+
+fn set_method(set: &RefCell<Map>, name: &str, args: &[Value]) -> Option<Value> {
+	let mut set = set.borrow_mut();
+	Some(match (name, args) {
+		("clear", []) => {
+			set.clear();
+			Value::Null
+		}
+		("add", [element]) => Value::Bool(set.insert(element.clone(), Value::Null).is_none()),
+		("remove", [element]) => Value::Bool(set.remove(element).is_some()),
+		("contains", [element]) => Value::Bool(set.contains_key(element)),
+		("toArray", []) => Value::array(set.keys().cloned().collect()),
+		_ => return None
+	})
+}
+
+// `template.format(args)`: each `{}` in it replaced by the next of `args`,
+// and `{{` and `}}` by a brace, as long as there are as many of each.
+pub fn format(template: &str, args: &[String]) -> Result<String, String> {
+	let mut formatted = String::new();
+	let mut args = args.iter();
+	let mut used = 0;
+	let mut chars = template.chars().peekable();
+	while let Some(char) = chars.next() {
+		match (char, chars.peek()) {
+			('{', Some('{')) | ('}', Some('}')) => {
+				formatted.push(char);
+				chars.next();
+			}
+			('{', Some('}')) => {
+				chars.next();
+				used += 1;
+				formatted.push_str(args.next().ok_or_else(|| format!("The format String has more '{{}}' than the {} arguments given", used - 1))?);
+			}
+			('{' | '}', _) => return Err(format!("The format String has a lone '{}'; write '{}{}' for one", char, char, char)),
+			_ => formatted.push(char)
+		}
+	}
+	if args.len() > 0 {
+		return Err(format!("The format String has {} '{{}}', but {} arguments were given", used, used + args.len()));
+	}
+	Ok(formatted)
+}
+
+// Calling a native function with the wrong number of arguments.
+pub fn arity(native: &Native, given: usize) -> Result<(), String> {
+	match native.arity {
+		Some(arity) if arity != given => {
+			let (plural, were) = (if arity == 1 { "" } else { "s" }, if given == 1 { "was" } else { "were" });
+			Err(format!("'{}' expects {} argument{} but {} {} given", native.name, arity, plural, given, were))
+		}
+		_ => Ok(())
+	}
+}
+
+// How long `sleep(ms)` waits: no time at all for less than none.
+pub fn millis(value: &Value) -> Result<Duration, String> {
+	match value {
+		Value::Int(millis) => Ok(Duration::from_millis((*millis).max(0) as u64)),
+		value => Err(format!("'sleep' takes an Int, not '{}'", value.type_name()))
+	}
+}
+
+// `readFile(path)`. Text rather than a value, since the VM reads on a
+// thread of its own and values stay on the one that made them.
+pub fn read_file(path: &str) -> Result<String, String> {
+	fs::read_to_string(path).map_err(|err| format!("Cannot read '{}': {}", path, err))
+}
+
+// `readLine()`: the next line of standard input without its line break, or
+// none at the end of it.
+pub fn read_line() -> Result<Option<String>, String> {
+	let mut line = String::new();
+	match io::stdin().read_line(&mut line) {
+		Ok(0) => Ok(None),
+		Ok(_) => {
+			line.truncate(line.trim_end_matches(['\n', '\r']).len());
+			Ok(Some(line))
+		}
+		Err(err) => Err(format!("Cannot read standard input: {}", err))
+	}
+}
+
+// The values `for (key, value) of` binds an entry to: an array's elements,
+// or an object's fields.
+pub fn unpack(item: &Value, count: usize) -> Result<Vec<Value>, String> {
+	let parts: Vec<Value> = match item {
+		Value::Array(items) => items.borrow().clone(),
+		Value::Object(object) => object.fields.borrow().clone(),
+		_ => Vec::new()
+	};
+	if parts.len() < count {
+		return Err(format!("Cannot take {} values out of '{}'", count, item.type_name()));
+	}
+	Ok(parts.into_iter().take(count).collect())
+}
+
+// What a value looks like printed or added to a string. `custom` is asked
+// first about every value, nested ones included, and says what objects with
+// a `toString()` and functions look like; otherwise enum variants are their
+// name, and structs and data classes show their fields.
+pub fn stringify<E>(value: &Value, custom: &mut dyn FnMut(&Value) -> Result<Option<String>, E>) -> Result<String, E> {
+	if let Some(text) = custom(value)? {
+		return Ok(text);
+	}
+	Ok(match value {
+		Value::Null => "null".to_string(),
+		Value::Bool(value) => value.to_string(),
+		Value::Int(value) => value.to_string(),
+		Value::Float(value) => format!("{:?}", value),
+		Value::String(text) => text.to_string(),
+		Value::Range(start, end) => format!("{}..{}", start, end),
+		Value::Array(items) => {
+			let items = items.borrow().clone();
+			let items = items.iter().map(|item| stringify(item, custom)).collect::<Result<Vec<String>, E>>()?;
+			format!("[{}]", items.join(", "))
+		}
+		Value::Map(map) => {
+			let entries: Vec<(Value, Value)> = map.borrow().iter().cloned().collect();
+			let mut parts = Vec::new();
+			for (key, value) in &entries {
+				parts.push(format!("{}: {}", stringify(key, custom)?, stringify(value, custom)?));
+			}
+			format!("{{{}}}", parts.join(", "))
+		}
+		Value::Set(set) => {
+			let elements: Vec<Value> = set.borrow().keys().cloned().collect();
+			let elements = elements.iter().map(|element| stringify(element, custom)).collect::<Result<Vec<String>, E>>()?;
+			format!("{{{}}}", elements.join(", "))
+		}
+		Value::Object(object) => {
+			let class = &object.class;
+			if let Some(variant) = object.variant {
+				return Ok(class.variants[variant].clone());
+			}
+			if !class.by_value {
+				return Ok(class.name.clone());
+			}
+			let fields = object.fields.borrow().clone();
+			let mut parts = Vec::new();
+			for (name, value) in class.fields.iter().zip(&fields) {
+				parts.push(format!("{}={}", name, stringify(value, custom)?));
+			}
+			format!("{}({})", class.name, parts.join(", "))
+		}
+		Value::Native(native) => format!("<fn {}>", native.name),
+		Value::Builtin(builtin) => format!("<fn {}>", builtin.name()),
+		Value::Function(_) => "<fn>".to_string(),
+		Value::Iterator(_) => "<iterator>".to_string(),
+		Value::Random(_) => "<random>".to_string(),
+		Value::Channel(_) => "<channel>".to_string(),
+		Value::Pointer(pointer) => format!("<pointer {:#x}>", pointer),
+		Value::Tagged(Tag::None, _) => "None".to_string(),
+		Value::Tagged(tag, value) => format!("{}({})", tag.name(), stringify(value, custom)?),
+		Value::Task(_) => "<task>".to_string()
+	})
+}
+
+// What a value looks like printed, without calling any `toString()`, for
+// errors that show one.
+pub fn plain(value: &Value) -> String {
+	stringify(value, &mut |_| Ok::<_, ()>(None)).unwrap_or_default()
+}
+
+// Where a `for` loop is in what it loops over.
+#[derive(Debug)]
+pub enum Cursor {
+	Array { items: Rc<RefCell<Vec<Value>>>, index: usize, entries: bool },
+	Range { next: i64, end: i64 },
+	Chars { chars: Vec<char>, index: usize, entries: bool },
+	// Anything with `hasNext()` and `next()`, which the program runs
+	Iterator(Value),
+	// A file, a line at a time, as `fs.lines` reads it
+	Lines { path: String, reader: BufReader<File> }
+}
+
+impl Cursor {
+	// Arrays, ranges, maps, sets and strings are walked directly, `for of` giving
+	// `[index, value]` pairs. Objects give none here: they are walked through
+	// their `iterator()`, or for `of` their `entries()`.
+	pub fn of(value: Value, entries: bool) -> Result<Option<Cursor>, String> {
+		Ok(Some(match value {
+			Value::Array(items) => Cursor::Array { items, index: 0, entries },
+			Value::Range(start, end) if !entries => Cursor::Range { next: start, end },
+			// Keys for `in`, and for `of` key and value; changes made while
+			// looping are not seen
+			Value::Map(map) => {
+				let items = map.borrow().iter().map(|(key, value)| if entries { Value::array(vec![key.clone(), value.clone()]) } else { key.clone() }).collect();
+				Cursor::Array { items: Rc::new(RefCell::new(items)), index: 0, entries: false }
+			}
+			// Likewise a copy of the elements
+			Value::Set(set) => {
+				let items = set.borrow().keys().cloned().collect();
+				Cursor::Array { items: Rc::new(RefCell::new(items)), index: 0, entries }
+			}
+			Value::String(text) => Cursor::Chars { chars: text.chars().collect(), index: 0, entries },
+			Value::Iterator(_) if !entries => Cursor::Iterator(value),
+			Value::Object(_) => return Ok(None),
+			value => return Err(format!("'{}' is not iterable", value.type_name()))
+		}))
+	}
+
+	// The next item, or none at the end. Iterators are advanced by whoever
+	// runs the program.
+	pub fn step(&mut self) -> Result<Option<Value>, String> {
+		let pair = |index: usize, value: Value| Value::array(vec![Value::Int(index as i64), value]);
+		match self {
+			Cursor::Array { items, index, entries } => {
+				let Some(item) = items.borrow().get(*index).cloned() else {
+					return Ok(None);
+				};
+				*index += 1;
+				Ok(Some(if *entries { pair(*index - 1, item) } else { item }))
+			}
+			Cursor::Range { next, end } => {
+				if next >= end {
+					return Ok(None);
+				}
+				*next += 1;
+				Ok(Some(Value::Int(*next - 1)))
+			}
+			Cursor::Chars { chars, index, entries } => {
+				let Some(c) = chars.get(*index) else {
+					return Ok(None);
+				};
+				*index += 1;
+				let item = Value::string(c.to_string());
+				Ok(Some(if *entries { pair(*index - 1, item) } else { item }))
+			}
+			Cursor::Iterator(_) => Ok(None),
+			Cursor::Lines { path, reader } => {
+				let mut line = String::new();
+				match reader.read_line(&mut line) {
+					Ok(0) => Ok(None),
+					Ok(_) => {
+						line.truncate(line.trim_end_matches(['\n', '\r']).len());
+						Ok(Some(Value::string(line)))
+					}
+					Err(err) => Err(format!("Cannot read '{}': {}", path, err))
+				}
+			}
+		}
+	}
+
+	// What it walks, for the collector to find through it.
+	pub fn walking(&self) -> Option<Value> {
+		match self {
+			Cursor::Array { items, .. } => Some(Value::Array(items.clone())),
+			Cursor::Iterator(iterator) => Some(iterator.clone()),
+			Cursor::Range { .. } | Cursor::Chars { .. } | Cursor::Lines { .. } => None
+		}
+	}
+}
+
+// What an `Iterator` value goes through: what `map`, `filter`, `zip` and
+// `enumerate` make, and what `iterator()` makes of anything a `for` loop can
+// go through. Working out the next element can call into the program, so
+// `stage` only says what it takes, and whoever runs the program does it.
+#[derive(Debug)]
+pub struct Sequence {
+	source: Source,
+	// The next element, or the end, once `hasNext()` has looked for it
+	peeked: Option<Option<Value>>
+}
+
+#[derive(Debug)]
+pub enum Source {
+	Cursor(Cursor),
+	// The sequence it is made from, and the function given for it
+	Map(Rc<RefCell<Sequence>>, Value),
+	Filter(Rc<RefCell<Sequence>>, Value),
+	Zip(Rc<RefCell<Sequence>>, Rc<RefCell<Sequence>>),
+	// With the index of the next element
+	Enumerate(Rc<RefCell<Sequence>>, i64),
+	// What a call of a generator function yields
+	Generator(Rc<Generator>)
+}
+
+// What it takes to work out the next element of a sequence.
+pub enum Stage {
+	Ready(Option<Value>),
+	// Calling `hasNext()`, and then `next()` if there is one
+	Protocol(Value),
+	// The next element of the sequence, passed to the function
+	Map(Rc<RefCell<Sequence>>, Value),
+	// The next element of the sequence the function says `true` of
+	Filter(Rc<RefCell<Sequence>>, Value),
+	// The next element of each, as a pair
+	Zip(Rc<RefCell<Sequence>>, Rc<RefCell<Sequence>>),
+	// The next element of the sequence, paired after its index
+	Enumerate(Rc<RefCell<Sequence>>, i64),
+	// Going on with the generator to its next `yield`
+	Resume(Rc<Generator>)
+}
+
+impl Sequence {
+	pub fn new(source: Source) -> Rc<RefCell<Sequence>> {
+		Rc::new(RefCell::new(Sequence { source, peeked: None }))
+	}
+
+	pub fn stage(&mut self) -> Result<Stage, String> {
+		if let Some(peeked) = self.peeked.take() {
+			return Ok(Stage::Ready(peeked));
+		}
+		Ok(match &mut self.source {
+			Source::Cursor(Cursor::Iterator(iterator)) => Stage::Protocol(iterator.clone()),
+			Source::Cursor(cursor) => Stage::Ready(cursor.step()?),
+			Source::Map(inner, function) => Stage::Map(inner.clone(), function.clone()),
+			Source::Filter(inner, test) => Stage::Filter(inner.clone(), test.clone()),
+			Source::Zip(first, second) => Stage::Zip(first.clone(), second.clone()),
+			Source::Enumerate(inner, index) => {
+				*index += 1;
+				Stage::Enumerate(inner.clone(), *index - 1)
+			}
+			Source::Generator(generator) => Stage::Resume(generator.clone())
+		})
+	}
+
+	// Keeps what `hasNext()` found, for the next element asked for.
+	pub fn peeked(&mut self, next: Option<Value>) {
+		self.peeked = Some(next);
+	}
+
+	// What it holds, for the collector to find through it.
+	pub fn values(&self) -> Vec<Value> {
+		let mut values: Vec<Value> = self.peeked.clone().flatten().into_iter().collect();
+		match &self.source {
+			Source::Cursor(cursor) => values.extend(cursor.walking()),
+			Source::Map(inner, function) | Source::Filter(inner, function) => {
+				values.push(Value::Iterator(inner.clone()));
+				values.push(function.clone());
+			}
+			Source::Zip(first, second) => values.extend([Value::Iterator(first.clone()), Value::Iterator(second.clone())]),
+			Source::Enumerate(inner, _) => values.push(Value::Iterator(inner.clone())),
+			// What the generator holds is on its stacks, which the VM marks
+			Source::Generator(_) => {}
+		}
+		values
+	}
+}
+
+// Whether `name` is a method of sequences taking that many arguments (see
+// `builtins::sequence_member`) that `value` has: anything a `for` loop can
+// go through but strings, and objects only with an `iterator()`.
+pub fn is_sequence_method(value: &Value, name: &str, args: usize) -> bool {
+	let arity = match name {
+		"iterator" | "toArray" | "enumerate" => 0,
+		"map" | "filter" | "any" | "all" | "zip" => 1,
+		"reduce" => 2,
+		_ => return false
+	};
+	let iterable = match value {
+		Value::Array(_) | Value::Map(_) | Value::Set(_) | Value::Range(..) | Value::Iterator(_) => true,
+		Value::Object(object) => object.class.method(ITERATOR_METHOD, Some(0)).is_some(),
+		_ => false
+	};
+	arity == args && iterable
+}
@@ -0,0 +1,607 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use crate::interpreter::limits::Limit;
+use crate::interpreter::ops::Sequence;
+use crate::interpreter::{StackFrame, gc};
+use crate::sema::builtins::Builtin;
+use crate::sema::types::{FnId, LocalId, TypeId};
+
+// What a running program works with, shared by the interpreter and
+// everything that runs beside it, like the standard library's native
+// functions.
+//
+// Strings, arrays, maps, sets, objects and functions are behind `Rc`, so
+// cloning a value is cheap whatever it holds, and two clones of an array,
+// map, set or object are the same one: changing it through either is seen
+// through both. Arrays, maps, sets, objects and cells are made through the
+// functions here, so the heap in `gc` can free the cycles between them.
+//
+// Equality is the same everywhere a program compares values, for `==`,
+// `in`, `case`, map keys and set elements:
+//
+// - Numbers are equal when they are the same number, an `Int` and a `Float`
+//   included, so `1 == 1.0`. NaN is equal to nothing, not even itself,
+//   except as a map key, where it is the same key as any other NaN.
+// - Strings are equal when they have the same text, ranges the same bounds,
+//   and `null` is only equal to `null`.
+// - Objects of structs and data classes are equal when they are of the same
+//   class and their fields are equal, and results and options when they are
+//   the same variant of one holding equal values.
+// - Anything else, other objects, enum variants, arrays, maps, sets,
+//   functions, iterators, generators, channels and tasks, is only equal to
+//   itself.
+//
+// Hashing agrees with equality: values that are equal hash the same, so
+// any value can be a map key. Changing a struct or data class object that is
+// a key changes what it is equal to, and the map loses track of it.
+#[derive(Debug, Clone)]
+pub enum Value {
+	Null,
+	Bool(bool),
+	Int(i64),
+	Float(f64),
+	String(Rc<str>),
+	Array(Rc<RefCell<Vec<Value>>>),
+	Map(Rc<RefCell<Map>>),
+	// A map of its elements to null
+	Set(Rc<RefCell<Map>>),
+	// Half-open `start..end`
+	Range(i64, i64),
+	Object(Rc<Object>),
+	Function(Rc<Closure>),
+	Native(Rc<Native>),
+	Builtin(Builtin),
+	Iterator(Rc<RefCell<Sequence>>),
+	// A `Result` or an `Option`, which `None` makes holding null
+	Tagged(Tag, Rc<Value>),
+	Random(Rc<Random>),
+	// An address a C function returned, passed back to others as it is
+	Pointer(usize),
+	Channel(Rc<Channel>),
+	// What calling an async function or builtin gives on the VM, for
+	// `await` to wait for
+	Task(Rc<Task>)
+}
+
+impl Value {
+	pub fn string(text: impl Into<Rc<str>>) -> Value {
+		Value::String(text.into())
+	}
+
+	pub fn array(items: Vec<Value>) -> Value {
+		let array = Rc::new(RefCell::new(items));
+		gc::track_array(&array);
+		Value::Array(array)
+	}
+
+	pub fn map(map: Map) -> Value {
+		let map = Rc::new(RefCell::new(map));
+		gc::track_map(&map);
+		Value::Map(map)
+	}
+
+	pub fn set(elements: impl IntoIterator<Item = Value>) -> Value {
+		let mut set = Map::new();
+		for element in elements {
+			set.insert(element, Value::Null);
+		}
+		let set = Rc::new(RefCell::new(set));
+		gc::track_map(&set);
+		Value::Set(set)
+	}
+
+	pub fn tagged(tag: Tag, value: Value) -> Value {
+		Value::Tagged(tag, Rc::new(value))
+	}
+
+	pub fn is_null(&self) -> bool {
+		matches!(self, Value::Null)
+	}
+
+	// Only `Bool`s get here once checked, but a dynamic one may not be.
+	pub fn truthy(&self) -> bool {
+		matches!(self, Value::Bool(true))
+	}
+
+	// What `typeof` says, and what errors call the type of a value.
+	pub fn type_name(&self) -> &str {
+		match self {
+			Value::Null => "Null",
+			Value::Bool(_) => "Bool",
+			Value::Int(_) => "Int",
+			Value::Float(_) => "Float",
+			Value::String(_) => "String",
+			Value::Array(_) => "Array",
+			Value::Map(_) => "Map",
+			Value::Set(_) => "Set",
+			Value::Range(..) => "Range",
+			Value::Object(object) => &object.class.name,
+			Value::Function(_) | Value::Native(_) | Value::Builtin(_) => "Function",
+			Value::Iterator(_) => "Iterator",
+			Value::Tagged(Tag::Ok | Tag::Err, _) => "Result",
+			Value::Tagged(Tag::Some | Tag::None, _) => "Option",
+			Value::Random(_) => "Random",
+			Value::Pointer(_) => "Pointer",
+			Value::Channel(_) => "Channel",
+			Value::Task(_) => "Task"
+		}
+	}
+}
+
+impl PartialEq for Value {
+	fn eq(&self, other: &Value) -> bool {
+		equal(self, other, false)
+	}
+}
+
+fn equal(left: &Value, right: &Value, keys: bool) -> bool {
+	match (left, right) {
+		(Value::Null, Value::Null) => true,
+		(Value::Bool(left), Value::Bool(right)) => left == right,
+		(Value::Int(left), Value::Int(right)) => left == right,
+		(Value::Float(left), Value::Float(right)) => left == right || (keys && left.is_nan() && right.is_nan()),
+		(Value::Int(int), Value::Float(float)) | (Value::Float(float), Value::Int(int)) => whole(*float) == Some(*int),
+		(Value::String(left), Value::String(right)) => left == right,
+		(Value::Range(start, end), Value::Range(other_start, other_end)) => (start, end) == (other_start, other_end),
+		(Value::Array(left), Value::Array(right)) => Rc::ptr_eq(left, right),
+		(Value::Map(left), Value::Map(right)) | (Value::Set(left), Value::Set(right)) => Rc::ptr_eq(left, right),
+		(Value::Object(left), Value::Object(right)) => {
+			Rc::ptr_eq(left, right)
+				|| (left.class.by_value
+					&& Rc::ptr_eq(&left.class, &right.class)
+					&& left.fields.borrow().iter().zip(right.fields.borrow().iter()).all(|(left, right)| equal(left, right, keys)))
+		}
+		(Value::Function(left), Value::Function(right)) => Rc::ptr_eq(left, right),
+		(Value::Native(left), Value::Native(right)) => Rc::ptr_eq(left, right),
+		(Value::Builtin(left), Value::Builtin(right)) => left == right,
+		(Value::Iterator(left), Value::Iterator(right)) => Rc::ptr_eq(left, right),
+		(Value::Tagged(left_tag, left), Value::Tagged(right_tag, right)) => left_tag == right_tag && equal(left, right, keys),
+		(Value::Random(left), Value::Random(right)) => Rc::ptr_eq(left, right),
+		(Value::Pointer(left), Value::Pointer(right)) => left == right,
+		(Value::Channel(left), Value::Channel(right)) => Rc::ptr_eq(left, right),
+		(Value::Task(left), Value::Task(right)) => Rc::ptr_eq(left, right),
+		_ => false
+	}
+}
+
+// The `Int` a `Float` is equal to, if it is a whole number in range.
+fn whole(float: f64) -> Option<i64> {
+	(float.fract() == 0.0 && (i64::MIN as f64..-(i64::MIN as f64)).contains(&float)).then_some(float as i64)
+}
+
+fn hash<H: Hasher>(value: &Value, state: &mut H) {
+	let address = |pointer: *const ()| pointer as usize;
+	match value {
+		Value::Null => 0u8.hash(state),
+		Value::Bool(value) => (1u8, value).hash(state),
+		Value::Int(value) => (2u8, value).hash(state),
+		// Whole numbers hash as the `Int` they are equal to
+		Value::Float(value) => match whole(*value) {
+			Some(int) => (2u8, int).hash(state),
+			None if value.is_nan() => (3u8, f64::NAN.to_bits()).hash(state),
+			None => (3u8, value.to_bits()).hash(state)
+		},
+		Value::String(text) => (4u8, text).hash(state),
+		Value::Range(start, end) => (5u8, start, end).hash(state),
+		Value::Object(object) if object.class.by_value => {
+			(6u8, object.class.id).hash(state);
+			for field in object.fields.borrow().iter() {
+				hash(field, state);
+			}
+		}
+		Value::Object(object) => (7u8, address(Rc::as_ptr(object).cast())).hash(state),
+		Value::Array(items) => (7u8, address(Rc::as_ptr(items).cast())).hash(state),
+		Value::Map(map) | Value::Set(map) => (7u8, address(Rc::as_ptr(map).cast())).hash(state),
+		Value::Function(closure) => (7u8, address(Rc::as_ptr(closure).cast())).hash(state),
+		Value::Native(native) => (7u8, address(Rc::as_ptr(native).cast())).hash(state),
+		Value::Iterator(sequence) => (7u8, address(Rc::as_ptr(sequence).cast())).hash(state),
+		Value::Random(random) => (7u8, address(Rc::as_ptr(random).cast())).hash(state),
+		Value::Pointer(pointer) => (7u8, pointer).hash(state),
+		Value::Channel(channel) => (7u8, address(Rc::as_ptr(channel).cast())).hash(state),
+		Value::Task(task) => (7u8, address(Rc::as_ptr(task).cast())).hash(state),
+		Value::Builtin(builtin) => (8u8, builtin.name()).hash(state),
+		Value::Tagged(tag, value) => {
+			(9u8, *tag as u8).hash(state);
+			hash(value, state);
+		}
+	}
+}
+
+// Which of its variants a `Result` or an `Option` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tag {
+	Ok,
+	Err,
+	Some,
+	None
+}
+
+impl Tag {
+	pub fn name(self) -> &'static str {
+		match self {
+			Tag::Ok => "Ok",
+			Tag::Err => "Err",
+			Tag::Some => "Some",
+			Tag::None => "None"
+		}
+	}
+
+	// `Ok` and `Some`, which have the value `?` and `unwrap()` take out.
+	pub fn has_value(self) -> bool {
+		matches!(self, Tag::Ok | Tag::Some)
+	}
+}
+
+// Values sent by tasks for others to receive, in the order they were sent.
+// Tasks take turns on one thread, so a channel is never used by two at once;
+// what is sent is the value itself, not a copy, as when it is passed to a
+// function.
+#[derive(Debug, Default)]
+pub struct Channel {
+	pub items: RefCell<VecDeque<Value>>,
+	pub closed: Cell<bool>
+}
+
+impl Channel {
+	pub fn send(&self, value: Value) -> Result<(), String> {
+		if self.closed.get() {
+			return Err("Cannot send on a closed channel".to_string());
+		}
+		self.items.borrow_mut().push_back(value);
+		Ok(())
+	}
+
+	// The next value sent, or null when there is none and never will be.
+	// None when there is none yet, to wait for.
+	pub fn receive(&self) -> Option<Value> {
+		match self.items.borrow_mut().pop_front() {
+			Some(value) => Some(value),
+			None => self.closed.get().then_some(Value::Null)
+		}
+	}
+}
+
+// A generator of pseudo-random numbers, SplitMix64: fast and even enough
+// for games and tests, though not for secrets. The same seed gives the same
+// numbers on every platform.
+#[derive(Debug)]
+pub struct Random {
+	state: Cell<u64>
+}
+
+impl Random {
+	pub fn new(seed: i64) -> Random {
+		Random { state: Cell::new(seed as u64) }
+	}
+
+	// Starts over, as if made with `seed`.
+	pub fn reseed(&self, seed: i64) {
+		self.state.set(seed as u64);
+	}
+
+	pub fn next(&self) -> u64 {
+		let state = self.state.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+		self.state.set(state);
+		let mut z = state;
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+		z ^ (z >> 31)
+	}
+
+	// Below `bound`, each as likely: what would favor the low ones is drawn
+	// again.
+	fn below(&self, bound: u64) -> u64 {
+		let skipped = (u64::MAX % bound + 1) % bound;
+		loop {
+			let next = self.next();
+			if next <= u64::MAX - skipped {
+				return next % bound;
+			}
+		}
+	}
+
+	// From `low` to `high`, both included.
+	pub fn int(&self, low: i64, high: i64) -> Result<i64, String> {
+		if low > high {
+			return Err(format!("Cannot pick an Int from {} to {}; the lower bound is greater", low, high));
+		}
+		let span = high.wrapping_sub(low) as u64;
+		let offset = if span == u64::MAX { self.next() } else { self.below(span + 1) };
+		Ok(low.wrapping_add(offset as i64))
+	}
+
+	// From 0 up to 1, not including 1.
+	pub fn float(&self) -> f64 {
+		(self.next() >> 11) as f64 / (1u64 << 53) as f64
+	}
+
+	pub fn choice(&self, items: &[Value]) -> Result<Value, String> {
+		if items.is_empty() {
+			return Err("Cannot choose from an empty array".to_string());
+		}
+		Ok(items[self.below(items.len() as u64) as usize].clone())
+	}
+
+	pub fn shuffle(&self, items: &mut [Value]) {
+		for index in (1..items.len()).rev() {
+			items.swap(index, self.below(index as u64 + 1) as usize);
+		}
+	}
+}
+
+// A value used as a map key: equal and hashed as described on `Value`.
+#[derive(Debug, Clone)]
+pub struct Key(pub Value);
+
+impl PartialEq for Key {
+	fn eq(&self, other: &Key) -> bool {
+		equal(&self.0, &other.0, true)
+	}
+}
+
+impl Eq for Key {}
+
+impl Hash for Key {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		hash(&self.0, state);
+	}
+}
+
+// Entries in the order their keys were first inserted, found by key through
+// an index.
+#[derive(Debug, Default)]
+pub struct Map {
+	entries: Vec<(Value, Value)>,
+	index: HashMap<Key, usize>
+}
+
+impl Map {
+	pub fn new() -> Map {
+		Map::default()
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	pub fn get(&self, key: &Value) -> Option<&Value> {
+		let index = self.index.get(&Key(key.clone()))?;
+		Some(&self.entries[*index].1)
+	}
+
+	pub fn contains_key(&self, key: &Value) -> bool {
+		self.index.contains_key(&Key(key.clone()))
+	}
+
+	// Sets the value of `key`, keeping its place if it was there already,
+	// and returns the value it had.
+	pub fn insert(&mut self, key: Value, value: Value) -> Option<Value> {
+		match self.index.get(&Key(key.clone())) {
+			Some(&index) => Some(std::mem::replace(&mut self.entries[index].1, value)),
+			None => {
+				self.index.insert(Key(key.clone()), self.entries.len());
+				self.entries.push((key, value));
+				None
+			}
+		}
+	}
+
+	// Removes `key`, keeping the order of the rest, and returns its value.
+	pub fn remove(&mut self, key: &Value) -> Option<Value> {
+		let index = self.index.remove(&Key(key.clone()))?;
+		let (_, value) = self.entries.remove(index);
+		for later in self.index.values_mut() {
+			if *later > index {
+				*later -= 1;
+			}
+		}
+		Some(value)
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &(Value, Value)> {
+		self.entries.iter()
+	}
+
+	pub fn keys(&self) -> impl Iterator<Item = &Value> {
+		self.entries.iter().map(|(key, _)| key)
+	}
+
+	pub fn clear(&mut self) {
+		self.entries.clear();
+		self.index.clear();
+	}
+}
+
+// What every object of a class shares: the header objects point to.
+#[derive(Debug)]
+pub struct Class {
+	pub id: TypeId,
+	pub name: String,
+	// Structs and data classes, compared and hashed by their fields
+	pub by_value: bool,
+	// Of its own fields and those it inherits, superclasses' first; objects
+	// store their values in this order
+	pub fields: Vec<String>,
+	// Of an enum, in order
+	pub variants: Vec<String>,
+	// Every class and interface it inherits from, however far up
+	pub supertypes: Vec<TypeId>,
+	// The method table: by name, the method with a body objects of the class
+	// run for each number of parameters, their own or the nearest inherited
+	pub methods: HashMap<String, Vec<(usize, FnId)>>
+}
+
+impl Class {
+	// Where an object of the class stores the field `name`.
+	pub fn field(&self, name: &str) -> Option<usize> {
+		self.fields.iter().position(|field| field == name)
+	}
+
+	// The method `name` objects of the class run, taking `arity` parameters
+	// if given.
+	pub fn method(&self, name: &str, arity: Option<usize>) -> Option<FnId> {
+		let methods = self.methods.get(name)?;
+		methods.iter().find(|(params, _)| arity.is_none_or(|arity| *params == arity)).map(|(_, method)| *method)
+	}
+
+	// Whether objects of the class are of `class`, for `is`.
+	pub fn is(&self, class: TypeId) -> bool {
+		self.id == class || self.supertypes.contains(&class)
+	}
+}
+
+// An instance of a class, struct or enum.
+#[derive(Debug)]
+pub struct Object {
+	pub class: Rc<Class>,
+	// In the order of `class.fields`
+	pub fields: RefCell<Vec<Value>>,
+	// Which of an enum's variants this is
+	pub variant: Option<usize>
+}
+
+impl Object {
+	pub fn new(class: Rc<Class>, fields: Vec<Value>, variant: Option<usize>) -> Rc<Object> {
+		let object = Rc::new(Object { class, fields: RefCell::new(fields), variant });
+		gc::track_object(&object);
+		object
+	}
+}
+
+// A function as a value. Lambdas and local functions keep the locals they
+// capture, in the order `Captures` gives them; named functions and methods
+// need none. Methods used as values keep the object they were taken from as
+// `this`.
+#[derive(Debug)]
+pub struct Closure {
+	pub function: FnId,
+	pub captures: Vec<Slot>,
+	pub this: Option<Value>
+}
+
+// A function written in Rust. It gets the arguments, whose count has been
+// checked against `arity` when that is given, and returns the result or the
+// message of the error to throw.
+pub struct Native {
+	pub name: String,
+	pub arity: Option<usize>,
+	pub function: Box<NativeFn>
+}
+
+pub type NativeFn = dyn Fn(&[Value]) -> Result<Value, String>;
+
+impl fmt::Debug for Native {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Native({})", self.name)
+	}
+}
+
+// A call of a generator function, which the VM runs on stacks of its own,
+// kept by `id`, a `yield` at a time as its iterator is gone through.
+#[derive(Debug)]
+pub struct Generator {
+	pub id: u64
+}
+
+// An async call, or a timer or read started by a builtin, which the VM's
+// event loop runs to the end while other code goes on.
+#[derive(Debug, Default)]
+pub struct Task {
+	pub state: RefCell<TaskState>
+}
+
+#[derive(Debug, Clone, Default)]
+pub enum TaskState {
+	#[default]
+	Running,
+	Done(Value),
+	Failed(Thrown)
+}
+
+// What was thrown, with the calls it was thrown through, innermost first,
+// and how many more there were than the trace keeps.
+#[derive(Debug, Clone)]
+pub struct Thrown {
+	pub value: Value,
+	pub trace: Vec<StackFrame>,
+	pub hidden: usize,
+	// Thrown for a run-time error, with the message as the value
+	pub error: bool,
+	// Thrown for going over a limit, which nothing catches
+	pub limit: Option<Limit>
+}
+
+// Where a local is kept: in the frame itself, or in a cell shared with the
+// closures that capture it.
+#[derive(Debug, Clone)]
+pub enum Slot {
+	Value(Value),
+	Cell(Rc<RefCell<Value>>)
+}
+
+impl Slot {
+	// A cell of its own holding `value`.
+	pub fn cell(value: Value) -> Slot {
+		let cell = Rc::new(RefCell::new(value));
+		gc::track_cell(&cell);
+		Slot::Cell(cell)
+	}
+}
+
+// The locals of one call. Every local has its own id, so a call needs only
+// one frame however many blocks its body has.
+#[derive(Debug, Default)]
+pub struct Frame {
+	slots: HashMap<LocalId, Slot>
+}
+
+impl Frame {
+	pub fn new() -> Frame {
+		Frame::default()
+	}
+
+	// A frame for a closure's body, starting with what it captured of
+	// `locals`.
+	pub fn captured(locals: &[LocalId], captures: &[Slot]) -> Frame {
+		Frame { slots: locals.iter().copied().zip(captures.iter().cloned()).collect() }
+	}
+
+	pub fn get(&self, local: LocalId) -> Option<Value> {
+		match self.slots.get(&local)? {
+			Slot::Value(value) => Some(value.clone()),
+			Slot::Cell(cell) => Some(cell.borrow().clone())
+		}
+	}
+
+	// Sets the local where it is kept, in its cell if it has one.
+	pub fn set(&mut self, local: LocalId, value: Value) {
+		match self.slots.get_mut(&local) {
+			Some(Slot::Cell(cell)) => *cell.borrow_mut() = value,
+			Some(Slot::Value(slot)) => *slot = value,
+			None => {
+				self.slots.insert(local, Slot::Value(value));
+			}
+		}
+	}
+
+	// Binds the local afresh, in a new cell when it is `boxed`, so closures
+	// made before keep the one they captured: each time round a loop has
+	// its own.
+	pub fn define(&mut self, local: LocalId, value: Value, boxed: bool) {
+		let slot = if boxed { Slot::cell(value) } else { Slot::Value(value) };
+		self.slots.insert(local, slot);
+	}
+
+	// What a closure made now captures of the local.
+	pub fn capture(&self, local: LocalId) -> Option<Slot> {
+		self.slots.get(&local).cloned()
+	}
+}
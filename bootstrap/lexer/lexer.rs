@@ -1,6 +1,5 @@
-use std::fmt::Error;
-
-use crate::lexer::token::{Token, TokenType};
+use crate::diagnostics::Diagnostic;
+use crate::lexer::token::{Span, Token, TokenType};
 
 pub struct Lexer {
     source: String,
@@ -8,6 +7,10 @@ pub struct Lexer {
     current: usize,
     line: i64,
     column: i64,
+    start_line: i64,
+    start_column: i64,
+    // Open `${` interpolations: the string's quote and the brace depth inside it
+    interpolations: Vec<(u8, usize)>,
     tokens: Vec<Token>
 }
 
@@ -19,41 +22,77 @@ impl Lexer {
 			current: 0,
 			line: 1,
 			column: 1,
+			start_line: 1,
+			start_column: 1,
+			interpolations: Vec::new(),
 			tokens: Vec::new()
 		}
 	}
 
-	pub fn lex(&mut self) -> Result<&Vec<Token>, String> {
+	// Lexes a fragment that begins at `line`/`column` of a larger file. Byte
+	// offsets in the resulting spans stay relative to the fragment.
+	pub fn at(source: String, line: i64, column: i64) -> Self {
+		Lexer {
+			line,
+			column,
+			start_line: line,
+			start_column: column,
+			..Lexer::new(source)
+		}
+	}
+
+	pub fn lex(&mut self) -> Result<&Vec<Token>, Box<Diagnostic>> {
 
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column;
             self.scan_tokens()?;
         }
 
+        if !self.interpolations.is_empty() {
+            self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column;
+            return Err(self.error("E0002", "Unterminated string interpolation".to_string()));
+        }
+
         self.tokens.push(Token {
             token_type: TokenType::Eof,
             lexeme: String::new(),
             line: self.line,
             column: self.column,
+            span: Span {
+                start: self.current,
+                end: self.current,
+                line: self.line,
+                column: self.column,
+            },
         });
 
         Ok(&self.tokens)
     }
 
-	fn add_token(&mut self, token_type: TokenType) -> Result<(), String> {
+	fn add_token(&mut self, token_type: TokenType) -> Result<(), Box<Diagnostic>> {
         let text = &self.source[self.start..self.current];
 
         self.tokens.push(Token {
             token_type,
             lexeme: text.to_string(),
-            line: self.line,
-            column: self.column,
+            line: self.start_line,
+            column: self.start_column,
+            span: Span {
+                start: self.start,
+                end: self.current,
+                line: self.start_line,
+                column: self.start_column,
+            },
         });
 
         Ok(())
     }
 
-    pub fn scan_tokens(&mut self) -> Result<(), String> {
+    pub fn scan_tokens(&mut self) -> Result<(), Box<Diagnostic>> {
         let c = self.advance();
 
         match c {
@@ -66,12 +105,25 @@ impl Lexer {
 
             b'/' => {
                 if self.match_char(b'/') {
+                    let doc = self.peek() == b'/' && self.peek_next() != b'/';
                     while !self.is_at_end() && self.peek() != b'\n' {
                         self.advance();
                     }
+                    if doc {
+                        let text = self.source[self.start + 3..self.current].trim().to_string();
+                        return self.emit(TokenType::DocComment, text);
+                    }
                     Ok(())
                 } else if self.match_char(b'*') {
-                    self.block_comment()
+                    let doc = self.peek() == b'*' && self.peek_next() != b'/';
+                    self.block_comment()?;
+                    if doc {
+                        let text = doc_block(&self.source[self.start + 3..self.current - 2]);
+                        return self.emit(TokenType::DocComment, text);
+                    }
+                    Ok(())
+                } else if self.match_char(b'=') {
+                    self.add_token(TokenType::SlashEqual)
                 } else {
                     self.add_token(TokenType::Slash)
                 }
@@ -81,18 +133,28 @@ impl Lexer {
 
             b'+' => {
                 if self.match_char(b'+') { self.add_token(TokenType::PlusPlus) }
+                else if self.match_char(b'=') { self.add_token(TokenType::PlusEqual) }
                 else { self.add_token(TokenType::Plus) }
             }
             b'-' => {
                 if self.match_char(b'-') { self.add_token(TokenType::MinusMinus) }
                 else if self.match_char(b'>') { self.add_token(TokenType::Arrow) }
+                else if self.match_char(b'=') { self.add_token(TokenType::MinusEqual) }
                 else { self.add_token(TokenType::Minus) }
             }
-            b'*' => self.add_token(TokenType::Star),
-            b'%' => self.add_token(TokenType::Percent),
+            b'*' => {
+                if self.match_char(b'=') { self.add_token(TokenType::StarEqual) }
+                else { self.add_token(TokenType::Star) }
+            }
+            b'%' => {
+                if self.match_char(b'=') { self.add_token(TokenType::PercentEqual) }
+                else { self.add_token(TokenType::Percent) }
+            }
+            b'^' => self.add_token(TokenType::BitXor),
 
             b'=' => {
                 if self.match_char(b'=') { self.add_token(TokenType::EqualEqual) }
+                else if self.match_char(b'>') { self.add_token(TokenType::FatArrow) }
                 else { self.add_token(TokenType::Equal) }
             }
             b'!' => {
@@ -129,20 +191,40 @@ impl Lexer {
                     if self.match_char(b'.') {
                         self.add_token(TokenType::Ellipsis)
                     } else {
-                        Err(format!("Expected third '.' for ellipsis at line {}", self.line))
+                        self.add_token(TokenType::DotDot)
                     }
                 } else {
                     self.add_token(TokenType::Dot)
                 }
             }
 
-            b'?' => self.add_token(TokenType::Question),
+            b'?' => {
+                if self.match_char(b'?') { self.add_token(TokenType::QuestionQuestion) }
+                else if self.match_char(b':') { self.add_token(TokenType::QuestionColon) }
+                else { self.add_token(TokenType::Question) }
+            }
             b',' => self.add_token(TokenType::Comma),
             b';' => self.add_token(TokenType::Semicolon),
             b'(' => self.add_token(TokenType::LeftParen),
             b')' => self.add_token(TokenType::RightParen),
-            b'{' => self.add_token(TokenType::LeftBrace),
-            b'}' => self.add_token(TokenType::RightBrace),
+            b'{' => {
+                if let Some((_, depth)) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.add_token(TokenType::LeftBrace)
+            }
+            b'}' => match self.interpolations.last_mut() {
+                Some((quote, 0)) => {
+                    let quote = *quote;
+                    self.interpolations.pop();
+                    self.string_body(quote, false)
+                }
+                Some((_, depth)) => {
+                    *depth -= 1;
+                    self.add_token(TokenType::RightBrace)
+                }
+                None => self.add_token(TokenType::RightBrace)
+            },
             b'[' => self.add_token(TokenType::LeftBracket),
             b']' => self.add_token(TokenType::RightBracket),
             b'$' => self.add_token(TokenType::Dollar),
@@ -152,32 +234,105 @@ impl Lexer {
 
             b'a'..=b'z' | b'A'..=b'Z' | b'_' => self.identifier(),
 
-            _ => Err(format!(
-                "Unexpected character '{}' at line {} column {}",
-                c as char, self.line, self.column
-            )),
+            _ => {
+                // Step over the rest of a multi-byte character so the span stays on a boundary
+                while !self.source.is_char_boundary(self.current) {
+                    self.current += 1;
+                }
+                let c = &self.source[self.start..self.current];
+                Err(self.error("E0001", format!("Unexpected character '{}'", c)))
+            }
         }
     }
 
-	fn string(&mut self) -> Result<(), String> {
+	fn string(&mut self) -> Result<(), Box<Diagnostic>> {
+		let quote = self.source.as_bytes()[self.start];
+		self.string_body(quote, true)
+	}
+
+	// Scans string text up to the closing quote or the next interpolation.
+	// `first` is false when resuming after an interpolated expression.
+	fn string_body(&mut self, quote: u8, mut first: bool) -> Result<(), Box<Diagnostic>> {
+		let mut content_start = self.current;
 		loop {
 			if self.is_at_end() {
-				return Err(format!("Unterminated string literal at line {}", self.line));
+				return Err(self.error("E0002", "Unterminated string literal".to_string()));
 			}
-			if self.peek() == b'"' {
+
+			let c = self.peek();
+			if c == b'\\' {
+				self.advance();
+				if self.peek() == b'\n' {
+					self.line += 1;
+					self.column = 0;
+				}
 				self.advance();
-				break;
+				continue;
+			}
+
+			if c == quote {
+				let content = self.source[content_start..self.current].to_string();
+				self.advance();
+				return if first {
+					self.add_token(TokenType::StringLiteral)
+				} else {
+					self.emit(TokenType::StringEnd, content)
+				};
+			}
+
+			if c == b'$' && self.peek_next() == b'{' {
+				let content = self.source[content_start..self.current].to_string();
+				self.advance();
+				self.advance();
+				self.interpolations.push((quote, 0));
+				return self.emit(if first { TokenType::StringStart } else { TokenType::StringMiddle }, content);
+			}
+
+			if c == b'$' && is_alpha(self.peek_next()) {
+				let content = self.source[content_start..self.current].to_string();
+				self.emit(if first { TokenType::StringStart } else { TokenType::StringMiddle }, content)?;
+				self.advance();
+				self.start = self.current;
+				self.start_column = self.column;
+				self.identifier()?;
+				self.start = self.current;
+				self.start_line = self.line;
+				self.start_column = self.column;
+				content_start = self.current;
+				first = false;
+				continue;
+			}
+
+			if c == b'\n' {
+				self.line += 1;
+				self.column = 0;
 			}
-            if self.peek() == b'\n' {
-                self.line += 1;
-                self.column = 0;
-            }
 			self.advance();
 		}
-		return self.add_token(TokenType::StringLiteral);
 	}
 
-	pub fn number(&mut self) -> Result<(), String> {
+	// Pushes a token whose lexeme differs from its source text, then starts
+	// the next token where this one ended.
+	fn emit(&mut self, token_type: TokenType, lexeme: String) -> Result<(), Box<Diagnostic>> {
+		self.tokens.push(Token {
+			token_type,
+			lexeme,
+			line: self.start_line,
+			column: self.start_column,
+			span: Span {
+				start: self.start,
+				end: self.current,
+				line: self.start_line,
+				column: self.start_column,
+			},
+		});
+		self.start = self.current;
+		self.start_line = self.line;
+		self.start_column = self.column;
+		Ok(())
+	}
+
+	pub fn number(&mut self) -> Result<(), Box<Diagnostic>> {
 		while is_digit(self.peek()) {
 			self.advance();
 		}
@@ -193,7 +348,7 @@ impl Lexer {
 		self.add_token(TokenType::NumberLiteral)
 	}
 
-	pub fn identifier(&mut self) -> Result<(), String> {
+	pub fn identifier(&mut self) -> Result<(), Box<Diagnostic>> {
 		while is_alpha_numeric(self.peek()) {
 			self.advance();
 		}
@@ -204,27 +359,36 @@ impl Lexer {
 		self.add_token(token_type)
 	}
 
-	fn block_comment(&mut self) -> Result<(), String> {
+	fn block_comment(&mut self) -> Result<(), Box<Diagnostic>> {
         loop {
             if self.is_at_end() {
-                return Err(format!("Unterminated block comment at line {}", self.line));
+                return Err(self.error("E0003", "Unterminated block comment".to_string()));
             }
             if self.peek() == b'*' && self.peek_next() == b'/' {
                 self.advance();
                 self.advance();
                 break;
             }
-            if self.peek() == b'\n' {
+            if self.advance() == b'\n' {
                 self.line += 1;
                 self.column = 1;
             }
-            self.advance();
         }
         Ok(())
     }
 
+	fn error(&self, code: &'static str, message: String) -> Box<Diagnostic> {
+		let span = Span {
+			start: self.start,
+			end: self.current,
+			line: self.start_line,
+			column: self.start_column
+		};
+		Box::new(Diagnostic::error(message, span).with_code(code))
+	}
+
 	pub fn is_at_end(&self) -> bool {
-		self.current >= self.source.len() as usize
+		self.current >= self.source.len()
 	}
 
 	pub fn advance(&mut self) -> u8 {
@@ -268,59 +432,87 @@ impl Lexer {
     }
 }
 
+// Words the lexer turns into keyword tokens instead of identifiers
+pub const KEYWORDS: &[(&str, TokenType)] = &[
+	("class", TokenType::Class),
+	("interface", TokenType::Interface),
+	("import", TokenType::Import),
+	("package", TokenType::Package),
+	("enum", TokenType::Enum),
+	("struct", TokenType::Struct),
+	("protected", TokenType::Protected),
+	("private", TokenType::Private),
+	("override", TokenType::Override),
+	("this", TokenType::This),
+	("new", TokenType::New),
+	("super", TokenType::Super),
+	("constructor", TokenType::Constructor),
+	("data", TokenType::Data),
+	("sealed", TokenType::Sealed),
+	("typeof", TokenType::Typeof),
+	("annotation", TokenType::Annotation),
+	("type", TokenType::Type),
+	("if", TokenType::If),
+	("else", TokenType::Else),
+	("elif", TokenType::Elif),
+	("while", TokenType::While),
+	("for", TokenType::For),
+	("loop", TokenType::Loop),
+	("break", TokenType::Break),
+	("continue", TokenType::Continue),
+	("async", TokenType::Async),
+	("await", TokenType::Await),
+	("spawn", TokenType::Spawn),
+	("fn", TokenType::Function),
+	("return", TokenType::Return),
+	("yield", TokenType::Yield),
+	("true", TokenType::True),
+	("false", TokenType::False),
+	("null", TokenType::Null),
+	("mut", TokenType::Mut),
+	("val", TokenType::Val),
+	("const", TokenType::Const),
+	("and", TokenType::And),
+	("or", TokenType::Or),
+	("not", TokenType::Not),
+	("is", TokenType::Is),
+	("in", TokenType::In),
+	("of", TokenType::Of),
+	("try", TokenType::Try),
+	("catch", TokenType::Catch),
+	("finally", TokenType::Finally),
+	("throw", TokenType::Throw),
+	("defer", TokenType::Defer),
+	("switch", TokenType::Switch),
+	("case", TokenType::Case),
+	("default", TokenType::Default)
+];
+
 fn lookup_keyword(text: &str) -> TokenType {
-	match text {
-		"class" => TokenType::Class,
-		"interface" => TokenType::Interface,
-		"import" => TokenType::Import,
-		"package" => TokenType::Package,
-		"enum" => TokenType::Enum,
-		"struct" => TokenType::Struct,
-		"protected" => TokenType::Protected,
-		"private" => TokenType::Private,
-		"override" => TokenType::Override,
-		"this" => TokenType::This,
-		"new" => TokenType::New,
-		"super" => TokenType::Super,
-		"constructor" => TokenType::Constructor,
-		"data" => TokenType::Data,
-		"typeof" => TokenType::Typeof,
-		"annotation" => TokenType::Annotation,
-		"if" => TokenType::If,
-		"else" => TokenType::Else,
-		"elif" => TokenType::Elif,
-		"while" => TokenType::While,
-		"for" => TokenType::For,
-		"loop" => TokenType::Loop,
-		"break" => TokenType::Break,
-		"continue" => TokenType::Continue,
-		"async" => TokenType::Async,
-		"await" => TokenType::Await,
-		"fn" => TokenType::Function,
-		"return" => TokenType::Return,
-		"true" => TokenType::True,
-		"false" => TokenType::False,
-		"null" => TokenType::Null,
-		"mut" => TokenType::Mut,
-		"val" => TokenType::Val,
-		"and" => TokenType::And,
-		"or" => TokenType::Or,
-		"not" => TokenType::Not,
-		"is" => TokenType::Is,
-		"in" => TokenType::In,
-		"of" => TokenType::Of,
-		_ => TokenType::Identifier,
-	}
+	KEYWORDS.iter().find(|(keyword, _)| *keyword == text).map_or(TokenType::Identifier, |(_, token_type)| *token_type)
 }
 
 fn is_digit(c: u8) -> bool {
-    c >= b'0' && c <= b'9'
+    c.is_ascii_digit()
 }
 
 fn is_alpha(c: u8) -> bool {
-    (c >= b'a' && c <= b'z') || (c >= b'A' && c <= b'Z') || c == b'_'
+    c.is_ascii_alphabetic() || c == b'_'
 }
 
 fn is_alpha_numeric(c: u8) -> bool {
     is_alpha(c) || is_digit(c)
 }
+
+// Body of a `/** */` comment without the conventional leading `*` on each line.
+fn doc_block(body: &str) -> String {
+	body.lines()
+		.map(|line| {
+			let line = line.trim();
+			line.strip_prefix('*').map_or(line, str::trim_start)
+		})
+		.collect::<Vec<_>>()
+		.join("\n")
+		.trim()
+		.to_string()
+}
@@ -8,6 +8,14 @@ pub enum TokenType {
 	Identifier,
 	NumberLiteral,
 	StringLiteral,
+	// "a ${b} c $d e" lexes as StringStart("a "), <tokens of b>,
+	// StringMiddle(" c "), Identifier(d), StringEnd(" e")
+	StringStart,
+	StringMiddle,
+	StringEnd,
+	// `/// text` or `/** text */`; the lexeme is the text with the comment
+	// markers stripped. The parser moves these off the token stream.
+	DocComment,
 
 	// Keywords: OOP / Structures
 	Class,
@@ -24,8 +32,10 @@ pub enum TokenType {
 	Super,
 	Constructor,
 	Data,
+	Sealed,
 	Typeof,
 	Annotation,
+	Type,
 
 	// Control Flow
 	If,
@@ -40,8 +50,10 @@ pub enum TokenType {
 	// Functions
 	Async,
 	Await,
+	Spawn,
 	Function,
 	Return,
+	Yield,
 
 	// Literals
 	True,
@@ -51,6 +63,7 @@ pub enum TokenType {
 	// Variables
 	Mut,
 	Val,
+	Const,
 
 	// Operators (word versions)
 	And,
@@ -65,6 +78,7 @@ pub enum TokenType {
 	Catch,
 	Finally,
 	Throw,
+	Defer,
 
 	// Switch/Case
 	Switch,
@@ -93,7 +107,12 @@ pub enum TokenType {
 	BangBang,      // !!
 
 	// Assignment
-	Equal, // =
+	Equal,        // =
+	PlusEqual,    // +=
+	MinusEqual,   // -=
+	StarEqual,    // *=
+	SlashEqual,   // /=
+	PercentEqual, // %=
 
 	// Brackets
 	LeftParen,
@@ -108,7 +127,10 @@ pub enum TokenType {
 	FatArrow,    // =>
 	ColonColon,  // ::
 	Question,    // ?
+	QuestionQuestion, // ??
+	QuestionColon, // ?: (same as ??)
 	Ellipsis,    // ...
+	DotDot,      // ..
 
 	BitAnd,
 	BitOr,
@@ -125,11 +147,32 @@ pub enum TokenType {
 	Semicolon
 }
 
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub struct Span {
+	pub start: usize,
+	pub end: usize,
+	pub line: i64,
+	pub column: i64
+}
+
+impl Span {
+	pub fn to(self, other: Span) -> Span {
+		Span {
+			start: self.start,
+			end: self.end.max(other.end),
+			line: self.line,
+			column: self.column
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
 pub struct Token {
 	pub token_type: TokenType,
 	pub lexeme: String,
 	pub line: i64,
-	pub column: i64
+	pub column: i64,
+	pub span: Span
 }
 
 impl fmt::Display for Token {
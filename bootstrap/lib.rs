@@ -0,0 +1,12 @@
+pub mod ast;
+pub mod bytecode;
+pub mod diagnostics;
+pub mod doc;
+pub mod driver;
+pub mod format;
+pub mod interpreter;
+pub mod lexer;
+pub mod llvm;
+pub mod parser;
+pub mod sema;
+pub mod stdlib;
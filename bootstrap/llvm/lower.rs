@@ -0,0 +1,1264 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::ast::expr::{BinaryOp, Literal, PostfixOp, UnaryOp};
+use crate::ast::stmt::ForKind;
+use crate::diagnostics::Diagnostic;
+use crate::interpreter;
+use crate::lexer::token::Span;
+use crate::sema::builtins::Builtin;
+use crate::sema::hir::{Block, Case, Catch, CType, Coercion, Expr, ExprKind, FnKind, Foreign, MemberTarget, Pattern, Program, Stmt, StmtKind, Variable};
+use crate::sema::types::{FnId, LocalId, Type};
+
+// A checked program as LLVM IR, and the shared libraries its `extern`
+// functions are in, to link it with.
+pub struct Lowered {
+	pub ir: String,
+	pub libraries: Vec<String>
+}
+
+// Lowers the code that runs from `main` and each file's top-level code to
+// LLVM IR that calls into `runtime.c`. `entry` is the file `main` is in, when
+// the project names one.
+//
+// `Int`s, `Float`s and `Bool`s are `i64`s, `double`s and `i1`s; everything
+// else is a pointer to an object of the runtime, or null, and those values
+// are boxed where they go into one. Locals are stack slots, so that what a
+// `try` block changed is still there after `longjmp` takes a throw to its
+// catches; `finally` blocks are lowered again at every way out of their
+// `try`, as the compiler does. Only functions that are called are lowered,
+// and what the native target cannot compile yet, like classes and lambdas,
+// is reported where it is used.
+pub fn lower(program: &Program, entry: Option<usize>) -> Result<Lowered, Vec<Diagnostic>> {
+	let mut lowerer = Lowerer {
+		program,
+		head: String::new(),
+		functions: String::new(),
+		constants: HashMap::new(),
+		literals: HashMap::new(),
+		globals: HashSet::new(),
+		externs: HashSet::new(),
+		libraries: BTreeSet::new(),
+		queued: HashSet::new(),
+		queue: Vec::new(),
+		errors: Vec::new(),
+		frame: Frame::new(0, Repr::Void)
+	};
+	let main = interpreter::main(program, entry);
+	if let Some(main) = main {
+		let function = program.function(main);
+		if !function.params.is_empty() {
+			lowerer.frame.file = function.file;
+			lowerer.unsupported("a 'main' that takes arguments", function.span);
+		}
+		lowerer.queue(main);
+	}
+	for &file in &program.init_order {
+		lowerer.frame = Frame::new(file, Repr::Void);
+		lowerer.stmts(&program.files[file].body);
+		let code = lowerer.finish(&format!("init.{}", file), "");
+		lowerer.functions.push_str(&code);
+	}
+	while let Some(id) = lowerer.queue.pop() {
+		lowerer.function(id);
+	}
+	if !lowerer.errors.is_empty() {
+		return Err(lowerer.errors);
+	}
+
+	let mut ir = String::from(RUNTIME);
+	ir.push_str(&lowerer.head);
+	ir.push_str(&lowerer.functions);
+	ir.push_str("define void @df_program() {\nentry:\n");
+	for (id, global) in program.globals.iter().enumerate() {
+		if Repr::of(&global.ty) == Repr::Ref {
+			ir.push_str(&format!("\tcall void @df_root(i8** @g.{})\n", id));
+		}
+	}
+	for slot in 0..lowerer.literals.len() {
+		ir.push_str(&format!("\tcall void @df_root(i8** @l.{})\n", slot));
+	}
+	for &file in &program.init_order {
+		ir.push_str(&format!("\tcall void @\"init.{}\"()\n", file));
+	}
+	if let Some(main) = main {
+		ir.push_str(&format!("\tcall {} {}()\n", Repr::of(&program.function(main).ret).llvm(), symbol(program, main)));
+	}
+	ir.push_str("\tret void\n}\n\nattributes #0 = { returns_twice }\n");
+	Ok(Lowered { ir, libraries: lowerer.libraries.into_iter().collect() })
+}
+
+// What `runtime.c` gives the generated code.
+const RUNTIME: &str = "declare i8* @df_literal(i8**, i8*, i64)
+declare void @df_root(i8**)
+declare i8* @df_box_int(i64)
+declare i8* @df_box_float(double)
+declare i8* @df_box_bool(i1 zeroext)
+declare i64 @df_unbox_int(i8*)
+declare double @df_unbox_float(i8*)
+declare zeroext i1 @df_unbox_bool(i8*)
+declare zeroext i1 @df_truthy(i8*)
+declare zeroext i1 @df_is(i8*, i32)
+declare i8* @df_cast(i8*, i32, i8*)
+declare i8* @df_type_of(i8*)
+declare zeroext i1 @df_equal(i8*, i8*)
+declare i32 @df_compare(i8*, i8*)
+declare i8* @df_concat(i8*, i8*)
+declare zeroext i1 @df_contains(i8*, i8*)
+declare i64 @df_length(i8*)
+declare i8* @df_from_c(i8*, i8*)
+declare i8* @df_to_c(i8*)
+declare i8* @df_to_string(i8*)
+declare void @df_print(i8*, i1 zeroext)
+declare void @df_newline()
+declare i64 @df_add(i64, i64)
+declare i64 @df_subtract(i64, i64)
+declare i64 @df_multiply(i64, i64)
+declare i64 @df_divide(i64, i64)
+declare i64 @df_modulo(i64, i64)
+declare i64 @df_shift_left(i64, i64)
+declare i64 @df_shift_right(i64, i64)
+declare i64 @df_negate(i64)
+declare i64 @df_to_int(double)
+declare i8* @df_try()
+declare void @df_untry()
+declare i32 @_setjmp(i8*) #0
+declare void @df_throw(i8*) noreturn
+declare void @df_fail(i8*) noreturn
+declare i8* @df_caught()
+declare zeroext i1 @df_caught_error()
+declare void @df_rethrow(i8*, i1 zeroext) noreturn
+
+";
+
+// How a value of a type is held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Repr {
+	Int,
+	Float,
+	Bool,
+	// A pointer to an object of the runtime, or null
+	Ref,
+	Void
+}
+
+impl Repr {
+	fn of(ty: &Type) -> Repr {
+		match ty {
+			Type::Int => Repr::Int,
+			Type::Float => Repr::Float,
+			Type::Bool => Repr::Bool,
+			Type::Void | Type::Never => Repr::Void,
+			_ => Repr::Ref
+		}
+	}
+
+	fn llvm(self) -> &'static str {
+		match self {
+			Repr::Int => "i64",
+			Repr::Float => "double",
+			Repr::Bool => "i1",
+			Repr::Ref => "i8*",
+			Repr::Void => "void"
+		}
+	}
+
+	fn zero(self) -> &'static str {
+		match self {
+			Repr::Int => "0",
+			Repr::Float => "0.0",
+			Repr::Bool => "false",
+			Repr::Ref => "null",
+			Repr::Void => ""
+		}
+	}
+}
+
+// The kinds of object a value of the type can be, in the masks `df_is` and
+// `df_cast` take; None for types the native target has no values of.
+fn mask(ty: &Type) -> Option<i32> {
+	Some(match ty {
+		Type::String => 1,
+		Type::Int => 2,
+		Type::Float => 4,
+		Type::Bool => 8,
+		Type::Null => 16,
+		Type::Any => 31,
+		Type::Void | Type::Never => 0,
+		Type::Nullable(inner) => mask(inner)? | 16,
+		Type::Union(members) => members.iter().map(mask).try_fold(0, |mask, member| Some(mask | member?))?,
+		_ => return None
+	})
+}
+
+fn symbol(program: &Program, id: FnId) -> String {
+	format!("@\"{}.{}\"", program.function(id).name, id.0)
+}
+
+// A value in the code lowered so far: a constant or a `%` register.
+#[derive(Debug, Clone)]
+struct Val {
+	repr: Repr,
+	text: String
+}
+
+impl Val {
+	fn new(repr: Repr, text: impl Into<String>) -> Val {
+		Val { repr, text: text.into() }
+	}
+
+	fn void() -> Val {
+		Val::new(Repr::Void, "")
+	}
+
+	// As an argument: its type, then itself.
+	fn arg(&self) -> String {
+		format!("{} {}", self.repr.llvm(), self.text)
+	}
+}
+
+struct Lowerer<'a> {
+	program: &'a Program,
+	// Constants and globals, and the C functions `extern` declares
+	head: String,
+	functions: String,
+	// Each C string once, by its text
+	constants: HashMap<String, usize>,
+	// The global each string literal is made into, by its text
+	literals: HashMap<String, usize>,
+	globals: HashSet<usize>,
+	externs: HashSet<String>,
+	libraries: BTreeSet<String>,
+	queued: HashSet<FnId>,
+	queue: Vec<FnId>,
+	errors: Vec<Diagnostic>,
+	// The function being lowered now
+	frame: Frame<'a>
+}
+
+// A function as it is lowered.
+struct Frame<'a> {
+	file: usize,
+	ret: Repr,
+	// The stack slots, which go in the entry block
+	allocas: String,
+	code: String,
+	count: usize,
+	slots: HashMap<LocalId, String>,
+	// The `try`s and loops around what is lowered now, innermost last
+	scopes: Vec<Scope<'a>>,
+	loops: Vec<Loop>,
+	// Whether the block lowered into has ended, so that what follows is
+	// unreachable and goes in a block of its own
+	ended: bool
+}
+
+impl Frame<'_> {
+	fn new(file: usize, ret: Repr) -> Self {
+		Frame { file, ret, allocas: String::new(), code: String::new(), count: 0, slots: HashMap::new(), scopes: Vec::new(), loops: Vec::new(), ended: false }
+	}
+}
+
+// What leaving a `try` takes: ending the handler its catches have, or the
+// one its `finally` has and then running it.
+#[derive(Clone, Copy)]
+enum Scope<'a> {
+	Handler,
+	Finally(&'a Block)
+}
+
+struct Loop {
+	label: Option<String>,
+	exit: String,
+	// None for a label on something that is not a loop, which only `break`
+	// can leave
+	next: Option<String>,
+	// How many scopes were around the loop
+	depth: usize
+}
+
+impl<'a> Lowerer<'a> {
+	fn unsupported(&mut self, what: &str, span: Span) -> Val {
+		self.errors.push(Diagnostic::error(format!("The native target cannot compile {} yet", what), span).in_file(self.frame.file));
+		Val::void()
+	}
+
+	fn queue(&mut self, id: FnId) {
+		if self.queued.insert(id) {
+			self.queue.push(id);
+		}
+	}
+
+	fn function(&mut self, id: FnId) {
+		let function = self.program.function(id);
+		self.frame = Frame::new(function.file, Repr::of(&function.ret));
+		if function.modifiers.is_async {
+			self.unsupported("async functions", function.span);
+		}
+		let mut params = Vec::new();
+		for (index, param) in function.params.iter().enumerate() {
+			let repr = Repr::of(&param.ty);
+			params.push(format!("{} %p{}", repr.llvm(), index));
+			let slot = self.slot(param.local);
+			self.emit(format!("store {} %p{}, {}* {}", repr.llvm(), index, repr.llvm(), slot));
+		}
+		if let Some(body) = &function.body {
+			self.block(body);
+		}
+		let code = self.finish(&format!("{}.{}", function.name, id.0), &params.join(", "));
+		self.functions.push_str(&code);
+	}
+
+	// The function lowered into the frame, now that it is all there.
+	fn finish(&mut self, name: &str, params: &str) -> String {
+		let ret = self.frame.ret;
+		if !self.frame.ended {
+			self.end(if ret == Repr::Void { "ret void" } else { "unreachable" });
+		}
+		let frame = &self.frame;
+		format!("define internal {} @\"{}\"({}) {{\nentry:\n{}\tbr label %start\nstart:\n{}}}\n\n", ret.llvm(), name, params, frame.allocas, frame.code)
+	}
+
+	// ---------------------------------------------------------------------
+	// Emitting
+	// ---------------------------------------------------------------------
+
+	fn emit(&mut self, line: impl AsRef<str>) {
+		if self.frame.ended {
+			let dead = self.label("dead");
+			self.frame.code.push_str(&format!("{}:\n", dead));
+			self.frame.ended = false;
+		}
+		self.frame.code.push('\t');
+		self.frame.code.push_str(line.as_ref());
+		self.frame.code.push('\n');
+	}
+
+	// Emits the instruction that ends the block.
+	fn end(&mut self, line: impl AsRef<str>) {
+		self.emit(line);
+		self.frame.ended = true;
+	}
+
+	fn jump(&mut self, label: &str) {
+		self.end(format!("br label %{}", label));
+	}
+
+	fn branch(&mut self, cond: &str, then: &str, otherwise: &str) {
+		self.end(format!("br i1 {}, label %{}, label %{}", cond, then, otherwise));
+	}
+
+	// Starts the block `label`, falling into it from the one before.
+	fn place(&mut self, label: &str) {
+		if !self.frame.ended {
+			self.jump(label);
+		}
+		self.frame.code.push_str(&format!("{}:\n", label));
+		self.frame.ended = false;
+	}
+
+	fn label(&mut self, name: &str) -> String {
+		self.frame.count += 1;
+		format!("{}{}", name, self.frame.count)
+	}
+
+	fn temp(&mut self) -> String {
+		self.frame.count += 1;
+		format!("%t{}", self.frame.count)
+	}
+
+	// `instruction` into a new register.
+	fn value(&mut self, repr: Repr, instruction: impl AsRef<str>) -> Val {
+		let temp = self.temp();
+		self.emit(format!("{} = {}", temp, instruction.as_ref()));
+		Val::new(repr, temp)
+	}
+
+	fn call(&mut self, repr: Repr, function: &str, args: &[Val]) -> Val {
+		let args: Vec<String> = args.iter().map(Val::arg).collect();
+		self.call_with(repr, function, &args.join(", "))
+	}
+
+	fn call_with(&mut self, repr: Repr, function: &str, args: &str) -> Val {
+		match repr {
+			Repr::Void => {
+				self.emit(format!("call void @{}({})", function, args));
+				Val::void()
+			}
+			repr => self.value(repr, format!("call {} @{}({})", repr.llvm(), function, args))
+		}
+	}
+
+	// A stack slot of its own, zeroed so the collector never sees what was
+	// there before.
+	fn alloca(&mut self, name: String, repr: Repr) -> String {
+		let ty = repr.llvm();
+		self.frame.allocas.push_str(&format!("\t{} = alloca {}\n\tstore {} {}, {}* {}\n", name, ty, ty, repr.zero(), ty, name));
+		name
+	}
+
+	fn scratch(&mut self, repr: Repr) -> String {
+		let name = self.temp();
+		self.alloca(name, repr)
+	}
+
+	fn slot(&mut self, local: LocalId) -> String {
+		if let Some(slot) = self.frame.slots.get(&local) {
+			return slot.clone();
+		}
+		let repr = Repr::of(&self.program.local(local).ty);
+		let slot = self.alloca(format!("%\"{}.{}\"", self.program.local(local).name, local.0), repr);
+		self.frame.slots.insert(local, slot.clone());
+		slot
+	}
+
+	fn load(&mut self, repr: Repr, place: &str) -> Val {
+		self.value(repr, format!("load {}, {}* {}", repr.llvm(), repr.llvm(), place))
+	}
+
+	fn store(&mut self, value: &Val, place: &str) {
+		if value.repr != Repr::Void {
+			self.emit(format!("store {}, {}* {}", value.arg(), value.repr.llvm(), place));
+		}
+	}
+
+	// A NUL-terminated constant, as an `i8*`.
+	fn constant(&mut self, text: &str) -> String {
+		let count = self.constants.len();
+		let index = *self.constants.entry(text.to_string()).or_insert(count);
+		if index == count {
+			self.head.push_str(&format!("@c.{} = private unnamed_addr constant [{} x i8] c\"{}\\00\"\n", index, text.len() + 1, escape(text)));
+		}
+		format!("getelementptr ([{} x i8], [{} x i8]* @c.{}, i64 0, i64 0)", text.len() + 1, text.len() + 1, index)
+	}
+
+	fn literal(&mut self, text: &str) -> Val {
+		let count = self.literals.len();
+		let index = *self.literals.entry(text.to_string()).or_insert(count);
+		if index == count {
+			self.head.push_str(&format!("@l.{} = internal global i8* null\n", index));
+		}
+		let bytes = self.constant(text);
+		self.call_with(Repr::Ref, "df_literal", &format!("i8** @l.{}, i8* {}, i64 {}", index, bytes, text.len()))
+	}
+
+	fn global(&mut self, id: usize) -> (Repr, String) {
+		let repr = Repr::of(&self.program.globals[id].ty);
+		let name = format!("@g.{}", id);
+		if self.globals.insert(id) {
+			self.head.push_str(&format!("{} = internal global {} {}\n", name, repr.llvm(), repr.zero()));
+		}
+		(repr, name)
+	}
+
+	// ---------------------------------------------------------------------
+	// Values
+	// ---------------------------------------------------------------------
+
+	// The value as `to` holds it: boxed or unboxed, or widened to a `Float`.
+	fn convert(&mut self, value: Val, to: Repr) -> Val {
+		match (value.repr, to) {
+			(from, to) if from == to => value,
+			// What never has a value, in code that never runs
+			(Repr::Void, to) => Val::new(to, to.zero()),
+			(_, Repr::Void) => Val::void(),
+			(Repr::Int, Repr::Float) => self.value(Repr::Float, format!("sitofp i64 {} to double", value.text)),
+			(Repr::Int, Repr::Ref) => self.call(Repr::Ref, "df_box_int", &[value]),
+			(Repr::Float, Repr::Ref) => self.call(Repr::Ref, "df_box_float", &[value]),
+			(Repr::Bool, Repr::Ref) => self.call(Repr::Ref, "df_box_bool", &[value]),
+			(Repr::Ref, Repr::Int) => self.call(Repr::Int, "df_unbox_int", &[value]),
+			(Repr::Ref, Repr::Float) => self.call(Repr::Float, "df_unbox_float", &[value]),
+			(Repr::Ref, Repr::Bool) => self.call(Repr::Bool, "df_unbox_bool", &[value]),
+			// Fails at run time, as the value is not one of those
+			(_, to) => {
+				let boxed = self.convert(value, Repr::Ref);
+				self.convert(boxed, to)
+			}
+		}
+	}
+
+	// Whether the value is `true`; anything else is false.
+	fn truthy(&mut self, value: Val) -> String {
+		match value.repr {
+			Repr::Bool => value.text,
+			Repr::Ref => self.call(Repr::Bool, "df_truthy", &[value]).text,
+			_ => "false".to_string()
+		}
+	}
+
+	fn not(&mut self, value: &str) -> String {
+		self.value(Repr::Bool, format!("xor i1 {}, true", value)).text
+	}
+
+	fn equal(&mut self, left: Val, right: Val) -> String {
+		match (left.repr, right.repr) {
+			(Repr::Int, Repr::Int) | (Repr::Bool, Repr::Bool) => self.value(Repr::Bool, format!("icmp eq {}, {}", left.arg(), right.text)).text,
+			(Repr::Float | Repr::Int, Repr::Float | Repr::Int) => {
+				let (left, right) = (self.convert(left, Repr::Float), self.convert(right, Repr::Float));
+				self.value(Repr::Bool, format!("fcmp oeq double {}, {}", left.text, right.text)).text
+			}
+			_ => {
+				let (left, right) = (self.convert(left, Repr::Ref), self.convert(right, Repr::Ref));
+				self.call(Repr::Bool, "df_equal", &[left, right]).text
+			}
+		}
+	}
+
+	// ---------------------------------------------------------------------
+	// Statements
+	// ---------------------------------------------------------------------
+
+	fn block(&mut self, block: &'a Block) {
+		self.stmts(&block.stmts);
+	}
+
+	fn stmts(&mut self, stmts: &'a [Stmt]) {
+		for stmt in stmts {
+			self.stmt(stmt);
+		}
+	}
+
+	fn stmt(&mut self, stmt: &'a Stmt) {
+		match &stmt.kind {
+			StmtKind::Expr(expr) => {
+				self.expr(expr);
+			}
+			StmtKind::Var { var, init } => {
+				let value = match init {
+					Some(init) => self.expr(init),
+					None => Val::void()
+				};
+				self.assign(*var, value);
+			}
+			StmtKind::Block(block) => self.block(block),
+			StmtKind::If { cond, then_branch, else_branch } => {
+				let cond = self.expr(cond);
+				let cond = self.truthy(cond);
+				let (then, otherwise, end) = (self.label("then"), self.label("else"), self.label("endif"));
+				self.branch(&cond, &then, &otherwise);
+				self.place(&then);
+				self.block(then_branch);
+				self.jump(&end);
+				self.place(&otherwise);
+				if let Some(else_branch) = else_branch {
+					self.stmt(else_branch);
+				}
+				self.place(&end);
+			}
+			StmtKind::While { .. } | StmtKind::Loop { .. } | StmtKind::For { .. } => self.looped(stmt, None),
+			StmtKind::Labeled { label, body } => self.looped(body, Some(label)),
+			StmtKind::Break(label) | StmtKind::Continue(label) => {
+				let breaks = matches!(stmt.kind, StmtKind::Break(_));
+				let found = self.frame.loops.iter().rev().find(|target| match label {
+					Some(label) => target.label.as_ref() == Some(label),
+					None => target.next.is_some()
+				});
+				let Some(target) = found else {
+					return;
+				};
+				let (depth, to) = (target.depth, if breaks { target.exit.clone() } else { target.next.clone().unwrap_or_default() });
+				self.unwind(depth);
+				self.jump(&to);
+			}
+			StmtKind::Return(value) => {
+				let value = value.as_ref().map(|value| self.expr(value));
+				self.ret(value);
+			}
+			StmtKind::Yield(_) => {
+				self.unsupported("'yield'", stmt.span);
+			}
+			StmtKind::Throw(value) => {
+				let value = self.expr(value);
+				self.throw(value);
+			}
+			StmtKind::Try { body, catches, finally } => self.try_finally(body, catches, finally.as_ref()),
+			StmtKind::Switch { subject, cases, default, .. } => self.switch(subject, cases, default.as_ref()),
+			StmtKind::Function { .. } => {
+				self.unsupported("local functions", stmt.span);
+			}
+		}
+	}
+
+	fn assign(&mut self, var: Variable, value: Val) {
+		let (repr, place) = match var {
+			Variable::Local(local) => (Repr::of(&self.program.local(local).ty), self.slot(local)),
+			Variable::Global(global) => self.global(global.0 as usize)
+		};
+		let value = self.convert(value, repr);
+		self.store(&value, &place);
+	}
+
+	fn read(&mut self, var: Variable) -> Val {
+		let (repr, place) = match var {
+			Variable::Local(local) => (Repr::of(&self.program.local(local).ty), self.slot(local)),
+			Variable::Global(global) => self.global(global.0 as usize)
+		};
+		match repr {
+			Repr::Void => Val::void(),
+			repr => self.load(repr, &place)
+		}
+	}
+
+	fn ret(&mut self, value: Option<Val>) {
+		let repr = self.frame.ret;
+		let value = value.map(|value| self.convert(value, repr));
+		self.unwind(0);
+		match value {
+			Some(value) if repr != Repr::Void => self.end(format!("ret {}", value.arg())),
+			_ => self.end("ret void")
+		}
+	}
+
+	fn throw(&mut self, value: Val) {
+		let value = self.convert(value, Repr::Ref);
+		self.call(Repr::Void, "df_throw", &[value]);
+		self.end("unreachable");
+	}
+
+	// Leaves the scopes down to `depth`, innermost first, running the
+	// `finally` blocks on the way.
+	fn unwind(&mut self, depth: usize) {
+		let scopes = self.frame.scopes.clone();
+		for index in (depth..scopes.len()).rev() {
+			self.frame.scopes.truncate(index);
+			self.call(Repr::Void, "df_untry", &[]);
+			if let Scope::Finally(finally) = scopes[index] {
+				self.block(finally);
+			}
+		}
+		self.frame.scopes = scopes;
+	}
+
+	fn looped(&mut self, stmt: &'a Stmt, label: Option<&String>) {
+		let depth = self.frame.scopes.len();
+		let exit = self.label("exit");
+		match &stmt.kind {
+			StmtKind::While { cond, body } => {
+				let (test, start) = (self.label("while"), self.label("body"));
+				self.place(&test);
+				let cond = self.expr(cond);
+				let cond = self.truthy(cond);
+				self.branch(&cond, &start, &exit);
+				self.place(&start);
+				self.frame.loops.push(Loop { label: label.cloned(), exit: exit.clone(), next: Some(test.clone()), depth });
+				self.block(body);
+				self.jump(&test);
+			}
+			StmtKind::Loop { body } => {
+				let start = self.label("loop");
+				self.place(&start);
+				self.frame.loops.push(Loop { label: label.cloned(), exit: exit.clone(), next: Some(start.clone()), depth });
+				self.block(body);
+				self.jump(&start);
+			}
+			StmtKind::For { kind: ForKind::In, bindings, iterable, body } if bindings.len() == 1 && matches!(iterable.kind, ExprKind::Range { .. }) => {
+				let ExprKind::Range { start, end } = &iterable.kind else {
+					unreachable!()
+				};
+				let (start, end) = (self.expr(start), self.expr(end));
+				let (counter, limit) = (self.scratch(Repr::Int), self.scratch(Repr::Int));
+				self.store(&start, &counter);
+				self.store(&end, &limit);
+				let (test, first, step) = (self.label("for"), self.label("body"), self.label("step"));
+				self.place(&test);
+				let (at, end) = (self.load(Repr::Int, &counter), self.load(Repr::Int, &limit));
+				let more = self.value(Repr::Bool, format!("icmp slt i64 {}, {}", at.text, end.text));
+				self.branch(&more.text, &first, &exit);
+				self.place(&first);
+				self.assign(Variable::Local(bindings[0]), at.clone());
+				self.frame.loops.push(Loop { label: label.cloned(), exit: exit.clone(), next: Some(step.clone()), depth });
+				self.block(body);
+				self.place(&step);
+				let at = self.load(Repr::Int, &counter);
+				let next = self.value(Repr::Int, format!("add i64 {}, 1", at.text));
+				self.store(&next, &counter);
+				self.jump(&test);
+			}
+			StmtKind::For { iterable, .. } => {
+				self.unsupported("loops over anything but a range", iterable.span);
+				return;
+			}
+			_ => {
+				self.frame.loops.push(Loop { label: label.cloned(), exit: exit.clone(), next: None, depth });
+				self.stmt(stmt);
+			}
+		}
+		self.frame.loops.pop();
+		self.place(&exit);
+	}
+
+	// Starts a `try`, branching to `body` now and to `caught` when something
+	// is thrown in it.
+	fn handler(&mut self, body: &str, caught: &str) {
+		let target = self.call(Repr::Ref, "df_try", &[]);
+		let result = self.value(Repr::Int, format!("call i32 @_setjmp(i8* {}) #0", target.text));
+		let ok = self.value(Repr::Bool, format!("icmp eq i32 {}, 0", result.text));
+		self.branch(&ok.text, body, caught);
+	}
+
+	fn try_finally(&mut self, body: &'a Block, catches: &'a [Catch], finally: Option<&'a Block>) {
+		let Some(finally) = finally else {
+			return self.try_catch(body, catches);
+		};
+		let (start, caught, end) = (self.label("try"), self.label("finally"), self.label("endtry"));
+		self.handler(&start, &caught);
+		self.place(&start);
+		self.frame.scopes.push(Scope::Finally(finally));
+		self.try_catch(body, catches);
+		self.frame.scopes.pop();
+		if !self.frame.ended {
+			self.call(Repr::Void, "df_untry", &[]);
+			self.block(finally);
+			self.jump(&end);
+		}
+		// Thrown and not caught: the `finally` block runs, then it is thrown on
+		self.place(&caught);
+		let (value, error) = (self.scratch(Repr::Ref), self.scratch(Repr::Bool));
+		let thrown = self.call(Repr::Ref, "df_caught", &[]);
+		self.store(&thrown, &value);
+		let thrown_error = self.call(Repr::Bool, "df_caught_error", &[]);
+		self.store(&thrown_error, &error);
+		self.block(finally);
+		let (thrown, thrown_error) = (self.load(Repr::Ref, &value), self.load(Repr::Bool, &error));
+		self.call(Repr::Void, "df_rethrow", &[thrown, thrown_error]);
+		self.end("unreachable");
+		self.place(&end);
+	}
+
+	fn try_catch(&mut self, body: &'a Block, catches: &'a [Catch]) {
+		if catches.is_empty() {
+			return self.block(body);
+		}
+		let (start, caught, end) = (self.label("try"), self.label("catch"), self.label("endtry"));
+		self.handler(&start, &caught);
+		self.place(&start);
+		self.frame.scopes.push(Scope::Handler);
+		self.block(body);
+		self.frame.scopes.pop();
+		if !self.frame.ended {
+			self.call(Repr::Void, "df_untry", &[]);
+			self.jump(&end);
+		}
+		self.place(&caught);
+		let thrown = self.call(Repr::Ref, "df_caught", &[]);
+		for catch in catches {
+			let matched = self.label("caught");
+			let next = self.label("next");
+			match &catch.ty {
+				None => self.jump(&matched),
+				Some(ty) => match mask(ty) {
+					Some(mask) => {
+						let is = self.call_with(Repr::Bool, "df_is", &format!("i8* {}, i32 {}", thrown.text, mask));
+						self.branch(&is.text, &matched, &next);
+					}
+					None => {
+						self.unsupported("catching instances of classes", catch.span);
+						self.jump(&next);
+					}
+				}
+			}
+			self.place(&matched);
+			self.assign(Variable::Local(catch.local), thrown.clone());
+			self.block(&catch.body);
+			self.jump(&end);
+			self.place(&next);
+		}
+		let error = self.call(Repr::Bool, "df_caught_error", &[]);
+		self.call(Repr::Void, "df_rethrow", &[thrown, error]);
+		self.end("unreachable");
+		self.place(&end);
+	}
+
+	fn switch(&mut self, subject: &'a Expr, cases: &'a [Case], default: Option<&'a Block>) {
+		let subject = self.expr(subject);
+		let end = self.label("endswitch");
+		for case in cases {
+			let body = self.label("case");
+			for pattern in &case.patterns {
+				let next = self.label("next");
+				let matched = match pattern {
+					Pattern::Value(value) => {
+						let value = self.expr(value);
+						self.equal(subject.clone(), value)
+					}
+					Pattern::Type { ty, span } => match mask(ty) {
+						Some(mask) => {
+							let boxed = self.convert(subject.clone(), Repr::Ref);
+							self.call_with(Repr::Bool, "df_is", &format!("i8* {}, i32 {}", boxed.text, mask)).text
+						}
+						None => {
+							self.unsupported("matching instances of classes", *span);
+							"false".to_string()
+						}
+					}
+				};
+				self.branch(&matched, &body, &next);
+				self.place(&next);
+			}
+			let next = self.label("next");
+			self.jump(&next);
+			self.place(&body);
+			self.block(&case.body);
+			self.jump(&end);
+			self.place(&next);
+		}
+		if let Some(default) = default {
+			self.block(default);
+		}
+		self.place(&end);
+	}
+
+	// ---------------------------------------------------------------------
+	// Expressions
+	// ---------------------------------------------------------------------
+
+	// The value of the expression, held as its type is.
+	fn expr(&mut self, expr: &'a Expr) -> Val {
+		let value = self.expr_inner(expr);
+		self.convert(value, Repr::of(&expr.ty))
+	}
+
+	fn expr_inner(&mut self, expr: &'a Expr) -> Val {
+		let span = expr.span;
+		match &expr.kind {
+			ExprKind::Literal(literal) => match literal {
+				Literal::Int(value) => Val::new(Repr::Int, value.to_string()),
+				Literal::Float(value) => Val::new(Repr::Float, format!("0x{:016X}", value.to_bits())),
+				Literal::Bool(value) => Val::new(Repr::Bool, value.to_string()),
+				Literal::Null => Val::new(Repr::Ref, "null"),
+				Literal::String(text) => self.literal(text)
+			},
+			ExprKind::Var(var) => self.read(*var),
+			ExprKind::Unary { op, operand } => match op {
+				UnaryOp::Negate => {
+					let value = self.expr(operand);
+					match value.repr {
+						Repr::Int => self.call(Repr::Int, "df_negate", &[value]),
+						Repr::Float => self.value(Repr::Float, format!("fneg double {}", value.text)),
+						_ => self.unsupported("negating an 'Any'", span)
+					}
+				}
+				UnaryOp::Not => {
+					let value = self.expr(operand);
+					let value = self.truthy(value);
+					let not = self.not(&value);
+					Val::new(Repr::Bool, not)
+				}
+				UnaryOp::PreIncrement => self.update(operand, 1, span).1,
+				UnaryOp::PreDecrement => self.update(operand, -1, span).1,
+				UnaryOp::Typeof => {
+					let value = self.expr(operand);
+					let value = self.convert(value, Repr::Ref);
+					self.call(Repr::Ref, "df_type_of", &[value])
+				}
+			},
+			ExprKind::Binary { op, left, right } => {
+				let left_value = self.expr(left);
+				match op {
+					BinaryOp::And | BinaryOp::Or => {
+						let result = self.scratch(Repr::Bool);
+						let first = self.truthy(left_value);
+						let first = Val::new(Repr::Bool, first);
+						self.store(&first, &result);
+						let (rest, end) = (self.label("rest"), self.label("end"));
+						match op {
+							BinaryOp::And => self.branch(&first.text, &rest, &end),
+							_ => self.branch(&first.text, &end, &rest)
+						}
+						self.place(&rest);
+						let second = self.expr(right);
+						let second = Val::new(Repr::Bool, self.truthy(second));
+						self.store(&second, &result);
+						self.place(&end);
+						self.load(Repr::Bool, &result)
+					}
+					BinaryOp::Coalesce if left_value.repr == Repr::Ref => {
+						let repr = Repr::of(&expr.ty);
+						let result = self.scratch(repr);
+						let (null, present, end) = (self.label("null"), self.label("present"), self.label("end"));
+						let is_null = self.value(Repr::Bool, format!("icmp eq i8* {}, null", left_value.text));
+						self.branch(&is_null.text, &null, &present);
+						self.place(&present);
+						let left_value = self.convert(left_value, repr);
+						self.store(&left_value, &result);
+						self.jump(&end);
+						self.place(&null);
+						let right = self.expr(right);
+						let right = self.convert(right, repr);
+						self.store(&right, &result);
+						self.place(&end);
+						match repr {
+							Repr::Void => Val::void(),
+							repr => self.load(repr, &result)
+						}
+					}
+					BinaryOp::Coalesce => left_value,
+					BinaryOp::In => match &right.kind {
+						ExprKind::Range { start, end } if left_value.repr == Repr::Int => {
+							let (start, end) = (self.expr(start), self.expr(end));
+							let above = self.value(Repr::Bool, format!("icmp sle i64 {}, {}", start.text, left_value.text));
+							let below = self.value(Repr::Bool, format!("icmp slt i64 {}, {}", left_value.text, end.text));
+							self.value(Repr::Bool, format!("and i1 {}, {}", above.text, below.text))
+						}
+						_ if left.ty == Type::String && right.ty == Type::String => {
+							let text = self.expr(right);
+							self.call(Repr::Bool, "df_contains", &[text, left_value])
+						}
+						_ => self.unsupported("'in' on anything but a range literal or a string", span)
+					},
+					_ => {
+						let right_value = self.expr(right);
+						self.binary(*op, (left_value, &left.ty), (right_value, &right.ty), &expr.ty, span)
+					}
+				}
+			}
+			ExprKind::Assign { target, op, value } => {
+				let ExprKind::Var(var) = &target.kind else {
+					return self.unsupported("assigning to anything but a variable", target.span);
+				};
+				let value = match op {
+					Some(op) => {
+						let old = self.read(*var);
+						let new = self.expr(value);
+						self.binary(*op, (old, &target.ty), (new, &value.ty), &target.ty, span)
+					}
+					None => self.expr(value)
+				};
+				let value = self.convert(value, Repr::of(&target.ty));
+				self.assign(*var, value.clone());
+				value
+			}
+			ExprKind::Postfix { op, operand } => match op {
+				PostfixOp::Increment => self.update(operand, 1, span).0,
+				PostfixOp::Decrement => self.update(operand, -1, span).0,
+				PostfixOp::NonNull => {
+					let value = self.expr(operand);
+					if value.repr == Repr::Ref {
+						let (null, present) = (self.label("null"), self.label("present"));
+						let is_null = self.value(Repr::Bool, format!("icmp eq i8* {}, null", value.text));
+						self.branch(&is_null.text, &null, &present);
+						self.place(&null);
+						let message = self.constant("Used '!!' on a value that is null");
+						self.call_with(Repr::Void, "df_fail", &format!("i8* {}", message));
+						self.end("unreachable");
+						self.place(&present);
+					}
+					value
+				}
+				PostfixOp::Propagate => self.unsupported("'?'", span)
+			},
+			ExprKind::Is { operand, ty, negated } => {
+				let value = self.expr(operand);
+				let value = self.convert(value, Repr::Ref);
+				let Some(mask) = mask(ty) else {
+					return self.unsupported("'is' with classes", span);
+				};
+				let is = self.call_with(Repr::Bool, "df_is", &format!("i8* {}, i32 {}", value.text, mask));
+				match negated {
+					true => Val::new(Repr::Bool, self.not(&is.text)),
+					false => is
+				}
+			}
+			ExprKind::Coerce { value, coercion } => {
+				let inner = self.expr(value);
+				match coercion {
+					Coercion::IntToFloat => self.convert(inner, Repr::Float),
+					Coercion::ToString if value.ty == Type::String => inner,
+					Coercion::ToString => {
+						let inner = self.convert(inner, Repr::Ref);
+						self.call(Repr::Ref, "df_to_string", &[inner])
+					}
+					Coercion::Cast => {
+						let Some(mask) = mask(&expr.ty) else {
+							return self.unsupported("using an 'Any' as an instance of a class", span);
+						};
+						let inner = self.convert(inner, Repr::Ref);
+						let name = self.constant(&expr.ty.to_string());
+						self.call_with(Repr::Ref, "df_cast", &format!("i8* {}, i32 {}, i8* {}", inner.text, mask, name))
+					}
+				}
+			}
+			ExprKind::If { cond, then_branch, else_branch } => {
+				let repr = Repr::of(&expr.ty);
+				let result = self.scratch(repr);
+				let cond = self.expr(cond);
+				let cond = self.truthy(cond);
+				let (then, otherwise, end) = (self.label("then"), self.label("else"), self.label("endif"));
+				self.branch(&cond, &then, &otherwise);
+				self.place(&then);
+				let value = self.expr(then_branch);
+				let value = self.convert(value, repr);
+				self.store(&value, &result);
+				self.jump(&end);
+				self.place(&otherwise);
+				let value = self.expr(else_branch);
+				let value = self.convert(value, repr);
+				self.store(&value, &result);
+				self.place(&end);
+				match repr {
+					Repr::Void => Val::void(),
+					repr => self.load(repr, &result)
+				}
+			}
+			ExprKind::Block(block) => match block.stmts.split_last() {
+				Some((Stmt { kind: StmtKind::Expr(last), .. }, rest)) => {
+					self.stmts(rest);
+					self.expr(last)
+				}
+				_ => {
+					self.block(block);
+					Val::void()
+				}
+			},
+			ExprKind::Call { callee, args } => self.call_expr(callee, args, span),
+			ExprKind::Member { object, name, target: MemberTarget::Builtin, safe: false, .. } if name == "length" && object.ty == Type::String => {
+				let object = self.expr(object);
+				self.call(Repr::Int, "df_length", &[object])
+			}
+			ExprKind::Return(value) => {
+				let value = value.as_ref().map(|value| self.expr(value));
+				self.ret(value);
+				Val::void()
+			}
+			ExprKind::Throw(value) => {
+				let value = self.expr(value);
+				self.throw(value);
+				Val::void()
+			}
+			ExprKind::Function(_) | ExprKind::Builtin(_) => self.unsupported("functions used as values", span),
+			ExprKind::Native(_) => self.unsupported("native functions", span),
+			ExprKind::This | ExprKind::Super | ExprKind::BackingField(_) | ExprKind::SuperCall { .. } | ExprKind::New { .. } | ExprKind::Variant { .. } => {
+				self.unsupported("classes", span)
+			}
+			ExprKind::Member { .. } => self.unsupported("this member", span),
+			ExprKind::Index { .. } | ExprKind::Array(_) => self.unsupported("arrays", span),
+			ExprKind::Map(_) | ExprKind::Set(_) => self.unsupported("maps and sets", span),
+			ExprKind::Range { .. } => self.unsupported("ranges outside 'for' and 'in'", span),
+			ExprKind::Lambda(_) => self.unsupported("lambdas", span),
+			ExprKind::Await(_) => self.unsupported("'await'", span)
+		}
+	}
+
+	// The old and new value of a variable `++` or `--` changes.
+	fn update(&mut self, target: &'a Expr, delta: i64, span: Span) -> (Val, Val) {
+		let ExprKind::Var(var) = &target.kind else {
+			let unsupported = self.unsupported("changing anything but a variable", target.span);
+			return (unsupported.clone(), unsupported);
+		};
+		let old = self.read(*var);
+		let new = match old.repr {
+			Repr::Int => self.call(Repr::Int, "df_add", &[old.clone(), Val::new(Repr::Int, delta.to_string())]),
+			Repr::Float => self.value(Repr::Float, format!("fadd double {}, {}", old.text, if delta > 0 { "1.0" } else { "-1.0" })),
+			_ => return (self.unsupported("changing an 'Any'", span), Val::void())
+		};
+		self.assign(*var, new.clone());
+		(old, new)
+	}
+
+	fn binary(&mut self, op: BinaryOp, left: (Val, &Type), right: (Val, &Type), ty: &Type, span: Span) -> Val {
+		let ((left, left_ty), (right, right_ty)) = (left, right);
+		match (op, left.repr, right.repr) {
+			(BinaryOp::Equal, ..) => Val::new(Repr::Bool, self.equal(left, right)),
+			(BinaryOp::NotEqual, ..) => {
+				let equal = self.equal(left, right);
+				Val::new(Repr::Bool, self.not(&equal))
+			}
+			(BinaryOp::Add, Repr::Ref, Repr::Ref) if *ty == Type::String => self.call(Repr::Ref, "df_concat", &[left, right]),
+			(_, Repr::Int, Repr::Int) => {
+				let function = match op {
+					BinaryOp::Add => "df_add",
+					BinaryOp::Subtract => "df_subtract",
+					BinaryOp::Multiply => "df_multiply",
+					BinaryOp::Divide => "df_divide",
+					BinaryOp::Modulo => "df_modulo",
+					BinaryOp::ShiftLeft => "df_shift_left",
+					BinaryOp::ShiftRight => "df_shift_right",
+					BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor => {
+						let instruction = match op {
+							BinaryOp::BitAnd => "and",
+							BinaryOp::BitOr => "or",
+							_ => "xor"
+						};
+						return self.value(Repr::Int, format!("{} i64 {}, {}", instruction, left.text, right.text));
+					}
+					_ => {
+						let condition = match op {
+							BinaryOp::Less => "slt",
+							BinaryOp::LessEqual => "sle",
+							BinaryOp::Greater => "sgt",
+							_ => "sge"
+						};
+						return self.value(Repr::Bool, format!("icmp {} i64 {}, {}", condition, left.text, right.text));
+					}
+				};
+				self.call(Repr::Int, function, &[left, right])
+			}
+			(_, Repr::Int | Repr::Float, Repr::Int | Repr::Float) if !matches!(op, BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor | BinaryOp::ShiftLeft | BinaryOp::ShiftRight) => {
+				let (left, right) = (self.convert(left, Repr::Float), self.convert(right, Repr::Float));
+				let (repr, instruction) = match op {
+					BinaryOp::Add => (Repr::Float, "fadd"),
+					BinaryOp::Subtract => (Repr::Float, "fsub"),
+					BinaryOp::Multiply => (Repr::Float, "fmul"),
+					BinaryOp::Divide => (Repr::Float, "fdiv"),
+					BinaryOp::Modulo => (Repr::Float, "frem"),
+					BinaryOp::Less => (Repr::Bool, "fcmp olt"),
+					BinaryOp::LessEqual => (Repr::Bool, "fcmp ole"),
+					BinaryOp::Greater => (Repr::Bool, "fcmp ogt"),
+					_ => (Repr::Bool, "fcmp oge")
+				};
+				self.value(repr, format!("{} double {}, {}", instruction, left.text, right.text))
+			}
+			(BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual, Repr::Ref, Repr::Ref)
+				if *left_ty == Type::String && *right_ty == Type::String =>
+			{
+				let order = self.call(Repr::Int, "df_compare", &[left, right]);
+				let condition = match op {
+					BinaryOp::Less => "slt",
+					BinaryOp::LessEqual => "sle",
+					BinaryOp::Greater => "sgt",
+					_ => "sge"
+				};
+				self.value(Repr::Bool, format!("icmp {} i32 {}, 0", condition, order.text))
+			}
+			_ => self.unsupported(&format!("'{}' on '{}' and '{}'", op.symbol(), left_ty, right_ty), span)
+		}
+	}
+
+	fn call_expr(&mut self, callee: &'a Expr, args: &'a [Expr], span: Span) -> Val {
+		match &callee.kind {
+			ExprKind::Function(id) => {
+				let function = self.program.function(*id);
+				if function.kind != FnKind::Function {
+					return self.unsupported("this call", span);
+				}
+				if args.len() != function.params.len() || function.params.iter().any(|param| param.variadic) {
+					return self.unsupported("calls that leave out arguments or pass a variable number of them", span);
+				}
+				if let Some(foreign) = &function.foreign {
+					if foreign.params.iter().chain([&foreign.ret]).any(|ctype| matches!(ctype, CType::Pointer | CType::NullablePointer)) {
+						return self.unsupported("C functions that take or return a Pointer", span);
+					}
+					return self.foreign(&function.name, foreign, args);
+				}
+				let mut values = Vec::new();
+				for (arg, param) in args.iter().zip(&function.params) {
+					let value = self.expr(arg);
+					values.push(self.convert(value, Repr::of(&param.ty)));
+				}
+				self.queue(*id);
+				let name = symbol(self.program, *id);
+				let args: Vec<String> = values.iter().map(Val::arg).collect();
+				let repr = Repr::of(&function.ret);
+				match repr {
+					Repr::Void => {
+						self.emit(format!("call void {}({})", name, args.join(", ")));
+						Val::void()
+					}
+					repr => self.value(repr, format!("call {} {}({})", repr.llvm(), name, args.join(", ")))
+				}
+			}
+			ExprKind::Builtin(builtin @ (Builtin::Print | Builtin::Println)) => {
+				let line = *builtin == Builtin::Println;
+				match args.first() {
+					Some(arg) => {
+						let value = self.expr(arg);
+						let value = self.convert(value, Repr::Ref);
+						self.call_with(Repr::Void, "df_print", &format!("i8* {}, i1 {}", value.text, line))
+					}
+					None if line => self.call(Repr::Void, "df_newline", &[]),
+					None => Val::void()
+				}
+			}
+			ExprKind::Member { object, name, target: MemberTarget::Builtin, safe: false, .. } if args.is_empty() => {
+				let receiver = self.expr(object);
+				match (name.as_str(), &object.ty) {
+					("toString", _) => {
+						let receiver = self.convert(receiver, Repr::Ref);
+						self.call(Repr::Ref, "df_to_string", &[receiver])
+					}
+					("toFloat", Type::Int) => self.convert(receiver, Repr::Float),
+					("toInt", Type::Float) => self.call(Repr::Int, "df_to_int", &[receiver]),
+					("isEmpty", Type::String) => {
+						let length = self.call(Repr::Int, "df_length", &[receiver]);
+						self.value(Repr::Bool, format!("icmp eq i64 {}, 0", length.text))
+					}
+					_ => self.unsupported(&format!("'{}' on a '{}'", name, object.ty), span)
+				}
+			}
+			ExprKind::Builtin(builtin) => self.unsupported(&format!("'{}'", builtin.name()), span),
+			ExprKind::Native(_) => self.unsupported("native functions", span),
+			ExprKind::Var(_) => self.unsupported("calls to local functions and lambdas", span),
+			ExprKind::Member { .. } => self.unsupported("methods", span),
+			_ => self.unsupported("this call", span)
+		}
+	}
+
+	// A call to the C function `name`, declared the first time.
+	fn foreign(&mut self, name: &str, foreign: &Foreign, args: &'a [Expr]) -> Val {
+		let ctype = |ctype: CType| match ctype {
+			CType::Int => "i64",
+			CType::Float => "double",
+			CType::Bool => "i32",
+			CType::String | CType::NullableString | CType::Pointer | CType::NullablePointer => "i8*",
+			CType::Void => "void"
+		};
+		if self.externs.insert(name.to_string()) {
+			let params: Vec<&str> = foreign.params.iter().map(|&param| ctype(param)).collect();
+			self.head.push_str(&format!("declare {} @{}({})\n", ctype(foreign.ret), name, params.join(", ")));
+		}
+		self.libraries.insert(foreign.library.clone());
+		// Every argument is there before any string is handed to C, so that
+		// making one cannot collect another while C has it
+		let mut values = Vec::new();
+		for (arg, &param) in args.iter().zip(&foreign.params) {
+			let value = self.expr(arg);
+			values.push(match param {
+				CType::Float => self.convert(value, Repr::Float),
+				CType::Int => self.convert(value, Repr::Int),
+				CType::Bool => self.convert(value, Repr::Bool),
+				_ => self.convert(value, Repr::Ref)
+			});
+		}
+		let mut passed = Vec::new();
+		for (value, &param) in values.into_iter().zip(&foreign.params) {
+			passed.push(match param {
+				CType::Bool => format!("i32 {}", self.value(Repr::Int, format!("zext i1 {} to i32", value.text)).text),
+				CType::String | CType::NullableString => format!("i8* {}", self.call(Repr::Ref, "df_to_c", &[value]).text),
+				_ => value.arg()
+			});
+		}
+		let call = format!("call {} @{}({})", ctype(foreign.ret), name, passed.join(", "));
+		match foreign.ret {
+			CType::Void => {
+				self.emit(call);
+				Val::void()
+			}
+			CType::Int => self.value(Repr::Int, call),
+			CType::Float => self.value(Repr::Float, call),
+			CType::Bool => {
+				let result = self.value(Repr::Int, call);
+				self.value(Repr::Bool, format!("icmp ne i32 {}, 0", result.text))
+			}
+			CType::Pointer | CType::NullablePointer => unreachable!("calls with pointers are not compiled"),
+			CType::String | CType::NullableString => {
+				let result = self.value(Repr::Ref, call);
+				let function = match foreign.ret {
+					CType::String => self.constant(name),
+					_ => "null".to_string()
+				};
+				self.call_with(Repr::Ref, "df_from_c", &format!("i8* {}, i8* {}", result.text, function))
+			}
+		}
+	}
+}
+
+// Text for a `c"..."` constant.
+fn escape(text: &str) -> String {
+	let mut escaped = String::new();
+	for byte in text.bytes() {
+		match byte {
+			b' '..=b'~' if byte != b'"' && byte != b'\\' => escaped.push(byte as char),
+			_ => escaped.push_str(&format!("\\{:02X}", byte))
+		}
+	}
+	escaped
+}
@@ -0,0 +1,58 @@
+pub mod lower;
+
+use std::fs;
+use std::path::Path;
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::llvm::lower::Lowered;
+
+// What programs built for the native target are linked with.
+pub const RUNTIME: &str = include_str!("runtime.c");
+
+// Builds so far, so that each has a directory of its own
+static BUILDS: AtomicUsize = AtomicUsize::new(0);
+
+// Makes an executable at `path` of a lowered program: `llc` compiles the IR
+// to an object file, and the C compiler compiles the runtime and links the
+// two, with the libraries `extern` functions are in.
+pub fn build(lowered: &Lowered, path: &Path) -> Result<(), String> {
+	let dir = std::env::temp_dir().join(format!("dotfun-native-{}-{}", process::id(), BUILDS.fetch_add(1, Ordering::Relaxed)));
+	fs::create_dir_all(&dir).map_err(|err| format!("Cannot create '{}': {}", dir.display(), err))?;
+	let result = build_in(lowered, path, &dir);
+	let _ = fs::remove_dir_all(&dir);
+	result
+}
+
+fn build_in(lowered: &Lowered, path: &Path, dir: &Path) -> Result<(), String> {
+	let (ir, object, runtime) = (dir.join("program.ll"), dir.join("program.o"), dir.join("runtime.c"));
+	for (file, text) in [(&ir, lowered.ir.as_str()), (&runtime, RUNTIME)] {
+		fs::write(file, text).map_err(|err| format!("Cannot write '{}': {}", file.display(), err))?;
+	}
+	let mut llc = Command::new(tool("LLC", "llc"));
+	llc.arg("-filetype=obj").arg("-relocation-model=pic").arg("-o").arg(&object).arg(&ir);
+	run(llc, "llc")?;
+	let mut cc = Command::new(tool("CC", "cc"));
+	cc.arg("-O2").arg("-o").arg(path).arg(&runtime).arg(&object).arg("-lm");
+	for library in &lowered.libraries {
+		match library.contains('/') {
+			true => cc.arg(library),
+			false => cc.arg(format!("-l:{}", library))
+		};
+	}
+	run(cc, "cc")
+}
+
+// The program to run for `name`, which the environment variable can name
+// instead.
+fn tool(variable: &str, name: &str) -> String {
+	std::env::var(variable).unwrap_or_else(|_| name.to_string())
+}
+
+fn run(mut command: Command, name: &str) -> Result<(), String> {
+	let output = command.output().map_err(|err| format!("Cannot run '{}', which the native target needs: {}", name, err))?;
+	match output.status.success() {
+		true => Ok(()),
+		false => Err(format!("'{}' failed:\n{}", name, String::from_utf8_lossy(&output.stderr).trim_end()))
+	}
+}
@@ -0,0 +1,564 @@
+// What programs built with `dotfun build --target native` are linked with:
+// the memory the generated code allocates, collected once nothing points to
+// it; the strings and boxed values it works with; and exceptions, which
+// `df_throw` takes to the innermost `try` with `longjmp`.
+//
+// Everything the generated code allocates is an `Object`: a string, or an
+// Int, Float or Bool boxed to go where any value can, like an `Any`. None
+// point to others, so collecting is marking those the stack, the globals
+// and the exception being thrown point to, and freeing the rest. The stack
+// is scanned conservatively: any word that is the address of an object
+// keeps it.
+
+#include <inttypes.h>
+#include <setjmp.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+enum { KIND_STRING, KIND_INT, KIND_FLOAT, KIND_BOOL };
+
+// In the masks `df_is` and `df_cast` take, for null
+#define NULL_BIT (1 << 4)
+
+typedef struct Object {
+	struct Object *next;
+	int32_t kind;
+	int32_t marked;
+	// The Int or Bool of a box, the bits of a Float, or a string's length in
+	// bytes
+	int64_t value;
+	// A string's bytes, with a NUL after them for C
+	char bytes[];
+} Object;
+
+static const char *KIND_NAMES[] = {"String", "Int", "Float", "Bool"};
+
+// ---------------------------------------------------------------------
+// Memory
+// ---------------------------------------------------------------------
+
+// Every object, and how many there are
+static Object *objects;
+static size_t count;
+// Collects once there are this many
+static size_t threshold = 10000;
+// The address of every object, for telling which words on the stack point to
+// one; open addressing, never more than half full
+static uintptr_t *table;
+static size_t capacity;
+// The top of the stack as `main` found it
+static char *stack_bottom;
+// Globals that can hold objects
+static Object ***roots;
+static size_t root_count;
+static size_t root_capacity;
+// The exception being thrown or caught, and whether it is an error the
+// runtime ran into rather than a value thrown
+static Object *thrown;
+static bool thrown_error;
+
+static size_t slot(uintptr_t address) {
+	return (address >> 4) * 11400714819323198485u & (capacity - 1);
+}
+
+static void insert(Object *object) {
+	if ((count + 1) * 2 > capacity) {
+		uintptr_t *old = table;
+		size_t old_capacity = capacity;
+		capacity = capacity ? capacity * 2 : 1024;
+		table = calloc(capacity, sizeof(uintptr_t));
+		for (size_t i = 0; i < old_capacity; i++) {
+			if (old[i]) {
+				size_t at = slot(old[i]);
+				while (table[at]) at = (at + 1) & (capacity - 1);
+				table[at] = old[i];
+			}
+		}
+		free(old);
+	}
+	size_t at = slot((uintptr_t)object);
+	while (table[at]) at = (at + 1) & (capacity - 1);
+	table[at] = (uintptr_t)object;
+}
+
+static Object *find(uintptr_t address) {
+	if (!capacity) return NULL;
+	for (size_t at = slot(address); table[at]; at = (at + 1) & (capacity - 1)) {
+		if (table[at] == address) return (Object *)address;
+	}
+	return NULL;
+}
+
+static void mark(Object *object) {
+	if (object) object->marked = 1;
+}
+
+// Not inlined, so that `registers` is below the frames of everything that
+// called it; `setjmp` puts the registers there, for the words in them.
+static __attribute__((noinline)) void mark_stack(void) {
+	jmp_buf registers;
+	setjmp(registers);
+	uintptr_t top = (uintptr_t)&registers & ~(uintptr_t)(sizeof(uintptr_t) - 1);
+	for (uintptr_t *word = (uintptr_t *)top; (char *)word < stack_bottom; word++) {
+		mark(find(*word));
+	}
+}
+
+static void collect(void) {
+	mark_stack();
+	for (size_t i = 0; i < root_count; i++) mark(*roots[i]);
+	mark(thrown);
+	memset(table, 0, capacity * sizeof(uintptr_t));
+	size_t live = 0;
+	Object **link = &objects;
+	while (*link) {
+		Object *object = *link;
+		if (object->marked) {
+			object->marked = 0;
+			link = &object->next;
+			live++;
+		} else {
+			*link = object->next;
+			free(object);
+		}
+	}
+	count = 0;
+	for (Object *object = objects; object; object = object->next) {
+		insert(object);
+		count++;
+	}
+	threshold = live * 2 > 10000 ? live * 2 : 10000;
+}
+
+static Object *allocate(int32_t kind, size_t bytes) {
+	if (count >= threshold) collect();
+	Object *object = malloc(sizeof(Object) + bytes);
+	if (!object) {
+		fputs("error: Out of memory\n", stderr);
+		exit(1);
+	}
+	object->kind = kind;
+	object->marked = 0;
+	object->next = objects;
+	objects = object;
+	insert(object);
+	count++;
+	return object;
+}
+
+// Keeps what the global at `slot` points to.
+void df_root(Object **slot) {
+	if (root_count == root_capacity) {
+		root_capacity = root_capacity ? root_capacity * 2 : 64;
+		roots = realloc(roots, root_capacity * sizeof(Object **));
+	}
+	roots[root_count++] = slot;
+}
+
+// ---------------------------------------------------------------------
+// Exceptions
+// ---------------------------------------------------------------------
+
+typedef struct Handler {
+	jmp_buf target;
+	struct Handler *outer;
+} Handler;
+
+// The `try` blocks running, innermost first
+static Handler *handlers;
+
+Object *df_to_string(Object *value);
+
+static _Noreturn void propagate(void) {
+	Handler *handler = handlers;
+	if (!handler) {
+		Object *text = df_to_string(thrown);
+		fflush(stdout);
+		fprintf(stderr, "error: %s%s\n", thrown_error ? "" : "Uncaught exception: ", text->bytes);
+		exit(1);
+	}
+	handlers = handler->outer;
+	jmp_buf target;
+	memcpy(target, handler->target, sizeof(jmp_buf));
+	free(handler);
+	longjmp(target, 1);
+}
+
+// Starts a `try` block, giving what the generated code passes to `setjmp`.
+void *df_try(void) {
+	Handler *handler = malloc(sizeof(Handler));
+	handler->outer = handlers;
+	handlers = handler;
+	return handler->target;
+}
+
+// Ends the innermost `try` block without anything thrown.
+void df_untry(void) {
+	Handler *handler = handlers;
+	handlers = handler->outer;
+	free(handler);
+}
+
+_Noreturn void df_throw(Object *value) {
+	thrown = value;
+	thrown_error = false;
+	propagate();
+}
+
+Object *df_string(const char *bytes, int64_t length);
+
+_Noreturn void df_fail(const char *message) {
+	thrown = NULL;
+	thrown = df_string(message, (int64_t)strlen(message));
+	thrown_error = true;
+	propagate();
+}
+
+// What was thrown, once the `try` block it was thrown in has jumped to its
+// catches.
+Object *df_caught(void) {
+	return thrown;
+}
+
+bool df_caught_error(void) {
+	return thrown_error;
+}
+
+// Throws again what was caught, as it was thrown.
+_Noreturn void df_rethrow(Object *value, bool error) {
+	thrown = value;
+	thrown_error = error;
+	propagate();
+}
+
+// ---------------------------------------------------------------------
+// Values
+// ---------------------------------------------------------------------
+
+Object *df_string(const char *bytes, int64_t length) {
+	Object *string = allocate(KIND_STRING, (size_t)length + 1);
+	string->value = length;
+	memcpy(string->bytes, bytes, (size_t)length);
+	string->bytes[length] = 0;
+	return string;
+}
+
+// A string literal, made the first time it is used and kept in `slot`.
+Object *df_literal(Object **slot, const char *bytes, int64_t length) {
+	if (!*slot) *slot = df_string(bytes, length);
+	return *slot;
+}
+
+Object *df_box_int(int64_t value) {
+	Object *box = allocate(KIND_INT, 0);
+	box->value = value;
+	return box;
+}
+
+Object *df_box_float(double value) {
+	Object *box = allocate(KIND_FLOAT, 0);
+	memcpy(&box->value, &value, sizeof(double));
+	return box;
+}
+
+Object *df_box_bool(bool value) {
+	Object *box = allocate(KIND_BOOL, 0);
+	box->value = value;
+	return box;
+}
+
+static const char *type_name(Object *value) {
+	return value ? KIND_NAMES[value->kind] : "Null";
+}
+
+static _Noreturn void cannot_use(Object *value, const char *type) {
+	char message[256];
+	snprintf(message, sizeof message, "Cannot use a '%s' as a '%s'", type_name(value), type);
+	df_fail(message);
+}
+
+int64_t df_unbox_int(Object *value) {
+	if (!value || value->kind != KIND_INT) cannot_use(value, "Int");
+	return value->value;
+}
+
+double df_unbox_float(Object *value) {
+	if (!value || value->kind != KIND_FLOAT) cannot_use(value, "Float");
+	double unboxed;
+	memcpy(&unboxed, &value->value, sizeof(double));
+	return unboxed;
+}
+
+bool df_unbox_bool(Object *value) {
+	if (!value || value->kind != KIND_BOOL) cannot_use(value, "Bool");
+	return value->value != 0;
+}
+
+// Only `true` is: conditions on anything else are false.
+bool df_truthy(Object *value) {
+	return value && value->kind == KIND_BOOL && value->value;
+}
+
+// What `typeof` says.
+Object *df_type_of(Object *value) {
+	const char *name = type_name(value);
+	return df_string(name, (int64_t)strlen(name));
+}
+
+// Whether the value is of one of the kinds in `mask`.
+bool df_is(Object *value, int32_t mask) {
+	return value ? (mask >> value->kind) & 1 : (mask & NULL_BIT) != 0;
+}
+
+// The value, when it is of one of the kinds in `mask`, which `type` names.
+Object *df_cast(Object *value, int32_t mask, const char *type) {
+	if (!df_is(value, mask)) cannot_use(value, type);
+	return value;
+}
+
+bool df_equal(Object *left, Object *right) {
+	if (!left || !right) return left == right;
+	if (left->kind == KIND_INT && right->kind == KIND_FLOAT) return df_equal(right, left);
+	if (left->kind == KIND_FLOAT && right->kind == KIND_INT) {
+		double value = df_unbox_float(left);
+		return value == (double)right->value && value >= -9223372036854775808.0 && value < 9223372036854775808.0 && (int64_t)value == right->value;
+	}
+	if (left->kind != right->kind) return false;
+	switch (left->kind) {
+	case KIND_STRING:
+		return left->value == right->value && memcmp(left->bytes, right->bytes, (size_t)left->value) == 0;
+	case KIND_FLOAT:
+		return df_unbox_float(left) == df_unbox_float(right);
+	default:
+		return left->value == right->value;
+	}
+}
+
+int32_t df_compare(Object *left, Object *right) {
+	size_t shorter = (size_t)(left->value < right->value ? left->value : right->value);
+	int order = memcmp(left->bytes, right->bytes, shorter);
+	if (order) return order < 0 ? -1 : 1;
+	return left->value < right->value ? -1 : left->value > right->value;
+}
+
+Object *df_concat(Object *left, Object *right) {
+	Object *string = allocate(KIND_STRING, (size_t)(left->value + right->value) + 1);
+	string->value = left->value + right->value;
+	memcpy(string->bytes, left->bytes, (size_t)left->value);
+	memcpy(string->bytes + left->value, right->bytes, (size_t)right->value);
+	string->bytes[string->value] = 0;
+	return string;
+}
+
+bool df_contains(Object *text, Object *item) {
+	if (item->value == 0) return true;
+	for (int64_t at = 0; at + item->value <= text->value; at++) {
+		if (memcmp(text->bytes + at, item->bytes, (size_t)item->value) == 0) return true;
+	}
+	return false;
+}
+
+// In characters, not bytes.
+int64_t df_length(Object *string) {
+	int64_t length = 0;
+	for (int64_t at = 0; at < string->value; at++) {
+		if ((string->bytes[at] & 0xC0) != 0x80) length++;
+	}
+	return length;
+}
+
+// A `char*` from C as a string, or null for NULL, unless `function`, which
+// returned it, is declared to return a `String` that cannot be null.
+Object *df_from_c(const char *text, const char *function) {
+	if (text) return df_string(text, (int64_t)strlen(text));
+	if (function) {
+		char message[256];
+		snprintf(message, sizeof message, "'%s' returned NULL for a String", function);
+		df_fail(message);
+	}
+	return NULL;
+}
+
+const char *df_to_c(Object *string) {
+	return string ? string->bytes : NULL;
+}
+
+// As the shortest digits that read back as the same value, in decimal
+// unless it is tiny or huge: `1.0`, `0.25`, `1e16`, `1.5e-5`.
+static void format_float(double value, char *out, size_t size) {
+	if (value != value) {
+		snprintf(out, size, "NaN");
+		return;
+	}
+	if (value == 1.0 / 0.0 || value == -1.0 / 0.0) {
+		snprintf(out, size, value > 0 ? "inf" : "-inf");
+		return;
+	}
+	char buffer[64];
+	for (int precision = 0; precision < 17; precision++) {
+		snprintf(buffer, sizeof buffer, "%.*e", precision, value);
+		if (strtod(buffer, NULL) == value) break;
+	}
+	// `buffer` is `-d.ddde+xx`: gather its digits and exponent
+	char digits[32];
+	size_t length = 0;
+	const char *at = buffer;
+	bool negative = *at == '-';
+	if (negative) at++;
+	for (; *at != 'e'; at++) {
+		if (*at != '.') digits[length++] = *at;
+	}
+	while (length > 1 && digits[length - 1] == '0') length--;
+	digits[length] = 0;
+	int exponent = atoi(at + 1);
+	double magnitude = negative ? -value : value;
+	char *write = out;
+	char *end = out + size - 1;
+	if (negative && write < end) *write++ = '-';
+	if (magnitude != 0 && (magnitude < 1e-4 || magnitude >= 1e16)) {
+		*write++ = digits[0];
+		if (length > 1) {
+			*write++ = '.';
+			for (size_t i = 1; i < length && write < end; i++) *write++ = digits[i];
+		}
+		snprintf(write, (size_t)(end - write) + 1, "e%d", exponent);
+		return;
+	}
+	if (exponent < 0) {
+		*write++ = '0';
+		*write++ = '.';
+		for (int i = 0; i < -exponent - 1 && write < end; i++) *write++ = '0';
+		for (size_t i = 0; i < length && write < end; i++) *write++ = digits[i];
+	} else {
+		for (int i = 0; i <= exponent && write < end; i++) *write++ = (size_t)i < length ? digits[i] : '0';
+		*write++ = '.';
+		if ((size_t)exponent + 1 < length) {
+			for (size_t i = (size_t)exponent + 1; i < length && write < end; i++) *write++ = digits[i];
+		} else {
+			*write++ = '0';
+		}
+	}
+	*write = 0;
+}
+
+// What the value looks like printed or added to a string.
+Object *df_to_string(Object *value) {
+	char text[64];
+	if (!value) {
+		snprintf(text, sizeof text, "null");
+	} else {
+		switch (value->kind) {
+		case KIND_STRING:
+			return value;
+		case KIND_INT:
+			snprintf(text, sizeof text, "%" PRId64, value->value);
+			break;
+		case KIND_FLOAT:
+			format_float(df_unbox_float(value), text, sizeof text);
+			break;
+		default:
+			snprintf(text, sizeof text, "%s", value->value ? "true" : "false");
+		}
+	}
+	return df_string(text, (int64_t)strlen(text));
+}
+
+void df_print(Object *value, bool line) {
+	Object *text = df_to_string(value);
+	fwrite(text->bytes, 1, (size_t)text->value, stdout);
+	if (line) putchar('\n');
+}
+
+// `println()`, with nothing to print.
+void df_newline(void) {
+	putchar('\n');
+}
+
+// ---------------------------------------------------------------------
+// Arithmetic
+// ---------------------------------------------------------------------
+
+// `Int`s do not wrap around, but throw like they do in the VM.
+static _Noreturn void overflow(void) {
+	df_fail("Integer overflow");
+}
+
+int64_t df_add(int64_t left, int64_t right) {
+	int64_t result;
+	if (__builtin_add_overflow(left, right, &result)) overflow();
+	return result;
+}
+
+int64_t df_subtract(int64_t left, int64_t right) {
+	int64_t result;
+	if (__builtin_sub_overflow(left, right, &result)) overflow();
+	return result;
+}
+
+int64_t df_multiply(int64_t left, int64_t right) {
+	int64_t result;
+	if (__builtin_mul_overflow(left, right, &result)) overflow();
+	return result;
+}
+
+int64_t df_divide(int64_t left, int64_t right) {
+	if (right == 0) df_fail("Division by zero");
+	if (left == INT64_MIN && right == -1) overflow();
+	return left / right;
+}
+
+int64_t df_modulo(int64_t left, int64_t right) {
+	if (right == 0) df_fail("Division by zero");
+	if (left == INT64_MIN && right == -1) overflow();
+	return left % right;
+}
+
+static int64_t shift(int64_t by) {
+	if (by < 0 || by > 63) {
+		char message[96];
+		snprintf(message, sizeof message, "Cannot shift by %" PRId64 "; shifts go from 0 to 63", by);
+		df_fail(message);
+	}
+	return by;
+}
+
+int64_t df_shift_left(int64_t left, int64_t right) {
+	return (int64_t)((uint64_t)left << shift(right));
+}
+
+int64_t df_shift_right(int64_t left, int64_t right) {
+	return left >> shift(right);
+}
+
+int64_t df_negate(int64_t value) {
+	if (value == INT64_MIN) overflow();
+	return -value;
+}
+
+int64_t df_to_int(double value) {
+	if (!(value > -9223372036854775808.0 && value < 9223372036854775808.0)) {
+		char text[64];
+		char message[128];
+		format_float(value, text, sizeof text);
+		snprintf(message, sizeof message, "%s is too large to be an 'Int'", text);
+		df_fail(message);
+	}
+	return (int64_t)value;
+}
+
+// ---------------------------------------------------------------------
+// Entry
+// ---------------------------------------------------------------------
+
+// The generated code: every file's top-level code, then `main`.
+void df_program(void);
+
+int main(void) {
+	stack_bottom = __builtin_frame_address(0);
+	df_program();
+	fflush(stdout);
+	return 0;
+}
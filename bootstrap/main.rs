@@ -1,6 +1,27 @@
-mod lexer;
-mod ast;
+use std::env;
+use std::process::ExitCode;
 
-fn main() {
-    println!("Hello, world!");
+use glee::diagnostics::{ice, timings};
+use glee::driver::{self, args};
+
+// Counts what each thread allocates, for `--timings`
+#[global_allocator]
+static ALLOCATOR: timings::Counting = timings::Counting;
+
+fn main() -> ExitCode {
+	let options = match args::parse(env::args().skip(1).collect()) {
+		Ok(options) => options,
+		Err(message) => {
+			eprintln!("error: {}", message);
+			eprintln!("Run 'dotfun --help' for how to use it.");
+			return ExitCode::from(driver::USAGE_ERROR);
+		}
+	};
+	match ice::catch(|| driver::run(&options)) {
+		Ok(code) => ExitCode::from(code),
+		Err(ice) => {
+			eprint!("{}", ice);
+			ExitCode::from(driver::ICE)
+		}
+	}
 }
@@ -0,0 +1,292 @@
+use crate::ast::Program;
+use crate::ast::spans;
+use crate::ast::stmt::{Block, FnBody, Member, Stmt, StmtKind};
+use crate::diagnostics::Diagnostic;
+use crate::lexer::lexer::Lexer;
+use crate::lexer::token::{Span, Token};
+use crate::parser::parser::Parser;
+
+// Replace the bytes `start..end` of the source with `text`.
+#[derive(Debug, Clone)]
+pub struct TextEdit {
+	pub start: usize,
+	pub end: usize,
+	pub text: String
+}
+
+// How much of the file an edit caused to be reparsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reparse {
+	Body,
+	Member,
+	Declaration,
+	Full
+}
+
+// Keeps a file's tree up to date across edits. An edit that falls strictly
+// inside a function body, a class member or a top-level declaration only
+// relexes and reparses that construct; the spans of everything after it are
+// shifted in place. Anything else, or a fragment that no longer parses as the
+// same kind of construct, falls back to parsing the whole file.
+pub struct IncrementalParser {
+	source: String,
+	// `None` while the file has syntax errors
+	program: Option<Program>,
+	errors: Vec<Diagnostic>
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Target {
+	Stmt(usize),
+	Member(usize, usize),
+	// The block body of a top-level function (`None`) or of a member
+	Body(usize, Option<usize>)
+}
+
+impl IncrementalParser {
+	pub fn new(source: String) -> Self {
+		let mut parser = IncrementalParser { source, program: None, errors: Vec::new() };
+		parser.reparse_all();
+		parser
+	}
+
+	pub fn source(&self) -> &str {
+		&self.source
+	}
+
+	pub fn program(&self) -> Option<&Program> {
+		self.program.as_ref()
+	}
+
+	pub fn errors(&self) -> &[Diagnostic] {
+		&self.errors
+	}
+
+	// What a full parse of the source would return.
+	pub fn parsed(&self) -> Result<Program, Vec<Diagnostic>> {
+		self.program.clone().ok_or_else(|| self.errors.clone())
+	}
+
+	pub fn apply(&mut self, edit: TextEdit) -> Result<Reparse, String> {
+		if edit.start > edit.end
+			|| edit.end > self.source.len()
+			|| !self.source.is_char_boundary(edit.start)
+			|| !self.source.is_char_boundary(edit.end)
+		{
+			return Err(format!("Invalid edit range {}..{}", edit.start, edit.end));
+		}
+
+		let candidates = match &self.program {
+			Some(program) => candidates(program, &edit),
+			None => Vec::new()
+		};
+		let old_source = self.source.clone();
+		self.source.replace_range(edit.start..edit.end, &edit.text);
+
+		for (target, span) in candidates {
+			if self.reparse(&old_source, &edit, target, span) {
+				return Ok(match target {
+					Target::Body(..) => Reparse::Body,
+					Target::Member(..) => Reparse::Member,
+					Target::Stmt(_) => Reparse::Declaration
+				});
+			}
+		}
+
+		self.reparse_all();
+		Ok(Reparse::Full)
+	}
+
+	fn reparse_all(&mut self) {
+		let result = lex(Lexer::new(self.source.clone())).and_then(|tokens| Parser::new(tokens).parse());
+		match result {
+			Ok(program) => {
+				self.program = Some(program);
+				self.errors.clear();
+			}
+			Err(errors) => {
+				self.program = None;
+				self.errors = errors;
+			}
+		}
+	}
+
+	fn reparse(&mut self, old_source: &str, edit: &TextEdit, target: Target, span: Span) -> bool {
+		let Some(program) = &mut self.program else {
+			return false;
+		};
+
+		let delta = edit.text.len() as isize - (edit.end - edit.start) as isize;
+		let new_end = (span.end as isize + delta) as usize;
+		let fragment = self.source[span.start..new_end].to_string();
+
+		let Ok(mut tokens) = lex(Lexer::at(fragment, span.line, span.column)) else {
+			return false;
+		};
+		for token in &mut tokens {
+			token.span.start += span.start;
+			token.span.end += span.start;
+		}
+
+		let mut parser = Parser::new(tokens).with_next_id(program.next_node_id);
+		let parsed = match target {
+			Target::Stmt(_) => parser.parse_declaration().map(Node::Stmt),
+			Target::Member(..) => parser.parse_member().map(Node::Member),
+			Target::Body(..) => parser.parse_block().map(Node::Block)
+		};
+		let Ok(node) = parsed else {
+			return false;
+		};
+		program.next_node_id = parser.next_node_id();
+
+		// Move everything at or after the old end of the construct, and stretch
+		// whatever encloses it, before swapping in the new node.
+		let (old_line, old_column) = position(old_source, span.end);
+		let (new_line, new_column) = position(&self.source, new_end);
+		spans::for_each_span_mut(program, &mut |s| {
+			if s.start >= span.end {
+				s.start = (s.start as isize + delta) as usize;
+				s.end = (s.end as isize + delta) as usize;
+				if s.line == old_line {
+					s.column += new_column - old_column;
+				}
+				s.line += new_line - old_line;
+			} else if s.start <= span.start && s.end >= span.end {
+				s.end = (s.end as isize + delta) as usize;
+			}
+		});
+
+		match (target, node) {
+			// Doc comments sit in front of the reparsed range, so keep the old ones
+			(Target::Stmt(index), Node::Stmt(mut stmt)) => {
+				stmt.set_doc(program.stmts[index].doc().map(str::to_string));
+				program.stmts[index] = stmt;
+			}
+			(Target::Member(index, member), Node::Member(mut new)) => {
+				if let Some(members) = members_mut(&mut program.stmts[index]) {
+					new.set_doc(members[member].doc().map(str::to_string));
+					members[member] = new;
+				}
+			}
+			(Target::Body(index, member), Node::Block(block)) => {
+				if let Some(body) = body_mut(&mut program.stmts[index], member) {
+					*body = block;
+				}
+			}
+			_ => unreachable!("reparsed node does not match its target")
+		}
+		true
+	}
+}
+
+enum Node {
+	Stmt(Stmt),
+	Member(Member),
+	Block(Block)
+}
+
+fn lex(mut lexer: Lexer) -> Result<Vec<Token>, Vec<Diagnostic>> {
+	match lexer.lex() {
+		Ok(tokens) => Ok(tokens.clone()),
+		Err(err) => Err(vec![*err])
+	}
+}
+
+// Constructs enclosing the edit, innermost first. An edit touching the first
+// or last byte of a construct could merge it with its neighbours, so only
+// edits strictly inside count.
+fn candidates(program: &Program, edit: &TextEdit) -> Vec<(Target, Span)> {
+	let inside = |span: Span| span.start < edit.start && edit.end < span.end;
+	let mut found = Vec::new();
+
+	let Some(index) = program.stmts.iter().position(|stmt| inside(stmt.span)) else {
+		return found;
+	};
+	let stmt = &program.stmts[index];
+
+	if let StmtKind::Function(decl) = &stmt.kind
+		&& let Some(FnBody::Block(body)) = &decl.body
+		&& inside(body.span)
+	{
+		found.push((Target::Body(index, None), body.span));
+	}
+
+	if let Some(members) = members(stmt)
+		&& let Some(member) = members.iter().position(|m| inside(member_span(m)))
+	{
+		if let Some(body) = member_body(&members[member])
+			&& inside(body)
+		{
+			found.push((Target::Body(index, Some(member)), body));
+		}
+		found.push((Target::Member(index, member), member_span(&members[member])));
+	}
+
+	found.push((Target::Stmt(index), stmt.span));
+	found
+}
+
+fn members(stmt: &Stmt) -> Option<&Vec<Member>> {
+	match &stmt.kind {
+		StmtKind::Class(decl) => Some(&decl.members),
+		StmtKind::Interface(decl) => Some(&decl.members),
+		StmtKind::Enum(decl) => Some(&decl.members),
+		_ => None
+	}
+}
+
+fn members_mut(stmt: &mut Stmt) -> Option<&mut Vec<Member>> {
+	match &mut stmt.kind {
+		StmtKind::Class(decl) => Some(&mut decl.members),
+		StmtKind::Interface(decl) => Some(&mut decl.members),
+		StmtKind::Enum(decl) => Some(&mut decl.members),
+		_ => None
+	}
+}
+
+fn member_span(member: &Member) -> Span {
+	match member {
+		Member::Field(field) => field.span,
+		Member::Method(method) => method.span,
+		Member::Constructor(ctor) => ctor.span
+	}
+}
+
+fn member_body(member: &Member) -> Option<Span> {
+	match member {
+		Member::Method(method) => match &method.body {
+			Some(FnBody::Block(body)) => Some(body.span),
+			_ => None
+		},
+		Member::Constructor(ctor) => Some(ctor.body.span),
+		Member::Field(_) => None
+	}
+}
+
+fn body_mut(stmt: &mut Stmt, member: Option<usize>) -> Option<&mut Block> {
+	match member {
+		None => match &mut stmt.kind {
+			StmtKind::Function(decl) => match &mut decl.body {
+				Some(FnBody::Block(body)) => Some(body),
+				_ => None
+			},
+			_ => None
+		},
+		Some(member) => match members_mut(stmt)?.get_mut(member)? {
+			Member::Method(method) => match &mut method.body {
+				Some(FnBody::Block(body)) => Some(body),
+				_ => None
+			},
+			Member::Constructor(ctor) => Some(&mut ctor.body),
+			Member::Field(_) => None
+		}
+	}
+}
+
+// 1-based line and byte column of `offset`, matching the lexer's counting.
+fn position(source: &str, offset: usize) -> (i64, i64) {
+	let before = &source[..offset];
+	let line = before.bytes().filter(|&b| b == b'\n').count() as i64 + 1;
+	let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+	(line, (offset - line_start) as i64 + 1)
+}
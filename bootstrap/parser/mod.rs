@@ -0,0 +1,4 @@
+pub mod incremental;
+#[allow(clippy::module_inception)]
+pub mod parser;
+pub mod precedence;
@@ -0,0 +1,2094 @@
+use std::collections::HashMap;
+
+use crate::ast::annotations::{Annotation, AnnotationDecl, AnnotationParam};
+use crate::ast::expr::{Expr, ExprKind, InterpolationPart, LambdaBody, Literal, PostfixOp, UnaryOp};
+use crate::ast::stmt::{
+	Accessor, AccessorKind, Binding, Block, CatchClause, ClassDecl, ConstructorDecl, EnumDecl, EnumVariant,
+	FieldDecl, FnBody, FnDecl, ForKind, ForLoop, ForPattern, ImportDecl, InterfaceDecl, Member, Modifiers, Param, Stmt, StmtKind,
+	StructDecl, SwitchCase, TypeAliasDecl, VarDecl, Visibility
+};
+use crate::ast::types::{TypeExpr, TypeExprKind, TypeParam};
+use crate::ast::{NodeId, Program};
+use crate::diagnostics::{Diagnostic, suggest};
+use crate::lexer::token::{Span, Token, TokenType};
+use crate::parser::precedence::{self, Assoc, Precedence};
+
+// Boxed, as errors are much bigger than most of what is parsed
+type PResult<T> = Result<T, Box<Diagnostic>>;
+
+const MAX_NESTING: usize = 200;
+
+pub struct Parser {
+	tokens: Vec<Token>,
+	current: usize,
+	next_id: u32,
+	errors: Vec<Diagnostic>,
+	depth: usize,
+	// Doc comments keyed by the index of the token they precede
+	docs: HashMap<usize, String>
+}
+
+impl Parser {
+	pub fn new(mut tokens: Vec<Token>) -> Self {
+		if tokens.last().is_none_or(|t| t.token_type != TokenType::Eof) {
+			// Place the end right after the last token, or at 1:1 for no tokens
+			let span = match tokens.last() {
+				Some(last) => Span {
+					start: last.span.end,
+					end: last.span.end,
+					line: last.span.line,
+					column: last.span.column + (last.span.end - last.span.start) as i64
+				},
+				None => Span { line: 1, column: 1, ..Span::default() }
+			};
+			tokens.push(Token {
+				token_type: TokenType::Eof,
+				lexeme: String::new(),
+				line: span.line,
+				column: span.column,
+				span
+			});
+		}
+
+		// Doc comments are not part of the grammar. Ones on consecutive lines
+		// merge; otherwise only the one nearest the declaration is kept.
+		let mut docs = HashMap::new();
+		let mut pending: Option<(String, i64)> = None;
+		let mut code = Vec::with_capacity(tokens.len());
+		for token in tokens {
+			if token.token_type != TokenType::DocComment {
+				if let Some((text, _)) = pending.take() {
+					docs.insert(code.len(), text);
+				}
+				code.push(token);
+				continue;
+			}
+			pending = match pending {
+				Some((mut text, line)) if line + 1 == token.line => {
+					text.push('\n');
+					text.push_str(&token.lexeme);
+					Some((text, token.line))
+				}
+				_ => Some((token.lexeme, token.line))
+			};
+		}
+
+		Parser {
+			tokens: code,
+			current: 0,
+			next_id: 0,
+			errors: Vec::new(),
+			depth: 0,
+			docs
+		}
+	}
+
+	// Continues numbering nodes from `next_id`, so a reparsed fragment can be
+	// spliced into an existing tree without id collisions.
+	pub fn with_next_id(mut self, next_id: u32) -> Self {
+		self.next_id = next_id;
+		self
+	}
+
+	pub fn next_node_id(&self) -> u32 {
+		self.next_id
+	}
+
+	pub fn parse(&mut self) -> Result<Program, Vec<Diagnostic>> {
+		let mut stmts = Vec::new();
+
+		while !self.is_at_end() {
+			let before = self.current;
+			match self.declaration() {
+				Ok(stmt) => stmts.push(stmt),
+				Err(err) => self.recover(*err, before)
+			}
+		}
+
+		if self.errors.is_empty() {
+			Ok(Program { stmts, next_node_id: self.next_id })
+		} else {
+			Err(std::mem::take(&mut self.errors))
+		}
+	}
+
+	// Single-construct entry points used for incremental reparsing. The
+	// tokens must hold exactly one such construct.
+	pub fn parse_declaration(&mut self) -> Result<Stmt, Vec<Diagnostic>> {
+		self.fragment(Parser::declaration)
+	}
+
+	pub fn parse_member(&mut self) -> Result<Member, Vec<Diagnostic>> {
+		self.fragment(Parser::member)
+	}
+
+	pub fn parse_block(&mut self) -> Result<Block, Vec<Diagnostic>> {
+		self.fragment(Parser::block)
+	}
+
+	fn fragment<T>(&mut self, parse: fn(&mut Parser) -> PResult<T>) -> Result<T, Vec<Diagnostic>> {
+		match parse(self) {
+			Ok(node) if self.is_at_end() && self.errors.is_empty() => return Ok(node),
+			Ok(_) if !self.is_at_end() => self.errors.push(*self.error_at_current("E0100", "Expected end of input")),
+			Ok(_) => {}
+			Err(err) => self.errors.push(*err)
+		}
+		Err(std::mem::take(&mut self.errors))
+	}
+
+	// ---------------------------------------------------------------------
+	// Declarations
+	// ---------------------------------------------------------------------
+
+	fn declaration(&mut self) -> PResult<Stmt> {
+		self.nested(Parser::declaration_inner)
+	}
+
+	fn declaration_inner(&mut self) -> PResult<Stmt> {
+		let start = self.peek().span;
+		let doc = self.doc_comment();
+		let annotations = self.annotations()?;
+		let modifiers = self.modifiers();
+		let decorated = self.peek().span.start != start.start;
+		let doc = doc.or_else(|| self.doc_comment());
+		if modifiers.library.is_some() && !self.check(TokenType::Function) {
+			return Err(self.error_at_current("E0100", "Expected 'fn' after 'extern'"));
+		}
+
+		match self.peek().token_type {
+			TokenType::Function => {
+				let mut decl = self.function(annotations, modifiers, start)?;
+				decl.doc = doc;
+				Ok(self.stmt(StmtKind::Function(Box::new(decl)), start))
+			}
+			TokenType::Class => {
+				let mut decl = self.class(annotations, modifiers, start)?;
+				decl.doc = doc;
+				Ok(self.stmt(StmtKind::Class(decl), start))
+			}
+			TokenType::Interface => {
+				let mut decl = self.interface(annotations, modifiers, start)?;
+				decl.doc = doc;
+				Ok(self.stmt(StmtKind::Interface(decl), start))
+			}
+			TokenType::Enum => {
+				let mut decl = self.enum_decl(annotations, modifiers, start)?;
+				decl.doc = doc;
+				Ok(self.stmt(StmtKind::Enum(decl), start))
+			}
+			TokenType::Struct => {
+				let mut decl = self.struct_decl(annotations, modifiers, start)?;
+				decl.doc = doc;
+				Ok(self.stmt(StmtKind::Struct(decl), start))
+			}
+			TokenType::Annotation => {
+				let mut decl = self.annotation_decl(start)?;
+				decl.doc = doc;
+				Ok(self.stmt(StmtKind::Annotation(decl), start))
+			}
+			TokenType::Type => {
+				let mut decl = self.type_alias(modifiers, start)?;
+				decl.doc = doc;
+				Ok(self.stmt(StmtKind::TypeAlias(decl), start))
+			}
+			TokenType::Val | TokenType::Mut | TokenType::Const if !decorated => {
+				let mut decl = self.var_decl()?;
+				decl.doc = doc;
+				self.end_statement()?;
+				Ok(self.stmt(StmtKind::Var(decl), start))
+			}
+			TokenType::Import if !decorated => self.import(start),
+			TokenType::Package if !decorated => self.package(start),
+			_ if decorated => Err(self.error_at_current("E0100", "Expected a declaration after modifiers")),
+			_ => self.statement()
+		}
+	}
+
+	fn annotations(&mut self) -> PResult<Vec<Annotation>> {
+		let mut annotations = Vec::new();
+
+		while self.check(TokenType::AT) {
+			let start = self.advance().span;
+			let name = self.qualified_name("Expected annotation name after '@'")?;
+			let mut args = Vec::new();
+			if self.check(TokenType::LeftParen) && self.same_line() {
+				self.advance();
+				args = self.arguments()?;
+			}
+			annotations.push(Annotation {
+				name,
+				args,
+				span: start.to(self.previous().span)
+			});
+		}
+
+		Ok(annotations)
+	}
+
+	fn modifiers(&mut self) -> Modifiers {
+		let mut modifiers = Modifiers::default();
+
+		loop {
+			match self.peek().token_type {
+				TokenType::Private => modifiers.visibility = Visibility::Private,
+				TokenType::Protected => modifiers.visibility = Visibility::Protected,
+				TokenType::Override => modifiers.is_override = true,
+				TokenType::Data => modifiers.is_data = true,
+				TokenType::Sealed => modifiers.is_sealed = true,
+				TokenType::Async if self.peek_next_type() == TokenType::Function => modifiers.is_async = true,
+				TokenType::Identifier if self.peek().lexeme == "abstract" && self.next_starts_declaration() => {
+					modifiers.is_abstract = true
+				}
+				TokenType::Identifier if self.peek().lexeme == "static" && self.next_starts_declaration() => modifiers.is_static = true,
+				TokenType::Identifier if self.peek().lexeme == "extern" && self.peek_next_type() == TokenType::StringLiteral => {
+					self.advance();
+					let library = self.peek();
+					let inner = library.lexeme.get(1..library.lexeme.len().saturating_sub(1)).unwrap_or("");
+					modifiers.library = Some(unescape(inner, library.span).unwrap_or_else(|_| inner.to_string()).into());
+				}
+				_ => break
+			}
+			self.advance();
+		}
+
+		modifiers
+	}
+
+	fn next_starts_declaration(&self) -> bool {
+		let next = self.peek_at(1);
+		match next.token_type {
+			TokenType::Function
+			| TokenType::Class
+			| TokenType::Interface
+			| TokenType::Private
+			| TokenType::Protected
+			| TokenType::Override
+			| TokenType::Sealed
+			| TokenType::Async
+			| TokenType::Val
+			| TokenType::Mut => true,
+			TokenType::Identifier => matches!(next.lexeme.as_str(), "static" | "abstract"),
+			_ => false
+		}
+	}
+
+	// Whether the current word is a modifier starting a declaration, rather
+	// than a name.
+	fn starts_declaration(&self) -> bool {
+		match self.peek().lexeme.as_str() {
+			"static" | "abstract" => self.next_starts_declaration(),
+			"extern" => self.peek_next_type() == TokenType::StringLiteral,
+			_ => false
+		}
+	}
+
+	fn function(&mut self, annotations: Vec<Annotation>, modifiers: Modifiers, start: Span) -> PResult<FnDecl> {
+		self.consume(TokenType::Function, "Expected 'fn'")?;
+
+		// Type parameters go before the name when an extension needs them for
+		// its receiver (`fn <T> List<T>.first()`), after it otherwise.
+		let mut type_params = self.type_params()?;
+		let (receiver, name_token) = self.function_name()?;
+		let name = name_token.lexeme;
+		let name_span = name_token.span;
+		if self.check(TokenType::Less) {
+			if !type_params.is_empty() {
+				return Err(self.error_at_current("E0101", "Type parameters were already declared before the function name"));
+			}
+			type_params = self.type_params()?;
+		}
+
+		self.consume(TokenType::LeftParen, "Expected '(' after function name")?;
+		let params = self.parameters()?;
+		let return_type = self.return_type()?;
+
+		let mut throws = Vec::new();
+		if self.check_identifier("throws") {
+			self.advance();
+			loop {
+				throws.push(self.type_expr()?);
+				if !self.match_token(TokenType::Comma) {
+					break;
+				}
+			}
+		}
+
+		let body = self.function_body()?;
+
+		Ok(FnDecl {
+			id: self.next_id(),
+			name,
+			name_span,
+			receiver,
+			modifiers,
+			annotations,
+			doc: None,
+			type_params,
+			params,
+			return_type,
+			throws,
+			body,
+			span: start.to(self.previous().span)
+		})
+	}
+
+	// `name`, or `Receiver.name` for extension functions. The receiver may be
+	// qualified (`a.b.Type.name`), generic (`List<T>.first`), an array type
+	// (`Int[].sum`) or nullable (`String?.orEmpty`).
+	fn function_name(&mut self) -> PResult<(Option<TypeExpr>, Token)> {
+		let start = self.peek().span;
+		let mut segments = vec![self.consume(TokenType::Identifier, "Expected function name")?.clone()];
+		while self.check(TokenType::Dot) && self.peek_next_type() == TokenType::Identifier {
+			self.advance();
+			segments.push(self.advance().clone());
+		}
+
+		let generic_receiver = self.check(TokenType::Less) && self.angle_group_followed_by(TokenType::Dot);
+		let suffixed_receiver = matches!(
+			(self.peek().token_type, self.peek_next_type()),
+			(TokenType::LeftBracket, TokenType::RightBracket) | (TokenType::Question, TokenType::Dot)
+		);
+
+		if !generic_receiver && !suffixed_receiver {
+			let name = segments.pop().expect("function name segment");
+			let receiver = if segments.is_empty() { None } else { Some(named_type(&segments, start)) };
+			return Ok((receiver, name));
+		}
+
+		let mut receiver = named_type(&segments, start);
+		if generic_receiver {
+			let args = self.type_args()?;
+			if let TypeExprKind::Named { args: slot, .. } = &mut receiver.kind {
+				*slot = args;
+			}
+			receiver.span = start.to(self.previous().span);
+		}
+		let receiver = self.type_suffixes(receiver, start)?;
+
+		self.consume(TokenType::Dot, "Expected '.' and a function name after extension receiver")?;
+		let name = self.consume(TokenType::Identifier, "Expected function name after '.'")?.clone();
+		Ok((Some(receiver), name))
+	}
+
+	fn return_type(&mut self) -> PResult<Option<TypeExpr>> {
+		if self.match_token(TokenType::Colon) || self.match_token(TokenType::Arrow) {
+			Ok(Some(self.type_expr()?))
+		} else {
+			Ok(None)
+		}
+	}
+
+	// Abstract and interface members have no body at all.
+	fn function_body(&mut self) -> PResult<Option<FnBody>> {
+		if self.check(TokenType::LeftBrace) {
+			return Ok(Some(FnBody::Block(self.block()?)));
+		}
+		if self.match_token(TokenType::Equal) {
+			let expr = self.expression()?;
+			self.match_token(TokenType::Semicolon);
+			return Ok(Some(FnBody::Expr(expr)));
+		}
+		self.match_token(TokenType::Semicolon);
+		Ok(None)
+	}
+
+	// Parses a parameter list after the opening '('.
+	fn parameters(&mut self) -> PResult<Vec<Param>> {
+		let mut params = Vec::new();
+
+		if !self.check(TokenType::RightParen) {
+			loop {
+				params.push(self.parameter()?);
+				if !self.match_token(TokenType::Comma) || self.check(TokenType::RightParen) {
+					break;
+				}
+			}
+		}
+
+		self.consume(TokenType::RightParen, "Expected ')' after parameters")?;
+		Ok(params)
+	}
+
+	fn parameter(&mut self) -> PResult<Param> {
+		let start = self.peek().span;
+		let variadic = self.match_token(TokenType::Ellipsis);
+		let name = self.consume(TokenType::Identifier, "Expected parameter name")?.lexeme.clone();
+
+		let ty = if self.match_token(TokenType::Colon) { Some(self.type_expr()?) } else { None };
+		let default = if self.match_token(TokenType::Equal) { Some(self.expression()?) } else { None };
+
+		Ok(Param {
+			id: self.next_id(),
+			name,
+			ty,
+			default,
+			variadic,
+			span: start.to(self.previous().span)
+		})
+	}
+
+	fn supertypes(&mut self) -> PResult<Vec<TypeExpr>> {
+		let mut supertypes = Vec::new();
+		if self.match_token(TokenType::Colon) {
+			loop {
+				supertypes.push(self.type_expr()?);
+				if !self.match_token(TokenType::Comma) {
+					break;
+				}
+			}
+		}
+		Ok(supertypes)
+	}
+
+	fn class(&mut self, annotations: Vec<Annotation>, modifiers: Modifiers, start: Span) -> PResult<ClassDecl> {
+		self.consume(TokenType::Class, "Expected 'class'")?;
+		let name_token = self.consume(TokenType::Identifier, "Expected class name")?;
+		let name = name_token.lexeme.clone();
+		let name_span = name_token.span;
+		let type_params = self.type_params()?;
+		let supertypes = self.supertypes()?;
+
+		self.consume(TokenType::LeftBrace, "Expected '{' before class body")?;
+		let members = self.members()?;
+
+		Ok(ClassDecl {
+			id: self.next_id(),
+			name,
+			name_span,
+			modifiers,
+			annotations,
+			doc: None,
+			type_params,
+			supertypes,
+			members,
+			span: start.to(self.previous().span)
+		})
+	}
+
+	fn interface(&mut self, annotations: Vec<Annotation>, modifiers: Modifiers, start: Span) -> PResult<InterfaceDecl> {
+		self.consume(TokenType::Interface, "Expected 'interface'")?;
+		let name_token = self.consume(TokenType::Identifier, "Expected interface name")?;
+		let name = name_token.lexeme.clone();
+		let name_span = name_token.span;
+		let type_params = self.type_params()?;
+		let supertypes = self.supertypes()?;
+
+		self.consume(TokenType::LeftBrace, "Expected '{' before interface body")?;
+		let members = self.members()?;
+
+		Ok(InterfaceDecl {
+			id: self.next_id(),
+			name,
+			name_span,
+			modifiers,
+			annotations,
+			doc: None,
+			type_params,
+			supertypes,
+			members,
+			span: start.to(self.previous().span)
+		})
+	}
+
+	fn enum_decl(&mut self, annotations: Vec<Annotation>, modifiers: Modifiers, start: Span) -> PResult<EnumDecl> {
+		self.consume(TokenType::Enum, "Expected 'enum'")?;
+		let name_token = self.consume(TokenType::Identifier, "Expected enum name")?;
+		let name = name_token.lexeme.clone();
+		let name_span = name_token.span;
+		let supertypes = self.supertypes()?;
+
+		self.consume(TokenType::LeftBrace, "Expected '{' before enum body")?;
+
+		// Variants come first and may be separated by commas or just whitespace.
+		let mut variants = Vec::new();
+		while self.check(TokenType::Identifier) && !self.starts_declaration() {
+			let doc = self.doc_comment();
+			let token = self.advance().clone();
+			let mut args = Vec::new();
+			if self.match_token(TokenType::LeftParen) {
+				args = self.arguments()?;
+			}
+			variants.push(EnumVariant {
+				id: self.next_id(),
+				name: token.lexeme,
+				doc,
+				args,
+				span: token.span.to(self.previous().span)
+			});
+			self.match_token(TokenType::Comma);
+		}
+		self.match_token(TokenType::Semicolon);
+
+		let members = self.members()?;
+
+		Ok(EnumDecl {
+			id: self.next_id(),
+			name,
+			name_span,
+			modifiers,
+			annotations,
+			doc: None,
+			supertypes,
+			variants,
+			members,
+			span: start.to(self.previous().span)
+		})
+	}
+
+	fn struct_decl(&mut self, annotations: Vec<Annotation>, modifiers: Modifiers, start: Span) -> PResult<StructDecl> {
+		self.consume(TokenType::Struct, "Expected 'struct'")?;
+		let name_token = self.consume(TokenType::Identifier, "Expected struct name")?;
+		let name = name_token.lexeme.clone();
+		let name_span = name_token.span;
+		let type_params = self.type_params()?;
+
+		self.consume(TokenType::LeftBrace, "Expected '{' before struct body")?;
+
+		let mut fields = Vec::new();
+		while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+			let field_start = self.peek().span;
+			let field_doc = self.doc_comment();
+			let field_annotations = self.annotations()?;
+			let field_modifiers = self.modifiers();
+			let mutable = self.match_token(TokenType::Mut);
+			self.match_token(TokenType::Val);
+
+			let name_token = self.consume(TokenType::Identifier, "Expected field name")?.clone();
+			self.consume(TokenType::Colon, "Expected ':' and a type after struct field name")?;
+			let ty = self.type_expr()?;
+			let init = if self.match_token(TokenType::Equal) { Some(self.expression()?) } else { None };
+
+			fields.push(FieldDecl {
+				id: self.next_id(),
+				name: name_token.lexeme,
+				name_span: name_token.span,
+				modifiers: field_modifiers,
+				annotations: field_annotations,
+				doc: field_doc,
+				mutable,
+				ty: Some(ty),
+				init,
+				getter: None,
+				setter: None,
+				span: field_start.to(self.previous().span)
+			});
+
+			if !self.match_token(TokenType::Comma) {
+				self.match_token(TokenType::Semicolon);
+			}
+		}
+
+		self.consume(TokenType::RightBrace, "Expected '}' after struct body")?;
+
+		Ok(StructDecl {
+			id: self.next_id(),
+			name,
+			name_span,
+			modifiers,
+			annotations,
+			doc: None,
+			type_params,
+			fields,
+			span: start.to(self.previous().span)
+		})
+	}
+
+	fn annotation_decl(&mut self, start: Span) -> PResult<AnnotationDecl> {
+		self.consume(TokenType::Annotation, "Expected 'annotation'")?;
+		let name = self.consume(TokenType::Identifier, "Expected annotation name")?.lexeme.clone();
+		self.consume(TokenType::LeftBrace, "Expected '{' before annotation body")?;
+
+		let mut params = Vec::new();
+		while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+			let param_start = self.peek().span;
+			let optional = self.match_token(TokenType::Question);
+			let name = self.consume(TokenType::Identifier, "Expected annotation field name")?.lexeme.clone();
+			self.consume(TokenType::Colon, "Expected ':' after annotation field name")?;
+			let ty = self.type_expr()?;
+			params.push(AnnotationParam {
+				name,
+				ty,
+				optional,
+				span: param_start.to(self.previous().span)
+			});
+			if !self.match_token(TokenType::Comma) {
+				self.match_token(TokenType::Semicolon);
+			}
+		}
+
+		self.consume(TokenType::RightBrace, "Expected '}' after annotation body")?;
+
+		Ok(AnnotationDecl {
+			name,
+			doc: None,
+			params,
+			span: start.to(self.previous().span)
+		})
+	}
+
+	fn type_alias(&mut self, modifiers: Modifiers, start: Span) -> PResult<TypeAliasDecl> {
+		self.consume(TokenType::Type, "Expected 'type'")?;
+		let name_token = self.consume(TokenType::Identifier, "Expected type alias name")?.clone();
+		let type_params = self.type_params()?;
+		self.consume(TokenType::Equal, "Expected '=' after type alias name")?;
+		let ty = self.type_expr()?;
+		self.match_token(TokenType::Semicolon);
+
+		Ok(TypeAliasDecl {
+			id: self.next_id(),
+			name: name_token.lexeme,
+			name_span: name_token.span,
+			modifiers,
+			doc: None,
+			type_params,
+			ty,
+			span: start.to(self.previous().span)
+		})
+	}
+
+	// Parses class/interface/enum members up to and including the closing '}'.
+	fn members(&mut self) -> PResult<Vec<Member>> {
+		let mut members = Vec::new();
+
+		while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+			if self.match_token(TokenType::Semicolon) {
+				continue;
+			}
+			members.push(self.member()?);
+		}
+
+		self.consume(TokenType::RightBrace, "Expected '}' after body")?;
+		Ok(members)
+	}
+
+	fn member(&mut self) -> PResult<Member> {
+		let start = self.peek().span;
+		let doc = self.doc_comment();
+		let annotations = self.annotations()?;
+		let modifiers = self.modifiers();
+		let doc = doc.or_else(|| self.doc_comment());
+
+		match self.peek().token_type {
+			TokenType::Function => {
+				let mut decl = self.function(annotations, modifiers, start)?;
+				decl.doc = doc;
+				Ok(Member::Method(decl))
+			}
+			TokenType::Constructor => {
+				self.advance();
+				self.consume(TokenType::LeftParen, "Expected '(' after 'constructor'")?;
+				let params = self.parameters()?;
+				let body = self.block()?;
+				Ok(Member::Constructor(ConstructorDecl {
+					id: self.next_id(),
+					modifiers,
+					doc,
+					params,
+					body,
+					span: start.to(self.previous().span)
+				}))
+			}
+			TokenType::Val | TokenType::Mut => {
+				let mut decl = self.field(annotations, modifiers, start)?;
+				decl.doc = doc;
+				Ok(Member::Field(decl))
+			}
+			_ => Err(self.error_at_current("E0100", "Expected a field, method or constructor"))
+		}
+	}
+
+	fn field(&mut self, annotations: Vec<Annotation>, modifiers: Modifiers, start: Span) -> PResult<FieldDecl> {
+		let decl = self.var_decl()?;
+		self.match_token(TokenType::Semicolon);
+
+		let mut getter = None;
+		let mut setter = None;
+		while let Some(accessor) = self.accessor()? {
+			let slot = match accessor.kind {
+				AccessorKind::Get => &mut getter,
+				AccessorKind::Set => &mut setter
+			};
+			if slot.is_some() {
+				let message = format!("Duplicate {} accessor for '{}'", accessor_name(accessor.kind), decl.name);
+				return Err(Box::new(Diagnostic::error(message, accessor.span).with_code("E0102")));
+			}
+			*slot = Some(Box::new(accessor));
+			self.match_token(TokenType::Semicolon);
+		}
+
+		if let Some(setter) = &setter
+			&& !decl.mutable
+		{
+			let message = format!("'{}' is not mutable and cannot have a setter; declare it with 'mut val'", decl.name);
+			return Err(Box::new(Diagnostic::error(message, setter.span).with_code("E0102")));
+		}
+
+		Ok(FieldDecl {
+			id: decl.id,
+			name: decl.name,
+			name_span: decl.name_span,
+			modifiers,
+			annotations,
+			doc: None,
+			mutable: decl.mutable,
+			ty: decl.ty,
+			init: decl.init,
+			getter,
+			setter,
+			span: start.to(self.previous().span)
+		})
+	}
+
+	// Accessors follow the field they belong to:
+	//   mut val name: String = ""
+	//       get() = field
+	//       private set(value) { field = value.trim() }
+	fn accessor(&mut self) -> PResult<Option<Accessor>> {
+		let mut offset = 0;
+		let visibility = match self.peek().token_type {
+			TokenType::Private => {
+				offset = 1;
+				Visibility::Private
+			}
+			TokenType::Protected => {
+				offset = 1;
+				Visibility::Protected
+			}
+			_ => Visibility::Public
+		};
+
+		let token = self.peek_at(offset);
+		let kind = match (token.token_type, token.lexeme.as_str()) {
+			(TokenType::Identifier, "get") => AccessorKind::Get,
+			(TokenType::Identifier, "set") => AccessorKind::Set,
+			_ => return Ok(None)
+		};
+
+		let start = self.peek().span;
+		self.current += offset + 1;
+
+		let mut param = None;
+		if self.match_token(TokenType::LeftParen) {
+			let mut params = self.parameters()?;
+			match (kind, params.len()) {
+				(AccessorKind::Get, 0) => {}
+				(AccessorKind::Set, 1) => param = params.pop(),
+				(AccessorKind::Get, _) => return Err(self.error_at_previous("E0102", "A getter takes no parameters")),
+				(AccessorKind::Set, _) => return Err(self.error_at_previous("E0102", "A setter takes exactly one parameter"))
+			}
+		}
+
+		let body = if self.check(TokenType::LeftBrace) {
+			Some(FnBody::Block(self.block()?))
+		} else if self.match_token(TokenType::Equal) {
+			Some(FnBody::Expr(self.expression()?))
+		} else {
+			None
+		};
+
+		if kind == AccessorKind::Set && body.is_some() && param.is_none() {
+			let message = "A setter with a body must name its parameter, e.g. 'set(value)'";
+			return Err(Box::new(Diagnostic::error(message, start.to(self.previous().span)).with_code("E0102")));
+		}
+
+		Ok(Some(Accessor {
+			id: self.next_id(),
+			kind,
+			visibility,
+			param,
+			body,
+			span: start.to(self.previous().span)
+		}))
+	}
+
+	// `val name: Type = init`, `mut val name = init` (the `val` after `mut` is
+	// optional) or `const NAME = init`
+	fn var_decl(&mut self) -> PResult<VarDecl> {
+		let constant = self.match_token(TokenType::Const);
+		let mutable = !constant && self.match_token(TokenType::Mut);
+		if !constant && !self.match_token(TokenType::Val) && !mutable {
+			return Err(self.error_at_current("E0100", "Expected 'val', 'mut' or 'const'"));
+		}
+
+		let name_token = self.consume(TokenType::Identifier, "Expected variable name")?.clone();
+		let ty = if self.match_token(TokenType::Colon) { Some(self.type_expr()?) } else { None };
+		let init = if self.match_token(TokenType::Equal) { Some(self.expression()?) } else { None };
+		if constant && init.is_none() {
+			return Err(self.error_at_current("E0103", &format!("Expected '=' and a value for const '{}'", name_token.lexeme)));
+		}
+
+		Ok(VarDecl {
+			id: self.next_id(),
+			name: name_token.lexeme,
+			name_span: name_token.span,
+			mutable,
+			constant,
+			ty,
+			init,
+			doc: None
+		})
+	}
+
+	fn import(&mut self, start: Span) -> PResult<Stmt> {
+		self.consume(TokenType::Import, "Expected 'import'")?;
+
+		let first = self.consume(TokenType::Identifier, "Expected module path after 'import'")?;
+		let mut path = vec![first.lexeme.clone()];
+		let mut name_span = first.span;
+		let mut wildcard = false;
+		while self.match_token(TokenType::Dot) {
+			if self.match_token(TokenType::Star) {
+				wildcard = true;
+				break;
+			}
+			let name = self.consume(TokenType::Identifier, "Expected identifier in import path")?;
+			path.push(name.lexeme.clone());
+			name_span = name.span;
+		}
+
+		let mut alias = None;
+		if !wildcard && self.check_identifier("as") {
+			self.advance();
+			alias = Some(self.consume(TokenType::Identifier, "Expected alias after 'as'")?.lexeme.clone());
+		}
+		self.match_token(TokenType::Semicolon);
+
+		let decl = ImportDecl {
+			path,
+			wildcard,
+			alias,
+			name_span,
+			span: start.to(self.previous().span)
+		};
+		Ok(self.stmt(StmtKind::Import(decl), start))
+	}
+
+	fn package(&mut self, start: Span) -> PResult<Stmt> {
+		self.consume(TokenType::Package, "Expected 'package'")?;
+		let mut path = vec![self.consume(TokenType::Identifier, "Expected package name")?.lexeme.clone()];
+		while self.match_token(TokenType::Dot) {
+			path.push(self.consume(TokenType::Identifier, "Expected identifier in package name")?.lexeme.clone());
+		}
+		self.match_token(TokenType::Semicolon);
+		Ok(self.stmt(StmtKind::Package(path), start))
+	}
+
+	// ---------------------------------------------------------------------
+	// Statements
+	// ---------------------------------------------------------------------
+
+	fn statement(&mut self) -> PResult<Stmt> {
+		let start = self.peek().span;
+
+		match self.peek().token_type {
+			TokenType::LeftBrace => {
+				let block = self.block()?;
+				Ok(self.stmt(StmtKind::Block(block), start))
+			}
+			TokenType::If => self.if_statement(),
+			TokenType::While => {
+				self.advance();
+				let cond = self.condition()?;
+				let body = self.body()?;
+				Ok(self.stmt(StmtKind::While { cond, body }, start))
+			}
+			TokenType::Loop => {
+				self.advance();
+				let body = self.block()?;
+				Ok(self.stmt(StmtKind::Loop { body }, start))
+			}
+			TokenType::For => self.for_statement(),
+			TokenType::Identifier if self.peek_next_type() == TokenType::Colon => {
+				let label = self.advance().lexeme.clone();
+				self.advance();
+				if !matches!(self.peek().token_type, TokenType::While | TokenType::Loop | TokenType::For) {
+					return Err(self.error_at_current("E0104", &format!("Only loops can be labeled; expected a loop after '{}:'", label)));
+				}
+				let body = Box::new(self.statement()?);
+				Ok(self.stmt(StmtKind::Labeled { label, body }, start))
+			}
+			TokenType::Break => {
+				self.advance();
+				let label = self.jump_label();
+				self.end_statement()?;
+				Ok(self.stmt(StmtKind::Break(label), start))
+			}
+			TokenType::Continue => {
+				self.advance();
+				let label = self.jump_label();
+				self.end_statement()?;
+				Ok(self.stmt(StmtKind::Continue(label), start))
+			}
+			TokenType::Return => {
+				self.advance();
+				let value = if self.ends_statement() { None } else { Some(self.expression()?) };
+				self.end_statement()?;
+				Ok(self.stmt(StmtKind::Return(value), start))
+			}
+			TokenType::Yield => {
+				self.advance();
+				let value = self.expression()?;
+				self.end_statement()?;
+				Ok(self.stmt(StmtKind::Yield(value), start))
+			}
+			TokenType::Throw => {
+				self.advance();
+				let value = self.expression()?;
+				self.end_statement()?;
+				Ok(self.stmt(StmtKind::Throw(value), start))
+			}
+			TokenType::Try => self.try_statement(),
+			TokenType::Switch => self.switch_statement(),
+			TokenType::Defer => {
+				self.advance();
+				let body = if self.check(TokenType::LeftBrace) {
+					let block = self.block()?;
+					Stmt { span: block.span, kind: StmtKind::Block(block) }
+				} else {
+					let expr = self.expression()?;
+					self.end_statement()?;
+					Stmt { span: expr.span, kind: StmtKind::Expr(expr) }
+				};
+				Ok(self.stmt(StmtKind::Defer(Box::new(body)), start))
+			}
+			_ => {
+				let expr = self.expression()?;
+				self.end_statement()?;
+				Ok(self.stmt(StmtKind::Expr(expr), start))
+			}
+		}
+	}
+
+	// `break outer`; the label has to be on the same line
+	fn jump_label(&mut self) -> Option<String> {
+		if self.check(TokenType::Identifier) && self.same_line() {
+			return Some(self.advance().lexeme.clone());
+		}
+		None
+	}
+
+	fn ends_statement(&self) -> bool {
+		matches!(self.peek().token_type, TokenType::Semicolon | TokenType::RightBrace | TokenType::Eof) || !self.same_line()
+	}
+
+	// Statements on one line need a `;` between them, except before what
+	// carries on the statement around them, like `else` or the next case.
+	fn end_statement(&mut self) -> PResult<()> {
+		if self.match_token(TokenType::Semicolon)
+			|| self.ends_statement()
+			|| matches!(self.peek().token_type, TokenType::Else | TokenType::Elif | TokenType::Case | TokenType::Default)
+		{
+			return Ok(());
+		}
+		let previous = self.previous();
+		let column = previous.span.column + previous.lexeme.chars().count() as i64;
+		let end = Span { start: previous.span.end, end: previous.span.end, line: previous.span.line, column };
+		let err = self.error_at_current("E0114", "Expected ';' or a line break between statements");
+		Err(Box::new(err.with_fix("add ';'", end, ";")))
+	}
+
+	fn if_statement(&mut self) -> PResult<Stmt> {
+		self.nested(Parser::if_statement_inner)
+	}
+
+	fn if_statement_inner(&mut self) -> PResult<Stmt> {
+		let start = self.advance().span;
+		let cond = self.condition()?;
+		let then_branch = self.body()?;
+
+		let else_branch = if self.check(TokenType::Elif) {
+			Some(Box::new(self.if_statement()?))
+		} else if self.match_token(TokenType::Else) {
+			if self.check(TokenType::If) {
+				Some(Box::new(self.if_statement()?))
+			} else {
+				let else_start = self.peek().span;
+				let block = self.body()?;
+				Some(Box::new(self.stmt(StmtKind::Block(block), else_start)))
+			}
+		} else {
+			None
+		};
+
+		Ok(self.stmt(StmtKind::If { cond, then_branch, else_branch }, start))
+	}
+
+	// for i in 0..10 { }
+	// for (k, v) of map { }
+	// for (item in items) { }
+	fn for_statement(&mut self) -> PResult<Stmt> {
+		let start = self.advance().span;
+
+		let mut header_parens = false;
+		let pattern = if self.match_token(TokenType::LeftParen) {
+			let mut bindings = vec![self.binding()?];
+			while self.match_token(TokenType::Comma) {
+				bindings.push(self.binding()?);
+			}
+			if !self.match_token(TokenType::RightParen) {
+				header_parens = true;
+			}
+			if bindings.len() == 1 {
+				ForPattern::Single(bindings.remove(0))
+			} else {
+				ForPattern::Tuple(bindings)
+			}
+		} else {
+			ForPattern::Single(self.binding()?)
+		};
+
+		let kind = if self.match_token(TokenType::In) {
+			ForKind::In
+		} else if self.match_token(TokenType::Of) {
+			ForKind::Of
+		} else {
+			return Err(self.error_at_current("E0100", "Expected 'in' or 'of' after loop variable"));
+		};
+
+		if kind == ForKind::In && matches!(pattern, ForPattern::Tuple(_)) {
+			return Err(self.error_at_previous("E0105", "Destructuring a loop variable needs 'of', e.g. 'for (k, v) of map'"));
+		}
+
+		let iterable = self.expression()?;
+		if header_parens {
+			self.consume(TokenType::RightParen, "Expected ')' after loop header")?;
+		}
+		let body = self.body()?;
+
+		let for_loop = ForLoop { kind, pattern, iterable, body };
+		Ok(self.stmt(StmtKind::For(for_loop), start))
+	}
+
+	fn binding(&mut self) -> PResult<Binding> {
+		let token = self.consume(TokenType::Identifier, "Expected loop variable name")?.clone();
+		Ok(Binding {
+			id: self.next_id(),
+			name: token.lexeme,
+			span: token.span
+		})
+	}
+
+	// `(expr)` after if/while/switch. The parentheses are conventional, not required.
+	fn condition(&mut self) -> PResult<Expr> {
+		if self.match_token(TokenType::LeftParen) {
+			let expr = self.expression()?;
+			self.consume(TokenType::RightParen, "Expected ')' after condition")?;
+			Ok(expr)
+		} else {
+			self.expression()
+		}
+	}
+
+	// A braced block, or a single statement treated as one.
+	fn body(&mut self) -> PResult<Block> {
+		if self.check(TokenType::LeftBrace) {
+			return self.block();
+		}
+		let stmt = self.statement()?;
+		let span = stmt.span;
+		Ok(Block { stmts: vec![stmt], span })
+	}
+
+	fn block(&mut self) -> PResult<Block> {
+		let start = self.consume(TokenType::LeftBrace, "Expected '{'")?.span;
+		let mut stmts = Vec::new();
+
+		while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+			let before = self.current;
+			match self.declaration() {
+				Ok(stmt) => stmts.push(stmt),
+				Err(err) => self.recover(*err, before)
+			}
+		}
+
+		self.consume(TokenType::RightBrace, "Expected '}' after block")?;
+		Ok(Block { stmts, span: start.to(self.previous().span) })
+	}
+
+	fn try_statement(&mut self) -> PResult<Stmt> {
+		let start = self.advance().span;
+		let body = self.block()?;
+
+		let mut catches = Vec::new();
+		while self.check(TokenType::Catch) {
+			let catch_start = self.advance().span;
+			let parenthesized = self.match_token(TokenType::LeftParen);
+			let name = self.consume(TokenType::Identifier, "Expected exception name after 'catch'")?.lexeme.clone();
+			let ty = if self.match_token(TokenType::Colon) { Some(self.type_expr()?) } else { None };
+			if parenthesized {
+				self.consume(TokenType::RightParen, "Expected ')' after catch clause")?;
+			}
+			let body = self.block()?;
+			catches.push(CatchClause {
+				id: self.next_id(),
+				name,
+				ty,
+				body,
+				span: catch_start.to(self.previous().span)
+			});
+		}
+
+		let finally = if self.match_token(TokenType::Finally) { Some(self.block()?) } else { None };
+
+		if catches.is_empty() && finally.is_none() {
+			return Err(self.error_at_current("E0100", "Expected 'catch' or 'finally' after try block"));
+		}
+
+		Ok(self.stmt(StmtKind::Try { body, catches, finally }, start))
+	}
+
+	fn switch_statement(&mut self) -> PResult<Stmt> {
+		let start = self.advance().span;
+		let subject = self.condition()?;
+		self.consume(TokenType::LeftBrace, "Expected '{' after switch subject")?;
+
+		let mut cases = Vec::new();
+		let mut default = None;
+
+		while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+			let case_start = self.peek().span;
+			if self.match_token(TokenType::Default) {
+				self.consume(TokenType::Colon, "Expected ':' after 'default'")?;
+				if default.is_some() {
+					return Err(Box::new(Diagnostic::error("A switch can only have one 'default' case", case_start).with_code("E0106")));
+				}
+				default = Some(self.case_body()?);
+				continue;
+			}
+
+			self.consume(TokenType::Case, "Expected 'case' or 'default' in switch")?;
+			let mut patterns = vec![self.expression()?];
+			while self.match_token(TokenType::Comma) {
+				patterns.push(self.expression()?);
+			}
+			self.consume(TokenType::Colon, "Expected ':' after case pattern")?;
+			let body = self.case_body()?;
+			cases.push(SwitchCase {
+				patterns,
+				body,
+				span: case_start.to(self.previous().span)
+			});
+		}
+
+		self.consume(TokenType::RightBrace, "Expected '}' after switch body")?;
+		Ok(self.stmt(StmtKind::Switch { subject, cases, default }, start))
+	}
+
+	// Either a block or the statements up to the next case label.
+	fn case_body(&mut self) -> PResult<Block> {
+		if self.check(TokenType::LeftBrace) {
+			return self.block();
+		}
+
+		let start = self.peek().span;
+		let mut stmts = Vec::new();
+		while !matches!(
+			self.peek().token_type,
+			TokenType::Case | TokenType::Default | TokenType::RightBrace | TokenType::Eof
+		) {
+			stmts.push(self.declaration()?);
+		}
+		Ok(Block { stmts, span: start.to(self.previous().span) })
+	}
+
+	// ---------------------------------------------------------------------
+	// Expressions
+	// ---------------------------------------------------------------------
+
+	pub fn expression(&mut self) -> PResult<Expr> {
+		self.parse_precedence(Precedence::Assignment)
+	}
+
+	fn parse_precedence(&mut self, min: Precedence) -> PResult<Expr> {
+		let mut left = self.unary()?;
+
+		loop {
+			let token_type = self.peek().token_type;
+
+			// `x !is T` is the only infix use of `!`
+			if token_type == TokenType::NotBang && self.peek_next_type() == TokenType::Is {
+				if Precedence::Comparison < min {
+					break;
+				}
+				self.advance();
+				self.advance();
+				let ty = self.type_expr()?;
+				left = self.is_expr(left, ty, true);
+				continue;
+			}
+
+			let Some((prec, assoc)) = precedence::infix(token_type) else {
+				break;
+			};
+			if prec < min {
+				break;
+			}
+			self.advance();
+
+			if token_type == TokenType::Is {
+				let ty = self.type_expr()?;
+				left = self.is_expr(left, ty, false);
+				continue;
+			}
+
+			let next_min = match assoc {
+				Assoc::Left | Assoc::None => prec.next(),
+				Assoc::Right => prec
+			};
+
+			let compound = precedence::compound_op(token_type);
+			if token_type == TokenType::Equal || compound.is_some() {
+				if !is_assignable(&left) {
+					return Err(Box::new(Diagnostic::error("Invalid assignment target", left.span).with_code("E0107")));
+				}
+				let value = self.parse_precedence(next_min)?;
+				let span = left.span.to(value.span);
+				left = self.expr(ExprKind::Assign { target: Box::new(left), op: compound, value: Box::new(value) }, span);
+				continue;
+			}
+
+			if token_type == TokenType::DotDot {
+				let end = self.parse_precedence(next_min)?;
+				if self.check(TokenType::DotDot) {
+					return Err(self.error_at_current("E0108", "Range operators cannot be chained; add parentheses"));
+				}
+				let span = left.span.to(end.span);
+				left = self.expr(ExprKind::Range { start: Box::new(left), end: Box::new(end) }, span);
+				continue;
+			}
+
+			let op = precedence::binary_op(token_type).expect("infix token without a binary operator");
+			let right = self.parse_precedence(next_min)?;
+			let span = left.span.to(right.span);
+			left = self.expr(ExprKind::Binary { op, left: Box::new(left), right: Box::new(right) }, span);
+		}
+
+		Ok(left)
+	}
+
+	fn is_expr(&mut self, operand: Expr, ty: TypeExpr, negated: bool) -> Expr {
+		let span = operand.span.to(ty.span);
+		self.expr(ExprKind::Is { operand: Box::new(operand), ty, negated }, span)
+	}
+
+	fn unary(&mut self) -> PResult<Expr> {
+		self.nested(Parser::unary_inner)
+	}
+
+	fn unary_inner(&mut self) -> PResult<Expr> {
+		let start = self.peek().span;
+		let op = match self.peek().token_type {
+			TokenType::Minus => Some(UnaryOp::Negate),
+			TokenType::NotBang | TokenType::Not => Some(UnaryOp::Not),
+			TokenType::PlusPlus => Some(UnaryOp::PreIncrement),
+			TokenType::MinusMinus => Some(UnaryOp::PreDecrement),
+			TokenType::Typeof => Some(UnaryOp::Typeof),
+			TokenType::Await => {
+				self.advance();
+				let operand = self.unary()?;
+				let span = start.to(operand.span);
+				return Ok(self.expr(ExprKind::Await(Box::new(operand)), span));
+			}
+			TokenType::Spawn => {
+				self.advance();
+				let block = self.block()?;
+				let span = start.to(block.span);
+				return Ok(self.expr(ExprKind::Spawn(block), span));
+			}
+			_ => None
+		};
+
+		if let Some(op) = op {
+			self.advance();
+			let operand = self.unary()?;
+			if matches!(op, UnaryOp::PreIncrement | UnaryOp::PreDecrement) && !is_assignable(&operand) {
+				let message = "Increment and decrement need a variable, field or index";
+				return Err(Box::new(Diagnostic::error(message, operand.span).with_code("E0107")));
+			}
+			let span = start.to(operand.span);
+			return Ok(self.expr(ExprKind::Unary { op, operand: Box::new(operand) }, span));
+		}
+
+		let primary = self.primary()?;
+		self.postfix(primary)
+	}
+
+	// Calls, member access, indexing and postfix operators. Anything that could
+	// also start a new statement only continues the expression on the same line.
+	fn postfix(&mut self, mut expr: Expr) -> PResult<Expr> {
+		loop {
+			match self.peek().token_type {
+				TokenType::LeftParen if self.same_line() => {
+					self.advance();
+					let args = self.arguments()?;
+					let span = expr.span.to(self.previous().span);
+					expr = self.expr(ExprKind::Call { callee: Box::new(expr), args }, span);
+				}
+				TokenType::Dot => {
+					self.advance();
+					expr = self.member_access(expr, false)?;
+				}
+				TokenType::Question if self.peek_next_type() == TokenType::Dot => {
+					self.advance();
+					self.advance();
+					expr = self.member_access(expr, true)?;
+				}
+				TokenType::LeftBracket if self.same_line() => {
+					self.advance();
+					let index = self.expression()?;
+					self.consume(TokenType::RightBracket, "Expected ']' after index")?;
+					let span = expr.span.to(self.previous().span);
+					expr = self.expr(ExprKind::Index { object: Box::new(expr), index: Box::new(index) }, span);
+				}
+				TokenType::PlusPlus | TokenType::MinusMinus if self.same_line() => {
+					if !is_assignable(&expr) {
+						return Err(self.error_at_current("E0107", "Increment and decrement need a variable, field or index"));
+					}
+					let op = if self.advance().token_type == TokenType::PlusPlus {
+						PostfixOp::Increment
+					} else {
+						PostfixOp::Decrement
+					};
+					let span = expr.span.to(self.previous().span);
+					expr = self.expr(ExprKind::Postfix { op, operand: Box::new(expr) }, span);
+				}
+				TokenType::BangBang if self.same_line() => {
+					self.advance();
+					let span = expr.span.to(self.previous().span);
+					expr = self.expr(ExprKind::Postfix { op: PostfixOp::NonNull, operand: Box::new(expr) }, span);
+				}
+				TokenType::Question if self.same_line() => {
+					self.advance();
+					let span = expr.span.to(self.previous().span);
+					expr = self.expr(ExprKind::Postfix { op: PostfixOp::Propagate, operand: Box::new(expr) }, span);
+				}
+				_ => break
+			}
+		}
+
+		Ok(expr)
+	}
+
+	fn member_access(&mut self, object: Expr, safe: bool) -> PResult<Expr> {
+		let name_token = self.member_name()?;
+		let span = object.span.to(name_token.span);
+		Ok(self.expr(
+			ExprKind::Member {
+				object: Box::new(object),
+				name: name_token.lexeme,
+				name_span: name_token.span,
+				safe
+			},
+			span
+		))
+	}
+
+	// Keywords are allowed after a dot so `x.class` or `Type.new` still parse.
+	fn member_name(&mut self) -> PResult<Token> {
+		let token = self.peek();
+		if token.token_type == TokenType::Identifier || token.lexeme.starts_with(|c: char| c.is_ascii_alphabetic()) {
+			Ok(self.advance().clone())
+		} else {
+			Err(self.error_at_current("E0100", "Expected member name after '.'"))
+		}
+	}
+
+	// Parses call arguments after the opening '('.
+	fn arguments(&mut self) -> PResult<Vec<Expr>> {
+		let mut args = Vec::new();
+
+		if !self.check(TokenType::RightParen) {
+			loop {
+				args.push(self.expression()?);
+				if !self.match_token(TokenType::Comma) || self.check(TokenType::RightParen) {
+					break;
+				}
+			}
+		}
+
+		self.consume(TokenType::RightParen, "Expected ')' after arguments")?;
+		Ok(args)
+	}
+
+	fn primary(&mut self) -> PResult<Expr> {
+		let token = self.peek().clone();
+		let start = token.span;
+
+		let kind = match token.token_type {
+			TokenType::NumberLiteral => {
+				self.advance();
+				ExprKind::Literal(number_literal(&token)?)
+			}
+			TokenType::StringLiteral => {
+				self.advance();
+				let inner = token.lexeme.get(1..token.lexeme.len().saturating_sub(1)).unwrap_or("");
+				ExprKind::Literal(Literal::String(unescape(inner, token.span)?))
+			}
+			TokenType::StringStart => return self.interpolation(),
+			TokenType::True => {
+				self.advance();
+				ExprKind::Literal(Literal::Bool(true))
+			}
+			TokenType::False => {
+				self.advance();
+				ExprKind::Literal(Literal::Bool(false))
+			}
+			TokenType::Null => {
+				self.advance();
+				ExprKind::Literal(Literal::Null)
+			}
+			TokenType::This => {
+				self.advance();
+				ExprKind::This
+			}
+			TokenType::Super => {
+				self.advance();
+				ExprKind::Super
+			}
+			TokenType::Identifier => {
+				if self.peek_next_type() == TokenType::FatArrow {
+					return self.short_lambda();
+				}
+				self.advance();
+				ExprKind::Identifier(token.lexeme)
+			}
+			TokenType::LeftParen => {
+				if self.is_lambda_ahead() {
+					return self.lambda();
+				}
+				self.advance();
+				let inner = self.expression()?;
+				self.consume(TokenType::RightParen, "Expected ')' after expression")?;
+				// Keep the parenthesized span so diagnostics cover the brackets.
+				let span = start.to(self.previous().span);
+				return Ok(Expr { span, ..inner });
+			}
+			TokenType::LeftBracket => {
+				self.advance();
+				if self.match_token(TokenType::Colon) {
+					self.consume(TokenType::RightBracket, "Expected ']' after ':' of an empty map")?;
+					ExprKind::Map(Vec::new())
+				} else {
+					self.list_or_map()?
+				}
+			}
+			TokenType::New => {
+				self.advance();
+				let class = self.type_expr()?;
+				self.consume(TokenType::LeftParen, "Expected '(' after class name in 'new' expression")?;
+				let args = self.arguments()?;
+				ExprKind::New { class, args }
+			}
+			TokenType::Function => return self.anonymous_function(),
+			TokenType::If => return self.if_expression(),
+			// Mostly seen as `x ?? return`; the value is whatever follows on the line
+			TokenType::Return => {
+				self.advance();
+				let ends = self.ends_statement()
+					|| matches!(self.peek().token_type, TokenType::RightParen | TokenType::RightBracket | TokenType::Comma);
+				ExprKind::Return(if ends { None } else { Some(Box::new(self.expression()?)) })
+			}
+			TokenType::Throw => {
+				self.advance();
+				ExprKind::Throw(Box::new(self.expression()?))
+			}
+			_ => return Err(self.error_at_current("E0100", "Expected expression"))
+		};
+
+		let span = start.to(self.previous().span);
+		Ok(self.expr(kind, span))
+	}
+
+	// The items of `[a, b]`, or the entries of `[k: v, ...]` when the first
+	// item is followed by a colon.
+	fn list_or_map(&mut self) -> PResult<ExprKind> {
+		let mut items = Vec::new();
+		let mut entries = Vec::new();
+		while !self.check(TokenType::RightBracket) {
+			let item = self.expression()?;
+			if !entries.is_empty() || items.is_empty() && self.check(TokenType::Colon) {
+				self.consume(TokenType::Colon, "Expected ':' after map key")?;
+				entries.push((item, self.expression()?));
+			} else {
+				items.push(item);
+			}
+			if !self.match_token(TokenType::Comma) {
+				break;
+			}
+		}
+		self.consume(TokenType::RightBracket, "Expected ']' after list items")?;
+		Ok(if entries.is_empty() { ExprKind::Array(items) } else { ExprKind::Map(entries) })
+	}
+
+	// `if (cond) a else b`. There is no `?:` ternary: `?` is kept for `??` and
+	// friends. The else branch is required and swallows everything down to
+	// `??`, so `if (c) a else b ?? d` reads as `if (c) a else (b ?? d)`.
+	fn if_expression(&mut self) -> PResult<Expr> {
+		let start = self.advance().span;
+		let cond = self.condition()?;
+		let then_branch = self.branch_expression()?;
+
+		let else_branch = if self.check(TokenType::Elif) {
+			self.if_expression()?
+		} else if self.match_token(TokenType::Else) {
+			if self.check(TokenType::If) {
+				self.if_expression()?
+			} else {
+				self.branch_expression()?
+			}
+		} else {
+			return Err(self.error_at_current("E0109", "An 'if' used as a value needs an 'else' branch"));
+		};
+
+		let span = start.to(else_branch.span);
+		Ok(self.expr(
+			ExprKind::If {
+				cond: Box::new(cond),
+				then_branch: Box::new(then_branch),
+				else_branch: Box::new(else_branch)
+			},
+			span
+		))
+	}
+
+	fn branch_expression(&mut self) -> PResult<Expr> {
+		if self.check(TokenType::LeftBrace) {
+			let block = self.block()?;
+			let span = block.span;
+			return Ok(self.expr(ExprKind::Block(block), span));
+		}
+		self.parse_precedence(Precedence::Coalesce)
+	}
+
+	fn interpolation(&mut self) -> PResult<Expr> {
+		let start = self.advance().clone();
+		let mut parts = Vec::new();
+		if !start.lexeme.is_empty() {
+			parts.push(InterpolationPart::Text(unescape(&start.lexeme, start.span)?));
+		}
+
+		loop {
+			parts.push(InterpolationPart::Expr(self.expression()?));
+
+			let token = self.peek().clone();
+			let done = match token.token_type {
+				TokenType::StringMiddle => false,
+				TokenType::StringEnd => true,
+				_ => return Err(self.error_at_current("E0100", "Expected '}' to close string interpolation"))
+			};
+			self.advance();
+			if !token.lexeme.is_empty() {
+				parts.push(InterpolationPart::Text(unescape(&token.lexeme, token.span)?));
+			}
+			if done {
+				break;
+			}
+		}
+
+		let span = start.span.to(self.previous().span);
+		Ok(self.expr(ExprKind::Interpolation(parts), span))
+	}
+
+	// Looks past a parenthesized group for `=>` to tell `(a, b) => a + b` from `(a + b)`.
+	fn is_lambda_ahead(&self) -> bool {
+		let mut depth = 0;
+		let mut i = self.current;
+		while i < self.tokens.len() {
+			match self.tokens[i].token_type {
+				TokenType::LeftParen => depth += 1,
+				TokenType::RightParen => {
+					depth -= 1;
+					if depth == 0 {
+						return self.tokens.get(i + 1).is_some_and(|t| t.token_type == TokenType::FatArrow);
+					}
+				}
+				TokenType::Eof => return false,
+				_ => {}
+			}
+			i += 1;
+		}
+		false
+	}
+
+	fn lambda(&mut self) -> PResult<Expr> {
+		let start = self.consume(TokenType::LeftParen, "Expected '('")?.span;
+		let params = self.parameters()?;
+		self.consume(TokenType::FatArrow, "Expected '=>' after lambda parameters")?;
+		let body = self.lambda_body()?;
+		let span = start.to(self.previous().span);
+		Ok(self.expr(ExprKind::Lambda { params, body }, span))
+	}
+
+	// `x => x * 2`
+	fn short_lambda(&mut self) -> PResult<Expr> {
+		let token = self.advance().clone();
+		let param = Param {
+			id: self.next_id(),
+			name: token.lexeme,
+			ty: None,
+			default: None,
+			variadic: false,
+			span: token.span
+		};
+		self.consume(TokenType::FatArrow, "Expected '=>'")?;
+		let body = self.lambda_body()?;
+		let span = token.span.to(self.previous().span);
+		Ok(self.expr(ExprKind::Lambda { params: vec![param], body }, span))
+	}
+
+	// `fn (a, b) { ... }` in expression position
+	fn anonymous_function(&mut self) -> PResult<Expr> {
+		let start = self.consume(TokenType::Function, "Expected 'fn'")?.span;
+		self.consume(TokenType::LeftParen, "Expected '(' after 'fn' in anonymous function")?;
+		let params = self.parameters()?;
+		self.return_type()?;
+		let body = LambdaBody::Block(self.block()?);
+		let span = start.to(self.previous().span);
+		Ok(self.expr(ExprKind::Lambda { params, body }, span))
+	}
+
+	fn lambda_body(&mut self) -> PResult<LambdaBody> {
+		if self.check(TokenType::LeftBrace) {
+			Ok(LambdaBody::Block(self.block()?))
+		} else {
+			Ok(LambdaBody::Expr(Box::new(self.expression()?)))
+		}
+	}
+
+	// ---------------------------------------------------------------------
+	// Types
+	// ---------------------------------------------------------------------
+
+	// A type, `A | B` unions included. After `is` the `|` belongs to the type
+	// too: `x is Int | String` tests for either.
+	pub fn type_expr(&mut self) -> PResult<TypeExpr> {
+		let start = self.peek().span;
+		let first = self.nested(Parser::type_expr_inner)?;
+		if !self.check(TokenType::BitOr) {
+			return Ok(first);
+		}
+		let mut members = vec![first];
+		while self.match_token(TokenType::BitOr) {
+			members.push(self.nested(Parser::type_expr_inner)?);
+		}
+		Ok(TypeExpr { kind: TypeExprKind::Union(members), span: start.to(self.previous().span) })
+	}
+
+	fn type_expr_inner(&mut self) -> PResult<TypeExpr> {
+		let start = self.peek().span;
+
+		let ty = if self.match_token(TokenType::LeftParen) {
+			let mut params = Vec::new();
+			if !self.check(TokenType::RightParen) {
+				loop {
+					params.push(self.type_expr()?);
+					if !self.match_token(TokenType::Comma) || self.check(TokenType::RightParen) {
+						break;
+					}
+				}
+			}
+			self.consume(TokenType::RightParen, "Expected ')' in type")?;
+
+			if self.match_token(TokenType::Arrow) {
+				let ret = self.type_expr()?;
+				TypeExpr {
+					kind: TypeExprKind::Function { params, ret: Box::new(ret) },
+					span: start.to(self.previous().span)
+				}
+			} else if params.len() == 1 {
+				// `(A)` just groups, e.g. `((Int) -> Int)?`
+				let inner = params.remove(0);
+				TypeExpr { span: start.to(self.previous().span), ..inner }
+			} else {
+				return Err(self.error_at_current("E0100", "Expected '->' after function type parameters"));
+			}
+		} else {
+			let name = self.qualified_name("Expected type name")?;
+			let args = if self.check(TokenType::Less) { self.type_args()? } else { Vec::new() };
+			TypeExpr {
+				kind: TypeExprKind::Named { name, args },
+				span: start.to(self.previous().span)
+			}
+		};
+
+		self.type_suffixes(ty, start)
+	}
+
+	// `[]` and `?` after a type, applied left to right: `Int?[]` is a list of
+	// nullable ints, `Int[]?` a nullable list.
+	fn type_suffixes(&mut self, mut ty: TypeExpr, start: Span) -> PResult<TypeExpr> {
+		loop {
+			let kind = if self.check(TokenType::LeftBracket) && self.peek_next_type() == TokenType::RightBracket {
+				self.advance();
+				self.advance();
+				TypeExprKind::Array(Box::new(ty))
+			} else if self.check(TokenType::Question) && self.same_line() {
+				self.advance();
+				if matches!(ty.kind, TypeExprKind::Nullable(_)) {
+					return Err(self.error_at_previous("E0110", "Type is already nullable"));
+				}
+				TypeExprKind::Nullable(Box::new(ty))
+			} else {
+				return Ok(ty);
+			};
+			ty = TypeExpr { kind, span: start.to(self.previous().span) };
+		}
+	}
+
+	fn type_args(&mut self) -> PResult<Vec<TypeExpr>> {
+		self.consume(TokenType::Less, "Expected '<'")?;
+		let mut args = vec![self.type_expr()?];
+		while self.match_token(TokenType::Comma) {
+			args.push(self.type_expr()?);
+		}
+		self.close_angle()?;
+		Ok(args)
+	}
+
+	fn type_params(&mut self) -> PResult<Vec<TypeParam>> {
+		let mut params = Vec::new();
+		if !self.match_token(TokenType::Less) {
+			return Ok(params);
+		}
+
+		loop {
+			let token = self.consume(TokenType::Identifier, "Expected type parameter name")?.clone();
+			let mut bounds = Vec::new();
+			if self.match_token(TokenType::Colon) {
+				bounds.push(self.type_expr()?);
+				while self.match_token(TokenType::BitAnd) {
+					bounds.push(self.type_expr()?);
+				}
+			}
+			params.push(TypeParam {
+				id: self.next_id(),
+				name: token.lexeme,
+				bounds,
+				span: token.span.to(self.previous().span)
+			});
+			if !self.match_token(TokenType::Comma) {
+				break;
+			}
+		}
+
+		self.close_angle()?;
+		Ok(params)
+	}
+
+	// The lexer turns `>>` into one token, so `List<List<Int>>` has to split it.
+	fn close_angle(&mut self) -> PResult<()> {
+		if self.match_token(TokenType::Greater) {
+			return Ok(());
+		}
+		if self.check(TokenType::ShiftRight) {
+			let token = &mut self.tokens[self.current];
+			token.token_type = TokenType::Greater;
+			token.lexeme = ">".to_string();
+			token.column += 1;
+			token.span.start += 1;
+			token.span.column += 1;
+			return Ok(());
+		}
+		Err(self.error_at_current("E0100", "Expected '>' to close type arguments"))
+	}
+
+	// Whether the `<...>` group starting at the current token is followed by `token_type`.
+	fn angle_group_followed_by(&self, token_type: TokenType) -> bool {
+		let mut depth = 0;
+		for (i, token) in self.tokens.iter().enumerate().skip(self.current) {
+			match token.token_type {
+				TokenType::Less => depth += 1,
+				TokenType::Greater => depth -= 1,
+				TokenType::ShiftRight => depth -= 2,
+				TokenType::Identifier
+				| TokenType::Comma
+				| TokenType::Dot
+				| TokenType::Colon
+				| TokenType::Question
+				| TokenType::LeftParen
+				| TokenType::RightParen
+				| TokenType::LeftBracket
+				| TokenType::RightBracket
+				| TokenType::Arrow
+				| TokenType::BitAnd
+				| TokenType::BitOr => {}
+				_ => return false
+			}
+			if depth <= 0 {
+				return self.tokens.get(i + 1).is_some_and(|t| t.token_type == token_type);
+			}
+		}
+		false
+	}
+
+	fn qualified_name(&mut self, message: &str) -> PResult<String> {
+		let mut name = self.consume(TokenType::Identifier, message)?.lexeme.clone();
+		while self.check(TokenType::Dot) && self.peek_next_type() == TokenType::Identifier {
+			self.advance();
+			name.push('.');
+			name.push_str(&self.advance().lexeme);
+		}
+		Ok(name)
+	}
+
+	// ---------------------------------------------------------------------
+	// Helpers
+	// ---------------------------------------------------------------------
+
+	// Bounds recursion so pathological input reports an error instead of
+	// overflowing the stack.
+	fn nested<T>(&mut self, parse: fn(&mut Parser) -> PResult<T>) -> PResult<T> {
+		if self.depth >= MAX_NESTING {
+			return Err(self.error_at_current("E0111", "Code is nested too deeply"));
+		}
+		self.depth += 1;
+		let result = parse(self);
+		self.depth -= 1;
+		result
+	}
+
+	fn next_id(&mut self) -> NodeId {
+		let id = NodeId(self.next_id);
+		self.next_id += 1;
+		id
+	}
+
+	fn expr(&mut self, kind: ExprKind, span: Span) -> Expr {
+		Expr { id: self.next_id(), kind, span }
+	}
+
+	fn stmt(&self, kind: StmtKind, start: Span) -> Stmt {
+		Stmt { kind, span: start.to(self.previous().span) }
+	}
+
+	// Records why the statement starting at `before` failed to parse, and
+	// skips past it. A statement missing only its `;` is already behind us,
+	// unless it was a misspelled keyword and the rest of the line is its.
+	// Something is always skipped, or the same statement would fail again.
+	fn recover(&mut self, err: Diagnostic, before: usize) {
+		let labels = err.secondary_labels.len();
+		let err = self.misspelled_keyword(err, before);
+		let misspelled = err.secondary_labels.len() > labels;
+		let whole = err.code == Some("E0114");
+		self.errors.push(err);
+		if misspelled {
+			self.skip_line(self.tokens[before].line);
+		}
+		if self.current == before || (!misspelled && !whole) {
+			self.synchronize(before);
+		}
+	}
+
+	// Skips what is left of `line`, along with any block opened on it.
+	fn skip_line(&mut self, line: i64) {
+		let mut depth = 0;
+		while !self.is_at_end() {
+			let token = self.peek();
+			match token.token_type {
+				_ if depth == 0 && token.line != line => return,
+				TokenType::LeftBrace => depth += 1,
+				TokenType::RightBrace if depth == 0 => return,
+				TokenType::RightBrace => depth -= 1,
+				_ => {}
+			}
+			self.advance();
+		}
+	}
+
+	// A line starting with a misspelled keyword, like `calss Foo : Bar`,
+	// parses as a name and then something that cannot follow it, so the error
+	// is about what comes after. The name is the likelier mistake.
+	fn misspelled_keyword(&self, err: Diagnostic, start: usize) -> Diagnostic {
+		// The name is the statement, or one before it on the same line
+		let starts_line = |index: usize| index == 0 || self.tokens[index - 1].line != self.tokens[index].line;
+		let word = match start {
+			_ if starts_line(start) => &self.tokens[start],
+			_ if starts_line(start - 1) => &self.tokens[start - 1],
+			_ => return err
+		};
+		if word.token_type != TokenType::Identifier || err.primary_span.start <= word.span.start {
+			return err;
+		}
+		let hints = err.suggestions.len();
+		let mut err = suggest::did_you_mean(err, &word.lexeme, word.span, suggest::keywords(&word.lexeme));
+		if err.suggestions.len() == hints {
+			return err;
+		}
+		// Like adding a `;` after the name, which would only make things worse
+		err.suggestions.drain(..hints);
+		err.with_label(word.span, "a name, not a keyword")
+	}
+
+	// Skips to something that looks like the start of the next statement.
+	fn synchronize(&mut self, before: usize) {
+		if self.current == before {
+			self.advance();
+		}
+
+		while !self.is_at_end() {
+			if self.previous().token_type == TokenType::Semicolon {
+				return;
+			}
+
+			match self.peek().token_type {
+				TokenType::Class
+				| TokenType::Interface
+				| TokenType::Enum
+				| TokenType::Struct
+				| TokenType::Type
+				| TokenType::Function
+				| TokenType::Val
+				| TokenType::Mut
+				| TokenType::Const
+				| TokenType::If
+				| TokenType::While
+				| TokenType::For
+				| TokenType::Loop
+				| TokenType::Return
+				| TokenType::Try
+				| TokenType::Switch
+				| TokenType::Import
+				| TokenType::Package
+				| TokenType::AT
+				| TokenType::RightBrace => return,
+				_ => {}
+			}
+
+			self.advance();
+		}
+	}
+
+	fn doc_comment(&self) -> Option<String> {
+		self.docs.get(&self.current).cloned()
+	}
+
+	fn same_line(&self) -> bool {
+		self.current == 0 || self.peek().line == self.previous().line
+	}
+
+	fn check_identifier(&self, word: &str) -> bool {
+		self.check(TokenType::Identifier) && self.peek().lexeme == word
+	}
+
+	fn consume(&mut self, token_type: TokenType, message: &str) -> PResult<&Token> {
+		if self.check(token_type) {
+			Ok(self.advance())
+		} else {
+			Err(self.error_at_current("E0100", message))
+		}
+	}
+
+	fn error_at_current(&self, code: &'static str, message: &str) -> Box<Diagnostic> {
+		let token = self.peek();
+		let found = if token.token_type == TokenType::Eof {
+			"end of file".to_string()
+		} else {
+			format!("'{}'", token.lexeme)
+		};
+		Box::new(Diagnostic::error(format!("{}, found {}", message, found), token.span).with_code(code))
+	}
+
+	fn error_at_previous(&self, code: &'static str, message: &str) -> Box<Diagnostic> {
+		Box::new(Diagnostic::error(message, self.previous().span).with_code(code))
+	}
+
+	fn match_token(&mut self, token_type: TokenType) -> bool {
+		if self.check(token_type) {
+			self.advance();
+			true
+		} else {
+			false
+		}
+	}
+
+	fn check(&self, token_type: TokenType) -> bool {
+		self.peek().token_type == token_type
+	}
+
+	fn advance(&mut self) -> &Token {
+		if !self.is_at_end() {
+			self.current += 1;
+		}
+		self.previous()
+	}
+
+	fn is_at_end(&self) -> bool {
+		self.peek().token_type == TokenType::Eof
+	}
+
+	fn peek(&self) -> &Token {
+		&self.tokens[self.current]
+	}
+
+	fn peek_at(&self, offset: usize) -> &Token {
+		let index = (self.current + offset).min(self.tokens.len() - 1);
+		&self.tokens[index]
+	}
+
+	fn peek_next_type(&self) -> TokenType {
+		self.peek_at(1).token_type
+	}
+
+	fn previous(&self) -> &Token {
+		&self.tokens[self.current.saturating_sub(1)]
+	}
+}
+
+fn accessor_name(kind: AccessorKind) -> &'static str {
+	match kind {
+		AccessorKind::Get => "get",
+		AccessorKind::Set => "set"
+	}
+}
+
+fn named_type(segments: &[Token], start: Span) -> TypeExpr {
+	let name = segments.iter().map(|t| t.lexeme.as_str()).collect::<Vec<_>>().join(".");
+	let end = segments.last().map(|t| t.span).unwrap_or(start);
+	TypeExpr {
+		kind: TypeExprKind::Named { name, args: Vec::new() },
+		span: start.to(end)
+	}
+}
+
+fn is_assignable(expr: &Expr) -> bool {
+	matches!(expr.kind, ExprKind::Identifier(_) | ExprKind::Member { .. } | ExprKind::Index { .. })
+}
+
+fn number_literal(token: &Token) -> PResult<Literal> {
+	let literal = if token.lexeme.contains('.') {
+		token.lexeme.parse::<f64>().map(Literal::Float).ok()
+	} else {
+		token.lexeme.parse::<i64>().map(Literal::Int).ok()
+	};
+
+	let message = || format!("Number literal '{}' is out of range", token.lexeme);
+	literal.ok_or_else(|| Box::new(Diagnostic::error(message(), token.span).with_code("E0112")))
+}
+
+fn unescape(inner: &str, span: Span) -> PResult<String> {
+	let mut out = String::with_capacity(inner.len());
+	let mut chars = inner.chars();
+
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			out.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('n') => out.push('\n'),
+			Some('t') => out.push('\t'),
+			Some('r') => out.push('\r'),
+			Some('0') => out.push('\0'),
+			Some('\\') => out.push('\\'),
+			Some('"') => out.push('"'),
+			Some('\'') => out.push('\''),
+			Some('$') => out.push('$'),
+			other => {
+				let message = format!("Unknown escape sequence '\\{}'", other.map(String::from).unwrap_or_default());
+				return Err(Box::new(Diagnostic::error(message, span).with_code("E0113")));
+			}
+		}
+	}
+
+	Ok(out)
+}
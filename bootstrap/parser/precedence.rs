@@ -0,0 +1,197 @@
+use crate::ast::expr::BinaryOp;
+use crate::lexer::token::TokenType;
+
+// Binding strength of infix operators, weakest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Precedence {
+	Lowest,
+	Assignment, // = += -= *= /= %=
+	Conditional, // if (c) a else b
+	Coalesce,   // ?? ?:
+	Or,         // || or
+	And,        // && and
+	BitOr,      // |
+	BitXor,     // ^
+	BitAnd,     // &
+	Equality,   // == !=
+	Comparison, // < > <= >= is in
+	Range,      // ..
+	Shift,      // << >>
+	Term,       // + -
+	Factor,     // * / %
+	Prefix,     // -x !x ++x --x typeof await
+	Postfix     // x() x.y x[i] x++ x-- x!! x?
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assoc {
+	Left,
+	Right,
+	// `a..b..c` is rejected rather than grouped either way
+	None
+}
+
+impl Precedence {
+	pub fn next(self) -> Precedence {
+		match self {
+			Precedence::Lowest => Precedence::Assignment,
+			Precedence::Assignment => Precedence::Conditional,
+			Precedence::Conditional => Precedence::Coalesce,
+			Precedence::Coalesce => Precedence::Or,
+			Precedence::Or => Precedence::And,
+			Precedence::And => Precedence::BitOr,
+			Precedence::BitOr => Precedence::BitXor,
+			Precedence::BitXor => Precedence::BitAnd,
+			Precedence::BitAnd => Precedence::Equality,
+			Precedence::Equality => Precedence::Comparison,
+			Precedence::Comparison => Precedence::Range,
+			Precedence::Range => Precedence::Shift,
+			Precedence::Shift => Precedence::Term,
+			Precedence::Term => Precedence::Factor,
+			Precedence::Factor => Precedence::Prefix,
+			Precedence::Prefix | Precedence::Postfix => Precedence::Postfix
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fixity {
+	Prefix,
+	Infix,
+	Postfix
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Operator {
+	pub symbol: &'static str,
+	pub fixity: Fixity,
+	pub precedence: Precedence,
+	pub assoc: Assoc
+}
+
+const fn op(symbol: &'static str, fixity: Fixity, precedence: Precedence, assoc: Assoc) -> Operator {
+	Operator { symbol, fixity, precedence, assoc }
+}
+
+// Every operator, weakest first. The parser's `infix` must agree with this
+// (tests/precedence.rs checks it), and the formatter and generated docs read
+// precedence from here rather than keeping their own copies.
+pub const OPERATORS: &[Operator] = &[
+	op("=", Fixity::Infix, Precedence::Assignment, Assoc::Right),
+	op("+=", Fixity::Infix, Precedence::Assignment, Assoc::Right),
+	op("-=", Fixity::Infix, Precedence::Assignment, Assoc::Right),
+	op("*=", Fixity::Infix, Precedence::Assignment, Assoc::Right),
+	op("/=", Fixity::Infix, Precedence::Assignment, Assoc::Right),
+	op("%=", Fixity::Infix, Precedence::Assignment, Assoc::Right),
+	op("if", Fixity::Prefix, Precedence::Conditional, Assoc::Right),
+	op("??", Fixity::Infix, Precedence::Coalesce, Assoc::Right),
+	op("?:", Fixity::Infix, Precedence::Coalesce, Assoc::Right),
+	op("||", Fixity::Infix, Precedence::Or, Assoc::Left),
+	op("or", Fixity::Infix, Precedence::Or, Assoc::Left),
+	op("&&", Fixity::Infix, Precedence::And, Assoc::Left),
+	op("and", Fixity::Infix, Precedence::And, Assoc::Left),
+	op("|", Fixity::Infix, Precedence::BitOr, Assoc::Left),
+	op("^", Fixity::Infix, Precedence::BitXor, Assoc::Left),
+	op("&", Fixity::Infix, Precedence::BitAnd, Assoc::Left),
+	op("==", Fixity::Infix, Precedence::Equality, Assoc::Left),
+	op("!=", Fixity::Infix, Precedence::Equality, Assoc::Left),
+	op("<", Fixity::Infix, Precedence::Comparison, Assoc::Left),
+	op("<=", Fixity::Infix, Precedence::Comparison, Assoc::Left),
+	op(">", Fixity::Infix, Precedence::Comparison, Assoc::Left),
+	op(">=", Fixity::Infix, Precedence::Comparison, Assoc::Left),
+	op("is", Fixity::Infix, Precedence::Comparison, Assoc::Left),
+	op("!is", Fixity::Infix, Precedence::Comparison, Assoc::Left),
+	op("in", Fixity::Infix, Precedence::Comparison, Assoc::Left),
+	op("..", Fixity::Infix, Precedence::Range, Assoc::None),
+	op("<<", Fixity::Infix, Precedence::Shift, Assoc::Left),
+	op(">>", Fixity::Infix, Precedence::Shift, Assoc::Left),
+	op("+", Fixity::Infix, Precedence::Term, Assoc::Left),
+	op("-", Fixity::Infix, Precedence::Term, Assoc::Left),
+	op("*", Fixity::Infix, Precedence::Factor, Assoc::Left),
+	op("/", Fixity::Infix, Precedence::Factor, Assoc::Left),
+	op("%", Fixity::Infix, Precedence::Factor, Assoc::Left),
+	op("-", Fixity::Prefix, Precedence::Prefix, Assoc::Right),
+	op("!", Fixity::Prefix, Precedence::Prefix, Assoc::Right),
+	op("not", Fixity::Prefix, Precedence::Prefix, Assoc::Right),
+	op("++", Fixity::Prefix, Precedence::Prefix, Assoc::Right),
+	op("--", Fixity::Prefix, Precedence::Prefix, Assoc::Right),
+	op("typeof", Fixity::Prefix, Precedence::Prefix, Assoc::Right),
+	op("await", Fixity::Prefix, Precedence::Prefix, Assoc::Right),
+	op("()", Fixity::Postfix, Precedence::Postfix, Assoc::Left),
+	op(".", Fixity::Postfix, Precedence::Postfix, Assoc::Left),
+	op("[]", Fixity::Postfix, Precedence::Postfix, Assoc::Left),
+	op("++", Fixity::Postfix, Precedence::Postfix, Assoc::Left),
+	op("--", Fixity::Postfix, Precedence::Postfix, Assoc::Left),
+	op("!!", Fixity::Postfix, Precedence::Postfix, Assoc::Left),
+	op("?", Fixity::Postfix, Precedence::Postfix, Assoc::Left)
+];
+
+pub fn infix(token_type: TokenType) -> Option<(Precedence, Assoc)> {
+	let entry = match token_type {
+		TokenType::Equal
+		| TokenType::PlusEqual
+		| TokenType::MinusEqual
+		| TokenType::StarEqual
+		| TokenType::SlashEqual
+		| TokenType::PercentEqual => (Precedence::Assignment, Assoc::Right),
+		TokenType::QuestionQuestion | TokenType::QuestionColon => (Precedence::Coalesce, Assoc::Right),
+		TokenType::OrOr | TokenType::Or => (Precedence::Or, Assoc::Left),
+		TokenType::AndAnd | TokenType::And => (Precedence::And, Assoc::Left),
+		TokenType::BitOr => (Precedence::BitOr, Assoc::Left),
+		TokenType::BitXor => (Precedence::BitXor, Assoc::Left),
+		TokenType::BitAnd => (Precedence::BitAnd, Assoc::Left),
+		TokenType::EqualEqual | TokenType::NotEqual => (Precedence::Equality, Assoc::Left),
+		TokenType::Less
+		| TokenType::LessEqual
+		| TokenType::Greater
+		| TokenType::GreaterEqual
+		| TokenType::Is
+		| TokenType::In => (Precedence::Comparison, Assoc::Left),
+		TokenType::DotDot => (Precedence::Range, Assoc::None),
+		TokenType::ShiftLeft | TokenType::ShiftRight => (Precedence::Shift, Assoc::Left),
+		TokenType::Plus | TokenType::Minus => (Precedence::Term, Assoc::Left),
+		TokenType::Star | TokenType::Slash | TokenType::Percent => (Precedence::Factor, Assoc::Left),
+		_ => return None
+	};
+	Some(entry)
+}
+
+pub fn binary_op(token_type: TokenType) -> Option<BinaryOp> {
+	let op = match token_type {
+		TokenType::Plus => BinaryOp::Add,
+		TokenType::Minus => BinaryOp::Subtract,
+		TokenType::Star => BinaryOp::Multiply,
+		TokenType::Slash => BinaryOp::Divide,
+		TokenType::Percent => BinaryOp::Modulo,
+		TokenType::EqualEqual => BinaryOp::Equal,
+		TokenType::NotEqual => BinaryOp::NotEqual,
+		TokenType::Less => BinaryOp::Less,
+		TokenType::LessEqual => BinaryOp::LessEqual,
+		TokenType::Greater => BinaryOp::Greater,
+		TokenType::GreaterEqual => BinaryOp::GreaterEqual,
+		TokenType::QuestionQuestion | TokenType::QuestionColon => BinaryOp::Coalesce,
+		TokenType::AndAnd | TokenType::And => BinaryOp::And,
+		TokenType::OrOr | TokenType::Or => BinaryOp::Or,
+		TokenType::BitAnd => BinaryOp::BitAnd,
+		TokenType::BitOr => BinaryOp::BitOr,
+		TokenType::BitXor => BinaryOp::BitXor,
+		TokenType::ShiftLeft => BinaryOp::ShiftLeft,
+		TokenType::ShiftRight => BinaryOp::ShiftRight,
+		TokenType::In => BinaryOp::In,
+		_ => return None
+	};
+	Some(op)
+}
+
+// The operator `x op= y` applies before storing into `x`.
+pub fn compound_op(token_type: TokenType) -> Option<BinaryOp> {
+	let op = match token_type {
+		TokenType::PlusEqual => BinaryOp::Add,
+		TokenType::MinusEqual => BinaryOp::Subtract,
+		TokenType::StarEqual => BinaryOp::Multiply,
+		TokenType::SlashEqual => BinaryOp::Divide,
+		TokenType::PercentEqual => BinaryOp::Modulo,
+		_ => return None
+	};
+	Some(op)
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+use crate::ast::Program;
+use crate::ast::stmt::{StmtKind, TypeAliasDecl};
+use crate::ast::types::{TypeExpr, TypeExprKind};
+use crate::diagnostics::Diagnostic;
+
+// Top-level `type` declarations of a module. Aliases are transparent: the
+// checker expands them away before comparing types, which is why recursive
+// aliases are rejected up front instead of being unfolded forever.
+#[derive(Debug, Default)]
+pub struct AliasTable {
+	aliases: HashMap<String, TypeAliasDecl>
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+	InProgress,
+	Done
+}
+
+impl AliasTable {
+	pub fn collect(program: &Program) -> Result<AliasTable, Vec<Diagnostic>> {
+		let mut table = AliasTable::default();
+		let mut errors = Vec::new();
+
+		for stmt in &program.stmts {
+			if let StmtKind::TypeAlias(decl) = &stmt.kind {
+				if let Some(previous) = table.aliases.get(&decl.name) {
+					errors.push(Diagnostic::error(
+						format!(
+							"Type alias '{}' is already defined at line {} column {}",
+							decl.name, previous.name_span.line, previous.name_span.column
+						),
+						decl.name_span
+					)
+					.with_code("E0209"));
+					continue;
+				}
+				table.aliases.insert(decl.name.clone(), decl.clone());
+			}
+		}
+
+		errors.extend(table.find_cycles());
+		if errors.is_empty() {
+			errors.extend(table.check_arity());
+		}
+
+		if errors.is_empty() { Ok(table) } else { Err(errors) }
+	}
+
+	pub fn get(&self, name: &str) -> Option<&TypeAliasDecl> {
+		self.aliases.get(name)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.aliases.is_empty()
+	}
+
+	// Replaces every alias in `ty` with what it stands for.
+	pub fn expand(&self, ty: &TypeExpr) -> Result<TypeExpr, Box<Diagnostic>> {
+		self.expand_with(ty, &HashMap::new())
+	}
+
+	fn expand_with(&self, ty: &TypeExpr, subst: &HashMap<&str, TypeExpr>) -> Result<TypeExpr, Box<Diagnostic>> {
+		let kind = match &ty.kind {
+			TypeExprKind::Named { name, args } => {
+				if args.is_empty()
+					&& let Some(replacement) = subst.get(name.as_str())
+				{
+					return Ok(TypeExpr { kind: replacement.kind.clone(), span: ty.span });
+				}
+
+				let args = args.iter().map(|arg| self.expand_with(arg, subst)).collect::<Result<Vec<_>, _>>()?;
+
+				match self.aliases.get(name) {
+					Some(alias) => {
+						if alias.type_params.len() != args.len() {
+							return Err(Box::new(arity_error(alias, args.len(), ty)));
+						}
+						let inner: HashMap<&str, TypeExpr> =
+							alias.type_params.iter().map(|p| p.name.as_str()).zip(args).collect();
+						let expanded = self.expand_with(&alias.ty, &inner)?;
+						return Ok(TypeExpr { kind: expanded.kind, span: ty.span });
+					}
+					None => TypeExprKind::Named { name: name.clone(), args }
+				}
+			}
+			TypeExprKind::Nullable(inner) => TypeExprKind::Nullable(Box::new(self.expand_with(inner, subst)?)),
+			TypeExprKind::Array(inner) => TypeExprKind::Array(Box::new(self.expand_with(inner, subst)?)),
+			TypeExprKind::Function { params, ret } => TypeExprKind::Function {
+				params: params.iter().map(|p| self.expand_with(p, subst)).collect::<Result<Vec<_>, _>>()?,
+				ret: Box::new(self.expand_with(ret, subst)?)
+			},
+			TypeExprKind::Union(members) => {
+				TypeExprKind::Union(members.iter().map(|member| self.expand_with(member, subst)).collect::<Result<Vec<_>, _>>()?)
+			}
+		};
+
+		Ok(TypeExpr { kind, span: ty.span })
+	}
+
+	fn find_cycles(&self) -> Vec<Diagnostic> {
+		let mut errors = Vec::new();
+		let mut state: HashMap<&str, Visit> = HashMap::new();
+
+		let mut names: Vec<&str> = self.aliases.keys().map(String::as_str).collect();
+		names.sort_by_key(|name| self.aliases[*name].span.start);
+
+		for name in names {
+			let mut path = Vec::new();
+			self.visit(name, &mut state, &mut path, &mut errors);
+		}
+
+		errors
+	}
+
+	fn visit<'a>(&'a self, name: &'a str, state: &mut HashMap<&'a str, Visit>, path: &mut Vec<&'a str>, errors: &mut Vec<Diagnostic>) {
+		match state.get(name) {
+			Some(Visit::Done) => return,
+			Some(Visit::InProgress) => {
+				let start = path.iter().position(|n| *n == name).unwrap_or(0);
+				let mut cycle: Vec<&str> = path[start..].to_vec();
+				cycle.push(name);
+				let decl = &self.aliases[name];
+				errors.push(Diagnostic::error(
+					format!("Type alias '{}' is recursive: {}", name, cycle.join(" -> ")),
+					decl.name_span
+				)
+				.with_code("E0408"));
+				return;
+			}
+			None => {}
+		}
+
+		state.insert(name, Visit::InProgress);
+		path.push(name);
+
+		let decl = &self.aliases[name];
+		let mut refs = Vec::new();
+		collect_names(&decl.ty, &mut refs);
+		for referenced in refs {
+			if decl.type_params.iter().any(|p| p.name == referenced) {
+				continue;
+			}
+			if let Some((key, _)) = self.aliases.get_key_value(referenced) {
+				self.visit(key, state, path, errors);
+			}
+		}
+
+		path.pop();
+		state.insert(name, Visit::Done);
+	}
+
+	// Alias uses inside other aliases get their argument count checked here;
+	// uses elsewhere are checked when they are expanded.
+	fn check_arity(&self) -> Vec<Diagnostic> {
+		let mut errors = Vec::new();
+		for decl in self.aliases.values() {
+			if let Err(err) = self.expand(&decl.ty) {
+				errors.push(*err);
+			}
+		}
+		errors.sort_by_key(|e| e.primary_span.start);
+		errors
+	}
+}
+
+fn arity_error(alias: &TypeAliasDecl, found: usize, ty: &TypeExpr) -> Diagnostic {
+	Diagnostic::error(
+		format!(
+			"Type alias '{}' expects {} type argument(s) but {} were given",
+			alias.name,
+			alias.type_params.len(),
+			found
+		),
+		ty.span
+	)
+	.with_code("E0407")
+}
+
+fn collect_names<'a>(ty: &'a TypeExpr, out: &mut Vec<&'a str>) {
+	match &ty.kind {
+		TypeExprKind::Named { name, args } => {
+			out.push(name);
+			for arg in args {
+				collect_names(arg, out);
+			}
+		}
+		TypeExprKind::Nullable(inner) | TypeExprKind::Array(inner) => collect_names(inner, out),
+		TypeExprKind::Function { params, ret } => {
+			for param in params {
+				collect_names(param, out);
+			}
+			collect_names(ret, out);
+		}
+		TypeExprKind::Union(members) => {
+			for member in members {
+				collect_names(member, out);
+			}
+		}
+	}
+}
@@ -0,0 +1,329 @@
+use crate::sema::types::Type;
+
+// Functions every file can call without importing anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+	Print,
+	Println,
+	// Waits the given number of milliseconds
+	Sleep,
+	// The text of the file at the path
+	ReadFile,
+	// The next line of standard input, without its line break, or null at
+	// the end
+	ReadLine,
+	// A new `Channel`, open and empty
+	Channel,
+	// What make a `Result` and an `Option`. `None` is a value, not called:
+	// the checker makes it a call with no arguments
+	Ok,
+	Err,
+	Some,
+	None
+}
+
+impl Builtin {
+	pub const ALL: &[Builtin] = &[
+		Builtin::Print,
+		Builtin::Println,
+		Builtin::Sleep,
+		Builtin::ReadFile,
+		Builtin::ReadLine,
+		Builtin::Channel,
+		Builtin::Ok,
+		Builtin::Err,
+		Builtin::Some,
+		Builtin::None
+	];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Builtin::Print => "print",
+			Builtin::Println => "println",
+			Builtin::Sleep => "sleep",
+			Builtin::ReadFile => "readFile",
+			Builtin::ReadLine => "readLine",
+			Builtin::Channel => "channel",
+			Builtin::Ok => "Ok",
+			Builtin::Err => "Err",
+			Builtin::Some => "Some",
+			Builtin::None => "None"
+		}
+	}
+
+	// Whether calling it starts a task to `await`, rather than doing it
+	// there and then. The interpreter does these there and then too.
+	pub fn is_async(self) -> bool {
+		matches!(self, Builtin::Sleep | Builtin::ReadFile | Builtin::ReadLine)
+	}
+
+	pub fn lookup(name: &str) -> Option<Builtin> {
+		Builtin::ALL.iter().copied().find(|builtin| builtin.name() == name)
+	}
+
+	// With `Any` for the value `Ok`, `Err` and `Some` wrap, which the checker
+	// infers for each call (see `wraps`).
+	pub fn signature(self) -> Type {
+		match self {
+			Builtin::Print | Builtin::Println => Type::Function { params: vec![Type::Any], ret: Box::new(Type::Void) },
+			Builtin::Sleep => Type::Function { params: vec![Type::Int], ret: Box::new(Type::Void) },
+			Builtin::ReadFile => Type::Function { params: vec![Type::String], ret: Box::new(Type::String) },
+			Builtin::ReadLine => Type::Function { params: Vec::new(), ret: Box::new(Type::String.nullable()) },
+			// Of `Any`, which a `Channel` of any type takes, as it is empty
+			Builtin::Channel => Type::Function { params: Vec::new(), ret: Box::new(Type::Channel(Box::new(Type::Any))) },
+			Builtin::None => Type::Function { params: Vec::new(), ret: Box::new(Type::Option(Box::new(Type::Never))) },
+			_ => self.wraps(&Type::Any).unwrap_or(Type::Error)
+		}
+	}
+
+	// What `Ok`, `Err` and `Some` are, wrapping a `value`. What they leave
+	// out is `Never`, which any type takes, so `Ok(1)` is a
+	// `Result<Int, Never>` that fits any `Result<Int, E>`.
+	pub fn wraps(self, value: &Type) -> Option<Type> {
+		let boxed = || Box::new(value.clone());
+		let ret = match self {
+			Builtin::Ok => Type::Result(boxed(), Box::new(Type::Never)),
+			Builtin::Err => Type::Result(Box::new(Type::Never), boxed()),
+			Builtin::Some => Type::Option(boxed()),
+			_ => return None
+		};
+		Some(Type::Function { params: vec![value.clone()], ret: Box::new(ret) })
+	}
+}
+
+pub const TYPE_NAMES: &[&str] = &[
+	"Int", "Float", "Bool", "String", "Void", "Any", "Never", "Map", "Set", "Iterator", "Result", "Option", "Random", "Pointer", "Channel"
+];
+
+pub fn type_named(name: &str) -> Option<Type> {
+	let ty = match name {
+		"Int" => Type::Int,
+		"Float" => Type::Float,
+		"Bool" => Type::Bool,
+		"String" => Type::String,
+		"Void" => Type::Void,
+		"Any" => Type::Any,
+		"Never" => Type::Never,
+		"Random" => Type::Random,
+		"Pointer" => Type::Pointer,
+		_ => return None
+	};
+	Some(ty)
+}
+
+// The built-in types that take type arguments, and how many.
+pub fn type_params(name: &str) -> Option<usize> {
+	match name {
+		"Map" | "Result" => Some(2),
+		"Set" | "Iterator" | "Option" | "Channel" => Some(1),
+		_ => None
+	}
+}
+
+// One of those of `args`, as many as it takes, or none for all of `Any`.
+pub fn generic_type(name: &str, mut args: Vec<Type>) -> Type {
+	let mut arg = || Box::new(args.pop().unwrap_or(Type::Any));
+	match name {
+		"Map" => {
+			let value = arg();
+			Type::Map(arg(), value)
+		}
+		"Result" => {
+			let error = arg();
+			Type::Result(arg(), error)
+		}
+		"Set" => Type::Set(arg()),
+		"Option" => Type::Option(arg()),
+		"Channel" => Type::Channel(arg()),
+		_ => Type::Iterator(arg())
+	}
+}
+
+// Members that values of built-in types come with. Every value has
+// `toString()`; classes get it through here too unless they declare their own.
+//
+// Strings are worked on by character, as `length` and indexing count them,
+// not by byte. Arrays grow and shrink through their methods; what looks an
+// element up finds it by equality, as `==` does.
+pub fn member(ty: &Type, name: &str) -> Option<Type> {
+	let method = |ret: Type| Type::Function { params: Vec::new(), ret: Box::new(ret) };
+	let taking = |params: Vec<Type>, ret: Type| Type::Function { params, ret: Box::new(ret) };
+	match (ty, name) {
+		(_, "toString") => Some(method(Type::String)),
+		(Type::Int, "toFloat") => Some(method(Type::Float)),
+		(Type::Float, "toInt") => Some(method(Type::Int)),
+		(Type::String, "length") => Some(Type::Int),
+		(Type::String, "isEmpty") => Some(method(Type::Bool)),
+		(Type::String, "trim" | "trimStart" | "trimEnd" | "toUpperCase" | "toLowerCase") => Some(method(Type::String)),
+		(Type::String, "chars") => Some(method(Type::Array(Box::new(Type::String)))),
+		(Type::String, "contains" | "startsWith" | "endsWith") => Some(taking(vec![Type::String], Type::Bool)),
+		(Type::String, "indexOf") => Some(taking(vec![Type::String], Type::Int)),
+		(Type::String, "split") => Some(taking(vec![Type::String], Type::Array(Box::new(Type::String)))),
+		(Type::String, "slice") => Some(taking(vec![Type::Int, Type::Int], Type::String)),
+		(Type::String, "replace") => Some(taking(vec![Type::String, Type::String], Type::String)),
+		(Type::String, "padStart" | "padEnd") => Some(taking(vec![Type::Int, Type::String], Type::String)),
+		(Type::String, "repeat") => Some(taking(vec![Type::Int], Type::String)),
+		// Each `{}` in the string is replaced by the next argument as a string
+		(Type::String, "format") => Some(taking(vec![Type::Array(Box::new(Type::Any))], Type::String)),
+		(Type::Array(_) | Type::Map(..) | Type::Set(_), "size") => Some(Type::Int),
+		(Type::Array(_) | Type::Map(..) | Type::Set(_), "isEmpty") => Some(method(Type::Bool)),
+		(Type::Array(_) | Type::Map(..) | Type::Set(_), "clear") => Some(method(Type::Void)),
+		(Type::Array(element), "push") => Some(taking(vec![(**element).clone()], Type::Void)),
+		(Type::Array(element), "insert") => Some(taking(vec![Type::Int, (**element).clone()], Type::Void)),
+		// The last element, taken off, or null when there is none
+		(Type::Array(element), "pop") => Some(method((**element).clone().nullable())),
+		(Type::Array(element), "removeAt") => Some(taking(vec![Type::Int], (**element).clone())),
+		// Whether the element was there to remove, the first of it if more
+		(Type::Array(element) | Type::Set(element), "remove") => Some(taking(vec![(**element).clone()], Type::Bool)),
+		(Type::Array(element) | Type::Set(element), "contains") => Some(taking(vec![(**element).clone()], Type::Bool)),
+		(Type::Array(element), "indexOf") => Some(taking(vec![(**element).clone()], Type::Int)),
+		// Null for a key the map does not have, as indexing it is
+		(Type::Map(key, value), "get") => Some(taking(vec![(**key).clone()], (**value).clone().nullable())),
+		(Type::Map(key, value), "set") => Some(taking(vec![(**key).clone(), (**value).clone()], Type::Void)),
+		// The value the key had, if it was there
+		(Type::Map(key, value), "remove") => Some(taking(vec![(**key).clone()], (**value).clone().nullable())),
+		(Type::Map(key, _), "containsKey") => Some(taking(vec![(**key).clone()], Type::Bool)),
+		(Type::Map(key, _), "keys") => Some(method(Type::Array(key.clone()))),
+		(Type::Map(_, value), "values") => Some(method(Type::Array(value.clone()))),
+		// Whether it was not there already
+		(Type::Set(element), "add") => Some(taking(vec![(**element).clone()], Type::Bool)),
+		(Type::Iterator(_), "hasNext") => Some(method(Type::Bool)),
+		(Type::Iterator(element), "next") => Some(method((**element).clone())),
+		(Type::Range, "start" | "end") => Some(Type::Int),
+		(Type::Array(element) | Type::Set(element) | Type::Map(element, _) | Type::Iterator(element), _) => sequence_member(element, name, &Type::Any),
+		(Type::Range, _) => sequence_member(&Type::Int, name, &Type::Any),
+		(Type::Result(..) | Type::Option(_), _) => wrapper_member(ty, name, &Type::Any),
+		(Type::Random, _) => random_member(name, &Type::Any),
+		// Sending never waits, and throws once the channel is closed.
+		// Receiving is async: it waits for a value if there is none yet, and
+		// gives null once the channel is closed and every value received
+		(Type::Channel(element), "send") => Some(taking(vec![(**element).clone()], Type::Void)),
+		(Type::Channel(element), "receive") => Some(method((**element).clone().nullable())),
+		(Type::Channel(_), "close") => Some(method(Type::Void)),
+		(Type::Channel(_), "isClosed") => Some(method(Type::Bool)),
+		_ => None
+	}
+}
+
+// The methods of `Result`s and `Option`s. Those that take a function call
+// it only with a value there is: `map` the value of an `Ok` or a `Some`,
+// `mapErr` the error of an `Err`, and `orElse` for an `Err` or `None`.
+// `unwrap()` throws for an `Err` or `None`.
+//
+// `map`, `mapErr`, `andThen` and `okOr` make something of the type
+// `result`, which the checker infers for each call.
+pub fn wrapper_member(ty: &Type, name: &str, result: &Type) -> Option<Type> {
+	let taking = |params: Vec<Type>, ret: Type| Type::Function { params, ret: Box::new(ret) };
+	let method = |ret: Type| taking(Vec::new(), ret);
+	let boxed = |ty: &Type| Box::new(ty.clone());
+	Some(match (ty, name) {
+		(Type::Result(..), "isOk" | "isErr") | (Type::Option(_), "isSome" | "isNone") => method(Type::Bool),
+		(Type::Result(value, _) | Type::Option(value), "unwrap") => method((**value).clone()),
+		(Type::Result(value, _) | Type::Option(value), "unwrapOr") => taking(vec![(**value).clone()], (**value).clone()),
+		(Type::Result(_, error), "unwrapErr") => method((**error).clone()),
+		(Type::Result(value, _), "ok") => method(Type::Option(value.clone())),
+		(Type::Result(_, error), "err") => method(Type::Option(error.clone())),
+		(Type::Result(value, error), "map") => taking(vec![taking(vec![(**value).clone()], result.clone())], Type::Result(boxed(result), error.clone())),
+		(Type::Result(value, error), "mapErr") => taking(vec![taking(vec![(**error).clone()], result.clone())], Type::Result(value.clone(), boxed(result))),
+		(Type::Result(value, error), "andThen") => {
+			let then = Type::Result(boxed(result), error.clone());
+			taking(vec![taking(vec![(**value).clone()], then.clone())], then)
+		}
+		(Type::Result(value, error), "orElse") => {
+			let otherwise = Type::Result(value.clone(), boxed(result));
+			taking(vec![taking(vec![(**error).clone()], otherwise.clone())], otherwise)
+		}
+		(Type::Option(value), "map") => taking(vec![taking(vec![(**value).clone()], result.clone())], Type::Option(boxed(result))),
+		(Type::Option(value), "andThen") => taking(vec![taking(vec![(**value).clone()], Type::Option(boxed(result)))], Type::Option(boxed(result))),
+		(Type::Option(_), "orElse") => taking(vec![method(ty.clone())], ty.clone()),
+		(Type::Option(value), "okOr") => taking(vec![result.clone()], Type::Result(value.clone(), boxed(result))),
+		// The value, or null for `None`
+		(Type::Option(value), "orNull") => method((**value).clone().nullable()),
+		_ => return None
+	})
+}
+
+// The methods of `Random` generators. `int(low, high)` includes both ends,
+// `float()` is from 0 up to 1 but not 1, and `shuffle` reorders an array in
+// place. `choice` returns an element of the type `result`, which the checker
+// infers for each call.
+pub fn random_member(name: &str, result: &Type) -> Option<Type> {
+	let taking = |params: Vec<Type>, ret: Type| Type::Function { params, ret: Box::new(ret) };
+	Some(match name {
+		"int" => taking(vec![Type::Int, Type::Int], Type::Int),
+		"float" => taking(Vec::new(), Type::Float),
+		"choice" => taking(vec![Type::Array(Box::new(result.clone()))], result.clone()),
+		"shuffle" => taking(vec![Type::Array(Box::new(Type::Any))], Type::Void),
+		_ => return None
+	})
+}
+
+// The methods of everything a `for` loop can go through, by the type of
+// its elements, which for a map are its keys. `map`, `filter`, `zip` and
+// `enumerate` are lazy: they make an `Iterator` that works out each element
+// only when it is asked for one, from as much of what it was made from as
+// that takes. The others go through the elements there and then.
+//
+// `map` and `reduce` make something of the type `result`, which the
+// checker infers for each call.
+pub fn sequence_member(element: &Type, name: &str, result: &Type) -> Option<Type> {
+	let taking = |params: Vec<Type>, ret: Type| Type::Function { params, ret: Box::new(ret) };
+	let iterator = |element: &Type| Type::Iterator(Box::new(element.clone()));
+	let test = || taking(vec![element.clone()], Type::Bool);
+	// Each element with the one of the other at the same place, or with its
+	// index, as an array of the two
+	let pairs = || Type::Iterator(Box::new(Type::Array(Box::new(Type::Any))));
+	Some(match name {
+		"iterator" => taking(Vec::new(), iterator(element)),
+		"toArray" => taking(Vec::new(), Type::Array(Box::new(element.clone()))),
+		"map" => taking(vec![taking(vec![element.clone()], result.clone())], iterator(result)),
+		"filter" => taking(vec![test()], iterator(element)),
+		// From `initial`, each element added to what it came to so far
+		"reduce" => taking(vec![result.clone(), taking(vec![result.clone(), element.clone()], result.clone())], result.clone()),
+		"any" | "all" => taking(vec![test()], Type::Bool),
+		"zip" => taking(vec![Type::Any], pairs()),
+		"enumerate" => taking(Vec::new(), pairs()),
+		_ => return None
+	})
+}
+
+pub const SEQUENCE_METHODS: &[&str] = &["iterator", "toArray", "map", "filter", "reduce", "any", "all", "zip", "enumerate"];
+
+pub fn member_names(ty: &Type) -> &'static [&'static str] {
+	match ty {
+		Type::Int => &["toString", "toFloat"],
+		Type::Float => &["toString", "toInt"],
+		Type::String => &[
+			"toString",
+			"length",
+			"isEmpty",
+			"trim",
+			"trimStart",
+			"trimEnd",
+			"toUpperCase",
+			"toLowerCase",
+			"chars",
+			"contains",
+			"startsWith",
+			"endsWith",
+			"indexOf",
+			"split",
+			"slice",
+			"replace",
+			"padStart",
+			"padEnd",
+			"repeat",
+			"format"
+		],
+		Type::Array(_) => &["toString", "size", "isEmpty", "clear", "push", "insert", "pop", "removeAt", "remove", "contains", "indexOf"],
+		Type::Map(..) => &["toString", "size", "isEmpty", "clear", "get", "set", "remove", "containsKey", "keys", "values"],
+		Type::Set(_) => &["toString", "size", "isEmpty", "clear", "add", "remove", "contains", "toArray"],
+		Type::Iterator(_) => &["toString", "hasNext", "next"],
+		Type::Range => &["toString", "start", "end"],
+		Type::Result(..) => &["toString", "isOk", "isErr", "unwrap", "unwrapOr", "unwrapErr", "ok", "err", "map", "mapErr", "andThen", "orElse"],
+		Type::Option(_) => &["toString", "isSome", "isNone", "unwrap", "unwrapOr", "map", "andThen", "orElse", "okOr", "orNull"],
+		Type::Random => &["toString", "int", "float", "choice", "shuffle"],
+		Type::Channel(_) => &["toString", "send", "receive", "close", "isClosed"],
+		_ => &["toString"]
+	}
+}
@@ -0,0 +1,4854 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use crate::ast::NodeId;
+use crate::ast::annotations::Annotation;
+use crate::ast::visitor::{self, Visitor};
+use crate::ast::expr::{BinaryOp, Expr, ExprKind, InterpolationPart, LambdaBody, Literal, PostfixOp, UnaryOp};
+use crate::ast::stmt::{
+	Accessor, AccessorKind, Block, ENTRIES_METHOD, EnumVariant, FieldDecl, FnBody, FnDecl, ForKind, ForLoop,
+	ITERATOR_METHOD, Member, Modifiers, NEXT_METHOD, Param, Stmt, StmtKind, TypeAliasDecl, Visibility
+};
+use crate::ast::types::{TypeExpr, TypeExprKind, TypeParam};
+use crate::diagnostics::{Diagnostic, suggest, timings};
+use crate::lexer::token::Span;
+use crate::sema::builtins::{self, Builtin};
+use crate::driver::parallel;
+use crate::sema::coerce;
+use crate::sema::consts;
+use crate::sema::flow;
+use crate::sema::lint;
+use crate::sema::merge::{Counts, Fork};
+use crate::sema::hir::{self, CType, ClassKind, Coercion, FnKind, MemberTarget, StaticTarget, Variable};
+use crate::sema::modules::{ModuleGraph, SourceFile, SymbolId};
+use crate::sema::processors::{self, Processor};
+use crate::sema::resolve::{self, Resolution, Resolutions};
+use crate::sema::types::{FnId, GlobalId, LocalId, Type, TypeId, TypeParamId};
+
+// Type checks a whole program and lowers it to HIR. Names are resolved first
+// and the checker only runs once every file resolves cleanly.
+//
+// Checking is bidirectional: `check` pushes an expected type into an
+// expression and `infer` works one out from the expression alone.
+// Declarations without a type take their initializer's type; parameters and
+// return types without one are `Any`, which is compatible with everything in
+// both directions, so untyped code is accepted and typed code is held to its
+// annotations.
+//
+// Annotation processors run last, over a program that checked cleanly.
+// Warnings in declarations marked `@Suppress("name")` are dropped for the
+// lints named; how the rest are reported is up to the caller's `LintLevels`.
+pub fn check(files: &[SourceFile]) -> Result<hir::Program, Vec<Diagnostic>> {
+	check_with(files, &mut processors::defaults(), &[], None)
+}
+
+// `check` with the caller's own annotation processors in place of the
+// defaults, for a runtime that gives code `natives` to call. With `kept`,
+// packages whose bodies checked cleanly last time it was given are not
+// checked again unless something they depend on changed.
+pub fn check_with(
+	files: &[SourceFile],
+	processors: &mut [Box<dyn Processor>],
+	natives: &[hir::Native],
+	kept: Option<&mut Bodies>
+) -> Result<hir::Program, Vec<Diagnostic>> {
+	let graph = timings::time("modules", "", || ModuleGraph::build(files, natives));
+
+	let mut errors = graph.import_cycles(files);
+	let mut resolutions = Vec::new();
+	for file in 0..files.len() {
+		match timings::time("resolve", &files[file].path, || resolve::resolve(&graph, files, file)) {
+			Ok(resolved) => resolutions.push(resolved),
+			Err(errs) => {
+				errors.extend(errs);
+				resolutions.push(Resolutions::default());
+			}
+		}
+	}
+	if !errors.is_empty() {
+		return Err(errors);
+	}
+	let mut warnings: Vec<Diagnostic> = resolutions.iter_mut().flat_map(|resolved| std::mem::take(&mut resolved.warnings)).collect();
+
+	let mut checker = Checker {
+		files,
+		graph: &graph,
+		resolutions: &resolutions,
+		program: hir::Program { natives: natives.to_vec(), ..hir::Program::default() },
+		nodes: vec![HashMap::new(); files.len()],
+		symbols: HashMap::new(),
+		extensions: HashMap::new(),
+		min_args: HashMap::new(),
+		min_fields: HashMap::new(),
+		aliases: HashMap::new(),
+		pending: HashMap::new(),
+		inferring: Vec::new(),
+		global_inits: HashMap::new(),
+		consts: HashMap::new(),
+		used_consts: HashSet::new(),
+		probed: HashMap::new(),
+		expanding: Vec::new(),
+		file: 0,
+		ctx: Context::default(),
+		narrowed: HashMap::new(),
+		unsolved: Vec::new(),
+		result: None,
+		errors: Vec::new()
+	};
+	timings::time("check", "", || {
+		checker.declare();
+		checker.imports();
+		checker.signatures();
+		checker.duplicates();
+		checker.inheritance();
+		checker.bodies(kept);
+		checker.externs();
+	});
+	checker.program.init_order = graph.initialization_order();
+	let flow = timings::time("flow", "", || flow::check(&checker.program));
+	checker.errors.extend(flow);
+	if checker.errors.is_empty() {
+		timings::time("lint", "", || {
+			warnings.extend(lint::lint(&checker.program, &checker.used_consts));
+			let report = processors::run(&mut checker.program, processors);
+			warnings.extend(report.warnings);
+			checker.errors.extend(report.errors);
+			checker.program.warnings = lint::suppress(&checker.program, warnings);
+		});
+	}
+
+	if checker.errors.is_empty() { Ok(checker.program) } else { Err(checker.errors) }
+}
+
+// What the bodies of each package checked to, for a caller that checks the
+// same files over and over to keep between checks. A package is checked
+// again once what its files or those of the packages it imports parsed to
+// changes, or the numbering of what it can see does.
+#[derive(Debug, Default)]
+pub struct Bodies {
+	packages: HashMap<String, (u64, PackageBodies)>,
+	// Of the last check, the packages taken from here
+	reused: Vec<String>
+}
+
+impl Bodies {
+	pub fn new() -> Bodies {
+		Bodies::default()
+	}
+
+	pub fn reused(&self) -> &[String] {
+		&self.reused
+	}
+}
+
+// What checking one package's bodies came to.
+#[derive(Debug, Clone)]
+struct PackageBodies {
+	fork: Fork,
+	files: Vec<(usize, hir::File)>,
+	errors: Vec<Diagnostic>,
+	used_consts: HashSet<Variable>
+}
+
+// What a declaration or binding in the AST became.
+#[derive(Debug, Clone, Copy)]
+enum Def<'a> {
+	Class(TypeId),
+	Function(FnId),
+	Global(GlobalId),
+	Local(LocalId),
+	// A function declared in a block: called through its local
+	LocalFunction(LocalId, FnId),
+	TypeParam(TypeParamId),
+	Alias(usize, &'a TypeAliasDecl)
+}
+
+// What a name used as a value turned out to be.
+enum Named {
+	Value(hir::Expr),
+	Type(TypeId),
+	Package(String)
+}
+
+// A `val` or field declared without a type, which takes the type of its
+// initializer the first time anything needs it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Pending {
+	Global(GlobalId),
+	Field(TypeId, usize)
+}
+
+// Types variables are known to have, narrowed from their declared ones
+type Facts = Vec<(Variable, Type)>;
+
+// Where the expressions being checked are.
+#[derive(Debug, Clone, Default)]
+struct Context {
+	// Return type of the enclosing function; None at the top level
+	ret: Option<Type>,
+	// Type of `this` in methods, accessors, constructors and extensions
+	this: Option<Type>,
+	class: Option<TypeId>,
+	// Name and type of the property whose accessor is being checked
+	accessor: Option<(String, Type)>,
+	constructor: bool,
+	// Loops around the statement being checked, innermost last, with their labels
+	loops: Vec<Option<String>>,
+	// How many of `loops` are around the `defer` being checked, which cannot
+	// jump out of what it defers; None outside one
+	deferred: Option<usize>,
+	// The static field whose initializer is being checked, which runs before
+	// those declared after it in its class are set
+	initializing: Option<GlobalId>,
+	// Function whose body is being checked; None at the top level and in
+	// field initializers
+	function: Option<FnId>
+}
+
+// Type parameters of a generic call being inferred, and what the arguments
+// and the call's context have said about them so far.
+struct Inference {
+	vars: Vec<TypeParamId>,
+	// The call's result type, and the type its context wants if any
+	ret: Type,
+	expected: Option<Type>,
+	solved: HashMap<TypeParamId, Type>,
+	// Parameters two arguments disagree on, with both types
+	conflicts: Vec<(TypeParamId, Type, Type)>
+}
+
+impl Inference {
+	fn new(vars: Vec<TypeParamId>, ret: Type) -> Inference {
+		Inference { vars, ret, expected: None, solved: HashMap::new(), conflicts: Vec::new() }
+	}
+}
+
+// Class, interface, enum and struct declarations seen through one shape.
+struct ClassView<'a> {
+	id: NodeId,
+	name: &'a str,
+	kind: ClassKind,
+	modifiers: &'a Modifiers,
+	annotations: &'a [Annotation],
+	type_params: &'a [TypeParam],
+	supertypes: &'a [TypeExpr],
+	members: &'a [Member],
+	fields: &'a [FieldDecl],
+	variants: &'a [EnumVariant],
+	span: Span
+}
+
+impl<'a> ClassView<'a> {
+	fn of(kind: &'a StmtKind) -> Option<ClassView<'a>> {
+		let view = match kind {
+			StmtKind::Class(decl) => ClassView {
+				id: decl.id,
+				name: &decl.name,
+				kind: ClassKind::Class,
+				modifiers: &decl.modifiers,
+				annotations: &decl.annotations,
+				type_params: &decl.type_params,
+				supertypes: &decl.supertypes,
+				members: &decl.members,
+				fields: &[],
+				variants: &[],
+				span: decl.name_span
+			},
+			StmtKind::Interface(decl) => ClassView {
+				id: decl.id,
+				name: &decl.name,
+				kind: ClassKind::Interface,
+				modifiers: &decl.modifiers,
+				annotations: &decl.annotations,
+				type_params: &decl.type_params,
+				supertypes: &decl.supertypes,
+				members: &decl.members,
+				fields: &[],
+				variants: &[],
+				span: decl.name_span
+			},
+			StmtKind::Enum(decl) => ClassView {
+				id: decl.id,
+				name: &decl.name,
+				kind: ClassKind::Enum,
+				modifiers: &decl.modifiers,
+				annotations: &decl.annotations,
+				type_params: &[],
+				supertypes: &decl.supertypes,
+				members: &decl.members,
+				fields: &[],
+				variants: &decl.variants,
+				span: decl.name_span
+			},
+			StmtKind::Struct(decl) => ClassView {
+				id: decl.id,
+				name: &decl.name,
+				kind: ClassKind::Struct,
+				modifiers: &decl.modifiers,
+				annotations: &decl.annotations,
+				type_params: &decl.type_params,
+				supertypes: &[],
+				members: &[],
+				fields: &decl.fields,
+				variants: &[],
+				span: decl.name_span
+			},
+			_ => return None
+		};
+		Some(view)
+	}
+}
+
+struct Checker<'a> {
+	files: &'a [SourceFile],
+	graph: &'a ModuleGraph,
+	resolutions: &'a [Resolutions],
+	program: hir::Program,
+	// Declarations and bindings by node id, one table per file
+	nodes: Vec<HashMap<NodeId, Def<'a>>>,
+	symbols: HashMap<SymbolId, Def<'a>>,
+	// Extension functions by name
+	extensions: HashMap<String, Vec<FnId>>,
+	// Arguments a function, or field values a struct, needs; trailing ones
+	// with defaults may be left out. Kept aside because defaults are only
+	// checked along with the bodies.
+	min_args: HashMap<FnId, usize>,
+	min_fields: HashMap<TypeId, usize>,
+	// Expanded alias bodies, still in terms of the alias's parameters
+	aliases: HashMap<(usize, NodeId), Type>,
+	// Initializers of declarations whose type is still to be inferred, with
+	// the file they are in
+	pending: HashMap<Pending, (usize, &'a Expr)>,
+	inferring: Vec<Pending>,
+	// Global initializers already checked while inferring their type
+	global_inits: HashMap<GlobalId, hir::Expr>,
+	// Values of the consts checked so far
+	consts: HashMap<Variable, Literal>,
+	// Consts whose uses were replaced by their values
+	used_consts: HashSet<Variable>,
+	// Arguments inferred while choosing between overloads, used as they are
+	// instead of being checked again
+	probed: HashMap<NodeId, hir::Expr>,
+	expanding: Vec<(usize, NodeId)>,
+	// File whose AST is being looked at
+	file: usize,
+	ctx: Context,
+	// Types that checks like `x != null` or `x is T` have established for
+	// variables at the point being checked
+	narrowed: HashMap<Variable, Type>,
+	// Type parameters of the call whose lambda argument is being checked
+	// that are still unknown; the lambda's body decides a return type
+	// mentioning them
+	unsolved: Vec<TypeParamId>,
+	// What built-in methods like `map` make, which calls infer as they do
+	// a generic function's type parameter; declared when first needed
+	result: Option<TypeParamId>,
+	errors: Vec<Diagnostic>
+}
+
+impl<'a> Checker<'a> {
+	// ---------------------------------------------------------------------
+	// Declarations
+	// ---------------------------------------------------------------------
+
+	// Gives every top-level declaration an id, so signatures can refer to
+	// declarations in any file regardless of order.
+	fn declare(&mut self) {
+		let files = self.files;
+		for (file, source) in files.iter().enumerate() {
+			self.file = file;
+			for stmt in &source.program.stmts {
+				match &stmt.kind {
+					StmtKind::Function(decl) => {
+						let id = self.new_function(&decl.name, FnKind::Function, &decl.modifiers, decl.name_span);
+						self.define(decl.id, Def::Function(id));
+					}
+					StmtKind::Var(decl) => {
+						let id = GlobalId(self.program.globals.len() as u32);
+						self.program.globals.push(hir::Global {
+							name: decl.name.clone(),
+							ty: Type::Any,
+							mutable: decl.mutable,
+							constant: decl.constant,
+							class: None,
+							doc: decl.doc.clone(),
+							file,
+							span: decl.name_span
+						});
+						self.define(decl.id, Def::Global(id));
+					}
+					_ => self.declare_type(stmt)
+				}
+			}
+		}
+
+		for (index, symbol) in self.graph.symbols.iter().enumerate() {
+			let stmt = &files[symbol.file].program.stmts[symbol.stmt];
+			if let Some(id) = declared_id(&stmt.kind)
+				&& let Some(def) = self.nodes[symbol.file].get(&id)
+			{
+				self.symbols.insert(SymbolId(index as u32), *def);
+			}
+		}
+	}
+
+	fn declare_type(&mut self, stmt: &'a Stmt) {
+		if let StmtKind::TypeAlias(decl) = &stmt.kind {
+			self.type_params(&decl.type_params);
+			self.define(decl.id, Def::Alias(self.file, decl));
+			return;
+		}
+		let Some(view) = ClassView::of(&stmt.kind) else {
+			return;
+		};
+
+		let id = TypeId(self.program.classes.len() as u32);
+		let type_params = self.type_params(view.type_params);
+		let annotations = self.annotations(view.annotations);
+		self.suppress(view.annotations, stmt.span);
+		let mut modifiers = view.modifiers.clone();
+		if modifiers.is_sealed && matches!(view.kind, ClassKind::Enum | ClassKind::Struct) {
+			self.error("E0300", "Only classes and interfaces can be 'sealed'".to_string(), view.span);
+			modifiers.is_sealed = false;
+		}
+		if modifiers.is_static {
+			self.error("E0300", "Only members of classes can be 'static'".to_string(), view.span);
+		}
+		// Values of a sealed class are always instances of one of its subclasses
+		modifiers.is_abstract |= modifiers.is_sealed;
+		self.program.classes.push(hir::Class {
+			name: view.name.to_string(),
+			package: self.graph.package_of(self.file).to_string(),
+			kind: view.kind,
+			modifiers,
+			type_params,
+			supertypes: Vec::new(),
+			fields: Vec::new(),
+			methods: Vec::new(),
+			constructors: Vec::new(),
+			variants: Vec::new(),
+			statics: Vec::new(),
+			annotations,
+			doc: stmt.doc().map(str::to_string),
+			file: self.file,
+			span: view.span
+		});
+		self.define(view.id, Def::Class(id));
+	}
+
+	// Allocates type parameters; their bounds are filled in with signatures.
+	fn type_params(&mut self, params: &[TypeParam]) -> Vec<TypeParamId> {
+		let mut ids = Vec::new();
+		for param in params {
+			let id = TypeParamId(self.program.type_params.len() as u32);
+			self.program.type_params.push(hir::TypeParamDef { name: param.name.clone(), bounds: Vec::new(), span: param.span });
+			self.define(param.id, Def::TypeParam(id));
+			ids.push(id);
+		}
+		ids
+	}
+
+	// Records what each `import` names, for editors to find it by.
+	fn imports(&mut self) {
+		let files = self.files;
+		for (file, source) in files.iter().enumerate() {
+			for stmt in &source.program.stmts {
+				let StmtKind::Import(decl) = &stmt.kind else {
+					continue;
+				};
+				let Some((name, prefix)) = decl.path.split_last().filter(|_| !decl.wildcard) else {
+					continue;
+				};
+				for id in self.graph.lookup(&prefix.join("."), name).unwrap_or_default() {
+					let target = match self.symbols.get(id) {
+						Some(Def::Class(class)) => hir::ImportTarget::Class(*class),
+						Some(Def::Function(function)) => hir::ImportTarget::Function(*function),
+						Some(Def::Global(global)) => hir::ImportTarget::Global(*global),
+						_ => continue
+					};
+					self.program.imports.push(hir::Import { target, file, span: decl.name_span });
+				}
+			}
+		}
+	}
+
+	fn new_function(&mut self, name: &str, kind: FnKind, modifiers: &Modifiers, span: Span) -> FnId {
+		let id = FnId(self.program.functions.len() as u32);
+		self.program.functions.push(hir::Function {
+			name: name.to_string(),
+			kind,
+			modifiers: modifiers.clone(),
+			type_params: Vec::new(),
+			receiver: None,
+			params: Vec::new(),
+			ret: Type::Any,
+			throws: Vec::new(),
+			generator: false,
+			body: None,
+			foreign: None,
+			annotations: Vec::new(),
+			doc: None,
+			file: self.file,
+			span
+		});
+		id
+	}
+
+	fn new_local(&mut self, name: &str, ty: Type, mutable: bool, span: Span) -> LocalId {
+		let id = LocalId(self.program.locals.len() as u32);
+		self.program.locals.push(hir::Local { name: name.to_string(), ty, mutable, span });
+		id
+	}
+
+	fn define(&mut self, node: NodeId, def: Def<'a>) {
+		self.nodes[self.file].insert(node, def);
+	}
+
+	fn def(&self, node: NodeId) -> Option<Def<'a>> {
+		self.nodes[self.file].get(&node).copied()
+	}
+
+	// ---------------------------------------------------------------------
+	// Signatures
+	// ---------------------------------------------------------------------
+
+	fn signatures(&mut self) {
+		let files = self.files;
+		for (file, source) in files.iter().enumerate() {
+			self.file = file;
+			for stmt in &source.program.stmts {
+				self.signature(stmt);
+			}
+		}
+	}
+
+	fn signature(&mut self, stmt: &'a Stmt) {
+		match &stmt.kind {
+			StmtKind::Function(decl) => {
+				if let Some(Def::Function(id) | Def::LocalFunction(_, id)) = self.def(decl.id) {
+					self.fn_signature(id, decl);
+				}
+			}
+			StmtKind::Var(decl) => {
+				if let Some(Def::Global(id)) = self.def(decl.id) {
+					match (&decl.ty, &decl.init) {
+						// A const's value has to be known wherever it is used, so
+						// its initializer is checked on first use like an inferred one
+						(Some(ty), Some(init)) if decl.constant => {
+							self.program.globals[id.0 as usize].ty = self.lower_type(ty);
+							self.pending.insert(Pending::Global(id), (self.file, init));
+						}
+						(Some(ty), _) => self.program.globals[id.0 as usize].ty = self.lower_type(ty),
+						(None, Some(init)) => {
+							self.pending.insert(Pending::Global(id), (self.file, init));
+						}
+						(None, None) => {}
+					}
+				}
+			}
+			kind => {
+				if let Some(view) = ClassView::of(kind)
+					&& let Some(Def::Class(id)) = self.def(view.id)
+				{
+					self.class_signature(id, &view);
+				}
+			}
+		}
+	}
+
+	fn fn_signature(&mut self, id: FnId, decl: &'a FnDecl) {
+		let type_params = self.type_params(&decl.type_params);
+		self.bounds(&decl.type_params, &type_params);
+		let receiver = decl.receiver.as_ref().map(|ty| self.lower_type(ty));
+		let params = self.params(id, &decl.params, &[]);
+		let ret = self.opt_type(&decl.return_type);
+		let throws = decl.throws.iter().map(|ty| self.lower_type(ty)).collect();
+		let annotations = self.annotations(&decl.annotations);
+
+		let function = &mut self.program.functions[id.0 as usize];
+		if receiver.is_some() && function.kind == FnKind::Function {
+			function.kind = FnKind::Extension;
+			self.extensions.entry(decl.name.clone()).or_default().push(id);
+		}
+		function.type_params = type_params;
+		function.receiver = receiver;
+		function.params = params;
+		function.ret = ret;
+		function.throws = throws;
+		function.annotations = annotations;
+		function.doc.clone_from(&decl.doc);
+		self.suppress(&decl.annotations, decl.span);
+	}
+
+	fn annotations(&mut self, annotations: &[Annotation]) -> Vec<hir::Annotation> {
+		let mut lowered = Vec::new();
+		for annotation in annotations {
+			let mut args = Vec::new();
+			for arg in &annotation.args {
+				match &arg.kind {
+					ExprKind::Literal(literal) => args.push(literal.clone()),
+					_ => self.error("E0600", format!("Arguments of '@{}' have to be literals", annotation.name), arg.span)
+				}
+			}
+			lowered.push(hir::Annotation { name: annotation.name.clone(), args, span: annotation.span });
+		}
+		lowered
+	}
+
+	// Records where `@Suppress` turns lints off. `span` is the whole
+	// declaration the annotations are on.
+	fn suppress(&mut self, annotations: &[Annotation], span: Span) {
+		for annotation in annotations.iter().filter(|annotation| annotation.name == "Suppress") {
+			let lints = annotation
+				.args
+				.iter()
+				.filter_map(|arg| match &arg.kind {
+					ExprKind::Literal(Literal::String(name)) => Some(name.clone()),
+					_ => None
+				})
+				.collect();
+			let suppression = hir::Suppression { lints, file: self.file, span, annotation: annotation.span };
+			self.program.suppressions.push(suppression);
+		}
+	}
+
+	fn bounds(&mut self, params: &'a [TypeParam], ids: &[TypeParamId]) {
+		for (param, id) in params.iter().zip(ids) {
+			let bounds = param.bounds.iter().map(|bound| self.lower_type(bound)).collect();
+			self.program.type_params[id.0 as usize].bounds = bounds;
+		}
+	}
+
+	// Parameters become locals up front so bodies and defaults can use them.
+	// `fallback` types unannotated parameters by position, as the setter's
+	// field type or a lambda's expected parameter types do.
+	fn params(&mut self, function: FnId, params: &'a [Param], fallback: &[Type]) -> Vec<hir::Param> {
+		let mut lowered = Vec::new();
+		let mut required = 0;
+		for (index, param) in params.iter().enumerate() {
+			if param.name != "_"
+				&& let Some(earlier) = params[..index].iter().find(|earlier| earlier.name == param.name)
+			{
+				let message = format!("Parameter '{}' is already declared at line {} column {}", param.name, earlier.span.line, earlier.span.column);
+				self.error("E0209", message, param.span);
+			}
+			let mut ty = match (&param.ty, fallback.get(index)) {
+				(Some(ty), _) => self.lower_type(ty),
+				(None, Some(fallback)) => fallback.clone(),
+				(None, None) => Type::Any
+			};
+			if param.variadic {
+				ty = Type::Array(Box::new(ty));
+			} else if param.default.is_none() {
+				required = lowered.len() + 1;
+			}
+			let local = self.new_local(&param.name, ty.clone(), false, param.span);
+			self.define(param.id, Def::Local(local));
+			lowered.push(hir::Param { local, ty, default: None, variadic: param.variadic });
+		}
+		self.min_args.insert(function, required);
+		lowered
+	}
+
+	fn class_signature(&mut self, id: TypeId, view: &ClassView<'a>) {
+		let type_params = self.program.class(id).type_params.clone();
+		self.bounds(view.type_params, &type_params);
+
+		let mut supertypes: Vec<Type> = Vec::new();
+		for ty in view.supertypes {
+			let lowered = self.lower_type(ty);
+			match &lowered {
+				Type::Class { id: parent, .. } => {
+					let parent_kind = self.program.class(*parent).kind;
+					let package = &self.program.class(*parent).package;
+					if self.program.class(*parent).modifiers.is_sealed && package != self.graph.package_of(self.file) {
+						self.error("E0301", format!("'{}' is sealed, so only types in package '{}' can extend it", lowered, package), ty.span);
+						continue;
+					}
+					if view.kind == ClassKind::Interface && parent_kind != ClassKind::Interface {
+						self.error("E0302", format!("Interface '{}' can only extend interfaces", view.name), ty.span);
+						continue;
+					}
+					if parent_kind == ClassKind::Class {
+						if supertypes.iter().any(|ty| self.is_class_kind(ty, ClassKind::Class)) {
+							self.error("E0302", format!("'{}' can only extend one class", view.name), ty.span);
+							continue;
+						}
+						// The superclass leads so constructors and `super` find it first
+						supertypes.insert(0, lowered);
+						continue;
+					}
+					if matches!(parent_kind, ClassKind::Enum | ClassKind::Struct) {
+						self.error("E0302", format!("Cannot inherit from '{}'", lowered), ty.span);
+						continue;
+					}
+					supertypes.push(lowered);
+				}
+				Type::Error => {}
+				_ => self.error("E0302", format!("Cannot inherit from '{}'", lowered), ty.span)
+			}
+		}
+
+		let mut fields = Vec::new();
+		let mut methods = Vec::new();
+		let mut constructors = Vec::new();
+		let mut statics = Vec::new();
+
+		for field in view.fields {
+			self.field(id, field, &mut fields);
+		}
+		if view.kind == ClassKind::Struct {
+			let required = view.fields.iter().rposition(|field| field.init.is_none()).map_or(0, |index| index + 1);
+			self.min_fields.insert(id, required);
+		}
+		for member in view.members {
+			match member {
+				Member::Field(field) if field.modifiers.is_static => statics.push(self.static_field(id, field)),
+				Member::Method(decl) if decl.modifiers.is_static => {
+					let method = self.new_function(&decl.name, FnKind::Static(id), &decl.modifiers, decl.name_span);
+					if decl.body.is_none() && decl.modifiers.library.is_none() {
+						self.error("E0313", format!("Static function '{}' needs a body, as nothing can override it", decl.name), decl.name_span);
+					}
+					self.define(decl.id, Def::Function(method));
+					self.fn_signature(method, decl);
+					let visibility = decl.modifiers.visibility;
+					statics.push(hir::Static { name: decl.name.clone(), visibility, target: StaticTarget::Method(method), span: decl.name_span });
+				}
+				Member::Field(field) => {
+					// Properties of interfaces are provided by the classes implementing them
+					if view.kind == ClassKind::Interface && field.init.is_some() {
+						self.error("E0303", format!("Interface '{}' cannot store a value in '{}'", view.name, field.name), field.name_span);
+					}
+					self.field(id, field, &mut fields)
+				}
+				Member::Method(decl) => {
+					let method = self.new_function(&decl.name, FnKind::Method(id), &decl.modifiers, decl.name_span);
+					// Interface methods without a body are abstract without saying so
+					if decl.body.is_none() {
+						self.program.functions[method.0 as usize].modifiers.is_abstract = true;
+					}
+					self.define(decl.id, Def::Function(method));
+					self.fn_signature(method, decl);
+					methods.push(method);
+				}
+				Member::Constructor(ctor) => {
+					if view.kind == ClassKind::Interface {
+						self.error("E0303", format!("Interface '{}' cannot have a constructor", view.name), ctor.span);
+					}
+					let function = self.new_function("constructor", FnKind::Constructor(id), &ctor.modifiers, ctor.span);
+					self.define(ctor.id, Def::Function(function));
+					let params = self.params(function, &ctor.params, &[]);
+					self.program.functions[function.0 as usize].params = params;
+					self.program.functions[function.0 as usize].ret = Type::Void;
+					self.program.functions[function.0 as usize].doc.clone_from(&ctor.doc);
+					constructors.push(function);
+				}
+			}
+		}
+
+		let variants = view
+			.variants
+			.iter()
+			.map(|variant| hir::Variant { name: variant.name.clone(), args: Vec::new(), doc: variant.doc.clone(), span: variant.span })
+			.collect();
+
+		let class = &mut self.program.classes[id.0 as usize];
+		class.supertypes = supertypes;
+		class.fields = fields;
+		class.methods = methods;
+		class.constructors = constructors;
+		class.variants = variants;
+		class.statics = statics;
+		if view.kind == ClassKind::Enum {
+			let statics = self.enum_statics(id, view.span);
+			for member in statics {
+				if let Some(declared) = self.program.class(id).statics.iter().find(|declared| declared.name == member.name) {
+					self.error("E0313", format!("Every enum comes with '{}', so it cannot declare its own", member.name), declared.span);
+					continue;
+				}
+				self.program.classes[id.0 as usize].statics.push(member);
+			}
+		}
+	}
+
+	// `values()` and `valueOf(name)`, which every enum comes with, as static
+	// functions over its variants.
+	fn enum_statics(&mut self, class: TypeId, span: Span) -> Vec<hir::Static> {
+		let ty = self.self_type(class);
+		let names: Vec<String> = self.program.class(class).variants.iter().map(|variant| variant.name.clone()).collect();
+		let expr = |kind, ty| hir::Expr { kind, ty, span };
+		let variant = |index| expr(hir::ExprKind::Variant { class, index }, ty.clone());
+		let returning = |value| hir::Block { stmts: vec![hir::Stmt { kind: hir::StmtKind::Return(Some(value)), span }], span };
+
+		let modifiers = Modifiers { is_static: true, ..Modifiers::default() };
+		let values = self.new_function("values", FnKind::Static(class), &modifiers, span);
+		let array = expr(hir::ExprKind::Array((0..names.len()).map(variant).collect()), Type::Array(Box::new(ty.clone())));
+		let function = &mut self.program.functions[values.0 as usize];
+		function.ret = array.ty.clone();
+		function.body = Some(returning(array));
+		function.doc = Some("The variants, in the order they are declared".to_string());
+
+		let value_of = self.new_function("valueOf", FnKind::Static(class), &modifiers, span);
+		let name = self.new_local("name", Type::String, false, span);
+		// `if (name == "A") A else if ... else null`, built from the last variant
+		let mut found = expr(hir::ExprKind::Literal(Literal::Null), ty.clone().nullable());
+		for (index, variant_name) in names.iter().enumerate().rev() {
+			let left = Box::new(expr(hir::ExprKind::Var(Variable::Local(name)), Type::String));
+			let right = Box::new(expr(hir::ExprKind::Literal(Literal::String(variant_name.clone())), Type::String));
+			let cond = Box::new(expr(hir::ExprKind::Binary { op: BinaryOp::Equal, left, right }, Type::Bool));
+			let then_branch = Box::new(variant(index));
+			found = expr(hir::ExprKind::If { cond, then_branch, else_branch: Box::new(found) }, ty.clone().nullable());
+		}
+		let function = &mut self.program.functions[value_of.0 as usize];
+		function.params = vec![hir::Param { local: name, ty: Type::String, default: None, variadic: false }];
+		function.ret = found.ty.clone();
+		function.body = Some(returning(found));
+		function.doc = Some("The variant named `name`, or null when there is none".to_string());
+
+		[("values", values), ("valueOf", value_of)]
+			.into_iter()
+			.map(|(name, id)| hir::Static { name: name.to_string(), visibility: Visibility::Public, target: StaticTarget::Method(id), span })
+			.collect()
+	}
+
+	// A static field is a global of its class, which the top-level code sets
+	// when it gets to the class, so it has to have an initializer.
+	fn static_field(&mut self, class: TypeId, field: &'a FieldDecl) -> hir::Static {
+		let id = GlobalId(self.program.globals.len() as u32);
+		self.program.globals.push(hir::Global {
+			name: field.name.clone(),
+			ty: Type::Any,
+			mutable: field.mutable,
+			constant: false,
+			class: Some(class),
+			doc: field.doc.clone(),
+			file: self.file,
+			span: field.name_span
+		});
+		self.define(field.id, Def::Global(id));
+		match (&field.ty, &field.init) {
+			(Some(ty), _) => self.program.globals[id.0 as usize].ty = self.lower_type(ty),
+			(None, Some(init)) => {
+				self.pending.insert(Pending::Global(id), (self.file, init));
+			}
+			(None, None) => {}
+		}
+		if field.init.is_none() {
+			self.error("E0313", format!("Static field '{}' needs an initializer, as no constructor sets it", field.name), field.name_span);
+		}
+		if field.getter.is_some() || field.setter.is_some() {
+			self.error("E0313", format!("Static field '{}' cannot have accessors", field.name), field.name_span);
+		}
+		hir::Static { name: field.name.clone(), visibility: field.modifiers.visibility, target: StaticTarget::Field(id), span: field.name_span }
+	}
+
+	fn field(&mut self, class: TypeId, field: &'a FieldDecl, fields: &mut Vec<hir::Field>) {
+		if field.ty.is_none()
+			&& let Some(init) = &field.init
+		{
+			self.pending.insert(Pending::Field(class, fields.len()), (self.file, init));
+		}
+		let ty = self.opt_type(&field.ty);
+		fields.push(self.field_signature(class, field, ty));
+	}
+
+	fn field_signature(&mut self, class: TypeId, field: &'a FieldDecl, ty: Type) -> hir::Field {
+		let getter = field.getter.as_deref().and_then(|accessor| self.accessor_signature(class, field, accessor, &ty));
+		let setter = field.setter.as_deref().and_then(|accessor| self.accessor_signature(class, field, accessor, &ty));
+		let annotations = self.annotations(&field.annotations);
+		self.suppress(&field.annotations, field.span);
+		hir::Field {
+			name: field.name.clone(),
+			ty,
+			mutable: field.mutable,
+			visibility: field.modifiers.visibility,
+			setter_visibility: field.setter.as_ref().map_or(field.modifiers.visibility, |setter| setter.visibility.max(field.modifiers.visibility)),
+			init: None,
+			getter,
+			setter,
+			annotations,
+			doc: field.doc.clone(),
+			span: field.name_span
+		}
+	}
+
+	// Accessors without a body only change visibility and need no function.
+	fn accessor_signature(&mut self, class: TypeId, field: &FieldDecl, accessor: &'a Accessor, ty: &Type) -> Option<FnId> {
+		accessor.body.as_ref()?;
+		let modifiers = Modifiers { visibility: accessor.visibility, ..Modifiers::default() };
+		let name = accessor.method_name(&field.name);
+		let (kind, ret) = match accessor.kind {
+			AccessorKind::Get => (FnKind::Getter(class), ty.clone()),
+			AccessorKind::Set => (FnKind::Setter(class), Type::Void)
+		};
+		let id = self.new_function(&name, kind, &modifiers, accessor.span);
+		self.define(accessor.id, Def::Function(id));
+		let params = self.params(id, accessor.param.as_slice(), std::slice::from_ref(ty));
+		let function = &mut self.program.functions[id.0 as usize];
+		function.params = params;
+		function.ret = ret;
+		Some(id)
+	}
+
+	// ---------------------------------------------------------------------
+	// Duplicates
+	// ---------------------------------------------------------------------
+
+	// A name can be declared only once in a package or class, except that
+	// functions whose parameter types differ are overloads of each other.
+	fn duplicates(&mut self) {
+		let graph = self.graph;
+		for package in graph.packages.values() {
+			for ids in package.symbols.values() {
+				for (index, id) in ids.iter().enumerate() {
+					let def = self.symbols.get(id).copied();
+					let Some(earlier) = ids[..index].iter().find(|earlier| !self.overloads(def, self.symbols.get(earlier).copied())) else {
+						continue;
+					};
+					let functions = [id, earlier].iter().all(|id| matches!(self.symbols.get(id), Some(Def::Function(_))));
+					let (symbol, earlier) = (graph.symbol(*id), graph.symbol(*earlier));
+					self.file = symbol.file;
+					let message = self.duplicate_message(&symbol.name, functions, earlier.file, earlier.span);
+					self.error("E0209", message, symbol.span);
+				}
+			}
+		}
+
+		for index in 0..self.program.classes.len() {
+			let class = self.program.class(TypeId(index as u32));
+			let fields = class.fields.iter().map(|field| (field.name.as_str(), None, field.span));
+			let methods = class.methods.iter().chain(&class.constructors).map(|&id| {
+				let function = self.program.function(id);
+				(function.name.as_str(), Some(Def::Function(id)), function.span)
+			});
+			let variants = class.variants.iter().map(|variant| (variant.name.as_str(), None, variant.span));
+			let statics = class.statics.iter().map(|member| match member.target {
+				StaticTarget::Field(_) => (member.name.as_str(), None, member.span),
+				StaticTarget::Method(id) => (member.name.as_str(), Some(Def::Function(id)), member.span)
+			});
+			let mut members: Vec<(&str, Option<Def>, Span)> = fields.chain(methods).chain(variants).chain(statics).collect();
+			members.sort_by_key(|(_, _, span)| (span.line, span.column));
+
+			let mut errors = Vec::new();
+			for (index, &(name, def, span)) in members.iter().enumerate() {
+				if let Some(&(_, earlier_def, earlier)) = members[..index].iter().find(|(other, earlier, _)| *other == name && !self.overloads(def, *earlier)) {
+					let functions = def.is_some() && earlier_def.is_some();
+					errors.push((self.duplicate_message(name, functions, class.file, earlier), span));
+				}
+			}
+			self.file = class.file;
+			for (message, span) in errors {
+				self.error("E0209", message, span);
+			}
+		}
+	}
+
+	// Functions can share a name when they take different parameters, or
+	// extend different types.
+	fn overloads(&self, a: Option<Def>, b: Option<Def>) -> bool {
+		let (Some(Def::Function(a)), Some(Def::Function(b))) = (a, b) else {
+			return false;
+		};
+		let (a, b) = (self.program.function(a), self.program.function(b));
+		let types = |function: &hir::Function| function.params.iter().map(|param| param.ty.clone()).collect::<Vec<_>>();
+		a.receiver != b.receiver || types(a) != types(b)
+	}
+
+	fn duplicate_message(&self, name: &str, functions: bool, file: usize, earlier: Span) -> String {
+		let location = self.location(file, earlier);
+		match name {
+			"constructor" => format!("A constructor with the same parameter types is already declared at {}", location),
+			_ if functions => format!("'{}' is already declared with the same parameter types at {}", name, location),
+			_ => format!("'{}' is already declared at {}", name, location)
+		}
+	}
+
+	// ---------------------------------------------------------------------
+	// Inheritance
+	// ---------------------------------------------------------------------
+
+	fn inheritance(&mut self) {
+		for index in 0..self.program.functions.len() {
+			self.file = self.program.functions[index].file;
+			let function = &self.program.functions[index];
+			if function.modifiers.is_override && !matches!(function.kind, FnKind::Method(_)) {
+				let message = format!("'{}' is not a method, so it cannot be marked 'override'", function.name);
+				self.error("E0304", message, function.span);
+			}
+			let function = &self.program.functions[index];
+			if function.modifiers.is_sealed {
+				self.error("E0300", "Only classes and interfaces can be 'sealed'".to_string(), function.span);
+			}
+			let function = &self.program.functions[index];
+			if function.modifiers.is_static && !matches!(function.kind, FnKind::Static(_)) {
+				self.error("E0300", "Only members of classes can be 'static'".to_string(), function.span);
+			}
+		}
+		for index in 0..self.program.classes.len() {
+			let id = TypeId(index as u32);
+			self.file = self.program.class(id).file;
+			for method in self.program.class(id).methods.clone() {
+				self.method_override(id, method);
+			}
+			self.abstract_members(id);
+			self.default_conflicts(id);
+		}
+	}
+
+	// A method with the name of an inherited one replaces it, which has to be
+	// asked for with `override` and keep a signature callers of the
+	// supertype can still use.
+	fn method_override(&mut self, class: TypeId, method: FnId) {
+		let function = self.program.function(method);
+		let (name, span, marked) = (function.name.clone(), function.span, function.modifiers.is_override);
+		let inherited = self.program.class(class).supertypes.iter().find_map(|supertype| match supertype {
+			Type::Class { id, args, .. } => self.find_member(*id, args, &name),
+			_ => None
+		});
+		match inherited {
+			None if marked => {
+				let supertypes = self.program.class(class).supertypes.clone();
+				let candidates: Vec<String> = supertypes.iter().flat_map(|ty| self.member_names(ty)).collect();
+				let message = format!("'{}' is marked 'override' but no supertype of '{}' has a method named '{}'", name, self.program.class(class).name, name);
+				self.misspelled("E0304", message, &name, span, candidates.iter().map(String::as_str));
+			}
+			None => {}
+			Some((MemberTarget::Field(owner), _)) => {
+				self.error("E0304", format!("Method '{}' cannot override the field '{}' of '{}'", name, name, self.program.class(owner).name), span);
+			}
+			Some((MemberTarget::Method(base), expected)) => {
+				let FnKind::Method(owner) = self.program.function(base).kind else {
+					return;
+				};
+				let owner = self.program.class(owner).name.clone();
+				if !marked {
+					self.error("E0304", format!("'{}' overrides '{}.{}'; mark it 'override'", name, owner, name), span);
+				}
+				let found = self.fn_type(method, &HashMap::new());
+				if !self.overrides(&found, &expected) {
+					let message = format!("'{}' does not match '{}.{}': expected '{}', found '{}'", name, owner, name, expected, found);
+					self.error("E0305", message, span);
+				}
+				let (visibility, inherited) = (self.program.function(method).modifiers.visibility, self.program.function(base).modifiers.visibility);
+				if visibility > inherited {
+					let message = format!(
+						"'{}' cannot be {} because it overrides '{}.{}', which is {}",
+						name,
+						visibility.keyword(),
+						owner,
+						name,
+						inherited.keyword()
+					);
+					self.error("E0305", message, span);
+				}
+				// Callers of the base method decide whether to await by its signature
+				let (is_async, base_async) = (self.program.function(method).modifiers.is_async, self.program.function(base).modifiers.is_async);
+				if is_async != base_async {
+					let (this, other) = if is_async { ("is", "is not") } else { ("is not", "is") };
+					let message = format!("'{}' {} async but overrides '{}.{}', which {}; make both async or neither", name, this, owner, name, other);
+					self.error("E0305", message, span);
+				}
+			}
+			Some(_) => {}
+		}
+	}
+
+	// Parameters have to match exactly; the return type may be narrower.
+	fn overrides(&self, found: &Type, expected: &Type) -> bool {
+		match (found, expected) {
+			(Type::Function { params: found, ret: found_ret }, Type::Function { params: expected, ret: expected_ret }) => {
+				found.len() == expected.len()
+					&& found.iter().zip(expected).all(|(found, expected)| self.same(found, expected))
+					&& self.assignable(found_ret, expected_ret)
+			}
+			_ => true
+		}
+	}
+
+	// Classes that can be instantiated need a body for every method they
+	// declare or inherit, and a field for every property their interfaces
+	// ask for.
+	fn abstract_members(&mut self, id: TypeId) {
+		let class = self.program.class(id);
+		if class.kind == ClassKind::Interface || class.modifiers.is_abstract {
+			return;
+		}
+		for method in class.methods.clone() {
+			let function = self.program.function(method);
+			if function.modifiers.is_abstract {
+				let message = format!("'{}' has no body; only abstract classes and interfaces can declare abstract methods", function.name);
+				self.error("E0306", message, function.span);
+			}
+		}
+
+		let ancestors = self.ancestors(id);
+		let this = self.self_type(id);
+		let mut missing: Vec<String> = Vec::new();
+		let mut mismatched = Vec::new();
+		let mut seen = HashSet::new();
+		for ancestor in &ancestors[1..] {
+			let owner = self.program.class(*ancestor);
+			for method in &owner.methods {
+				let function = self.program.function(*method);
+				if function.modifiers.is_abstract
+					&& seen.insert(function.name.clone())
+					&& self.implementations(id, &function.name).is_empty()
+				{
+					missing.push(format!("'{}' from '{}'", function.name, owner.name));
+				}
+			}
+			if owner.kind != ClassKind::Interface {
+				continue;
+			}
+			let subst = match self.upcast(&this, *ancestor) {
+				Some(Type::Class { args, .. }) => owner.type_params.iter().copied().zip(args).collect(),
+				_ => HashMap::new()
+			};
+			for property in &owner.fields {
+				if !seen.insert(property.name.clone()) {
+					continue;
+				}
+				let provided = ancestors
+					.iter()
+					.filter(|class| self.program.class(**class).kind != ClassKind::Interface)
+					.find_map(|class| self.program.class(*class).field(&property.name));
+				let expected = property.ty.substitute(&subst);
+				match provided {
+					None => missing.push(format!("'{}' from '{}'", property.name, owner.name)),
+					Some(field) if !self.assignable(&field.ty, &expected) => {
+						let message = format!("'{}' has type '{}' but '{}' requires '{}'", field.name, field.ty, owner.name, expected);
+						mismatched.push((message, field.span));
+					}
+					Some(_) => {}
+				}
+			}
+		}
+		for (message, span) in mismatched {
+			self.error("E0307", message, span);
+		}
+		if !missing.is_empty() {
+			let class = self.program.class(id);
+			let message = format!("'{}' must be declared 'abstract' or implement {}", class.name, missing.join(", "));
+			self.error("E0307", message, class.span);
+		}
+	}
+
+	// Two interfaces can each bring a default body for the same method. The
+	// class has to pick one by overriding it unless one interface already
+	// overrides the other.
+	fn default_conflicts(&mut self, id: TypeId) {
+		let mut seen = HashSet::new();
+		let mut conflicts = Vec::new();
+		for ancestor in &self.ancestors(id)[1..] {
+			for method in &self.program.class(*ancestor).methods {
+				let name = &self.program.function(*method).name;
+				if !seen.insert(name.clone()) {
+					continue;
+				}
+				let found = self.implementations(id, name);
+				if found.len() > 1 {
+					let owners: Vec<String> = found
+						.iter()
+						.filter_map(|method| match self.program.function(*method).kind {
+							FnKind::Method(owner) => Some(format!("'{}'", self.program.class(owner).name)),
+							_ => None
+						})
+						.collect();
+					conflicts.push(format!("'{}' inherits different bodies for '{}' from {}; override it to choose one", self.program.class(id).name, name, owners.join(" and ")));
+				}
+			}
+		}
+		let span = self.program.class(id).span;
+		for message in conflicts {
+			self.error("E0308", message, span);
+		}
+	}
+
+	// The bodies calling `name` on an instance of `id` could run. A method of
+	// the class or a superclass wins; otherwise it is the default methods of
+	// interfaces that no more specific interface overrides, which is a
+	// conflict when there is more than one.
+	fn implementations(&self, id: TypeId, name: &str) -> Vec<FnId> {
+		let ancestors = self.ancestors(id);
+		let concrete = |class: TypeId| {
+			self.program.class(class).methods.iter().copied().find(|method| {
+				let function = self.program.function(*method);
+				function.name == name && !function.modifiers.is_abstract
+			})
+		};
+		let (classes, interfaces): (Vec<TypeId>, Vec<TypeId>) =
+			ancestors.iter().partition(|class| self.program.class(**class).kind != ClassKind::Interface);
+		if let Some(method) = classes.into_iter().find_map(concrete) {
+			return vec![method];
+		}
+		let defaults: Vec<TypeId> = interfaces.into_iter().filter(|class| concrete(*class).is_some()).collect();
+		defaults
+			.iter()
+			.filter(|owner| !defaults.iter().any(|other| other != *owner && self.ancestors(*other).contains(owner)))
+			.filter_map(|owner| concrete(*owner))
+			.collect()
+	}
+
+	// A class and every class and interface it inherits from, nearest first.
+	fn ancestors(&self, id: TypeId) -> Vec<TypeId> {
+		let mut ancestors = vec![id];
+		let mut next = 0;
+		while next < ancestors.len() {
+			let class = self.program.class(ancestors[next]);
+			next += 1;
+			for supertype in &class.supertypes {
+				if let Type::Class { id, .. } = supertype
+					&& !ancestors.contains(id)
+				{
+					ancestors.push(*id);
+				}
+			}
+		}
+		ancestors
+	}
+
+	// ---------------------------------------------------------------------
+	// Extern functions
+	// ---------------------------------------------------------------------
+
+	// An `extern` function is a C function with the signature it declares,
+	// so it has no body, and takes and returns only what C can.
+	fn externs(&mut self) {
+		for index in 0..self.program.functions.len() {
+			let function = &self.program.functions[index];
+			let Some(library) = &function.modifiers.library else {
+				continue;
+			};
+			let params: Vec<Option<CType>> = function.params.iter().map(|param| CType::of(&param.ty).filter(|ctype| *ctype != CType::Void)).collect();
+			let floats = params.iter().filter(|ctype| **ctype == Some(CType::Float)).count();
+			let name = &function.name;
+			let problem = if function.kind != FnKind::Function || function.receiver.is_some() {
+				Some(format!("'{}' is not a top-level function, so it cannot be 'extern'", name))
+			} else if function.body.is_some() {
+				Some(format!("'{}' is 'extern', so its body is in C and it cannot have one", name))
+			} else if !function.type_params.is_empty() {
+				Some(format!("'{}' is 'extern', so it cannot be generic", name))
+			} else if function.params.iter().any(|param| param.default.is_some() || param.variadic) {
+				Some(format!("'{}' is 'extern', so its parameters cannot have defaults or be '...rest'", name))
+			} else if let Some(param) = params.iter().position(Option::is_none) {
+				let ty = &function.params[param].ty;
+				Some(format!("'{}' cannot pass a '{}' to C; extern functions take Int, Float, Bool, String and Pointer", name, ty))
+			} else if CType::of(&function.ret).is_none() {
+				Some(format!("'{}' cannot get a '{}' back from C; extern functions return Int, Float, Bool, String, Pointer and Void", name, function.ret))
+			} else if floats > CType::MAX_FLOATS || params.len() - floats > CType::MAX_INTS {
+				let (ints, floats) = (CType::MAX_INTS, CType::MAX_FLOATS);
+				Some(format!("'{}' takes too many arguments to call in C: at most {} Floats and {} of anything else", name, floats, ints))
+			} else {
+				None
+			};
+			match problem {
+				Some(message) => {
+					let (file, span) = (function.file, function.span);
+					self.file = file;
+					self.error("E0312", message, span);
+				}
+				None => {
+					let library = library.to_string();
+					let params = params.into_iter().flatten().collect();
+					let ret = CType::of(&function.ret).unwrap_or(CType::Void);
+					self.program.functions[index].foreign = Some(hir::Foreign { library, params, ret });
+				}
+			}
+		}
+	}
+
+	// ---------------------------------------------------------------------
+	// Bodies
+	// ---------------------------------------------------------------------
+
+	// Checks the top-level code and bodies of every file. Once declarations
+	// without a type have one, which can take initializers in any package,
+	// packages' bodies need nothing of each other: each is checked in a copy
+	// of the checker on a thread of its own, once those it imports are, and
+	// the copies are merged back in order. What is found is the same however
+	// many threads there are, and errors are in the order of the files.
+	fn bodies(&mut self, mut kept: Option<&mut Bodies>) {
+		let reported = self.errors.len();
+		let mut pending: Vec<Pending> = self.pending.keys().copied().collect();
+		pending.sort();
+		for key in pending {
+			self.infer_pending(key);
+		}
+		// Shared by every copy, rather than one made by each
+		self.result_param();
+
+		let files = self.files;
+		// Of each file, what it parsed to, to tell whether it changed
+		let parsed = match kept {
+			Some(_) => parallel::map(&(0..files.len()).collect::<Vec<usize>>(), |&file| hashed(&format!("{:?}", files[file]))),
+			None => Vec::new()
+		};
+		let mut reused = Vec::new();
+		let mut bodies: Vec<Option<hir::File>> = vec![None; files.len()];
+		for wave in self.graph.waves() {
+			let base = Counts::of(&self.program);
+			let known = kept.as_deref();
+			let checked = parallel::map(&wave, |package| {
+				let print = known.map(|_| self.fingerprint(package, &parsed));
+				if let Some(known) = known
+					&& let Some((before, checked)) = known.packages.get(*package)
+					&& print == Some(*before)
+				{
+					return (checked.clone(), print, true);
+				}
+				let mut checker = self.fork();
+				let files = checker.package_bodies(package);
+				let fork = Fork::split(checker.program, base, &self.graph.packages[*package].files);
+				(PackageBodies { fork, files, errors: checker.errors, used_consts: checker.used_consts }, print, false)
+			});
+			for (package, (checked, print, taken)) in wave.iter().zip(checked) {
+				if taken {
+					reused.push(package.to_string());
+				}
+				if let (Some(kept), Some(print)) = (kept.as_deref_mut(), print)
+					&& !taken
+					&& checked.errors.is_empty()
+				{
+					kept.packages.insert(package.to_string(), (print, checked.clone()));
+				}
+				let shift = checked.fork.merge(&mut self.program);
+				for (file, mut body) in checked.files {
+					shift.stmts(&mut body.body);
+					bodies[file] = Some(body);
+				}
+				self.errors.extend(checked.errors);
+				self.used_consts.extend(checked.used_consts.into_iter().map(|var| shift.variable(var)));
+			}
+		}
+		if let Some(kept) = kept {
+			kept.packages.retain(|package, _| self.graph.packages.contains_key(package));
+			kept.reused = reused;
+		}
+		self.program.files = bodies.into_iter().flatten().collect();
+		self.errors[reported..].sort_by_key(|error| error.file);
+	}
+
+	// What checking `package`'s bodies depends on: what its files and those
+	// of the packages it imports parsed to, and those with extensions, which
+	// apply anywhere; the numbers of what is declared in them; and how long
+	// each table is.
+	fn fingerprint(&self, package: &str, parsed: &[u64]) -> u64 {
+		let mut seen = HashSet::from([package]);
+		let mut pending = vec![package];
+		while let Some(from) = pending.pop() {
+			for import in self.graph.imports.iter().filter(|import| import.from == from) {
+				if seen.insert(&import.to) {
+					pending.push(&import.to);
+				}
+			}
+		}
+		let mut files: Vec<usize> = seen.iter().filter_map(|package| self.graph.packages.get(*package)).flat_map(|package| package.files.clone()).collect();
+		files.extend(self.extensions.values().flatten().map(|&id| self.program.function(id).file));
+		files.sort();
+		files.dedup();
+		let visible = |file: usize| files.binary_search(&file).is_ok();
+		let mut hasher = DefaultHasher::new();
+		(package, Counts::of(&self.program)).hash(&mut hasher);
+		for &file in &files {
+			(file, parsed[file]).hash(&mut hasher);
+		}
+		for (id, class) in self.program.classes.iter().enumerate().filter(|(_, class)| visible(class.file)) {
+			(id, &class.name).hash(&mut hasher);
+		}
+		for (id, function) in self.program.functions.iter().enumerate().filter(|(_, function)| visible(function.file)) {
+			(id, &function.name).hash(&mut hasher);
+		}
+		for (id, global) in self.program.globals.iter().enumerate().filter(|(_, global)| visible(global.file)) {
+			(id, &global.name).hash(&mut hasher);
+		}
+		hasher.finish()
+	}
+
+	// A checker for one package's bodies to be checked in, apart from the
+	// others'.
+	fn fork(&self) -> Checker<'a> {
+		Checker {
+			files: self.files,
+			graph: self.graph,
+			resolutions: self.resolutions,
+			program: self.program.clone(),
+			nodes: self.nodes.clone(),
+			symbols: self.symbols.clone(),
+			extensions: self.extensions.clone(),
+			min_args: self.min_args.clone(),
+			min_fields: self.min_fields.clone(),
+			aliases: self.aliases.clone(),
+			pending: self.pending.clone(),
+			inferring: Vec::new(),
+			global_inits: self.global_inits.clone(),
+			consts: self.consts.clone(),
+			used_consts: HashSet::new(),
+			probed: HashMap::new(),
+			expanding: Vec::new(),
+			file: 0,
+			ctx: Context::default(),
+			narrowed: HashMap::new(),
+			unsolved: Vec::new(),
+			result: self.result,
+			errors: Vec::new()
+		}
+	}
+
+	// The top-level code of each file of `package`, by file.
+	fn package_bodies(&mut self, package: &str) -> Vec<(usize, hir::File)> {
+		let files = self.files;
+		let mut checked = Vec::new();
+		for &file in &self.graph.packages[package].files {
+			self.file = file;
+			self.ctx = Context::default();
+			let mut body = Vec::new();
+			for stmt in &files[file].program.stmts {
+				let checked = match &stmt.kind {
+					StmtKind::Var(decl) => self.global(decl.id, decl.init.as_ref(), stmt.span),
+					StmtKind::Function(decl) => {
+						if let Some(Def::Function(id)) = self.def(decl.id) {
+							self.function_body(id, decl);
+						}
+						None
+					}
+					StmtKind::Defer(_) => {
+						self.error("E0511", "'defer' can only be used in a block, to run something as the block is left".to_string(), stmt.span);
+						None
+					}
+					_ => self.stmt(stmt)
+				};
+				body.extend(checked);
+			}
+			checked.push((file, hir::File { path: files[file].path.clone(), package: package.to_string(), body }));
+		}
+		checked
+	}
+
+	fn global(&mut self, node: NodeId, init: Option<&'a Expr>, span: Span) -> Option<hir::Stmt> {
+		let Some(Def::Global(id)) = self.def(node) else {
+			return None;
+		};
+		self.infer_pending(Pending::Global(id));
+		let ty = self.program.global(id).ty.clone();
+		let init = match self.global_inits.remove(&id) {
+			Some(init) => Some(init),
+			None => init.map(|init| self.check(init, &ty))
+		};
+		Some(hir::Stmt { kind: hir::StmtKind::Var { var: Variable::Global(id), init }, span })
+	}
+
+	// Records the value of a `const` once its initializer is checked and
+	// folded, so later uses can be replaced by it.
+	fn constant(&mut self, var: Variable, init: &hir::Expr) {
+		match &init.kind {
+			hir::ExprKind::Literal(value) => {
+				self.consts.insert(var, value.clone());
+			}
+			_ if init.ty == Type::Error => {}
+			// Another const whose value could not be worked out, already reported
+			hir::ExprKind::Var(Variable::Global(global)) if self.program.global(*global).constant => {}
+			_ => {
+				let name = match var {
+					Variable::Local(local) => &self.program.local(local).name,
+					Variable::Global(global) => &self.program.global(global).name
+				};
+				let message = format!(
+					"The value of const '{}' must be known at compile time; use literals, other consts and operators on them",
+					name
+				);
+				self.error("E0310", message, init.span);
+			}
+		}
+	}
+
+	// Checks the initializer of an unannotated declaration, or of a `const`,
+	// and gives the declaration its type. An initializer that needs its own declaration's
+	// type cannot be inferred and leaves it `Any`.
+	fn infer_pending(&mut self, key: Pending) {
+		if self.inferring.contains(&key) {
+			let (name, span) = match key {
+				Pending::Global(id) => (&self.program.global(id).name, self.program.global(id).span),
+				Pending::Field(class, index) => {
+					let field = &self.program.class(class).fields[index];
+					(&field.name, field.span)
+				}
+			};
+			let message = match key {
+				Pending::Global(id) if self.program.global(id).constant => format!("The value of const '{}' depends on itself", name),
+				_ => format!("Cannot infer the type of '{}' because its initializer refers to it; add a type annotation", name)
+			};
+			self.error("E0311", message, span);
+			self.pending.remove(&key);
+			return;
+		}
+		let Some(&(file, init)) = self.pending.get(&key) else {
+			return;
+		};
+
+		let ctx = match key {
+			// A static field's initializer is checked in its class
+			Pending::Global(id) => {
+				let class = self.program.global(id).class;
+				Context { class, initializing: class.map(|_| id), ..Context::default() }
+			}
+			Pending::Field(class, _) => {
+				Context {
+					ret: None,
+					this: Some(self.self_type(class)),
+					class: Some(class),
+					accessor: None,
+					constructor: false,
+					loops: Vec::new(),
+					deferred: None,
+					initializing: None,
+					function: None
+				}
+			}
+		};
+		let saved_ctx = std::mem::replace(&mut self.ctx, ctx);
+		let saved_file = std::mem::replace(&mut self.file, file);
+		let saved_narrowed = std::mem::take(&mut self.narrowed);
+		let declared = match key {
+			Pending::Global(id) if self.program.global(id).ty != Type::Any => Some(self.program.global(id).ty.clone()),
+			_ => None
+		};
+		self.inferring.push(key);
+		let init = match &declared {
+			Some(ty) => self.check(init, ty),
+			None => self.infer(init)
+		};
+		self.inferring.pop();
+		self.narrowed = saved_narrowed;
+		self.file = saved_file;
+		self.ctx = saved_ctx;
+
+		// Reported as a cycle while inferring; the declaration stays `Any`
+		if self.pending.remove(&key).is_none() {
+			return;
+		}
+		let ty = inferred(&init.ty);
+		match key {
+			Pending::Global(id) => {
+				if declared.is_none() {
+					self.program.globals[id.0 as usize].ty = ty;
+				}
+				if self.program.global(id).constant {
+					self.constant(Variable::Global(id), &init);
+				}
+				self.global_inits.insert(id, init);
+			}
+			Pending::Field(class, index) => {
+				let field = &mut self.program.classes[class.0 as usize].fields[index];
+				field.ty = ty;
+				field.init = Some(init);
+			}
+		}
+	}
+
+	fn function_body(&mut self, id: FnId, decl: &'a FnDecl) {
+		let function = self.program.function(id);
+		let this = match function.kind {
+			FnKind::Method(class) => Some(self.self_type(class)),
+			// Even in its class, a static function has no object
+			FnKind::Static(_) => None,
+			_ => function.receiver.clone().or(self.ctx.this.clone())
+		};
+		let class = match function.kind {
+			FnKind::Method(class) | FnKind::Static(class) => Some(class),
+			_ => self.ctx.class
+		};
+		let ctx = Context {
+			ret: Some(function.ret.clone()),
+			this,
+			class,
+			accessor: None,
+			constructor: false,
+			loops: Vec::new(),
+			deferred: None,
+			initializing: None,
+			function: Some(id)
+		};
+		if let Some(FnBody::Block(block)) = &decl.body
+			&& let Some(span) = yields(block)
+		{
+			self.generator(id, span);
+		}
+		self.body(id, &decl.params, decl.body.as_ref(), ctx);
+	}
+
+	// A function whose body yields is a generator: calling it makes an
+	// iterator of what it yields, so that is what it has to return.
+	fn generator(&mut self, id: FnId, span: Span) {
+		self.program.functions[id.0 as usize].generator = true;
+		let function = self.program.function(id);
+		let message = if function.modifiers.is_async {
+			format!("'{}' yields, so it cannot be 'async'; it runs as its iterator is gone through", function.name)
+		} else if !matches!(function.ret, Type::Iterator(_) | Type::Any | Type::Error) {
+			format!("'{}' yields, so it has to return an 'Iterator' of what it yields, not '{}'", function.name, function.ret)
+		} else {
+			return;
+		};
+		self.error("E0512", message, span);
+	}
+
+	fn body(&mut self, id: FnId, params: &'a [Param], body: Option<&'a FnBody>, ctx: Context) {
+		let saved = std::mem::replace(&mut self.ctx, ctx);
+		// A function can run at any time, so nothing is known on entry
+		let saved_narrowed = std::mem::take(&mut self.narrowed);
+
+		for (index, param) in params.iter().enumerate() {
+			if let Some(default) = &param.default {
+				let ty = self.program.function(id).params[index].ty.clone();
+				let default = self.check(default, &ty);
+				self.program.functions[id.0 as usize].params[index].default = Some(default);
+			}
+		}
+
+		let body = body.map(|body| match body {
+			FnBody::Block(block) => self.block(block),
+			FnBody::Expr(expr) => {
+				let ret = self.ctx.ret.clone().unwrap_or(Type::Any);
+				let value = self.check(expr, &ret);
+				let span = value.span;
+				hir::Block { stmts: vec![hir::Stmt { kind: hir::StmtKind::Return(Some(value)), span }], span }
+			}
+		});
+		self.program.functions[id.0 as usize].body = body;
+
+		self.narrowed = saved_narrowed;
+		self.ctx = saved;
+	}
+
+	// Gives what sets the class's static fields, in the order they are
+	// declared.
+	fn class_body(&mut self, id: TypeId, view: &ClassView<'a>) -> Vec<hir::Stmt> {
+		// Static fields are set once, by the top-level code
+		if self.ctx.function.is_some()
+			&& let Some(member) = self.program.class(id).statics.first()
+		{
+			self.error("E0313", format!("'{}' is static, but only top-level types can have static members", member.name), member.span);
+		}
+		let this = self.self_type(id);
+		let saved = std::mem::replace(
+			&mut self.ctx,
+			Context {
+				ret: None,
+				this: Some(this.clone()),
+				class: Some(id),
+				accessor: None,
+				constructor: false,
+				loops: Vec::new(),
+				deferred: None,
+				initializing: None,
+				function: None
+			}
+		);
+		let saved_narrowed = std::mem::take(&mut self.narrowed);
+
+		let fields: Vec<&FieldDecl> = view
+			.fields
+			.iter()
+			.chain(view.members.iter().filter_map(|member| match member {
+				Member::Field(field) if !field.modifiers.is_static => Some(field),
+				_ => None
+			}))
+			.collect();
+		for (index, field) in fields.into_iter().enumerate() {
+			self.infer_pending(Pending::Field(id, index));
+			let ty = self.program.class(id).fields[index].ty.clone();
+			if let Some(init) = &field.init
+				&& self.program.class(id).fields[index].init.is_none()
+			{
+				let init = self.check(init, &ty);
+				self.program.classes[id.0 as usize].fields[index].init = Some(init);
+			}
+			for accessor in [&field.getter, &field.setter].into_iter().flatten() {
+				if let Some(Def::Function(function)) = self.def(accessor.id) {
+					let ret = self.program.function(function).ret.clone();
+					let ctx = Context {
+						ret: Some(ret),
+						this: Some(this.clone()),
+						class: Some(id),
+						accessor: Some((field.name.clone(), ty.clone())),
+						constructor: false,
+						loops: Vec::new(),
+						deferred: None,
+						initializing: None,
+						function: Some(function)
+					};
+					self.body(function, accessor.param.as_slice(), accessor.body.as_ref(), ctx);
+				}
+			}
+		}
+
+		for member in view.members {
+			match member {
+				Member::Method(decl) => {
+					if let Some(Def::Function(function)) = self.def(decl.id) {
+						self.function_body(function, decl);
+					}
+				}
+				Member::Constructor(ctor) => {
+					if let Some(Def::Function(function)) = self.def(ctor.id) {
+						let ctx = Context {
+							ret: Some(Type::Void),
+							this: Some(this.clone()),
+							class: Some(id),
+							accessor: None,
+							constructor: true,
+							loops: Vec::new(),
+							deferred: None,
+							initializing: None,
+							function: Some(function)
+						};
+						let saved = std::mem::replace(&mut self.ctx, ctx);
+						for (index, param) in ctor.params.iter().enumerate() {
+							if let Some(default) = &param.default {
+								let ty = self.program.function(function).params[index].ty.clone();
+								let default = self.check(default, &ty);
+								self.program.functions[function.0 as usize].params[index].default = Some(default);
+							}
+						}
+						let body = self.block(&ctor.body);
+						self.program.functions[function.0 as usize].body = Some(body);
+						self.narrowed.clear();
+						self.ctx = saved;
+					}
+				}
+				Member::Field(_) => {}
+			}
+		}
+
+		for (index, variant) in view.variants.iter().enumerate() {
+			let args = self.constructor_args(id, &[], &variant.args, variant.span).1;
+			self.program.classes[id.0 as usize].variants[index].args = args;
+		}
+
+		let mut statics = Vec::new();
+		for member in view.members {
+			if let Member::Field(field) = member
+				&& let Some(Def::Global(global)) = self.def(field.id)
+			{
+				self.ctx = Context { class: Some(id), initializing: Some(global), ..Context::default() };
+				statics.extend(self.global(field.id, field.init.as_ref(), field.span));
+			}
+		}
+
+		self.narrowed = saved_narrowed;
+		self.ctx = saved;
+		statics
+	}
+
+	// ---------------------------------------------------------------------
+	// Statements
+	// ---------------------------------------------------------------------
+
+	fn block(&mut self, block: &'a Block) -> hir::Block {
+		self.hoist(&block.stmts);
+		let (stmts, _) = self.stmts(&block.stmts, None);
+		hir::Block { stmts, span: block.span }
+	}
+
+	// The statements of a block, with those after each `defer` in a `try`
+	// whose `finally` runs what it deferred. With `value`, a last expression
+	// is the block's value, checked against what is expected of it; kept in
+	// a variable of its own when a `finally` has to run after it.
+	fn stmts(&mut self, stmts: &'a [Stmt], value: Option<Option<&Type>>) -> (Vec<hir::Stmt>, Type) {
+		let mut checked = Vec::new();
+		for (index, stmt) in stmts.iter().enumerate() {
+			if let StmtKind::Defer(deferred) = &stmt.kind {
+				let rest = &stmts[index + 1..];
+				let finally = self.deferred(deferred, rest);
+				let (mut body, ty) = self.stmts(rest, value);
+				let kept = match body.last_mut().map(|last| &mut last.kind) {
+					Some(hir::StmtKind::Expr(last)) if value.is_some() && !matches!(ty, Type::Void | Type::Never) => {
+						let local = self.new_local("<value>", ty.clone(), false, last.span);
+						let target = hir::Expr { kind: hir::ExprKind::Var(Variable::Local(local)), ty: ty.clone(), span: last.span };
+						let value = std::mem::replace(last, self.error_expr(last.span));
+						let assign = hir::ExprKind::Assign { target: Box::new(target.clone()), op: None, value: Box::new(value) };
+						*last = hir::Expr { kind: assign, ty: ty.clone(), span: target.span };
+						checked.push(hir::Stmt { kind: hir::StmtKind::Var { var: Variable::Local(local), init: None }, span: stmt.span });
+						Some(target)
+					}
+					_ => None
+				};
+				let span = stmt.span.to(rest.last().map_or(stmt.span, |last| last.span));
+				let body = hir::Block { stmts: body, span };
+				checked.push(hir::Stmt { kind: hir::StmtKind::Try { body, catches: Vec::new(), finally: Some(finally) }, span });
+				if let Some(target) = kept {
+					let span = target.span;
+					checked.push(hir::Stmt { kind: hir::StmtKind::Expr(target), span });
+				}
+				return (checked, ty);
+			}
+			if let Some(expected) = value
+				&& index + 1 == stmts.len()
+				&& let StmtKind::Expr(expr) = &stmt.kind
+			{
+				let value = match expected {
+					Some(expected) => self.check(expr, expected),
+					None => self.infer(expr)
+				};
+				let ty = value.ty.clone();
+				checked.push(hir::Stmt { kind: hir::StmtKind::Expr(value), span: stmt.span });
+				return (checked, ty);
+			}
+			checked.extend(self.stmt(stmt));
+		}
+		(checked, Type::Void)
+	}
+
+	// What `defer` runs, checked where it is written. It can run from
+	// anywhere in `rest`, once something there throws or returns, so knows
+	// nothing of what `rest` assigns.
+	fn deferred(&mut self, deferred: &'a Stmt, rest: &'a [Stmt]) -> hir::Block {
+		self.forget_assigned(|assigned| rest.iter().for_each(|stmt| assigned.visit_stmt(stmt)));
+		let saved = self.ctx.deferred.replace(self.ctx.loops.len());
+		let narrowed = self.narrowed.clone();
+		let stmts = self.stmt(deferred).into_iter().collect();
+		self.narrowed = narrowed;
+		self.ctx.deferred = saved;
+		hir::Block { stmts, span: deferred.span }
+	}
+
+	// Functions and types declared in a block can be used anywhere in it, so
+	// they are declared before any of its statements are checked.
+	fn hoist(&mut self, stmts: &'a [Stmt]) {
+		for stmt in stmts {
+			if let StmtKind::Function(decl) = &stmt.kind {
+				let function = self.new_function(&decl.name, FnKind::Function, &decl.modifiers, decl.name_span);
+				let local = self.new_local(&decl.name, Type::Any, false, decl.name_span);
+				self.define(decl.id, Def::LocalFunction(local, function));
+			} else {
+				self.declare_type(stmt);
+			}
+		}
+		for stmt in stmts {
+			self.signature(stmt);
+			if let StmtKind::Function(decl) = &stmt.kind
+				&& let Some(Def::LocalFunction(local, function)) = self.def(decl.id)
+			{
+				self.program.locals[local.0 as usize].ty = self.fn_type(function, &HashMap::new());
+			}
+		}
+	}
+
+	fn stmt(&mut self, stmt: &'a Stmt) -> Option<hir::Stmt> {
+		let kind = match &stmt.kind {
+			StmtKind::Expr(expr) => hir::StmtKind::Expr(self.infer(expr)),
+			StmtKind::Var(decl) => {
+				let (ty, init) = match (&decl.ty, &decl.init) {
+					(None, Some(init)) => {
+						let init = self.infer(init);
+						(inferred(&init.ty), Some(init))
+					}
+					(ty, init) => {
+						let ty = self.opt_type(ty);
+						(ty.clone(), init.as_ref().map(|init| self.check(init, &ty)))
+					}
+				};
+				let local = self.new_local(&decl.name, ty, decl.mutable, decl.name_span);
+				self.define(decl.id, Def::Local(local));
+				if decl.constant
+					&& let Some(init) = &init
+				{
+					self.constant(Variable::Local(local), init);
+				}
+				hir::StmtKind::Var { var: Variable::Local(local), init }
+			}
+			StmtKind::Block(block) => hir::StmtKind::Block(self.block(block)),
+			StmtKind::If { cond, then_branch, else_branch } => {
+				let cond = self.condition(cond);
+				let (when_true, when_false) = self.facts(&cond);
+				let before = self.narrowed.clone();
+
+				self.assume(&when_true);
+				let then_block = self.block(then_branch);
+				let after_then = std::mem::replace(&mut self.narrowed, before.clone());
+				self.assume(&when_false);
+				let else_stmt = else_branch.as_ref().and_then(|stmt| self.stmt(stmt)).map(Box::new);
+
+				// Only what holds on every path that gets past the `if` holds after it
+				let then_exits = always_exits(&then_branch.stmts);
+				let else_exits = else_branch.as_deref().is_some_and(|stmt| always_exits(std::slice::from_ref(stmt)));
+				match (then_exits, else_exits) {
+					(true, true) => self.narrowed = before,
+					(true, false) => {}
+					(false, true) => self.narrowed = after_then,
+					(false, false) => self.narrowed.retain(|var, ty| after_then.get(var) == Some(ty))
+				}
+				hir::StmtKind::If { cond, then_branch: then_block, else_branch: else_stmt }
+			}
+			StmtKind::While { .. } | StmtKind::Loop { .. } | StmtKind::For(_) => self.loop_stmt(stmt, None),
+			StmtKind::Labeled { label, body } => {
+				if self.ctx.loops.iter().any(|outer| outer.as_ref() == Some(label)) {
+					self.error("E0501", format!("This loop is already inside a loop labeled '{}'; give it a different label", label), stmt.span);
+				}
+				let inner = hir::Stmt { kind: self.loop_stmt(body, Some(label)), span: body.span };
+				hir::StmtKind::Labeled { label: label.clone(), body: Box::new(inner) }
+			}
+			StmtKind::Break(label) => {
+				self.jump("break", label.as_ref(), stmt.span);
+				hir::StmtKind::Break(label.clone())
+			}
+			StmtKind::Continue(label) => {
+				self.jump("continue", label.as_ref(), stmt.span);
+				hir::StmtKind::Continue(label.clone())
+			}
+			StmtKind::Return(value) => self.return_stmt(value.as_ref(), stmt.span),
+			StmtKind::Yield(value) => self.yield_stmt(value, stmt.span),
+			StmtKind::Throw(value) => hir::StmtKind::Throw(self.infer(value)),
+			StmtKind::Try { body, catches, finally } => {
+				// A catch or finally block can start anywhere in the body
+				self.forget_assigned(|v| v.visit_stmt(stmt));
+				let head = self.narrowed.clone();
+				let body = self.block(body);
+				self.narrowed = head.clone();
+				let catches = catches
+					.iter()
+					.map(|catch| {
+						let ty = catch.ty.as_ref().map(|ty| self.lower_type(ty));
+						let local = self.new_local(&catch.name, ty.clone().unwrap_or(Type::Any), false, catch.span);
+						self.define(catch.id, Def::Local(local));
+						let body = self.block(&catch.body);
+						self.narrowed = head.clone();
+						hir::Catch { local, ty, body, span: catch.span }
+					})
+					.collect();
+				let finally = finally.as_ref().map(|block| self.block(block));
+				self.narrowed = head;
+				hir::StmtKind::Try { body, catches, finally }
+			}
+			StmtKind::Switch { subject, cases, default } => {
+				let subject = self.infer(subject);
+				self.forget_assigned(|v| v.visit_stmt(stmt));
+				let head = self.narrowed.clone();
+				let cases: Vec<hir::Case> = cases
+					.iter()
+					.map(|case| {
+						let patterns: Vec<hir::Pattern> = case.patterns.iter().map(|pattern| self.case_pattern(pattern, &subject.ty)).collect();
+						// `case Circle:` makes the subject a `Circle` in the body
+						if let [hir::Pattern::Type { ty, .. }] = patterns.as_slice()
+							&& let Some(var) = self.narrowable(&subject)
+						{
+							let ty = self.narrowed_to(&subject.ty, ty);
+							self.narrowed.insert(var, ty);
+						}
+						let body = self.block(&case.body);
+						self.narrowed = head.clone();
+						hir::Case { patterns, body, span: case.span }
+					})
+					.collect();
+				let default = default.as_ref().map(|block| self.block(block));
+				self.narrowed = head;
+				let exhaustive = self.coverage(&subject.ty, &cases, default.as_ref(), stmt.span);
+				hir::StmtKind::Switch { subject, cases, default, exhaustive }
+			}
+			StmtKind::Function(decl) => {
+				let Some(Def::LocalFunction(local, function)) = self.def(decl.id) else {
+					return None;
+				};
+				self.function_body(function, decl);
+				hir::StmtKind::Function { local, function }
+			}
+			kind => {
+				if let Some(view) = ClassView::of(kind)
+					&& let Some(Def::Class(id)) = self.def(view.id)
+				{
+					let statics = self.class_body(id, &view);
+					if !statics.is_empty() {
+						return Some(hir::Stmt { kind: hir::StmtKind::Block(hir::Block { stmts: statics, span: stmt.span }), span: stmt.span });
+					}
+				}
+				return None;
+			}
+		};
+		Some(hir::Stmt { kind, span: stmt.span })
+	}
+
+	fn condition(&mut self, cond: &'a Expr) -> hir::Expr {
+		let cond = self.infer(cond);
+		if !self.assignable(&cond.ty, &Type::Bool) {
+			self.error("E0400", format!("Condition must be 'Bool', found '{}'", cond.ty), cond.span);
+		}
+		cond
+	}
+
+	// What a condition being true, and being false, says about the types of
+	// variables: `x != null`, `x is T` and combinations of them with `!`,
+	// `&&` and `||`.
+	fn facts(&self, cond: &hir::Expr) -> (Facts, Facts) {
+		match &cond.kind {
+			hir::ExprKind::Binary { op: op @ (BinaryOp::Equal | BinaryOp::NotEqual), left, right } => {
+				let operand = match (&left.kind, &right.kind) {
+					(_, hir::ExprKind::Literal(Literal::Null)) => left,
+					(hir::ExprKind::Literal(Literal::Null), _) => right,
+					_ => return (Vec::new(), Vec::new())
+				};
+				let Some(var) = self.narrowable(operand) else {
+					return (Vec::new(), Vec::new());
+				};
+				let non_null = vec![(var, operand.ty.non_null().clone())];
+				if *op == BinaryOp::NotEqual { (non_null, Vec::new()) } else { (Vec::new(), non_null) }
+			}
+			hir::ExprKind::Is { operand, ty, negated } => {
+				let Some(var) = self.narrowable(operand) else {
+					return (Vec::new(), Vec::new());
+				};
+				if !self.assignable(ty, &operand.ty) {
+					return (Vec::new(), Vec::new());
+				}
+				let is = vec![(var, self.narrowed_to(&operand.ty, ty))];
+				let is_not: Facts = self.excluding(&operand.ty, ty).map(|rest| (var, rest)).into_iter().collect();
+				if *negated { (is_not, is) } else { (is, is_not) }
+			}
+			hir::ExprKind::Unary { op: UnaryOp::Not, operand } => {
+				let (when_true, when_false) = self.facts(operand);
+				(when_false, when_true)
+			}
+			hir::ExprKind::Binary { op: BinaryOp::And, left, right } => {
+				let mut when_true = self.facts(left).0;
+				when_true.extend(self.facts(right).0);
+				(when_true, Vec::new())
+			}
+			hir::ExprKind::Binary { op: BinaryOp::Or, left, right } => {
+				let mut when_false = self.facts(left).1;
+				when_false.extend(self.facts(right).1);
+				(Vec::new(), when_false)
+			}
+			_ => (Vec::new(), Vec::new())
+		}
+	}
+
+	// What a value of type `declared` is once it is known to be a `ty`: the
+	// members of a union that are one, or else `ty` itself.
+	fn narrowed_to(&self, declared: &Type, ty: &Type) -> Type {
+		if let Type::Union(members) = declared.non_null() {
+			let kept: Vec<Type> = members.iter().filter(|member| self.assignable(member, ty)).cloned().collect();
+			if !kept.is_empty() {
+				return Type::union(kept);
+			}
+		}
+		ty.clone()
+	}
+
+	// What is left of a union once a value is known not to be a `ty`. Other
+	// types say nothing by not being something.
+	fn excluding(&self, declared: &Type, ty: &Type) -> Option<Type> {
+		let Type::Union(members) = declared.non_null() else {
+			return None;
+		};
+		let mut rest: Vec<Type> = members.iter().filter(|member| !self.assignable(member, ty)).cloned().collect();
+		if let Type::Nullable(_) = declared
+			&& !self.assignable(&Type::Null, ty)
+		{
+			rest.push(Type::Null);
+		}
+		Some(Type::union(rest))
+	}
+
+	// Variables whose type can be narrowed: locals, and globals that cannot
+	// change under us. Fields are read through `this` or another object that
+	// may have been changed by any call in between, so they never are.
+	fn narrowable(&self, expr: &hir::Expr) -> Option<Variable> {
+		match expr.kind {
+			hir::ExprKind::Var(var @ Variable::Local(_)) => Some(var),
+			hir::ExprKind::Var(var @ Variable::Global(global)) if !self.program.global(global).mutable => Some(var),
+			_ => None
+		}
+	}
+
+	fn assume(&mut self, facts: &[(Variable, Type)]) {
+		for (var, ty) in facts {
+			self.narrowed.insert(*var, ty.clone());
+		}
+	}
+
+	fn declared_type(&self, var: Variable) -> Type {
+		match var {
+			Variable::Local(local) => self.program.local(local).ty.clone(),
+			Variable::Global(global) => self.program.global(global).ty.clone()
+		}
+	}
+
+	fn variable_type(&self, var: Variable) -> Type {
+		self.narrowed.get(&var).cloned().unwrap_or_else(|| self.declared_type(var))
+	}
+
+	// Storing a value that is not null in a nullable variable makes it non-null
+	// until the next assignment.
+	fn assigned(&mut self, var: Variable, value: &Type) {
+		let declared = self.declared_type(var);
+		let known_non_null = !matches!(value, Type::Nullable(_) | Type::Null | Type::Any | Type::Error);
+		match declared {
+			Type::Nullable(inner) if known_non_null && matches!(var, Variable::Local(_)) => {
+				self.narrowed.insert(var, *inner);
+			}
+			_ => {
+				self.narrowed.remove(&var);
+			}
+		}
+	}
+
+	// Drops what is known about variables assigned somewhere in the code
+	// `walk` visits, for code that can run more than once or be jumped out of.
+	fn forget_assigned(&mut self, walk: impl FnOnce(&mut AssignedNames)) {
+		let mut assigned = AssignedNames::default();
+		walk(&mut assigned);
+		for node in assigned.names {
+			let def = match self.resolutions[self.file].exprs.get(&node) {
+				Some(Resolution::Local(node)) => self.def(*node),
+				Some(Resolution::Symbol(symbol)) => self.symbols.get(symbol).copied(),
+				_ => None
+			};
+			match def {
+				Some(Def::Local(local)) => self.narrowed.remove(&Variable::Local(local)),
+				Some(Def::Global(global)) => self.narrowed.remove(&Variable::Global(global)),
+				_ => None
+			};
+		}
+	}
+
+	// Checks the two branches of an `if` expression, each knowing what the
+	// condition says on its side.
+	fn branches(
+		&mut self,
+		cond: &'a Expr,
+		then_branch: impl FnOnce(&mut Self) -> hir::Expr,
+		else_branch: impl FnOnce(&mut Self) -> hir::Expr
+	) -> (hir::Expr, hir::Expr, hir::Expr) {
+		let cond = self.condition(cond);
+		let (when_true, when_false) = self.facts(&cond);
+		let before = self.narrowed.clone();
+		self.assume(&when_true);
+		let then_branch = then_branch(self);
+		let after_then = std::mem::replace(&mut self.narrowed, before);
+		self.assume(&when_false);
+		let else_branch = else_branch(self);
+		match (then_branch.ty == Type::Never, else_branch.ty == Type::Never) {
+			(true, _) => {}
+			(false, true) => self.narrowed = after_then,
+			(false, false) => self.narrowed.retain(|var, ty| after_then.get(var) == Some(ty))
+		}
+		(cond, then_branch, else_branch)
+	}
+
+	// A case is a value to compare the subject with, or a type the subject
+	// has to be a value of.
+	fn case_pattern(&mut self, pattern: &'a Expr, subject: &Type) -> hir::Pattern {
+		let ty = match self.type_ref(pattern) {
+			Some(class) => Some(self.pattern_type(class)),
+			None => self.builtin_pattern(pattern)
+		};
+		if let Some(ty) = ty {
+			if !self.assignable(&ty, subject) {
+				self.error("E0411", format!("A value of type '{}' can never be a '{}'", subject, ty), pattern.span);
+			}
+			return hir::Pattern::Type { ty, span: pattern.span };
+		}
+		let pattern = self.infer(pattern);
+		if !self.assignable(&pattern.ty, subject) && !self.assignable(subject, &pattern.ty) {
+			self.error("E0411", format!("A case of type '{}' can never match a value of type '{}'", pattern.ty, subject), pattern.span);
+		}
+		hir::Pattern::Value(pattern)
+	}
+
+	// `case Int:` and the other built-in types a value can be tested for at
+	// run time, when the name is not taken by anything else.
+	fn builtin_pattern(&self, pattern: &Expr) -> Option<Type> {
+		let ExprKind::Identifier(name) = &pattern.kind else {
+			return None;
+		};
+		if self.resolutions[self.file].exprs.contains_key(&pattern.id) {
+			return None;
+		}
+		builtins::type_named(name).filter(|ty| matches!(ty, Type::Int | Type::Float | Type::Bool | Type::String))
+	}
+
+	// What a value matched by `case C:` is known to be. Type arguments are
+	// not checked at run time, so they are left open.
+	fn pattern_type(&self, class: TypeId) -> Type {
+		let class_def = self.program.class(class);
+		Type::Class { id: class, name: class_def.name.clone(), args: vec![Type::Any; class_def.type_params.len()] }
+	}
+
+	// Reports cases that repeat an earlier one and, for subjects with a fixed
+	// set of values, the values no case handles. Returns whether the cases
+	// cover every value so `default` is never needed.
+	fn coverage(&mut self, subject: &Type, cases: &[hir::Case], default: Option<&hir::Block>, span: Span) -> bool {
+		let mut handled: HashMap<String, i64> = HashMap::new();
+		let mut types = Vec::new();
+		for pattern in cases.iter().flat_map(|case| &case.patterns) {
+			let span = match pattern {
+				hir::Pattern::Value(value) => value.span,
+				hir::Pattern::Type { ty, span } => {
+					types.push(ty.clone());
+					*span
+				}
+			};
+			let Some(value) = self.pattern_value(pattern) else {
+				continue;
+			};
+			match handled.get(&value) {
+				Some(line) => self.error("E0504", format!("This case can never run; {} is already handled on line {}", value, line), span),
+				None => {
+					handled.insert(value, span.line);
+				}
+			}
+		}
+
+		// Each member of a union needs a case for its type, or cases for all
+		// of its values when it has a fixed set of them
+		let (members, union) = match subject.non_null() {
+			Type::Union(members) => (members.as_slice(), true),
+			other => (std::slice::from_ref(other), false)
+		};
+		let mut values = Vec::new();
+		for member in members {
+			if types.iter().any(|ty| self.assignable(member, ty)) {
+				continue;
+			}
+			match member {
+				Type::Bool => values.extend(["'true'".to_string(), "'false'".to_string()]),
+				Type::Class { id, .. } if self.program.class(*id).kind == ClassKind::Enum => {
+					values.extend(self.program.class(*id).variants.iter().map(|variant| format!("'{}'", variant.name)));
+				}
+				Type::Class { id, .. } if self.program.class(*id).modifiers.is_sealed => self.uncovered(*id, &types, &mut values),
+				Type::Class { id, .. } if union => values.push(format!("'{}'", self.program.class(*id).name)),
+				_ if union => values.push(format!("'{}'", member)),
+				_ => return false
+			}
+		}
+		if let Type::Nullable(_) = subject {
+			values.push("'null'".to_string());
+		}
+		let missing: Vec<String> = values.into_iter().filter(|value| !handled.contains_key(value)).collect();
+		match default {
+			Some(default) if missing.is_empty() => {
+				let message = format!("'default' can never run; every value of '{}' already has a case", subject);
+				self.error("E0504", message, default.span);
+			}
+			None if !missing.is_empty() => {
+				let fix = if missing.len() == 1 { "a case for it" } else { "cases for them" };
+				let message = format!("Switch over '{}' does not handle {}; add {} or a 'default'", subject, missing.join(", "), fix);
+				self.error("E0504", message, span);
+			}
+			_ => {}
+		}
+		missing.is_empty()
+	}
+
+	// Collects the subclasses of `id` that no `case` for a type covers,
+	// looking through sealed subclasses that are not covered as a whole.
+	// Sealed classes can only be extended in their own package, so every
+	// subclass is known.
+	fn uncovered(&self, id: TypeId, types: &[Type], uncovered: &mut Vec<String>) {
+		if types.iter().any(|ty| self.assignable(&self.pattern_type(id), ty)) {
+			return;
+		}
+		let class = self.program.class(id);
+		if !class.modifiers.is_sealed {
+			let name = format!("'{}'", class.name);
+			if !uncovered.contains(&name) {
+				uncovered.push(name);
+			}
+			return;
+		}
+		for (index, subclass) in self.program.classes.iter().enumerate() {
+			if subclass.supertypes.iter().any(|ty| matches!(ty, Type::Class { id: parent, .. } if *parent == id)) {
+				self.uncovered(TypeId(index as u32), types, uncovered);
+			}
+		}
+	}
+
+	// A case pattern as written in messages, when it is a constant or a type.
+	fn pattern_value(&self, pattern: &hir::Pattern) -> Option<String> {
+		let pattern = match pattern {
+			hir::Pattern::Value(value) => value,
+			hir::Pattern::Type { ty: Type::Class { id, .. }, .. } => return Some(format!("'{}'", self.program.class(*id).name)),
+			hir::Pattern::Type { ty, .. } => return Some(format!("'{}'", ty))
+		};
+		let value = match &pattern.kind {
+			hir::ExprKind::Variant { class, index } => format!("'{}'", self.program.class(*class).variants[*index].name),
+			hir::ExprKind::Literal(Literal::String(text)) => format!("{:?}", text),
+			hir::ExprKind::Literal(Literal::Int(value)) => format!("'{}'", value),
+			hir::ExprKind::Literal(Literal::Float(value)) => format!("'{}'", value),
+			hir::ExprKind::Literal(Literal::Bool(value)) => format!("'{}'", value),
+			hir::ExprKind::Literal(Literal::Null) => "'null'".to_string(),
+			_ => return None
+		};
+		Some(value)
+	}
+
+	// What `x?` is: the value of a `Result` or an `Option`, whose `Err` or
+	// `None` the function it is in returns, so has to be able to.
+	fn propagate(&mut self, operand: &hir::Expr, span: Span) -> Type {
+		let (value, kind) = match &operand.ty {
+			ty @ (Type::Any | Type::Error) => return ty.clone(),
+			Type::Result(value, _) => (value, "a 'Result'"),
+			Type::Option(value) => (value, "an 'Option'"),
+			ty => {
+				self.error("E0510", format!("'?' needs a 'Result' or an 'Option', found '{}'", ty), span);
+				return Type::Error;
+			}
+		};
+		if self.ctx.deferred.is_some() {
+			self.error("E0511", "Cannot use '?' in what is deferred, as it would return; it runs as its block is left".to_string(), span);
+			return (**value).clone();
+		}
+		let fits = match (&operand.ty, self.ctx.ret.as_ref()) {
+			(_, None) => {
+				self.error("E0510", "'?' is only allowed inside a function".to_string(), span);
+				return Type::Error;
+			}
+			(_, Some(Type::Any | Type::Error)) | (Type::Option(_), Some(Type::Option(_))) => true,
+			(Type::Result(_, error), Some(Type::Result(_, returned))) => {
+				if !self.assignable(error, returned) {
+					self.error("E0510", format!("'?' would return an error of type '{}' from a function whose errors are '{}'", error, returned), span);
+				}
+				true
+			}
+			_ => false
+		};
+		if !fits {
+			let ret = self.ctx.ret.clone().unwrap_or(Type::Void);
+			self.error("E0510", format!("'?' on {} needs the function to return {}, not '{}'", kind, kind, ret), span);
+		}
+		(**value).clone()
+	}
+
+	fn return_stmt(&mut self, value: Option<&'a Expr>, span: Span) -> hir::StmtKind {
+		if self.ctx.deferred.is_some() {
+			self.error("E0511", "Cannot 'return' from what is deferred; it runs as its block is left".to_string(), span);
+		}
+		let Some(ret) = self.ctx.ret.clone() else {
+			self.error("E0500", "'return' is only allowed inside a function".to_string(), span);
+			return hir::StmtKind::Return(value.map(|value| self.infer(value)));
+		};
+		// What a generator gives is what it yields; returning only ends it
+		if self.generating().is_some() {
+			let value = value.map(|value| self.infer(value));
+			if let Some(value) = &value {
+				self.error("E0512", "A generator cannot return a value; 'return' alone ends its iterator".to_string(), value.span);
+			}
+			return hir::StmtKind::Return(value);
+		}
+		match value {
+			Some(value) if ret == Type::Void => {
+				let value = self.infer(value);
+				if !matches!(value.ty, Type::Void | Type::Never | Type::Error) {
+					self.error("E0500", "Cannot return a value from a function that returns 'Void'".to_string(), value.span);
+				}
+				hir::StmtKind::Return(Some(value))
+			}
+			Some(value) => hir::StmtKind::Return(Some(self.check(value, &ret))),
+			None => {
+				if !matches!(ret, Type::Void | Type::Any | Type::Error) {
+					self.error("E0500", format!("Missing return value of type '{}'", ret), span);
+				}
+				hir::StmtKind::Return(None)
+			}
+		}
+	}
+
+	fn yield_stmt(&mut self, value: &'a Expr, span: Span) -> hir::StmtKind {
+		let Some(element) = self.generating() else {
+			self.error("E0512", "'yield' can only be used in a function that returns an 'Iterator'".to_string(), span);
+			return hir::StmtKind::Yield(self.infer(value));
+		};
+		hir::StmtKind::Yield(self.check(value, &element))
+	}
+
+	// Of the generator being checked, the type of what it yields.
+	fn generating(&self) -> Option<Type> {
+		let function = self.program.function(self.ctx.function?);
+		match &function.ret {
+			_ if !function.generator => None,
+			Type::Iterator(element) => Some((**element).clone()),
+			_ => Some(Type::Any)
+		}
+	}
+
+	// `label` is set when the loop is the body of `label: ...`.
+	fn loop_stmt(&mut self, stmt: &'a Stmt, label: Option<&String>) -> hir::StmtKind {
+		self.ctx.loops.push(label.cloned());
+		let kind = match &stmt.kind {
+			StmtKind::While { cond, body } => {
+				self.forget_assigned(|v| v.visit_block(body));
+				let cond = self.condition(cond);
+				let (when_true, when_false) = self.facts(&cond);
+				let head = self.narrowed.clone();
+				self.assume(&when_true);
+				let body_block = self.block(body);
+				self.narrowed = head;
+				// Without a `break` the loop only ends once its condition is false
+				if !breaks_out(&body.stmts, label, false) {
+					self.assume(&when_false);
+				}
+				hir::StmtKind::While { cond, body: body_block }
+			}
+			StmtKind::Loop { body } => {
+				self.forget_assigned(|v| v.visit_block(body));
+				let head = self.narrowed.clone();
+				let body = self.block(body);
+				self.narrowed = head;
+				hir::StmtKind::Loop { body }
+			}
+			StmtKind::For(for_loop) => self.for_loop(for_loop),
+			_ => unreachable!("only loops can be labeled")
+		};
+		self.ctx.loops.pop();
+		kind
+	}
+
+	fn jump(&mut self, keyword: &str, label: Option<&String>, span: Span) {
+		if self.ctx.loops.is_empty() {
+			self.error("E0501", format!("'{}' can only be used inside a loop", keyword), span);
+		} else if let Some(label) = label
+			&& !self.ctx.loops.iter().any(|outer| outer.as_ref() == Some(label))
+		{
+			let message = format!("No loop around this '{}' is labeled '{}'", keyword, label);
+			let labels: Vec<String> = self.ctx.loops.iter().flatten().cloned().collect();
+			self.misspelled("E0501", message, label, span, labels.iter().map(String::as_str));
+		}
+		let target = match label {
+			Some(label) => self.ctx.loops.iter().position(|outer| outer.as_ref() == Some(label)),
+			None => self.ctx.loops.len().checked_sub(1)
+		};
+		if let (Some(target), Some(depth)) = (target, self.ctx.deferred)
+			&& target < depth
+		{
+			self.error("E0511", format!("Cannot '{}' out of what is deferred; it runs as its block is left", keyword), span);
+		}
+	}
+
+	fn for_loop(&mut self, for_loop: &'a ForLoop) -> hir::StmtKind {
+		let iterable = self.infer(&for_loop.iterable);
+		let bindings = for_loop.pattern.bindings();
+		let types = match for_loop.kind {
+			ForKind::In => vec![self.element_type(&iterable)],
+			ForKind::Of => self.entry_types(&iterable, bindings.len())
+		};
+
+		let bindings = bindings
+			.iter()
+			.zip(types)
+			.map(|(binding, ty)| {
+				let local = self.new_local(&binding.name, ty, false, binding.span);
+				self.define(binding.id, Def::Local(local));
+				local
+			})
+			.collect();
+		self.forget_assigned(|v| v.visit_block(&for_loop.body));
+		let head = self.narrowed.clone();
+		let body = self.block(&for_loop.body);
+		self.narrowed = head;
+		hir::StmtKind::For { kind: for_loop.kind, bindings, iterable, body }
+	}
+
+	fn element_type(&mut self, iterable: &hir::Expr) -> Type {
+		if let Type::Nullable(_) = iterable.ty {
+			self.null_error(iterable, "loop over it");
+		}
+		match iterable.ty.non_null() {
+			Type::String => Type::String,
+			Type::Any => Type::Any,
+			Type::Error => Type::Error,
+			ty => match self.elements(ty) {
+				Some(element) => element,
+				None => {
+					self.error("E0402", format!("'{}' is not iterable", iterable.ty), iterable.span);
+					Type::Error
+				}
+			}
+		}
+	}
+
+	// The type of the elements of what has the methods of a sequence (see
+	// `builtins::sequence_member`): built-in collections, ranges and
+	// iterators, and objects with `iterator()` whose result has `next()`.
+	fn elements(&self, ty: &Type) -> Option<Type> {
+		match ty {
+			Type::Array(element) | Type::Set(element) | Type::Iterator(element) => Some((**element).clone()),
+			// A map's keys
+			Type::Map(key, _) => Some((**key).clone()),
+			Type::Range => Some(Type::Int),
+			Type::Class { id, args, .. } => {
+				let Some((_, Type::Function { ret: iterator, .. })) = self.find_member(*id, args, ITERATOR_METHOD) else {
+					return None;
+				};
+				match iterator.non_null() {
+					Type::Iterator(element) => Some((**element).clone()),
+					Type::Class { id, args, .. } => match self.find_member(*id, args, NEXT_METHOD) {
+						Some((_, Type::Function { ret: element, .. })) => Some(*element),
+						_ => None
+					},
+					_ => None
+				}
+			}
+			_ => None
+		}
+	}
+
+	// `for (k, v) of x` binds index and element for arrays and strings, and
+	// key and value for maps; other entries are only known at run time.
+	fn entry_types(&mut self, iterable: &hir::Expr, count: usize) -> Vec<Type> {
+		if let Type::Nullable(_) = iterable.ty {
+			self.null_error(iterable, "loop over it");
+		}
+		let pair = match iterable.ty.non_null() {
+			Type::Array(element) => Some((Type::Int, (**element).clone())),
+			Type::Map(key, value) => Some(((**key).clone(), (**value).clone())),
+			Type::String => Some((Type::Int, Type::String)),
+			Type::Any | Type::Error => None,
+			Type::Class { id, args, .. } if self.find_member(*id, args, ENTRIES_METHOD).is_some() => None,
+			_ => {
+				self.error("E0402", format!("'{}' has no entries to loop over", iterable.ty), iterable.span);
+				return vec![Type::Error; count];
+			}
+		};
+		match (pair, count) {
+			(Some((key, value)), 2) => vec![key, value],
+			_ => vec![Type::Any; count]
+		}
+	}
+
+	// ---------------------------------------------------------------------
+	// Expressions
+	// ---------------------------------------------------------------------
+
+	// Checks `expr` against the type its context expects.
+	fn check(&mut self, expr: &'a Expr, expected: &Type) -> hir::Expr {
+		let checked = match &expr.kind {
+			_ if self.probed.contains_key(&expr.id) => self.infer(expr),
+			// Branches are checked one by one so the mismatch is reported in
+			// the branch at fault.
+			ExprKind::If { cond, then_branch, else_branch } => {
+				let (cond, then_branch, else_branch) = self.branches(
+					cond,
+					|checker| checker.check(then_branch, expected),
+					|checker| checker.check(else_branch, expected)
+				);
+				let ty = self.join(&then_branch.ty, &else_branch.ty);
+				let kind = hir::ExprKind::If { cond: Box::new(cond), then_branch: Box::new(then_branch), else_branch: Box::new(else_branch) };
+				return hir::Expr { kind, ty, span: expr.span };
+			}
+			ExprKind::Block(block) => {
+				let (block, ty) = self.block_value(block, Some(expected));
+				return hir::Expr { kind: hir::ExprKind::Block(block), ty, span: expr.span };
+			}
+			// Lambda parameters without annotations take the expected types
+			ExprKind::Lambda { params, body } if matches!(expected.non_null(), Type::Function { .. }) => {
+				let (kind, ty) = self.lambda(params, body, expr.span, Some(expected.non_null()));
+				hir::Expr { kind, ty, span: expr.span }
+			}
+			// Generic calls can take type arguments from what they are expected to be
+			ExprKind::Call { callee, args } => self.call(callee, args, expr.span, Some(expected)),
+			// Elements are checked against the expected element type, so
+			// `[]` and `[1, null]` can be a `String[]` or an `Int?[]`
+			ExprKind::Array(items) if let Type::Array(element) = expected.non_null() => {
+				let items = items.iter().map(|item| self.check(item, element)).collect();
+				let ty = Type::Array(element.clone());
+				return hir::Expr { kind: hir::ExprKind::Array(items), ty, span: expr.span };
+			}
+			// Sets are written as arrays of their elements
+			ExprKind::Array(items) if let Type::Set(element) = expected.non_null() => {
+				let items = items.iter().map(|item| self.check(item, element)).collect();
+				let ty = Type::Set(element.clone());
+				return hir::Expr { kind: hir::ExprKind::Set(items), ty, span: expr.span };
+			}
+			ExprKind::Map(entries) if let Type::Map(key, value) = expected.non_null() => {
+				let entries = entries.iter().map(|(k, v)| (self.check(k, key), self.check(v, value))).collect();
+				let ty = Type::Map(key.clone(), value.clone());
+				return hir::Expr { kind: hir::ExprKind::Map(entries), ty, span: expr.span };
+			}
+			_ => self.infer(expr)
+		};
+		if !self.assignable(&checked.ty, expected)
+			&& let Some((coercion, ty)) = coerce::widening(&checked.ty, expected)
+		{
+			return self.coerce(checked, coercion, &ty);
+		}
+		let fits = self.expect(&checked.ty, expected, checked.span);
+		// Dynamic values are checked when they reach typed code
+		if fits && let Some(coercion) = coerce::dynamic(&checked.ty, expected) {
+			return self.coerce(checked, coercion, expected);
+		}
+		checked
+	}
+
+	fn coerce(&mut self, value: hir::Expr, coercion: Coercion, ty: &Type) -> hir::Expr {
+		if value.ty == *ty || value.ty.is_error() {
+			return value;
+		}
+		let span = value.span;
+		self.fold(hir::Expr { kind: hir::ExprKind::Coerce { value: Box::new(value), coercion }, ty: ty.clone(), span })
+	}
+
+	// `await` suspends the function it is in, so that function has to be
+	// `async`. Lambdas cannot be.
+	fn await_allowed(&mut self, span: Span) {
+		let message = match self.ctx.function.map(|id| self.program.function(id)) {
+			None if self.ctx.class.is_none() => {
+				"'await' can only be used inside an 'async fn'; move this code into 'async fn main()'".to_string()
+			}
+			None => "'await' can only be used inside an 'async fn'".to_string(),
+			Some(function) if function.modifiers.is_async => return,
+			Some(function) if function.kind == FnKind::Lambda => {
+				"'await' cannot be used in a lambda, which is never async; use a local 'async fn' instead".to_string()
+			}
+			Some(function) => format!("'await' can only be used inside an 'async fn'; mark '{}' 'async'", function.name)
+		};
+		self.error("E0503", message, span);
+	}
+
+	// Converts both operands of a binary operator to what it works on; see
+	// `coerce::operands`.
+	fn operands(&mut self, op: BinaryOp, result: &Type, left: hir::Expr, right: hir::Expr) -> (hir::Expr, hir::Expr) {
+		let Some((coercion, ty)) = coerce::operands(op, &left.ty, &right.ty, result) else {
+			return (left, right);
+		};
+		(self.coerce(left, coercion, &ty), self.coerce(right, coercion, &ty))
+	}
+
+	// `"a${b}c"` is `"a" + b + "c"`, with each interpolated value turned into
+	// a string.
+	fn interpolation(&mut self, parts: &'a [InterpolationPart], span: Span) -> hir::Expr {
+		let mut result: Option<hir::Expr> = None;
+		for part in parts {
+			let part = match part {
+				InterpolationPart::Text(text) => hir::Expr { kind: hir::ExprKind::Literal(Literal::String(text.clone())), ty: Type::String, span },
+				InterpolationPart::Expr(expr) => {
+					let value = self.infer(expr);
+					self.coerce(value, Coercion::ToString, &Type::String)
+				}
+			};
+			result = Some(match result {
+				None => part,
+				Some(left) => {
+					let kind = hir::ExprKind::Binary { op: BinaryOp::Add, left: Box::new(left), right: Box::new(part) };
+					self.fold(hir::Expr { kind, ty: Type::String, span })
+				}
+			});
+		}
+		result.unwrap_or(hir::Expr { kind: hir::ExprKind::Literal(Literal::String(String::new())), ty: Type::String, span })
+	}
+
+	fn expect(&mut self, found: &Type, expected: &Type, span: Span) -> bool {
+		if self.assignable(found, expected) {
+			return true;
+		}
+		if !found.is_error() && !expected.is_error() {
+			let mut message = format!("Type mismatch: expected '{}', found '{}'", expected, found);
+			if let Some(reason) = coerce::lossy(found, expected) {
+				message = format!("{}; {}", message, reason);
+			}
+			self.error("E0400", message, span);
+		}
+		false
+	}
+
+	// A block used as a value is worth its last expression.
+	fn block_value(&mut self, block: &'a Block, expected: Option<&Type>) -> (hir::Block, Type) {
+		self.hoist(&block.stmts);
+		let (stmts, ty) = self.stmts(&block.stmts, Some(expected));
+		if let Some(expected) = expected
+			&& ty == Type::Void
+		{
+			self.expect(&ty, expected, block.span);
+		}
+		(hir::Block { stmts, span: block.span }, ty)
+	}
+
+	fn infer(&mut self, expr: &'a Expr) -> hir::Expr {
+		if let Some(probed) = self.probed.remove(&expr.id) {
+			return probed;
+		}
+		let span = expr.span;
+		let (kind, ty) = match &expr.kind {
+			ExprKind::Literal(literal) => {
+				let ty = match literal {
+					Literal::Int(_) => Type::Int,
+					Literal::Float(_) => Type::Float,
+					Literal::String(_) => Type::String,
+					Literal::Bool(_) => Type::Bool,
+					Literal::Null => Type::Null
+				};
+				(hir::ExprKind::Literal(literal.clone()), ty)
+			}
+			ExprKind::Interpolation(parts) => return self.interpolation(parts, span),
+			ExprKind::Identifier(name) => return self.identifier(expr, name),
+			ExprKind::This => match self.ctx.this.clone() {
+				Some(ty) => (hir::ExprKind::This, ty),
+				None if self.ctx.class.is_some() => {
+					self.error("E0502", "'this' is not available in a static member, which belongs to the class rather than an object".to_string(), span);
+					(hir::ExprKind::This, Type::Error)
+				}
+				None => {
+					self.error("E0502", "'this' is only available in classes and extension functions".to_string(), span);
+					(hir::ExprKind::This, Type::Error)
+				}
+			},
+			ExprKind::Super => (hir::ExprKind::Super, self.superclass(span)),
+			ExprKind::Unary { op, operand } => self.unary(*op, operand, span),
+			ExprKind::Binary { op, left, right } => {
+				let left = self.infer(left);
+				if let Some(method) = self.operator_method(*op, &left.ty) {
+					return self.operator(*op, method, left, right, span);
+				}
+				let right = match op {
+					// The right side only runs when the left allows it
+					BinaryOp::And | BinaryOp::Or => {
+						let (when_true, when_false) = self.facts(&left);
+						let before = self.narrowed.clone();
+						self.assume(if *op == BinaryOp::And { &when_true } else { &when_false });
+						let right = self.infer(right);
+						self.narrowed = before;
+						right
+					}
+					BinaryOp::Coalesce => {
+						let before = self.narrowed.clone();
+						let right = self.infer(right);
+						self.narrowed = before;
+						// Code after `x ?? return` only runs when `x` was not null
+						if right.ty == Type::Never
+							&& let Some(var) = self.narrowable(&left)
+						{
+							self.narrowed.insert(var, left.ty.non_null().clone());
+						}
+						right
+					}
+					_ => self.infer(right)
+				};
+				let ty = self.binary(*op, &left, &right, span);
+				let (left, right) = self.operands(*op, &ty, left, right);
+				(hir::ExprKind::Binary { op: *op, left: Box::new(left), right: Box::new(right) }, ty)
+			}
+			ExprKind::Assign { target, op: None, value } => {
+				let target = self.assign_target(target);
+				let value = self.check(value, &target.ty);
+				if let hir::ExprKind::Var(var) = target.kind {
+					self.assigned(var, &value.ty);
+				}
+				let ty = target.ty.clone();
+				(hir::ExprKind::Assign { target: Box::new(target), op: None, value: Box::new(value) }, ty)
+			}
+			ExprKind::Assign { target, op: Some(op), value } => {
+				let target = self.assign_target(target);
+				let value = self.infer(value);
+				// The old value is read as what is known about it right now
+				let current = match target.kind {
+					hir::ExprKind::Var(var) => self.variable_type(var),
+					_ => target.ty.clone()
+				};
+				let read = hir::Expr { ty: current, ..target.clone() };
+				let result = self.binary(*op, &read, &value, span);
+				let (_, value) = self.operands(*op, &result, read, value);
+				self.expect(&result, &target.ty, span);
+				if let hir::ExprKind::Var(var) = target.kind {
+					self.assigned(var, &result);
+				}
+				let ty = target.ty.clone();
+				(hir::ExprKind::Assign { target: Box::new(target), op: Some(*op), value: Box::new(value) }, ty)
+			}
+			ExprKind::Postfix { op, operand } => {
+				let (operand, ty) = match op {
+					PostfixOp::NonNull => {
+						let operand = self.infer(operand);
+						let ty = match &operand.ty {
+							Type::Null => {
+								self.error("E0409", "'!!' on a value that is always null".to_string(), span);
+								Type::Error
+							}
+							ty => ty.non_null().clone()
+						};
+						(operand, ty)
+					}
+					PostfixOp::Propagate => {
+						let operand = self.infer(operand);
+						let ty = self.propagate(&operand, span);
+						(operand, ty)
+					}
+					PostfixOp::Increment | PostfixOp::Decrement => {
+						let symbol = if *op == PostfixOp::Increment { "++" } else { "--" };
+						let operand = self.step_target(operand, symbol);
+						let ty = operand.ty.clone();
+						(operand, ty)
+					}
+				};
+				(hir::ExprKind::Postfix { op: *op, operand: Box::new(operand) }, ty)
+			}
+			ExprKind::Is { operand, ty, negated } => {
+				let operand = self.infer(operand);
+				let ty = self.lower_type(ty);
+				(hir::ExprKind::Is { operand: Box::new(operand), ty, negated: *negated }, Type::Bool)
+			}
+			ExprKind::If { cond, then_branch, else_branch } => {
+				let (cond, then_branch, else_branch) = self.branches(cond, |checker| checker.infer(then_branch), |checker| checker.infer(else_branch));
+				let ty = self.join(&then_branch.ty, &else_branch.ty);
+				(hir::ExprKind::If { cond: Box::new(cond), then_branch: Box::new(then_branch), else_branch: Box::new(else_branch) }, ty)
+			}
+			ExprKind::Block(block) => {
+				let (block, ty) = self.block_value(block, None);
+				(hir::ExprKind::Block(block), ty)
+			}
+			ExprKind::Call { callee, args } => return self.call(callee, args, span, None),
+			ExprKind::Member { object, name, name_span, safe } => return self.member_expr(expr, object, name, *name_span, *safe),
+			ExprKind::Index { object, index } => {
+				let object = self.infer(object);
+				let index = self.infer(index);
+				if matches!(object.ty, Type::Nullable(_) | Type::Null) {
+					self.null_error(&object, "index it");
+				}
+				let ty = match object.ty.non_null() {
+					Type::Array(element) => (**element).clone(),
+					// Null for a key the map does not have
+					Type::Map(key, value) => {
+						if !self.assignable(&index.ty, key) {
+							self.error("E0400", format!("Key must be '{}', found '{}'", key, index.ty), index.span);
+						}
+						(**value).clone().nullable()
+					}
+					Type::String => Type::String,
+					Type::Any => Type::Any,
+					Type::Error => Type::Error,
+					other => {
+						self.error("E0402", format!("Cannot index into '{}'", other), object.span);
+						Type::Error
+					}
+				};
+				if matches!(object.ty.non_null(), Type::Array(_) | Type::String) && !self.assignable(&index.ty, &Type::Int) {
+					self.error("E0400", format!("Index must be 'Int', found '{}'", index.ty), index.span);
+				}
+				(hir::ExprKind::Index { object: Box::new(object), index: Box::new(index) }, ty)
+			}
+			ExprKind::New { class, args } => {
+				let ty = self.lower_type(class);
+				let written = !matches!(&class.kind, TypeExprKind::Named { args, .. } if args.is_empty());
+				return match ty {
+					Type::Class { id, args: type_args, .. } => self.construct(id, if written { &type_args } else { &[] }, args, span),
+					Type::Error => self.error_expr(span),
+					other => {
+						self.error("E0309", format!("'{}' is not a class", other), class.span);
+						self.error_expr(span)
+					}
+				};
+			}
+			ExprKind::Range { start, end } => {
+				let start = self.range_bound(start);
+				let end = self.range_bound(end);
+				(hir::ExprKind::Range { start: Box::new(start), end: Box::new(end) }, Type::Range)
+			}
+			ExprKind::Array(items) => {
+				let items: Vec<hir::Expr> = items.iter().map(|item| self.infer(item)).collect();
+				let element = items.iter().map(|item| item.ty.clone()).reduce(|a, b| self.join(&a, &b)).unwrap_or(Type::Any);
+				(hir::ExprKind::Array(items), Type::Array(Box::new(element)))
+			}
+			ExprKind::Map(entries) => {
+				let entries: Vec<(hir::Expr, hir::Expr)> = entries.iter().map(|(key, value)| (self.infer(key), self.infer(value))).collect();
+				let joined = |check: &Self, types: Vec<Type>| types.into_iter().reduce(|a, b| check.join(&a, &b)).unwrap_or(Type::Any);
+				let key = joined(self, entries.iter().map(|(key, _)| key.ty.clone()).collect());
+				let value = joined(self, entries.iter().map(|(_, value)| value.ty.clone()).collect());
+				(hir::ExprKind::Map(entries), Type::Map(Box::new(key), Box::new(value)))
+			}
+			ExprKind::Lambda { params, body } => self.lambda(params, body, span, None),
+			ExprKind::Spawn(block) => self.spawn(block, span),
+			ExprKind::Await(inner) => {
+				self.await_allowed(span);
+				let inner = self.infer(inner);
+				let ty = inner.ty.clone();
+				(hir::ExprKind::Await(Box::new(inner)), ty)
+			}
+			ExprKind::Return(value) => {
+				let hir::StmtKind::Return(value) = self.return_stmt(value.as_deref(), span) else {
+					unreachable!("return_stmt always builds a return");
+				};
+				(hir::ExprKind::Return(value.map(Box::new)), Type::Never)
+			}
+			ExprKind::Throw(value) => (hir::ExprKind::Throw(Box::new(self.infer(value))), Type::Never)
+		};
+		self.fold(hir::Expr { kind, ty, span })
+	}
+
+	// Replaces an operation on literals by its result.
+	fn fold(&mut self, expr: hir::Expr) -> hir::Expr {
+		if expr.ty == Type::Error {
+			return expr;
+		}
+		match consts::fold(&expr.kind) {
+			Ok(Some(value)) => hir::Expr { kind: hir::ExprKind::Literal(value), ..expr },
+			Ok(None) => expr,
+			Err(message) => {
+				self.error("E0412", message, expr.span);
+				expr
+			}
+		}
+	}
+
+	fn error_expr(&self, span: Span) -> hir::Expr {
+		hir::Expr { kind: hir::ExprKind::Literal(Literal::Null), ty: Type::Error, span }
+	}
+
+	fn range_bound(&mut self, bound: &'a Expr) -> hir::Expr {
+		let bound = self.infer(bound);
+		if !self.assignable(&bound.ty, &Type::Int) {
+			self.error("E0400", format!("Range bounds must be 'Int', found '{}'", bound.ty), bound.span);
+		}
+		bound
+	}
+
+	fn identifier(&mut self, expr: &'a Expr, name: &str) -> hir::Expr {
+		let span = expr.span;
+		if let Some(resolution) = self.resolutions[self.file].exprs.get(&expr.id).cloned() {
+			return match self.named(&resolution, name, span) {
+				Named::Value(value) => value,
+				Named::Type(id) => {
+					self.error("E0210", format!("'{}' is a type, not a value", self.program.class(id).name), span);
+					self.error_expr(span)
+				}
+				Named::Package(package) => {
+					self.error("E0210", format!("'{}' is a package, not a value", package), span);
+					self.error_expr(span)
+				}
+			};
+		}
+
+		if let Some(builtin) = Builtin::lookup(name) {
+			let callee = hir::Expr { kind: hir::ExprKind::Builtin(builtin), ty: builtin.signature(), span };
+			if builtin == Builtin::None {
+				let kind = hir::ExprKind::Call { callee: Box::new(callee), args: Vec::new() };
+				return hir::Expr { kind, ty: Type::Option(Box::new(Type::Never)), span };
+			}
+			return callee;
+		}
+		if let Some(native) = self.native(name, span) {
+			return native;
+		}
+		// Inherited members are not in the resolver's scopes
+		if let Some(this) = self.ctx.this.clone()
+			&& let Type::Class { id, args, .. } = &this
+			&& let Some((target, ty)) = self.class_member(*id, args, name)
+		{
+			self.member_access(target, name, span);
+			let object = hir::Expr { kind: hir::ExprKind::This, ty: this.clone(), span };
+			let kind = hir::ExprKind::Member { object: Box::new(object), name: name.to_string(), name_span: span, target, safe: false };
+			return hir::Expr { kind, ty, span };
+		}
+		if let Some(class) = self.ctx.class
+			&& let Some(found) = self.static_member(class, name, span)
+		{
+			return found;
+		}
+
+		// The resolver knows what else is in scope, and a keyword may be
+		// what was meant, as in `retrun`
+		let mut candidates: Vec<String> = self.resolutions[self.file].closest.get(&expr.id).cloned().into_iter().collect();
+		candidates.extend(Builtin::ALL.iter().map(|builtin| builtin.name().to_string()));
+		candidates.extend(self.program.natives.iter().map(|native| native.name.clone()).filter(|name| !name.contains('.')));
+		if let Some(this) = &self.ctx.this {
+			candidates.extend(self.member_names(this));
+		}
+		candidates.extend(suggest::keywords(name).map(str::to_string));
+		let message = format!("Cannot find '{}' in this scope", name);
+		self.not_found("E0200", message, name, span, candidates.iter().map(String::as_str), false);
+		self.error_expr(span)
+	}
+
+	// The native registered as `name`, if there is one.
+	fn native(&self, name: &str, span: Span) -> Option<hir::Expr> {
+		let index = self.program.natives.iter().position(|native| native.name == name)?;
+		Some(hir::Expr { kind: hir::ExprKind::Native(index), ty: self.program.natives[index].signature(), span })
+	}
+
+	fn named(&mut self, resolution: &Resolution, name: &str, span: Span) -> Named {
+		let def = match resolution {
+			Resolution::Local(node) | Resolution::TypeParam(node) => self.def(*node),
+			Resolution::Symbol(symbol) => self.symbols.get(symbol).copied(),
+			Resolution::Member => return Named::Value(self.implicit_member(name, span)),
+			Resolution::Package(package) => return Named::Package(package.clone()),
+			// The graph has the natives of the runtime the program is checked for
+			Resolution::Native(native) => return Named::Value(self.native(native, span).unwrap_or_else(|| self.error_expr(span)))
+		};
+
+		let (kind, ty) = match def {
+			Some(Def::Local(local) | Def::LocalFunction(local, _)) => self.variable(Variable::Local(local)),
+			Some(Def::Global(global)) => {
+				self.infer_pending(Pending::Global(global));
+				self.variable(Variable::Global(global))
+			}
+			Some(Def::Function(function)) => {
+				if let Some(receiver) = &self.program.function(function).receiver {
+					self.error("E0210", format!("'{}' extends '{}'; call it on a value, as in 'value.{}()'", name, receiver, name), span);
+					return Named::Value(self.error_expr(span));
+				}
+				(hir::ExprKind::Function(function), self.fn_type(function, &HashMap::new()))
+			}
+			Some(Def::Class(id)) => return Named::Type(id),
+			Some(Def::TypeParam(_) | Def::Alias(..)) => {
+				self.error("E0210", format!("'{}' is a type, not a value", name), span);
+				return Named::Value(self.error_expr(span));
+			}
+			None => {
+				self.error("E0210", format!("'{}' cannot be used as a value", name), span);
+				return Named::Value(self.error_expr(span));
+			}
+		};
+		Named::Value(hir::Expr { kind, ty, span })
+	}
+
+	fn variable(&mut self, var: Variable) -> (hir::ExprKind, Type) {
+		let ty = self.variable_type(var);
+		match self.consts.get(&var) {
+			Some(value) => {
+				self.used_consts.insert(var);
+				(hir::ExprKind::Literal(value.clone()), ty)
+			}
+			None => (hir::ExprKind::Var(var), ty)
+		}
+	}
+
+	// A member of the enclosing class used without `this.`, or `field` in an
+	// accessor.
+	fn implicit_member(&mut self, name: &str, span: Span) -> hir::Expr {
+		if name == "field"
+			&& let Some((field, ty)) = self.ctx.accessor.clone()
+		{
+			return hir::Expr { kind: hir::ExprKind::BackingField(field), ty, span };
+		}
+
+		let Some(class) = self.ctx.class else {
+			self.error("E0200", format!("Cannot find '{}' in this scope", name), span);
+			return self.error_expr(span);
+		};
+		if let Some(index) = self.program.class(class).variants.iter().position(|variant| variant.name == name) {
+			let ty = self.self_type(class);
+			return hir::Expr { kind: hir::ExprKind::Variant { class, index }, ty, span };
+		}
+		if let Some(found) = self.static_member(class, name, span) {
+			return found;
+		}
+		if self.ctx.this.is_none() {
+			let message = format!("'{}' belongs to objects of '{}', and a static member has none", name, self.program.class(class).name);
+			self.error("E0502", message, span);
+			return self.error_expr(span);
+		}
+		let this = self.self_type(class);
+		let object = hir::Expr { kind: hir::ExprKind::This, ty: this, span };
+		self.member(object, name, span, span, false)
+	}
+
+	fn member_expr(&mut self, expr: &'a Expr, object: &'a Expr, name: &str, name_span: Span, safe: bool) -> hir::Expr {
+		let span = expr.span;
+
+		// `pkg.name`, already looked up by the resolver
+		if let Some(resolution) = self.resolutions[self.file].exprs.get(&expr.id).cloned() {
+			return match self.named(&resolution, name, span) {
+				Named::Value(value) => value,
+				Named::Type(id) => {
+					self.error("E0210", format!("'{}' is a type, not a value", self.program.class(id).name), span);
+					self.error_expr(span)
+				}
+				Named::Package(package) => {
+					self.error("E0210", format!("'{}' is a package, not a value", package), span);
+					self.error_expr(span)
+				}
+			};
+		}
+		// Names missing from a package were reported during resolution
+		if let Some(Resolution::Package(_)) = self.resolutions[self.file].exprs.get(&object.id) {
+			return self.error_expr(span);
+		}
+
+		// `math.sqrt`, when `math` is nothing else but a native is called that
+		if let ExprKind::Identifier(prefix) = &object.kind
+			&& !self.resolutions[self.file].exprs.contains_key(&object.id)
+			&& let Some(native) = self.native(&format!("{}.{}", prefix, name), span)
+		{
+			return native;
+		}
+
+		if let Some(class) = self.type_ref(object) {
+			if let Some(found) = self.static_member(class, name, span) {
+				return found;
+			}
+			let found = self.program.class(class).variants.iter().position(|variant| variant.name == name);
+			return match found {
+				Some(index) => hir::Expr { kind: hir::ExprKind::Variant { class, index }, ty: self.self_type(class), span },
+				None => {
+					let class = self.program.class(class);
+					if class.kind == ClassKind::Enum {
+						let message = format!("Enum '{}' has no variant '{}'", class.name, name);
+						let error = Diagnostic::error(message, name_span).with_code("E0211").in_file(self.file);
+						let variants = class.variants.iter().map(|variant| variant.name.as_str());
+						self.errors.push(suggest::did_you_mean(error, name, name_span, variants));
+					} else {
+						let message = format!("'{}' is a type; '{}' can only be used on an instance of it", class.name, name);
+						self.error("E0210", message, name_span);
+					}
+					self.error_expr(span)
+				}
+			};
+		}
+
+		let object = self.infer(object);
+		self.member(object, name, name_span, span, safe)
+	}
+
+	// A static member of the class, or of one it inherits from, as the
+	// global or function it is.
+	fn static_member(&mut self, class: TypeId, name: &str, span: Span) -> Option<hir::Expr> {
+		let (owner, member) = self.ancestors(class).into_iter().find_map(|owner| {
+			let member = self.program.class(owner).statics.iter().find(|member| member.name == name)?;
+			Some((owner, member.clone()))
+		})?;
+		self.check_access(owner, &format!("'{}'", name), member.visibility, member.span, span);
+		let (kind, ty) = match member.target {
+			StaticTarget::Field(global) => {
+				// Initializers run in order, so those of later fields have not yet
+				if let Some(current) = self.ctx.initializing
+					&& self.program.global(current).class == Some(owner)
+					&& global.0 >= current.0
+				{
+					let message = format!("'{}' is set after '{}', so it is still null here", name, self.program.global(current).name);
+					self.error("E0313", message, span);
+					return Some(self.error_expr(span));
+				}
+				self.infer_pending(Pending::Global(global));
+				self.variable(Variable::Global(global))
+			}
+			StaticTarget::Method(method) => (hir::ExprKind::Function(method), self.fn_type(method, &HashMap::new()))
+		};
+		Some(hir::Expr { kind, ty, span })
+	}
+
+	// The class a name refers to when it is used as `Name(...)` or `Name.x`.
+	fn type_ref(&self, expr: &Expr) -> Option<TypeId> {
+		let def = match self.resolutions[self.file].exprs.get(&expr.id)? {
+			Resolution::Local(node) => self.def(*node),
+			Resolution::Symbol(symbol) => self.symbols.get(symbol).copied(),
+			_ => None
+		};
+		match def? {
+			Def::Class(id) => Some(id),
+			_ => None
+		}
+	}
+
+	fn member(&mut self, object: hir::Expr, name: &str, name_span: Span, span: Span, safe: bool) -> hir::Expr {
+		let found = match object.ty.non_null() {
+			Type::Any => Some((MemberTarget::Dynamic, Type::Any)),
+			Type::Error => Some((MemberTarget::Dynamic, Type::Error)),
+			Type::Class { id, args, .. } => self.class_member(*id, args, name),
+			Type::Param { id, .. } => {
+				let bounds = self.program.type_param(*id).bounds.clone();
+				let mut found = None;
+				for bound in &bounds {
+					found = match bound {
+						Type::Class { id, args, .. } => self.class_member(*id, args, name),
+						_ => builtins::member(bound, name).map(|ty| (MemberTarget::Builtin, ty))
+					};
+					if found.is_some() {
+						break;
+					}
+				}
+				found
+			}
+			_ => None
+		};
+		let found = found
+			.or_else(|| self.extension(&object.ty, name))
+			.or_else(|| builtins::member(object.ty.non_null(), name).map(|ty| (MemberTarget::Builtin, ty)))
+			.or_else(|| match object.ty.non_null() {
+				// The name of an enum value's variant, as it is declared
+				Type::Class { id, .. } if name == "name" && self.program.class(*id).kind == ClassKind::Enum => Some((MemberTarget::Builtin, Type::String)),
+				Type::Class { .. } => {
+					let element = self.elements(object.ty.non_null())?;
+					builtins::sequence_member(&element, name, &Type::Any).map(|ty| (MemberTarget::Builtin, ty))
+				}
+				_ => None
+			});
+
+		let Some((target, ty)) = found else {
+			if let Type::Class { id, .. } = object.ty.non_null()
+				&& let Some(owner) = self.ancestors(*id).into_iter().find(|owner| self.program.class(*owner).statics.iter().any(|member| member.name == name))
+			{
+				let owner = &self.program.class(owner).name;
+				self.error("E0211", format!("'{}' is static, so it belongs to '{}' rather than its objects; use '{}.{}'", name, owner, owner, name), name_span);
+				return self.error_expr(span);
+			}
+			let message = format!("Type '{}' has no member '{}'", object.ty, name);
+			let candidates = self.member_names(object.ty.non_null());
+			self.misspelled("E0211", message, name, name_span, candidates.iter().map(String::as_str));
+			return self.error_expr(span);
+		};
+
+		self.member_access(target, name, name_span);
+
+		let nullable = matches!(object.ty, Type::Nullable(_) | Type::Null);
+		// Extensions declared on `T?` are made to be called on null
+		let accepts_null = match target {
+			MemberTarget::Extension(id) => matches!(self.program.function(id).receiver, Some(Type::Nullable(_) | Type::Any)),
+			_ => false
+		};
+		let ty = match (nullable && !accepts_null, safe) {
+			(true, false) => {
+				self.null_error(&object, &format!("access '{}'", name));
+				ty
+			}
+			// A safe method call makes the call's result nullable instead; see `call`
+			(true, true) if !matches!(ty, Type::Function { .. }) => ty.nullable(),
+			_ => ty
+		};
+		let kind = hir::ExprKind::Member { object: Box::new(object), name: name.to_string(), name_span, target, safe };
+		hir::Expr { kind, ty, span }
+	}
+
+	fn member_access(&mut self, target: MemberTarget, name: &str, span: Span) {
+		match target {
+			MemberTarget::Field(owner) => {
+				if let Some(field) = self.program.class(owner).field(name) {
+					let (visibility, declared) = (field.visibility, field.span);
+					self.check_access(owner, &format!("'{}'", name), visibility, declared, span);
+				}
+			}
+			MemberTarget::Method(method) => {
+				let function = self.program.function(method);
+				if let FnKind::Method(owner) = function.kind {
+					let (visibility, declared) = (function.modifiers.visibility, function.span);
+					self.check_access(owner, &format!("'{}'", name), visibility, declared, span);
+				}
+			}
+			// Private extensions belong to their package, like other top-level declarations
+			MemberTarget::Extension(extension) => {
+				let function = self.program.function(extension);
+				let package = self.graph.package_of(function.file);
+				if function.modifiers.visibility == Visibility::Private && package != self.graph.package_of(self.file) {
+					let message = format!(
+						"'{}' is private to package '{}'; it is declared at {}",
+						name,
+						package,
+						self.location(function.file, function.span)
+					);
+					self.error("E0208", message, span);
+				}
+			}
+			MemberTarget::Builtin | MemberTarget::Dynamic => {}
+		}
+	}
+
+	// Private members can only be used inside their class, and protected ones
+	// also inside its subclasses.
+	fn check_access(&mut self, owner: TypeId, what: &str, visibility: Visibility, declared: Span, span: Span) {
+		let allowed = match visibility {
+			Visibility::Public => true,
+			Visibility::Private => self.ctx.class == Some(owner),
+			Visibility::Protected => self.ctx.class.is_some_and(|class| self.ancestors(class).contains(&owner))
+		};
+		if allowed {
+			return;
+		}
+		let class = self.program.class(owner);
+		let rule = match visibility {
+			Visibility::Private => format!("it is private to '{}'", class.name),
+			_ => format!("it is protected, so only '{}' and its subclasses can use it", class.name)
+		};
+		let location = self.location(class.file, declared);
+		self.error("E0208", format!("Cannot use {} here; {} (declared at {})", what, rule, location), span);
+	}
+
+	// Where a declaration is, for messages about code elsewhere.
+	fn location(&self, file: usize, span: Span) -> String {
+		let place = format!("line {} column {}", span.line, span.column);
+		if file == self.file { place } else { format!("{} of {}", place, self.files[file].path) }
+	}
+
+	fn null_error(&mut self, object: &hir::Expr, action: &str) {
+		let subject = match &object.kind {
+			hir::ExprKind::Var(Variable::Local(local)) => format!("'{}'", self.program.local(*local).name),
+			hir::ExprKind::Var(Variable::Global(global)) => format!("'{}'", self.program.global(*global).name),
+			hir::ExprKind::Member { name, .. } => format!("'{}'", name),
+			_ => format!("A value of type '{}'", object.ty)
+		};
+		let fix = if action.starts_with("access") { "use '?.' or '!!'" } else { "check it first or use '!!'" };
+		self.error("E0409", format!("{} may be null; {} to {}", subject, fix, action), object.span);
+	}
+
+	// `find_member`, inferring the type of an unannotated field on first use.
+	fn class_member(&mut self, id: TypeId, args: &[Type], name: &str) -> Option<(MemberTarget, Type)> {
+		let found = self.find_member(id, args, name);
+		if let Some((MemberTarget::Field(owner), _)) = found
+			&& let Some(index) = self.program.class(owner).fields.iter().position(|field| field.name == name)
+			&& self.pending.contains_key(&Pending::Field(owner, index))
+		{
+			self.infer_pending(Pending::Field(owner, index));
+			return self.find_member(id, args, name);
+		}
+		found
+	}
+
+	// Fields and methods of a class and everything it inherits, nearest first.
+	fn find_member(&self, id: TypeId, args: &[Type], name: &str) -> Option<(MemberTarget, Type)> {
+		let mut queue = vec![(id, args.to_vec())];
+		let mut seen = HashSet::new();
+		let mut next = 0;
+		while next < queue.len() {
+			let (id, args) = queue[next].clone();
+			next += 1;
+			if !seen.insert(id) {
+				continue;
+			}
+			let class = self.program.class(id);
+			let subst = class.type_params.iter().copied().zip(args.iter().cloned()).collect();
+			if let Some(field) = class.field(name) {
+				return Some((MemberTarget::Field(id), field.ty.substitute(&subst)));
+			}
+			if let Some(method) = class.methods.iter().find(|method| self.program.function(**method).name == name) {
+				return Some((MemberTarget::Method(*method), self.fn_type(*method, &subst)));
+			}
+			for supertype in &class.supertypes {
+				if let Type::Class { id, args, .. } = supertype.substitute(&subst) {
+					queue.push((id, args));
+				}
+			}
+		}
+		None
+	}
+
+	fn extension(&self, receiver: &Type, name: &str) -> Option<(MemberTarget, Type)> {
+		for id in self.extensions.get(name)? {
+			let function = self.program.function(*id);
+			let any = function.type_params.iter().map(|param| (*param, Type::Any)).collect();
+			let Some(expected) = &function.receiver else {
+				continue;
+			};
+			if self.assignable(receiver, &expected.substitute(&any)) {
+				return Some((MemberTarget::Extension(*id), self.fn_type(*id, &HashMap::new())));
+			}
+		}
+		None
+	}
+
+	fn member_names(&self, ty: &Type) -> Vec<String> {
+		let mut names: Vec<String> = builtins::member_names(ty).iter().map(|name| name.to_string()).collect();
+		if self.elements(ty).is_some() {
+			names.extend(builtins::SEQUENCE_METHODS.iter().map(|name| name.to_string()));
+		}
+		if let Type::Class { id, .. } = ty {
+			let mut queue = vec![*id];
+			let mut seen = HashSet::new();
+			while let Some(id) = queue.pop() {
+				if !seen.insert(id) {
+					continue;
+				}
+				let class = self.program.class(id);
+				if class.kind == ClassKind::Enum {
+					names.push("name".to_string());
+				}
+				names.extend(class.fields.iter().map(|field| field.name.clone()));
+				names.extend(class.methods.iter().map(|method| self.program.function(*method).name.clone()));
+				queue.extend(class.supertypes.iter().filter_map(|ty| match ty {
+					Type::Class { id, .. } => Some(*id),
+					_ => None
+				}));
+			}
+		}
+		names.extend(self.extensions.keys().cloned());
+		names
+	}
+
+	// The type of a function used as a value. Its own type parameters stand
+	// for `Any`; calls infer them instead (see `generic_call`).
+	fn fn_type(&self, id: FnId, subst: &HashMap<TypeParamId, Type>) -> Type {
+		let function = self.program.function(id);
+		let mut subst = subst.clone();
+		subst.extend(function.type_params.iter().map(|param| (*param, Type::Any)));
+		function.signature().substitute(&subst)
+	}
+
+	fn self_type(&self, id: TypeId) -> Type {
+		let class = self.program.class(id);
+		let args = class
+			.type_params
+			.iter()
+			.map(|param| Type::Param { id: *param, name: self.program.type_param(*param).name.clone() })
+			.collect();
+		Type::Class { id, name: class.name.clone(), args }
+	}
+
+	fn superclass(&mut self, span: Span) -> Type {
+		let Some(this) = self.ctx.this.clone() else {
+			self.error("E0502", "'super' is only available in classes".to_string(), span);
+			return Type::Error;
+		};
+		match self.superclass_of(&this) {
+			Some(superclass) => superclass,
+			None => {
+				self.error("E0502", format!("'{}' has no superclass", this), span);
+				Type::Error
+			}
+		}
+	}
+
+	fn superclass_of(&self, ty: &Type) -> Option<Type> {
+		let Type::Class { id, args, .. } = ty else {
+			return None;
+		};
+		let class = self.program.class(*id);
+		let subst = class.type_params.iter().copied().zip(args.iter().cloned()).collect();
+		class
+			.supertypes
+			.first()
+			.filter(|ty| self.is_class_kind(ty, ClassKind::Class))
+			.map(|ty| ty.substitute(&subst))
+	}
+
+	fn is_class_kind(&self, ty: &Type, kind: ClassKind) -> bool {
+		matches!(ty, Type::Class { id, .. } if self.program.class(*id).kind == kind)
+	}
+
+	fn unary(&mut self, op: UnaryOp, operand: &'a Expr, span: Span) -> (hir::ExprKind, Type) {
+		let (operand, ty) = match op {
+			UnaryOp::Negate => {
+				let operand = self.infer(operand);
+				let ty = match &operand.ty {
+					ty if ty.is_numeric() || ty.is_dynamic() => ty.clone(),
+					ty => {
+						self.error("E0401", format!("Operator '-' cannot be applied to '{}'", ty), span);
+						Type::Error
+					}
+				};
+				(operand, ty)
+			}
+			UnaryOp::Not => {
+				let operand = self.infer(operand);
+				if !self.assignable(&operand.ty, &Type::Bool) {
+					self.error("E0401", format!("Operator '!' cannot be applied to '{}'", operand.ty), span);
+				}
+				(operand, Type::Bool)
+			}
+			UnaryOp::PreIncrement | UnaryOp::PreDecrement => {
+				let symbol = if op == UnaryOp::PreIncrement { "++" } else { "--" };
+				let operand = self.step_target(operand, symbol);
+				let ty = operand.ty.clone();
+				(operand, ty)
+			}
+			UnaryOp::Typeof => (self.infer(operand), Type::String)
+		};
+		(hir::ExprKind::Unary { op, operand: Box::new(operand) }, ty)
+	}
+
+	// Operand of `++` or `--`.
+	fn step_target(&mut self, operand: &'a Expr, symbol: &str) -> hir::Expr {
+		let operand = self.assign_target(operand);
+		if !operand.ty.is_numeric() && !operand.ty.is_dynamic() {
+			self.error("E0401", format!("Operator '{}' cannot be applied to '{}'", symbol, operand.ty), operand.span);
+		}
+		operand
+	}
+
+	fn assign_target(&mut self, target: &'a Expr) -> hir::Expr {
+		let constant = match &target.kind {
+			ExprKind::Identifier(name) => Some(name),
+			_ => None
+		};
+		let mut target = self.infer(target);
+		// What is stored must fit the declaration, not what is known right now
+		if let hir::ExprKind::Var(var) = target.kind {
+			target.ty = self.declared_type(var);
+		}
+		// Reading a key can find none, but what is stored is a value
+		if let hir::ExprKind::Index { object, .. } = &target.kind
+			&& let Type::Map(_, value) = object.ty.non_null()
+		{
+			target.ty = (**value).clone();
+		}
+		if let hir::ExprKind::Member { object, name, target: MemberTarget::Field(class), .. } = &target.kind {
+			self.field_write(object, *class, name, target.span);
+			// Reading it was allowed, so only a narrower setter can still be in the way
+			if let Some(field) = self.program.class(*class).field(name)
+				&& field.setter_visibility != field.visibility
+			{
+				let (visibility, declared) = (field.setter_visibility, field.span);
+				self.check_access(*class, &format!("the setter of '{}'", name), visibility, declared, target.span);
+			}
+		}
+		let name = match &target.kind {
+			hir::ExprKind::Var(_) | hir::ExprKind::BackingField(_) | hir::ExprKind::Index { .. } => return target,
+			hir::ExprKind::Member { target: MemberTarget::Field(_) | MemberTarget::Dynamic, .. } => return target,
+			_ if target.ty == Type::Error => return target,
+			hir::ExprKind::Literal(_) if let Some(name) = constant => {
+				self.error("E0508", format!("Cannot assign to '{}'; it is a 'const'", name), target.span);
+				return target;
+			}
+			hir::ExprKind::Member { name, .. } => name.clone(),
+			hir::ExprKind::Function(function) => self.program.function(*function).name.clone(),
+			hir::ExprKind::Builtin(builtin) => builtin.name().to_string(),
+			hir::ExprKind::Native(native) => self.program.natives[*native].name.clone(),
+			_ => {
+				self.error("E0107", "Invalid assignment target".to_string(), target.span);
+				return target;
+			}
+		};
+		self.error("E0107", format!("Cannot assign to '{}'; it is not a variable or field", name), target.span);
+		target
+	}
+
+	// A `val` field can only be set by its own class's constructors, through
+	// `this`.
+	fn field_write(&mut self, object: &hir::Expr, class: TypeId, name: &str, span: Span) {
+		let Some(field) = self.program.class(class).field(name) else {
+			return;
+		};
+		if field.mutable || field.setter.is_some() {
+			return;
+		}
+		if self.ctx.constructor && self.ctx.class == Some(class) && matches!(object.kind, hir::ExprKind::This) {
+			return;
+		}
+		let message = if field.getter.is_some() {
+			format!("Cannot assign to '{}'; it has a getter but no setter", name)
+		} else if self.ctx.constructor && self.ctx.class == Some(class) {
+			format!("Cannot assign to '{}' here; a 'val' field can only be set through 'this' in its constructor", name)
+		} else {
+			format!("Cannot assign to '{}' because it is a 'val'; declare it with 'mut' on line {} to allow this", name, field.span.line)
+		};
+		self.error("E0508", message, span);
+	}
+
+	fn binary(&mut self, op: BinaryOp, left: &hir::Expr, right: &hir::Expr, span: Span) -> Type {
+		let (l, r) = (&left.ty, &right.ty);
+		let dynamic = l.is_dynamic() || r.is_dynamic();
+		let ty = match op {
+			BinaryOp::Add if *l == Type::String || *r == Type::String => Some(Type::String),
+			BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo => {
+				match (l, r) {
+					(Type::Int, Type::Int) => Some(Type::Int),
+					_ if l.is_numeric() && r.is_numeric() => Some(Type::Float),
+					_ if dynamic && (l.is_numeric() || r.is_numeric() || (l.is_dynamic() && r.is_dynamic())) => Some(Type::Any),
+					_ => None
+				}
+			}
+			BinaryOp::Equal | BinaryOp::NotEqual => Some(Type::Bool),
+			BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual => {
+				let comparable = |ty: &Type| ty.is_numeric() || *ty == Type::String || ty.is_dynamic();
+				let ok = comparable(l) && comparable(r) && (dynamic || l.is_numeric() == r.is_numeric());
+				ok.then_some(Type::Bool)
+			}
+			BinaryOp::And | BinaryOp::Or => {
+				(self.assignable(l, &Type::Bool) && self.assignable(r, &Type::Bool)).then_some(Type::Bool)
+			}
+			BinaryOp::Coalesce => Some(match l {
+				Type::Null => r.clone(),
+				_ => self.join(l.non_null(), r)
+			}),
+			BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor | BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+				(self.assignable(l, &Type::Int) && self.assignable(r, &Type::Int)).then_some(Type::Int)
+			}
+			BinaryOp::In => match r.non_null() {
+				Type::Array(element) => {
+					let element = (**element).clone();
+					(self.assignable(l, &element) || self.assignable(&element, l)).then_some(Type::Bool)
+				}
+				// A map's keys, and a set's elements
+				Type::Map(element, _) | Type::Set(element) => {
+					let element = (**element).clone();
+					(self.assignable(l, &element) || self.assignable(&element, l)).then_some(Type::Bool)
+				}
+				Type::Range => self.assignable(l, &Type::Int).then_some(Type::Bool),
+				Type::String => self.assignable(l, &Type::String).then_some(Type::Bool),
+				Type::Any | Type::Error => Some(Type::Bool),
+				_ => None
+			}
+		};
+
+		ty.unwrap_or_else(|| {
+			if !l.is_error() && !r.is_error() {
+				self.error("E0401", format!("Operator '{}' cannot be applied to '{}' and '{}'", op.symbol(), l, r), span);
+			}
+			Type::Error
+		})
+	}
+
+	// Classes take part in arithmetic through methods named after the
+	// operator, so `a + b` is `a.plus(b)`, and in comparisons through
+	// `compareTo`, so `a < b` is `a.compareTo(b) < 0`. Overloads of the method
+	// are chosen between as for any call.
+	fn operator(&mut self, op: BinaryOp, name: &str, left: hir::Expr, right: &'a Expr, span: Span) -> hir::Expr {
+		let method = self.member(left, name, span, span, false);
+		let call = self.call_value(method, std::slice::from_ref(right), span, None);
+		if name != COMPARE_METHOD {
+			return call;
+		}
+		if !call.ty.is_error() && !self.assignable(&call.ty, &Type::Int) {
+			self.error("E0401", format!("'{}' has to return an 'Int' to be used by '{}', not '{}'", name, op.symbol(), call.ty), span);
+		}
+		let zero = hir::Expr { kind: hir::ExprKind::Literal(Literal::Int(0)), ty: Type::Int, span };
+		let kind = hir::ExprKind::Binary { op, left: Box::new(call), right: Box::new(zero) };
+		hir::Expr { kind, ty: Type::Bool, span }
+	}
+
+	// The method of class type `ty` that `op` stands for, if it has one.
+	fn operator_method(&self, op: BinaryOp, ty: &Type) -> Option<&'static str> {
+		let name = match op {
+			BinaryOp::Add => "plus",
+			BinaryOp::Subtract => "minus",
+			BinaryOp::Multiply => "times",
+			BinaryOp::Divide => "div",
+			BinaryOp::Modulo => "rem",
+			BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual => COMPARE_METHOD,
+			_ => return None
+		};
+		let Type::Class { id, args, .. } = ty else {
+			return None;
+		};
+		matches!(self.find_member(*id, args, name), Some((MemberTarget::Method(_), _))).then_some(name)
+	}
+
+	// `expected` is the function type the context wants, if any. Its
+	// parameter types fill in unannotated parameters and its return type is
+	// what the body must produce; without it an expression body's type is the
+	// return type and a block body returns `Any`.
+	fn lambda(&mut self, params: &'a [Param], body_ast: &'a LambdaBody, span: Span, expected: Option<&Type>) -> (hir::ExprKind, Type) {
+		let id = self.new_function("<lambda>", FnKind::Lambda, &Modifiers::default(), span);
+		let mut arity_error = false;
+		let (expected_params, expected_ret) = match expected {
+			Some(Type::Function { params: expected, ret }) if expected.len() == params.len() => {
+				(expected.clone(), Some((**ret).clone()).filter(|ret| !mentions(ret, &self.unsolved)))
+			}
+			Some(Type::Function { params: expected, .. }) => {
+				let plural = if expected.len() == 1 { "" } else { "s" };
+				self.error("E0403", format!("Expected a function taking {} parameter{} but this one takes {}", expected.len(), plural, params.len()), span);
+				arity_error = true;
+				(Vec::new(), None)
+			}
+			_ => (Vec::new(), None)
+		};
+		let lowered = self.params(id, params, &expected_params);
+		self.program.functions[id.0 as usize].params = lowered;
+
+		let saved = self.ctx.clone();
+		self.ctx.accessor = None;
+		self.ctx.constructor = false;
+		self.ctx.loops.clear();
+		self.ctx.deferred = None;
+		self.ctx.initializing = None;
+		self.ctx.function = Some(id);
+		// Captured variables keep what is known about them only while nothing
+		// can assign them between now and when the lambda runs
+		let saved_narrowed = self.narrowed.clone();
+		self.narrowed.retain(|var, _| match *var {
+			Variable::Local(local) => !self.program.local(local).mutable,
+			Variable::Global(_) => true
+		});
+		let (body, ret) = match body_ast {
+			LambdaBody::Expr(expr) => {
+				self.ctx.ret = Some(expected_ret.clone().unwrap_or(Type::Any));
+				let value = match &expected_ret {
+					// The value of a lambda used as a `Void` callback is thrown away
+					Some(Type::Void) | None => self.infer(expr),
+					Some(ret) => self.check(expr, ret)
+				};
+				let ret = expected_ret.unwrap_or_else(|| value.ty.clone());
+				let span = value.span;
+				(hir::Block { stmts: vec![hir::Stmt { kind: hir::StmtKind::Return(Some(value)), span }], span }, ret)
+			}
+			LambdaBody::Block(block) => {
+				let ret = expected_ret.unwrap_or(Type::Any);
+				self.ctx.ret = Some(ret.clone());
+				(self.block(block), ret)
+			}
+		};
+		self.ctx = saved;
+		self.narrowed = saved_narrowed;
+		self.forget_assigned(|assigned| match body_ast {
+			LambdaBody::Expr(expr) => assigned.visit_expr(expr),
+			LambdaBody::Block(block) => assigned.visit_block(block)
+		});
+
+		let function = &mut self.program.functions[id.0 as usize];
+		function.ret = ret;
+		function.body = Some(body);
+		let ty = if arity_error { Type::Error } else { self.fn_type(id, &HashMap::new()) };
+		(hir::ExprKind::Lambda(id), ty)
+	}
+
+	// `spawn { ... }` is an async lambda called there and then, so the
+	// engines run it as a task of its own. Its type is that of the block's
+	// last expression, which the task finishes with.
+	fn spawn(&mut self, block: &'a Block, span: Span) -> (hir::ExprKind, Type) {
+		let modifiers = Modifiers { is_async: true, ..Modifiers::default() };
+		let id = self.new_function("<spawn>", FnKind::Lambda, &modifiers, span);
+		let saved = self.ctx.clone();
+		self.ctx.accessor = None;
+		self.ctx.constructor = false;
+		self.ctx.loops.clear();
+		self.ctx.deferred = None;
+		self.ctx.initializing = None;
+		self.ctx.function = Some(id);
+		self.ctx.ret = Some(Type::Any);
+		// As for lambdas, the task may run after what it captures is assigned
+		let saved_narrowed = self.narrowed.clone();
+		self.narrowed.retain(|var, _| match *var {
+			Variable::Local(local) => !self.program.local(local).mutable,
+			Variable::Global(_) => true
+		});
+		let (mut body, ret) = self.block_value(block, None);
+		self.ctx = saved;
+		self.narrowed = saved_narrowed;
+		self.forget_assigned(|assigned| assigned.visit_block(block));
+
+		if let Some(last) = body.stmts.last_mut()
+			&& let hir::StmtKind::Expr(value) = &mut last.kind
+		{
+			let value = std::mem::replace(value, self.error_expr(span));
+			last.kind = hir::StmtKind::Return(Some(value));
+		}
+		let function = &mut self.program.functions[id.0 as usize];
+		function.ret = ret.clone();
+		function.body = Some(body);
+		let lambda = hir::Expr { kind: hir::ExprKind::Lambda(id), ty: self.fn_type(id, &HashMap::new()), span };
+		(hir::ExprKind::Call { callee: Box::new(lambda), args: Vec::new() }, ret)
+	}
+
+	// ---------------------------------------------------------------------
+	// Calls
+	// ---------------------------------------------------------------------
+
+	fn call(&mut self, callee: &'a Expr, args: &'a [Expr], span: Span, expected: Option<&Type>) -> hir::Expr {
+		if let ExprKind::Super = callee.kind {
+			return self.super_call(args, span);
+		}
+		if let Some(class) = self.type_ref(callee) {
+			self.type_use(class, callee.span);
+			return self.construct(class, &[], args, span);
+		}
+
+		let callee = self.infer(callee);
+		self.call_value(callee, args, span, expected)
+	}
+
+	fn call_value(&mut self, mut callee: hir::Expr, args: &'a [Expr], span: Span, expected: Option<&Type>) -> hir::Expr {
+		if let Type::Nullable(inner) = &callee.ty
+			&& let Type::Function { .. } = **inner
+		{
+			self.null_error(&callee, "call it");
+		}
+		if let hir::ExprKind::Function(id) | hir::ExprKind::Member { target: MemberTarget::Method(id) | MemberTarget::Extension(id), .. } =
+			callee.kind
+		{
+			let candidates = self.candidates(id, &callee);
+			if candidates.len() > 1 {
+				let Some((chosen, ty)) = self.choose_overload(&candidates, args, span) else {
+					let args = args.iter().map(|arg| self.infer(arg)).collect();
+					return hir::Expr { kind: hir::ExprKind::Call { callee: Box::new(callee), args }, ty: Type::Error, span };
+				};
+				match &mut callee.kind {
+					hir::ExprKind::Member { target: target @ (MemberTarget::Method(_) | MemberTarget::Extension(_)), name, .. } => {
+						*target = match target {
+							MemberTarget::Method(_) => MemberTarget::Method(chosen),
+							_ => MemberTarget::Extension(chosen)
+						};
+						let (target, name) = (*target, name.clone());
+						self.member_access(target, &name, callee.span);
+					}
+					kind => *kind = hir::ExprKind::Function(chosen)
+				}
+				callee.ty = ty;
+			}
+		}
+		let (params, mut ret) = match callee.ty.non_null() {
+			Type::Function { params, ret } => (params.clone(), (**ret).clone()),
+			ty @ (Type::Any | Type::Error) => {
+				let ty = ty.clone();
+				let args = args.iter().map(|arg| self.infer(arg)).collect();
+				return hir::Expr { kind: hir::ExprKind::Call { callee: Box::new(callee), args }, ty, span };
+			}
+			other => {
+				self.error("E0402", format!("'{}' is not a function", other), callee.span);
+				let args = args.iter().map(|arg| self.infer(arg)).collect();
+				return hir::Expr { kind: hir::ExprKind::Call { callee: Box::new(callee), args }, ty: Type::Error, span };
+			}
+		};
+
+		let (name, function) = match &callee.kind {
+			hir::ExprKind::Function(function) => (Some(self.program.function(*function).name.clone()), Some(*function)),
+			hir::ExprKind::Member { name, target: MemberTarget::Method(function) | MemberTarget::Extension(function), .. } => {
+				(Some(name.clone()), Some(*function))
+			}
+			hir::ExprKind::Member { name, .. } => (Some(name.clone()), None),
+			hir::ExprKind::Var(Variable::Local(local)) => (Some(self.program.local(*local).name.clone()), None),
+			hir::ExprKind::Builtin(builtin) => (Some(builtin.name().to_string()), None),
+			hir::ExprKind::Native(native) => (Some(self.program.natives[*native].name.clone()), None),
+			_ => (None, None)
+		};
+		let generic = match function {
+			Some(function) => self.generic_call(function, &callee),
+			None => self.generic_builtin(&callee)
+		};
+		let args = match generic {
+			Some((mut inference, params)) => {
+				inference.expected = expected.filter(|expected| **expected != Type::Void).cloned();
+				let generic_ret = inference.ret.clone();
+				let args = self.arguments(name.as_deref(), &params, function, args, span, Some(&mut inference));
+				let subst = self.instantiate(name.as_deref(), inference, span, false);
+				let params = params.iter().map(|param| param.substitute(&subst)).collect();
+				ret = generic_ret.substitute(&subst);
+				callee.ty = Type::Function { params, ret: Box::new(ret.clone()) };
+				args
+			}
+			None => self.arguments(name.as_deref(), &params, function, args, span, None)
+		};
+		// `a?.f()` is null when `a` is
+		if let hir::ExprKind::Member { object, safe: true, .. } = &callee.kind
+			&& matches!(object.ty, Type::Nullable(_) | Type::Null)
+		{
+			ret = ret.nullable();
+		}
+		hir::Expr { kind: hir::ExprKind::Call { callee: Box::new(callee), args }, ty: ret, span }
+	}
+
+	// Every function a call to `id` through `callee` could mean, with its type
+	// as seen through `callee`: top-level functions of the same name in its
+	// package, methods of the same name in the object's class and everything
+	// it inherits, or extensions of the same name that fit the object.
+	// Methods that override a nearer one with the same parameters are left
+	// out. Local functions cannot be overloaded.
+	fn candidates(&self, id: FnId, callee: &hir::Expr) -> Vec<(FnId, Type)> {
+		let function = self.program.function(id);
+		let name = function.name.clone();
+		match (&callee.kind, function.kind) {
+			(hir::ExprKind::Function(_), FnKind::Function) => {
+				let symbols = self.graph.lookup(self.graph.package_of(function.file), &name).unwrap_or_default();
+				let functions: Vec<FnId> = symbols
+					.iter()
+					.filter_map(|symbol| match self.symbols.get(symbol) {
+						Some(Def::Function(id)) if self.program.function(*id).receiver.is_none() => Some(*id),
+						_ => None
+					})
+					.collect();
+				if !functions.contains(&id) {
+					return Vec::new();
+				}
+				functions.into_iter().map(|id| (id, self.fn_type(id, &HashMap::new()))).collect()
+			}
+			(hir::ExprKind::Function(_), FnKind::Static(class)) => {
+				let statics = &self.program.class(class).statics;
+				let methods = statics.iter().filter_map(|member| match member.target {
+					StaticTarget::Method(method) if member.name == name => Some(method),
+					_ => None
+				});
+				methods.map(|method| (method, self.fn_type(method, &HashMap::new()))).collect()
+			}
+			(hir::ExprKind::Member { object, target: MemberTarget::Method(_), .. }, _) => {
+				let Type::Class { id, args, .. } = object.ty.non_null().clone() else {
+					return Vec::new();
+				};
+				let mut queue = vec![(id, args)];
+				let mut seen = HashSet::new();
+				let mut found: Vec<(FnId, Type)> = Vec::new();
+				let mut next = 0;
+				while next < queue.len() {
+					let (id, args) = queue[next].clone();
+					next += 1;
+					if !seen.insert(id) {
+						continue;
+					}
+					let class = self.program.class(id);
+					let subst: HashMap<TypeParamId, Type> = class.type_params.iter().copied().zip(args.iter().cloned()).collect();
+					for &method in &class.methods {
+						if self.program.function(method).name != name {
+							continue;
+						}
+						let ty = self.fn_type(method, &subst);
+						let params = |ty: &Type| match ty {
+							Type::Function { params, .. } => params.clone(),
+							_ => Vec::new()
+						};
+						if !found.iter().any(|(_, nearer)| params(nearer) == params(&ty)) {
+							found.push((method, ty));
+						}
+					}
+					for supertype in &class.supertypes {
+						if let Type::Class { id, args, .. } = supertype.substitute(&subst) {
+							queue.push((id, args));
+						}
+					}
+				}
+				found
+			}
+			(hir::ExprKind::Member { object, target: MemberTarget::Extension(_), .. }, _) => {
+				let extensions = self.extensions.get(&name).cloned().unwrap_or_default();
+				extensions
+					.into_iter()
+					.filter(|id| {
+						let function = self.program.function(*id);
+						let any = function.type_params.iter().map(|param| (*param, Type::Any)).collect();
+						function.receiver.as_ref().is_some_and(|receiver| self.assignable(&object.ty, &receiver.substitute(&any)))
+					})
+					.map(|id| (id, self.fn_type(id, &HashMap::new())))
+					.collect()
+			}
+			_ => Vec::new()
+		}
+	}
+
+	// Picks the overload a call means:
+	//
+	// 1. Arguments other than lambdas are inferred once, on their own, and
+	//    kept for when the chosen function checks them.
+	// 2. A candidate applies when it takes that many arguments and each
+	//    argument fits its parameter, as it is or widened (see `coerce`). A
+	//    lambda fits a function parameter taking as many parameters as it
+	//    declares. Candidates taking every argument as it is beat those
+	//    needing a widening.
+	// 3. Of those that apply, the most specific wins: the one whose
+	//    parameter types would all be accepted by every other candidate.
+	// 4. When several are equally specific, one without a variadic
+	//    parameter beats one with, one that needs no defaults beats one that
+	//    does, and one without type parameters beats a generic one.
+	//
+	// Anything but exactly one candidate left is an error listing them.
+	fn choose_overload(&mut self, candidates: &[(FnId, Type)], args: &'a [Expr], span: Span) -> Option<(FnId, Type)> {
+		let mut arg_types = Vec::new();
+		for arg in args {
+			arg_types.push(match &arg.kind {
+				ExprKind::Lambda { params, .. } => Err(params.len()),
+				_ => {
+					let probed = self.infer(arg);
+					let ty = probed.ty.clone();
+					self.probed.insert(arg.id, probed);
+					Ok(ty)
+				}
+			});
+		}
+
+		// Parameter types lined up with the arguments, or None when the count is off
+		let lined_up = |checker: &Self, id: FnId, ty: &Type| -> Option<Vec<Type>> {
+			let Type::Function { params, .. } = ty else {
+				return None;
+			};
+			let variadic = checker.program.function(id).params.last().is_some_and(|param| param.variadic);
+			let min = checker.min_args.get(&id).copied().unwrap_or(params.len());
+			if args.len() < min || (!variadic && args.len() > params.len()) {
+				return None;
+			}
+			let params = (0..args.len()).map(|index| match params.get(index) {
+				_ if variadic && index + 1 >= params.len() => match params.last() {
+					Some(Type::Array(element)) => (**element).clone(),
+					_ => Type::Any
+				},
+				Some(param) => param.clone(),
+				None => Type::Any
+			});
+			Some(params.collect())
+		};
+		let fits = |checker: &Self, params: &[Type], widen: bool| {
+			params.iter().zip(&arg_types).all(|(param, arg)| match (arg, param.non_null()) {
+				(_, Type::Any | Type::Error) | (Ok(Type::Error), _) => true,
+				(Ok(arg), _) => checker.assignable(arg, param) || (widen && coerce::widening(arg, param).is_some()),
+				(Err(arity), Type::Function { params, .. }) => params.len() == *arity,
+				(Err(_), _) => false
+			})
+		};
+		let mut applicable: Vec<(FnId, Type, Vec<Type>)> = candidates
+			.iter()
+			.filter_map(|(id, ty)| {
+				let params = lined_up(self, *id, ty)?;
+				fits(self, &params, true).then(|| (*id, ty.clone(), params))
+			})
+			.collect();
+		if applicable.iter().any(|(_, _, params)| fits(self, params, false)) {
+			applicable.retain(|(_, _, params)| fits(self, params, false));
+		}
+
+		// `Any` accepts everything but is not narrower than anything
+		let narrower = |a: &Type, b: &Type| *b == Type::Any || (*a != Type::Any && self.assignable(a, b));
+		let specific: Vec<usize> = (0..applicable.len())
+			.filter(|&a| (0..applicable.len()).all(|b| applicable[a].2.iter().zip(&applicable[b].2).all(|(a, b)| narrower(a, b))))
+			.collect();
+		if !specific.is_empty() {
+			applicable = specific.into_iter().map(|index| applicable[index].clone()).collect();
+		}
+		let preferences: [&dyn Fn(&hir::Function) -> bool; 3] = [
+			&|function| !function.params.last().is_some_and(|param| param.variadic),
+			&|function| function.params.len() == args.len(),
+			&|function| function.type_params.is_empty()
+		];
+		for preferred in preferences {
+			if applicable.len() > 1 && applicable.iter().any(|(id, ..)| preferred(self.program.function(*id))) {
+				applicable.retain(|(id, ..)| preferred(self.program.function(*id)));
+			}
+		}
+
+		if let [(id, ty, _)] = applicable.as_slice() {
+			return Some((*id, ty.clone()));
+		}
+		if arg_types.iter().any(|arg| arg.as_ref().is_ok_and(Type::is_error)) {
+			return None;
+		}
+		let name = self.program.function(candidates[0].0).name.clone();
+		let ambiguous = !applicable.is_empty();
+		let listed: Vec<(FnId, Type)> = match ambiguous {
+			true => applicable.into_iter().map(|(id, ty, _)| (id, ty)).collect(),
+			false => candidates.to_vec()
+		};
+		let listed: Vec<String> = listed
+			.iter()
+			.map(|(id, ty)| {
+				let function = self.program.function(*id);
+				let params: Vec<String> = match ty {
+					Type::Function { params, .. } => params
+						.iter()
+						.zip(&function.params)
+						.map(|(ty, param)| match ty {
+							Type::Array(element) if param.variadic => format!("...{}", element),
+							_ => ty.to_string()
+						})
+						.collect(),
+					_ => Vec::new()
+				};
+				let params = params.join(", ");
+				format!("'{}({})' at {}", name, params, self.location(function.file, function.span))
+			})
+			.collect();
+		let listed = match listed.split_last() {
+			Some((last, [])) => last.clone(),
+			Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+			None => String::new()
+		};
+		let message = if ambiguous {
+			format!("Call to '{}' is ambiguous between {}", name, listed)
+		} else {
+			let given: Vec<String> = arg_types
+				.iter()
+				.map(|arg| match arg {
+					Ok(ty) => ty.to_string(),
+					Err(arity) => format!("a lambda taking {}", arity)
+				})
+				.collect();
+			format!("No overload of '{}' accepts ({}); the candidates are {}", name, given.join(", "), listed)
+		};
+		self.error("E0404", message, span);
+		None
+	}
+
+	// The signature of generic function `id` called through `callee`, with its
+	// own type parameters left to infer. A method gets its class's type
+	// arguments from the object, and an extension learns what it can from
+	// its receiver.
+	fn generic_call(&mut self, id: FnId, callee: &hir::Expr) -> Option<(Inference, Vec<Type>)> {
+		let function = self.program.function(id);
+		if function.type_params.is_empty() {
+			return None;
+		}
+		let mut inference = Inference::new(function.type_params.clone(), function.ret.clone());
+		let mut params: Vec<Type> = function.params.iter().map(|param| param.ty.clone()).collect();
+		let hir::ExprKind::Member { object, .. } = &callee.kind else {
+			return Some((inference, params));
+		};
+		match function.kind {
+			FnKind::Method(owner) => {
+				if let Some(Type::Class { args, .. }) = self.upcast(object.ty.non_null(), owner) {
+					let subst = self.program.class(owner).type_params.iter().copied().zip(args).collect();
+					params = params.iter().map(|param| param.substitute(&subst)).collect();
+					inference.ret = inference.ret.substitute(&subst);
+				}
+			}
+			FnKind::Extension => {
+				if let Some(receiver) = &function.receiver {
+					self.unify(receiver, object.ty.non_null(), &mut inference);
+				}
+			}
+			_ => {}
+		}
+		Some((inference, params))
+	}
+
+	// The type parameter of what built-in methods like `map` make.
+	fn result_param(&mut self) -> TypeParamId {
+		*self.result.get_or_insert_with(|| {
+			let id = TypeParamId(self.program.type_params.len() as u32);
+			self.program.type_params.push(hir::TypeParamDef { name: "R".to_string(), bounds: Vec::new(), span: Span::default() });
+			id
+		})
+	}
+
+	// The signature of a built-in method generic in what it makes, like
+	// `map` or `choice`, or of `Ok`, `Err` and `Some`, with that left to infer.
+	fn generic_builtin(&mut self, callee: &hir::Expr) -> Option<(Inference, Vec<Type>)> {
+		// Results, options and generators are not sequences
+		let other = |ty: &Type| matches!(ty, Type::Result(..) | Type::Option(_) | Type::Random);
+		// Of a sequence, its elements
+		let element = match &callee.kind {
+			hir::ExprKind::Builtin(builtin) if builtin.wraps(&Type::Any).is_some() => None,
+			hir::ExprKind::Member { object, target: MemberTarget::Builtin, .. } if other(object.ty.non_null()) => None,
+			hir::ExprKind::Member { object, target: MemberTarget::Builtin, .. } => Some(self.elements(object.ty.non_null())?),
+			_ => return None
+		};
+		let var = self.result_param();
+		let result = Type::Param { id: var, name: "R".to_string() };
+		let ty = match (&callee.kind, element) {
+			(hir::ExprKind::Builtin(builtin), _) => builtin.wraps(&result),
+			(hir::ExprKind::Member { name, .. }, Some(element)) => builtins::sequence_member(&element, name, &result),
+			(hir::ExprKind::Member { object, name, .. }, None) if *object.ty.non_null() == Type::Random => builtins::random_member(name, &result),
+			(hir::ExprKind::Member { object, name, .. }, None) => builtins::wrapper_member(object.ty.non_null(), name, &result),
+			_ => None
+		};
+		let Some(Type::Function { params, ret }) = ty else {
+			return None;
+		};
+		mentions(&ret, &[var]).then(|| (Inference::new(vec![var], *ret), params))
+	}
+
+	// Checks arguments against parameter types. Trailing parameters with
+	// defaults may be left out and a variadic parameter takes the rest.
+	// With `inference`, parameter types may mention type parameters still to
+	// be inferred from the arguments.
+	fn arguments(
+		&mut self,
+		name: Option<&str>,
+		params: &[Type],
+		function: Option<FnId>,
+		args: &'a [Expr],
+		span: Span,
+		inference: Option<&mut Inference>
+	) -> Vec<hir::Expr> {
+		let variadic = function.is_some_and(|id| self.program.function(id).params.last().is_some_and(|param| param.variadic));
+		let min = function.and_then(|id| self.min_args.get(&id).copied()).unwrap_or(params.len());
+		let max = if variadic { None } else { Some(params.len()) };
+
+		if args.len() < min || max.is_some_and(|max| args.len() > max) {
+			let expected = match max {
+				None => format!("at least {}", min),
+				Some(max) if max == min => min.to_string(),
+				Some(max) => format!("{} to {}", min, max)
+			};
+			let subject = name.map_or_else(|| "This function".to_string(), |name| format!("'{}'", name));
+			let plural = if max == Some(1) && min == 1 { "" } else { "s" };
+			let were = if args.len() == 1 { "was" } else { "were" };
+			self.error("E0403", format!("{} expects {} argument{} but {} {} given", subject, expected, plural, args.len(), were), span);
+		}
+
+		let expected = (0..args.len()).map(|index| {
+			if variadic && index + 1 >= params.len() {
+				match params.last() {
+					Some(Type::Array(element)) => Some((**element).clone()),
+					_ => None
+				}
+			} else {
+				params.get(index).cloned()
+			}
+		});
+		match inference {
+			Some(inference) => self.generic_args(expected.collect(), args, inference),
+			None => args
+				.iter()
+				.zip(expected)
+				.map(|(arg, expected)| match expected {
+					Some(expected) => self.check(arg, &expected),
+					None => self.infer(arg)
+				})
+				.collect()
+		}
+	}
+
+	// Arguments whose parameter type mentions a type parameter being
+	// inferred are inferred on their own first and say what it is; the
+	// others are checked as usual. Lambdas come last, so their parameters
+	// can take types the other arguments decided, and the context fills in
+	// what the arguments leave open. Once everything is known the inferred
+	// arguments are checked against the parameter types they ended up with.
+	fn generic_args(&mut self, expected: Vec<Option<Type>>, args: &'a [Expr], inference: &mut Inference) -> Vec<hir::Expr> {
+		let mut checked: Vec<Option<hir::Expr>> = vec![None; args.len()];
+		let mut lambdas = Vec::new();
+		for (index, arg) in args.iter().enumerate() {
+			let Some(expected) = expected[index].as_ref().filter(|expected| mentions(expected, &inference.vars)) else {
+				checked[index] = Some(match &expected[index] {
+					Some(expected) => self.check(arg, expected),
+					None => self.infer(arg)
+				});
+				continue;
+			};
+			if let ExprKind::Lambda { .. } = arg.kind
+				&& let Type::Function { .. } = expected.non_null()
+			{
+				lambdas.push(index);
+				continue;
+			}
+			let found = self.infer(arg);
+			self.unify(expected, &found.ty, inference);
+			checked[index] = Some(found);
+		}
+
+		if let Some(wanted) = inference.expected.clone() {
+			let mut from_context = Inference::new(inference.vars.clone(), Type::Void);
+			self.unify(&inference.ret, &wanted, &mut from_context);
+			for (var, ty) in from_context.solved {
+				inference.solved.entry(var).or_insert(ty);
+			}
+		}
+
+		for index in lambdas {
+			let ExprKind::Lambda { params, body } = &args[index].kind else {
+				continue;
+			};
+			let unsolved: Vec<TypeParamId> = inference.vars.iter().filter(|var| !inference.solved.contains_key(var)).copied().collect();
+			let any = unsolved.iter().map(|var| (*var, Type::Any)).collect();
+			let wanted = match expected[index].as_ref().map(|expected| expected.non_null().substitute(&inference.solved)) {
+				Some(Type::Function { params, ret }) => Type::Function { params: params.iter().map(|param| param.substitute(&any)).collect(), ret },
+				_ => Type::Any
+			};
+			let saved = std::mem::replace(&mut self.unsolved, unsolved);
+			let (kind, ty) = self.lambda(params, body, args[index].span, Some(&wanted));
+			self.unsolved = saved;
+			if let Some(expected) = &expected[index] {
+				self.unify(expected.non_null(), &ty, inference);
+			}
+			checked[index] = Some(hir::Expr { kind, ty, span: args[index].span });
+		}
+
+		// Unknown parameters are reported once the call is instantiated;
+		// arguments disagreeing on one are reported there too
+		let mut settled = inference.solved.clone();
+		for var in &inference.vars {
+			settled.entry(*var).or_insert(Type::Any);
+		}
+		for (var, ..) in &inference.conflicts {
+			settled.insert(*var, Type::Error);
+		}
+		for (index, arg) in args.iter().enumerate() {
+			if let (Some(found), Some(expected)) = (&checked[index], &expected[index])
+				&& mentions(expected, &inference.vars)
+			{
+				self.expect(&found.ty, &expected.substitute(&settled), arg.span);
+			}
+		}
+		checked.into_iter().flatten().collect()
+	}
+
+	// Matches a parameter type against the type of what is passed for it,
+	// learning what the type parameters being inferred stand for. Two
+	// arguments saying different things about one parameter settle on the
+	// type both fit in, or conflict when there is none.
+	fn unify(&self, param: &Type, found: &Type, inference: &mut Inference) {
+		match (param, found) {
+			(_, Type::Error | Type::Never) => {}
+			(Type::Param { id, .. }, _) if inference.vars.contains(id) => {
+				let ty = match inference.solved.get(id) {
+					None => found.clone(),
+					Some(known) => {
+						let joined = self.join(known, found);
+						if joined == Type::Any && !known.is_dynamic() && !found.is_dynamic() {
+							inference.conflicts.push((*id, known.clone(), found.clone()));
+							return;
+						}
+						joined
+					}
+				};
+				inference.solved.insert(*id, ty);
+			}
+			(Type::Nullable(_), Type::Null) => {}
+			(Type::Nullable(param), Type::Nullable(found)) => self.unify(param, found, inference),
+			(Type::Nullable(param), _) => self.unify(param, found, inference),
+			(Type::Array(param), Type::Array(found)) | (Type::Set(param), Type::Set(found)) | (Type::Iterator(param), Type::Iterator(found)) => {
+				self.unify(param, found, inference)
+			}
+			(Type::Map(key, value), Type::Map(found_key, found_value)) | (Type::Result(key, value), Type::Result(found_key, found_value)) => {
+				self.unify(key, found_key, inference);
+				self.unify(value, found_value, inference);
+			}
+			(Type::Option(param), Type::Option(found)) | (Type::Channel(param), Type::Channel(found)) => self.unify(param, found, inference),
+			(Type::Function { params, ret }, Type::Function { params: found_params, ret: found_ret }) if params.len() == found_params.len() => {
+				for (param, found) in params.iter().zip(found_params) {
+					self.unify(param, found, inference);
+				}
+				self.unify(ret, found_ret, inference);
+			}
+			(Type::Class { id, args, .. }, Type::Class { .. }) => {
+				if let Some(Type::Class { args: found_args, .. }) = self.upcast(found, *id) {
+					for (arg, found) in args.iter().zip(&found_args) {
+						self.unify(arg, found, inference);
+					}
+				}
+			}
+			_ => {}
+		}
+	}
+
+	// Settles the type arguments of a generic call. Those nothing decided
+	// are reported, or are `Any` with `fallback`, and each is checked
+	// against its parameter's bounds.
+	fn instantiate(&mut self, name: Option<&str>, inference: Inference, span: Span, fallback: bool) -> HashMap<TypeParamId, Type> {
+		let subject = name.map_or_else(|| "this call".to_string(), |name| format!("'{}'", name));
+		let mut subst = inference.solved;
+		for (var, first, second) in inference.conflicts {
+			if subst.get(&var) == Some(&Type::Error) {
+				continue;
+			}
+			let param = self.program.type_param(var).name.clone();
+			self.error("E0405", format!("Cannot infer '{}' for {}: the arguments make it both '{}' and '{}'", param, subject, first, second), span);
+			subst.insert(var, Type::Error);
+		}
+		for var in &inference.vars {
+			if subst.contains_key(var) {
+				continue;
+			}
+			if !fallback {
+				let param = &self.program.type_param(*var).name;
+				self.error("E0405", format!("Cannot infer '{}' for {}; give the result a declared type", param, subject), span);
+			}
+			subst.insert(*var, if fallback { Type::Any } else { Type::Error });
+		}
+		for var in &inference.vars {
+			let ty = &subst[var];
+			if ty.is_dynamic() {
+				continue;
+			}
+			let param = self.program.type_param(*var);
+			let unmet: Vec<String> = param
+				.bounds
+				.iter()
+				.map(|bound| bound.substitute(&subst))
+				.filter(|bound| !self.assignable(ty, bound))
+				.map(|bound| format!("Cannot use '{}' for '{}' in {}; it has to be a '{}'", ty, param.name, subject, bound))
+				.collect();
+			for message in unmet {
+				self.error("E0406", message, span);
+			}
+		}
+		subst
+	}
+
+	// `new C(...)`, `C(...)`, and enum variant arguments.
+	fn construct(&mut self, id: TypeId, type_args: &[Type], args: &'a [Expr], span: Span) -> hir::Expr {
+		let class = self.program.class(id);
+		let problem = match class.kind {
+			ClassKind::Interface => Some(format!("Cannot create an instance of interface '{}'", class.name)),
+			ClassKind::Enum => Some(format!("Cannot create an instance of enum '{}'; use one of its variants", class.name)),
+			ClassKind::Class if class.modifiers.is_sealed => {
+				Some(format!("Cannot create an instance of sealed class '{}'; create one of its subclasses instead", class.name))
+			}
+			ClassKind::Class if class.modifiers.is_abstract => Some(format!("Cannot create an instance of abstract class '{}'", class.name)),
+			_ => None
+		};
+		if let Some(problem) = problem {
+			self.error("E0309", problem, span);
+			let args = args.iter().map(|arg| self.infer(arg)).collect();
+			return hir::Expr { kind: hir::ExprKind::New { class: id, constructor: None, args }, ty: Type::Error, span };
+		}
+
+		let (ty, args, constructor) = self.constructor_args(id, type_args, args, span);
+		hir::Expr { kind: hir::ExprKind::New { class: id, constructor, args }, ty, span }
+	}
+
+	// Picks the constructor whose parameter count fits and checks the
+	// arguments against it. Type arguments that are not given are inferred
+	// from the arguments, and are `Any` when nothing says what they are.
+	fn constructor_args(&mut self, id: TypeId, type_args: &[Type], args: &'a [Expr], span: Span) -> (Type, Vec<hir::Expr>, Option<FnId>) {
+		let class = self.program.class(id);
+		let mut inference = type_args.is_empty().then(|| Inference::new(class.type_params.clone(), Type::Void));
+		let subst: HashMap<TypeParamId, Type> = class.type_params.iter().copied().zip(type_args.iter().cloned()).collect();
+		let name = class.name.clone();
+
+		if class.kind == ClassKind::Struct && class.constructors.is_empty() {
+			let params: Vec<Type> = class.fields.iter().map(|field| field.ty.substitute(&subst)).collect();
+			let min = self.min_fields.get(&id).copied().unwrap_or(params.len());
+			if args.len() < min || args.len() > params.len() {
+				let expected = if min == params.len() { min.to_string() } else { format!("{} to {}", min, params.len()) };
+				self.error("E0403", format!("'{}' expects {} field value(s) but {} were given", name, expected, args.len()), span);
+			}
+			let args = match &mut inference {
+				Some(inference) => self.generic_args((0..args.len()).map(|index| params.get(index).cloned()).collect(), args, inference),
+				None => args
+					.iter()
+					.enumerate()
+					.map(|(index, arg)| match params.get(index) {
+						Some(param) => self.check(arg, param),
+						None => self.infer(arg)
+					})
+					.collect()
+			};
+			return (self.instance(id, type_args, inference, span), args, None);
+		}
+
+		let constructors = class.constructors.clone();
+		let fits = |checker: &Self, ctor: &FnId| {
+			let function = checker.program.function(*ctor);
+			let min = checker.min_args.get(ctor).copied().unwrap_or(0);
+			let variadic = function.params.last().is_some_and(|param| param.variadic);
+			args.len() >= min && (variadic || args.len() <= function.params.len())
+		};
+		let Some(constructor) = constructors.iter().find(|ctor| fits(self, ctor)).or(constructors.first()).copied() else {
+			if !args.is_empty() {
+				let were = if args.len() == 1 { "was" } else { "were" };
+				self.error("E0403", format!("'{}' has no constructor; it takes no arguments but {} {} given", name, args.len(), were), span);
+			}
+			let args = args.iter().map(|arg| self.infer(arg)).collect();
+			return (self.instance(id, type_args, inference, span), args, None);
+		};
+
+		let function = self.program.function(constructor);
+		let (visibility, declared) = (function.modifiers.visibility, function.span);
+		self.check_access(id, &format!("the constructor of '{}'", name), visibility, declared, span);
+		let params: Vec<Type> = self.program.function(constructor).params.iter().map(|param| param.ty.substitute(&subst)).collect();
+		let args = self.arguments(Some(&name), &params, Some(constructor), args, span, inference.as_mut());
+		(self.instance(id, type_args, inference, span), args, Some(constructor))
+	}
+
+	// The class type a constructor call makes, with the type arguments given
+	// or inferred.
+	fn instance(&mut self, id: TypeId, type_args: &[Type], inference: Option<Inference>, span: Span) -> Type {
+		let name = self.program.class(id).name.clone();
+		let args = match inference {
+			Some(inference) => {
+				let vars = inference.vars.clone();
+				let subst = self.instantiate(Some(&name), inference, span, true);
+				vars.iter().map(|var| subst[var].clone()).collect()
+			}
+			None => type_args.to_vec()
+		};
+		Type::Class { id, name, args }
+	}
+
+	fn super_call(&mut self, args: &'a [Expr], span: Span) -> hir::Expr {
+		let void = |kind| hir::Expr { kind, ty: Type::Void, span };
+		if !self.ctx.constructor {
+			self.error("E0502", "'super(...)' can only be called from a constructor".to_string(), span);
+			let args = args.iter().map(|arg| self.infer(arg)).collect();
+			return void(hir::ExprKind::SuperCall { constructor: None, args });
+		}
+		let superclass = self.superclass(span);
+		let Type::Class { id, args: type_args, .. } = superclass else {
+			let args = args.iter().map(|arg| self.infer(arg)).collect();
+			return void(hir::ExprKind::SuperCall { constructor: None, args });
+		};
+		let (_, args, constructor) = self.constructor_args(id, &type_args, args, span);
+		void(hir::ExprKind::SuperCall { constructor, args })
+	}
+
+	// ---------------------------------------------------------------------
+	// Types
+	// ---------------------------------------------------------------------
+
+	fn opt_type(&mut self, ty: &Option<TypeExpr>) -> Type {
+		match ty {
+			Some(ty) => self.lower_type(ty),
+			None => Type::Any
+		}
+	}
+
+	fn lower_type(&mut self, ty: &TypeExpr) -> Type {
+		match &ty.kind {
+			TypeExprKind::Named { name, args } => {
+				let args: Vec<Type> = args.iter().map(|arg| self.lower_type(arg)).collect();
+				let def = match self.resolutions[self.file].types.get(&ty.span) {
+					Some(Resolution::Local(node) | Resolution::TypeParam(node)) => self.def(*node),
+					Some(Resolution::Symbol(symbol)) => self.symbols.get(symbol).copied(),
+					_ => None
+				};
+				let Some(def) = def else {
+					if let Some(expected) = builtins::type_params(name) {
+						if !args.is_empty() && args.len() != expected {
+							self.error("E0407", format!("'{}' expects {} type argument(s) but {} were given", name, expected, args.len()), ty.span);
+							return Type::Error;
+						}
+						return builtins::generic_type(name, args);
+					}
+					if let Some(builtin) = builtins::type_named(name) {
+						if !args.is_empty() {
+							self.error("E0407", format!("'{}' takes no type arguments", name), ty.span);
+						}
+						return builtin;
+					}
+					// Qualified names that do not exist were reported by the resolver
+					if !name.contains('.') {
+						let message = format!("Unknown type '{}'", name);
+						let candidates = self.type_names();
+						self.not_found("E0201", message, name, ty.span, candidates.iter().map(String::as_str), true);
+					}
+					return Type::Error;
+				};
+				if let Def::Class(id) = def {
+					self.type_use(id, ty.span);
+				}
+				self.named_type(def, name, args, ty.span)
+			}
+			TypeExprKind::Nullable(inner) => self.lower_type(inner).nullable(),
+			TypeExprKind::Array(inner) => Type::Array(Box::new(self.lower_type(inner))),
+			TypeExprKind::Function { params, ret } => Type::Function {
+				params: params.iter().map(|param| self.lower_type(param)).collect(),
+				ret: Box::new(self.lower_type(ret))
+			},
+			TypeExprKind::Union(members) => {
+				let members = members.iter().map(|member| self.lower_type(member)).collect();
+				self.union_type(members, ty.span)
+			}
+		}
+	}
+
+	fn type_use(&mut self, class: TypeId, span: Span) {
+		self.program.type_uses.push(hir::TypeUse { class, file: self.file, span });
+	}
+
+	// `A | B` as written. Members that fit in another member add nothing and
+	// are dropped, so `Circle | Shape` is just `Shape`.
+	fn union_type(&mut self, members: Vec<Type>, span: Span) -> Type {
+		if members.contains(&Type::Void) {
+			self.error("E0410", "'Void' cannot be part of a union".to_string(), span);
+			return Type::Error;
+		}
+		let ty = Type::union(members);
+		let Type::Union(members) = ty.non_null() else {
+			return ty;
+		};
+		let redundant = |index: usize| {
+			members.iter().enumerate().any(|(other, wider)| {
+				other != index && self.assignable(&members[index], wider) && (other < index || !self.assignable(wider, &members[index]))
+			})
+		};
+		let kept = (0..members.len()).filter(|&index| !redundant(index)).map(|index| members[index].clone()).collect();
+		let kept = Type::union(kept);
+		if let Type::Nullable(_) = ty { kept.nullable() } else { kept }
+	}
+
+	fn named_type(&mut self, def: Def<'a>, name: &str, args: Vec<Type>, span: Span) -> Type {
+		match def {
+			Def::Class(id) => {
+				let class = self.program.class(id);
+				let expected = class.type_params.len();
+				let class_name = class.name.clone();
+				// A generic class without arguments is instantiated with `Any`
+				if args.is_empty() {
+					return Type::Class { id, name: class_name, args: vec![Type::Any; expected] };
+				}
+				if args.len() != expected {
+					self.error("E0407", format!("'{}' expects {} type argument(s) but {} were given", class_name, expected, args.len()), span);
+					return Type::Error;
+				}
+				Type::Class { id, name: class_name, args }
+			}
+			Def::TypeParam(id) => {
+				if !args.is_empty() {
+					self.error("E0407", format!("Type parameter '{}' takes no type arguments", name), span);
+				}
+				Type::Param { id, name: self.program.type_param(id).name.clone() }
+			}
+			Def::Alias(file, decl) => self.alias(file, decl, args, span),
+			_ => {
+				self.error("E0201", format!("'{}' is not a type", name), span);
+				Type::Error
+			}
+		}
+	}
+
+	fn alias(&mut self, file: usize, decl: &'a TypeAliasDecl, args: Vec<Type>, span: Span) -> Type {
+		let key = (file, decl.id);
+		let body = match self.aliases.get(&key) {
+			Some(body) => body.clone(),
+			None => {
+				if self.expanding.contains(&key) {
+					self.error("E0408", format!("Type alias '{}' is recursive", decl.name), decl.name_span);
+					return Type::Error;
+				}
+				self.expanding.push(key);
+				let saved = std::mem::replace(&mut self.file, file);
+				let body = self.lower_type(&decl.ty);
+				self.file = saved;
+				self.expanding.pop();
+				self.aliases.insert(key, body.clone());
+				body
+			}
+		};
+
+		let params: Vec<TypeParamId> = decl
+			.type_params
+			.iter()
+			.filter_map(|param| match self.nodes[file].get(&param.id) {
+				Some(Def::TypeParam(id)) => Some(*id),
+				_ => None
+			})
+			.collect();
+		if args.len() != params.len() {
+			self.error(
+				"E0407",
+				format!("Type alias '{}' expects {} type argument(s) but {} were given", decl.name, params.len(), args.len()),
+				span
+			);
+			return Type::Error;
+		}
+		body.substitute(&params.into_iter().zip(args).collect())
+	}
+
+	fn type_names(&self) -> Vec<String> {
+		let mut names: Vec<String> = builtins::TYPE_NAMES.iter().map(|name| name.to_string()).collect();
+		names.extend(self.program.classes.iter().map(|class| class.name.clone()));
+		names
+	}
+
+	// Whether a value of type `from` can be used where `to` is expected.
+	fn assignable(&self, from: &Type, to: &Type) -> bool {
+		if from == to {
+			return true;
+		}
+		match (from, to) {
+			(Type::Any | Type::Error | Type::Never, _) | (_, Type::Any | Type::Error) => true,
+			(Type::Null, Type::Nullable(_)) => true,
+			(Type::Nullable(from), Type::Nullable(to)) => self.assignable(from, to),
+			(_, Type::Nullable(to)) => self.assignable(from, to),
+			(Type::Union(members), _) => members.iter().all(|member| self.assignable(member, to)),
+			(_, Type::Union(members)) => members.iter().any(|member| self.assignable(from, member)),
+			// Channels are received from as well as sent to
+			(Type::Array(from), Type::Array(to)) | (Type::Set(from), Type::Set(to)) | (Type::Channel(from), Type::Channel(to)) => self.same(from, to),
+			// Iterators, results and options are only read from
+			(Type::Iterator(from), Type::Iterator(to)) | (Type::Option(from), Type::Option(to)) => self.assignable(from, to),
+			(Type::Result(from_value, from_error), Type::Result(to_value, to_error)) => {
+				self.assignable(from_value, to_value) && self.assignable(from_error, to_error)
+			}
+			(Type::Map(from_key, from_value), Type::Map(to_key, to_value)) => self.same(from_key, to_key) && self.same(from_value, to_value),
+			(Type::Function { params: from_params, ret: from_ret }, Type::Function { params: to_params, ret: to_ret }) => {
+				from_params.len() == to_params.len()
+					&& from_params.iter().zip(to_params).all(|(from, to)| self.assignable(to, from))
+					&& (**to_ret == Type::Void || self.assignable(from_ret, to_ret))
+			}
+			(Type::Class { .. }, Type::Class { id, args, .. }) => match self.upcast(from, *id) {
+				Some(Type::Class { args: found, .. }) => found.iter().zip(args).all(|(found, arg)| self.same(found, arg)),
+				_ => false
+			},
+			(Type::Param { id, .. }, _) => self.program.type_param(*id).bounds.iter().any(|bound| self.assignable(bound, to)),
+			_ => false
+		}
+	}
+
+	// Type arguments are invariant: `Box<Int>` is not a `Box<Any>`, but a
+	// `Box<Any>` fits anywhere a `Box` of something is expected.
+	fn same(&self, a: &Type, b: &Type) -> bool {
+		self.assignable(a, b) && self.assignable(b, a)
+	}
+
+	// `ty` seen as its ancestor `target`, with the ancestor's type arguments.
+	fn upcast(&self, ty: &Type, target: TypeId) -> Option<Type> {
+		let mut queue = vec![ty.clone()];
+		let mut seen = HashSet::new();
+		let mut next = 0;
+		while next < queue.len() {
+			let current = queue[next].clone();
+			next += 1;
+			let Type::Class { id, args, .. } = &current else {
+				continue;
+			};
+			if *id == target {
+				return Some(current);
+			}
+			if !seen.insert(*id) {
+				continue;
+			}
+			let class = self.program.class(*id);
+			let subst = class.type_params.iter().copied().zip(args.iter().cloned()).collect();
+			queue.extend(class.supertypes.iter().map(|supertype| supertype.substitute(&subst)));
+		}
+		None
+	}
+
+	// The type both branches of an `if` fit in.
+	fn join(&self, a: &Type, b: &Type) -> Type {
+		match (a, b) {
+			(Type::Error, _) | (_, Type::Error) => Type::Error,
+			(Type::Any, _) | (_, Type::Any) => Type::Any,
+			(Type::Never, other) | (other, Type::Never) => other.clone(),
+			(Type::Null, other) | (other, Type::Null) => other.clone().nullable(),
+			_ if self.assignable(a, b) => b.clone(),
+			// `Ok(1)` and `Err("no")` make a `Result<Int, String>`
+			(Type::Result(a_value, a_error), Type::Result(b_value, b_error)) => {
+				Type::Result(Box::new(self.join(a_value, b_value)), Box::new(self.join(a_error, b_error)))
+			}
+			(Type::Option(a), Type::Option(b)) => Type::Option(Box::new(self.join(a, b))),
+			_ if self.assignable(b, a) => a.clone(),
+			(Type::Nullable(inner), other) | (other, Type::Nullable(inner)) => self.join(inner, other.non_null()).nullable(),
+			_ => Type::Any
+		}
+	}
+
+	// Errors have a code from `diagnostics::codes` naming the kind of problem.
+	fn error(&mut self, code: &'static str, message: String, span: Span) {
+		self.errors.push(Diagnostic::error(message, span).with_code(code).in_file(self.file));
+	}
+
+	// An error about `name`, suggesting the candidate closest to it.
+	fn misspelled<'b>(&mut self, code: &'static str, message: String, name: &str, span: Span, candidates: impl IntoIterator<Item = &'b str>) {
+		let error = Diagnostic::error(message, span).with_code(code).in_file(self.file);
+		self.errors.push(suggest::did_you_mean(error, name, span, candidates));
+	}
+
+	// Like `misspelled`, and offers to import `name` when exactly one other
+	// package declares a type, or for `types` false anything, by that name.
+	fn not_found<'b>(&mut self, code: &'static str, message: String, name: &str, span: Span, candidates: impl IntoIterator<Item = &'b str>, types: bool) {
+		let here = self.graph.package_of(self.file);
+		let mut packages = self.graph.packages.iter().filter(|(package, contents)| {
+			let ids = contents.symbols.get(name).map(Vec::as_slice).unwrap_or_default();
+			let visible = ids.iter().map(|id| self.graph.symbol(*id)).any(|symbol| {
+				symbol.visibility != Visibility::Private && (!types || symbol.kind.is_type())
+			});
+			!package.is_empty() && package.as_str() != here && visible
+		});
+		let error = Diagnostic::error(message, span).with_code(code).in_file(self.file);
+		let error = match (packages.next(), packages.next()) {
+			(Some((package, _)), None) => {
+				let path = format!("{}.{}", package, name);
+				let (at, text) = self.import_edit(&path);
+				error.with_fix(format!("import '{}'", path), at, text)
+			}
+			_ => error
+		};
+		self.errors.push(suggest::did_you_mean(error, name, span, candidates));
+	}
+
+	// Where and what to insert to import `path` into the current file: after
+	// its last import, else after its `package` line, else at the top.
+	fn import_edit(&self, path: &str) -> (Span, String) {
+		let stmts = &self.files[self.file].program.stmts;
+		let last = |wanted: fn(&StmtKind) -> bool| stmts.iter().rev().find(|stmt| wanted(&stmt.kind)).map(|stmt| stmt.span);
+		let after = |span: Span| Span { start: span.end, end: span.end, line: span.line, column: span.column + (span.end - span.start) as i64 };
+		if let Some(import) = last(|kind| matches!(kind, StmtKind::Import(_))) {
+			(after(import), format!("\nimport {}", path))
+		} else if let Some(package) = last(|kind| matches!(kind, StmtKind::Package(_))) {
+			(after(package), format!("\n\nimport {}", path))
+		} else {
+			(Span { start: 0, end: 0, line: 1, column: 1 }, format!("import {}\n\n", path))
+		}
+	}
+}
+
+// The type a declaration gets from its initializer. A lone `null` says
+// nothing about what will be stored later.
+fn inferred(ty: &Type) -> Type {
+	match ty {
+		Type::Null | Type::Never => Type::Any,
+		ty => ty.clone()
+	}
+}
+
+const COMPARE_METHOD: &str = "compareTo";
+
+// Whether `ty` refers to any of `params`.
+fn mentions(ty: &Type, params: &[TypeParamId]) -> bool {
+	match ty {
+		Type::Param { id, .. } => params.contains(id),
+		Type::Nullable(inner) | Type::Array(inner) | Type::Set(inner) | Type::Iterator(inner) | Type::Option(inner) | Type::Channel(inner) => {
+			mentions(inner, params)
+		}
+		Type::Map(key, value) | Type::Result(key, value) => mentions(key, params) || mentions(value, params),
+		Type::Function { params: inputs, ret } => mentions(ret, params) || inputs.iter().any(|input| mentions(input, params)),
+		Type::Class { args, .. } | Type::Union(args) => args.iter().any(|arg| mentions(arg, params)),
+		_ => false
+	}
+}
+
+// Identifiers assigned to, by node id.
+#[derive(Default)]
+struct AssignedNames {
+	names: Vec<NodeId>
+}
+
+impl Visitor for AssignedNames {
+	fn visit_expr(&mut self, expr: &Expr) {
+		let target = match &expr.kind {
+			ExprKind::Assign { target, .. } => Some(target),
+			ExprKind::Unary { op: UnaryOp::PreIncrement | UnaryOp::PreDecrement, operand }
+			| ExprKind::Postfix { op: PostfixOp::Increment | PostfixOp::Decrement, operand } => Some(operand),
+			_ => None
+		};
+		if let Some(target) = target
+			&& let ExprKind::Identifier(_) = target.kind
+		{
+			self.names.push(target.id);
+		}
+		visitor::walk_expr(self, expr);
+	}
+}
+
+// Where `block` yields, if it does, outside the functions and classes in it.
+fn yields(block: &Block) -> Option<Span> {
+	let mut yields = Yields::default();
+	yields.visit_block(block);
+	yields.found
+}
+
+#[derive(Default)]
+struct Yields {
+	found: Option<Span>
+}
+
+impl Visitor for Yields {
+	fn visit_stmt(&mut self, stmt: &Stmt) {
+		match &stmt.kind {
+			StmtKind::Yield(_) if self.found.is_none() => self.found = Some(stmt.span),
+			StmtKind::Class(_) | StmtKind::Interface(_) | StmtKind::Enum(_) | StmtKind::Struct(_) => return,
+			_ => {}
+		}
+		visitor::walk_stmt(self, stmt);
+	}
+
+	fn visit_expr(&mut self, expr: &Expr) {
+		if !matches!(expr.kind, ExprKind::Lambda { .. } | ExprKind::Spawn(_)) {
+			visitor::walk_expr(self, expr);
+		}
+	}
+
+	fn visit_fn(&mut self, _: &FnDecl) {}
+}
+
+// Whether running `stmts` never gets to the code after them.
+fn always_exits(stmts: &[Stmt]) -> bool {
+	stmts.last().is_some_and(|stmt| match &stmt.kind {
+		StmtKind::Return(_) | StmtKind::Throw(_) | StmtKind::Break(_) | StmtKind::Continue(_) => true,
+		StmtKind::Expr(expr) => matches!(expr.kind, ExprKind::Return(_) | ExprKind::Throw(_)),
+		StmtKind::Block(block) => always_exits(&block.stmts),
+		StmtKind::If { then_branch, else_branch: Some(else_branch), .. } => {
+			always_exits(&then_branch.stmts) && always_exits(std::slice::from_ref(else_branch))
+		}
+		_ => false
+	})
+}
+
+// Whether a `break` in `stmts` leaves the loop they are the body of, which
+// is labeled `label`. `nested` once inside another loop, where only a
+// `break label` still does.
+fn breaks_out(stmts: &[Stmt], label: Option<&String>, nested: bool) -> bool {
+	let any = |stmts: &[Stmt]| breaks_out(stmts, label, nested);
+	stmts.iter().any(|stmt| match &stmt.kind {
+		StmtKind::Break(None) => !nested,
+		StmtKind::Break(Some(target)) => label == Some(target),
+		StmtKind::Block(block) => any(&block.stmts),
+		StmtKind::If { then_branch, else_branch, .. } => {
+			any(&then_branch.stmts) || else_branch.as_deref().is_some_and(|stmt| any(std::slice::from_ref(stmt)))
+		}
+		StmtKind::Try { body, catches, finally } => {
+			any(&body.stmts)
+				|| catches.iter().any(|catch| any(&catch.body.stmts))
+				|| finally.as_ref().is_some_and(|block| any(&block.stmts))
+		}
+		StmtKind::Switch { cases, default, .. } => {
+			cases.iter().any(|case| any(&case.body.stmts)) || default.as_ref().is_some_and(|block| any(&block.stmts))
+		}
+		StmtKind::While { body, .. } | StmtKind::Loop { body } => label.is_some() && breaks_out(&body.stmts, label, true),
+		StmtKind::For(for_loop) => label.is_some() && breaks_out(&for_loop.body.stmts, label, true),
+		StmtKind::Labeled { body, .. } => any(std::slice::from_ref(body)),
+		_ => false
+	})
+}
+
+fn declared_id(kind: &StmtKind) -> Option<NodeId> {
+	let id = match kind {
+		StmtKind::Function(decl) => decl.id,
+		StmtKind::Var(decl) => decl.id,
+		StmtKind::TypeAlias(decl) => decl.id,
+		kind => ClassView::of(kind)?.id
+	};
+	Some(id)
+}
+
+fn hashed(text: &str) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	text.hash(&mut hasher);
+	hasher.finish()
+}
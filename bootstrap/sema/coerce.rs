@@ -0,0 +1,76 @@
+use crate::ast::expr::BinaryOp;
+use crate::sema::hir::Coercion;
+use crate::sema::types::Type;
+
+// The conversions the language makes without being asked, kept in one place
+// so the checker and code generation agree on them. Each one the checker
+// applies becomes a `Coerce` node, except where nothing changes at run time.
+//
+// - Numeric widening: an `Int` is accepted where a `Float` is expected, and
+//   operators mixing the two work on Floats.
+// - Nullable widening: a `T` is accepted where a `T?` is expected. Values
+//   look the same either way, so no node is needed.
+// - String conversion: `+` with a string on one side, and interpolation,
+//   turn the other side into a string.
+// - Dynamic values: an `Any` used where a static type is expected is checked
+//   at run time.
+//
+// Conversions that can lose information, like `Float` to `Int`, are never
+// implicit; `lossy` says what to write instead.
+
+// The conversion that makes `from` fit where `to` is expected, when `from`
+// is not assignable to it, along with the type it converts to. Null stays
+// null, so an `Int?` widens to a `Float?`.
+pub fn widening(from: &Type, to: &Type) -> Option<(Coercion, Type)> {
+	let nullable = matches!(from, Type::Nullable(_));
+	if nullable && !matches!(to, Type::Nullable(_)) {
+		return None;
+	}
+	match (from.non_null(), to.non_null()) {
+		(Type::Int, Type::Float) if nullable => Some((Coercion::IntToFloat, Type::Float.nullable())),
+		(Type::Int, Type::Float) => Some((Coercion::IntToFloat, Type::Float)),
+		_ => None
+	}
+}
+
+// The check a dynamically typed value needs to be used as a `to`.
+pub fn dynamic(from: &Type, to: &Type) -> Option<Coercion> {
+	let checked = !matches!(to.non_null(), Type::Any | Type::Error | Type::Void | Type::Param { .. });
+	(*from == Type::Any && checked).then_some(Coercion::Cast)
+}
+
+// What both operands of `op` are converted to, given the operator's result:
+// `+` making a string converts both sides to strings, and arithmetic and
+// comparisons mixing an `Int` with a `Float` work on Floats.
+pub fn operands(op: BinaryOp, left: &Type, right: &Type, result: &Type) -> Option<(Coercion, Type)> {
+	let mixed = left.is_numeric() && right.is_numeric() && left != right;
+	match op {
+		BinaryOp::Add if *result == Type::String => Some((Coercion::ToString, Type::String)),
+		BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo if mixed => {
+			Some((Coercion::IntToFloat, Type::Float))
+		}
+		BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual if mixed => {
+			Some((Coercion::IntToFloat, Type::Float))
+		}
+		_ => None
+	}
+}
+
+// Why `from` is not converted to `to` implicitly, when it could be
+// explicitly but would lose something.
+pub fn lossy(from: &Type, to: &Type) -> Option<&'static str> {
+	match (from.non_null(), to.non_null()) {
+		(Type::Float, Type::Int) => Some("converting drops the fraction, so call 'toInt()' to do it on purpose"),
+		_ => None
+	}
+}
+
+// An `Int` constant widened to a `Float`. Ints beyond 2^53 do not all have a
+// Float of the same value, and a constant silently changing is an error.
+pub fn int_to_float(value: i64) -> Result<f64, String> {
+	let float = value as f64;
+	if float as i128 != value as i128 {
+		return Err(format!("'{}' has no exact 'Float' value; write '{:.1}' to use the nearest one", value, float));
+	}
+	Ok(float)
+}
@@ -0,0 +1,152 @@
+use crate::ast::expr::{BinaryOp, Literal, UnaryOp};
+use crate::sema::coerce;
+use crate::sema::hir::{Coercion, ExprKind};
+
+// Works out an expression whose operands are all literals, so `60 * 60`,
+// `"v${2}"` and `!true` become single literals. The checker folds as it
+// goes, so operands that could be folded already are by the time their
+// operator is looked at. Returns None when the expression is not constant,
+// and an error for operations that would fail at run time.
+pub fn fold(kind: &ExprKind) -> Result<Option<Literal>, String> {
+	match kind {
+		ExprKind::Unary { op, operand } => match &operand.kind {
+			ExprKind::Literal(value) => unary(*op, value),
+			_ => Ok(None)
+		},
+		ExprKind::Binary { op, left, right } => match (&left.kind, &right.kind) {
+			(ExprKind::Literal(left), ExprKind::Literal(right)) => binary(*op, left, right),
+			_ => Ok(None)
+		},
+		ExprKind::If { cond, then_branch, else_branch } => match (&cond.kind, &then_branch.kind, &else_branch.kind) {
+			(ExprKind::Literal(Literal::Bool(cond)), ExprKind::Literal(then_value), ExprKind::Literal(else_value)) => {
+				Ok(Some(if *cond { then_value.clone() } else { else_value.clone() }))
+			}
+			_ => Ok(None)
+		},
+		ExprKind::Coerce { value, coercion } => match (&value.kind, coercion) {
+			(ExprKind::Literal(Literal::Int(value)), Coercion::IntToFloat) => coerce::int_to_float(*value).map(|value| Some(Literal::Float(value))),
+			(ExprKind::Literal(value), Coercion::ToString) => Ok(display(value).map(Literal::String)),
+			_ => Ok(None)
+		},
+		_ => Ok(None)
+	}
+}
+
+fn unary(op: UnaryOp, value: &Literal) -> Result<Option<Literal>, String> {
+	let folded = match (op, value) {
+		(UnaryOp::Negate, Literal::Int(value)) => match value.checked_neg() {
+			Some(negated) => Literal::Int(negated),
+			None => return Err(format!("'-{}' does not fit in an 'Int'", value))
+		},
+		(UnaryOp::Negate, Literal::Float(value)) => Literal::Float(-value),
+		(UnaryOp::Not, Literal::Bool(value)) => Literal::Bool(!value),
+		_ => return Ok(None)
+	};
+	Ok(Some(folded))
+}
+
+fn binary(op: BinaryOp, left: &Literal, right: &Literal) -> Result<Option<Literal>, String> {
+	let folded = match (left, right) {
+		(Literal::Int(a), Literal::Int(b)) => return int(op, *a, *b),
+		(Literal::Int(_) | Literal::Float(_), Literal::Int(_) | Literal::Float(_)) => return Ok(float(op, number(left), number(right))),
+		(Literal::String(_), _) | (_, Literal::String(_)) if op == BinaryOp::Add => match (display(left), display(right)) {
+			(Some(a), Some(b)) => Literal::String(a + &b),
+			_ => return Ok(None)
+		},
+		(Literal::String(a), Literal::String(b)) => match compare(op, a.cmp(b)) {
+			Some(result) => Literal::Bool(result),
+			None => return Ok(None)
+		},
+		(Literal::Bool(a), Literal::Bool(b)) => match op {
+			BinaryOp::And => Literal::Bool(*a && *b),
+			BinaryOp::Or => Literal::Bool(*a || *b),
+			BinaryOp::Equal => Literal::Bool(a == b),
+			BinaryOp::NotEqual => Literal::Bool(a != b),
+			_ => return Ok(None)
+		},
+		(Literal::Null, _) if op == BinaryOp::Coalesce => right.clone(),
+		(_, _) if op == BinaryOp::Coalesce => left.clone(),
+		(Literal::Null, Literal::Null) => match op {
+			BinaryOp::Equal => Literal::Bool(true),
+			BinaryOp::NotEqual => Literal::Bool(false),
+			_ => return Ok(None)
+		},
+		(Literal::Null, _) | (_, Literal::Null) => match op {
+			BinaryOp::Equal => Literal::Bool(false),
+			BinaryOp::NotEqual => Literal::Bool(true),
+			_ => return Ok(None)
+		},
+		_ => return Ok(None)
+	};
+	Ok(Some(folded))
+}
+
+fn int(op: BinaryOp, a: i64, b: i64) -> Result<Option<Literal>, String> {
+	let checked = match op {
+		BinaryOp::Add => a.checked_add(b),
+		BinaryOp::Subtract => a.checked_sub(b),
+		BinaryOp::Multiply => a.checked_mul(b),
+		BinaryOp::Divide | BinaryOp::Modulo if b == 0 => return Err(format!("'{} {} 0' divides by zero", a, op.symbol())),
+		BinaryOp::Divide => a.checked_div(b),
+		BinaryOp::Modulo => a.checked_rem(b),
+		BinaryOp::BitAnd => Some(a & b),
+		BinaryOp::BitOr => Some(a | b),
+		BinaryOp::BitXor => Some(a ^ b),
+		BinaryOp::ShiftLeft | BinaryOp::ShiftRight if !(0..64).contains(&b) => {
+			return Err(format!("Cannot shift by {}; the amount has to be from 0 to 63", b));
+		}
+		BinaryOp::ShiftLeft => Some(a << b),
+		BinaryOp::ShiftRight => Some(a >> b),
+		_ => return Ok(compare(op, a.cmp(&b)).map(Literal::Bool))
+	};
+	match checked {
+		Some(value) => Ok(Some(Literal::Int(value))),
+		None => Err(format!("'{} {} {}' does not fit in an 'Int'", a, op.symbol(), b))
+	}
+}
+
+fn float(op: BinaryOp, a: f64, b: f64) -> Option<Literal> {
+	let value = match op {
+		BinaryOp::Add => a + b,
+		BinaryOp::Subtract => a - b,
+		BinaryOp::Multiply => a * b,
+		BinaryOp::Divide => a / b,
+		BinaryOp::Modulo => a % b,
+		_ => return compare(op, a.partial_cmp(&b)?).map(Literal::Bool)
+	};
+	Some(Literal::Float(value))
+}
+
+fn compare(op: BinaryOp, ordering: std::cmp::Ordering) -> Option<bool> {
+	let result = match op {
+		BinaryOp::Equal => ordering.is_eq(),
+		BinaryOp::NotEqual => ordering.is_ne(),
+		BinaryOp::Less => ordering.is_lt(),
+		BinaryOp::LessEqual => ordering.is_le(),
+		BinaryOp::Greater => ordering.is_gt(),
+		BinaryOp::GreaterEqual => ordering.is_ge(),
+		_ => return None
+	};
+	Some(result)
+}
+
+fn number(value: &Literal) -> f64 {
+	match value {
+		Literal::Int(value) => *value as f64,
+		Literal::Float(value) => *value,
+		_ => f64::NAN
+	}
+}
+
+// How a literal reads when it is put in a string. Floats are left to the
+// runtime, whose formatting the compiler does not try to copy.
+fn display(value: &Literal) -> Option<String> {
+	let text = match value {
+		Literal::String(text) => text.clone(),
+		Literal::Int(value) => value.to_string(),
+		Literal::Bool(value) => value.to_string(),
+		Literal::Null => "null".to_string(),
+		Literal::Float(_) => return None
+	};
+	Some(text)
+}
@@ -0,0 +1,689 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::expr::{BinaryOp, PostfixOp, UnaryOp};
+use crate::diagnostics::Diagnostic;
+use crate::lexer::token::Span;
+use crate::sema::hir::{self, Block, Expr, ExprKind, FnKind, MemberTarget, Pattern, Program, Stmt, StmtKind, Variable};
+use crate::sema::types::{FnId, LocalId, Type, TypeId};
+
+// Checks that follow control flow through checked bodies. A `val` or `mut`
+// declared without an initializer has to be assigned on every path before it
+// is read, only `mut` variables can be assigned more than once, functions that
+// return a value have to on every path, and no statement may follow one that
+// always jumps away.
+//
+// Constructors are followed the same way for the fields of their class that
+// have no initializer and cannot be null: each has to be assigned on every
+// path before the constructor ends, and `this` cannot be used for anything
+// but reading and assigning those fields until all of them are. A
+// constructor calling `super(...)` can only assign its own fields before
+// that, since the superclass constructor may call methods that read them.
+pub fn check(program: &Program) -> Vec<Diagnostic> {
+	let params = program.functions.iter().flat_map(|function| function.params.iter().map(|param| param.local)).collect();
+	let mut flow = Flow {
+		program,
+		params,
+		declared: HashMap::new(),
+		tracked: HashSet::new(),
+		state: None,
+		loops: Vec::new(),
+		touched: HashSet::new(),
+		quiet: 0,
+		class: None,
+		returns: None,
+		closures: 0,
+		file: 0,
+		errors: Vec::new()
+	};
+	for (index, file) in program.files.iter().enumerate() {
+		flow.file = index;
+		flow.body(&file.body);
+	}
+	for function in &program.functions {
+		let Some(body) = &function.body else {
+			continue;
+		};
+		// Lambdas are followed where they are created, along with what they
+		// capture; on their own they are only checked for how they end
+		let lambda = function.kind == FnKind::Lambda;
+		flow.file = function.file;
+		flow.quiet += usize::from(lambda);
+		let falls_off = match function.kind {
+			FnKind::Constructor(class) => flow.constructor(class, &body.stmts, function.span),
+			_ => flow.body(&body.stmts)
+		};
+		flow.quiet -= usize::from(lambda);
+		// A generator ends by falling off, which ends its iterator
+		if falls_off && !function.generator && !matches!(function.ret, Type::Void | Type::Any | Type::Error) {
+			let message = if lambda {
+				format!("This lambda does not return a value on every path; it has to return '{}'", function.ret)
+			} else {
+				format!("'{}' does not return a value on every path; it is declared to return '{}'", function.name, function.ret)
+			};
+			flow.error("E0505", message, function.span);
+		}
+	}
+	// Without a constructor nothing assigns them
+	for class in &program.classes {
+		if class.constructors.is_empty() && matches!(class.kind, hir::ClassKind::Class | hir::ClassKind::Enum) {
+			flow.file = class.file;
+			for index in required_fields(class) {
+				let field = &class.fields[index];
+				let message = format!("'{}' is never assigned; give it a value here, make its type nullable, or assign it in a constructor", field.name);
+				flow.error("E0509", message, field.span);
+			}
+		}
+	}
+	flow.errors
+}
+
+// Fields a constructor has to assign: those without an initializer or a
+// getter whose type does not allow null.
+fn required_fields(class: &hir::Class) -> Vec<usize> {
+	let required = |field: &hir::Field| {
+		field.init.is_none() && field.getter.is_none() && !matches!(field.ty, Type::Nullable(_) | Type::Null | Type::Any | Type::Error)
+	};
+	(0..class.fields.len()).filter(|index| required(&class.fields[*index])).collect()
+}
+
+// Something whose assignment is followed: a variable, a field of the class
+// whose constructor is being followed, or that constructor's `super(...)`
+// call having run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Slot {
+	Var(Variable),
+	Field(usize),
+	Super
+}
+
+// What is known about tracked slots at one point in a body.
+#[derive(Debug, Clone, Default)]
+struct State {
+	// Assigned on every path that gets here
+	assigned: HashSet<Slot>,
+	// Assigned on at least one path that gets here
+	maybe: HashSet<Slot>
+}
+
+// Where `break` and `continue` for one loop leave off.
+#[derive(Debug, Default)]
+struct Jumps {
+	label: Option<String>,
+	breaks: Option<State>,
+	continues: Option<State>
+}
+
+struct Flow<'a> {
+	program: &'a Program,
+	params: HashSet<LocalId>,
+	// Variables from `val` and `mut` declarations, as opposed to parameters,
+	// loop variables and the like, with the file and span of the declaration
+	declared: HashMap<Variable, (usize, Span)>,
+	// Variables declared without an initializer in the current body, and in
+	// a constructor the fields it has to assign
+	tracked: HashSet<Slot>,
+	// None where control cannot reach
+	state: Option<State>,
+	loops: Vec<Jumps>,
+	// Every slot assigned so far, on any path
+	touched: HashSet<Slot>,
+	// Nonzero while a loop body is followed the first time, only to find
+	// out what it assigns
+	quiet: usize,
+	// Class whose constructor is being followed
+	class: Option<TypeId>,
+	// States where the constructor returns early
+	returns: Option<State>,
+	// Nonzero inside lambdas and local functions, which can run after the
+	// constructor is done and so see `this` from wherever they end up
+	closures: usize,
+	// The file the code being followed is in
+	file: usize,
+	errors: Vec<Diagnostic>
+}
+
+impl<'a> Flow<'a> {
+	// Returns whether control can reach the end.
+	fn body(&mut self, stmts: &[Stmt]) -> bool {
+		self.tracked.clear();
+		self.state = Some(State::default());
+		self.loops.clear();
+		self.stmts(stmts);
+		self.state.is_some()
+	}
+
+	// Follows a constructor of `class`, then reports the fields it does not
+	// assign on some way out.
+	fn constructor(&mut self, class: TypeId, stmts: &[Stmt], span: Span) -> bool {
+		let fields = required_fields(self.program.class(class));
+		self.tracked.clear();
+		self.tracked.extend(fields.iter().map(|index| Slot::Field(*index)));
+		let calls_super = stmts.iter().any(|stmt| matches!(&stmt.kind, StmtKind::Expr(expr) if matches!(expr.kind, ExprKind::SuperCall { .. })));
+		if calls_super {
+			self.tracked.insert(Slot::Super);
+		}
+		self.state = Some(State::default());
+		self.loops.clear();
+		self.class = Some(class);
+		self.returns = None;
+		self.stmts(stmts);
+		let falls_off = self.state.is_some();
+		let ends = join(self.state.take(), self.returns.take());
+		if let Some(ends) = ends {
+			let missing: Vec<String> = fields
+				.iter()
+				.filter(|index| !ends.assigned.contains(&Slot::Field(**index)))
+				.map(|index| format!("'{}'", self.name(Slot::Field(*index))))
+				.collect();
+			if !missing.is_empty() {
+				let message = format!(
+					"This constructor does not assign {} on every path; fields without an initializer have to be assigned unless they can be null",
+					missing.join(", ")
+				);
+				self.error("E0509", message, span);
+			}
+		}
+		self.class = None;
+		falls_off
+	}
+
+	fn error(&mut self, code: &'static str, message: String, span: Span) {
+		self.report(Diagnostic::error(message, span).with_code(code).in_file(self.file));
+	}
+
+	fn report(&mut self, error: Diagnostic) {
+		// Bodies of lambdas are followed more than once
+		if self.quiet == 0 && !self.errors.contains(&error) {
+			self.errors.push(error);
+		}
+	}
+
+	fn block(&mut self, block: &Block) {
+		self.stmts(&block.stmts);
+	}
+
+	fn stmts(&mut self, stmts: &[Stmt]) {
+		let reachable = self.state.is_some();
+		for stmt in stmts {
+			// Local functions are declared up front, wherever they are written
+			if reachable && self.state.is_none() && !matches!(stmt.kind, StmtKind::Function { .. }) {
+				self.error("E0506", "Unreachable code".to_string(), stmt.span);
+				return;
+			}
+			self.stmt(stmt);
+		}
+	}
+
+	fn stmt(&mut self, stmt: &Stmt) {
+		match &stmt.kind {
+			StmtKind::Expr(expr) | StmtKind::Yield(expr) => self.expr(expr),
+			StmtKind::Var { var, init } => {
+				self.declared.insert(*var, (self.file, stmt.span));
+				match init {
+					Some(init) => self.expr(init),
+					None => {
+						let slot = Slot::Var(*var);
+						self.tracked.insert(slot);
+						// Declared again each time a loop comes back around
+						if let Some(state) = &mut self.state {
+							state.assigned.remove(&slot);
+							state.maybe.remove(&slot);
+						}
+					}
+				}
+			}
+			StmtKind::Block(block) => self.block(block),
+			StmtKind::If { cond, then_branch, else_branch } => {
+				self.expr(cond);
+				let before = self.state.clone();
+				self.block(then_branch);
+				let after_then = std::mem::replace(&mut self.state, before);
+				if let Some(else_branch) = else_branch {
+					self.stmt(else_branch);
+				}
+				self.state = join(after_then, self.state.take());
+			}
+			StmtKind::While { .. } | StmtKind::Loop { .. } | StmtKind::For { .. } => self.loop_stmt(stmt, None),
+			StmtKind::Labeled { label, body } => self.loop_stmt(body, Some(label)),
+			StmtKind::Break(label) => {
+				let state = self.state.take();
+				match self.jumps(label.as_ref()) {
+					Some(jumps) => jumps.breaks = join(jumps.breaks.take(), state),
+					// Already reported; carry on as if it were not there
+					None => self.state = state
+				}
+			}
+			StmtKind::Continue(label) => {
+				let state = self.state.take();
+				match self.jumps(label.as_ref()) {
+					Some(jumps) => jumps.continues = join(jumps.continues.take(), state),
+					// Already reported; carry on as if it were not there
+					None => self.state = state
+				}
+			}
+			StmtKind::Return(value) => {
+				if let Some(value) = value {
+					self.expr(value);
+				}
+				self.returned();
+			}
+			StmtKind::Throw(value) => {
+				self.expr(value);
+				self.state = None;
+			}
+			StmtKind::Try { body, catches, finally } => self.try_stmt(body, catches, finally.as_ref()),
+			StmtKind::Switch { subject, cases, default, exhaustive } => {
+				self.expr(subject);
+				let head = self.state.clone();
+				let mut ends = None;
+				for case in cases {
+					self.state = head.clone();
+					for pattern in &case.patterns {
+						if let Pattern::Value(value) = pattern {
+							self.expr(value);
+						}
+					}
+					self.block(&case.body);
+					ends = join(ends, self.state.take());
+				}
+				self.state = if *exhaustive && default.is_none() { None } else { head };
+				if let Some(default) = default {
+					self.block(default);
+				}
+				self.state = join(ends, self.state.take());
+			}
+			StmtKind::Function { function, .. } => self.closure(*function)
+		}
+	}
+
+	fn loop_stmt(&mut self, stmt: &Stmt, label: Option<&String>) {
+		match &stmt.kind {
+			StmtKind::While { cond, body } => self.repeat(Some(cond), body, true, label),
+			StmtKind::Loop { body } => self.repeat(None, body, false, label),
+			StmtKind::For { iterable, body, .. } => {
+				self.expr(iterable);
+				self.repeat(None, body, true, label);
+			}
+			_ => self.stmt(stmt)
+		}
+	}
+
+	// The loop a `break` or `continue` with `label` leaves off.
+	fn jumps(&mut self, label: Option<&String>) -> Option<&mut Jumps> {
+		match label {
+			None => self.loops.last_mut(),
+			Some(label) => self.loops.iter_mut().rev().find(|jumps| jumps.label.as_ref() == Some(label))
+		}
+	}
+
+	// Follows a loop body entered from the current state. Whatever the body
+	// assigns may already be assigned when it runs again, so it is followed
+	// once quietly to find that out and then again for real. `cond` runs
+	// before every pass, and `skippable` loops can end there without running
+	// the body at all.
+	fn repeat(&mut self, cond: Option<&Expr>, body: &Block, skippable: bool, label: Option<&String>) {
+		let entry = self.state.clone();
+		self.quiet += 1;
+		let (_, back, _) = self.pass(cond, body, label);
+		self.quiet -= 1;
+
+		self.state = entry;
+		if let (Some(state), Some(back)) = (&mut self.state, back) {
+			state.maybe.extend(back.maybe);
+		}
+		let (exit, _, breaks) = self.pass(cond, body, label);
+		self.state = join(if skippable { exit } else { None }, breaks);
+	}
+
+	// Returns the states leaving at the condition, going around again, and
+	// leaving through `break`.
+	fn pass(&mut self, cond: Option<&Expr>, body: &Block, label: Option<&String>) -> (Option<State>, Option<State>, Option<State>) {
+		if let Some(cond) = cond {
+			self.expr(cond);
+		}
+		let exit = self.state.clone();
+		self.loops.push(Jumps { label: label.cloned(), ..Jumps::default() });
+		self.block(body);
+		let jumps = self.loops.pop().unwrap_or_default();
+		let back = join(self.state.take(), jumps.continues);
+		(exit, back, jumps.breaks)
+	}
+
+	fn try_stmt(&mut self, body: &Block, catches: &[hir::Catch], finally: Option<&Block>) {
+		let entry = self.state.clone();
+		let touched = std::mem::take(&mut self.touched);
+		self.block(body);
+		let mut ends = self.state.take();
+
+		// A catch can start from anywhere in the body
+		let mut thrown = entry.map(|mut state| {
+			state.maybe.extend(self.touched.iter().copied());
+			state
+		});
+		for catch in catches {
+			self.state = thrown.clone();
+			self.block(&catch.body);
+			ends = join(ends, self.state.take());
+		}
+		if let Some(state) = &mut thrown {
+			state.maybe.extend(self.touched.iter().copied());
+		}
+		self.touched.extend(touched);
+
+		let Some(finally) = finally else {
+			self.state = ends;
+			return;
+		};
+		// `finally` also runs when something escapes the catches, but only
+		// the ways in that finish normally carry on after the `try`
+		self.state = join(ends.clone(), thrown);
+		self.block(finally);
+		self.quiet += 1;
+		self.state = ends;
+		self.block(finally);
+		self.quiet -= 1;
+	}
+
+	// A lambda or local function can run any number of times from where it
+	// is created, seeing captured variables as they are then.
+	fn closure(&mut self, function: FnId) {
+		let Some(body) = &self.program.function(function).body else {
+			return;
+		};
+		let loops = std::mem::take(&mut self.loops);
+		self.closures += 1;
+		self.repeat(None, body, true, None);
+		self.closures -= 1;
+		self.loops = loops;
+	}
+
+	fn returned(&mut self) {
+		let state = self.state.take();
+		if self.class.is_some() && self.closures == 0 {
+			self.returns = join(self.returns.take(), state);
+		}
+	}
+
+	fn expr(&mut self, expr: &Expr) {
+		match &expr.kind {
+			ExprKind::Literal(_)
+			| ExprKind::Function(_)
+			| ExprKind::Builtin(_)
+			| ExprKind::Native(_)
+			| ExprKind::BackingField(_)
+			| ExprKind::Variant { .. } => {}
+			ExprKind::This | ExprKind::Super => self.this_used(expr.span),
+			ExprKind::Var(var) => self.read(Slot::Var(*var), expr.span),
+			ExprKind::Unary { op: UnaryOp::PreIncrement | UnaryOp::PreDecrement, operand }
+			| ExprKind::Postfix { op: PostfixOp::Increment | PostfixOp::Decrement, operand } => {
+				self.expr(operand);
+				if let Some(slot) = self.slot(operand) {
+					self.write(slot, operand.span);
+				}
+			}
+			ExprKind::Unary { operand, .. }
+			| ExprKind::Postfix { operand, .. }
+			| ExprKind::Is { operand, .. }
+			| ExprKind::Coerce { value: operand, .. } => self.expr(operand),
+			ExprKind::Binary { op: BinaryOp::And | BinaryOp::Or | BinaryOp::Coalesce, left, right } => {
+				// The right side does not always run
+				self.expr(left);
+				let skipped = self.state.clone();
+				self.expr(right);
+				self.state = join(skipped, self.state.take());
+			}
+			ExprKind::Binary { left, right, .. } | ExprKind::Index { object: left, index: right } => {
+				self.expr(left);
+				self.expr(right);
+			}
+			ExprKind::Range { start, end } => {
+				self.expr(start);
+				self.expr(end);
+			}
+			ExprKind::Assign { target, op, value } => match self.slot(target) {
+				Some(slot) => {
+					if op.is_some() && !self.before_super(target.span) {
+						self.read(slot, target.span);
+					}
+					self.expr(value);
+					self.write(slot, target.span);
+				}
+				None => {
+					self.expr(target);
+					self.expr(value);
+				}
+			},
+			ExprKind::If { cond, then_branch, else_branch } => {
+				self.expr(cond);
+				let before = self.state.clone();
+				self.expr(then_branch);
+				let after_then = std::mem::replace(&mut self.state, before);
+				self.expr(else_branch);
+				self.state = join(after_then, self.state.take());
+			}
+			ExprKind::Block(block) => self.block(block),
+			ExprKind::Call { callee, args } => {
+				self.expr(callee);
+				for arg in args {
+					self.expr(arg);
+				}
+			}
+			ExprKind::SuperCall { args, .. } => {
+				for arg in args {
+					self.expr(arg);
+				}
+				if self.tracked.contains(&Slot::Super) {
+					self.write(Slot::Super, expr.span);
+				}
+			}
+			ExprKind::New { args, .. } | ExprKind::Array(args) | ExprKind::Set(args) => {
+				for arg in args {
+					self.expr(arg);
+				}
+			}
+			ExprKind::Map(entries) => {
+				for (key, value) in entries {
+					self.expr(key);
+					self.expr(value);
+				}
+			}
+			// Fields of `this` can be read while others are still unassigned,
+			// as long as no getter runs
+			ExprKind::Member { object, target: MemberTarget::Field(owner), name, .. }
+				if self.closures == 0
+					&& matches!(object.kind, ExprKind::This)
+					&& self.program.class(*owner).field(name).is_some_and(|field| field.getter.is_none()) =>
+			{
+				if self.before_super(object.span) {
+					return;
+				}
+				if let Some(slot) = self.slot(expr) {
+					self.read(slot, expr.span);
+				}
+			}
+			ExprKind::Member { object, .. } | ExprKind::Await(object) => self.expr(object),
+			ExprKind::Lambda(function) => self.closure(*function),
+			ExprKind::Return(value) => {
+				if let Some(value) = value {
+					self.expr(value);
+				}
+				self.returned();
+			}
+			ExprKind::Throw(value) => {
+				self.expr(value);
+				self.state = None;
+			}
+		}
+	}
+
+	// The slot `expr` assigns or reads: a variable, or in a constructor one
+	// of its class's fields through `this`.
+	fn slot(&self, expr: &Expr) -> Option<Slot> {
+		match &expr.kind {
+			ExprKind::Var(var) => Some(Slot::Var(*var)),
+			ExprKind::Member { object, target: MemberTarget::Field(owner), name, .. }
+				if self.class == Some(*owner) && self.closures == 0 && matches!(object.kind, ExprKind::This) =>
+			{
+				self.program.class(*owner).fields.iter().position(|field| field.name == *name).map(Slot::Field)
+			}
+			_ => None
+		}
+	}
+
+	// Reports `this` used while the constructor has not yet called
+	// `super(...)`, and returns whether it had to.
+	fn before_super(&mut self, span: Span) -> bool {
+		let Some(state) = &self.state else {
+			return false;
+		};
+		if !self.tracked.contains(&Slot::Super) || state.assigned.contains(&Slot::Super) {
+			return false;
+		}
+		self.error("E0509", "'this' cannot be used before 'super(...)' is called".to_string(), span);
+		true
+	}
+
+	// `this` used for something other than reading or assigning a field: it
+	// may reach code that reads fields not assigned yet.
+	fn this_used(&mut self, span: Span) {
+		if self.before_super(span) {
+			return;
+		}
+		let Some(state) = &self.state else {
+			return;
+		};
+		let mut pending: Vec<usize> = self
+			.tracked
+			.iter()
+			.filter_map(|slot| match slot {
+				Slot::Field(index) if !state.assigned.contains(slot) => Some(*index),
+				_ => None
+			})
+			.collect();
+		if pending.is_empty() {
+			return;
+		}
+		pending.sort();
+		let names: Vec<String> = pending.iter().map(|index| format!("'{}'", self.name(Slot::Field(*index)))).collect();
+		let verb = if names.len() == 1 { "is" } else { "are" };
+		let message = format!(
+			"'this' is used before {} {} assigned; assign every field before calling methods on 'this' or passing it on",
+			names.join(", "),
+			verb
+		);
+		self.error("E0509", message, span);
+	}
+
+	fn read(&mut self, slot: Slot, span: Span) {
+		let Some(state) = &self.state else {
+			return;
+		};
+		if !self.tracked.contains(&slot) || state.assigned.contains(&slot) {
+			return;
+		}
+		let message = if state.maybe.contains(&slot) {
+			format!("'{}' might not be assigned on every path that gets here", self.name(slot))
+		} else {
+			format!("'{}' is used before it is assigned", self.name(slot))
+		};
+		self.error("E0507", message, span);
+	}
+
+	fn write(&mut self, slot: Slot, span: Span) {
+		let Some(state) = &mut self.state else {
+			return;
+		};
+		self.touched.insert(slot);
+		if !self.tracked.contains(&slot) {
+			let Slot::Var(var) = slot else {
+				return;
+			};
+			if !self.mutable(slot) {
+				let message = match var {
+					Variable::Local(local) if self.params.contains(&local) => {
+						format!("Cannot assign to parameter '{}'; copy it into a 'mut' variable first", self.name(slot))
+					}
+					_ if self.declared.contains_key(&var) || matches!(var, Variable::Global(_)) => {
+						format!("Cannot assign to '{}' because it is a 'val'{}", self.name(slot), self.add_mut(slot))
+					}
+					_ => format!("Cannot assign to '{}'; it is not a variable declared with 'mut'", self.name(slot))
+				};
+				self.val_error(slot, message, span);
+			}
+			return;
+		}
+		let again = state.maybe.contains(&slot);
+		state.assigned.insert(slot);
+		state.maybe.insert(slot);
+		if again && slot == Slot::Super {
+			self.error("E0509", "'super(...)' may already have been called".to_string(), span);
+		} else if again && !self.mutable(slot) {
+			let message = format!("'{}' is a 'val' and may already have been assigned{}", self.name(slot), self.add_mut(slot));
+			self.val_error(slot, message, span);
+		}
+	}
+
+	// Offers to turn the `val` declaring `slot` into `mut`, when it is in the
+	// file being followed.
+	fn val_error(&mut self, slot: Slot, message: String, span: Span) {
+		let error = Diagnostic::error(message, span).with_code("E0508").in_file(self.file);
+		let declaration = match slot {
+			Slot::Var(Variable::Global(global)) if self.program.global(global).constant => None,
+			Slot::Var(var) => self.declared.get(&var).filter(|(file, _)| *file == self.file),
+			_ => None
+		};
+		let error = match declaration {
+			Some(&(_, at)) => {
+				let keyword = Span { start: at.start, end: at.start + 3, line: at.line, column: at.column };
+				error.with_fix("declare it with 'mut'", keyword, "mut")
+			}
+			None => error
+		};
+		self.report(error);
+	}
+
+	fn add_mut(&self, slot: Slot) -> String {
+		let span = match slot {
+			Slot::Var(Variable::Local(local)) => self.program.local(local).span,
+			Slot::Var(Variable::Global(global)) => self.program.global(global).span,
+			Slot::Field(index) => self.field(index).span,
+			Slot::Super => Span::default()
+		};
+		format!("; declare it with 'mut' on line {} to allow this", span.line)
+	}
+
+	fn name(&self, slot: Slot) -> &'a str {
+		match slot {
+			Slot::Var(Variable::Local(local)) => &self.program.local(local).name,
+			Slot::Var(Variable::Global(global)) => &self.program.global(global).name,
+			Slot::Field(index) => &self.field(index).name,
+			Slot::Super => "super"
+		}
+	}
+
+	fn mutable(&self, slot: Slot) -> bool {
+		match slot {
+			Slot::Var(Variable::Local(local)) => self.program.local(local).mutable,
+			Slot::Var(Variable::Global(global)) => self.program.global(global).mutable,
+			Slot::Field(index) => self.field(index).mutable,
+			Slot::Super => false
+		}
+	}
+
+	fn field(&self, index: usize) -> &'a hir::Field {
+		let class = self.class.expect("fields are only followed in constructors");
+		&self.program.class(class).fields[index]
+	}
+}
+
+// The state where two paths come together.
+fn join(a: Option<State>, b: Option<State>) -> Option<State> {
+	match (a, b) {
+		(None, state) | (state, None) => state,
+		(Some(mut a), Some(b)) => {
+			a.assigned.retain(|var| b.assigned.contains(var));
+			a.maybe.extend(b.maybe);
+			Some(a)
+		}
+	}
+}
@@ -0,0 +1,499 @@
+use crate::ast::expr::{BinaryOp, Literal, PostfixOp, UnaryOp};
+use crate::ast::stmt::{ForKind, Modifiers, Visibility};
+use crate::diagnostics::Diagnostic;
+use crate::lexer::token::Span;
+use crate::sema::builtins::Builtin;
+use crate::sema::types::{FnId, GlobalId, LocalId, Type, TypeId, TypeParamId};
+
+// The checked program. Every name is resolved to what it refers to and every
+// expression carries its type, so later phases never look at the AST or redo
+// any lookups. Sugar that only changes how things are written is gone:
+// interpolated strings are concatenations, expression bodies are returns,
+// members used without `this.` are explicit, consts are their values, and
+// implicit conversions are `Coerce` nodes.
+// Declarations from all files live in the tables below and are referred to
+// by index; `files` holds each file's top-level statements in source order.
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+	pub files: Vec<File>,
+	// Indices into `files` in the order their top-level code runs, with each
+	// package after the packages it imports
+	pub init_order: Vec<usize>,
+	pub classes: Vec<Class>,
+	pub functions: Vec<Function>,
+	pub globals: Vec<Global>,
+	pub locals: Vec<Local>,
+	pub type_params: Vec<TypeParamDef>,
+	// Class names written in types and `C(...)` calls, which keep no spans
+	// once lowered
+	pub type_uses: Vec<TypeUse>,
+	// What each `import` names, which also keeps no span once resolved
+	pub imports: Vec<Import>,
+	// Functions marked `@Test`, for the test runner
+	pub tests: Vec<FnId>,
+	// The natives of the runtime it was checked for, which `Native`
+	// expressions index
+	pub natives: Vec<Native>,
+	pub suppressions: Vec<Suppression>,
+	// Things that are allowed but probably mistakes, like unused variables
+	pub warnings: Vec<Diagnostic>
+}
+
+impl Program {
+	pub fn class(&self, id: TypeId) -> &Class {
+		&self.classes[id.0 as usize]
+	}
+
+	pub fn function(&self, id: FnId) -> &Function {
+		&self.functions[id.0 as usize]
+	}
+
+	pub fn global(&self, id: GlobalId) -> &Global {
+		&self.globals[id.0 as usize]
+	}
+
+	pub fn local(&self, id: LocalId) -> &Local {
+		&self.locals[id.0 as usize]
+	}
+
+	pub fn type_param(&self, id: TypeParamId) -> &TypeParamDef {
+		&self.type_params[id.0 as usize]
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct File {
+	pub path: String,
+	pub package: String,
+	pub body: Vec<Stmt>
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TypeUse {
+	pub class: TypeId,
+	pub file: usize,
+	pub span: Span
+}
+
+// A declaration an `import` names, where it names it. Importing an
+// overloaded function names every overload.
+#[derive(Debug, Clone, Copy)]
+pub struct Import {
+	pub target: ImportTarget,
+	pub file: usize,
+	pub span: Span
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportTarget {
+	Class(TypeId),
+	Function(FnId),
+	Global(GlobalId)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClassKind {
+	Class,
+	Interface,
+	Enum,
+	Struct
+}
+
+#[derive(Debug, Clone)]
+pub struct Class {
+	pub name: String,
+	pub package: String,
+	pub kind: ClassKind,
+	pub modifiers: Modifiers,
+	pub type_params: Vec<TypeParamId>,
+	// The superclass comes first when there is one, then interfaces
+	pub supertypes: Vec<Type>,
+	pub fields: Vec<Field>,
+	pub methods: Vec<FnId>,
+	pub constructors: Vec<FnId>,
+	pub variants: Vec<Variant>,
+	pub statics: Vec<Static>,
+	pub annotations: Vec<Annotation>,
+	// Text of the doc comment in front of it
+	pub doc: Option<String>,
+	pub file: usize,
+	pub span: Span
+}
+
+impl Class {
+	pub fn field(&self, name: &str) -> Option<&Field> {
+		self.fields.iter().find(|field| field.name == name)
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+	pub name: String,
+	pub ty: Type,
+	pub mutable: bool,
+	pub visibility: Visibility,
+	// Narrower than `visibility` after `private set` and the like
+	pub setter_visibility: Visibility,
+	pub init: Option<Expr>,
+	pub getter: Option<FnId>,
+	pub setter: Option<FnId>,
+	pub annotations: Vec<Annotation>,
+	pub doc: Option<String>,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub struct Variant {
+	pub name: String,
+	// Arguments for the enum's constructor
+	pub args: Vec<Expr>,
+	pub doc: Option<String>,
+	pub span: Span
+}
+
+// A `static` member, which belongs to the class rather than to its objects:
+// a field kept in a global, set as the top-level code gets to the class, or
+// a function called without an object.
+#[derive(Debug, Clone)]
+pub struct Static {
+	pub name: String,
+	pub visibility: Visibility,
+	pub target: StaticTarget,
+	pub span: Span
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StaticTarget {
+	Field(GlobalId),
+	Method(FnId)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FnKind {
+	Function,
+	Method(TypeId),
+	Constructor(TypeId),
+	Getter(TypeId),
+	Setter(TypeId),
+	// `static fn`, which has no `this`
+	Static(TypeId),
+	// `fn Receiver.name()`; `this` is the receiver
+	Extension,
+	Lambda
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+	pub name: String,
+	pub kind: FnKind,
+	pub modifiers: Modifiers,
+	pub type_params: Vec<TypeParamId>,
+	pub receiver: Option<Type>,
+	pub params: Vec<Param>,
+	pub ret: Type,
+	pub throws: Vec<Type>,
+	// Whether its body yields, so calling it makes an `Iterator` of what it
+	// yields rather than running it
+	pub generator: bool,
+	// None for abstract and interface methods, and `extern` functions
+	pub body: Option<Block>,
+	// What calling an `extern` function takes to C and back
+	pub foreign: Option<Foreign>,
+	pub annotations: Vec<Annotation>,
+	pub doc: Option<String>,
+	pub file: usize,
+	pub span: Span
+}
+
+impl Function {
+	pub fn signature(&self) -> Type {
+		Type::Function {
+			params: self.params.iter().map(|param| param.ty.clone()).collect(),
+			ret: Box::new(self.ret.clone())
+		}
+	}
+}
+
+// `@Name(args)` in front of a declaration. Arguments are literals, so
+// annotation processors can read them without running anything.
+#[derive(Debug, Clone)]
+pub struct Annotation {
+	pub name: String,
+	pub args: Vec<Literal>,
+	pub span: Span
+}
+
+// `@Suppress("unused", ...)`: warnings from the lints named, or from all of
+// them for `warnings`, are turned off in the declaration it is on.
+#[derive(Debug, Clone)]
+pub struct Suppression {
+	pub lints: Vec<String>,
+	pub file: usize,
+	// The whole declaration
+	pub span: Span,
+	pub annotation: Span
+}
+
+impl Annotation {
+	// The first argument when it is a string, like the message of `@Deprecated("...")`
+	pub fn text(&self) -> Option<&str> {
+		match self.args.first() {
+			Some(Literal::String(text)) => Some(text),
+			_ => None
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Param {
+	pub local: LocalId,
+	// For `...rest` this is an array of what each argument must be
+	pub ty: Type,
+	pub default: Option<Expr>,
+	pub variadic: bool
+}
+
+// A function written in Rust that the host registered with its runtime, as
+// code sees it: called by its name, which may be dotted like `math.sqrt`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Native {
+	pub name: String,
+	pub params: Vec<Type>,
+	pub ret: Type
+}
+
+impl Native {
+	pub fn signature(&self) -> Type {
+		Type::Function { params: self.params.clone(), ret: Box::new(self.ret.clone()) }
+	}
+}
+
+// An `extern` function as C sees it: the symbol of its name in `library`,
+// taking and returning `CType`s.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Foreign {
+	pub library: String,
+	pub params: Vec<CType>,
+	pub ret: CType
+}
+
+// Of the types code has, those that cross over to C: an `Int` is an
+// `int64_t`; a `Float` a `double`; a `Bool` an `int`; a `String` a `char*`,
+// copied on the way in and out; and a `Pointer` any other pointer, such as a
+// handle a library gives out, passed through as it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CType {
+	Int,
+	Float,
+	Bool,
+	String,
+	// A `char*` that can be NULL, for null
+	NullableString,
+	Pointer,
+	// A pointer that can be NULL, for null
+	NullablePointer,
+	// Only returned
+	Void
+}
+
+impl CType {
+	pub const ALL: &[CType] =
+		&[CType::Int, CType::Float, CType::Bool, CType::String, CType::NullableString, CType::Void, CType::Pointer, CType::NullablePointer];
+
+	// C functions are called with the arguments in registers, so only so
+	// many of each kind: these of `Float`s, and of everything else.
+	pub const MAX_FLOATS: usize = 8;
+	pub const MAX_INTS: usize = 6;
+
+	pub fn of(ty: &Type) -> Option<CType> {
+		let ctype = match ty {
+			Type::Int => CType::Int,
+			Type::Float => CType::Float,
+			Type::Bool => CType::Bool,
+			Type::String => CType::String,
+			Type::Nullable(inner) if **inner == Type::String => CType::NullableString,
+			Type::Pointer => CType::Pointer,
+			Type::Nullable(inner) if **inner == Type::Pointer => CType::NullablePointer,
+			Type::Void => CType::Void,
+			_ => return None
+		};
+		Some(ctype)
+	}
+}
+
+// A top-level `val` or `const`, initialized when its file's body runs.
+#[derive(Debug, Clone)]
+pub struct Global {
+	pub name: String,
+	pub ty: Type,
+	pub mutable: bool,
+	// Uses of a `const` are replaced by its value
+	pub constant: bool,
+	// The class of a `static` field, whose members its initializer can use
+	pub class: Option<TypeId>,
+	pub doc: Option<String>,
+	pub file: usize,
+	pub span: Span
+}
+
+// A parameter, `val`, loop variable, catch variable or local function.
+#[derive(Debug, Clone)]
+pub struct Local {
+	pub name: String,
+	pub ty: Type,
+	pub mutable: bool,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeParamDef {
+	pub name: String,
+	pub bounds: Vec<Type>,
+	pub span: Span
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Variable {
+	Local(LocalId),
+	Global(GlobalId)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Block {
+	pub stmts: Vec<Stmt>,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub struct Stmt {
+	pub kind: StmtKind,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub enum StmtKind {
+	Expr(Expr),
+	Var { var: Variable, init: Option<Expr> },
+	Block(Block),
+	If { cond: Expr, then_branch: Block, else_branch: Option<Box<Stmt>> },
+	While { cond: Expr, body: Block },
+	Loop { body: Block },
+	For { kind: ForKind, bindings: Vec<LocalId>, iterable: Expr, body: Block },
+	// A loop that `break` and `continue` can name
+	Labeled { label: String, body: Box<Stmt> },
+	Break(Option<String>),
+	Continue(Option<String>),
+	Return(Option<Expr>),
+	Yield(Expr),
+	Throw(Expr),
+	Try { body: Block, catches: Vec<Catch>, finally: Option<Block> },
+	// `exhaustive` when the cases cover every value of the subject's type
+	Switch { subject: Expr, cases: Vec<Case>, default: Option<Block>, exhaustive: bool },
+	// A function declared in a block, bound to `local` as a closure
+	Function { local: LocalId, function: FnId }
+}
+
+#[derive(Debug, Clone)]
+pub struct Catch {
+	pub local: LocalId,
+	// None catches everything
+	pub ty: Option<Type>,
+	pub body: Block,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub struct Case {
+	pub patterns: Vec<Pattern>,
+	pub body: Block,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+	// Matches a subject equal to the value
+	Value(Expr),
+	// `case Circle:` matches instances of the class, subclasses included, and
+	// `case Int:` values of a built-in type
+	Type { ty: Type, span: Span }
+}
+
+#[derive(Debug, Clone)]
+pub struct Expr {
+	pub kind: ExprKind,
+	pub ty: Type,
+	pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
+	Literal(Literal),
+	Var(Variable),
+	// A named function used as a value or called directly
+	Function(FnId),
+	Builtin(Builtin),
+	// By its index in the program's natives
+	Native(usize),
+	This,
+	Super,
+	// `field` inside an accessor: the storage behind the named property,
+	// read and written without going through the accessors again
+	BackingField(String),
+
+	Unary { op: UnaryOp, operand: Box<Expr> },
+	Binary { op: BinaryOp, left: Box<Expr>, right: Box<Expr> },
+	// `op` is applied to the target's old value first for `x += y` and the like
+	Assign { target: Box<Expr>, op: Option<BinaryOp>, value: Box<Expr> },
+	Postfix { op: PostfixOp, operand: Box<Expr> },
+	Is { operand: Box<Expr>, ty: Type, negated: bool },
+	// `value` converted to the node's type
+	Coerce { value: Box<Expr>, coercion: Coercion },
+	If { cond: Box<Expr>, then_branch: Box<Expr>, else_branch: Box<Expr> },
+	Block(Block),
+
+	Call { callee: Box<Expr>, args: Vec<Expr> },
+	// `super(...)` in a constructor
+	SuperCall { constructor: Option<FnId>, args: Vec<Expr> },
+	// `new C(...)` and `C(...)`; structs without constructors take their
+	// fields in order
+	New { class: TypeId, constructor: Option<FnId>, args: Vec<Expr> },
+	// `safe` for `a?.b`: null when `a` is, and a call through it is skipped
+	Member { object: Box<Expr>, name: String, name_span: Span, target: MemberTarget, safe: bool },
+	Variant { class: TypeId, index: usize },
+	Index { object: Box<Expr>, index: Box<Expr> },
+
+	Range { start: Box<Expr>, end: Box<Expr> },
+	Array(Vec<Expr>),
+	Map(Vec<(Expr, Expr)>),
+	// An array literal where a `Set` is expected
+	Set(Vec<Expr>),
+	Lambda(FnId),
+	Await(Box<Expr>),
+	Return(Option<Box<Expr>>),
+	Throw(Box<Expr>)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coercion {
+	// An `Int` used as a `Float`, as an operand next to one or where one is
+	// expected; see `coerce` for all the implicit conversions
+	IntToFloat,
+	// A value added to a string or interpolated into one
+	ToString,
+	// A dynamically typed value used where a static type is expected; fails
+	// at run time when the value does not have that type
+	Cast
+}
+
+// What `object.name` was found to be when checking. Methods are still
+// dispatched on the run-time class; the id is the statically chosen one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemberTarget {
+	Field(TypeId),
+	Method(FnId),
+	Extension(FnId),
+	// `length`, `toString()` and the other members primitives come with
+	Builtin,
+	// Member of an `Any` value, looked up at run time
+	Dynamic
+}
@@ -0,0 +1,335 @@
+use std::collections::HashSet;
+
+use crate::ast::stmt::{ENTRIES_METHOD, HAS_NEXT_METHOD, ITERATOR_METHOD, NEXT_METHOD, Visibility};
+use crate::diagnostics::lints::{self, ALL, LINTS};
+use crate::diagnostics::{Diagnostic, suggest};
+use crate::lexer::token::Span;
+use crate::sema::hir::{Block, Expr, ExprKind, FnKind, MemberTarget, Pattern, Program, Stmt, StmtKind, Variable};
+use crate::sema::types::{FnId, LocalId, TypeId};
+
+// Warnings for code that is never used: locals, parameters, and private
+// functions and fields. Names starting with `_` are left alone. Consts are
+// replaced by their values while checking, so the checker passes along the
+// ones it saw used.
+//
+// Calls to async functions whose result is dropped without `await`, and
+// `await` on calls that are not async, are warned about too.
+pub fn lint(program: &Program, used_consts: &HashSet<Variable>) -> Vec<Diagnostic> {
+	let mut lint = Lint {
+		program,
+		read: used_consts.clone(),
+		called: HashSet::new(),
+		fields: HashSet::new(),
+		locals: Vec::new(),
+		file: 0,
+		warnings: Vec::new()
+	};
+
+	for (index, file) in program.files.iter().enumerate() {
+		lint.file = index;
+		lint.stmts(&file.body);
+	}
+	for class in &program.classes {
+		lint.file = class.file;
+		for field in &class.fields {
+			if let Some(init) = &field.init {
+				lint.expr(init);
+			}
+		}
+		for variant in &class.variants {
+			lint.exprs(&variant.args);
+		}
+	}
+	for function in &program.functions {
+		lint.file = function.file;
+		for param in &function.params {
+			if let Some(default) = &param.default {
+				lint.expr(default);
+			}
+		}
+		if let Some(body) = &function.body {
+			lint.stmts(&body.stmts);
+		}
+	}
+
+	let mut warnings = std::mem::take(&mut lint.warnings);
+	for &(local, file) in &lint.locals {
+		if !lint.read.contains(&Variable::Local(local)) {
+			let local = program.local(local);
+			warnings.extend(unused(&local.name, format!("'{}' is never used", local.name), file, local.span));
+		}
+	}
+	for (index, function) in program.functions.iter().enumerate() {
+		let id = FnId(index as u32);
+		if function.body.is_none() {
+			continue;
+		}
+		if lint.private_unused(id) {
+			let message = format!("Private function '{}' is never called", function.name);
+			warnings.extend(unused(&function.name, message, function.file, function.span));
+			continue;
+		}
+		if lint.checks_params(id) {
+			for param in &function.params {
+				let local = program.local(param.local);
+				if !lint.read.contains(&Variable::Local(param.local)) {
+					let message = format!("Parameter '{}' of '{}' is never used", local.name, function.name);
+					warnings.extend(unused(&local.name, message, function.file, local.span));
+				}
+			}
+		}
+	}
+	for (index, class) in program.classes.iter().enumerate() {
+		for field in &class.fields {
+			if field.visibility != Visibility::Private {
+				continue;
+			}
+			if !lint.fields.contains(&(TypeId(index as u32), field.name.clone())) {
+				warnings.extend(unused(&field.name, format!("Private field '{}' is never read", field.name), class.file, field.span));
+			}
+		}
+	}
+	warnings
+}
+
+// Drops the warnings turned off by `@Suppress` on a declaration they are in,
+// after warning about names given to it that are not lints.
+pub fn suppress(program: &Program, mut warnings: Vec<Diagnostic>) -> Vec<Diagnostic> {
+	for suppression in &program.suppressions {
+		for name in &suppression.lints {
+			if name == ALL || lints::lint(name).is_some() {
+				continue;
+			}
+			let span = suppression.annotation;
+			let warning = Diagnostic::warning(format!("Unknown lint '{}' in '@Suppress'", name), span).with_code("W0006");
+			let names = LINTS.iter().map(|lint| lint.name).chain([ALL]);
+			warnings.push(suggest::did_you_mean(warning.in_file(suppression.file), name, span, names));
+		}
+	}
+	warnings.retain(|warning| {
+		let span = warning.primary_span;
+		!program.suppressions.iter().any(|suppression| {
+			suppression.file == warning.file
+				&& suppression.span.start <= span.start
+				&& span.end <= suppression.span.end
+				&& lints::suppresses(&suppression.lints, warning)
+		})
+	});
+	warnings
+}
+
+fn unused(name: &str, message: String, file: usize, span: Span) -> Option<Diagnostic> {
+	(!name.starts_with('_')).then(|| Diagnostic::warning(message, span).with_code("W0001").in_file(file))
+}
+
+struct Lint<'a> {
+	program: &'a Program,
+	// Variables whose value is used somewhere; only being assigned does not count
+	read: HashSet<Variable>,
+	called: HashSet<FnId>,
+	fields: HashSet<(TypeId, String)>,
+	// Declared variables and local functions, and the file each is in
+	locals: Vec<(LocalId, usize)>,
+	// The file the code being walked is in
+	file: usize,
+	warnings: Vec<Diagnostic>
+}
+
+impl<'a> Lint<'a> {
+	fn private_unused(&self, id: FnId) -> bool {
+		let function = self.program.function(id);
+		let private = function.modifiers.visibility == Visibility::Private;
+		// Loops call these by name without saying so
+		let protocol = [ITERATOR_METHOD, ENTRIES_METHOD, HAS_NEXT_METHOD, NEXT_METHOD].contains(&function.name.as_str());
+		let named = matches!(function.kind, FnKind::Function | FnKind::Extension | FnKind::Method(_) | FnKind::Static(_));
+		private && named && !protocol && !self.called.contains(&id)
+	}
+
+	// Methods other classes can override or implement must keep their
+	// parameters, used or not, and lambdas are shaped by what they are passed to.
+	fn checks_params(&self, id: FnId) -> bool {
+		let function = self.program.function(id);
+		match function.kind {
+			FnKind::Function | FnKind::Extension | FnKind::Constructor(_) | FnKind::Static(_) => true,
+			FnKind::Method(_) => function.modifiers.visibility == Visibility::Private && !function.modifiers.is_override,
+			FnKind::Getter(_) | FnKind::Setter(_) | FnKind::Lambda => false
+		}
+	}
+
+	// The name of the function a call goes to, when that is known before it
+	// runs, and whether it is async.
+	fn callee(&self, expr: &Expr) -> Option<(&'a str, bool)> {
+		let ExprKind::Call { callee, .. } = &expr.kind else {
+			return None;
+		};
+		match callee.kind {
+			ExprKind::Function(id) | ExprKind::Member { target: MemberTarget::Method(id) | MemberTarget::Extension(id), .. } => {
+				let function = self.program.function(id);
+				Some((&function.name, function.modifiers.is_async))
+			}
+			ExprKind::Builtin(builtin) => Some((builtin.name(), builtin.is_async())),
+			_ => None
+		}
+	}
+
+	fn block(&mut self, block: &Block) {
+		self.stmts(&block.stmts);
+	}
+
+	fn stmts(&mut self, stmts: &[Stmt]) {
+		for stmt in stmts {
+			self.stmt(stmt);
+		}
+	}
+
+	fn stmt(&mut self, stmt: &Stmt) {
+		match &stmt.kind {
+			StmtKind::Expr(expr) => {
+				if let Some((name, true)) = self.callee(expr) {
+					let message = format!(
+						"'{}' is async and its result is dropped without waiting for it; add 'await', or keep it in 'val _' to let it run on its own",
+						name
+					);
+					self.warnings.push(Diagnostic::warning(message, expr.span).with_code("W0003").in_file(self.file));
+				}
+				self.expr(expr)
+			}
+			StmtKind::Yield(expr) | StmtKind::Throw(expr) => self.expr(expr),
+			StmtKind::Var { var, init } => {
+				if let Variable::Local(local) = var {
+					self.locals.push((*local, self.file));
+				}
+				if let Some(init) = init {
+					self.expr(init);
+				}
+			}
+			StmtKind::Block(block) | StmtKind::Loop { body: block } => self.block(block),
+			StmtKind::If { cond, then_branch, else_branch } => {
+				self.expr(cond);
+				self.block(then_branch);
+				if let Some(else_branch) = else_branch {
+					self.stmt(else_branch);
+				}
+			}
+			StmtKind::While { cond, body } => {
+				self.expr(cond);
+				self.block(body);
+			}
+			StmtKind::For { bindings, iterable, body, .. } => {
+				self.locals.extend(bindings.iter().map(|&local| (local, self.file)));
+				self.expr(iterable);
+				self.block(body);
+			}
+			StmtKind::Labeled { body, .. } => self.stmt(body),
+			StmtKind::Break(_) | StmtKind::Continue(_) | StmtKind::Return(None) => {}
+			StmtKind::Return(Some(value)) => self.expr(value),
+			StmtKind::Try { body, catches, finally } => {
+				self.block(body);
+				for catch in catches {
+					self.block(&catch.body);
+				}
+				if let Some(finally) = finally {
+					self.block(finally);
+				}
+			}
+			StmtKind::Switch { subject, cases, default, .. } => {
+				self.expr(subject);
+				for case in cases {
+					for pattern in &case.patterns {
+						if let Pattern::Value(value) = pattern {
+							self.expr(value);
+						}
+					}
+					self.block(&case.body);
+				}
+				if let Some(default) = default {
+					self.block(default);
+				}
+			}
+			StmtKind::Function { local, .. } => self.locals.push((*local, self.file)),
+		}
+	}
+
+	fn expr(&mut self, expr: &Expr) {
+		match &expr.kind {
+			ExprKind::Literal(_) | ExprKind::Builtin(_) | ExprKind::Native(_) | ExprKind::This | ExprKind::Super | ExprKind::BackingField(_) => {}
+			ExprKind::Variant { .. } => {}
+			ExprKind::Var(var) => {
+				self.read.insert(*var);
+			}
+			ExprKind::Function(function) => {
+				self.called.insert(*function);
+			}
+			ExprKind::Unary { operand, .. }
+			| ExprKind::Postfix { operand, .. }
+			| ExprKind::Is { operand, .. }
+			| ExprKind::Coerce { value: operand, .. } => self.expr(operand),
+			ExprKind::Binary { left, right, .. } | ExprKind::Range { start: left, end: right } => {
+				self.expr(left);
+				self.expr(right);
+			}
+			ExprKind::Index { object, index } => {
+				self.expr(object);
+				self.expr(index);
+			}
+			ExprKind::Assign { target, op, value } => {
+				// Storing into a variable or field is not a use of it
+				match &target.kind {
+					ExprKind::Var(_) if op.is_none() => {}
+					ExprKind::Member { object, target: MemberTarget::Field(_), .. } if op.is_none() => self.expr(object),
+					_ => self.expr(target)
+				}
+				self.expr(value);
+			}
+			ExprKind::If { cond, then_branch, else_branch } => {
+				self.expr(cond);
+				self.expr(then_branch);
+				self.expr(else_branch);
+			}
+			ExprKind::Block(block) => self.block(block),
+			ExprKind::Call { callee, args } => {
+				self.expr(callee);
+				self.exprs(args);
+			}
+			ExprKind::SuperCall { constructor, args } | ExprKind::New { constructor, args, .. } => {
+				self.called.extend(*constructor);
+				self.exprs(args);
+			}
+			ExprKind::Member { object, name, target, .. } => {
+				match target {
+					MemberTarget::Field(class) => {
+						self.fields.insert((*class, name.clone()));
+					}
+					MemberTarget::Method(function) | MemberTarget::Extension(function) => {
+						self.called.insert(*function);
+					}
+					MemberTarget::Builtin | MemberTarget::Dynamic => {}
+				}
+				self.expr(object);
+			}
+			ExprKind::Array(items) | ExprKind::Set(items) => self.exprs(items),
+			ExprKind::Map(entries) => {
+				for (key, value) in entries {
+					self.expr(key);
+					self.expr(value);
+				}
+			}
+			ExprKind::Lambda(_) => {}
+			ExprKind::Await(inner) => {
+				if let Some((name, false)) = self.callee(inner) {
+					let message = format!("'await' does nothing here; '{}' is not async", name);
+					self.warnings.push(Diagnostic::warning(message, expr.span).with_code("W0004").in_file(self.file));
+				}
+				self.expr(inner)
+			}
+			ExprKind::Throw(inner) | ExprKind::Return(Some(inner)) => self.expr(inner),
+			ExprKind::Return(None) => {}
+		}
+	}
+
+	fn exprs(&mut self, exprs: &[Expr]) {
+		for expr in exprs {
+			self.expr(expr);
+		}
+	}
+}
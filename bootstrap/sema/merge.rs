@@ -0,0 +1,409 @@
+use crate::sema::hir::{
+	Block, Case, Catch, Class, Expr, ExprKind, FnKind, Function, Global, Local, MemberTarget, Pattern, Program, StaticTarget, Stmt, StmtKind, TypeParamDef,
+	TypeUse, Variable
+};
+use crate::sema::types::{FnId, GlobalId, LocalId, Type, TypeId, TypeParamId};
+
+// Joins programs checked apart back into one. Packages' bodies are checked
+// on threads of their own, each in a copy of the program as it was once every
+// declaration was known. What a copy adds to the tables, like lambdas and
+// locals, is numbered from where the tables ended when it was made, so the
+// copies' numbers clash; each copy's are moved up to follow what the program
+// has by the time it is merged. A fork can be kept and merged into a later
+// check of the same files, as long as it would come out the same.
+
+// How long each table of a program is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Counts {
+	classes: usize,
+	functions: usize,
+	globals: usize,
+	locals: usize,
+	type_params: usize,
+	type_uses: usize,
+	suppressions: usize
+}
+
+impl Counts {
+	pub fn of(program: &Program) -> Counts {
+		Counts {
+			classes: program.classes.len(),
+			functions: program.functions.len(),
+			globals: program.globals.len(),
+			locals: program.locals.len(),
+			type_params: program.type_params.len(),
+			type_uses: program.type_uses.len(),
+			suppressions: program.suppressions.len()
+		}
+	}
+}
+
+// What a copy of a program, made when it had `base`, added to the tables and
+// worked out about the declarations of some of its files, like their bodies,
+// without the rest of the copy.
+#[derive(Debug, Clone)]
+pub struct Fork {
+	base: Counts,
+	// Of those files, by id
+	classes: Vec<(usize, Class)>,
+	functions: Vec<(usize, Function)>,
+	globals: Vec<(usize, Global)>,
+	// The tables from `base` on
+	added: Program
+}
+
+impl Fork {
+	pub fn split(mut forked: Program, base: Counts, files: &[usize]) -> Fork {
+		let added = Program {
+			classes: forked.classes.split_off(base.classes),
+			functions: forked.functions.split_off(base.functions),
+			globals: forked.globals.split_off(base.globals),
+			locals: forked.locals.split_off(base.locals),
+			type_params: forked.type_params.split_off(base.type_params),
+			type_uses: forked.type_uses.split_off(base.type_uses),
+			suppressions: forked.suppressions.split_off(base.suppressions),
+			..Program::default()
+		};
+		let ours = |file: usize| files.contains(&file);
+		Fork {
+			base,
+			classes: forked.classes.into_iter().enumerate().filter(|(_, class)| ours(class.file)).collect(),
+			functions: forked.functions.into_iter().enumerate().filter(|(_, function)| ours(function.file)).collect(),
+			globals: forked.globals.into_iter().enumerate().filter(|(_, global)| ours(global.file)).collect(),
+			added
+		}
+	}
+
+	// Adds it to `program`, which has everything the copy had up to `base`.
+	// Returns how to renumber anything else of the copy's.
+	pub fn merge(self, program: &mut Program) -> Shift {
+		let shift = Shift { base: self.base, to: Counts::of(program) };
+		for (id, mut class) in self.classes {
+			shift.class(&mut class);
+			program.classes[id] = class;
+		}
+		program.classes.extend(self.added.classes.into_iter().map(|mut class| {
+			shift.class(&mut class);
+			class
+		}));
+		for (id, mut function) in self.functions {
+			shift.function(&mut function);
+			program.functions[id] = function;
+		}
+		program.functions.extend(self.added.functions.into_iter().map(|mut function| {
+			shift.function(&mut function);
+			function
+		}));
+		for (id, mut global) in self.globals {
+			shift.global(&mut global);
+			program.globals[id] = global;
+		}
+		program.globals.extend(self.added.globals.into_iter().map(|mut global| {
+			shift.global(&mut global);
+			global
+		}));
+
+		// Locals and type parameters belong to functions, whose own are only
+		// ever added
+		program.locals.extend(self.added.locals.into_iter().map(|mut local| {
+			shift.local(&mut local);
+			local
+		}));
+		program.type_params.extend(self.added.type_params.into_iter().map(|mut param| {
+			shift.type_param(&mut param);
+			param
+		}));
+		program.type_uses.extend(self.added.type_uses.into_iter().map(|mut type_use| {
+			shift.type_use(&mut type_use);
+			type_use
+		}));
+		program.suppressions.extend(self.added.suppressions);
+		shift
+	}
+}
+
+// Moves the numbers a copy gave what it added, those from `base` on, to
+// follow `to`.
+#[derive(Debug, Clone, Copy)]
+pub struct Shift {
+	base: Counts,
+	to: Counts
+}
+
+fn moved(id: u32, base: usize, to: usize) -> u32 {
+	if (id as usize) < base { id } else { id + (to - base) as u32 }
+}
+
+impl Shift {
+	fn class_id(&self, id: TypeId) -> TypeId {
+		TypeId(moved(id.0, self.base.classes, self.to.classes))
+	}
+
+	fn function_id(&self, id: FnId) -> FnId {
+		FnId(moved(id.0, self.base.functions, self.to.functions))
+	}
+
+	fn global_id(&self, id: GlobalId) -> GlobalId {
+		GlobalId(moved(id.0, self.base.globals, self.to.globals))
+	}
+
+	fn local_id(&self, id: LocalId) -> LocalId {
+		LocalId(moved(id.0, self.base.locals, self.to.locals))
+	}
+
+	fn type_param_id(&self, id: TypeParamId) -> TypeParamId {
+		TypeParamId(moved(id.0, self.base.type_params, self.to.type_params))
+	}
+
+	pub fn variable(&self, var: Variable) -> Variable {
+		match var {
+			Variable::Local(local) => Variable::Local(self.local_id(local)),
+			Variable::Global(global) => Variable::Global(self.global_id(global))
+		}
+	}
+
+	fn class(&self, class: &mut Class) {
+		self.type_params(&mut class.type_params);
+		self.types(&mut class.supertypes);
+		for field in &mut class.fields {
+			self.ty(&mut field.ty);
+			if let Some(init) = &mut field.init {
+				self.expr(init);
+			}
+			for accessor in field.getter.iter_mut().chain(&mut field.setter) {
+				*accessor = self.function_id(*accessor);
+			}
+		}
+		for id in class.methods.iter_mut().chain(&mut class.constructors) {
+			*id = self.function_id(*id);
+		}
+		for variant in &mut class.variants {
+			self.exprs(&mut variant.args);
+		}
+		for member in &mut class.statics {
+			member.target = match member.target {
+				StaticTarget::Field(global) => StaticTarget::Field(self.global_id(global)),
+				StaticTarget::Method(method) => StaticTarget::Method(self.function_id(method))
+			};
+		}
+	}
+
+	fn function(&self, function: &mut Function) {
+		function.kind = match function.kind {
+			FnKind::Method(class) => FnKind::Method(self.class_id(class)),
+			FnKind::Constructor(class) => FnKind::Constructor(self.class_id(class)),
+			FnKind::Getter(class) => FnKind::Getter(self.class_id(class)),
+			FnKind::Setter(class) => FnKind::Setter(self.class_id(class)),
+			FnKind::Static(class) => FnKind::Static(self.class_id(class)),
+			kind => kind
+		};
+		self.type_params(&mut function.type_params);
+		if let Some(receiver) = &mut function.receiver {
+			self.ty(receiver);
+		}
+		for param in &mut function.params {
+			param.local = self.local_id(param.local);
+			self.ty(&mut param.ty);
+			if let Some(default) = &mut param.default {
+				self.expr(default);
+			}
+		}
+		self.ty(&mut function.ret);
+		self.types(&mut function.throws);
+		if let Some(body) = &mut function.body {
+			self.block(body);
+		}
+	}
+
+	fn global(&self, global: &mut Global) {
+		self.ty(&mut global.ty);
+		global.class = global.class.map(|class| self.class_id(class));
+	}
+
+	fn local(&self, local: &mut Local) {
+		self.ty(&mut local.ty);
+	}
+
+	fn type_param(&self, param: &mut TypeParamDef) {
+		self.types(&mut param.bounds);
+	}
+
+	fn type_use(&self, type_use: &mut TypeUse) {
+		type_use.class = self.class_id(type_use.class);
+	}
+
+	fn type_params(&self, ids: &mut [TypeParamId]) {
+		for id in ids {
+			*id = self.type_param_id(*id);
+		}
+	}
+
+	fn types(&self, types: &mut [Type]) {
+		for ty in types {
+			self.ty(ty);
+		}
+	}
+
+	fn ty(&self, ty: &mut Type) {
+		match ty {
+			Type::Nullable(inner) | Type::Array(inner) | Type::Set(inner) | Type::Iterator(inner) | Type::Option(inner) | Type::Channel(inner) => {
+				self.ty(inner)
+			}
+			Type::Map(key, value) | Type::Result(key, value) => {
+				self.ty(key);
+				self.ty(value);
+			}
+			Type::Function { params, ret } => {
+				self.types(params);
+				self.ty(ret);
+			}
+			Type::Class { id, args, .. } => {
+				*id = self.class_id(*id);
+				self.types(args);
+			}
+			Type::Param { id, .. } => *id = self.type_param_id(*id),
+			Type::Union(members) => self.types(members),
+			_ => {}
+		}
+	}
+
+	pub fn block(&self, block: &mut Block) {
+		self.stmts(&mut block.stmts);
+	}
+
+	pub fn stmts(&self, stmts: &mut [Stmt]) {
+		for stmt in stmts {
+			self.stmt(stmt);
+		}
+	}
+
+	fn stmt(&self, stmt: &mut Stmt) {
+		match &mut stmt.kind {
+			StmtKind::Expr(expr) | StmtKind::Yield(expr) | StmtKind::Throw(expr) | StmtKind::Return(Some(expr)) => self.expr(expr),
+			StmtKind::Var { var, init } => {
+				*var = self.variable(*var);
+				if let Some(init) = init {
+					self.expr(init);
+				}
+			}
+			StmtKind::Block(block) | StmtKind::Loop { body: block } => self.block(block),
+			StmtKind::If { cond, then_branch, else_branch } => {
+				self.expr(cond);
+				self.block(then_branch);
+				if let Some(else_branch) = else_branch {
+					self.stmt(else_branch);
+				}
+			}
+			StmtKind::While { cond, body } => {
+				self.expr(cond);
+				self.block(body);
+			}
+			StmtKind::For { bindings, iterable, body, .. } => {
+				for binding in bindings {
+					*binding = self.local_id(*binding);
+				}
+				self.expr(iterable);
+				self.block(body);
+			}
+			StmtKind::Labeled { body, .. } => self.stmt(body),
+			StmtKind::Break(_) | StmtKind::Continue(_) | StmtKind::Return(None) => {}
+			StmtKind::Try { body, catches, finally } => {
+				self.block(body);
+				for Catch { local, ty, body, .. } in catches {
+					*local = self.local_id(*local);
+					if let Some(ty) = ty {
+						self.ty(ty);
+					}
+					self.block(body);
+				}
+				if let Some(finally) = finally {
+					self.block(finally);
+				}
+			}
+			StmtKind::Switch { subject, cases, default, .. } => {
+				self.expr(subject);
+				for Case { patterns, body, .. } in cases {
+					for pattern in patterns {
+						match pattern {
+							Pattern::Value(value) => self.expr(value),
+							Pattern::Type { ty, .. } => self.ty(ty)
+						}
+					}
+					self.block(body);
+				}
+				if let Some(default) = default {
+					self.block(default);
+				}
+			}
+			StmtKind::Function { local, function } => {
+				*local = self.local_id(*local);
+				*function = self.function_id(*function);
+			}
+		}
+	}
+
+	fn exprs(&self, exprs: &mut [Expr]) {
+		for expr in exprs {
+			self.expr(expr);
+		}
+	}
+
+	fn expr(&self, expr: &mut Expr) {
+		self.ty(&mut expr.ty);
+		match &mut expr.kind {
+			ExprKind::Literal(_) | ExprKind::Builtin(_) | ExprKind::Native(_) | ExprKind::This | ExprKind::Super | ExprKind::BackingField(_) => {}
+			ExprKind::Return(None) => {}
+			ExprKind::Var(var) => *var = self.variable(*var),
+			ExprKind::Function(function) | ExprKind::Lambda(function) => *function = self.function_id(*function),
+			ExprKind::Variant { class, .. } => *class = self.class_id(*class),
+			ExprKind::Unary { operand, .. } | ExprKind::Postfix { operand, .. } | ExprKind::Coerce { value: operand, .. } => self.expr(operand),
+			ExprKind::Is { operand, ty, .. } => {
+				self.expr(operand);
+				self.ty(ty);
+			}
+			ExprKind::Binary { left, right, .. }
+			| ExprKind::Range { start: left, end: right }
+			| ExprKind::Index { object: left, index: right }
+			| ExprKind::Assign { target: left, value: right, .. } => {
+				self.expr(left);
+				self.expr(right);
+			}
+			ExprKind::If { cond, then_branch, else_branch } => {
+				self.expr(cond);
+				self.expr(then_branch);
+				self.expr(else_branch);
+			}
+			ExprKind::Block(block) => self.block(block),
+			ExprKind::Call { callee, args } => {
+				self.expr(callee);
+				self.exprs(args);
+			}
+			ExprKind::SuperCall { constructor, args } => {
+				*constructor = constructor.map(|id| self.function_id(id));
+				self.exprs(args);
+			}
+			ExprKind::New { class, constructor, args } => {
+				*class = self.class_id(*class);
+				*constructor = constructor.map(|id| self.function_id(id));
+				self.exprs(args);
+			}
+			ExprKind::Member { object, target, .. } => {
+				*target = match *target {
+					MemberTarget::Field(class) => MemberTarget::Field(self.class_id(class)),
+					MemberTarget::Method(function) => MemberTarget::Method(self.function_id(function)),
+					MemberTarget::Extension(function) => MemberTarget::Extension(self.function_id(function)),
+					target => target
+				};
+				self.expr(object);
+			}
+			ExprKind::Array(items) | ExprKind::Set(items) => self.exprs(items),
+			ExprKind::Map(entries) => {
+				for (key, value) in entries {
+					self.expr(key);
+					self.expr(value);
+				}
+			}
+			ExprKind::Await(inner) | ExprKind::Throw(inner) | ExprKind::Return(Some(inner)) => self.expr(inner)
+		}
+	}
+}
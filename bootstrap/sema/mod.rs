@@ -0,0 +1,14 @@
+pub mod aliases;
+pub mod builtins;
+pub mod check;
+pub mod coerce;
+pub mod consts;
+pub mod flow;
+pub mod hir;
+pub mod lint;
+pub mod merge;
+pub mod modules;
+pub mod processors;
+pub mod query;
+pub mod resolve;
+pub mod types;
@@ -0,0 +1,335 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::ast::Program;
+use crate::ast::stmt::{StmtKind, Visibility};
+use crate::diagnostics::Diagnostic;
+use crate::lexer::token::Span;
+use crate::sema::hir::Native;
+
+// The standard library's packages are `std` and those in it, like
+// `std.json`, which no program's own can be.
+pub const STD: &str = "std";
+
+// The standard library's package that every file imports everything from.
+pub const PRELUDE: &str = "std.prelude";
+
+// One parsed source file.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+	pub path: String,
+	pub program: Program,
+	// The package the file is in if it has no `package` statement, when that
+	// is not the root package: a dependency's files are in the one named after
+	// it, so that programs import them by that name
+	pub package: Option<String>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SymbolId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+	Function,
+	Class,
+	Interface,
+	Enum,
+	Struct,
+	TypeAlias,
+	Annotation,
+	Variable
+}
+
+impl SymbolKind {
+	pub fn is_type(self) -> bool {
+		!matches!(self, SymbolKind::Function | SymbolKind::Variable)
+	}
+}
+
+// A top-level declaration, visible to the rest of its package and, unless
+// it is `private`, to files that import it.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+	pub name: String,
+	pub kind: SymbolKind,
+	pub visibility: Visibility,
+	pub package: String,
+	pub file: usize,
+	// Index of the declaring statement in the file's program
+	pub stmt: usize,
+	pub span: Span
+}
+
+// An import in `file` that makes package `from` depend on package `to`.
+#[derive(Debug, Clone)]
+pub struct PackageImport {
+	pub from: String,
+	pub to: String,
+	pub file: usize,
+	pub span: Span
+}
+
+#[derive(Debug, Default)]
+pub struct Package {
+	pub files: Vec<usize>,
+	pub symbols: BTreeMap<String, Vec<SymbolId>>,
+	// Of a package of the standard library, the natives in it, by the name
+	// they have in it and the one the runtime has them under
+	pub natives: BTreeMap<String, String>
+}
+
+// Packages of a whole program and the declarations in each. A file joins the
+// package named by its `package` statement, or the root package `""` if it
+// has none; several files may contribute to the same package.
+//
+// Declarations within a package can refer to each other in any order, but
+// imports between packages must not form a cycle: a package's globals are
+// initialized after those of every package it imports, which a cycle makes
+// impossible. `import_cycles` reports the ones there are.
+//
+// The natives of the runtime a program is checked for are in the standard
+// library's packages: `json.parse` in `std.json` as `parse`, and those with
+// no `.` in their name in the prelude.
+#[derive(Debug, Default)]
+pub struct ModuleGraph {
+	pub packages: BTreeMap<String, Package>,
+	pub symbols: Vec<Symbol>,
+	// Imports of other packages, in source order
+	pub imports: Vec<PackageImport>,
+	file_packages: Vec<String>
+}
+
+impl ModuleGraph {
+	pub fn build(files: &[SourceFile], natives: &[Native]) -> ModuleGraph {
+		let mut graph = ModuleGraph::default();
+
+		for native in natives {
+			let (package, name) = match native.name.rsplit_once('.') {
+				Some((module, name)) => (format!("{}.{}", STD, module), name),
+				None => (PRELUDE.to_string(), native.name.as_str())
+			};
+			graph.packages.entry(package).or_default().natives.insert(name.to_string(), native.name.clone());
+		}
+
+		for (file, source) in files.iter().enumerate() {
+			let package = file_package(source);
+			graph.packages.entry(package.clone()).or_default().files.push(file);
+			graph.file_packages.push(package.clone());
+
+			for (index, stmt) in source.program.stmts.iter().enumerate() {
+				let Some((name, kind, visibility, span)) = declared_symbol(&stmt.kind) else {
+					continue;
+				};
+				let id = SymbolId(graph.symbols.len() as u32);
+				graph.symbols.push(Symbol { name: name.clone(), kind, visibility, package: package.clone(), file, stmt: index, span });
+				graph.packages.entry(package.clone()).or_default().symbols.entry(name).or_default().push(id);
+			}
+		}
+
+		// Imports can name any package, so they are looked at once all are known
+		for (file, source) in files.iter().enumerate() {
+			for stmt in &source.program.stmts {
+				if let StmtKind::Import(decl) = &stmt.kind
+					&& let Some(to) = graph.imported_package(&decl.path, decl.wildcard)
+					&& to != graph.file_packages[file]
+				{
+					let from = graph.file_packages[file].clone();
+					graph.imports.push(PackageImport { from, to, file, span: decl.span });
+				}
+			}
+		}
+
+		graph
+	}
+
+	// The package an import refers to or takes a declaration from. Imports
+	// of missing packages are reported by the resolver.
+	fn imported_package(&self, path: &[String], wildcard: bool) -> Option<String> {
+		let full = path.join(".");
+		if wildcard {
+			return self.has_package(&full).then_some(full);
+		}
+		let (name, prefix) = path.split_last()?;
+		let prefix = prefix.join(".");
+		if !prefix.is_empty() && (self.lookup(&prefix, name).is_some() || self.native(&prefix, name).is_some()) {
+			Some(prefix)
+		} else {
+			self.has_package(&full).then_some(full)
+		}
+	}
+
+	// Cycles of packages importing each other, each reported once at the
+	// import that starts it, with the whole path spelled out.
+	pub fn import_cycles(&self, files: &[SourceFile]) -> Vec<Diagnostic> {
+		let mut edges: BTreeMap<&str, Vec<&PackageImport>> = BTreeMap::new();
+		for import in &self.imports {
+			let out = edges.entry(&import.from).or_default();
+			if !out.iter().any(|edge| edge.to == import.to) {
+				out.push(import);
+			}
+		}
+
+		let mut cycles = Vec::new();
+		let mut done = HashSet::new();
+		for package in edges.keys() {
+			let mut path = Vec::new();
+			find_cycles(package, &edges, &mut path, &mut done, &mut cycles);
+		}
+
+		let mut seen = HashSet::new();
+		let mut errors = Vec::new();
+		for mut cycle in cycles {
+			// The same cycle is found from each package on it
+			let start = (0..cycle.len()).min_by_key(|&index| &cycle[index].from).unwrap_or(0);
+			cycle.rotate_left(start);
+			let mut key: Vec<&str> = cycle.iter().map(|edge| edge.from.as_str()).collect();
+			key.sort();
+			if !seen.insert(key) {
+				continue;
+			}
+
+			let steps: Vec<String> = cycle
+				.iter()
+				.map(|edge| {
+					let span = edge.span;
+					let path = &files[edge.file].path;
+					format!("'{}' imports '{}' at line {} column {} of {}", edge.from, edge.to, span.line, span.column, path)
+				})
+				.collect();
+			let message = format!(
+				"Packages import each other in a cycle: {}; move what they share into a package of its own",
+				steps.join(", then ")
+			);
+			errors.push(Diagnostic::error(message, cycle[0].span).with_code("E0207").in_file(cycle[0].file));
+		}
+		errors
+	}
+
+	pub fn symbol(&self, id: SymbolId) -> &Symbol {
+		&self.symbols[id.0 as usize]
+	}
+
+	pub fn package_of(&self, file: usize) -> &str {
+		&self.file_packages[file]
+	}
+
+	pub fn lookup(&self, package: &str, name: &str) -> Option<&[SymbolId]> {
+		self.packages.get(package)?.symbols.get(name).map(Vec::as_slice)
+	}
+
+	// The name the runtime has the native `name` of `package` under.
+	pub fn native(&self, package: &str, name: &str) -> Option<&str> {
+		self.packages.get(package)?.natives.get(name).map(String::as_str)
+	}
+
+	pub fn has_package(&self, name: &str) -> bool {
+		self.packages.contains_key(name)
+	}
+
+	// True for `a` and `a.b` when package `a.b.c` exists, so qualified names
+	// can be told apart from member access on a value.
+	pub fn is_package_prefix(&self, name: &str) -> bool {
+		let prefix = format!("{}.", name);
+		self.packages.keys().any(|package| package == name || package.starts_with(&prefix))
+	}
+
+	pub fn package_names(&self) -> impl Iterator<Item = &str> {
+		self.packages.keys().map(String::as_str).filter(|name| !name.is_empty())
+	}
+
+	// The packages with files, in waves that only import packages of the
+	// waves before them, each in order by name.
+	pub fn waves(&self) -> Vec<Vec<&str>> {
+		let mut left: Vec<&str> = self.packages.iter().filter(|(_, package)| !package.files.is_empty()).map(|(name, _)| name.as_str()).collect();
+		let mut done: HashSet<&str> = self.packages.iter().filter(|(_, package)| package.files.is_empty()).map(|(name, _)| name.as_str()).collect();
+		let mut waves = Vec::new();
+		while !left.is_empty() {
+			let ready = |package: &str| self.imports.iter().filter(|import| import.from == package).all(|import| done.contains(import.to.as_str()));
+			let mut wave: Vec<&str> = left.iter().copied().filter(|package| ready(package)).collect();
+			// Cycles are reported before anything is checked, so any left
+			// here have nothing more to wait for
+			if wave.is_empty() {
+				wave = left.clone();
+			}
+			left.retain(|package| !wave.contains(package));
+			done.extend(&wave);
+			waves.push(wave);
+		}
+		waves
+	}
+
+	// Every file, with the files of each package after those of the packages
+	// it imports, which is the order their globals are initialized in. Files
+	// of one package keep their order.
+	pub fn initialization_order(&self) -> Vec<usize> {
+		let mut order = Vec::new();
+		let mut visited = HashSet::new();
+		for package in self.packages.keys() {
+			self.initialize(package, &mut visited, &mut order);
+		}
+		order
+	}
+
+	fn initialize<'g>(&'g self, package: &'g str, visited: &mut HashSet<&'g str>, order: &mut Vec<usize>) {
+		if !visited.insert(package) {
+			return;
+		}
+		for import in self.imports.iter().filter(|import| import.from == package) {
+			self.initialize(&import.to, visited, order);
+		}
+		order.extend(&self.packages[package].files);
+	}
+}
+
+// Depth-first search along imports, collecting each path that leads back to a
+// package already on it. Packages whose imports have all been followed are
+// not searched again.
+fn find_cycles<'g>(
+	package: &'g str,
+	edges: &BTreeMap<&'g str, Vec<&'g PackageImport>>,
+	path: &mut Vec<&'g PackageImport>,
+	done: &mut HashSet<&'g str>,
+	cycles: &mut Vec<Vec<&'g PackageImport>>
+) {
+	if done.contains(package) {
+		return;
+	}
+	for &edge in edges.get(package).map(Vec::as_slice).unwrap_or_default() {
+		if let Some(start) = path.iter().position(|step| step.from == edge.to) {
+			let mut cycle = path[start..].to_vec();
+			cycle.push(edge);
+			cycles.push(cycle);
+		} else {
+			path.push(edge);
+			find_cycles(&edge.to, edges, path, done, cycles);
+			path.pop();
+		}
+	}
+	done.insert(package);
+}
+
+fn file_package(file: &SourceFile) -> String {
+	file.program
+		.stmts
+		.iter()
+		.find_map(|stmt| match &stmt.kind {
+			StmtKind::Package(path) => Some(path.join(".")),
+			_ => None
+		})
+		.or_else(|| file.package.clone())
+		.unwrap_or_default()
+}
+
+fn declared_symbol(kind: &StmtKind) -> Option<(String, SymbolKind, Visibility, Span)> {
+	let symbol = match kind {
+		StmtKind::Function(decl) => (decl.name.clone(), SymbolKind::Function, decl.modifiers.visibility, decl.name_span),
+		StmtKind::Class(decl) => (decl.name.clone(), SymbolKind::Class, decl.modifiers.visibility, decl.name_span),
+		StmtKind::Interface(decl) => (decl.name.clone(), SymbolKind::Interface, decl.modifiers.visibility, decl.name_span),
+		StmtKind::Enum(decl) => (decl.name.clone(), SymbolKind::Enum, decl.modifiers.visibility, decl.name_span),
+		StmtKind::Struct(decl) => (decl.name.clone(), SymbolKind::Struct, decl.modifiers.visibility, decl.name_span),
+		StmtKind::TypeAlias(decl) => (decl.name.clone(), SymbolKind::TypeAlias, decl.modifiers.visibility, decl.name_span),
+		StmtKind::Annotation(decl) => (decl.name.clone(), SymbolKind::Annotation, Visibility::Public, decl.span),
+		StmtKind::Var(decl) => (decl.name.clone(), SymbolKind::Variable, Visibility::Public, decl.name_span),
+		_ => return None
+	};
+	Some(symbol)
+}
@@ -0,0 +1,378 @@
+use std::collections::HashMap;
+
+use crate::diagnostics::Diagnostic;
+use crate::lexer::token::Span;
+use crate::sema::hir::{Annotation, Block, Expr, ExprKind, FnKind, MemberTarget, Pattern, Program, Stmt, StmtKind};
+use crate::sema::types::{FnId, Type, TypeId};
+
+// Annotation processors run over the checked program once it has no errors.
+// Each one handles a single annotation: it is shown every declaration
+// carrying it, in source order, and then gets a last look at the whole
+// program to report uses or record what it collected. New processors
+// implement `Processor` and are passed to `check_with`; `check` runs
+// `defaults()`.
+pub trait Processor {
+	// The annotation's name, as written after `@`
+	fn annotation(&self) -> &str;
+
+	fn declaration(&mut self, program: &Program, target: Target, annotation: &Annotation, report: &mut Report);
+
+	fn finish(&mut self, _program: &mut Program, _report: &mut Report) {}
+}
+
+// A declaration an annotation is written on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Target {
+	Class(TypeId),
+	Function(FnId),
+	// By its index in the class's fields
+	Field(TypeId, usize)
+}
+
+impl Target {
+	pub fn file(self, program: &Program) -> usize {
+		match self {
+			Target::Class(id) | Target::Field(id, _) => program.class(id).file,
+			Target::Function(id) => program.function(id).file
+		}
+	}
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+	pub errors: Vec<Diagnostic>,
+	pub warnings: Vec<Diagnostic>
+}
+
+pub fn defaults() -> Vec<Box<dyn Processor>> {
+	vec![Box::new(Deprecated::default()), Box::new(Tests::default())]
+}
+
+pub fn run(program: &mut Program, processors: &mut [Box<dyn Processor>]) -> Report {
+	let mut annotated: Vec<(usize, Target, &Annotation)> = Vec::new();
+	for (index, class) in program.classes.iter().enumerate() {
+		let id = TypeId(index as u32);
+		annotated.extend(class.annotations.iter().map(|annotation| (class.file, Target::Class(id), annotation)));
+		for (field, declared) in class.fields.iter().enumerate() {
+			annotated.extend(declared.annotations.iter().map(|annotation| (class.file, Target::Field(id, field), annotation)));
+		}
+	}
+	for (index, function) in program.functions.iter().enumerate() {
+		let target = Target::Function(FnId(index as u32));
+		annotated.extend(function.annotations.iter().map(|annotation| (function.file, target, annotation)));
+	}
+	annotated.sort_by_key(|(file, _, annotation)| (*file, annotation.span.line, annotation.span.column));
+
+	let mut report = Report::default();
+	for processor in processors.iter_mut() {
+		for (_, target, annotation) in &annotated {
+			if annotation.name == processor.annotation() {
+				processor.declaration(program, *target, annotation, &mut report);
+			}
+		}
+	}
+	for processor in processors.iter_mut() {
+		processor.finish(program, &mut report);
+	}
+	report
+}
+
+// `@Deprecated` or `@Deprecated("use g instead")`. Every use of the
+// declaration gets a warning, except from code that is deprecated itself.
+// `@Suppress("deprecation")` turns them off like any other warning.
+#[derive(Debug, Default)]
+pub struct Deprecated {
+	// With the message to add to the warning, if there is one
+	targets: HashMap<Target, Option<String>>
+}
+
+impl Processor for Deprecated {
+	fn annotation(&self) -> &str {
+		"Deprecated"
+	}
+
+	fn declaration(&mut self, _program: &Program, target: Target, annotation: &Annotation, _report: &mut Report) {
+		self.targets.insert(target, annotation.text().map(str::to_string));
+	}
+
+	fn finish(&mut self, program: &mut Program, report: &mut Report) {
+		if self.targets.is_empty() {
+			return;
+		}
+		let mut uses = Uses { program, targets: &self.targets, quiet: HashMap::new(), file: 0, warnings: Vec::new() };
+		uses.program();
+		report.warnings.extend(uses.warnings);
+	}
+}
+
+// Follows the program looking for uses of deprecated declarations.
+struct Uses<'a> {
+	program: &'a Program,
+	targets: &'a HashMap<Target, Option<String>>,
+	// Lambdas and local functions created in deprecated code
+	quiet: HashMap<FnId, bool>,
+	// The file the code being followed is in
+	file: usize,
+	warnings: Vec<Diagnostic>
+}
+
+impl<'a> Uses<'a> {
+	fn program(&mut self) {
+		let program = self.program;
+		for (index, file) in program.files.iter().enumerate() {
+			self.file = index;
+			self.stmts(&file.body, false);
+		}
+		for (index, class) in program.classes.iter().enumerate() {
+			self.file = class.file;
+			let class_quiet = self.class_quiet(TypeId(index as u32));
+			for (field, declared) in class.fields.iter().enumerate() {
+				let quiet = class_quiet || self.targets.contains_key(&Target::Field(TypeId(index as u32), field));
+				if let Some(init) = &declared.init {
+					self.expr(init, quiet);
+				}
+			}
+			for variant in &class.variants {
+				self.exprs(&variant.args, class_quiet);
+			}
+		}
+		// Lambdas and local functions come after the function they are in,
+		// so whether they are quiet is known by the time they are reached
+		for (index, function) in program.functions.iter().enumerate() {
+			let quiet = self.function_quiet(FnId(index as u32));
+			self.file = function.file;
+			for param in &function.params {
+				if let Some(default) = &param.default {
+					self.expr(default, quiet);
+				}
+			}
+			if let Some(body) = &function.body {
+				self.block(body, quiet);
+			}
+		}
+	}
+
+	fn class_quiet(&self, id: TypeId) -> bool {
+		self.targets.contains_key(&Target::Class(id))
+	}
+
+	fn function_quiet(&self, id: FnId) -> bool {
+		let function = self.program.function(id);
+		let class = match function.kind {
+			FnKind::Method(class) | FnKind::Constructor(class) | FnKind::Getter(class) | FnKind::Setter(class) | FnKind::Static(class) => Some(class),
+			_ => None
+		};
+		self.targets.contains_key(&Target::Function(id))
+			|| class.is_some_and(|class| self.class_quiet(class))
+			|| self.quiet.get(&id).copied().unwrap_or(false)
+	}
+
+	fn used(&mut self, target: Target, span: Span, quiet: bool) {
+		if quiet {
+			return;
+		}
+		let Some(message) = self.targets.get(&target) else {
+			return;
+		};
+		let name = match target {
+			Target::Class(id) => &self.program.class(id).name,
+			Target::Function(id) => &self.program.function(id).name,
+			Target::Field(id, index) => &self.program.class(id).fields[index].name
+		};
+		let warning = match message {
+			Some(message) => format!("'{}' is deprecated: {}", name, message),
+			None => format!("'{}' is deprecated", name)
+		};
+		self.warnings.push(Diagnostic::warning(warning, span).with_code("W0005").in_file(self.file));
+	}
+
+	fn block(&mut self, block: &Block, quiet: bool) {
+		self.stmts(&block.stmts, quiet);
+	}
+
+	fn stmts(&mut self, stmts: &[Stmt], quiet: bool) {
+		for stmt in stmts {
+			self.stmt(stmt, quiet);
+		}
+	}
+
+	fn stmt(&mut self, stmt: &Stmt, quiet: bool) {
+		match &stmt.kind {
+			StmtKind::Expr(expr) | StmtKind::Yield(expr) | StmtKind::Throw(expr) | StmtKind::Return(Some(expr)) => self.expr(expr, quiet),
+			StmtKind::Var { init, .. } => {
+				if let Some(init) = init {
+					self.expr(init, quiet);
+				}
+			}
+			StmtKind::Block(block) | StmtKind::Loop { body: block } => self.block(block, quiet),
+			StmtKind::If { cond, then_branch, else_branch } => {
+				self.expr(cond, quiet);
+				self.block(then_branch, quiet);
+				if let Some(else_branch) = else_branch {
+					self.stmt(else_branch, quiet);
+				}
+			}
+			StmtKind::While { cond, body } => {
+				self.expr(cond, quiet);
+				self.block(body, quiet);
+			}
+			StmtKind::For { iterable, body, .. } => {
+				self.expr(iterable, quiet);
+				self.block(body, quiet);
+			}
+			StmtKind::Labeled { body, .. } => self.stmt(body, quiet),
+			StmtKind::Break(_) | StmtKind::Continue(_) | StmtKind::Return(None) => {}
+			StmtKind::Try { body, catches, finally } => {
+				self.block(body, quiet);
+				for catch in catches {
+					self.block(&catch.body, quiet);
+				}
+				if let Some(finally) = finally {
+					self.block(finally, quiet);
+				}
+			}
+			StmtKind::Switch { subject, cases, default, .. } => {
+				self.expr(subject, quiet);
+				for case in cases {
+					for pattern in &case.patterns {
+						match pattern {
+							Pattern::Value(value) => self.expr(value, quiet),
+							Pattern::Type { ty: Type::Class { id, .. }, span } => self.used(Target::Class(*id), *span, quiet),
+							Pattern::Type { .. } => {}
+						}
+					}
+					self.block(&case.body, quiet);
+				}
+				if let Some(default) = default {
+					self.block(default, quiet);
+				}
+			}
+			StmtKind::Function { function, .. } => {
+				self.quiet.insert(*function, quiet);
+			}
+		}
+	}
+
+	fn expr(&mut self, expr: &Expr, quiet: bool) {
+		match &expr.kind {
+			ExprKind::Literal(_)
+			| ExprKind::Var(_)
+			| ExprKind::Builtin(_)
+			| ExprKind::Native(_)
+			| ExprKind::This
+			| ExprKind::Super
+			| ExprKind::BackingField(_) => {}
+			ExprKind::Function(function) => self.used(Target::Function(*function), expr.span, quiet),
+			ExprKind::Variant { class, .. } => self.used(Target::Class(*class), expr.span, quiet),
+			ExprKind::Unary { operand, .. } | ExprKind::Postfix { operand, .. } | ExprKind::Coerce { value: operand, .. } => {
+				self.expr(operand, quiet)
+			}
+			ExprKind::Is { operand, ty, .. } => {
+				if let Type::Class { id, .. } = ty.non_null() {
+					self.used(Target::Class(*id), expr.span, quiet);
+				}
+				self.expr(operand, quiet);
+			}
+			ExprKind::Binary { left, right, .. }
+			| ExprKind::Range { start: left, end: right }
+			| ExprKind::Index { object: left, index: right }
+			| ExprKind::Assign { target: left, value: right, .. } => {
+				self.expr(left, quiet);
+				self.expr(right, quiet);
+			}
+			ExprKind::If { cond, then_branch, else_branch } => {
+				self.expr(cond, quiet);
+				self.expr(then_branch, quiet);
+				self.expr(else_branch, quiet);
+			}
+			ExprKind::Block(block) => self.block(block, quiet),
+			ExprKind::Call { callee, args } => {
+				self.expr(callee, quiet);
+				self.exprs(args, quiet);
+			}
+			ExprKind::SuperCall { constructor, args } => {
+				if let Some(constructor) = constructor {
+					self.used(Target::Function(*constructor), expr.span, quiet);
+				}
+				self.exprs(args, quiet);
+			}
+			ExprKind::New { class, constructor, args } => {
+				self.used(Target::Class(*class), expr.span, quiet);
+				if let Some(constructor) = constructor {
+					self.used(Target::Function(*constructor), expr.span, quiet);
+				}
+				self.exprs(args, quiet);
+			}
+			ExprKind::Member { object, name, target, .. } => {
+				match target {
+					MemberTarget::Field(class) => {
+						if let Some(index) = self.program.class(*class).fields.iter().position(|field| field.name == *name) {
+							self.used(Target::Field(*class, index), expr.span, quiet);
+						}
+					}
+					MemberTarget::Method(function) | MemberTarget::Extension(function) => {
+						self.used(Target::Function(*function), expr.span, quiet);
+					}
+					MemberTarget::Builtin | MemberTarget::Dynamic => {}
+				}
+				self.expr(object, quiet);
+			}
+			ExprKind::Array(items) | ExprKind::Set(items) => self.exprs(items, quiet),
+			ExprKind::Map(entries) => {
+				for (key, value) in entries {
+					self.expr(key, quiet);
+					self.expr(value, quiet);
+				}
+			}
+			ExprKind::Lambda(function) => {
+				self.quiet.insert(*function, quiet);
+			}
+			ExprKind::Await(inner) | ExprKind::Throw(inner) | ExprKind::Return(Some(inner)) => self.expr(inner, quiet),
+			ExprKind::Return(None) => {}
+		}
+	}
+
+	fn exprs(&mut self, exprs: &[Expr], quiet: bool) {
+		for expr in exprs {
+			self.expr(expr, quiet);
+		}
+	}
+}
+
+// `@Test` marks a top-level function for the test runner, which calls it
+// without arguments. The ones found end up in `Program::tests`.
+#[derive(Debug, Default)]
+pub struct Tests {
+	tests: Vec<FnId>
+}
+
+impl Processor for Tests {
+	fn annotation(&self) -> &str {
+		"Test"
+	}
+
+	fn declaration(&mut self, program: &Program, target: Target, annotation: &Annotation, report: &mut Report) {
+		let Target::Function(id) = target else {
+			let error = Diagnostic::error("Only functions can be marked '@Test'", annotation.span).with_code("E0601");
+			report.errors.push(error.in_file(target.file(program)));
+			return;
+		};
+		let function = program.function(id);
+		let problem = if function.kind != FnKind::Function {
+			"only top-level functions can be tests"
+		} else if !function.type_params.is_empty() {
+			"a test cannot have type parameters"
+		} else if function.params.iter().any(|param| param.default.is_none() && !param.variadic) {
+			"the test runner calls tests without arguments"
+		} else {
+			self.tests.push(id);
+			return;
+		};
+		let error = Diagnostic::error(format!("'{}' cannot be marked '@Test'; {}", function.name, problem), annotation.span);
+		report.errors.push(error.with_code("E0601").in_file(function.file));
+	}
+
+	fn finish(&mut self, program: &mut Program, _report: &mut Report) {
+		program.tests = std::mem::take(&mut self.tests);
+	}
+}
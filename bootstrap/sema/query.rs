@@ -0,0 +1,347 @@
+use crate::lexer::token::Span;
+use crate::sema::hir::{Block, Expr, ExprKind, FnKind, ImportTarget, MemberTarget, Pattern, Program, Stmt, StmtKind, Variable};
+use crate::sema::types::{FnId, GlobalId, LocalId, Type, TypeId};
+
+// Questions editors and refactoring tools ask about a checked program: what
+// the name under the cursor refers to, and everywhere a declaration is used.
+// Files are indices into `Program::files` and offsets are byte offsets into
+// the file's source, like `Span::start`. Each query walks the whole program,
+// so callers asking many questions of one program should keep an `Index`.
+
+// A declaration a name can refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Symbol {
+	Class(TypeId),
+	Function(FnId),
+	// By index in the class's fields or variants
+	Field(TypeId, usize),
+	Variant(TypeId, usize),
+	Global(GlobalId),
+	Local(LocalId)
+}
+
+impl Symbol {
+	// The name it is declared with. Constructors go by their class's, as
+	// they are called.
+	pub fn name(self, program: &Program) -> &str {
+		match self {
+			Symbol::Class(id) => &program.class(id).name,
+			Symbol::Function(id) => match program.function(id).kind {
+				FnKind::Constructor(class) => &program.class(class).name,
+				_ => &program.function(id).name
+			},
+			Symbol::Field(class, index) => &program.class(class).fields[index].name,
+			Symbol::Variant(class, index) => &program.class(class).variants[index].name,
+			Symbol::Global(id) => &program.global(id).name,
+			Symbol::Local(id) => &program.local(id).name
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+	pub file: usize,
+	pub span: Span
+}
+
+// Where the declaration of the name at `offset` is, along with what it is.
+// On a declaration's own name this is the declaration itself.
+pub fn definition_at(program: &Program, file: usize, offset: usize) -> Option<(Symbol, Location)> {
+	Index::new(program).definition_at(file, offset)
+}
+
+// Every use of `symbol` in source order, not counting its declaration.
+pub fn references_of(program: &Program, symbol: Symbol) -> Vec<Location> {
+	Index::new(program).references_of(symbol)
+}
+
+// Every name in a program and what it refers to.
+pub struct Index {
+	declarations: Vec<(Symbol, Location)>,
+	uses: Vec<(Symbol, Location)>,
+	// Of every expression
+	types: Vec<(Location, Type)>
+}
+
+impl Index {
+	pub fn new(program: &Program) -> Index {
+		let mut walk = Walk { program, file: 0, index: Index { declarations: Vec::new(), uses: Vec::new(), types: Vec::new() } };
+		walk.program();
+		let index = &mut walk.index;
+		index.uses.sort_by_key(|(_, location)| (location.file, location.span.start));
+		// Types written in signatures are lowered more than once
+		index.uses.dedup();
+		walk.index
+	}
+
+	pub fn symbol_at(&self, file: usize, offset: usize) -> Option<Symbol> {
+		self.symbols_at(file, offset).first().copied()
+	}
+
+	// What the name at `offset` refers to, which is more than one symbol
+	// only for an import of overloaded functions.
+	pub fn symbols_at(&self, file: usize, offset: usize) -> Vec<Symbol> {
+		self.name_at(file, offset).map(|(_, symbols)| symbols).unwrap_or_default()
+	}
+
+	// The span of the name at `offset`, along with what it refers to.
+	pub fn name_at(&self, file: usize, offset: usize) -> Option<(Span, Vec<Symbol>)> {
+		// Names nest, as in `a.b`, so the innermost one is the one meant. The
+		// end is included so that a cursor right after a name still finds it.
+		let around: Vec<&(Symbol, Location)> = self
+			.declarations
+			.iter()
+			.chain(&self.uses)
+			.filter(|(_, location)| location.file == file && location.span.start <= offset && offset <= location.span.end)
+			.collect();
+		let innermost = around.iter().map(|(_, location)| location.span).min_by_key(|span| span.end - span.start)?;
+		Some((innermost, around.into_iter().filter(|(_, location)| location.span == innermost).map(|(symbol, _)| *symbol).collect()))
+	}
+
+	// The type of the innermost expression at `offset`, and its span. Of
+	// expressions with the same span, like a value and its coercion, the
+	// inner one is meant.
+	pub fn type_at(&self, file: usize, offset: usize) -> Option<(&Type, Span)> {
+		self.types
+			.iter()
+			.rev()
+			.filter(|(location, _)| location.file == file && location.span.start <= offset && offset <= location.span.end)
+			.min_by_key(|(location, _)| location.span.end - location.span.start)
+			.map(|(location, ty)| (ty, location.span))
+	}
+
+	pub fn definition(&self, symbol: Symbol) -> Option<Location> {
+		self.declarations.iter().find(|(declared, _)| *declared == symbol).map(|(_, location)| *location)
+	}
+
+	pub fn definition_at(&self, file: usize, offset: usize) -> Option<(Symbol, Location)> {
+		let symbol = self.symbol_at(file, offset)?;
+		Some((symbol, self.definition(symbol)?))
+	}
+
+	// Every name in `file`, with what it refers to and whether it is the
+	// declaration's own, in no particular order. A name's span can take in
+	// more than the name, like all of `Color.Red` for `Red`.
+	pub fn names(&self, file: usize) -> impl Iterator<Item = (Symbol, Span, bool)> + '_ {
+		let declarations = self.declarations.iter().map(|(symbol, location)| (*symbol, *location, true));
+		let uses = self.uses.iter().map(|(symbol, location)| (*symbol, *location, false));
+		declarations.chain(uses).filter(move |(_, location, _)| location.file == file).map(|(symbol, location, declared)| (symbol, location.span, declared))
+	}
+
+	pub fn references_of(&self, symbol: Symbol) -> Vec<Location> {
+		self.uses.iter().filter(|(used, _)| *used == symbol).map(|(_, location)| *location).collect()
+	}
+}
+
+struct Walk<'a> {
+	program: &'a Program,
+	// The file the code being walked is in
+	file: usize,
+	index: Index
+}
+
+impl<'a> Walk<'a> {
+	fn program(&mut self) {
+		let program = self.program;
+		for (index, file) in program.files.iter().enumerate() {
+			self.file = index;
+			self.stmts(&file.body);
+		}
+		for (index, global) in program.globals.iter().enumerate() {
+			self.declared(Symbol::Global(GlobalId(index as u32)), global.file, global.span);
+		}
+		for (index, class) in program.classes.iter().enumerate() {
+			let id = TypeId(index as u32);
+			self.file = class.file;
+			self.declared(Symbol::Class(id), class.file, class.span);
+			for (field, declared) in class.fields.iter().enumerate() {
+				self.declared(Symbol::Field(id, field), class.file, declared.span);
+				if let Some(init) = &declared.init {
+					self.expr(init);
+				}
+			}
+			for (variant, declared) in class.variants.iter().enumerate() {
+				self.declared(Symbol::Variant(id, variant), class.file, declared.span);
+				self.exprs(&declared.args);
+			}
+		}
+		for (index, function) in program.functions.iter().enumerate() {
+			self.file = function.file;
+			// Lambdas have no name, and accessors go by their field's
+			if matches!(function.kind, FnKind::Function | FnKind::Method(_) | FnKind::Constructor(_) | FnKind::Static(_) | FnKind::Extension) {
+				self.declared(Symbol::Function(FnId(index as u32)), function.file, function.span);
+			}
+			for param in &function.params {
+				self.local(param.local);
+				if let Some(default) = &param.default {
+					self.expr(default);
+				}
+			}
+			if let Some(body) = &function.body {
+				self.block(body);
+			}
+		}
+		for type_use in &program.type_uses {
+			self.index.uses.push((Symbol::Class(type_use.class), Location { file: type_use.file, span: type_use.span }));
+		}
+		for import in &program.imports {
+			let symbol = match import.target {
+				ImportTarget::Class(id) => Symbol::Class(id),
+				ImportTarget::Function(id) => Symbol::Function(id),
+				ImportTarget::Global(id) => Symbol::Global(id)
+			};
+			self.index.uses.push((symbol, Location { file: import.file, span: import.span }));
+		}
+	}
+
+	fn declared(&mut self, symbol: Symbol, file: usize, span: Span) {
+		self.index.declarations.push((symbol, Location { file, span }));
+	}
+
+	fn local(&mut self, local: LocalId) {
+		self.declared(Symbol::Local(local), self.file, self.program.local(local).span);
+	}
+
+	fn used(&mut self, symbol: Symbol, span: Span) {
+		self.index.uses.push((symbol, Location { file: self.file, span }));
+	}
+
+	fn block(&mut self, block: &Block) {
+		self.stmts(&block.stmts);
+	}
+
+	fn stmts(&mut self, stmts: &[Stmt]) {
+		for stmt in stmts {
+			self.stmt(stmt);
+		}
+	}
+
+	fn stmt(&mut self, stmt: &Stmt) {
+		match &stmt.kind {
+			StmtKind::Expr(expr) | StmtKind::Yield(expr) | StmtKind::Throw(expr) | StmtKind::Return(Some(expr)) => self.expr(expr),
+			StmtKind::Var { var, init } => {
+				if let Variable::Local(local) = var {
+					self.local(*local);
+				}
+				if let Some(init) = init {
+					self.expr(init);
+				}
+			}
+			StmtKind::Block(block) | StmtKind::Loop { body: block } => self.block(block),
+			StmtKind::If { cond, then_branch, else_branch } => {
+				self.expr(cond);
+				self.block(then_branch);
+				if let Some(else_branch) = else_branch {
+					self.stmt(else_branch);
+				}
+			}
+			StmtKind::While { cond, body } => {
+				self.expr(cond);
+				self.block(body);
+			}
+			StmtKind::For { bindings, iterable, body, .. } => {
+				for binding in bindings {
+					self.local(*binding);
+				}
+				self.expr(iterable);
+				self.block(body);
+			}
+			StmtKind::Labeled { body, .. } => self.stmt(body),
+			StmtKind::Break(_) | StmtKind::Continue(_) | StmtKind::Return(None) => {}
+			StmtKind::Try { body, catches, finally } => {
+				self.block(body);
+				for catch in catches {
+					self.local(catch.local);
+					self.block(&catch.body);
+				}
+				if let Some(finally) = finally {
+					self.block(finally);
+				}
+			}
+			StmtKind::Switch { subject, cases, default, .. } => {
+				self.expr(subject);
+				for case in cases {
+					for pattern in &case.patterns {
+						match pattern {
+							Pattern::Value(value) => self.expr(value),
+							Pattern::Type { ty: Type::Class { id, .. }, span } => self.used(Symbol::Class(*id), *span),
+							Pattern::Type { .. } => {}
+						}
+					}
+					self.block(&case.body);
+				}
+				if let Some(default) = default {
+					self.block(default);
+				}
+			}
+			StmtKind::Function { local, .. } => self.local(*local)
+		}
+	}
+
+	fn expr(&mut self, expr: &Expr) {
+		self.index.types.push((Location { file: self.file, span: expr.span }, expr.ty.clone()));
+		match &expr.kind {
+			ExprKind::Literal(_) | ExprKind::Builtin(_) | ExprKind::Native(_) | ExprKind::This | ExprKind::Super | ExprKind::BackingField(_) => {}
+			ExprKind::Lambda(_) | ExprKind::Return(None) => {}
+			ExprKind::Var(Variable::Local(local)) => self.used(Symbol::Local(*local), expr.span),
+			ExprKind::Var(Variable::Global(global)) => self.used(Symbol::Global(*global), expr.span),
+			ExprKind::Function(function) => self.used(Symbol::Function(*function), expr.span),
+			ExprKind::Variant { class, index } => self.used(Symbol::Variant(*class, *index), expr.span),
+			ExprKind::Unary { operand, .. }
+			| ExprKind::Postfix { operand, .. }
+			| ExprKind::Is { operand, .. }
+			| ExprKind::Coerce { value: operand, .. } => self.expr(operand),
+			ExprKind::Binary { left, right, .. }
+			| ExprKind::Range { start: left, end: right }
+			| ExprKind::Index { object: left, index: right }
+			| ExprKind::Assign { target: left, value: right, .. } => {
+				self.expr(left);
+				self.expr(right);
+			}
+			ExprKind::If { cond, then_branch, else_branch } => {
+				self.expr(cond);
+				self.expr(then_branch);
+				self.expr(else_branch);
+			}
+			ExprKind::Block(block) => self.block(block),
+			ExprKind::Call { callee, args } => {
+				self.expr(callee);
+				self.exprs(args);
+			}
+			ExprKind::SuperCall { constructor, args } | ExprKind::New { constructor, args, .. } => {
+				if let Some(constructor) = constructor {
+					self.used(Symbol::Function(*constructor), expr.span);
+				}
+				self.exprs(args);
+			}
+			ExprKind::Member { object, name, name_span, target, .. } => {
+				match target {
+					MemberTarget::Field(class) => {
+						if let Some(index) = self.program.class(*class).fields.iter().position(|field| field.name == *name) {
+							self.used(Symbol::Field(*class, index), *name_span);
+						}
+					}
+					MemberTarget::Method(function) | MemberTarget::Extension(function) => {
+						self.used(Symbol::Function(*function), *name_span);
+					}
+					MemberTarget::Builtin | MemberTarget::Dynamic => {}
+				}
+				self.expr(object);
+			}
+			ExprKind::Array(items) | ExprKind::Set(items) => self.exprs(items),
+			ExprKind::Map(entries) => {
+				for (key, value) in entries {
+					self.expr(key);
+					self.expr(value);
+				}
+			}
+			ExprKind::Await(inner) | ExprKind::Throw(inner) | ExprKind::Return(Some(inner)) => self.expr(inner)
+		}
+	}
+
+	fn exprs(&mut self, exprs: &[Expr]) {
+		for expr in exprs {
+			self.expr(expr);
+		}
+	}
+}
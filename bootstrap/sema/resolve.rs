@@ -0,0 +1,732 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::NodeId;
+use crate::ast::expr::{Expr, ExprKind, LambdaBody};
+use crate::ast::stmt::{Block, FnBody, FnDecl, ImportDecl, Member, Param, Stmt, StmtKind, Visibility};
+use crate::ast::types::{TypeExpr, TypeExprKind, TypeParam};
+use crate::ast::visitor::{self, Visitor};
+use crate::diagnostics::{Diagnostic, suggest};
+use crate::lexer::token::Span;
+use crate::sema::modules::{ModuleGraph, PRELUDE, STD, SourceFile, SymbolId};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+	// A `val`, parameter, loop binding, catch variable or local declaration
+	Local(NodeId),
+	TypeParam(NodeId),
+	// A field, method or enum variant of the enclosing type, used without `this.`
+	Member,
+	// A top-level declaration; overloads share a name, this is the first
+	Symbol(SymbolId),
+	Package(String),
+	// A native of the standard library, by the name the runtime has it under
+	Native(String)
+}
+
+// What each name in a file refers to. Expressions are keyed by node id; type
+// expressions have no ids and are keyed by span.
+#[derive(Debug, Default)]
+pub struct Resolutions {
+	pub exprs: HashMap<NodeId, Resolution>,
+	pub types: HashMap<Span, Resolution>,
+	// For identifiers found nowhere, the visible name closest to each, for
+	// the checker to suggest if it cannot find them either
+	pub closest: HashMap<NodeId, String>,
+	// Imports nothing in the file uses
+	pub warnings: Vec<Diagnostic>
+}
+
+#[derive(Default)]
+struct Scope {
+	values: HashMap<String, Resolution>,
+	types: HashMap<String, Resolution>
+}
+
+// Resolves the names in one file against the module graph. Lookups go from
+// the innermost scope outwards, then explicit imports, then the file's own
+// package, then wildcard imports, then the prelude. Names found nowhere are
+// left for the type checker, which knows the builtins; only import and
+// qualified-name problems are reported here.
+pub fn resolve(graph: &ModuleGraph, files: &[SourceFile], file: usize) -> Result<Resolutions, Vec<Diagnostic>> {
+	let mut resolver = Resolver {
+		graph,
+		files,
+		file,
+		package: graph.package_of(file).to_string(),
+		imports: HashMap::new(),
+		wildcards: Vec::new(),
+		used: HashSet::new(),
+		scopes: Vec::new(),
+		resolutions: Resolutions::default(),
+		errors: Vec::new(),
+		reported: HashSet::new()
+	};
+
+	for symbol in graph.symbols.iter().filter(|symbol| symbol.file == file && symbol.visibility == Visibility::Protected) {
+		let message = format!(
+			"Only class members can be 'protected'; make '{}' 'private' to keep it inside package '{}'",
+			symbol.name, symbol.package
+		);
+		resolver.error("E0300", message, symbol.span);
+	}
+
+	let stmts = &files[file].program.stmts;
+	resolver.imports(stmts);
+	for stmt in stmts {
+		match &stmt.kind {
+			StmtKind::Import(_) | StmtKind::Package(_) => {}
+			_ => resolver.visit_stmt(stmt)
+		}
+	}
+	resolver.unused_imports();
+
+	if resolver.errors.is_empty() { Ok(resolver.resolutions) } else { Err(resolver.errors) }
+}
+
+struct Resolver<'a> {
+	graph: &'a ModuleGraph,
+	files: &'a [SourceFile],
+	file: usize,
+	package: String,
+	imports: HashMap<String, (Resolution, Span)>,
+	wildcards: Vec<(String, Span)>,
+	// Import bindings and wildcard packages that names were found through
+	used: HashSet<String>,
+	scopes: Vec<Scope>,
+	resolutions: Resolutions,
+	errors: Vec<Diagnostic>,
+	// Ambiguous names already reported, so each is flagged once per file
+	reported: HashSet<String>
+}
+
+impl Resolver<'_> {
+	// ---------------------------------------------------------------------
+	// Imports
+	// ---------------------------------------------------------------------
+
+	fn imports(&mut self, stmts: &[Stmt]) {
+		let mut package_seen = false;
+		for stmt in stmts {
+			match &stmt.kind {
+				StmtKind::Package(_) if package_seen => {
+					self.error("E0206", "A file can only declare one package".to_string(), stmt.span);
+				}
+				StmtKind::Package(path) if path.first().is_some_and(|root| root == STD) => {
+					let message = format!("'{}' is the standard library's package; name this one something else", path.join("."));
+					self.error("E0212", message, stmt.span);
+					package_seen = true;
+				}
+				StmtKind::Package(_) => package_seen = true,
+				StmtKind::Import(decl) => self.import(decl),
+				_ => {}
+			}
+		}
+	}
+
+	fn import(&mut self, decl: &ImportDecl) {
+		let full = decl.path.join(".");
+
+		if decl.wildcard {
+			if self.graph.has_package(&full) {
+				if full != self.package && !self.wildcards.iter().any(|(package, _)| *package == full) {
+					self.wildcards.push((full, decl.span));
+				}
+			} else {
+				self.missing_package(&full, decl.span);
+			}
+			return;
+		}
+
+		let Some((name, prefix)) = decl.path.split_last() else {
+			return;
+		};
+		let prefix = prefix.join(".");
+
+		let resolution = if !prefix.is_empty()
+			&& let Some(symbols) = self.graph.lookup(&prefix, name)
+		{
+			Resolution::Symbol(symbols[0])
+		} else if let Some(native) = self.graph.native(&prefix, name) {
+			Resolution::Native(native.to_string())
+		} else if self.graph.has_package(&full) {
+			Resolution::Package(full)
+		} else if self.graph.has_package(&prefix) && !prefix.is_empty() {
+			let message = format!("Package '{}' has no declaration named '{}'", prefix, name);
+			let package = &self.graph.packages[&prefix];
+			let candidates = package.symbols.keys().chain(package.natives.keys()).map(String::as_str);
+			self.misspelled("E0203", message, name, decl.span, candidates);
+			return;
+		} else {
+			// `std.jsn` is a misspelled package, though `std` is not one
+			let missing = if prefix.is_empty() || self.graph.is_package_prefix(&prefix) { &full } else { &prefix };
+			self.missing_package(missing, decl.span);
+			return;
+		};
+
+		if let Resolution::Symbol(id) = resolution {
+			self.check_visible(id, decl.span);
+		}
+		let binding = decl.alias.clone().unwrap_or_else(|| name.clone());
+		if let Some((previous, span)) = self.imports.get(&binding) {
+			if *previous != resolution {
+				let message = format!("'{}' is already imported at line {} column {}", binding, span.line, span.column);
+				self.error("E0204", message, decl.span);
+			}
+			return;
+		}
+		if let Resolution::Symbol(id) = resolution
+			&& self.graph.symbol(id).package != self.package
+			&& self.graph.lookup(&self.package, &binding).is_some()
+		{
+			let message = format!("Import of '{}' conflicts with a declaration of the same name in this package", binding);
+			self.error("E0204", message, decl.span);
+			return;
+		}
+		self.imports.insert(binding, (resolution, decl.span));
+	}
+
+	// Extension functions are found by the type checker without going through
+	// names, so imports that may bring some in are never reported.
+	fn unused_imports(&mut self) {
+		let mut warnings = Vec::new();
+		for (binding, (resolution, span)) in &self.imports {
+			if self.used.contains(binding) || binding.starts_with('_') {
+				continue;
+			}
+			if let Resolution::Symbol(id) = resolution
+				&& self.is_extension(*id)
+			{
+				continue;
+			}
+			warnings.push(Diagnostic::warning(format!("'{}' is imported but never used", binding), *span).with_code("W0002").in_file(self.file));
+		}
+		for (package, span) in &self.wildcards {
+			let extensions = self.graph.packages[package].symbols.values().any(|ids| ids.iter().any(|id| self.is_extension(*id)));
+			if !self.used.contains(package) && !extensions {
+				warnings.push(Diagnostic::warning(format!("Nothing imported from '{}.*' is used", package), *span).with_code("W0002").in_file(self.file));
+			}
+		}
+		warnings.sort_by_key(|warning| (warning.primary_span.line, warning.primary_span.column));
+		self.resolutions.warnings = warnings;
+	}
+
+	fn is_extension(&self, id: SymbolId) -> bool {
+		let symbol = self.graph.symbol(id);
+		matches!(&self.files[symbol.file].program.stmts[symbol.stmt].kind, StmtKind::Function(decl) if decl.receiver.is_some())
+	}
+
+	fn missing_package(&mut self, name: &str, span: Span) {
+		let message = format!("Cannot find package '{}'", name);
+		self.misspelled("E0202", message, name, span, self.graph.package_names());
+	}
+
+	// ---------------------------------------------------------------------
+	// Lookup
+	// ---------------------------------------------------------------------
+
+	fn lookup_value(&mut self, name: &str, span: Span) -> Option<Resolution> {
+		if let Some(found) = self.scopes.iter().rev().find_map(|scope| scope.values.get(name)) {
+			return Some(found.clone());
+		}
+		self.lookup_global(name, span, false)
+	}
+
+	// The name closest to `name` among those `lookup_value` could have found.
+	fn closest_value(&self, name: &str) -> Option<String> {
+		let graph = self.graph;
+		let scopes = self.scopes.iter().flat_map(|scope| scope.values.keys());
+		let packages = std::iter::once(&self.package).chain(self.wildcards.iter().map(|(package, _)| package));
+		let globals = packages.filter_map(|package| graph.packages.get(package)).flat_map(|package| package.symbols.keys());
+		let candidates = scopes.chain(self.imports.keys()).chain(globals).map(String::as_str);
+		suggest::closest(name, candidates).map(str::to_string)
+	}
+
+	fn lookup_type(&mut self, name: &str, span: Span) -> Option<Resolution> {
+		if let Some(found) = self.scopes.iter().rev().find_map(|scope| scope.types.get(name)) {
+			return Some(found.clone());
+		}
+		self.lookup_global(name, span, true)
+	}
+
+	fn lookup_global(&mut self, name: &str, span: Span, types_only: bool) -> Option<Resolution> {
+		if let Some((resolution, _)) = self.imports.get(name) {
+			self.used.insert(name.to_string());
+			return Some(resolution.clone());
+		}
+		if let Some(symbols) = self.graph.lookup(&self.package, name) {
+			return Some(Resolution::Symbol(symbols[0]));
+		}
+
+		let graph = self.graph;
+		let (found, hidden): (Vec<(String, SymbolId)>, Vec<_>) = self
+			.wildcards
+			.iter()
+			.filter_map(|(package, _)| Some((package.clone(), graph.lookup(package, name)?[0])))
+			.filter(|(_, id)| !types_only || graph.symbol(*id).kind.is_type())
+			.partition(|(_, id)| graph.symbol(*id).visibility != Visibility::Private);
+		// Private declarations are not imported, but saying so beats not finding them
+		if found.is_empty()
+			&& let Some((_, id)) = hidden.first()
+		{
+			self.check_visible(*id, span);
+			return Some(Resolution::Symbol(*id));
+		}
+		if found.len() > 1 && self.reported.insert(name.to_string()) {
+			let packages: Vec<String> = found.iter().map(|(package, _)| format!("'{}'", package)).collect();
+			let message = format!(
+				"'{}' is ambiguous: it is declared in {}; import the one you mean explicitly",
+				name,
+				packages.join(" and ")
+			);
+			self.error("E0205", message, span);
+		}
+		if let Some((package, id)) = found.first() {
+			self.used.insert(package.clone());
+			return Some(Resolution::Symbol(*id));
+		}
+		if !types_only
+			&& let Some((package, native)) = self.wildcards.iter().find_map(|(package, _)| Some((package, graph.native(package, name)?)))
+		{
+			self.used.insert(package.clone());
+			return Some(Resolution::Native(native.to_string()));
+		}
+
+		// What the prelude has, unless this is it
+		if self.package != PRELUDE {
+			let visible = |id: &&SymbolId| graph.symbol(**id).visibility != Visibility::Private && (!types_only || graph.symbol(**id).kind.is_type());
+			if let Some(&id) = graph.lookup(PRELUDE, name).and_then(|symbols| symbols.first()).filter(visible) {
+				return Some(Resolution::Symbol(id));
+			}
+			if !types_only && let Some(native) = graph.native(PRELUDE, name) {
+				return Some(Resolution::Native(native.to_string()));
+			}
+		}
+
+		if !types_only && self.graph.is_package_prefix(name) {
+			return Some(Resolution::Package(name.to_string()));
+		}
+		None
+	}
+
+	// Walks `a.b.c` from a package towards the declaration it names and records
+	// the result on the node that names it. Anything after the declaration is
+	// member access for the type checker.
+	fn resolve_path(&mut self, mut package: String, segments: &[(&str, NodeId, Span)]) {
+		for (i, &(name, id, span)) in segments.iter().enumerate() {
+			let nested = format!("{}.{}", package, name);
+			if self.graph.is_package_prefix(&nested) {
+				package = nested;
+				if i + 1 == segments.len() {
+					self.resolutions.exprs.insert(id, Resolution::Package(package.clone()));
+				}
+				continue;
+			}
+			match self.graph.lookup(&package, name) {
+				Some(symbols) => {
+					self.check_visible(symbols[0], span);
+					self.resolutions.exprs.insert(id, Resolution::Symbol(symbols[0]));
+				}
+				None => match self.graph.native(&package, name) {
+					Some(native) => {
+						self.resolutions.exprs.insert(id, Resolution::Native(native.to_string()));
+					}
+					None => self.unknown_in_package(&package, name, span)
+				}
+			}
+			return;
+		}
+	}
+
+	// A `private` declaration can only be used from its own package.
+	fn check_visible(&mut self, id: SymbolId, span: Span) {
+		let symbol = self.graph.symbol(id);
+		if symbol.visibility != Visibility::Private || symbol.package == self.package {
+			return;
+		}
+		let message = format!(
+			"'{}' is private to package '{}'; it is declared at line {} column {} of {}",
+			symbol.name, symbol.package, symbol.span.line, symbol.span.column, self.files[symbol.file].path
+		);
+		self.error("E0208", message, span);
+	}
+
+	fn unknown_in_package(&mut self, package: &str, name: &str, span: Span) {
+		if !self.graph.has_package(package) {
+			self.missing_package(&format!("{}.{}", package, name), span);
+			return;
+		}
+		let message = format!("Package '{}' has no declaration named '{}'", package, name);
+		let nested = format!("{}.", package);
+		let graph = self.graph;
+		let candidates = graph.packages[package].symbols.keys().chain(graph.packages[package].natives.keys()).map(String::as_str).chain(
+			graph.package_names().filter_map(|other| other.strip_prefix(nested.as_str())).filter(|rest| !rest.contains('.'))
+		);
+		self.misspelled("E0203", message, name, span, candidates);
+	}
+
+	fn type_expr(&mut self, ty: &TypeExpr) {
+		match &ty.kind {
+			TypeExprKind::Named { name, args } => {
+				self.type_name(name, ty.span);
+				for arg in args {
+					self.type_expr(arg);
+				}
+			}
+			TypeExprKind::Nullable(inner) | TypeExprKind::Array(inner) => self.type_expr(inner),
+			TypeExprKind::Function { params, ret } => {
+				for param in params {
+					self.type_expr(param);
+				}
+				self.type_expr(ret);
+			}
+			TypeExprKind::Union(members) => {
+				for member in members {
+					self.type_expr(member);
+				}
+			}
+		}
+	}
+
+	fn type_name(&mut self, name: &str, span: Span) {
+		let Some((qualifier, last)) = name.rsplit_once('.') else {
+			if let Some(resolution) = self.lookup_type(name, span) {
+				self.resolutions.types.insert(span, resolution);
+			}
+			return;
+		};
+
+		// `b.Thing` after `import a.b` goes through the imported package
+		let (root, rest) = qualifier.split_once('.').unwrap_or((qualifier, ""));
+		if self.imports.contains_key(root) {
+			self.used.insert(root.to_string());
+		}
+		let package = match self.imports.get(root) {
+			Some((Resolution::Package(package), _)) if rest.is_empty() => package.clone(),
+			Some((Resolution::Package(package), _)) => format!("{}.{}", package, rest),
+			_ => qualifier.to_string()
+		};
+		match self.graph.lookup(&package, last) {
+			Some(symbols) => {
+				self.check_visible(symbols[0], span);
+				self.resolutions.types.insert(span, Resolution::Symbol(symbols[0]));
+			}
+			None => self.unknown_in_package(&package, last, span)
+		}
+	}
+
+	fn opt_type(&mut self, ty: &Option<TypeExpr>) {
+		if let Some(ty) = ty {
+			self.type_expr(ty);
+		}
+	}
+
+	// ---------------------------------------------------------------------
+	// Scopes
+	// ---------------------------------------------------------------------
+
+	fn push(&mut self) {
+		self.scopes.push(Scope::default());
+	}
+
+	fn pop(&mut self) {
+		self.scopes.pop();
+	}
+
+	fn define_value(&mut self, name: &str, resolution: Resolution) {
+		if let Some(scope) = self.scopes.last_mut() {
+			scope.values.insert(name.to_string(), resolution);
+		}
+	}
+
+	fn define_type(&mut self, name: &str, resolution: Resolution) {
+		if let Some(scope) = self.scopes.last_mut() {
+			scope.types.insert(name.to_string(), resolution);
+		}
+	}
+
+	fn type_params(&mut self, params: &[TypeParam]) {
+		for param in params {
+			self.define_type(&param.name, Resolution::TypeParam(param.id));
+		}
+		for param in params {
+			for bound in &param.bounds {
+				self.type_expr(bound);
+			}
+		}
+	}
+
+	fn params(&mut self, params: &[Param]) {
+		for param in params {
+			self.opt_type(&param.ty);
+			if let Some(default) = &param.default {
+				self.visit_expr(default);
+			}
+			self.define_value(&param.name, Resolution::Local(param.id));
+		}
+	}
+
+	// Local functions and classes can be used before their declaration in
+	// the same block, like top-level ones.
+	fn hoist(&mut self, stmts: &[Stmt]) {
+		for stmt in stmts {
+			match &stmt.kind {
+				StmtKind::Function(decl) => self.define_value(&decl.name, Resolution::Local(decl.id)),
+				StmtKind::Class(decl) => {
+					self.define_value(&decl.name, Resolution::Local(decl.id));
+					self.define_type(&decl.name, Resolution::Local(decl.id));
+				}
+				StmtKind::Interface(decl) => self.define_type(&decl.name, Resolution::Local(decl.id)),
+				StmtKind::Enum(decl) => {
+					self.define_value(&decl.name, Resolution::Local(decl.id));
+					self.define_type(&decl.name, Resolution::Local(decl.id));
+				}
+				StmtKind::Struct(decl) => {
+					self.define_value(&decl.name, Resolution::Local(decl.id));
+					self.define_type(&decl.name, Resolution::Local(decl.id));
+				}
+				StmtKind::TypeAlias(decl) => self.define_type(&decl.name, Resolution::Local(decl.id)),
+				_ => {}
+			}
+		}
+	}
+
+	// ---------------------------------------------------------------------
+	// Declarations
+	// ---------------------------------------------------------------------
+
+	fn members(&mut self, members: &[Member], extra: &[&str]) {
+		self.push();
+		for name in extra {
+			self.define_value(name, Resolution::Member);
+		}
+		for member in members {
+			match member {
+				Member::Field(field) => self.define_value(&field.name, Resolution::Member),
+				Member::Method(method) => self.define_value(&method.name, Resolution::Member),
+				Member::Constructor(_) => {}
+			}
+		}
+
+		for member in members {
+			match member {
+				Member::Field(field) => {
+					self.opt_type(&field.ty);
+					if let Some(init) = &field.init {
+						self.visit_expr(init);
+					}
+					for accessor in [&field.getter, &field.setter].into_iter().flatten() {
+						self.push();
+						self.define_value("field", Resolution::Member);
+						if let Some(param) = &accessor.param {
+							self.params(std::slice::from_ref(param));
+						}
+						if let Some(body) = &accessor.body {
+							visitor::walk_fn_body(self, body);
+						}
+						self.pop();
+					}
+				}
+				Member::Method(method) => self.visit_fn(method),
+				Member::Constructor(ctor) => {
+					self.push();
+					self.params(&ctor.params);
+					self.visit_block(&ctor.body);
+					self.pop();
+				}
+			}
+		}
+		self.pop();
+	}
+
+	fn type_decl(&mut self, type_params: &[TypeParam], supertypes: &[TypeExpr], members: &[Member], extra: &[&str]) {
+		self.push();
+		self.type_params(type_params);
+		for ty in supertypes {
+			self.type_expr(ty);
+		}
+		self.members(members, extra);
+		self.pop();
+	}
+
+	// `a.b.c` made only of names, as (name, node, name span) from the root
+	fn path<'e>(expr: &'e Expr, out: &mut Vec<(&'e str, NodeId, Span)>) -> bool {
+		match &expr.kind {
+			ExprKind::Identifier(name) => {
+				out.push((name, expr.id, expr.span));
+				true
+			}
+			ExprKind::Member { object, name, name_span, safe: false } => {
+				if !Self::path(object, out) {
+					return false;
+				}
+				out.push((name, expr.id, *name_span));
+				true
+			}
+			_ => false
+		}
+	}
+
+	fn error(&mut self, code: &'static str, message: String, span: Span) {
+		self.errors.push(Diagnostic::error(message, span).with_code(code).in_file(self.file));
+	}
+
+	// An error about `name`, suggesting the candidate closest to it.
+	fn misspelled<'b>(&mut self, code: &'static str, message: String, name: &str, span: Span, candidates: impl IntoIterator<Item = &'b str>) {
+		let error = Diagnostic::error(message, span).with_code(code).in_file(self.file);
+		self.errors.push(suggest::did_you_mean(error, name, span, candidates));
+	}
+}
+
+impl Visitor for Resolver<'_> {
+	fn visit_block(&mut self, block: &Block) {
+		self.push();
+		self.hoist(&block.stmts);
+		visitor::walk_block(self, block);
+		self.pop();
+	}
+
+	fn visit_fn(&mut self, decl: &FnDecl) {
+		self.push();
+		self.type_params(&decl.type_params);
+		self.opt_type(&decl.receiver);
+		self.params(&decl.params);
+		self.opt_type(&decl.return_type);
+		for ty in &decl.throws {
+			self.type_expr(ty);
+		}
+		match &decl.body {
+			// The body shares the parameters' scope
+			Some(FnBody::Block(block)) => {
+				self.hoist(&block.stmts);
+				visitor::walk_block(self, block);
+			}
+			Some(FnBody::Expr(expr)) => self.visit_expr(expr),
+			None => {}
+		}
+		self.pop();
+	}
+
+	fn visit_stmt(&mut self, stmt: &Stmt) {
+		match &stmt.kind {
+			StmtKind::Var(decl) => {
+				self.opt_type(&decl.ty);
+				if let Some(init) = &decl.init {
+					self.visit_expr(init);
+				}
+				self.define_value(&decl.name, Resolution::Local(decl.id));
+			}
+			StmtKind::For(for_loop) => {
+				self.visit_expr(&for_loop.iterable);
+				self.push();
+				for binding in for_loop.pattern.bindings() {
+					self.define_value(&binding.name, Resolution::Local(binding.id));
+				}
+				self.visit_block(&for_loop.body);
+				self.pop();
+			}
+			StmtKind::Try { body, catches, finally } => {
+				self.visit_block(body);
+				for catch in catches {
+					self.opt_type(&catch.ty);
+					self.push();
+					self.define_value(&catch.name, Resolution::Local(catch.id));
+					self.visit_block(&catch.body);
+					self.pop();
+				}
+				if let Some(finally) = finally {
+					self.visit_block(finally);
+				}
+			}
+			StmtKind::Class(decl) => self.type_decl(&decl.type_params, &decl.supertypes, &decl.members, &[]),
+			StmtKind::Interface(decl) => self.type_decl(&decl.type_params, &decl.supertypes, &decl.members, &[]),
+			StmtKind::Enum(decl) => {
+				for variant in &decl.variants {
+					for arg in &variant.args {
+						self.visit_expr(arg);
+					}
+				}
+				let variants: Vec<&str> = decl.variants.iter().map(|variant| variant.name.as_str()).collect();
+				self.type_decl(&[], &decl.supertypes, &decl.members, &variants);
+			}
+			StmtKind::Struct(decl) => {
+				self.push();
+				self.type_params(&decl.type_params);
+				for field in &decl.fields {
+					self.opt_type(&field.ty);
+					if let Some(init) = &field.init {
+						self.visit_expr(init);
+					}
+				}
+				self.pop();
+			}
+			StmtKind::Annotation(decl) => {
+				for param in &decl.params {
+					self.type_expr(&param.ty);
+				}
+			}
+			StmtKind::TypeAlias(decl) => {
+				self.push();
+				self.type_params(&decl.type_params);
+				self.type_expr(&decl.ty);
+				self.pop();
+			}
+			StmtKind::Import(_) | StmtKind::Package(_) => {
+				self.error("E0206", "Imports and package declarations must be at the top level of a file".to_string(), stmt.span);
+			}
+			_ => visitor::walk_stmt(self, stmt)
+		}
+	}
+
+	fn visit_expr(&mut self, expr: &Expr) {
+		match &expr.kind {
+			ExprKind::Identifier(name) => match self.lookup_value(name, expr.span) {
+				Some(resolution) => {
+					self.resolutions.exprs.insert(expr.id, resolution);
+				}
+				None => {
+					if let Some(closest) = self.closest_value(name) {
+						self.resolutions.closest.insert(expr.id, closest);
+					}
+				}
+			},
+			ExprKind::Member { object, .. } => {
+				let mut segments = Vec::new();
+				if !Self::path(expr, &mut segments) {
+					self.visit_expr(object);
+					return;
+				}
+				let (root, root_id, root_span) = segments[0];
+				match self.lookup_value(root, root_span) {
+					Some(Resolution::Package(package)) => {
+						self.resolutions.exprs.insert(root_id, Resolution::Package(package.clone()));
+						self.resolve_path(package, &segments[1..]);
+					}
+					Some(resolution) => {
+						self.resolutions.exprs.insert(root_id, resolution);
+					}
+					None => {}
+				}
+			}
+			ExprKind::Is { operand, ty, .. } => {
+				self.visit_expr(operand);
+				self.type_expr(ty);
+			}
+			ExprKind::New { class, args } => {
+				self.type_expr(class);
+				for arg in args {
+					self.visit_expr(arg);
+				}
+			}
+			ExprKind::Lambda { params, body } => {
+				self.push();
+				self.params(params);
+				match body {
+					LambdaBody::Expr(body) => self.visit_expr(body),
+					LambdaBody::Block(block) => self.visit_block(block)
+				}
+				self.pop();
+			}
+			_ => visitor::walk_expr(self, expr)
+		}
+	}
+}
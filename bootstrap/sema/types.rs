@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::fmt;
+
+// Indices into the tables of a checked program (see `hir::Program`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TypeId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FnId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GlobalId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LocalId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TypeParamId(pub u32);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+	Int,
+	Float,
+	Bool,
+	String,
+	Void,
+	// Type of the `null` literal on its own
+	Null,
+	// Unannotated values are `Any`: they can be used as anything and anything
+	// can be stored in them, checked at run time instead.
+	Any,
+	// `throw`, `return` and other expressions that never produce a value
+	Never,
+	// Half-open `start..end` over Ints
+	Range,
+	Nullable(Box<Type>),
+	Array(Box<Type>),
+	// `Map<K, V>`, keyed by any value that can be hashed, in the order the
+	// keys were first set
+	Map(Box<Type>, Box<Type>),
+	// `Set<T>`, in the order the elements were first added
+	Set(Box<Type>),
+	// `Iterator<T>`: elements worked out one at a time as they are asked for
+	Iterator(Box<Type>),
+	// `Result<T, E>`: `Ok` with a value or `Err` with an error, which `?`
+	// returns from the function
+	Result(Box<Type>, Box<Type>),
+	// `Option<T>`: `Some` with a value or `None`
+	Option(Box<Type>),
+	// `Random`: a generator of pseudo-random numbers, the same ones for the
+	// same seed
+	Random,
+	// `Pointer`: the address of something of C's, which only `extern`
+	// functions make and look into
+	Pointer,
+	// `Channel<T>`: values sent by one task for others to receive, in order
+	Channel(Box<Type>),
+	Function { params: Vec<Type>, ret: Box<Type> },
+	// A class, interface, enum or struct with its type arguments
+	Class { id: TypeId, name: String, args: Vec<Type> },
+	Param { id: TypeParamId, name: String },
+	// A value of any of the members; build with `Type::union`
+	Union(Vec<Type>),
+	// Stands in for the type of anything that failed to check, so one mistake
+	// is reported once rather than at every use.
+	Error
+}
+
+impl Type {
+	pub fn nullable(self) -> Type {
+		match self {
+			Type::Nullable(_) | Type::Null | Type::Any | Type::Error => self,
+			_ => Type::Nullable(Box::new(self))
+		}
+	}
+
+	// `A | B | ...`, flattened and without repeats. Null among the members
+	// makes the union nullable, `Never` adds nothing, and `Any` takes over.
+	pub fn union(members: Vec<Type>) -> Type {
+		if let Some(dynamic) = members.iter().find(|member| member.is_dynamic()) {
+			return dynamic.clone();
+		}
+		let mut flat = Vec::new();
+		let mut nullable = false;
+		for member in members {
+			flatten(member, &mut flat, &mut nullable);
+		}
+		let ty = match flat.len() {
+			0 if nullable => return Type::Null,
+			0 => return Type::Never,
+			1 => flat.remove(0),
+			_ => Type::Union(flat)
+		};
+		if nullable { ty.nullable() } else { ty }
+	}
+
+	pub fn non_null(&self) -> &Type {
+		match self {
+			Type::Nullable(inner) => inner,
+			_ => self
+		}
+	}
+
+	pub fn is_numeric(&self) -> bool {
+		matches!(self, Type::Int | Type::Float)
+	}
+
+	// `Any` and `Error` accept every operation; checks skip them.
+	pub fn is_dynamic(&self) -> bool {
+		matches!(self, Type::Any | Type::Error)
+	}
+
+	pub fn is_error(&self) -> bool {
+		match self {
+			Type::Error => true,
+			Type::Nullable(inner) | Type::Array(inner) | Type::Set(inner) | Type::Iterator(inner) | Type::Option(inner) | Type::Channel(inner) => inner.is_error(),
+			Type::Map(key, value) | Type::Result(key, value) => key.is_error() || value.is_error(),
+			Type::Function { params, ret } => ret.is_error() || params.iter().any(Type::is_error),
+			Type::Class { args, .. } | Type::Union(args) => args.iter().any(Type::is_error),
+			_ => false
+		}
+	}
+
+	// Replaces type parameters with the types they are instantiated with.
+	pub fn substitute(&self, subst: &HashMap<TypeParamId, Type>) -> Type {
+		if subst.is_empty() {
+			return self.clone();
+		}
+		match self {
+			Type::Param { id, .. } => subst.get(id).cloned().unwrap_or_else(|| self.clone()),
+			Type::Nullable(inner) => inner.substitute(subst).nullable(),
+			Type::Array(inner) => Type::Array(Box::new(inner.substitute(subst))),
+			Type::Map(key, value) => Type::Map(Box::new(key.substitute(subst)), Box::new(value.substitute(subst))),
+			Type::Set(inner) => Type::Set(Box::new(inner.substitute(subst))),
+			Type::Iterator(inner) => Type::Iterator(Box::new(inner.substitute(subst))),
+			Type::Result(value, error) => Type::Result(Box::new(value.substitute(subst)), Box::new(error.substitute(subst))),
+			Type::Option(inner) => Type::Option(Box::new(inner.substitute(subst))),
+			Type::Channel(inner) => Type::Channel(Box::new(inner.substitute(subst))),
+			Type::Function { params, ret } => Type::Function {
+				params: params.iter().map(|param| param.substitute(subst)).collect(),
+				ret: Box::new(ret.substitute(subst))
+			},
+			Type::Class { id, name, args } => Type::Class {
+				id: *id,
+				name: name.clone(),
+				args: args.iter().map(|arg| arg.substitute(subst)).collect()
+			},
+			Type::Union(members) => Type::union(members.iter().map(|member| member.substitute(subst)).collect()),
+			_ => self.clone()
+		}
+	}
+}
+
+impl fmt::Display for Type {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Type::Int => write!(f, "Int"),
+			Type::Float => write!(f, "Float"),
+			Type::Bool => write!(f, "Bool"),
+			Type::String => write!(f, "String"),
+			Type::Void => write!(f, "Void"),
+			Type::Null => write!(f, "Null"),
+			Type::Any => write!(f, "Any"),
+			Type::Never => write!(f, "Never"),
+			Type::Range => write!(f, "Range"),
+			Type::Random => write!(f, "Random"),
+			Type::Pointer => write!(f, "Pointer"),
+			Type::Nullable(inner) => match **inner {
+				Type::Function { .. } | Type::Union(_) => write!(f, "({})?", inner),
+				_ => write!(f, "{}?", inner)
+			},
+			Type::Array(inner) => match **inner {
+				Type::Function { .. } | Type::Union(_) => write!(f, "({})[]", inner),
+				_ => write!(f, "{}[]", inner)
+			},
+			Type::Map(key, value) => write!(f, "Map<{}, {}>", key, value),
+			Type::Set(inner) => write!(f, "Set<{}>", inner),
+			Type::Iterator(inner) => write!(f, "Iterator<{}>", inner),
+			Type::Result(value, error) => write!(f, "Result<{}, {}>", value, error),
+			Type::Option(inner) => write!(f, "Option<{}>", inner),
+			Type::Channel(inner) => write!(f, "Channel<{}>", inner),
+			Type::Function { params, ret } => {
+				write!(f, "(")?;
+				for (i, param) in params.iter().enumerate() {
+					if i > 0 {
+						write!(f, ", ")?;
+					}
+					write!(f, "{}", param)?;
+				}
+				write!(f, ") -> {}", ret)
+			}
+			Type::Class { name, args, .. } => {
+				write!(f, "{}", name)?;
+				if !args.is_empty() {
+					write!(f, "<")?;
+					for (i, arg) in args.iter().enumerate() {
+						if i > 0 {
+							write!(f, ", ")?;
+						}
+						write!(f, "{}", arg)?;
+					}
+					write!(f, ">")?;
+				}
+				Ok(())
+			}
+			Type::Param { name, .. } => write!(f, "{}", name),
+			Type::Union(members) => {
+				for (i, member) in members.iter().enumerate() {
+					if i > 0 {
+						write!(f, " | ")?;
+					}
+					match member {
+						Type::Function { .. } => write!(f, "({})", member)?,
+						_ => write!(f, "{}", member)?
+					}
+				}
+				Ok(())
+			}
+			Type::Error => write!(f, "<error>")
+		}
+	}
+}
+
+fn flatten(member: Type, flat: &mut Vec<Type>, nullable: &mut bool) {
+	match member {
+		Type::Never => {}
+		Type::Null => *nullable = true,
+		Type::Nullable(inner) => {
+			*nullable = true;
+			flatten(*inner, flat, nullable);
+		}
+		Type::Union(members) => {
+			for member in members {
+				flatten(member, flat, nullable);
+			}
+		}
+		member => {
+			if !flat.contains(&member) {
+				flat.push(member);
+			}
+		}
+	}
+}
@@ -0,0 +1,91 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::env;
+use std::process::Command;
+
+use crate::interpreter::native::{Marshal, Runtime};
+use crate::interpreter::value::{Map, Value};
+use crate::sema::types::Type;
+
+// Environment variables. What `env.set` and `env.remove` change is seen by
+// the program and the processes it starts with `process.run`, not by the
+// process running it, which other threads may be reading the environment
+// of, nor by the programs run after it.
+pub fn register(runtime: &mut Runtime) {
+	runtime
+		.register("env.get", |name: String| get(&name))
+		.register("env.set", |name: String, value: String| change(name, Some(value)))
+		.register("env.remove", |name: String| change(name, None))
+		.register("env.vars", || Vars(vars()));
+}
+
+thread_local! {
+	// Set to a value, or removed with none
+	static CHANGED: RefCell<BTreeMap<String, Option<String>>> = RefCell::default();
+}
+
+// Forgets what the last program run on this thread changed, for the next
+// to start from the environment of the process.
+pub fn clear() {
+	CHANGED.with(|changed| changed.borrow_mut().clear());
+}
+
+fn change(name: String, value: Option<String>) {
+	CHANGED.with(|changed| changed.borrow_mut().insert(name, value));
+}
+
+fn get(name: &str) -> Option<String> {
+	match CHANGED.with(|changed| changed.borrow().get(name).cloned()) {
+		Some(value) => value,
+		None => env::var(name).ok()
+	}
+}
+
+// Each variable with its value, in order, leaving out those that are not
+// text.
+fn vars() -> BTreeMap<String, String> {
+	let mut vars: BTreeMap<String, String> = env::vars_os().filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?))).collect();
+	CHANGED.with(|changed| {
+		for (name, value) in changed.borrow().iter() {
+			match value {
+				Some(value) => vars.insert(name.clone(), value.clone()),
+				None => vars.remove(name)
+			};
+		}
+	});
+	vars
+}
+
+// Makes the changes for a process about to start.
+pub fn apply(command: &mut Command) {
+	CHANGED.with(|changed| {
+		for (name, value) in changed.borrow().iter() {
+			match value {
+				Some(value) => command.env(name, value),
+				None => command.env_remove(name)
+			};
+		}
+	});
+}
+
+// What `env.vars` returns.
+struct Vars(BTreeMap<String, String>);
+
+impl Marshal for Vars {
+	fn ty() -> Type {
+		Type::Map(Box::new(Type::String), Box::new(Type::String))
+	}
+
+	// Only ever returned
+	fn from_value(_: &Value) -> Option<Vars> {
+		None
+	}
+
+	fn into_value(self) -> Value {
+		let mut map = Map::new();
+		for (name, value) in self.0 {
+			map.insert(Value::string(name), Value::string(value));
+		}
+		Value::map(map)
+	}
+}
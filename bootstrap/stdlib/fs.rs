@@ -0,0 +1,66 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Write};
+
+use crate::interpreter::native::{Marshal, Runtime};
+use crate::interpreter::ops::{Cursor, Sequence, Source};
+use crate::interpreter::value::Value;
+use crate::sema::types::Type;
+
+// Files and directories, by path. What the system will not do is thrown,
+// with what it said, for `catch` to handle:
+//
+//     Cannot read 'notes.txt': No such file or directory (os error 2)
+//
+// `fs.lines` reads a file a line at a time, as it is iterated, so big ones
+// need not fit in memory.
+pub fn register(runtime: &mut Runtime) {
+	runtime
+		.register("fs.readText", |path: String| fs::read_to_string(&path).map_err(|err| failed("read", &path, err)))
+		.register("fs.writeText", |path: String, text: String| fs::write(&path, text).map_err(|err| failed("write", &path, err)))
+		.register("fs.appendText", |path: String, text: String| append(&path, &text).map_err(|err| failed("append to", &path, err)))
+		.register("fs.lines", |path: String| File::open(&path).map(|file| Lines(Cursor::Lines { reader: BufReader::new(file), path: path.clone() })).map_err(|err| failed("read", &path, err)))
+		.register("fs.exists", |path: String| fs::exists(&path).map_err(|err| failed("look for", &path, err)))
+		.register("fs.isDir", |path: String| fs::metadata(&path).is_ok_and(|metadata| metadata.is_dir()))
+		.register("fs.listDir", |path: String| list(&path).map_err(|err| failed("list", &path, err)))
+		.register("fs.createDir", |path: String| fs::create_dir_all(&path).map_err(|err| failed("create", &path, err)))
+		.register("fs.remove", |path: String| remove(&path).map_err(|err| failed("remove", &path, err)));
+}
+
+fn failed(doing: &str, path: &str, err: io::Error) -> String {
+	format!("Cannot {} '{}': {}", doing, path, err)
+}
+
+fn append(path: &str, text: &str) -> io::Result<()> {
+	OpenOptions::new().create(true).append(true).open(path)?.write_all(text.as_bytes())
+}
+
+// The names in a directory, in order.
+fn list(path: &str) -> io::Result<Vec<String>> {
+	let mut names = fs::read_dir(path)?.map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned())).collect::<io::Result<Vec<String>>>()?;
+	names.sort();
+	Ok(names)
+}
+
+// A file, or a directory with nothing in it.
+fn remove(path: &str) -> io::Result<()> {
+	if fs::symlink_metadata(path)?.is_dir() { fs::remove_dir(path) } else { fs::remove_file(path) }
+}
+
+// What `fs.lines` returns: an `Iterator<String>` of the lines of a file
+// still open, without their line breaks.
+struct Lines(Cursor);
+
+impl Marshal for Lines {
+	fn ty() -> Type {
+		Type::Iterator(Box::new(Type::String))
+	}
+
+	// Only ever returned
+	fn from_value(_: &Value) -> Option<Lines> {
+		None
+	}
+
+	fn into_value(self) -> Value {
+		Value::Iterator(Sequence::new(Source::Cursor(self.0)))
+	}
+}
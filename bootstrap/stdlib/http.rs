@@ -0,0 +1,416 @@
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::driver::json::{self, Json};
+use crate::interpreter::native::{Background, Marshal, Runtime};
+use crate::interpreter::value::{Map, Value};
+use crate::sema::types::Type;
+use crate::stdlib;
+
+// HTTP requests, over plain `http://` URLs. There is no TLS, so
+// `https://` URLs, and redirects to them, fail rather than being sent in the
+// clear.
+// Each returns a map of the `status`, the `headers`, with their names in
+// lower case, and the `body` of the response, following redirects:
+//
+//     val response = http.get("http://localhost:8080/health")
+//     if response["status"] != 200 {
+//         println(response["body"])
+//     }
+//
+// `http.request` takes the method and a map of options: `headers`, a
+// `body`, and a `timeout` in milliseconds, 30 seconds unless given, for
+// connecting and for each read and write. `http.getJson` and
+// `http.postJson` send and read JSON, as `json.parse` reads it, and throw
+// for a status that is not a success. A redirect to another host is
+// followed without the `Authorization` and `Cookie` headers given for the
+// first.
+//
+// Each has an async variant, like `http.getAsync`, which runs on a thread of
+// its own while the program goes on, for `await` to wait for.
+pub fn register(runtime: &mut Runtime) {
+	runtime
+		.register("http.get", |url: String| fetch(Request::new("GET", url)))
+		.register("http.post", |url: String, body: String| fetch(Request::new("POST", url).text(body)))
+		.register("http.request", |method: String, url: String, options: Options| fetch(options.request(method, url)?))
+		.register("http.getJson", |url: String| read_json(Request::new("GET", url).json(None)))
+		.register("http.postJson", |url: String, value: Value| read_json(Request::new("POST", url).json(Some(stdlib::json::stringify(&value, None)?))))
+		.register("http.getAsync", |url: String| Background::new(move || fetch(Request::new("GET", url))))
+		.register("http.postAsync", |url: String, body: String| Background::new(move || fetch(Request::new("POST", url).text(body))))
+		.register("http.requestAsync", |method: String, url: String, options: Options| {
+			let request = options.request(method, url)?;
+			Ok::<_, String>(Background::new(move || fetch(request)))
+		})
+		.register("http.getJsonAsync", |url: String| Background::new(move || read_json(Request::new("GET", url).json(None))))
+		.register("http.postJsonAsync", |url: String, value: Value| {
+			let request = Request::new("POST", url).json(Some(stdlib::json::stringify(&value, None)?));
+			Ok::<_, String>(Background::new(move || read_json(request)))
+		});
+}
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+const MAX_REDIRECTS: usize = 10;
+
+// The headers that are only for the host they were given for.
+const CREDENTIALS: [&str; 3] = ["authorization", "cookie", "proxy-authorization"];
+
+// What to send, made on the program's thread so it can be sent on another.
+struct Request {
+	method: String,
+	url: String,
+	headers: Vec<(String, String)>,
+	body: Option<String>,
+	timeout: Duration
+}
+
+impl Request {
+	fn new(method: &str, url: String) -> Request {
+		Request { method: method.to_string(), url, headers: Vec::new(), body: None, timeout: TIMEOUT }
+	}
+
+	fn text(mut self, body: String) -> Request {
+		self.headers.push(("Content-Type".to_string(), "text/plain; charset=utf-8".to_string()));
+		self.body = Some(body);
+		self
+	}
+
+	fn json(mut self, body: Option<String>) -> Request {
+		self.headers.push(("Accept".to_string(), "application/json".to_string()));
+		if let Some(body) = body {
+			self.headers.push(("Content-Type".to_string(), "application/json".to_string()));
+			self.body = Some(body);
+		}
+		self
+	}
+
+	fn has(&self, header: &str) -> bool {
+		self.headers.iter().any(|(name, _)| name.eq_ignore_ascii_case(header))
+	}
+}
+
+// What `http.request` takes besides the method and URL.
+struct Options(Vec<(String, Value)>);
+
+impl Marshal for Options {
+	fn ty() -> Type {
+		Type::Map(Box::new(Type::String), Box::new(Type::Any))
+	}
+
+	fn from_value(value: &Value) -> Option<Options> {
+		let Value::Map(map) = value else {
+			return None;
+		};
+		let entries = map.borrow().iter().cloned().collect::<Vec<(Value, Value)>>();
+		let options = entries.into_iter().map(|(key, value)| match key {
+			Value::String(key) => Some((key.to_string(), value)),
+			_ => None
+		});
+		options.collect::<Option<Vec<(String, Value)>>>().map(Options)
+	}
+
+	fn into_value(self) -> Value {
+		let mut map = Map::new();
+		for (key, value) in self.0 {
+			map.insert(Value::string(key), value);
+		}
+		Value::map(map)
+	}
+}
+
+impl Options {
+	fn request(self, method: String, url: String) -> Result<Request, String> {
+		if method.is_empty() || !method.bytes().all(|byte| byte.is_ascii_alphabetic()) {
+			return Err(format!("'{}' is not an HTTP method", method));
+		}
+		let mut request = Request::new(&method.to_ascii_uppercase(), url);
+		for (key, value) in self.0 {
+			match (key.as_str(), value) {
+				("headers", Value::Map(headers)) => {
+					for (name, value) in headers.borrow().iter() {
+						let (Value::String(name), Value::String(value)) = (name, value) else {
+							return Err("The headers of a request are a Map of Strings to Strings".to_string());
+						};
+						request.headers.push((name.to_string(), value.to_string()));
+					}
+				}
+				("body", Value::String(body)) => request.body = Some(body.to_string()),
+				("timeout", Value::Int(millis)) => request.timeout = Duration::from_millis(millis.max(1) as u64),
+				("headers", value) => return Err(format!("The headers of a request are a Map, not a '{}'", value.type_name())),
+				("body", value) => return Err(format!("The body of a request is a String, not a '{}'", value.type_name())),
+				("timeout", value) => return Err(format!("The timeout of a request is an Int of milliseconds, not a '{}'", value.type_name())),
+				(key, _) => return Err(format!("A request has no option '{}'; it takes 'headers', 'body' and 'timeout'", key))
+			}
+		}
+		Ok(request)
+	}
+}
+
+// What a server answered.
+struct Response {
+	status: i64,
+	headers: Vec<(String, String)>,
+	body: String
+}
+
+impl Response {
+	fn header(&self, name: &str) -> Option<&str> {
+		self.headers.iter().find(|(given, _)| given == name).map(|(_, value)| value.as_str())
+	}
+}
+
+impl Marshal for Response {
+	fn ty() -> Type {
+		Type::Map(Box::new(Type::String), Box::new(Type::Any))
+	}
+
+	// Only ever returned
+	fn from_value(_: &Value) -> Option<Response> {
+		None
+	}
+
+	fn into_value(self) -> Value {
+		let mut headers = Map::new();
+		for (name, value) in self.headers {
+			let name = Value::string(name);
+			// Repeated headers are one, as HTTP allows
+			let value = match headers.get(&name) {
+				Some(Value::String(before)) => format!("{}, {}", before, value),
+				_ => value
+			};
+			headers.insert(name, Value::string(value));
+		}
+		let mut map = Map::new();
+		map.insert(Value::string("status"), Value::Int(self.status));
+		map.insert(Value::string("headers"), Value::map(headers));
+		map.insert(Value::string("body"), Value::string(self.body));
+		Value::map(map)
+	}
+}
+
+// A body read as JSON, made into a value back on the program's thread.
+struct Parsed(Json);
+
+impl Marshal for Parsed {
+	fn ty() -> Type {
+		Type::Any
+	}
+
+	// Only ever returned
+	fn from_value(_: &Value) -> Option<Parsed> {
+		None
+	}
+
+	fn into_value(self) -> Value {
+		stdlib::json::value(self.0)
+	}
+}
+
+fn fetch(mut request: Request) -> Result<Response, String> {
+	let (method, url) = (request.method.clone(), request.url.clone());
+	let failed = |reason: String| format!("Cannot {} '{}': {}", method, url, reason);
+	let mut origin = None;
+	for _ in 0..=MAX_REDIRECTS {
+		let target = Url::parse(&request.url).map_err(failed)?;
+		let here = (target.host.to_ascii_lowercase(), target.port);
+		match &origin {
+			None => origin = Some(here),
+			Some(first) if *first != here => request.headers.retain(|(name, _)| !CREDENTIALS.iter().any(|header| name.eq_ignore_ascii_case(header))),
+			_ => {}
+		}
+		let response = send(&request, &target).map_err(|err| match err.kind() {
+			ErrorKind::WouldBlock | ErrorKind::TimedOut => failed(format!("it took longer than {} ms", request.timeout.as_millis())),
+			_ => failed(err.to_string())
+		})?;
+		let location = match response.status {
+			301 | 302 | 303 | 307 | 308 => response.header("location"),
+			_ => None
+		};
+		let Some(location) = location else {
+			return Ok(response);
+		};
+		// As browsers do, all but the last two ask for the new place with a GET
+		if !matches!(response.status, 307 | 308) && request.method != "HEAD" {
+			request.method = "GET".to_string();
+			request.body = None;
+			request.headers.retain(|(name, _)| !name.eq_ignore_ascii_case("content-type"));
+		}
+		request.url = target.join(location);
+	}
+	Err(failed(format!("it redirected more than {} times", MAX_REDIRECTS)))
+}
+
+// The body of what `request` gets, as JSON, when it succeeds.
+fn read_json(request: Request) -> Result<Parsed, String> {
+	let (method, url) = (request.method.clone(), request.url.clone());
+	let response = fetch(request)?;
+	if !(200..300).contains(&response.status) {
+		return Err(format!("{} '{}' failed with status {}", method, url, response.status));
+	}
+	json::parse(&response.body).map(Parsed).map_err(|err| format!("Cannot parse JSON from '{}': {}", url, err))
+}
+
+// Where an `http://` URL points.
+struct Url {
+	host: String,
+	port: u16,
+	// With the query, if any, but not the fragment
+	path: String
+}
+
+impl Url {
+	fn parse(url: &str) -> Result<Url, String> {
+		let rest = match url.split_once("://") {
+			Some((scheme, rest)) if scheme.eq_ignore_ascii_case("http") => rest,
+			Some((scheme, _)) if scheme.eq_ignore_ascii_case("https") => return Err("there is no TLS for 'https://', only plain 'http://'".to_string()),
+			Some((scheme, _)) => return Err(format!("'{}' URLs are not HTTP", scheme)),
+			None => return Err("a URL starts with 'http://'".to_string())
+		};
+		let rest = rest.split('#').next().unwrap_or_default();
+		// They would end the line the request starts with, and start another
+		if rest.contains(|c: char| c.is_control() || c == ' ') {
+			return Err("a URL has no spaces or control characters in it".to_string());
+		}
+		let (authority, path) = rest.find(['/', '?']).map_or((rest, ""), |at| rest.split_at(at));
+		let path = if path.starts_with('/') { path.to_string() } else { format!("/{}", path) };
+		let (host, port) = match authority.rsplit_once(':') {
+			Some((host, port)) if !port.contains(']') => (host, port.parse().map_err(|_| format!("'{}' is not a port", port))?),
+			_ => (authority, 80)
+		};
+		if host.is_empty() || host.contains('@') {
+			return Err("the URL names no host".to_string());
+		}
+		Ok(Url { host: host.to_string(), port, path })
+	}
+
+	// Where `location` points from here: another URL, or a path on this host.
+	fn join(&self, location: &str) -> String {
+		if location.contains("://") {
+			return location.to_string();
+		}
+		let path = if location.starts_with('/') {
+			location.to_string()
+		} else {
+			let path = self.path.split('?').next().unwrap_or_default();
+			format!("{}{}", &path[..path.rfind('/').map_or(0, |at| at + 1)], location)
+		};
+		format!("http://{}:{}{}", self.host, self.port, path)
+	}
+
+	// How the `Host` header names the host, leaving out the default port.
+	fn authority(&self) -> String {
+		if self.port == 80 { self.host.clone() } else { format!("{}:{}", self.host, self.port) }
+	}
+}
+
+fn send(request: &Request, url: &Url) -> io::Result<Response> {
+	let stream = connect(url, request.timeout)?;
+	stream.set_read_timeout(Some(request.timeout))?;
+	stream.set_write_timeout(Some(request.timeout))?;
+	let mut head = format!("{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n", request.method, url.path, url.authority());
+	for (name, value) in [("User-Agent", "dotfun"), ("Accept", "*/*")] {
+		if !request.has(name) {
+			head.push_str(&format!("{}: {}\r\n", name, value));
+		}
+	}
+	for (name, value) in &request.headers {
+		if [name, value].iter().any(|text| text.contains(['\r', '\n'])) {
+			return Err(io::Error::new(ErrorKind::InvalidInput, format!("the header '{}' has a line break in it", name.trim())));
+		}
+		head.push_str(&format!("{}: {}\r\n", name, value));
+	}
+	let body = request.body.as_deref().unwrap_or_default();
+	if request.body.is_some() {
+		head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+	}
+	head.push_str("\r\n");
+	let mut writer = &stream;
+	writer.write_all(head.as_bytes())?;
+	writer.write_all(body.as_bytes())?;
+	writer.flush()?;
+	receive(&mut BufReader::new(&stream), request.method == "HEAD")
+}
+
+// The first of the host's addresses that answers.
+fn connect(url: &Url, timeout: Duration) -> io::Result<TcpStream> {
+	let host = url.host.trim_start_matches('[').trim_end_matches(']');
+	let mut last = io::Error::new(ErrorKind::NotFound, format!("'{}' has no address", host));
+	for address in (host, url.port).to_socket_addrs()? {
+		match TcpStream::connect_timeout(&address, timeout) {
+			Ok(stream) => return Ok(stream),
+			Err(err) => last = err
+		}
+	}
+	Err(last)
+}
+
+fn receive(reader: &mut impl BufRead, head: bool) -> io::Result<Response> {
+	let status_line = line(reader)?;
+	let status = match status_line.split(' ').collect::<Vec<&str>>()[..] {
+		[version, status, ..] if version.starts_with("HTTP/") => status.parse().ok(),
+		_ => None
+	};
+	let status: i64 = status.ok_or_else(|| invalid("the server did not answer in HTTP"))?;
+	let mut headers = Vec::new();
+	loop {
+		let line = line(reader)?;
+		if line.is_empty() {
+			break;
+		}
+		let (name, value) = line.split_once(':').ok_or_else(|| invalid("the server sent a header without a ':'"))?;
+		headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+	}
+	let mut response = Response { status, headers, body: String::new() };
+	if head || (100..200).contains(&status) || status == 204 || status == 304 {
+		return Ok(response);
+	}
+	let mut body = Vec::new();
+	if response.header("transfer-encoding").is_some_and(|encoding| encoding.to_ascii_lowercase().ends_with("chunked")) {
+		chunks(reader, &mut body)?;
+	} else if let Some(length) = response.header("content-length") {
+		let length: u64 = length.parse().map_err(|_| invalid("the server sent a Content-Length that is not a number"))?;
+		reader.take(length).read_to_end(&mut body)?;
+		if (body.len() as u64) < length {
+			return Err(invalid("the server closed the connection before the end of the body"));
+		}
+	} else {
+		reader.read_to_end(&mut body)?;
+	}
+	response.body = String::from_utf8_lossy(&body).into_owned();
+	Ok(response)
+}
+
+// A body sent in chunks, each after its size in hexadecimal, up to one of
+// none.
+fn chunks(reader: &mut impl BufRead, body: &mut Vec<u8>) -> io::Result<()> {
+	loop {
+		let size = line(reader)?;
+		let size = size.split(';').next().unwrap_or_default().trim();
+		let size = u64::from_str_radix(size, 16).map_err(|_| invalid("the server sent a chunk without its size"))?;
+		if size == 0 {
+			// What trails the body, up to a line of its own
+			while !line(reader)?.is_empty() {}
+			return Ok(());
+		}
+		let read = reader.take(size).read_to_end(body)?;
+		if (read as u64) < size {
+			return Err(invalid("the server closed the connection in the middle of a chunk"));
+		}
+		line(reader)?;
+	}
+}
+
+// A line of the head of a response, without its line break.
+fn line(reader: &mut impl BufRead) -> io::Result<String> {
+	let mut line = Vec::new();
+	if reader.read_until(b'\n', &mut line)? == 0 {
+		return Err(invalid("the server closed the connection before answering"));
+	}
+	let line = String::from_utf8_lossy(&line);
+	Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn invalid(message: &str) -> io::Error {
+	io::Error::new(ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,140 @@
+use std::fmt::Write;
+
+use crate::diagnostics::json::string;
+use crate::driver::json::{self, Json};
+use crate::interpreter::native::Runtime;
+use crate::interpreter::value::{Map, Value};
+
+// JSON, for exchanging data with other tools. `json.parse` reads objects as
+// `Map`s with `String` keys, in the order of their keys, arrays as arrays,
+// and numbers as `Int`s when they are whole and an `Int` holds them exactly,
+// `Float`s otherwise.
+//
+// `json.stringify` writes a value on one line, and `json.pretty` with each
+// item on a line of its own, indented by as many spaces as it is told.
+// Sets are written as arrays, objects as objects of their fields, and enum
+// values without fields as the name of their variant. What JSON has no
+// place for, like functions and NaN, is thrown.
+pub fn register(runtime: &mut Runtime) {
+	runtime
+		.register("json.parse", |text: String| json::parse(&text).map(value).map_err(|err| format!("Cannot parse JSON: {}", err)))
+		.register("json.stringify", |value: Value| stringify(&value, None))
+		.register("json.pretty", |value: Value, indent: i64| stringify(&value, Some(indent.max(0) as usize)));
+}
+
+// Whole numbers from here on away from zero are not all `Int`s exactly.
+const EXACT: f64 = 9_007_199_254_740_992.0;
+
+// Nested deeper than this, a value most likely holds itself.
+const MAX_DEPTH: usize = 512;
+
+pub fn value(json: Json) -> Value {
+	match json {
+		Json::Null => Value::Null,
+		Json::Bool(value) => Value::Bool(value),
+		Json::Number(number) if number.fract() == 0.0 && number.abs() <= EXACT => Value::Int(number as i64),
+		Json::Number(number) => Value::Float(number),
+		Json::String(text) => Value::string(text),
+		Json::Array(items) => Value::array(items.into_iter().map(value).collect()),
+		Json::Object(object) => {
+			let mut map = Map::new();
+			for (key, item) in object {
+				map.insert(Value::string(key), value(item));
+			}
+			Value::map(map)
+		}
+	}
+}
+
+pub fn stringify(value: &Value, indent: Option<usize>) -> Result<String, String> {
+	let mut out = String::new();
+	Writer { out: &mut out, indent }.value(value, 0)?;
+	Ok(out)
+}
+
+struct Writer<'a> {
+	out: &'a mut String,
+	indent: Option<usize>
+}
+
+impl Writer<'_> {
+	fn value(&mut self, value: &Value, depth: usize) -> Result<(), String> {
+		if depth > MAX_DEPTH {
+			return Err(format!("Cannot stringify values nested more than {} deep, or that hold themselves", MAX_DEPTH));
+		}
+		match value {
+			Value::Null => self.out.push_str("null"),
+			Value::Bool(value) => {
+				let _ = write!(self.out, "{}", value);
+			}
+			Value::Int(value) => {
+				let _ = write!(self.out, "{}", value);
+			}
+			Value::Float(value) if value.is_finite() => {
+				let _ = write!(self.out, "{:?}", value);
+			}
+			Value::String(text) => self.out.push_str(&string(text)),
+			Value::Array(items) => {
+				let items = items.borrow().clone();
+				self.items('[', ']', depth, items.iter().map(|item| (None, item)))?;
+			}
+			Value::Set(set) => {
+				let elements: Vec<Value> = set.borrow().keys().cloned().collect();
+				self.items('[', ']', depth, elements.iter().map(|element| (None, element)))?;
+			}
+			Value::Map(map) => {
+				let entries = map.borrow().iter().cloned().collect::<Vec<(Value, Value)>>();
+				let mut keyed = Vec::new();
+				for (key, item) in &entries {
+					match key {
+						Value::String(key) => keyed.push((Some(key.to_string()), item)),
+						key => return Err(format!("Cannot stringify a Map with '{}' keys; JSON keys are Strings", key.type_name()))
+					}
+				}
+				self.items('{', '}', depth, keyed.into_iter())?;
+			}
+			Value::Object(object) if object.class.fields.is_empty() && object.variant.is_some() => {
+				let name = &object.class.variants[object.variant.unwrap_or_default()];
+				self.out.push_str(&string(name));
+			}
+			Value::Object(object) => {
+				let fields = object.fields.borrow().clone();
+				self.items('{', '}', depth, object.class.fields.iter().cloned().map(Some).zip(fields.iter()))?;
+			}
+			Value::Float(value) => return Err(format!("Cannot stringify {}; JSON numbers are finite", value)),
+			value => return Err(format!("Cannot stringify a '{}' as JSON", value.type_name()))
+		}
+		Ok(())
+	}
+
+	// An array's items, or an object's keyed ones, between `open` and `close`.
+	fn items<'v>(&mut self, open: char, close: char, depth: usize, items: impl Iterator<Item = (Option<String>, &'v Value)>) -> Result<(), String> {
+		self.out.push(open);
+		let mut empty = true;
+		for (index, (key, item)) in items.enumerate() {
+			if index > 0 {
+				self.out.push(',');
+			}
+			self.line(depth + 1);
+			if let Some(key) = key {
+				self.out.push_str(&string(&key));
+				self.out.push_str(if self.indent.is_some() { ": " } else { ":" });
+			}
+			self.value(item, depth + 1)?;
+			empty = false;
+		}
+		if !empty {
+			self.line(depth);
+		}
+		self.out.push(close);
+		Ok(())
+	}
+
+	// When pretty, a line break and the indentation of `depth`.
+	fn line(&mut self, depth: usize) {
+		if let Some(indent) = self.indent {
+			self.out.push('\n');
+			self.out.extend(std::iter::repeat_n(' ', indent * depth));
+		}
+	}
+}
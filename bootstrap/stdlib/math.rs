@@ -0,0 +1,83 @@
+use std::f64::consts;
+
+use crate::interpreter::native::Runtime;
+
+// Numbers: roots and powers, rounding, trigonometry in radians, and the
+// constants, which are natives taking nothing, like `math.pi()`. Those
+// taking a `Float` take an `Int` too, widened; those for `Int`s alone end
+// in `Int`, like `math.absInt`.
+//
+// Rounding to an `Int` throws for what no `Int` holds, infinity and NaN
+// among them. As `+` and the other operators throw when an `Int`
+// overflows, `math.checkedAdd` and its like return null instead, and
+// `math.wrappingAdd` and its like wrap around.
+pub fn register(runtime: &mut Runtime) {
+	runtime
+		.register("math.pi", || consts::PI)
+		.register("math.e", || consts::E)
+		.register("math.tau", || consts::TAU)
+		.register("math.infinity", || f64::INFINITY)
+		.register("math.nan", || f64::NAN)
+		.register("math.intMax", || i64::MAX)
+		.register("math.intMin", || i64::MIN)
+		.register("math.sqrt", |x: f64| x.sqrt())
+		.register("math.cbrt", |x: f64| x.cbrt())
+		.register("math.pow", |x: f64, y: f64| x.powf(y))
+		.register("math.powInt", |x: i64, y: i64| u32::try_from(y).ok().and_then(|y| x.checked_pow(y)).ok_or_else(|| overflow("powInt")))
+		.register("math.exp", |x: f64| x.exp())
+		.register("math.ln", |x: f64| x.ln())
+		.register("math.log10", |x: f64| x.log10())
+		.register("math.log2", |x: f64| x.log2())
+		.register("math.abs", |x: f64| x.abs())
+		.register("math.absInt", |x: i64| x.checked_abs().ok_or_else(|| overflow("absInt")))
+		.register("math.sign", |x: f64| if x == 0.0 || x.is_nan() { x } else { x.signum() })
+		.register("math.min", |x: f64, y: f64| x.min(y))
+		.register("math.max", |x: f64, y: f64| x.max(y))
+		.register("math.minInt", |x: i64, y: i64| x.min(y))
+		.register("math.maxInt", |x: i64, y: i64| x.max(y))
+		.register("math.clamp", |x: f64, low: f64, high: f64| if low > high { Err(reversed(low, high)) } else { Ok(x.clamp(low, high)) })
+		.register("math.clampInt", |x: i64, low: i64, high: i64| if low > high { Err(reversed(low, high)) } else { Ok(x.clamp(low, high)) })
+		.register("math.floor", |x: f64| to_int(x.floor()))
+		.register("math.ceil", |x: f64| to_int(x.ceil()))
+		.register("math.round", |x: f64| to_int(x.round()))
+		.register("math.truncate", |x: f64| to_int(x.trunc()))
+		.register("math.toFloat", |x: i64| x as f64)
+		.register("math.isNaN", |x: f64| x.is_nan())
+		.register("math.isFinite", |x: f64| x.is_finite())
+		.register("math.sin", |x: f64| x.sin())
+		.register("math.cos", |x: f64| x.cos())
+		.register("math.tan", |x: f64| x.tan())
+		.register("math.asin", |x: f64| x.asin())
+		.register("math.acos", |x: f64| x.acos())
+		.register("math.atan", |x: f64| x.atan())
+		.register("math.atan2", |y: f64, x: f64| y.atan2(x))
+		.register("math.hypot", |x: f64, y: f64| x.hypot(y))
+		.register("math.toRadians", |degrees: f64| degrees.to_radians())
+		.register("math.toDegrees", |radians: f64| radians.to_degrees())
+		.register("math.checkedAdd", |x: i64, y: i64| x.checked_add(y))
+		.register("math.checkedSub", |x: i64, y: i64| x.checked_sub(y))
+		.register("math.checkedMul", |x: i64, y: i64| x.checked_mul(y))
+		.register("math.checkedDiv", |x: i64, y: i64| x.checked_div(y))
+		.register("math.wrappingAdd", |x: i64, y: i64| x.wrapping_add(y))
+		.register("math.wrappingSub", |x: i64, y: i64| x.wrapping_sub(y))
+		.register("math.wrappingMul", |x: i64, y: i64| x.wrapping_mul(y))
+		.register("math.wrappingNeg", |x: i64| x.wrapping_neg());
+}
+
+// `x`, already a whole number, as an `Int`.
+fn to_int(x: f64) -> Result<i64, String> {
+	// `i64::MAX as f64` rounds up to 2^63, which no `Int` holds
+	if x.is_finite() && x >= i64::MIN as f64 && x < i64::MAX as f64 {
+		Ok(x as i64)
+	} else {
+		Err(format!("{} is out of range for an Int", x))
+	}
+}
+
+fn overflow(name: &str) -> String {
+	format!("Integer overflow in 'math.{}'", name)
+}
+
+fn reversed(low: impl std::fmt::Display, high: impl std::fmt::Display) -> String {
+	format!("Cannot clamp between {} and {}; the lower bound is greater", low, high)
+}
@@ -0,0 +1,48 @@
+pub mod env;
+pub mod fs;
+pub mod http;
+pub mod json;
+pub mod math;
+pub mod os;
+pub mod prelude;
+pub mod process;
+pub mod random;
+pub mod regex;
+pub mod testing;
+pub mod time;
+
+use crate::interpreter::native::Runtime;
+
+// Goes up whenever the standard library changes what programs can use. A
+// project can ask for at least a version with `std` in its manifest, and a
+// compiled program says which it was built with.
+pub const VERSION: u32 = 1;
+
+// The standard library: natives written in Rust, and code written in
+// dotFun, that every program dotfun checks and runs can use. Each module is
+// a package in `std`: the natives of `std.math` are named after it, like
+// `math.sqrt`, and the files of one, like `std.testing`, are checked and
+// compiled with every program. Natives can be called by those names without
+// importing anything, and modules imported like any other package, as
+// `import std.json`, `import std.json.parse` or `import std.testing.*`.
+//
+// The prelude, `std.prelude`, has the natives named by themselves, like
+// `toInt`, and every file can use what is in it without importing it.
+//
+// `print`, `println`, `readLine`, `sleep` and `readFile` are builtins
+// instead, which the engines run themselves, as they use the output and
+// event loop the program runs with.
+pub fn register(runtime: &mut Runtime) {
+	prelude::register(runtime);
+	math::register(runtime);
+	fs::register(runtime);
+	json::register(runtime);
+	time::register(runtime);
+	random::register(runtime);
+	regex::register(runtime);
+	env::register(runtime);
+	os::register(runtime);
+	process::register(runtime);
+	http::register(runtime);
+	testing::register(runtime);
+}
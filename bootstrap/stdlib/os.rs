@@ -0,0 +1,18 @@
+use std::env;
+
+use crate::interpreter::native::Runtime;
+
+// The system the program runs on, and what it was run with. `os.args()` is
+// empty until the host gives it the arguments, as `dotfun run` does with
+// those after `--`, through `args`.
+pub fn register(runtime: &mut Runtime) {
+	runtime
+		.register("os.args", Vec::<String>::new)
+		.register("os.name", || env::consts::OS.to_string())
+		.register("os.cwd", || env::current_dir().map(|dir| dir.to_string_lossy().into_owned()).map_err(|err| format!("Cannot find the current directory: {}", err)));
+}
+
+pub fn args(runtime: &mut Runtime, args: &[String]) {
+	let args = args.to_vec();
+	runtime.register("os.args", move || args.clone());
+}
@@ -0,0 +1,13 @@
+use crate::interpreter::native::Runtime;
+
+// Reading numbers out of text, and checking what should be true. What does
+// not parse is thrown, for `catch` to handle; every value already has
+// `toString()`. `join`, for waiting on many tasks at once, is written in
+// dotFun.
+pub fn register(runtime: &mut Runtime) {
+	runtime
+		.register("toInt", |text: String| text.trim().parse::<i64>().map_err(|_| format!("Cannot read '{}' as an Int", text)))
+		.register("toFloat", |text: String| text.trim().parse::<f64>().map_err(|_| format!("Cannot read '{}' as a Float", text)))
+		.register("assert", |holds: bool| if holds { Ok(()) } else { Err("Assertion failed".to_string()) })
+		.source("std.prelude", include_str!("prelude.gl"));
+}
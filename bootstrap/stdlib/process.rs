@@ -0,0 +1,69 @@
+use std::io::{self, Write};
+use std::process::{self, Command};
+
+use crate::interpreter::native::{Marshal, Runtime};
+use crate::interpreter::value::{Map, Value};
+use crate::sema::types::Type;
+use crate::stdlib::env;
+
+// Other processes, and this one. `process.run` runs a program to the end,
+// with the environment as `env` left it, and returns a map of its exit
+// `status`, null when a signal ended it, and what it wrote to `stdout` and
+// `stderr`:
+//
+//     val result = process.run("git", ["status", "--short"])
+//     if result["status"] != 0 {
+//         println(result["stderr"])
+//     }
+//
+// `process.exit` ends this process at once, with what was printed written
+// out but no `finally` run.
+pub fn register(runtime: &mut Runtime) {
+	runtime
+		.register("process.run", |program: String, args: Vec<String>| run(&program, &args))
+		.register("process.exit", exit);
+}
+
+fn exit(code: i64) {
+	let _ = io::stdout().flush();
+	let _ = io::stderr().flush();
+	process::exit(code as i32)
+}
+
+fn run(program: &str, args: &[String]) -> Result<Finished, String> {
+	let mut command = Command::new(program);
+	command.args(args);
+	env::apply(&mut command);
+	let output = command.output().map_err(|err| format!("Cannot run '{}': {}", program, err))?;
+	Ok(Finished {
+		status: output.status.code(),
+		stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+		stderr: String::from_utf8_lossy(&output.stderr).into_owned()
+	})
+}
+
+// What `process.run` returns.
+struct Finished {
+	status: Option<i32>,
+	stdout: String,
+	stderr: String
+}
+
+impl Marshal for Finished {
+	fn ty() -> Type {
+		Type::Map(Box::new(Type::String), Box::new(Type::Any))
+	}
+
+	// Only ever returned
+	fn from_value(_: &Value) -> Option<Finished> {
+		None
+	}
+
+	fn into_value(self) -> Value {
+		let mut map = Map::new();
+		map.insert(Value::string("status"), self.status.map_or(Value::Null, |status| Value::Int(status.into())));
+		map.insert(Value::string("stdout"), Value::string(self.stdout));
+		map.insert(Value::string("stderr"), Value::string(self.stderr));
+		Value::map(map)
+	}
+}
@@ -0,0 +1,71 @@
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::rc::Rc;
+
+use crate::interpreter::native::{Marshal, Runtime};
+use crate::interpreter::value::{Random, Value};
+use crate::sema::types::Type;
+
+// Pseudo-random numbers. `random.int`, `random.float`, `random.choice` and
+// `random.shuffle` draw from a generator each thread starts with a seed of
+// its own, which `random.seed` fixes, for runs that come out the same each
+// time:
+//
+//     random.seed(42)
+//     val roll = random.int(1, 6)
+//
+// `random.generator(seed)` makes a `Random` of its own, with the same
+// methods, so what draws from it is not thrown off by anything else drawing
+// numbers.
+pub fn register(runtime: &mut Runtime) {
+	runtime
+		.register("random.seed", |seed: i64| DEFAULT.with(|random| random.reseed(seed)))
+		.register("random.int", |low: i64, high: i64| DEFAULT.with(|random| random.int(low, high)))
+		.register("random.float", || DEFAULT.with(Random::float))
+		.register("random.choice", |items: Array| DEFAULT.with(|random| random.choice(&items.0.borrow())))
+		.register("random.shuffle", |items: Array| DEFAULT.with(|random| random.shuffle(&mut items.0.borrow_mut())))
+		.register("random.generator", |seed: i64| Generator(Random::new(seed)));
+}
+
+thread_local! {
+	static DEFAULT: Random = Random::new(RandomState::new().build_hasher().finish() as i64);
+}
+
+// An array as it is, for shuffling in place.
+struct Array(Rc<RefCell<Vec<Value>>>);
+
+impl Marshal for Array {
+	fn ty() -> Type {
+		Type::Array(Box::new(Type::Any))
+	}
+
+	fn from_value(value: &Value) -> Option<Array> {
+		match value {
+			Value::Array(items) => Some(Array(items.clone())),
+			_ => None
+		}
+	}
+
+	fn into_value(self) -> Value {
+		Value::Array(self.0)
+	}
+}
+
+// What `random.generator` returns.
+struct Generator(Random);
+
+impl Marshal for Generator {
+	fn ty() -> Type {
+		Type::Random
+	}
+
+	// Only ever returned
+	fn from_value(_: &Value) -> Option<Generator> {
+		None
+	}
+
+	fn into_value(self) -> Value {
+		Value::Random(Rc::new(self.0))
+	}
+}
@@ -0,0 +1,636 @@
+use crate::interpreter::limits;
+use crate::interpreter::native::Runtime;
+
+// Regular expressions, in the syntax most languages share: `.`, classes like
+// `[a-z]` and `\d`, `^` and `$`, `\b`, groups, `|`, and the repetitions `*`,
+// `+`, `?` and `{n,m}`, lazy with a `?` after them. `(?:...)` groups without
+// capturing. There are no backreferences, so any pattern matches in time
+// proportional to the length of the text.
+//
+// Matches are of characters, as strings are worked on. `regex.match` and
+// `regex.findAll` give each match as an array of what it matched and then
+// what each group did, null for a group that took no part. `regex.replace`
+// replaces every match, with `$1` in the replacement for what group 1
+// matched, `$0` for the whole match and `$$` for a `$`.
+//
+// A pattern that does not compile is thrown, saying where in it:
+//
+//     Invalid pattern 'a(b': '(' is not closed, at 1
+pub fn register(runtime: &mut Runtime) {
+	runtime
+		.register("regex.test", |pattern: String, text: String| Ok::<_, String>(Regex::new(&pattern)?.search(&chars(&text)).find(0)?.is_some()))
+		.register("regex.match", |pattern: String, text: String| {
+			let text = chars(&text);
+			Ok::<_, String>(Regex::new(&pattern)?.search(&text).find(0)?.map(|groups| strings(&text, &groups)))
+		})
+		.register("regex.findAll", |pattern: String, text: String| {
+			let text = chars(&text);
+			Ok::<_, String>(Regex::new(&pattern)?.find_all(&text)?.iter().map(|groups| strings(&text, groups)).collect::<Vec<_>>())
+		})
+		.register("regex.replace", |pattern: String, text: String, replacement: String| replace(&Regex::new(&pattern)?, &text, &replacement))
+		.register("regex.split", |pattern: String, text: String| {
+			let text = chars(&text);
+			let mut pieces = Vec::new();
+			let mut last = 0;
+			for groups in Regex::new(&pattern)?.find_all(&text)? {
+				if let Some((start, end)) = groups[0] {
+					pieces.push(text[last..start].iter().collect::<String>());
+					last = end;
+				}
+			}
+			pieces.push(text[last..].iter().collect());
+			Ok::<_, String>(pieces)
+		})
+		.register("regex.escape", |text: String| {
+			text.chars().fold(String::new(), |mut escaped, char| {
+				if "\\.+*?()|[]{}^$".contains(char) {
+					escaped.push('\\');
+				}
+				escaped.push(char);
+				escaped
+			})
+		});
+}
+
+fn chars(text: &str) -> Vec<char> {
+	text.chars().collect()
+}
+
+// Where a match and each group are in the text, by character.
+type Groups = Vec<Option<(usize, usize)>>;
+
+fn strings(text: &[char], groups: &Groups) -> Vec<Option<String>> {
+	groups.iter().map(|group| group.map(|(start, end)| text[start..end].iter().collect())).collect()
+}
+
+fn replace(regex: &Regex, text: &str, replacement: &str) -> Result<String, String> {
+	let text = chars(text);
+	let mut out = String::new();
+	let mut last = 0;
+	for groups in regex.find_all(&text)? {
+		let Some((start, end)) = groups[0] else { continue };
+		out.extend(&text[last..start]);
+		let mut rest = replacement.chars().peekable();
+		while let Some(char) = rest.next() {
+			if char != '$' {
+				out.push(char);
+			} else if rest.next_if_eq(&'$').is_some() {
+				out.push('$');
+			} else if rest.peek().is_some_and(char::is_ascii_digit) {
+				let mut group = 0;
+				while let Some(digit) = rest.next_if(char::is_ascii_digit) {
+					group = group * 10 + digit as usize - '0' as usize;
+				}
+				match groups.get(group) {
+					Some(Some((start, end))) => out.extend(&text[*start..*end]),
+					Some(None) => {}
+					None => return Err(format!("The replacement uses group {}, but the pattern has {}", group, groups.len() - 1))
+				}
+			} else {
+				out.push('$');
+			}
+		}
+		last = end;
+	}
+	out.extend(&text[last..]);
+	Ok(out)
+}
+
+// A compiled pattern: a program of instructions that matching backtracks
+// through, never trying an instruction at the same place in the text twice.
+struct Regex {
+	program: Vec<Inst>,
+	groups: usize
+}
+
+#[derive(Debug, Clone)]
+enum Inst {
+	Char(char),
+	// Any character but a line break
+	Any,
+	Class(Class),
+	Assert(Assertion),
+	// Tries the first, then the second
+	Split(usize, usize),
+	Jump(usize),
+	// Records the place in the text in this slot: the start of a group in
+	// the even ones, the end in the odd ones
+	Save(usize),
+	Match
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Assertion {
+	Start,
+	End,
+	WordBoundary,
+	NotWordBoundary
+}
+
+// Characters from ranges, or from outside them when negated.
+#[derive(Debug, Clone)]
+struct Class {
+	ranges: Vec<(char, char)>,
+	negated: bool
+}
+
+impl Class {
+	fn has(&self, char: char) -> bool {
+		self.ranges.iter().any(|&(low, high)| low <= char && char <= high) != self.negated
+	}
+}
+
+const DIGITS: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+const SPACE: &[(char, char)] = &[('\t', '\r'), (' ', ' ')];
+
+// More than this many of something is most likely a mistake, and would make
+// the program as big.
+const MAX_REPEAT: u32 = 1000;
+
+impl Regex {
+	fn new(pattern: &str) -> Result<Regex, String> {
+		let mut parser = Parser { chars: chars(pattern), at: 0, groups: 0 };
+		let node = parser
+			.alternation()
+			.and_then(|node| match parser.peek() {
+				Some(')') => Err(("unmatched ')'".to_string(), parser.at)),
+				_ => Ok(node)
+			})
+			.map_err(|(message, at)| format!("Invalid pattern '{}': {}, at {}", pattern, message, at))?;
+		let mut program = vec![Inst::Save(0)];
+		compile(&node, &mut program);
+		program.extend([Inst::Save(1), Inst::Match]);
+		Ok(Regex { program, groups: parser.groups })
+	}
+
+	fn search<'a>(&'a self, text: &'a [char]) -> Search<'a> {
+		Search { regex: self, text, blocks: vec![None; text.len() / BLOCK + 1], made: Vec::new() }
+	}
+
+	// Each match, not overlapping; after an empty one, the next starts a
+	// character further on.
+	fn find_all(&self, text: &[char]) -> Result<Vec<Groups>, String> {
+		let mut search = self.search(text);
+		let mut matches = Vec::new();
+		let mut from = 0;
+		while from <= text.len() {
+			let Some(groups) = search.find(from)? else { break };
+			let Some((start, end)) = groups[0] else { break };
+			from = if end == start { end + 1 } else { end };
+			matches.push(groups);
+		}
+		Ok(matches)
+	}
+}
+
+// Places in the text whose tries are kept together.
+const BLOCK: usize = 64;
+
+// Matching a pattern in a text. Each instruction at each place in the text
+// is tried once per `find`, as failing there once fails there again. What
+// was tried is kept a block of places at a time, made when the search first
+// gets there and dropped once it starts past it, so only the places between
+// where it started and the furthest it got take up memory. That memory is
+// counted against the program's limit, if it has one.
+struct Search<'a> {
+	regex: &'a Regex,
+	text: &'a [char],
+	// Of each block, a bit for each instruction at each place
+	blocks: Vec<Option<Box<[u64]>>>,
+	// The blocks made, in no order
+	made: Vec<usize>
+}
+
+impl Search<'_> {
+	// The first match at or after `from`, with its groups, or the error of
+	// using more memory than the program may.
+	fn find(&mut self, from: usize) -> Result<Option<Groups>, String> {
+		self.forget(usize::MAX);
+		for start in from..=self.text.len() {
+			// Nothing starting here gets to the blocks before it
+			if start % BLOCK == 0 {
+				self.forget(start / BLOCK);
+			}
+			if let Some(groups) = self.run(start)? {
+				return Ok(Some(groups));
+			}
+		}
+		Ok(None)
+	}
+
+	// Drops the blocks before `block`.
+	fn forget(&mut self, block: usize) {
+		let blocks = &mut self.blocks;
+		self.made.retain(|&made| {
+			if made < block {
+				blocks[made] = None;
+			}
+			made >= block
+		});
+	}
+
+	// Whether instruction `pc` was tried at `at` already, marking it tried.
+	fn tried(&mut self, pc: usize, at: usize) -> Result<bool, String> {
+		let length = self.regex.program.len();
+		let block = match &mut self.blocks[at / BLOCK] {
+			Some(block) => block,
+			empty => {
+				limits::reserve((self.made.len() + 1) * length * size_of::<u64>())?;
+				self.made.push(at / BLOCK);
+				empty.insert(vec![0; length].into_boxed_slice())
+			}
+		};
+		let bit = at % BLOCK * length + pc;
+		let tried = block[bit / 64] & (1 << (bit % 64)) != 0;
+		block[bit / 64] |= 1 << (bit % 64);
+		Ok(tried)
+	}
+
+	// Backtracks from `start`, trying each way in the order the pattern
+	// prefers, which for greedy repetitions is taking more.
+	fn run(&mut self, start: usize) -> Result<Option<Groups>, String> {
+		enum Job {
+			Try(usize, usize),
+			Restore(usize, Option<usize>)
+		}
+		let (program, text) = (&self.regex.program, self.text);
+		let mut slots: Vec<Option<usize>> = vec![None; 2 * (self.regex.groups + 1)];
+		let mut jobs = vec![Job::Try(0, start)];
+		while let Some(job) = jobs.pop() {
+			let (mut pc, mut at) = match job {
+				Job::Try(pc, at) => (pc, at),
+				Job::Restore(slot, value) => {
+					slots[slot] = value;
+					continue;
+				}
+			};
+			loop {
+				if self.tried(pc, at)? {
+					break;
+				}
+				let char = text.get(at).copied();
+				match &program[pc] {
+					Inst::Char(expected) if char == Some(*expected) => {}
+					Inst::Any if char.is_some_and(|char| char != '\n') => {}
+					Inst::Class(class) if char.is_some_and(|char| class.has(char)) => {}
+					Inst::Char(_) | Inst::Any | Inst::Class(_) => break,
+					Inst::Assert(assertion) => {
+						let word = |at: Option<usize>| at.and_then(|at| text.get(at)).is_some_and(|&char| char.is_alphanumeric() || char == '_');
+						let holds = match assertion {
+							Assertion::Start => at == 0,
+							Assertion::End => at == text.len(),
+							boundary => (word(at.checked_sub(1)) != word(Some(at))) == (*boundary == Assertion::WordBoundary)
+						};
+						if !holds {
+							break;
+						}
+						pc += 1;
+						continue;
+					}
+					Inst::Split(first, second) => {
+						jobs.push(Job::Try(*second, at));
+						pc = *first;
+						continue;
+					}
+					Inst::Jump(target) => {
+						pc = *target;
+						continue;
+					}
+					Inst::Save(slot) => {
+						jobs.push(Job::Restore(*slot, slots[*slot]));
+						slots[*slot] = Some(at);
+						pc += 1;
+						continue;
+					}
+					Inst::Match => return Ok(Some(slots.chunks(2).map(|pair| Some((pair[0]?, pair[1]?))).collect()))
+				}
+				pc += 1;
+				at += 1;
+			}
+		}
+		Ok(None)
+	}
+}
+
+#[derive(Debug)]
+enum Node {
+	Empty,
+	Char(char),
+	Any,
+	Class(Class),
+	Assert(Assertion),
+	Group(Box<Node>, Option<usize>),
+	Concat(Vec<Node>),
+	Alternation(Vec<Node>),
+	Repeat { node: Box<Node>, min: u32, max: Option<u32>, greedy: bool }
+}
+
+fn compile(node: &Node, program: &mut Vec<Inst>) {
+	match node {
+		Node::Empty => {}
+		Node::Char(char) => program.push(Inst::Char(*char)),
+		Node::Any => program.push(Inst::Any),
+		Node::Class(class) => program.push(Inst::Class(class.clone())),
+		Node::Assert(assertion) => program.push(Inst::Assert(*assertion)),
+		Node::Group(node, None) => compile(node, program),
+		Node::Group(node, Some(index)) => {
+			program.push(Inst::Save(2 * index));
+			compile(node, program);
+			program.push(Inst::Save(2 * index + 1));
+		}
+		Node::Concat(nodes) => {
+			for node in nodes {
+				compile(node, program);
+			}
+		}
+		Node::Alternation(options) => {
+			let mut jumps = Vec::new();
+			for (index, option) in options.iter().enumerate() {
+				if index + 1 < options.len() {
+					let split = program.len();
+					program.push(Inst::Split(split + 1, 0));
+					compile(option, program);
+					jumps.push(program.len());
+					program.push(Inst::Jump(0));
+					program[split] = Inst::Split(split + 1, program.len());
+				} else {
+					compile(option, program);
+				}
+			}
+			let end = program.len();
+			for jump in jumps {
+				program[jump] = Inst::Jump(end);
+			}
+		}
+		Node::Repeat { node, min, max, greedy } => {
+			for _ in 0..*min {
+				compile(node, program);
+			}
+			let split = |body: usize, out: usize| if *greedy { Inst::Split(body, out) } else { Inst::Split(out, body) };
+			match max {
+				None => {
+					let start = program.len();
+					program.push(Inst::Jump(start));
+					compile(node, program);
+					program.push(Inst::Jump(start));
+					program[start] = split(start + 1, program.len());
+				}
+				// Each optional one split off to the end
+				Some(max) => {
+					let mut splits = Vec::new();
+					for _ in *min..*max {
+						splits.push(program.len());
+						program.push(Inst::Jump(0));
+						compile(node, program);
+					}
+					let end = program.len();
+					for at in splits {
+						program[at] = split(at + 1, end);
+					}
+				}
+			}
+		}
+	}
+}
+
+// Reads a pattern, failing with what is wrong and where, by character.
+struct Parser {
+	chars: Vec<char>,
+	at: usize,
+	groups: usize
+}
+
+type Parsed<T> = Result<T, (String, usize)>;
+
+impl Parser {
+	fn peek(&self) -> Option<char> {
+		self.chars.get(self.at).copied()
+	}
+
+	fn eat(&mut self, char: char) -> bool {
+		let found = self.peek() == Some(char);
+		if found {
+			self.at += 1;
+		}
+		found
+	}
+
+	fn alternation(&mut self) -> Parsed<Node> {
+		let mut options = vec![self.concat()?];
+		while self.eat('|') {
+			options.push(self.concat()?);
+		}
+		Ok(if options.len() == 1 { options.remove(0) } else { Node::Alternation(options) })
+	}
+
+	fn concat(&mut self) -> Parsed<Node> {
+		let mut nodes = Vec::new();
+		while let Some(char) = self.peek() {
+			if char == '|' || char == ')' {
+				break;
+			}
+			let atom = self.atom()?;
+			nodes.push(self.repeat(atom)?);
+		}
+		Ok(match nodes.len() {
+			0 => Node::Empty,
+			1 => nodes.remove(0),
+			_ => Node::Concat(nodes)
+		})
+	}
+
+	fn atom(&mut self) -> Parsed<Node> {
+		let at = self.at;
+		let char = self.chars[at];
+		self.at += 1;
+		Ok(match char {
+			'.' => Node::Any,
+			'^' => Node::Assert(Assertion::Start),
+			'$' => Node::Assert(Assertion::End),
+			'(' => {
+				let index = if self.eat('?') {
+					if !self.eat(':') {
+						return Err(("only '(?:' groups are supported".to_string(), at));
+					}
+					None
+				} else {
+					self.groups += 1;
+					Some(self.groups)
+				};
+				let node = self.alternation()?;
+				if !self.eat(')') {
+					return Err(("'(' is not closed".to_string(), at));
+				}
+				Node::Group(Box::new(node), index)
+			}
+			'[' => Node::Class(self.class(at)?),
+			'*' | '+' | '?' => return Err((format!("nothing to repeat before '{}'", char), at)),
+			'{' if self.quantifier(at).is_some() => return Err(("nothing to repeat before '{'".to_string(), at)),
+			'\\' => match self.escape(at)? {
+				Escape::Char(char) => Node::Char(char),
+				Escape::Class(class) => Node::Class(class),
+				Escape::Assert(assertion) => Node::Assert(assertion)
+			},
+			char => Node::Char(char)
+		})
+	}
+
+	// A repetition after `atom`, if there is one.
+	fn repeat(&mut self, atom: Node) -> Parsed<Node> {
+		let at = self.at;
+		let (min, max) = match self.peek() {
+			Some('*') => (0, None),
+			Some('+') => (1, None),
+			Some('?') => (0, Some(1)),
+			Some('{') => match self.quantifier(at) {
+				Some((min, max, length)) => {
+					self.at += length - 1;
+					(min, max)
+				}
+				None => return Ok(atom)
+			},
+			_ => return Ok(atom)
+		};
+		self.at += 1;
+		if matches!(atom, Node::Assert(_)) {
+			return Err(("nothing to repeat".to_string(), at));
+		}
+		if min > MAX_REPEAT || max.is_some_and(|max| max > MAX_REPEAT) {
+			return Err((format!("cannot repeat more than {} times", MAX_REPEAT), at));
+		}
+		if max.is_some_and(|max| max < min) {
+			return Err(("the repetition's maximum is less than its minimum".to_string(), at));
+		}
+		let greedy = !self.eat('?');
+		if matches!(self.peek(), Some('*' | '+' | '?')) {
+			return Err(("nothing to repeat".to_string(), self.at));
+		}
+		Ok(Node::Repeat { node: Box::new(atom), min, max, greedy })
+	}
+
+	// `{n}`, `{n,}` or `{n,m}` from the `{` at `start`, and its length; a `{`
+	// of anything else is only a `{`.
+	fn quantifier(&self, start: usize) -> Option<(u32, Option<u32>, usize)> {
+		let end = start + self.chars[start..].iter().position(|&char| char == '}')?;
+		let inside: String = self.chars[start + 1..end].iter().collect();
+		// Too many to count is too many to repeat
+		let number = |text: &str| (!text.is_empty() && text.chars().all(|char| char.is_ascii_digit())).then(|| text.parse::<u32>().unwrap_or(u32::MAX));
+		let (min, max) = match inside.split_once(',') {
+			None => {
+				let count = number(&inside)?;
+				(count, Some(count))
+			}
+			Some((min, "")) => (number(min)?, None),
+			Some((min, max)) => (number(min)?, Some(number(max)?))
+		};
+		Some((min, max, end - start + 1))
+	}
+
+	// After the `[` at `start`.
+	fn class(&mut self, start: usize) -> Parsed<Class> {
+		let negated = self.eat('^');
+		let mut ranges = Vec::new();
+		let mut first = true;
+		loop {
+			let at = self.at;
+			let low = match self.peek() {
+				None => return Err(("'[' is not closed".to_string(), start)),
+				Some(']') if !first => {
+					self.at += 1;
+					break;
+				}
+				Some('\\') => {
+					self.at += 1;
+					match self.escape(at)? {
+						Escape::Char(char) => char,
+						Escape::Class(class) => {
+							ranges.extend(class_ranges(&class));
+							first = false;
+							continue;
+						}
+						Escape::Assert(_) => return Err(("'\\b' cannot be in a class".to_string(), at))
+					}
+				}
+				Some(char) => {
+					self.at += 1;
+					char
+				}
+			};
+			first = false;
+			if self.peek() == Some('-') && self.chars.get(self.at + 1).is_some_and(|&char| char != ']') {
+				self.at += 1;
+				let high_at = self.at;
+				let high = match self.chars[high_at] {
+					'\\' => {
+						self.at += 1;
+						match self.escape(high_at)? {
+							Escape::Char(char) => char,
+							_ => return Err(("a range has to end in a character".to_string(), high_at))
+						}
+					}
+					char => {
+						self.at += 1;
+						char
+					}
+				};
+				if high < low {
+					return Err((format!("the range '{}-{}' is out of order", low, high), at));
+				}
+				ranges.push((low, high));
+			} else {
+				ranges.push((low, low));
+			}
+		}
+		Ok(Class { ranges, negated })
+	}
+
+	// After the `\` at `start`.
+	fn escape(&mut self, start: usize) -> Parsed<Escape> {
+		let Some(char) = self.peek() else {
+			return Err(("the pattern ends in '\\'".to_string(), start));
+		};
+		self.at += 1;
+		let class = |ranges: &[(char, char)], negated: bool| Escape::Class(Class { ranges: ranges.to_vec(), negated });
+		Ok(match char {
+			'd' => class(DIGITS, false),
+			'D' => class(DIGITS, true),
+			'w' => class(WORD, false),
+			'W' => class(WORD, true),
+			's' => class(SPACE, false),
+			'S' => class(SPACE, true),
+			'b' => Escape::Assert(Assertion::WordBoundary),
+			'B' => Escape::Assert(Assertion::NotWordBoundary),
+			'n' => Escape::Char('\n'),
+			't' => Escape::Char('\t'),
+			'r' => Escape::Char('\r'),
+			char if char.is_ascii_alphanumeric() => return Err((format!("unknown escape '\\{}'", char), start)),
+			char => Escape::Char(char)
+		})
+	}
+}
+
+enum Escape {
+	Char(char),
+	Class(Class),
+	Assert(Assertion)
+}
+
+// The ranges of a class of an escape, like `\D`, turned inside out if it is
+// negated, for putting in a class of its own. Theirs are in order and
+// ASCII.
+fn class_ranges(class: &Class) -> Vec<(char, char)> {
+	if !class.negated {
+		return class.ranges.clone();
+	}
+	let mut ranges = Vec::new();
+	let mut next = '\0';
+	for &(low, high) in &class.ranges {
+		if low > next {
+			ranges.push((next, (low as u8 - 1) as char));
+		}
+		next = (high as u8 + 1) as char;
+	}
+	ranges.push((next, char::MAX));
+	ranges
+}
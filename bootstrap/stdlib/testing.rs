@@ -0,0 +1,7 @@
+use crate::interpreter::native::Runtime;
+
+// Assertions for tests, written in dotFun rather than Rust, as what they do
+// is call and compare values: `import std.testing.*` to use them.
+pub fn register(runtime: &mut Runtime) {
+	runtime.source("std.testing", include_str!("testing.gl"));
+}
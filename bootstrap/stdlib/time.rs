@@ -0,0 +1,189 @@
+use std::sync::OnceLock;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::interpreter::native::Runtime;
+
+// Times and durations, both `Int`s of milliseconds: times since the start of
+// 1970 in UTC, and durations as `sleep` takes them, so `+` and `-` are their
+// arithmetic and `sleep(time.seconds(2))` waits on the event loop like any
+// other `sleep`.
+//
+// `time.now()` follows the system's clock, which may be set back;
+// `time.monotonic()` never goes back, for measuring how long something took.
+// `time.format` writes a time in ISO 8601, like `2024-03-01T12:30:00.000Z`,
+// and `time.parse` reads one back, a date alone or with an offset from UTC
+// too.
+pub fn register(runtime: &mut Runtime) {
+	runtime
+		.register("time.now", now)
+		.register("time.monotonic", || start().elapsed().as_millis() as i64)
+		.register("time.seconds", |n: i64| scale(n, 1_000, "seconds"))
+		.register("time.minutes", |n: i64| scale(n, 60_000, "minutes"))
+		.register("time.hours", |n: i64| scale(n, 3_600_000, "hours"))
+		.register("time.days", |n: i64| scale(n, DAY, "days"))
+		.register("time.toSeconds", |millis: i64| millis as f64 / 1_000.0)
+		.register("time.date", |year: i64, month: i64, day: i64| date(year, month, day).ok_or_else(|| format!("{}-{}-{} is not a date", year, month, day)))
+		.register("time.format", format)
+		.register("time.parse", |text: String| parse(&text).ok_or_else(|| format!("Cannot read '{}' as an ISO 8601 time", text)));
+}
+
+const DAY: i64 = 86_400_000;
+
+// Past this, counting days could overflow before the milliseconds do.
+const MAX_YEAR: i64 = 300_000_000;
+
+// What `time.monotonic()` counts from: its first call.
+fn start() -> Instant {
+	static START: OnceLock<Instant> = OnceLock::new();
+	*START.get_or_init(Instant::now)
+}
+
+fn now() -> i64 {
+	match SystemTime::now().duration_since(UNIX_EPOCH) {
+		Ok(since) => since.as_millis() as i64,
+		Err(before) => -(before.duration().as_millis() as i64)
+	}
+}
+
+fn scale(n: i64, unit: i64, name: &str) -> Result<i64, String> {
+	n.checked_mul(unit).ok_or_else(|| format!("Integer overflow in 'time.{}'", name))
+}
+
+// Midnight at the start of a day, when there is one and an `Int` holds it,
+// which it does for some 290 million years either side of 1970.
+fn date(year: i64, month: i64, day: i64) -> Option<i64> {
+	if !(-MAX_YEAR..=MAX_YEAR).contains(&year) || !(1..=12).contains(&month) || day < 1 || day > days_in(year, month) {
+		return None;
+	}
+	days_from_civil(year, month, day).checked_mul(DAY)
+}
+
+fn days_in(year: i64, month: i64) -> i64 {
+	match month {
+		2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+		2 => 28,
+		4 | 6 | 9 | 11 => 30,
+		_ => 31
+	}
+}
+
+// Days since 1970-01-01 of a date in the proleptic Gregorian calendar, and
+// back, counting in 400-year eras from March, so leap days come last.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+	let year = if month <= 2 { year - 1 } else { year };
+	let era = year.div_euclid(400);
+	let of_era = year - era * 400;
+	let of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+	let of_cycle = of_era * 365 + of_era / 4 - of_era / 100 + of_year;
+	era * 146_097 + of_cycle - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+	let days = days + 719_468;
+	let era = days.div_euclid(146_097);
+	let of_cycle = days - era * 146_097;
+	let of_era = (of_cycle - of_cycle / 1_460 + of_cycle / 36_524 - of_cycle / 146_096) / 365;
+	let of_year = of_cycle - (365 * of_era + of_era / 4 - of_era / 100);
+	let shifted = (5 * of_year + 2) / 153;
+	let day = of_year - (153 * shifted + 2) / 5 + 1;
+	let month = if shifted < 10 { shifted + 3 } else { shifted - 9 };
+	let year = of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+	(year, month, day)
+}
+
+// In UTC, to the millisecond. Years past 9999 or before 0 get a sign, as
+// ISO 8601 writes them.
+fn format(time: i64) -> String {
+	let (year, month, day) = civil_from_days(time.div_euclid(DAY));
+	let of_day = time.rem_euclid(DAY);
+	let year = if (0..=9999).contains(&year) { format!("{:04}", year) } else { format!("{:+05}", year) };
+	format!(
+		"{}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+		year,
+		month,
+		day,
+		of_day / 3_600_000,
+		of_day / 60_000 % 60,
+		of_day / 1_000 % 60,
+		of_day % 1_000
+	)
+}
+
+// `YYYY-MM-DD`, or with `THH:MM`, seconds and a fraction of them if given,
+// and then `Z` or an offset like `+02:00`. Without one, the time is in UTC.
+fn parse(text: &str) -> Option<i64> {
+	let mut reader = Reader { text: text.as_bytes(), at: 0 };
+	let year = reader.number(4)?;
+	reader.expect(b'-')?;
+	let month = reader.number(2)?;
+	reader.expect(b'-')?;
+	let day = reader.number(2)?;
+	let mut time = date(year, month, day)?;
+	if reader.eat(b'T') || reader.eat(b't') || reader.eat(b' ') {
+		let hour = reader.number(2)?;
+		reader.expect(b':')?;
+		let minute = reader.number(2)?;
+		let second = if reader.eat(b':') { reader.number(2)? } else { 0 };
+		let millis = if reader.eat(b'.') { reader.fraction()? } else { 0 };
+		if hour > 23 || minute > 59 || second > 59 {
+			return None;
+		}
+		time += ((hour * 60 + minute) * 60 + second) * 1_000 + millis;
+		if !reader.eat(b'Z')
+			&& !reader.eat(b'z')
+			&& let Some(sign) = [b'+', b'-'].into_iter().find(|&sign| reader.eat(sign))
+		{
+			let hours = reader.number(2)?;
+			reader.eat(b':');
+			let minutes = reader.number(2)?;
+			if hours > 23 || minutes > 59 {
+				return None;
+			}
+			let offset = (hours * 60 + minutes) * 60_000;
+			time = if sign == b'+' { time - offset } else { time + offset };
+		}
+	}
+	(reader.at == text.len()).then_some(time)
+}
+
+struct Reader<'a> {
+	text: &'a [u8],
+	at: usize
+}
+
+impl Reader<'_> {
+	fn eat(&mut self, byte: u8) -> bool {
+		let found = self.text.get(self.at) == Some(&byte);
+		if found {
+			self.at += 1;
+		}
+		found
+	}
+
+	fn expect(&mut self, byte: u8) -> Option<()> {
+		self.eat(byte).then_some(())
+	}
+
+	// Exactly `digits` of them.
+	fn number(&mut self, digits: usize) -> Option<i64> {
+		let text = self.text.get(self.at..self.at + digits)?;
+		if !text.iter().all(u8::is_ascii_digit) {
+			return None;
+		}
+		self.at += digits;
+		Some(text.iter().fold(0, |number, digit| number * 10 + (digit - b'0') as i64))
+	}
+
+	// Milliseconds of a fraction of a second, with what is past them dropped.
+	fn fraction(&mut self) -> Option<i64> {
+		let start = self.at;
+		while self.text.get(self.at).is_some_and(u8::is_ascii_digit) {
+			self.at += 1;
+		}
+		let digits = &self.text[start..self.at];
+		if digits.is_empty() {
+			return None;
+		}
+		Some(digits.iter().chain([b'0'; 3].iter()).take(3).fold(0, |millis, digit| millis * 10 + (digit - b'0') as i64))
+	}
+}
@@ -0,0 +1,86 @@
+// How command lines are read, and the ones that make no sense.
+
+use glee::driver::args::{Emit, LCOV, Profile, Target, Timings, parse};
+
+fn args(line: &str) -> Vec<String> {
+	line.split_whitespace().map(str::to_string).collect()
+}
+
+#[test]
+fn emit_goes_to_stdout_or_a_file() {
+	let options = parse(args("check - --emit tokens,hir=out.hir --emit=ast")).unwrap();
+	assert_eq!(options.inputs, ["-"]);
+	assert_eq!(options.emit, [(Emit::Tokens, None), (Emit::Hir, Some("out.hir".to_string())), (Emit::Ast, None)]);
+	assert_eq!(options.emit_path(Emit::Tokens), None);
+	assert_eq!(options.emit_path(Emit::Hir), Some("out.hir"));
+
+	let errors = [
+		("check --emit hir=", "'--emit hir=' needs a file"),
+		("check --emit hir,hir=out.hir", "'--emit hir' is given twice"),
+		("check --emit tree", "Unknown --emit 'tree'; expected 'tokens', 'ast', 'hir' or 'bytecode'")
+	];
+	for (line, expected) in errors {
+		assert_eq!(parse(args(line)).unwrap_err(), expected, "parsing {:?}", line);
+	}
+}
+
+#[test]
+fn timings_print_a_table_or_write_a_trace() {
+	assert_eq!(parse(args("check --timings main.gl")).unwrap().timings, Some(Timings::Table));
+	assert_eq!(parse(args("check --timings main.gl")).unwrap().inputs, ["main.gl"]);
+	assert_eq!(parse(args("check --timings=trace.json")).unwrap().timings, Some(Timings::Trace("trace.json".to_string())));
+	assert_eq!(parse(args("check --timings=")).unwrap_err(), "'--timings=' needs a file");
+}
+
+#[test]
+fn compiled_programs_can_only_be_run_on_their_own() {
+	assert_eq!(parse(args("run app.dfc -- a b")).unwrap().program_args, ["a", "b"]);
+	assert_eq!(parse(args("build --out app.dfc main.gl")).unwrap().out.as_deref(), Some("app.dfc"));
+	for line in ["check app.dfc", "run main.gl app.dfc", "run --watch app.dfc"] {
+		assert_eq!(parse(args(line)).unwrap_err(), "A .dfc file can only be run, on its own", "parsing {:?}", line);
+	}
+	assert_eq!(parse(args("fmt --out x")).unwrap_err(), "'--out' only goes with 'doc' or 'build'");
+}
+
+#[test]
+fn garbage_collection_is_set_for_run_and_test() {
+	let options = parse(args("run --gc-threshold 500 --gc-stress main.gl")).unwrap();
+	assert_eq!((options.gc.threshold, options.gc.stress), (500, true));
+	assert_eq!(parse(args("run --gc-threshold 0")).unwrap_err(), "'--gc-threshold 0' needs a count above 0");
+	assert_eq!(parse(args("check --gc-stress")).unwrap_err(), "'--gc-stress' only goes with 'run' or 'test'");
+}
+
+#[test]
+fn targets_are_for_build() {
+	assert_eq!(parse(args("build main.gl")).unwrap().target, Target::Bytecode);
+	assert_eq!(parse(args("build --target native main.gl")).unwrap().target, Target::Native);
+	assert_eq!(parse(args("build --target wasm")).unwrap_err(), "Unknown --target 'wasm'; expected 'bytecode' or 'native'");
+	assert_eq!(parse(args("run --target native")).unwrap_err(), "'--target' only goes with 'build'");
+}
+
+#[test]
+fn the_debugger_cannot_debug_stdin() {
+	assert_eq!(parse(args("debug main.gl -- a")).unwrap().program_args, ["a"]);
+	assert_eq!(parse(args("debug -")).unwrap_err(), "Cannot debug stdin, which the debugger reads its commands from");
+}
+
+#[test]
+fn editors_say_what_the_debug_adapter_debugs() {
+	assert_eq!(parse(args("dap main.gl")).unwrap_err(), "'dap' takes no files; the editor says what to debug");
+}
+
+#[test]
+fn profiles_of_runs_print_a_table_or_write_folded_stacks() {
+	assert_eq!(parse(args("run --profile main.gl")).unwrap().profile, Some(Profile::Table));
+	assert_eq!(parse(args("run --profile=out.folded main.gl")).unwrap().profile, Some(Profile::Folded("out.folded".to_string())));
+	assert_eq!(parse(args("run --profile=")).unwrap_err(), "'--profile=' needs a file");
+	assert_eq!(parse(args("test --profile")).unwrap_err(), "'--profile' only goes with 'run'");
+}
+
+#[test]
+fn coverage_of_tests_is_written_to_lcov_info_unless_a_file_is_named() {
+	assert_eq!(parse(args("test --coverage main.gl")).unwrap().coverage.as_deref(), Some(LCOV));
+	assert_eq!(parse(args("test --coverage=out/tests.info")).unwrap().coverage.as_deref(), Some("out/tests.info"));
+	assert_eq!(parse(args("test --coverage=")).unwrap_err(), "'--coverage=' needs a file");
+	assert_eq!(parse(args("run --coverage main.gl")).unwrap_err(), "'--coverage' only goes with 'test'");
+}
@@ -0,0 +1,744 @@
+// What the bytecode compiler makes of programs, and that the VM runs them as
+// the interpreter does.
+
+mod common;
+
+use common::{checked, run_both};
+use glee::bytecode::compiler::compile;
+use glee::bytecode::disassemble::disassemble;
+use glee::bytecode::Op;
+use glee::bytecode::file::{self, MAGIC, VERSION};
+use glee::bytecode::vm::{self, Vm};
+use glee::interpreter::gc::{self, Settings};
+use glee::interpreter::limits::RuntimeLimits;
+use glee::interpreter::native::Runtime;
+use glee::interpreter::{self, Uncaught};
+use glee::stdlib;
+
+// Runs `source` on the VM, collecting garbage as often as it can, and on
+// the interpreter, checks they agree, and returns what it printed and what
+// stopped it.
+fn run(source: &str) -> (String, Option<Uncaught>) {
+	run_both(&checked(source), &Runtime::new(), Settings { stress: true, ..Settings::default() })
+}
+
+#[test]
+fn functions_disassemble_one_instruction_a_line() {
+	let program = checked("fn add(a: Int, b: Int = 1): Int = a + b
+
+fn main() {
+	println(add(2))
+}");
+	let text = disassemble(&compile(&program, None));
+	let add: Vec<&str> = text.lines().take(8).collect();
+	assert_eq!(add, [
+		"fn add (main.gl:1:4), 2 params, 2 slots",
+		"  0000  1:4     JumpIfGiven 1 0013",
+		"  0007  1:25    Constant 1",
+		"  0010  1:4     SetLocal 1",
+		"  0013  1:35    GetLocal 0",
+		"  0016  1:39    GetLocal 1",
+		"  0019  1:35    Add",
+		"  0020          Return"
+	]);
+	assert!(text.contains("CallFunction add 1"), "{}", text);
+	assert!(text.contains("Builtin println"), "{}", text);
+}
+
+#[test]
+fn loops_closures_and_defaults_run_as_interpreted() {
+	let (out, _) = run("val limit = 3
+
+fn counter(start: Int = 10): () -> Int {
+	mut count = start
+	return () => {
+		count += 1
+		return count
+	}
+}
+
+fn sum(...numbers: Int): Int {
+	mut total = 0
+	for n in numbers {
+		total += n
+	}
+	return total
+}
+
+fn main() {
+	val next = counter()
+	next()
+	println(next())
+	mut found = 0
+	outer: for i in 0..10 {
+		for j in 0..10 {
+			if j == limit { continue outer }
+			if i == limit { break outer }
+			found += j
+		}
+	}
+	val names: String?[] = [\"a\", null]
+	for (i, name) of names {
+		println(i + \": \" + (name?.length ?? -1))
+	}
+	val counts = [1, 2]
+	counts[1]++
+	switch found {
+		case 9: println(\"nine \" + sum(1, 2, 3) + \" \" + counts)
+		default: println(found)
+	}
+}");
+	assert_eq!(out, "12\n0: 1\n1: -1\nnine 6 [1, 3]\n");
+}
+
+#[test]
+fn classes_run_as_interpreted() {
+	let (out, _) = run("interface Shape {
+	fn area(): Int
+	fn describe(): String = \"area \" + area()
+}
+
+class Rect : Shape {
+	val width: Int
+	val height: Int
+	constructor(width: Int, height: Int) {
+		this.width = width
+		this.height = height
+	}
+	override fn area(): Int = width * height
+}
+
+class Square : Rect {
+	constructor(side: Int) {
+		super(side, side)
+	}
+	override fn describe(): String = \"square, \" + super.describe()
+}
+
+class Thermometer {
+	mut celsius: Int = 0
+		get() = field
+		set(value) { field = value * 2 }
+}
+
+struct Size {
+	width: Int
+	height: Int = 1
+}
+
+enum Color { Red, Green }
+
+fn main() {
+	val shapes: Shape[] = [Rect(2, 3), Square(4)]
+	for shape in shapes {
+		println(shape.describe() + \" \" + (shape is Square))
+	}
+	val thermometer = Thermometer()
+	thermometer.celsius = 5
+	println(thermometer.celsius)
+	println(Size(2) == Size(2, 1))
+	println(Color.Green)
+	val describe = shapes[0].describe
+	println(describe())
+}");
+	assert_eq!(out, "area 6 false\nsquare, area 16 true\n10\ntrue\nGreen\narea 6\n");
+}
+
+#[test]
+fn errors_unwind_through_finally_as_interpreted() {
+	let (out, uncaught) = run("fn divide(a: Int, b: Int): Int = a / b
+
+fn attempt(): Int {
+	for i in 0..3 {
+		try {
+			if i == 1 { return i }
+		} finally {
+			println(\"finally \" + i)
+		}
+	}
+	return -1
+}
+
+fn main() {
+	try {
+		try {
+			divide(1, 0)
+		} catch (e: Int) {
+			println(\"not this one\")
+		} finally {
+			println(\"inner\")
+		}
+	} catch (e) {
+		println(\"caught: \" + e)
+	}
+	println(attempt())
+	throw \"gave up\"
+}");
+	assert_eq!(out, "inner\ncaught: Division by zero\nfinally 0\nfinally 1\n1\n");
+	assert_eq!(uncaught.unwrap().message, "Uncaught exception: gave up");
+}
+
+#[test]
+fn stack_traces_match_the_interpreter() {
+	let (_, uncaught) = run("fn countdown(n: Int): Int = if n == 0 { 1 / n } else { countdown(n - 1) + 1 }
+
+fn main() {
+	countdown(3)
+}");
+	assert_eq!(uncaught.unwrap().diagnostic(&["main.gl".to_string()]).notes, ["at countdown (main.gl:1:41)", "at countdown (main.gl:1:56), 3 times", "at main (main.gl:4:2)"]);
+}
+
+#[test]
+fn calls_in_tail_position_recurse_a_million_deep() {
+	let program = checked("fn count(n: Int, total: Int): Int = if (n == 0) total else count(n - 1, total + 1)
+
+fn isEven(n: Int): Bool {
+	if n == 0 { return true }
+	return isOdd(n - 1)
+}
+
+fn isOdd(n: Int): Bool = if n == 0 { false } else { isEven(n - 1) }
+
+class Counter {
+	fn down(n: Int): Int {
+		while (true) {
+			if n == 0 { return 0 }
+			return this.down(n - 1)
+		}
+		return -1
+	}
+}
+
+fn main() {
+	println(count(1000000, 0))
+	println(isEven(1000001))
+	println(Counter().down(1000000))
+	mut val spin: (Int) -> Int = (n: Int) => n
+	spin = (n: Int) => if (n == 0) 42 else spin(n - 1)
+	println(spin(1000000))
+}");
+	let module = compile(&program, None);
+	assert!(disassemble(&module).contains("Tail\n"), "{}", disassemble(&module));
+	let mut out = Vec::new();
+	vm::run(&module, &Runtime::new(), &[], Settings::default(), &mut out).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), "1000000\nfalse\n0\n42\n");
+
+	// What is left to do after the call, or a `try` it is in, keeps the frame
+	let uncaught = |source: &str| {
+		let mut out = Vec::new();
+		vm::run(&compile(&checked(source), None), &Runtime::new(), &[], Settings::default(), &mut out).unwrap_err().message
+	};
+	assert!(uncaught("fn down(n: Int): Int = if (n == 0) 0 else down(n - 1) + 1\n\nfn main() {\n\tprintln(down(1000000))\n}").starts_with("Stack overflow"));
+	let source = "fn down(n: Int): Int {\n\ttry {\n\t\treturn if (n == 0) 0 else down(n - 1)\n\t} finally {}\n}\n\nfn main() {\n\tprintln(down(1000000))\n}";
+	assert!(uncaught(source).starts_with("Stack overflow"));
+}
+
+#[test]
+fn deferred_statements_run_as_their_block_is_left() {
+	let (out, uncaught) = run("class Resource {
+	val name: String
+	constructor(name: String) {
+		this.name = name
+		println(\"open \" + name)
+	}
+	fn close() {
+		println(\"close \" + name)
+	}
+}
+
+fn use(fail: Bool): Int {
+	val a = Resource(\"a\")
+	defer a.close()
+	val b = Resource(\"b\")
+	defer {
+		println(\"closing\")
+		b.close()
+	}
+	if fail {
+		throw \"failed\"
+	}
+	return 1
+}
+
+fn main() {
+	println(use(false))
+	try {
+		use(true)
+	} catch (e) {
+		println(\"caught \" + e)
+	}
+	for i in 0..3 {
+		defer println(\"end of \" + i)
+		if i == 1 {
+			continue
+		}
+		println(\"body \" + i)
+	}
+	val n = if true {
+		defer println(\"after the value\")
+		41 + 1
+	} else {
+		0
+	}
+	println(n)
+	defer println(\"main done\")
+	use(true)
+}");
+	assert_eq!(out, "\
+open a
+open b
+closing
+close b
+close a
+1
+open a
+open b
+closing
+close b
+close a
+caught failed
+body 0
+end of 0
+end of 1
+body 2
+end of 2
+after the value
+42
+open a
+open b
+closing
+close b
+close a
+main done
+");
+	assert_eq!(uncaught.unwrap().message, "Uncaught exception: failed");
+}
+
+#[test]
+fn generators_yield_the_elements_of_their_iterators() {
+	let (out, uncaught) = run("fn fibonacci(count: Int): Iterator<Int> {
+	mut a = 0
+	mut b = 1
+	for _ in 0..count {
+		yield a
+		val next = a + b
+		a = b
+		b = next
+	}
+}
+
+fn upTo(limit: Int): Iterator<Int> {
+	mut n = 0
+	while true {
+		if n > limit {
+			return
+		}
+		yield n
+		n += 1
+	}
+}
+
+fn failing(): Iterator<String> {
+	yield \"fine\"
+	throw \"broken\"
+}
+
+class Bag {
+	val items: String[] = [\"x\", \"y\"]
+	fn each(): Iterator<String> {
+		for item in items {
+			yield item + \"!\"
+		}
+	}
+}
+
+fn main() {
+	for n in fibonacci(8) {
+		print(n + \" \")
+	}
+	println(\"\")
+	println(upTo(3).map((n) => n * 10).toArray())
+	println(fibonacci(0).toArray())
+	println(Bag().each().toArray())
+	val counted = upTo(5)
+	println(counted.next() + counted.next())
+	try {
+		println(failing().toArray())
+	} catch (e) {
+		println(\"caught \" + e)
+	}
+}");
+	assert_eq!(out, "0 1 1 2 3 5 8 13 \n[0, 10, 20, 30]\n[]\n[x!, y!]\n1\ncaught broken\n");
+	assert!(uncaught.is_none());
+}
+
+#[test]
+fn static_members_belong_to_the_class() {
+	let (out, uncaught) = run("class Counter {
+	static mut created = 0
+	static val start: Int = 10
+	static val first = start + 1
+	private static val prefix = \"#\"
+	val id: Int
+
+	constructor() {
+		created += 1
+		id = start + created
+	}
+
+	static fn make(): Counter = Counter()
+	static fn make(count: Int): Counter[] {
+		val made: Counter[] = []
+		for _ in 0..count {
+			made.push(make())
+		}
+		return made
+	}
+
+	fn label(): String = prefix + id
+}
+
+class Special : Counter {
+	fn twice(): Int = created * 2
+}
+
+fn main() {
+	println(Counter.first)
+	println(Counter.make().label())
+	println(Counter.make(2).size)
+	Counter.created += 10
+	println(Special.created)
+	println(Special().twice())
+	val make = Counter.make
+	println(make().id)
+}");
+	assert_eq!(out, "11\n#11\n2\n13\n28\n25\n");
+	assert!(uncaught.is_none());
+}
+
+#[test]
+fn enums_have_methods_and_know_their_variants() {
+	let (out, uncaught) = run("enum Planet {
+	Mercury(3.7),
+	Earth(9.8)
+
+	val gravity: Float
+	constructor(gravity: Float) {
+		this.gravity = gravity
+	}
+
+	fn weigh(mass: Float): Float = mass * gravity
+}
+
+fn main() {
+	for planet in Planet.values() {
+		println(planet.name + \" \" + planet.weigh(10.0))
+	}
+	println(Planet.valueOf(\"Earth\") == Planet.Earth)
+	println(Planet.valueOf(\"Pluto\"))
+	val names = Planet.values
+	println(names().size)
+}");
+	assert_eq!(out, "Mercury 37.0\nEarth 98.0\ntrue\nnull\n2\n");
+	assert!(uncaught.is_none());
+}
+
+#[test]
+fn compiled_programs_save_and_load_unchanged() {
+	let program = checked("class Greeter {
+	val greeting: String = \"hello\"
+	fn greet(name: String?): String = greeting + \", \" + (name ?? \"world\")
+}
+
+fn main() {
+	val scale = 1.5
+	try {
+		println(Greeter().greet(null) + \" \" + scale * 2)
+	} catch (e: Int | String) {
+		println(e)
+	}
+}");
+	let module = compile(&program, None);
+	let bytes = file::save(&module);
+	assert!(bytes.starts_with(MAGIC));
+	let loaded = file::load(&bytes).unwrap();
+	assert_eq!(loaded, module);
+	let mut out = Vec::new();
+	vm::run(&loaded, &Runtime::new(), &[], Settings::default(), &mut out).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), "hello, world 3.0\n");
+
+	let mut newer = bytes.clone();
+	newer[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(VERSION + 1).to_le_bytes());
+	let expected = format!("compiled for bytecode version {}, but this dotfun runs version {}; build it again", VERSION + 1, VERSION);
+	assert_eq!(file::load(&newer).unwrap_err(), expected);
+	let mut later = bytes.clone();
+	later[MAGIC.len() + 4..MAGIC.len() + 8].copy_from_slice(&(stdlib::VERSION + 1).to_le_bytes());
+	let expected = format!("needs version {} of the standard library, but this dotfun has version {}", stdlib::VERSION + 1, stdlib::VERSION);
+	assert_eq!(file::load(&later).unwrap_err(), expected);
+	assert_eq!(file::load(b"fn main() {}").unwrap_err(), "not a compiled dotfun program");
+	assert_eq!(file::load(&bytes[..bytes.len() - 1]).unwrap_err(), "corrupt: it ends too soon");
+}
+
+#[test]
+fn corrupt_files_are_refused_before_they_run() {
+	let module = compile(&checked("class Shape {
+	val sides: Int = 4
+}
+
+fn main() {
+	try {
+		println(Shape().sides)
+	} catch (e) {
+		println(e)
+	}
+}"), None);
+	let main = module.main.unwrap() as usize;
+	let with_main = |code: &[u8]| {
+		let mut corrupt = module.clone();
+		corrupt.functions[main].code = Some(code.to_vec());
+		file::load(&file::save(&corrupt)).unwrap_err()
+	};
+	let (pop, null, jump, constant, ret) = (Op::Pop as u8, Op::Null as u8, Op::Jump as u8, Op::Constant as u8, Op::Return as u8);
+	assert_eq!(with_main(&[pop, null, ret]), "corrupt: the instruction at 0 in 'main' pops more than was pushed");
+	assert_eq!(with_main(&[null]), "corrupt: the instruction at 0 in 'main' runs off the end of the code of 'main'");
+	assert_eq!(with_main(&[jump, 2, 0, 0, 0]), "corrupt: the instruction at 0 in 'main' goes to 2, where no instruction of 'main' starts");
+	assert_eq!(with_main(&[constant, 0x0f, 0x27, ret]), "corrupt: the instruction at 0 in 'main' refers to constant 9999, which is not there");
+	let mut cycle = module.clone();
+	cycle.classes[0].superclass = Some(0);
+	assert_eq!(file::load(&file::save(&cycle)).unwrap_err(), "corrupt: class 'Shape' is its own superclass");
+
+	// Whatever a byte is changed to, the file is refused or runs
+	let bytes = file::save(&module);
+	let mut seed = 1u64;
+	let mut runtime = Runtime::new();
+	runtime.limits = RuntimeLimits { max_instructions: Some(10_000), ..RuntimeLimits::default() };
+	for _ in 0..2000 {
+		seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+		let mut changed = bytes.clone();
+		let at = MAGIC.len() + 8 + (seed >> 33) as usize % (bytes.len() - MAGIC.len() - 8);
+		changed[at] ^= 1 << (seed >> 60 & 7);
+		if let Ok(module) = file::load(&changed) {
+			let _ = vm::run(&module, &runtime, &[], Settings::default(), &mut Vec::new());
+		}
+	}
+}
+
+#[test]
+fn cycles_are_collected_and_what_is_reachable_kept() {
+	let module = compile(&checked("class Node {
+	mut next: Node? = null
+	mut label = \"\"
+}
+
+fn main() {
+	val kept: Node?[] = [null, null, null]
+	for i in 0..1000 {
+		val a = Node()
+		val b = Node()
+		a.next = b
+		b.next = a
+		b.label = \"node \" + i
+		val recurse = () => a
+		if i % 400 == 0 {
+			kept[i / 400] = a
+		}
+	}
+	for node in kept {
+		println(node!!.next!!.label)
+	}
+}"), None);
+	let mut out = Vec::new();
+	let mut vm = Vm::new(&module, &Runtime::new(), Settings { threshold: 100, stress: false }, &mut out);
+	vm.init().unwrap();
+	vm.call(module.main.unwrap(), Vec::new()).unwrap();
+	let stats = gc::stats();
+	drop(vm);
+	assert_eq!(String::from_utf8(out).unwrap(), "node 0\nnode 400\nnode 800\n");
+	assert!(stats.collections > 0);
+	assert!(stats.freed > 1500, "{:?}", stats);
+	assert!(stats.live < 300, "{:?}", stats);
+}
+
+#[test]
+fn async_calls_take_turns_on_the_event_loop() {
+	let path = std::env::temp_dir().join(format!("dotfun-async-{}.txt", std::process::id()));
+	std::fs::write(&path, "from a file").unwrap();
+	let source = "async fn tick(name: String, ms: Int, times: Int): Int {
+	for i in 0..times {
+		await sleep(ms)
+		println(name + \" \" + i)
+	}
+	return times * ms
+}
+
+async fn fail(): Int {
+	await sleep(1)
+	throw \"broken\"
+}
+
+async fn main() {
+	val a = tick(\"a\", 20, 3)
+	val b = tick(\"b\", 30, 2)
+	println(\"started\")
+	println(await a + await b)
+	try {
+		await fail()
+	} catch (e) {
+		println(\"caught \" + e)
+	}
+	println(await readFile(\"PATH\"))
+	val _ = tick(\"c\", 1, 1)
+	println(\"main done\")
+}"
+	.replace("PATH", &path.display().to_string());
+	let program = checked(&source);
+	let module = compile(&program, None);
+	assert!(disassemble(&module).contains("async fn main"));
+	let mut out = Vec::new();
+	vm::run(&module, &Runtime::new(), &[], Settings { stress: true, ..Settings::default() }, &mut out).unwrap();
+	let tail = "120\ncaught broken\nfrom a file\n";
+	assert_eq!(String::from_utf8(out).unwrap(), format!("started\na 0\nb 0\na 1\nb 1\na 2\n{}main done\nc 0\n", tail));
+
+	// The interpreter has no event loop, and runs each call to the end
+	let mut out = Vec::new();
+	interpreter::run(&program, &Runtime::new(), None, &[], &mut out).unwrap();
+	std::fs::remove_file(&path).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), format!("a 0\na 1\na 2\nb 0\nb 1\nstarted\n{}c 0\nmain done\n", tail));
+}
+
+#[test]
+fn tasks_that_cannot_finish_stop_the_program() {
+	let run = |source: &str| {
+		let mut out = Vec::new();
+		vm::run(&compile(&checked(source), None), &Runtime::new(), &[], Settings::default(), &mut out).unwrap_err()
+	};
+	let uncaught = run("async fn work(n: Int): Int {
+	await sleep(n)
+	if n > 5 { throw \"too slow: \" + n }
+	return n
+}
+
+async fn main() {
+	await work(1)
+	await work(10)
+}");
+	assert_eq!(uncaught.message, "Uncaught exception: too slow: 10");
+	assert_eq!(uncaught.diagnostic(&["main.gl".to_string()]).notes, ["at work (main.gl:3:13)"]);
+
+	let uncaught = run("mut pending: Any = null
+
+async fn stuck() {
+	await pending
+}
+
+async fn main() {
+	pending = stuck()
+	await pending
+}");
+	assert_eq!(uncaught.message, "Every task left is waiting for another, so none of them can finish");
+	assert_eq!(uncaught.span.line, 7);
+}
+
+#[test]
+fn async_calls_outside_async_code_run_to_their_end() {
+	let path = std::env::temp_dir().join(format!("dotfun-unawaited-{}.txt", std::process::id()));
+	std::fs::write(&path, "four").unwrap();
+	let source = "async fn greeting(name: String): String {
+	await sleep(1)
+	return \"hello \" + name
+}
+
+fn shout(name: String): String {
+	val said = greeting(name)
+	return said.toUpperCase()
+}
+
+async fn later(): String {
+	await sleep(1)
+	return shout(\"later\")
+}
+
+fn main() {
+	println(readFile(\"PATH\").length)
+	val a = greeting(\"a\")
+	println(a.length)
+	sleep(1)
+	println(shout(\"b\"))
+	println(later())
+	val words: Channel<String> = channel()
+	words.send(\"sent\")
+	println(words.receive()!!.length)
+}"
+	.replace("PATH", &path.display().to_string());
+	let (out, uncaught) = run(&source);
+	std::fs::remove_file(&path).unwrap();
+	assert_eq!(uncaught, None);
+	assert_eq!(out, "4\n7\nHELLO B\nHELLO LATER\n4\n");
+}
+
+#[test]
+fn spawned_tasks_pass_values_over_channels() {
+	let source = "async fn main() {
+	val words: Channel<String> = channel()
+	val counts: Channel<Int> = channel()
+	val counter = spawn {
+		mut count = 0
+		mut word = await words.receive()
+		while word != null {
+			count += word.length
+			counts.send(count)
+			word = await words.receive()
+		}
+		count
+	}
+	for word in [\"one\", \"three\"] {
+		words.send(word)
+		println(word + \" \" + await counts.receive())
+	}
+	words.close()
+	println(await counter)
+	println(await words.receive())
+}";
+	let program = checked(source);
+	let mut out = Vec::new();
+	vm::run(&compile(&program, None), &Runtime::new(), &[], Settings { stress: true, ..Settings::default() }, &mut out).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), "one 3\nthree 8\n8\nnull\n");
+
+	// The interpreter runs the spawned block to the end first, before anything is sent
+	let mut out = Vec::new();
+	let uncaught = interpreter::run(&program, &Runtime::new(), None, &[], &mut out).unwrap_err();
+	assert!(uncaught.message.ends_with("as the interpreter runs each task to the end"), "{}", uncaught.message);
+}
+
+#[test]
+fn generators_run_as_far_as_their_iterator_is_gone_through() {
+	let source = "fn naturals(): Iterator<Int> {
+	mut n = 0
+	while true {
+		println(\"yielding \" + n)
+		yield n
+		n += 1
+	}
+}
+
+fn evens(): Iterator<Int> {
+	for n in naturals() {
+		if n % 2 == 0 {
+			yield n
+		}
+	}
+}
+
+fn main() {
+	for n in evens() {
+		println(\"got \" + n)
+		if n >= 2 {
+			break
+		}
+	}
+}";
+	let program = checked(source);
+	let mut out = Vec::new();
+	vm::run(&compile(&program, None), &Runtime::new(), &[], Settings { stress: true, ..Settings::default() }, &mut out).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), "yielding 0\ngot 0\nyielding 1\nyielding 2\ngot 2\n");
+}
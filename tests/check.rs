@@ -0,0 +1,1389 @@
+// What the checker accepts and rejects, rule by rule: programs that keep to
+// a rule check cleanly, and ones that break it get the rule's code at the
+// place that breaks it.
+
+use glee::ast::expr::{BinaryOp, Literal};
+use glee::diagnostics::Diagnostic;
+use glee::lexer::lexer::Lexer;
+use glee::parser::parser::Parser;
+use glee::sema::check::check;
+use glee::sema::hir::{Coercion, Expr, ExprKind, Program, Stmt, StmtKind};
+use glee::sema::modules::SourceFile;
+use glee::sema::types::Type;
+
+fn checked(sources: &[(&str, &str)]) -> Result<Program, Vec<Diagnostic>> {
+	let files: Vec<SourceFile> = sources
+		.iter()
+		.map(|(path, text)| {
+			let tokens = Lexer::new(text.to_string()).lex().unwrap().clone();
+			SourceFile { path: path.to_string(), program: Parser::new(tokens).parse().unwrap(), package: None }
+		})
+		.collect();
+	check(&files)
+}
+
+// Each diagnostic as its code, and the file, line and column it points at.
+fn places(diagnostics: &[Diagnostic]) -> Vec<(&'static str, usize, i64, i64)> {
+	diagnostics
+		.iter()
+		.map(|diagnostic| (diagnostic.code.unwrap_or("-"), diagnostic.file, diagnostic.primary_span.line, diagnostic.primary_span.column))
+		.collect()
+}
+
+// A program of one file, which has to check without errors or warnings.
+fn accepted(source: &str) -> Program {
+	match checked(&[("main.gl", source)]) {
+		Ok(program) => {
+			assert!(program.warnings.is_empty(), "{:?}", places(&program.warnings));
+			program
+		}
+		Err(errors) => panic!("{:?}", errors.iter().map(|error| format!("{}: {}", error.primary_span.line, error.message)).collect::<Vec<_>>())
+	}
+}
+
+// What checking the files found: the errors, or without any, the warnings.
+fn found_in(sources: &[(&str, &str)]) -> Vec<(&'static str, usize, i64, i64)> {
+	match checked(sources) {
+		Ok(program) => places(&program.warnings),
+		Err(errors) => places(&errors)
+	}
+}
+
+// The same of a program of one file, each as its code, line and column.
+fn found(source: &str) -> Vec<(&'static str, i64, i64)> {
+	found_in(&[("main.gl", source)]).into_iter().map(|(code, _, line, column)| (code, line, column)).collect()
+}
+
+#[test]
+fn expressions_and_statements_are_checked_against_the_types_they_need() {
+	accepted(
+		"class Box<T> {
+	val item: T
+	constructor(item: T) {
+		this.item = item
+	}
+	fn get(): T = item
+}
+
+fn twice(n: Int): Int = n * 2
+
+fn main() {
+	val box = Box(\"a\")
+	val s: String = box.get()
+	val n: Int = twice(2) + 1
+	if n > 2 && s == \"a\" {
+		println(s)
+	}
+}"
+	);
+	let found = found(
+		"fn twice(n: Int): Int = n * 2
+
+class Box<T> {
+	val item: T
+	constructor(item: T) {
+		this.item = item
+	}
+	fn get(): T = item
+}
+
+fn main() {
+	val n: Int = Box(\"a\").get()
+	twice(\"x\")
+	twice(1, 2)
+	if 1 {
+	}
+	val m = -true
+	val k = 3(4)
+}"
+	);
+	assert_eq!(found, [("E0400", 12, 15), ("E0400", 13, 8), ("E0403", 14, 2), ("E0400", 15, 5), ("E0401", 17, 10), ("E0402", 18, 10)]);
+}
+
+#[test]
+fn declarations_take_the_type_of_what_they_are_set_to() {
+	accepted(
+		"fn main() {
+	val lookup = [\"a\": 1]
+	val n: Int = lookup[\"a\"]!! + 1
+	val xs = [1, 2].map((x) => x * 2).toArray()
+	val first: Int = xs[0]
+	val g: (Int) -> String = (x) => \"n\" + x
+	val text: String = g(n)
+	val empty: Map<String, Int> = [:]
+	mut count = 0
+	count = count + first + empty.size
+	println(text + count)
+}"
+	);
+	// What was inferred is held to, through map literals, lambdas and calls
+	let found = found(
+		"fn main() {
+	val lookup = [\"a\": 1]
+	val s: String = lookup[\"a\"]!!
+	val xs = [1, 2].map((x) => x * 2).toArray()
+	val t: String = xs[0]
+	val g: (Int) -> Int = (x) => x + 1
+	val u: String = g(1)
+}"
+	);
+	assert_eq!(found, [("E0400", 3, 18), ("E0400", 5, 18), ("E0400", 7, 18)]);
+}
+
+#[test]
+fn nullable_values_are_used_only_where_they_are_known_not_to_be_null() {
+	accepted(
+		"fn size(x: String?): Int {
+	if x != null {
+		return x.length
+	}
+	return 0
+}
+
+fn first(x: String?): Int {
+	if x == null {
+		return 0
+	}
+	return x.length
+}
+
+fn second(x: String?): Int {
+	val y = x ?? return 0
+	return y.length + (x?.length ?? 0)
+}
+
+fn third(x: String?): Int = x!!.length
+
+fn main() {
+	mut name: String? = null
+	name = \"a\"
+	println(name.length + size(name) + first(null) + second(\"b\") + third(\"c\"))
+}"
+	);
+	// What a check told stops holding once the variable may have changed:
+	// after an assignment, at the top of a loop that assigns it, and for
+	// fields and `mut` globals, any time at all
+	let found = found(
+		"mut shared: String? = \"a\"
+
+class Named {
+	mut name: String? = null
+	fn size(): Int {
+		if name != null {
+			return name.length
+		}
+		return 0
+	}
+}
+
+fn main(x: String?) {
+	println(x.length)
+	mut s: String? = x
+	if s != null {
+		s = null
+		println(s.length)
+	}
+	mut t: String? = x
+	if t != null {
+		while true {
+			println(t.length)
+			t = null
+		}
+	}
+	if shared != null {
+		println(shared.length)
+	}
+}"
+	);
+	assert_eq!(found, [("E0409", 7, 11), ("E0409", 14, 10), ("E0409", 18, 11), ("E0409", 23, 12), ("E0409", 28, 11)]);
+}
+
+#[test]
+fn variables_are_assigned_before_they_are_read_on_every_path() {
+	accepted(
+		"fn pick(b: Bool): Int {
+	val x: Int
+	if b {
+		x = 1
+	} else {
+		x = 2
+	}
+	return x
+}
+
+fn looped(): Int {
+	val x: Int
+	loop {
+		x = 3
+		break
+	}
+	return x
+}
+
+fn counted(n: Int): Int {
+	mut total: Int
+	total = 0
+	for i in 0..n {
+		total = total + i
+	}
+	return total
+}
+
+fn main() {
+	println(pick(true) + looped() + counted(3))
+}"
+	);
+	// A loop body that assigns may run no times, or run again after it has
+	let found = found(
+		"fn main(b: Bool, n: Int) {
+	val x: Int
+	if b {
+		x = 1
+	}
+	println(x)
+	mut y: Int
+	while n > 0 {
+		y = 1
+	}
+	println(y)
+	mut z: Int
+	for i in 0..n {
+		z = i
+	}
+	println(z)
+	val w: Int
+	while b {
+		println(w)
+		w = 2
+		break
+	}
+	val once = 1
+	once = 2
+	val twice: Int
+	twice = 1
+	twice = 2
+	val inLoop: Int
+	while b {
+		inLoop = 1
+	}
+}"
+	);
+	assert_eq!(
+		found,
+		[
+			("E0507", 6, 10),
+			("E0507", 11, 10),
+			("E0507", 16, 10),
+			("E0507", 19, 11),
+			("E0508", 24, 2),
+			("E0508", 27, 2),
+			("E0508", 30, 3)
+		]
+	);
+}
+
+#[test]
+fn only_mut_variables_and_fields_are_changed() {
+	accepted(
+		"class Counter {
+	mut count = 0
+	val step = 1
+	fn bump() {
+		count += step
+		count++
+		this.count--
+	}
+}
+
+fn main() {
+	mut n = 1
+	n += 2
+	n++
+	val counter = Counter()
+	counter.bump()
+	counter.count = n
+	println(counter.count)
+}"
+	);
+	let source = "class Counter {
+	mut count = 0
+	val step = 1
+	fn bump() {
+		step += 1
+		this.step++
+	}
+}
+
+fn main(xs: Int) {
+	val n = 1
+	n += 2
+	n++
+	--n
+	val counter = Counter()
+	counter.step = 3
+	xs = 1
+}";
+	assert_eq!(
+		found(source),
+		[
+			("E0508", 5, 3),
+			("E0508", 6, 3),
+			("E0508", 16, 2),
+			("E0508", 12, 2),
+			("E0508", 13, 2),
+			("E0508", 14, 4),
+			("E0508", 17, 2)
+		]
+	);
+	// A local `val` is offered the fix of declaring it `mut`
+	let errors = checked(&[("main.gl", source)]).err().unwrap();
+	let fix = &errors.iter().find(|error| error.primary_span.line == 12).unwrap().suggestions[0];
+	assert_eq!(fix.edits.len(), 1);
+	assert_eq!((fix.edits[0].span.line, fix.edits[0].span.column, fix.edits[0].replacement.as_str()), (11, 2, "mut"));
+	assert_eq!(&source[fix.edits[0].span.start..fix.edits[0].span.end], "val");
+}
+
+#[test]
+fn overrides_match_what_they_override() {
+	accepted(
+		"abstract class Shape {
+	abstract fn area(): Int
+	fn describe(): String = \"area \" + area()
+}
+
+class Square : Shape {
+	val side: Int
+	constructor(side: Int) {
+		this.side = side
+	}
+	override fn area(): Int = side * side
+}
+
+class Cube : Square {
+	constructor(side: Int) {
+		super(side)
+	}
+	override fn area(): Int = super.area() * 6
+	override fn describe(): String = \"cube \" + super.describe()
+}
+
+fn main() {
+	val shapes: Shape[] = [Square(2), Cube(1)]
+	println(shapes[0].describe() + shapes[1].describe())
+}"
+	);
+	let source = "abstract class Shape {
+	abstract fn area(): Int
+	fn describe(): String = \"shape\"
+}
+
+class Square : Shape {
+	override fn area(): String = \"4\"
+	fn describe(): String = \"square\"
+	override fn aera(): Int = 4
+}
+
+class Circle : Shape {
+}
+
+class Dot : Shape {
+	override fn area(x: Int): Int = x
+}";
+	assert_eq!(found(source), [("E0305", 7, 14), ("E0304", 8, 5), ("E0304", 9, 14), ("E0307", 12, 7), ("E0305", 16, 14)]);
+	// An `override` of nothing is offered the name it comes closest to
+	let errors = checked(&[("main.gl", source)]).err().unwrap();
+	assert_eq!(errors[2].suggestions[0].edits[0].replacement, "area");
+}
+
+#[test]
+fn inheritance_errors_are_in_the_file_of_what_they_are_about() {
+	let main = "import shapes.Shape
+
+class Square : Shape {
+	fn area(): Int = 4
+}
+
+override fn top(): Int = 1";
+	let shapes = "package shapes
+
+class Shape {
+	fn area(): Int = 1
+}
+
+class Circle : Shape {
+	fn area(): Int = 3
+}
+
+override fn bottom(): Int = 2";
+	let found = found_in(&[("main.gl", main), ("shapes.gl", shapes)]);
+	assert_eq!(found, [("E0304", 0, 7, 13), ("E0304", 1, 11, 13), ("E0304", 0, 4, 5), ("E0304", 1, 8, 5)]);
+}
+
+#[test]
+fn classes_implement_everything_their_interfaces_ask_for() {
+	accepted(
+		"interface Named {
+	val name: String
+	fn greet(): String = \"hi \" + name
+}
+
+interface Loud : Named {
+	override fn greet(): String = \"HI \" + name
+}
+
+interface Sized {
+	fn size(): Int
+	fn greet(): String = \"sized\"
+}
+
+class Dog : Named, Loud {
+	val name: String = \"rex\"
+}
+
+class Cat : Named, Sized {
+	val name: String = \"tom\"
+	override fn size(): Int = 2
+	override fn greet(): String = \"meow\"
+}
+
+fn main() {
+	val named: Named = Dog()
+	println(named.greet() + Cat().greet() + Cat().size())
+}"
+	);
+	// Two defaults for one method conflict unless the class picks one, or
+	// one interface already overrides the other as `Loud` does above
+	let source = "interface Named {
+	val name: String
+	fn greet(): String = \"hi\"
+}
+
+interface Sized {
+	fn size(): Int
+	fn grow(by: Int): Int
+	fn greet(): String = \"sized\"
+}
+
+class Empty : Sized {
+}
+
+class Both : Named, Sized {
+	val name: Int = 3
+	override fn size(): Int = 1
+	override fn grow(by: Int): Int = by
+}";
+	assert_eq!(found(source), [("E0307", 12, 7), ("E0307", 16, 6), ("E0308", 15, 7)]);
+	let errors = checked(&[("main.gl", source)]).err().unwrap();
+	assert!(errors[0].message.ends_with("implement 'size' from 'Sized', 'grow' from 'Sized'"), "{}", errors[0].message);
+}
+
+#[test]
+fn switches_handle_every_value_once() {
+	accepted(
+		"enum Color {
+	Red,
+	Green,
+	Blue
+}
+
+sealed class Shape {
+}
+
+class Circle : Shape {
+}
+
+class Square : Shape {
+}
+
+fn name(color: Color): String {
+	switch color {
+		case Color.Red: return \"red\"
+		case Color.Green, Color.Blue: return \"other\"
+	}
+}
+
+fn kind(shape: Shape): String {
+	switch shape {
+		case Circle: return \"circle\"
+		case Square: return \"square\"
+	}
+}
+
+fn truth(b: Bool?): String {
+	switch b {
+		case true: return \"yes\"
+		case false: return \"no\"
+		case null: return \"unknown\"
+	}
+}
+
+fn main() {
+	println(name(Color.Red) + kind(Circle()) + truth(null))
+}"
+	);
+	let source = "enum Color {
+	Red,
+	Green,
+	Blue
+}
+
+sealed class Shape {
+}
+
+class Circle : Shape {
+}
+
+class Square : Shape {
+}
+
+fn main(color: Color, shape: Shape, b: Bool, n: Int) {
+	switch color {
+		case Color.Red: println(\"red\")
+	}
+	switch shape {
+		case Circle: println(\"circle\")
+	}
+	switch b {
+		case true: println(\"yes\")
+		case false: println(\"no\")
+		default: println(\"never\")
+	}
+	switch n {
+		case 1: println(\"one\")
+		case 2, 1: println(\"again\")
+	}
+}";
+	assert_eq!(found(source), [("E0504", 17, 2), ("E0504", 20, 2), ("E0504", 26, 12), ("E0504", 30, 11)]);
+	let errors = checked(&[("main.gl", source)]).err().unwrap();
+	assert!(errors[0].message.contains("does not handle 'Green', 'Blue'"), "{}", errors[0].message);
+	assert!(errors[1].message.contains("does not handle 'Square'"), "{}", errors[1].message);
+}
+
+#[test]
+fn functions_return_what_they_declare_on_every_path() {
+	accepted(
+		"fn sign(n: Int): Int {
+	if n > 0 {
+		return 1
+	} else if n < 0 {
+		return -1
+	} else {
+		return 0
+	}
+}
+
+fn fail(n: Int): Int {
+	if n > 0 {
+		return n
+	}
+	throw \"negative\"
+}
+
+fn spin(): Int {
+	loop {
+		return 3
+	}
+}
+
+fn say(): Void {
+	println(\"hi\")
+	return
+}
+
+fn main() {
+	say()
+	println(sign(2) + fail(1) + spin())
+}"
+	);
+	let found = found(
+		"fn sign(n: Int): Int {
+	if n > 0 {
+		return 1
+	}
+}
+
+fn late(): Int {
+	return 1
+	println(\"never\")
+}
+
+fn say(): Void {
+	return 3
+}
+
+fn none(): Int {
+	return
+}
+
+fn main(n: Int) {
+	while n > 0 {
+		break
+		println(\"never\")
+	}
+	throw \"always\"
+	println(\"never\")
+}"
+	);
+	assert_eq!(found, [("E0500", 13, 9), ("E0500", 17, 2), ("E0505", 1, 4), ("E0506", 9, 2), ("E0506", 23, 3), ("E0506", 26, 2)]);
+}
+
+#[test]
+fn break_and_continue_leave_a_loop_around_them() {
+	accepted(
+		"fn main() {
+	mut found = 0
+	outer: for i in 0..5 {
+		inner: for j in 0..5 {
+			if j == i {
+				continue outer
+			}
+			if i + j > 6 {
+				break outer
+			}
+			if j > 3 {
+				break inner
+			}
+			found += j
+		}
+	}
+	mut n = 0
+	while n < 10 {
+		n++
+		if n == 2 {
+			continue
+		}
+		if n == 5 {
+			break
+		}
+	}
+	println(found + n)
+}"
+	);
+	// A lambda is not inside the loops around where it is written
+	let source = "fn main(n: Int) {
+	break
+	if n > 0 {
+		continue
+	}
+	outer: while n > 0 {
+		break outr
+		outer: for i in 0..n {
+			println(i)
+		}
+		val f = () => {
+			break
+		}
+		f()
+	}
+}";
+	assert_eq!(found(source), [("E0501", 2, 2), ("E0501", 4, 3), ("E0501", 7, 3), ("E0501", 8, 3), ("E0501", 12, 4)]);
+	let errors = checked(&[("main.gl", source)]).err().unwrap();
+	assert_eq!(errors[2].notes, ["did you mean 'outer'?"]);
+}
+
+#[test]
+fn type_arguments_are_inferred_at_calls_and_meet_their_bounds() {
+	// From the arguments, from the declared result, and through a lambda
+	accepted(
+		"interface Sized {
+	fn size(): Int
+}
+
+class Bag : Sized {
+	override fn size(): Int = 3
+}
+
+fn first<T>(xs: T[]): T = xs[0]
+
+fn empty<T>(): T[] = []
+
+fn biggest<T : Sized>(a: T, b: T): T {
+	if a.size() > b.size() {
+		return a
+	}
+	return b
+}
+
+fn apply<A, B>(x: A, f: (A) -> B): B = f(x)
+
+fn main() {
+	val n: Int = first([1, 2])
+	val none: String[] = empty()
+	val bag: Bag = biggest(Bag(), Bag())
+	val text: String = apply(2, (x) => \"n\" + (x + 1))
+	println(text + n + none.size + bag.size())
+}"
+	);
+	let found = found(
+		"interface Sized {
+	fn size(): Int
+}
+
+fn empty<T>(): T[] = []
+
+fn same<T>(a: T, b: T): T = a
+
+fn biggest<T : Sized>(a: T, b: T): T = a
+
+fn main() {
+	val none = empty()
+	val mixed: Int = same(1, \"a\")
+	val big = biggest(1, 2)
+}"
+	);
+	assert_eq!(found, [("E0405", 12, 13), ("E0405", 13, 19), ("E0406", 14, 12)]);
+}
+
+#[test]
+fn private_and_protected_members_are_used_only_where_they_are_visible() {
+	let shapes = "package shapes
+
+class Square {
+	private val side: Int
+	constructor(side: Int) {
+		this.side = side
+	}
+	fn area(): Int = side * helper()
+}
+
+private fn helper(): Int = 1";
+	let main = "import shapes.Square
+
+class Base {
+	private val secret = 1
+	protected val shared = 2
+	fn reveal(other: Base): Int = secret + other.secret
+}
+
+class Derived : Base {
+	fn peek(): Int = shared + 1
+}
+
+fn main() {
+	println(Square(2).area() + Derived().peek() + Base().reveal(Base()))
+}";
+	assert_eq!(found_in(&[("main.gl", main), ("shapes.gl", shapes)]), []);
+
+	let main = "import shapes.Square
+
+class Base {
+	private val secret = 1
+	protected val shared = 2
+	private fn hidden(): Int = 3
+}
+
+class Derived : Base {
+	fn peek(): Int = secret + hidden()
+}
+
+fn main() {
+	println(Square(2).side + Base().shared)
+}";
+	let sources = [("main.gl", main), ("shapes.gl", shapes)];
+	assert_eq!(found_in(&sources), [("E0208", 0, 10, 19), ("E0208", 0, 10, 28), ("E0208", 0, 14, 20), ("E0208", 0, 14, 34)]);
+	// Each names where what it cannot use is declared
+	let errors = checked(&sources).err().unwrap();
+	assert!(errors[0].message.ends_with("(declared at line 4 column 14)"), "{}", errors[0].message);
+	assert!(errors[2].message.ends_with("(declared at line 4 column 14 of shapes.gl)"), "{}", errors[2].message);
+
+	let main = "import shapes.helper
+
+fn main() {
+	println(helper())
+}";
+	assert_eq!(found_in(&[("main.gl", main), ("shapes.gl", shapes)]), [("E0208", 0, 1, 1)]);
+}
+
+#[test]
+fn constants_are_worked_out_while_checking() {
+	accepted(
+		"const LIMIT = 2 * 8
+const NAME = \"limit \" + LIMIT
+const ON = !false && LIMIT > 10
+
+fn main(n: Int) {
+	switch n {
+		case LIMIT: println(NAME)
+		case LIMIT + 1: println(ON)
+		default: println(n)
+	}
+}"
+	);
+	// Cases are compared by their values, however they are written
+	let found = found(
+		"const LIMIT = 2 * 8
+
+fn now(): Int = 3
+
+const LATER = now()
+
+fn main(n: Int) {
+	switch n {
+		case LIMIT: println(\"a\")
+		case 16: println(\"b\")
+		case 4 * 4 + 1, LIMIT + 1: println(\"c\")
+	}
+	println(1 / 0)
+	println(9223372036854775807 + 1)
+	LIMIT = 3
+}"
+	);
+	assert_eq!(found, [("E0310", 5, 15), ("E0504", 10, 8), ("E0504", 11, 19), ("E0412", 13, 10), ("E0412", 14, 10), ("E0508", 15, 2)]);
+}
+
+#[test]
+fn import_cycles_are_reported_along_the_whole_cycle() {
+	let main = "import a.first
+
+fn main() {
+	println(first())
+}";
+	let a = "package a
+
+import b.second
+
+fn first(): Int = second() + 1";
+	assert_eq!(found_in(&[("main.gl", main), ("a.gl", a), ("b.gl", "package b\n\nfn second(): Int = 2")]), []);
+
+	let sources = [
+		("main.gl", main),
+		("a.gl", a),
+		("b.gl", "package b\n\nimport c.third\n\nfn second(): Int = third()"),
+		("c.gl", "package c\n\nimport a.first\n\nfn third(): Int = 2")
+	];
+	assert_eq!(found_in(&sources), [("E0207", 1, 3, 1)]);
+	let errors = checked(&sources).err().unwrap();
+	let steps = "'a' imports 'b' at line 3 column 1 of a.gl, then 'b' imports 'c' at line 3 column 1 of b.gl, then 'c' imports 'a' at line 3 column 1 of c.gl";
+	assert!(errors[0].message.contains(steps), "{}", errors[0].message);
+}
+
+#[test]
+fn unused_code_is_warned_about_unless_it_says_it_may_be() {
+	let shapes = "package shapes
+
+fn area(side: Int): Int = side * side
+
+fn perimeter(side: Int): Int = side * 4";
+	// Names starting with `_` and `@Suppress` turn the warnings off
+	let main = "import shapes.area
+
+class Counter {
+	private mut count = 0
+	fn bump(): Int {
+		count += step()
+		return count
+	}
+	private fn step(): Int = 1
+}
+
+@Suppress(\"unused\")
+fn quiet(unusedParam: Int) {
+	val ignored = 1
+}
+
+fn main(_args: Int) {
+	val _skipped = 2
+	println(area(2) + Counter().bump())
+}";
+	assert_eq!(found_in(&[("main.gl", main), ("shapes.gl", shapes)]), []);
+
+	// A variable only ever assigned to is not used either
+	let main = "import shapes.area
+import shapes.perimeter
+
+class Counter {
+	private val count = 0
+	private fn step(): Int = 1
+}
+
+private fn helper() {
+}
+
+fn main(args: Int) {
+	val unused = 2
+	mut changed = 0
+	changed = 1
+	println(perimeter(2))
+	println(Counter())
+}";
+	assert_eq!(
+		found_in(&[("main.gl", main), ("shapes.gl", shapes)]),
+		[
+			("W0002", 0, 1, 1),
+			("W0001", 0, 13, 6),
+			("W0001", 0, 14, 6),
+			("W0001", 0, 9, 12),
+			("W0001", 0, 12, 9),
+			("W0001", 0, 6, 13),
+			("W0001", 0, 5, 14)
+		]
+	);
+}
+
+#[test]
+fn names_are_declared_once_apart_from_overloads() {
+	accepted(
+		"class Point {
+	val x: Int = 0
+	fn move(by: Int): Int = x + by
+	fn move(by: String): String = by
+}
+
+enum Color {
+	Red,
+	Green
+}
+
+fn show(n: Int): String = \"int \" + n
+
+fn show(s: String): String = \"string \" + s
+
+fn main() {
+	println(show(1) + show(\"a\") + Point().move(2) + Point().move(\"b\") + Color.Green)
+}"
+	);
+	// Overloads differ in their parameter types, not in names or results
+	let source = "class Point {
+	val x: Int = 0
+	val x: Int = 1
+	fn move(by: Int): Int = x + by
+	fn move(step: Int): Int = x
+}
+
+enum Color {
+	Red,
+	Red
+}
+
+fn show(n: Int): String = \"int\"
+
+fn show(m: Int): Int = 2
+
+fn twice(n: Int, n: Int): Int = n";
+	assert_eq!(found(source), [("E0209", 17, 18), ("E0209", 15, 4), ("E0209", 3, 6), ("E0209", 5, 5), ("E0209", 10, 2)]);
+	let errors = checked(&[("main.gl", source)]).err().unwrap();
+	assert_eq!(errors[1].message, "'show' is already declared with the same parameter types at line 13 column 4");
+	assert_eq!(errors[4].message, "'Red' is already declared at line 9 column 2");
+}
+
+// The value returned by the function named `name`, declared with an
+// expression body.
+fn returned<'a>(program: &'a Program, name: &str) -> &'a Expr {
+	let function = program.functions.iter().find(|function| function.name == name).unwrap();
+	match &function.body.as_ref().unwrap().stmts[..] {
+		[Stmt { kind: StmtKind::Return(Some(value)), .. }] => value,
+		stmts => panic!("{:?}", stmts)
+	}
+}
+
+#[test]
+fn checked_programs_spell_out_what_the_source_leaves_implicit() {
+	let program = accepted(
+		"const SIDE = 2
+
+class Square {
+	val side: Int = SIDE
+	fn area(): Float = side * 1.5
+	fn label(): String = \"side ${side}\"
+}
+
+fn main() {
+	println(Square().area())
+	println(Square().label())
+}"
+	);
+	// Members used without `this.`, with the `Int` widened to a `Float`
+	let area = returned(&program, "area");
+	assert_eq!(area.ty, Type::Float);
+	let ExprKind::Binary { op: BinaryOp::Multiply, left, .. } = &area.kind else {
+		panic!("{:?}", area);
+	};
+	let ExprKind::Coerce { value, coercion: Coercion::IntToFloat } = &left.kind else {
+		panic!("{:?}", left);
+	};
+	assert!(matches!(&value.kind, ExprKind::Member { object, name, .. } if name == "side" && matches!(object.kind, ExprKind::This)));
+	// Interpolation is concatenation with the value turned into a string
+	let label = returned(&program, "label");
+	let ExprKind::Binary { op: BinaryOp::Add, left, right } = &label.kind else {
+		panic!("{:?}", label);
+	};
+	assert!(matches!(&left.kind, ExprKind::Literal(Literal::String(text)) if text == "side "));
+	assert!(matches!(right.kind, ExprKind::Coerce { coercion: Coercion::ToString, .. }) && right.ty == Type::String);
+	// A const is its value wherever it is used
+	let field = &program.classes.iter().find(|class| class.name == "Square").unwrap().fields[0];
+	assert!(matches!(&field.init, Some(Expr { kind: ExprKind::Literal(Literal::Int(2)), ty: Type::Int, .. })), "{:?}", field.init);
+}
+
+#[test]
+fn await_is_used_in_async_functions_on_async_calls() {
+	accepted(
+		"async fn fetch(n: Int): Int = n * 2
+
+async fn total(): Int {
+	val a = await fetch(1)
+	val _ = fetch(2)
+	return a + await fetch(3)
+}
+
+async fn main() {
+	println(await total())
+}"
+	);
+	let misplaced = found(
+		"async fn fetch(n: Int): Int = n * 2
+
+fn plain(): Int = 1
+
+fn sync(): Int = await fetch(1)
+
+class Loader {
+	fn load(): Int = await fetch(2)
+}
+
+async fn main() {
+	val f = () => await fetch(4)
+	println(await plain() + f())
+}
+
+await fetch(5)"
+	);
+	assert_eq!(misplaced, [("E0503", 5, 18), ("E0503", 8, 19), ("E0503", 12, 16), ("E0503", 16, 1)]);
+	// Dropping what an async call returns, and awaiting what is not one
+	let dropped = found(
+		"async fn fetch(n: Int): Int = n * 2
+
+fn plain(): Int = 1
+
+async fn main() {
+	fetch(3)
+	println(await plain())
+}"
+	);
+	assert_eq!(dropped, [("W0003", 6, 2), ("W0004", 7, 10)]);
+}
+
+#[test]
+fn overloads_are_chosen_by_their_parameters_or_reported_as_ambiguous() {
+	// Each call's result type shows which overload it got: exact matches
+	// beat widening, narrower parameters beat wider ones and fixed counts
+	// beat variadics, for operators as for calls
+	accepted(
+		"class Animal {
+}
+
+class Dog : Animal {
+}
+
+fn pick(_n: Int): String = \"int\"
+
+fn pick(_x: Float): Int = 1
+
+fn wide(x: Float): Float = x
+
+fn wide(s: String): String = s
+
+fn meet(_a: Animal): Int = 1
+
+fn meet(_d: Dog): String = \"dog\"
+
+fn sum(..._xs: Int): Int = 0
+
+fn sum(_a: Int, _b: Int): String = \"two\"
+
+class Money {
+	val cents: Int
+	constructor(cents: Int) {
+		this.cents = cents
+	}
+	fn plus(other: Money): Money = Money(cents + other.cents)
+	fn plus(more: Int): Money = Money(cents + more)
+}
+
+fn main() {
+	val m: Money = Money(1) + Money(2) + 3
+	println(m.cents)
+	val a: String = pick(1)
+	val b: Int = pick(1.5)
+	val c: Float = wide(2)
+	val d: String = meet(Dog())
+	val e: Int = meet(Animal())
+	val f: String = sum(1, 2)
+	val g: Int = sum(1, 2, 3)
+	println(a + b + c + d + e + f + g)
+}"
+	);
+	let source = "interface Named {
+}
+
+interface Sized {
+}
+
+class Both : Named, Sized {
+}
+
+fn mix(a: Int, b: Float): Int = 1
+
+fn mix(a: Float, b: Int): Int = 2
+
+fn label(n: Named): String = \"named\"
+
+fn label(s: Sized): String = \"sized\"
+
+fn one(s: String): Int = 1
+
+fn one(b: Bool): Int = 2
+
+fn main() {
+	println(mix(1, 2))
+	println(label(Both()))
+	println(one(3))
+}";
+	assert_eq!(found(source), [("E0404", 23, 10), ("E0404", 24, 10), ("E0404", 25, 10)]);
+	// Each lists the candidates it could not choose between, or that did not fit
+	let errors = checked(&[("main.gl", source)]).err().unwrap();
+	assert_eq!(
+		errors[0].message,
+		"Call to 'mix' is ambiguous between 'mix(Int, Float)' at line 10 column 4 and 'mix(Float, Int)' at line 12 column 4"
+	);
+	assert_eq!(
+		errors[1].message,
+		"Call to 'label' is ambiguous between 'label(Named)' at line 14 column 4 and 'label(Sized)' at line 16 column 4"
+	);
+	assert!(errors[2].message.starts_with("No overload of 'one' accepts (Int); the candidates are 'one(String)'"), "{}", errors[2].message);
+}
+
+#[test]
+fn constructors_assign_every_field_before_this_is_used() {
+	accepted(
+		"class Base {
+	val id: Int
+	constructor(id: Int) {
+		this.id = id
+	}
+}
+
+class Point : Base {
+	val x: Int
+	val y: Int
+	val label: String?
+	val scale = 1
+	constructor(x: Int, flip: Bool) {
+		super(x)
+		if flip {
+			this.x = 0
+			y = x
+		} else {
+			this.x = x
+			y = 0
+		}
+		println(describe())
+	}
+	fn describe(): String = \"\" + x + y + label + scale + id
+}
+
+fn main() {
+	println(Point(1, true).describe())
+}"
+	);
+	let found = found(
+		"class Base {
+	val id: Int
+	constructor(id: Int) {
+		this.id = id
+	}
+}
+
+class Point : Base {
+	val x: Int
+	val y: Int
+	constructor(x: Int, flip: Bool) {
+		this.x = x
+		super(x)
+		if flip {
+			y = 0
+		}
+	}
+	constructor(x: Int) {
+		super(x)
+		this.x = x
+		println(describe())
+		y = 0
+	}
+	constructor(name: String) {
+		println(describe())
+		super(1)
+		this.x = 1
+		y = 1
+	}
+	fn describe(): String = \"\" + x + y
+}
+
+class Loose {
+	val name: String
+}"
+	);
+	assert_eq!(found, [("E0509", 11, 2), ("E0509", 21, 11), ("E0509", 25, 11), ("E0509", 34, 6)]);
+}
+
+#[test]
+fn sealed_hierarchies_are_closed_to_other_packages_and_switched_over_whole() {
+	let shapes = "package shapes
+
+sealed interface Shape {
+}
+
+class Circle : Shape {
+}
+
+sealed class Square : Shape {
+}
+
+class Tile : Square {
+}
+
+class Block : Square {
+}";
+	// A sealed subclass is covered by a case for it or by cases for all of its own
+	let main = "import shapes.Shape
+import shapes.Circle
+import shapes.Square
+import shapes.Tile
+import shapes.Block
+
+fn name(shape: Shape): String {
+	switch shape {
+		case Circle: return \"circle\"
+		case Square: return \"square\"
+	}
+}
+
+fn finer(shape: Shape): String {
+	switch shape {
+		case Circle: return \"circle\"
+		case Tile: return \"tile\"
+		case Block: return \"block\"
+	}
+}
+
+fn main() {
+	println(name(Circle()) + finer(Tile()))
+}";
+	assert_eq!(found_in(&[("main.gl", main), ("shapes.gl", shapes)]), []);
+
+	let main = "import shapes.Shape
+import shapes.Circle
+
+class Triangle : Shape {
+}
+
+sealed fn never() {
+}
+
+fn name(shape: Shape): String {
+	switch shape {
+		case Circle: return \"circle\"
+		default: return \"other\"
+	}
+}";
+	assert_eq!(found_in(&[("main.gl", main), ("shapes.gl", shapes)]), [("E0301", 0, 4, 18), ("E0300", 0, 7, 11)]);
+
+	let main = "import shapes.Shape
+import shapes.Circle
+
+fn name(shape: Shape): String {
+	switch shape {
+		case Circle: return \"circle\"
+	}
+}";
+	assert_eq!(found_in(&[("main.gl", main), ("shapes.gl", shapes)]), [("E0504", 0, 5, 2), ("E0505", 0, 4, 4)]);
+}
+
+#[test]
+fn unions_take_any_of_their_members_and_narrow_by_type() {
+	accepted(
+		"fn describe(value: Int | String | Bool): String {
+	if value is Int {
+		return \"int \" + (value + 1)
+	}
+	if value is String {
+		return \"string \" + value.length
+	}
+	return \"bool \" + !value
+}
+
+fn kind(value: Int | String): String {
+	switch value {
+		case Int: return \"int\"
+		case String: return \"string\"
+	}
+}
+
+fn main() {
+	val x: Int | String = 3
+	val wider: Int | String | Bool = x
+	println(describe(wider) + describe(\"a\") + kind(x))
+}"
+	);
+	let found = found(
+		"fn bad(value: Int | Void) {
+}
+
+fn kind(value: Int | String): String {
+	switch value {
+		case Int: return \"int\"
+	}
+}
+
+fn main(value: Int | String) {
+	val n: Int = value
+	println(value.length)
+	switch value {
+		case Bool: println(\"never\")
+		default: println(\"other\")
+	}
+	val y: Int | String = true
+}"
+	);
+	assert_eq!(
+		found,
+		[
+			("E0410", 1, 15),
+			("E0504", 5, 2),
+			("E0400", 11, 15),
+			("E0211", 12, 16),
+			("E0411", 14, 8),
+			("E0400", 17, 24),
+			("E0505", 4, 4)
+		]
+	);
+}
+
+#[test]
+fn values_are_converted_implicitly_only_without_losing_anything() {
+	accepted(
+		"fn half(x: Float): Float = x / 2
+
+fn main() {
+	val n: Int = 3
+	val f: Float = n
+	val maybe: Float? = n
+	val g: Float = 1
+	val mixed = n * 1.5
+	val named: String? = \"a\"
+	val text: String = \"n\" + n + true
+	println(half(n) + f + maybe!! + g + mixed + named!! + text)
+}"
+	);
+	let source = "fn main(f: Float, maybe: Int?) {
+	val n: Int = f
+	val m: Int = 2.5
+	val big: Float = 9007199254740993
+	val g: Float = maybe
+	val s: String = 3
+}";
+	assert_eq!(found(source), [("E0400", 2, 15), ("E0400", 3, 15), ("E0412", 4, 19), ("E0400", 5, 17), ("E0400", 6, 18)]);
+	// Lossy conversions say how to make them on purpose
+	let errors = checked(&[("main.gl", source)]).err().unwrap();
+	assert!(errors[0].message.ends_with("; converting drops the fraction, so call 'toInt()' to do it on purpose"), "{}", errors[0].message);
+	assert_eq!(errors[3].message, "Type mismatch: expected 'Float', found 'Int?'");
+}
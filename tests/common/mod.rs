@@ -0,0 +1,45 @@
+// What the tests of running programs share: a program of one file checked
+// for a runtime, and run on both engines, which have to agree. Each test
+// file uses only some of it.
+#![allow(dead_code)]
+
+use glee::bytecode::compiler::compile;
+use glee::bytecode::vm;
+use glee::diagnostics::Diagnostic;
+use glee::interpreter::gc::Settings;
+use glee::interpreter::native::Runtime;
+use glee::interpreter::{self, Uncaught};
+use glee::lexer::lexer::Lexer;
+use glee::parser::parser::Parser;
+use glee::sema::hir::Program;
+use glee::sema::modules::SourceFile;
+
+// `source` as the file `main.gl`, which has to parse.
+pub fn parsed(source: &str) -> SourceFile {
+	let tokens = Lexer::new(source.to_string()).lex().unwrap().clone();
+	SourceFile { path: "main.gl".to_string(), program: Parser::new(tokens).parse().unwrap(), package: None }
+}
+
+// `source` checked for `runtime`, with the natives and files it has.
+pub fn checked_with(runtime: &Runtime, source: &str) -> Result<Program, Vec<Diagnostic>> {
+	runtime.check(&[parsed(source)])
+}
+
+// `source` checked on its own, which has to pass.
+pub fn checked(source: &str) -> Program {
+	checked_with(&Runtime::new(), source).unwrap()
+}
+
+// Runs `program` on the interpreter and on the VM, collecting garbage as
+// `gc` says, checks they agree, and returns what it printed and what stopped
+// it.
+pub fn run_both(program: &Program, runtime: &Runtime, gc: Settings) -> (String, Option<Uncaught>) {
+	let mut expected = Vec::new();
+	let expected_uncaught = interpreter::run(program, runtime, None, &[], &mut expected).err();
+	let mut out = Vec::new();
+	let uncaught = vm::run(&compile(program, None), runtime, &[], gc, &mut out).err();
+	let out = String::from_utf8(out).unwrap();
+	assert_eq!(out, String::from_utf8(expected).unwrap());
+	assert_eq!(uncaught, expected_uncaught);
+	(out, uncaught)
+}
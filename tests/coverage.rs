@@ -0,0 +1,85 @@
+// What a program compiled for coverage counts of the lines it runs, and how
+// `dotfun test --coverage` reports it.
+
+mod common;
+
+use common::checked;
+use glee::bytecode::compiler::{compile, instrument};
+use glee::bytecode::coverage::Coverage;
+use glee::bytecode::file;
+use glee::bytecode::vm::Vm;
+use glee::interpreter::gc::Settings;
+use glee::interpreter::native::Runtime;
+
+const PROGRAM: &str = "val limit = 10
+
+fn sign(n: Int): Int {
+	if n > 0 {
+		return 1
+	}
+	if n < 0 {
+		return -1
+	}
+	return 0
+}
+
+fn clamp(n: Int): Int {
+	try {
+		return if (n > limit) limit else n
+	} finally {
+		println(\"clamped\")
+	}
+}
+
+@Test
+fn signs() {
+	for (i in 1..4) {
+		sign(i)
+	}
+	clamp(20)
+}";
+
+// Runs the program's tests as `dotfun test` does, and what they covered.
+fn covered(source: &str) -> Coverage {
+	let program = checked(source);
+	let module = instrument(&program, None);
+	let mut out = Vec::new();
+	let mut vm = Vm::new(&module, &Runtime::new(), Settings::default(), &mut out);
+	vm.init().unwrap();
+	for test in &program.tests {
+		vm.call(test.0, Vec::new()).unwrap();
+	}
+	let counts = vm.counts().to_vec();
+	drop(vm);
+	assert_eq!(String::from_utf8(out).unwrap(), "clamped\n");
+	Coverage::new(&module, &counts)
+}
+
+#[test]
+fn each_line_counts_the_times_its_statements_ran() {
+	let coverage = covered(PROGRAM);
+	let lines: Vec<(i64, u64)> = coverage.files[0].lines.iter().map(|(&line, &count)| (line, count)).collect();
+	assert_eq!(lines, [(1, 1), (4, 3), (5, 3), (7, 0), (8, 0), (10, 0), (14, 1), (15, 1), (17, 1), (23, 1), (24, 3), (26, 1)]);
+	assert_eq!(coverage.files[0].missed(), "7-10");
+	assert_eq!(coverage.lcov(), format!(
+		"TN:\nSF:main.gl\n{}LF:12\nLH:9\nend_of_record\n",
+		lines.iter().map(|(line, count)| format!("DA:{},{}\n", line, count)).collect::<String>()
+	));
+	let summary = coverage.summary();
+	assert_eq!(summary.lines().collect::<Vec<_>>(), [
+		"file                             lines     run   cover  not run",
+		"main.gl                             12       9   75.0%  7-10",
+		"total                               12       9   75.0%"
+	]);
+}
+
+#[test]
+fn only_instrumented_programs_count_and_they_save_unchanged() {
+	let program = checked(PROGRAM);
+	let module = instrument(&program, None);
+	// The `finally` block is compiled in again at each way out of its `try`,
+	// with counters of its own
+	assert_eq!(module.counters.len(), 14);
+	assert_eq!(file::load(&file::save(&module)).unwrap(), module);
+	assert!(compile(&program, None).counters.is_empty());
+}
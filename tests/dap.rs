@@ -0,0 +1,170 @@
+// What `dotfun dap` says to an editor debugging a program through it.
+
+use std::fs;
+use std::io::Cursor;
+
+use glee::driver::dap::serve;
+use glee::driver::json::{self, Json};
+
+const PROGRAM: &str = "val names = [\"a\", \"b\"]
+
+fn square(n: Int): Int {
+	val result = n * n
+	return result
+}
+
+fn main() {
+	mut val total = 0
+	for (i in 0..3) {
+		total += square(i)
+	}
+	println(total)
+}
+";
+
+// Frames each request as the protocol does, numbering them from 1.
+fn requests(requests: &[(&str, Json)]) -> Vec<u8> {
+	let mut input = Vec::new();
+	for (seq, (command, arguments)) in requests.iter().enumerate() {
+		let request = Json::object([("seq", (seq + 1).into()), ("type", "request".into()), ("command", (*command).into()), ("arguments", arguments.clone())]);
+		let text = request.to_string();
+		input.extend(format!("Content-Length: {}\r\n\r\n{}", text.len(), text).into_bytes());
+	}
+	input
+}
+
+// The messages in what was sent back.
+fn messages(output: &[u8]) -> Vec<Json> {
+	let mut text = String::from_utf8(output.to_vec()).unwrap();
+	let mut messages = Vec::new();
+	while let Some((header, rest)) = text.split_once("\r\n\r\n") {
+		let length: usize = header.trim_start_matches("Content-Length: ").parse().unwrap();
+		messages.push(json::parse(&rest[..length]).unwrap());
+		text = rest[length..].to_string();
+	}
+	messages
+}
+
+fn debug(name: &str, script: &[(&str, Json)]) -> Vec<Json> {
+	let path = std::env::temp_dir().join(format!("dotfun-dap-{}-{}.gl", name, std::process::id()));
+	fs::write(&path, PROGRAM).unwrap();
+	let path = path.to_str().unwrap().to_string();
+	let mut requests = requests(&[
+		("initialize", Json::object([("adapterID", "dotfun".into())])),
+		("launch", Json::object([("program", path.as_str().into())])),
+		("setBreakpoints", Json::object([("source", Json::object([("path", path.as_str().into())])), ("breakpoints", vec![Json::object([("line", 4i64.into())])].into())])),
+		("configurationDone", Json::object([]))
+	]);
+	requests.extend(self::requests(script));
+	let mut output = Vec::new();
+	assert_eq!(serve(Box::new(Cursor::new(requests)), Box::new(&mut output)), 0);
+	fs::remove_file(&path).unwrap();
+	messages(&output)
+}
+
+fn response<'a>(messages: &'a [Json], command: &str) -> &'a Json {
+	messages.iter().find(|message| message.get("type").as_str() == Some("response") && message.get("command").as_str() == Some(command)).unwrap()
+}
+
+fn events<'a>(messages: &'a [Json], event: &str) -> Vec<&'a Json> {
+	messages.iter().filter(|message| message.get("event").as_str() == Some(event)).map(|message| message.get("body")).collect()
+}
+
+#[test]
+fn editors_stop_at_breakpoints_and_look_at_variables() {
+	let messages = debug("variables", &[
+		("stackTrace", Json::object([("threadId", 1i64.into())])),
+		("scopes", Json::object([("frameId", 0i64.into())])),
+		("variables", Json::object([("variablesReference", 1i64.into())])),
+		("variables", Json::object([("variablesReference", 2i64.into())])),
+		("variables", Json::object([("variablesReference", 3i64.into())])),
+		("evaluate", Json::object([("expression", "n * 10".into()), ("frameId", 0i64.into())])),
+		("evaluate", Json::object([("expression", "total".into()), ("frameId", 1i64.into())])),
+		("evaluate", Json::object([("expression", "square(n)".into()), ("frameId", 0i64.into())])),
+		("setBreakpoints", Json::object([("source", Json::object([("path", "elsewhere.gl".into())])), ("breakpoints", vec![Json::object([("line", 2i64.into())])].into())])),
+		("continue", Json::object([("threadId", 1i64.into())])),
+		("continue", Json::object([("threadId", 1i64.into())])),
+		("continue", Json::object([("threadId", 1i64.into())])),
+		("disconnect", Json::object([]))
+	]);
+	assert!(response(&messages, "launch").get("success").as_bool().unwrap());
+	assert_eq!(events(&messages, "stopped").len(), 3);
+	assert_eq!(events(&messages, "stopped")[0].get("reason").as_str(), Some("breakpoint"));
+	// Files not in the program have no lines to stop at
+	let set = messages.iter().filter(|message| message.get("command").as_str() == Some("setBreakpoints")).nth(1).unwrap();
+	assert_eq!(set.get("body").get("breakpoints").items()[0].get("verified"), &Json::Bool(false));
+
+	let frames = response(&messages, "stackTrace").get("body").get("stackFrames").items();
+	let frames: Vec<(&str, i64)> = frames.iter().map(|frame| (frame.get("name").as_str().unwrap(), frame.get("line").as_i64().unwrap())).collect();
+	assert_eq!(frames, [("square", 4), ("main", 11)]);
+
+	let variables: Vec<Vec<(&str, &str, i64)>> = messages
+		.iter()
+		.filter(|message| message.get("command").as_str() == Some("variables"))
+		.map(|message| {
+			let variables = message.get("body").get("variables").items();
+			variables.iter().map(|variable| (variable.get("name").as_str().unwrap(), variable.get("value").as_str().unwrap(), variable.get("variablesReference").as_i64().unwrap())).collect()
+		})
+		.collect();
+	assert_eq!(variables, [vec![("n", "0", 0), ("result", "null", 0)], vec![("names", "[\"a\", \"b\"]", 3)], vec![("[0]", "\"a\"", 0), ("[1]", "\"b\"", 0)]]);
+
+	let evaluated: Vec<String> = messages
+		.iter()
+		.filter(|message| message.get("command").as_str() == Some("evaluate"))
+		.map(|message| message.get("body").get("result").as_str().or(message.get("message").as_str()).unwrap().to_string())
+		.collect();
+	assert_eq!(evaluated, ["0", "0", "The debugger cannot call functions"]);
+
+	let output: Vec<&str> = events(&messages, "output").iter().filter_map(|output| output.get("output").as_str()).collect();
+	assert_eq!(output.concat(), "5\n");
+	assert_eq!(events(&messages, "exited")[0].get("exitCode").as_i64(), Some(0));
+	assert_eq!(events(&messages, "terminated").len(), 1);
+}
+
+#[test]
+fn stepping_is_said_to_have_stopped_the_program() {
+	let messages = debug("stepping", &[
+		("stepOut", Json::object([("threadId", 1i64.into())])),
+		("next", Json::object([("threadId", 1i64.into())])),
+		("stackTrace", Json::object([("threadId", 1i64.into())])),
+		("stepIn", Json::object([("threadId", 1i64.into())])),
+		("stackTrace", Json::object([("threadId", 1i64.into())])),
+		("continue", Json::object([("threadId", 1i64.into())])),
+		("continue", Json::object([("threadId", 1i64.into())])),
+		("continue", Json::object([("threadId", 1i64.into())])),
+		("disconnect", Json::object([]))
+	]);
+	let reasons: Vec<&str> = events(&messages, "stopped").iter().map(|stopped| stopped.get("reason").as_str().unwrap()).collect();
+	assert_eq!(reasons, ["breakpoint", "step", "step", "step", "breakpoint", "breakpoint"]);
+	let traces: Vec<i64> = messages
+		.iter()
+		.filter(|message| message.get("command").as_str() == Some("stackTrace"))
+		.map(|message| message.get("body").get("stackFrames").items()[0].get("line").as_i64().unwrap())
+		.collect();
+	assert_eq!(traces, [10, 11]);
+}
+
+#[test]
+fn programs_with_errors_are_not_launched() {
+	let path = std::env::temp_dir().join(format!("dotfun-dap-errors-{}.gl", std::process::id()));
+	fs::write(&path, "fn main() {\n\tprintln(missing)\n}\n").unwrap();
+	let input = requests(&[("launch", Json::object([("program", path.to_str().unwrap().into())])), ("disconnect", Json::object([]))]);
+	let mut output = Vec::new();
+	serve(Box::new(Cursor::new(input)), Box::new(&mut output));
+	fs::remove_file(&path).unwrap();
+	let messages = messages(&output);
+	let launch = response(&messages, "launch");
+	assert_eq!((launch.get("success"), launch.get("message").as_str()), (&Json::Bool(false), Some("The program has errors")));
+	let output = events(&messages, "output")[0].get("output").as_str().unwrap();
+	assert!(output.contains("missing"), "{}", output);
+}
+
+#[test]
+fn json_is_read_and_written_back_the_same() {
+	let text = r#"{"a":[1,-2.5,true,null],"b":"é😀\n\"","c":{}}"#;
+	let value = json::parse(text).unwrap();
+	assert_eq!(value.get("b").as_str(), Some("é😀\n\""));
+	assert_eq!(value.get("a").items()[1], Json::Number(-2.5));
+	assert_eq!(json::parse(&value.to_string()).unwrap(), value);
+	assert_eq!(json::parse("[1,").unwrap_err(), "Expected a value, found the end of the JSON");
+}
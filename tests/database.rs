@@ -0,0 +1,71 @@
+// Queries are answered from memory until the sources they read change.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use glee::driver::database::{Database, Parsed};
+use glee::lexer::lexer::Lexer;
+use glee::parser::incremental::{IncrementalParser, TextEdit};
+use glee::parser::parser::Parser;
+use glee::sema::check::check;
+use glee::sema::modules::SourceFile;
+
+fn parse(text: &str) -> Parsed {
+	let tokens = Lexer::new(text.to_string()).lex().map_err(|err| vec![*err])?.clone();
+	Parser::new(tokens).parse()
+}
+
+#[test]
+fn only_what_changed_is_worked_out_again() {
+	let mut database = Database::new();
+	let paths = ["a.gl".to_string(), "b.gl".to_string()];
+	let parses = AtomicUsize::new(0);
+	let checks = AtomicUsize::new(0);
+	let build = |database: &mut Database| {
+		let parsed = database.parse(&paths, true, |_, text| {
+			parses.fetch_add(1, Ordering::Relaxed);
+			parse(text)
+		});
+		let files: Vec<SourceFile> = paths
+			.iter()
+			.zip(parsed)
+			.map(|(path, parsed)| SourceFile { path: path.clone(), program: parsed.unwrap(), package: None })
+			.collect();
+		let key: Vec<(String, Option<String>)> = paths.iter().map(|path| (path.clone(), None)).collect();
+		database.check(&key, |_| {
+			checks.fetch_add(1, Ordering::Relaxed);
+			check(&files)
+		})
+	};
+
+	database.set_source("a.gl", "fn main() {\n\tval four: Int = twice(2)\n\tprintln(four)\n}");
+	database.set_source("b.gl", "fn twice(x: Int): Int = x * 2");
+	assert!(build(&mut database).is_ok());
+	assert_eq!((parses.load(Ordering::Relaxed), checks.load(Ordering::Relaxed)), (2, 1));
+
+	// The same text again is no change
+	database.set_source("b.gl", "fn twice(x: Int): Int = x * 2");
+	let revision = database.revision();
+	assert!(build(&mut database).is_ok());
+	assert_eq!((parses.load(Ordering::Relaxed), checks.load(Ordering::Relaxed)), (2, 1));
+	assert_eq!(database.revision(), revision);
+
+	database.set_source("b.gl", "fn twice(x: Int): String = \"twice\"");
+	let errors = build(&mut database).unwrap_err();
+	assert_eq!((parses.load(Ordering::Relaxed), checks.load(Ordering::Relaxed)), (3, 2));
+	assert_eq!(errors[0].message, "Type mismatch: expected 'Int', found 'String'");
+}
+
+#[test]
+fn trees_parsed_elsewhere_are_not_parsed_again() {
+	let mut database = Database::new();
+	let paths = ["a.gl".to_string()];
+	let mut parser = IncrementalParser::new("fn main() {\n\tprintln(1)\n}".to_string());
+	parser.apply(TextEdit { start: 21, end: 22, text: "2 + 3".to_string() }).unwrap();
+	database.set_parsed("a.gl", parser.source(), parser.parsed());
+	let parsed = database.parse(&paths, true, |_, _| panic!("parsed again"));
+	assert_eq!(format!("{:?}", parsed[0].as_ref().unwrap().stmts), format!("{:?}", parser.program().unwrap().stmts));
+
+	// Until the text changes
+	database.set_source("a.gl", "fn main() {}");
+	assert_eq!(database.parse(&paths, true, |_, text| parse(text))[0].as_ref().unwrap().stmts.len(), 1);
+}
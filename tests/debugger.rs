@@ -0,0 +1,166 @@
+// Where the debugger stops programs, and what it shows of them there.
+
+mod common;
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+use common::checked;
+use glee::bytecode::compiler::compile;
+use glee::bytecode::debugger::{self, Debugger, Frontend, Reason, Resume};
+use glee::bytecode::file;
+use glee::bytecode::vm::{self, Vm};
+use glee::interpreter::native::Runtime;
+
+const PROGRAM: &str = "val greeting = \"hi\"
+
+class Point {
+	val x: Int
+	val y: Int
+	constructor(x: Int, y: Int) {
+		this.x = x
+		this.y = y
+	}
+	fn sum(): Int {
+		val total = x + y
+		return total
+	}
+}
+
+fn square(n: Int): Int {
+	val result = n * n
+	return result
+}
+
+fn main() {
+	mut val count = 0
+	for (i in 0..3) {
+		count += square(i)
+	}
+	val p = Point(2, 3)
+	println(p.sum())
+	println(count)
+}";
+
+// Each time the program stops: why, the function and line, and what each
+// expression asked about is, or the error.
+type Stop = (Reason, String, i64, Vec<String>);
+
+// Goes on from each stop as `resumes` say, in order, and then to the end,
+// setting the breakpoints given with each.
+struct Script {
+	resumes: Vec<(Resume, Vec<i64>)>,
+	expressions: Vec<&'static str>,
+	stops: Rc<RefCell<Vec<Stop>>>
+}
+
+impl Frontend for Script {
+	fn paused(&mut self, vm: &Vm, breakpoints: &mut BTreeSet<(usize, i64)>, reason: Reason) -> Resume {
+		let frame = &vm.backtrace()[0];
+		let values = self
+			.expressions
+			.iter()
+			.map(|text| debugger::evaluate(vm, 0, text).map_or_else(|err| format!("error: {}", err), |value| debugger::show(&value)))
+			.collect();
+		self.stops.borrow_mut().push((reason, frame.function.clone(), frame.span.line, values));
+		if self.resumes.is_empty() {
+			breakpoints.clear();
+			return Resume::Continue;
+		}
+		let (resume, lines) = self.resumes.remove(0);
+		*breakpoints = lines.into_iter().map(|line| (0, line)).collect();
+		resume
+	}
+}
+
+// Runs `PROGRAM` under the debugger, returning where it stopped and what it
+// printed.
+fn debug(breakpoints: &[i64], resumes: Vec<(Resume, Vec<i64>)>, expressions: Vec<&'static str>) -> (Vec<Stop>, String) {
+	let module = compile(&checked(PROGRAM), None);
+	let stops = Rc::new(RefCell::new(Vec::new()));
+	let script = Script { resumes, expressions, stops: stops.clone() };
+	let debugger = Debugger::new(Box::new(script), breakpoints.iter().map(|&line| (0, line)).collect(), breakpoints.is_empty());
+	let mut out = Vec::new();
+	vm::debug(&module, &Runtime::new(), &[], debugger, &mut out).unwrap();
+	(stops.take(), String::from_utf8(out).unwrap())
+}
+
+fn lines(stops: &[Stop]) -> Vec<(Reason, &str, i64)> {
+	stops.iter().map(|(reason, function, line, _)| (*reason, function.as_str(), *line)).collect()
+}
+
+#[test]
+fn breakpoints_stop_each_time_their_line_is_reached() {
+	let (stops, out) = debug(&[17], vec![(Resume::Continue, vec![17]), (Resume::Continue, vec![17]), (Resume::Continue, vec![])], vec!["n", "result"]);
+	assert_eq!(lines(&stops), [(Reason::Breakpoint, "square", 17); 3]);
+	let values: Vec<&[String]> = stops.iter().map(|(_, _, _, values)| values.as_slice()).collect();
+	assert_eq!(values, [["0", "null"], ["1", "null"], ["2", "null"]]);
+	assert_eq!(out, "5\n5\n");
+}
+
+#[test]
+fn steps_go_into_over_and_out_of_calls() {
+	let resumes = vec![
+		(Resume::StepInto, vec![]),
+		(Resume::StepOver, vec![]),
+		(Resume::StepOut, vec![]),
+		(Resume::StepOver, vec![]),
+		(Resume::StepOver, vec![])
+	];
+	let (stops, _) = debug(&[24], resumes, vec![]);
+	assert_eq!(lines(&stops), [
+		(Reason::Breakpoint, "main", 24),
+		(Reason::Step, "square", 17),
+		(Reason::Step, "square", 18),
+		(Reason::Step, "main", 24),
+		(Reason::Step, "main", 23),
+		(Reason::Step, "main", 24)
+	]);
+}
+
+#[test]
+fn programs_can_stop_before_their_first_line() {
+	let (stops, _) = debug(&[], vec![(Resume::StepOver, vec![])], vec!["greeting"]);
+	assert_eq!(lines(&stops), [(Reason::Entry, "<top level>", 1), (Reason::Step, "main", 22)]);
+	assert_eq!(stops[0].3, ["null"]);
+	assert_eq!(stops[1].3, ["\"hi\""]);
+}
+
+#[test]
+fn expressions_read_locals_fields_and_globals_without_calling_anything() {
+	let expressions = vec!["x * 10 + y", "this", "total", "greeting + \"!\"", "[x, y][1]", "this.sum()", "x = 1", "nothing"];
+	let (stops, _) = debug(&[12], vec![], expressions);
+	assert_eq!(stops[0].3, [
+		"23",
+		"Point(x: 2, y: 3)",
+		"5",
+		"\"hi!\"",
+		"3",
+		"error: The debugger cannot call functions",
+		"error: The debugger cannot change the program",
+		"error: Cannot find 'nothing' here"
+	]);
+}
+
+#[test]
+fn backtraces_and_locals_are_of_each_call_in_progress() {
+	let module = compile(&checked(PROGRAM), None);
+	struct Inspect(Rc<RefCell<Vec<String>>>);
+	impl Frontend for Inspect {
+		fn paused(&mut self, vm: &Vm, _: &mut BTreeSet<(usize, i64)>, _: Reason) -> Resume {
+			let mut seen = self.0.borrow_mut();
+			for (depth, frame) in vm.backtrace().iter().enumerate() {
+				let locals: Vec<String> = vm.locals(depth).iter().map(|(name, value)| format!("{}={}", name, debugger::show(value))).collect();
+				seen.push(format!("{}:{} {}", frame.function, frame.span.line, locals.join(" ")));
+			}
+			Resume::Continue
+		}
+	}
+	let seen = Rc::new(RefCell::new(Vec::new()));
+	let debugger = Debugger::new(Box::new(Inspect(seen.clone())), BTreeSet::from([(0, 18)]), false);
+	// Debug info survives being written to a file and read back
+	let module = file::load(&file::save(&module)).unwrap();
+	vm::debug(&module, &Runtime::new(), &[], debugger, &mut Vec::new()).unwrap();
+	assert_eq!(seen.borrow()[4..], ["square:18 n=2 result=4", "main:24 count=1 i=2 p=null"]);
+}
@@ -0,0 +1,113 @@
+// What `dotfun doc` documents, the signatures it shows, and the links between
+// the pages it writes.
+
+use glee::doc::{Docs, Link, html, json};
+use glee::lexer::lexer::Lexer;
+use glee::parser::parser::Parser;
+use glee::sema::check::check;
+use glee::sema::modules::SourceFile;
+
+// Docs for files made from `(path, source)`; the last file is a dependency's,
+// and left out.
+fn document(sources: &[(&str, &str)]) -> Docs {
+	let files: Vec<SourceFile> = sources
+		.iter()
+		.map(|(path, source)| {
+			let tokens = Lexer::new(source.to_string()).lex().unwrap().clone();
+			SourceFile { path: path.to_string(), program: Parser::new(tokens).parse().unwrap(), package: None }
+		})
+		.collect();
+	let program = check(&files).unwrap();
+	Docs::new(&program, |file| file + 1 < sources.len())
+}
+
+const ZOO: &str = "package zoo
+/// Something alive. See [Dog] and `speak`.
+abstract class Animal {
+	val name: String
+	private mut age: Int = 0
+	/// Makes one
+	constructor(name: String) {
+		this.name = name
+	}
+	fn speak(): String = \"...\"
+}
+
+class Dog : Animal {
+	constructor(name: String) {
+		super(name)
+	}
+	override fn speak(): String = \"woof\"
+	private fn secret() {}
+}
+
+/// Picks one, or nothing
+fn pick<T: Animal>(first: T, count: Int = 1, ...rest: T): Dog? = null
+fn <T> T[].second(): T? = null
+private fn hidden() {}
+
+/// How many there can be
+const LIMIT = 10
+
+enum Color {
+	/// The first
+	Red,
+	Green
+}";
+
+#[test]
+fn signatures_are_written_as_declared() {
+	let docs = document(&[("zoo.gl", ZOO), ("dependency.gl", "fn elsewhere() {}")]);
+	assert_eq!(docs.packages.len(), 1);
+	let items: Vec<(&str, String)> = docs.all().map(|(_, item)| (item.anchor.as_str(), item.signature_text())).collect();
+	assert_eq!(items, [
+		("Animal", "abstract class Animal".to_string()),
+		("Animal.name", "val name: String".to_string()),
+		("Animal.constructor", "constructor(name: String)".to_string()),
+		("Animal.speak", "fn speak(): String".to_string()),
+		("Dog", "class Dog : Animal".to_string()),
+		("Dog.constructor", "constructor(name: String)".to_string()),
+		("Dog.speak", "override fn speak(): String".to_string()),
+		("pick", "fn pick<T: Animal>(first: T, count: Int = ..., ...rest: T): Dog?".to_string()),
+		("second", "fn <T> T[].second(): T?".to_string()),
+		("LIMIT", "const LIMIT: Int".to_string()),
+		("Color", "enum Color".to_string()),
+		("Color.Red", "Red".to_string()),
+		("Color.Green", "Green".to_string()),
+		("Color.values", "static fn values(): Color[]".to_string()),
+		("Color.valueOf", "static fn valueOf(name: String): Color?".to_string())
+	]);
+	let pick = docs.packages[0].items.iter().find(|item| item.name == "pick").unwrap();
+	assert_eq!(pick.doc.as_deref(), Some("Picks one, or nothing"));
+	assert_eq!((pick.path.as_str(), pick.line), ("zoo.gl", 22));
+	let linked: Vec<&str> = pick.signature.iter().filter(|segment| segment.link.is_some()).map(|segment| segment.text.as_str()).collect();
+	assert_eq!(linked, ["Animal", "Dog"]);
+}
+
+#[test]
+fn doc_comments_link_to_what_they_name() {
+	let docs = document(&[("zoo.gl", ZOO), ("main.gl", "import zoo.Dog\n/// Walks a [Dog], a [zoo.Color.Red] or a [Cat]\nfn walk(dog: Dog) {}"), ("dependency.gl", "")]);
+	let link = |package: &str, anchor: &str| Some(Link { package: package.to_string(), anchor: anchor.to_string() });
+	assert_eq!(docs.resolve("", "Dog"), link("zoo", "Dog"));
+	assert_eq!(docs.resolve("zoo", "Color.Red"), link("zoo", "Color.Red"));
+	assert_eq!(docs.resolve("", "zoo.Color.Red"), link("zoo", "Color.Red"));
+	assert_eq!(docs.resolve("", "Cat"), None);
+
+	let pages = html::pages(&docs, "zoo");
+	let names: Vec<&str> = pages.iter().map(|(name, _)| name.as_str()).collect();
+	assert_eq!(names, ["index.html", "_root.html", "zoo.html", "search-index.js"]);
+	let root = &pages[1].1;
+	assert!(root.contains("<pre class=\"signature\">fn walk(dog: <a href=\"zoo.html#Dog\">Dog</a>)</pre>"), "{}", root);
+	assert!(root.contains("<p>Walks a <a href=\"zoo.html#Dog\"><code>Dog</code></a>, a <a href=\"zoo.html#Color.Red\"><code>zoo.Color.Red</code></a> or a [Cat]</p>"), "{}", root);
+	let zoo = &pages[2].1;
+	assert!(zoo.contains("<p>Something alive. See <a href=\"zoo.html#Dog\"><code>Dog</code></a> and <code>speak</code>.</p>"), "{}", zoo);
+	assert!(pages[3].1.contains("{\"name\":\"Color.Red\",\"kind\":\"variant\",\"package\":\"zoo\",\"url\":\"zoo.html#Color.Red\"}"));
+}
+
+#[test]
+fn docs_are_written_as_json_too() {
+	let docs = document(&[("main.gl", "/// Says \"hi\"\nfn greet(to: Friend) {}\nclass Friend {}"), ("dependency.gl", "")]);
+	let greet = "{\"name\":\"greet\",\"kind\":\"fn\",\"signature\":\"fn greet(to: Friend)\",\"links\":[{\"text\":\"Friend\",\"offset\":13,\"package\":\"\",\"anchor\":\"Friend\"}],\"doc\":\"Says \\\"hi\\\"\",\"file\":\"main.gl\",\"line\":2,\"anchor\":\"greet\",\"members\":[]}";
+	let friend = "{\"name\":\"Friend\",\"kind\":\"class\",\"signature\":\"class Friend\",\"links\":[],\"doc\":null,\"file\":\"main.gl\",\"line\":3,\"anchor\":\"Friend\",\"members\":[]}";
+	assert_eq!(json::to_json(&docs), format!("{{\"packages\":[{{\"name\":\"\",\"items\":[{},{}]}}]}}\n", greet, friend));
+}
@@ -0,0 +1,63 @@
+// What `dotfun` prints, run as it is from the command line.
+
+use std::fs;
+use std::process::Command;
+
+const TESTS: &str = "import std.testing.*
+
+@Test
+fn adds() {
+	assertEquals(1 + 2, 3)
+}
+
+@Test
+fn greets() {
+	assertEquals(\"hello\\nworld\\n\", \"hello\\nthere\\n\")
+}
+
+@Test
+fn quotes() {
+	assertEquals(\"a b\", \"a\\tb\")
+}";
+
+// A line with the time it ends with, in milliseconds, left out.
+fn untimed(line: &str) -> String {
+	let Some(ms) = line.strip_suffix("ms").or_else(|| line.strip_suffix("ms)")) else {
+		return line.to_string();
+	};
+	let digits = ms.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+	assert!(digits.len() < ms.len(), "{}", line);
+	format!("{}{}", digits, if line.ends_with(')') { "_ms)" } else { "_ms" })
+}
+
+#[test]
+fn tests_are_timed_and_show_how_what_they_got_differs() {
+	let path = std::env::temp_dir().join(format!("dotfun-tests-{}.gl", std::process::id()));
+	fs::write(&path, TESTS).unwrap();
+	let output = Command::new(env!("CARGO_BIN_EXE_dotfun")).arg("test").arg(&path).arg("--color=never").output().unwrap();
+	fs::remove_file(&path).unwrap();
+	assert_eq!(output.status.code(), Some(1));
+	let stdout = String::from_utf8(output.stdout).unwrap();
+	assert_eq!(stdout.lines().map(untimed).collect::<Vec<_>>(), [
+		"test adds ... ok (_ms)",
+		"test greets ... FAILED (_ms)",
+		"test quotes ... FAILED (_ms)",
+		"",
+		"test result: FAILED. 1 passed; 2 failed; finished in _ms"
+	]);
+	let stderr = String::from_utf8(output.stderr).unwrap();
+	assert!(
+		stderr.contains(
+			"\
+error: Uncaught exception: Expected \"hello\\nthere\\n\" but got \"hello\\nworld\\n\"
+  hello
+- there
++ world
+"
+		),
+		"{}",
+		stderr
+	);
+	assert!(stderr.contains("error: Uncaught exception: Expected \"a\\tb\" but got \"a b\"\n"), "{}", stderr);
+	assert_eq!(stderr.matches("error: ").count(), 2);
+}
@@ -0,0 +1,124 @@
+// The catalog `--explain` prints has to cover every code the compiler
+// reports, and each example in it has to actually get its diagnostic.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::thread;
+
+use glee::diagnostics::Diagnostic;
+use glee::diagnostics::codes::{CATALOG, explain};
+use glee::diagnostics::lints::LINTS;
+use glee::lexer::lexer::Lexer;
+use glee::parser::parser::Parser;
+use glee::sema::check::check;
+use glee::sema::modules::SourceFile;
+
+#[test]
+fn codes_are_unique_and_well_formed() {
+	let mut seen = BTreeSet::new();
+	for explanation in CATALOG {
+		let code = explanation.code;
+		let digits = &code[1..];
+		assert!(code.len() == 5 && (code.starts_with('E') || code.starts_with('W')), "'{}' is not a code", code);
+		assert!(digits.chars().all(|c| c.is_ascii_digit()), "'{}' is not a code", code);
+		assert!(seen.insert(code), "'{}' is in the catalog twice", code);
+		assert!(!explanation.title.is_empty() && !explanation.text.is_empty(), "'{}' is not explained", code);
+		if code.starts_with('W') {
+			assert!(LINTS.iter().any(|lint| lint.code == code), "warning '{}' belongs to no lint", code);
+		}
+	}
+	assert_eq!(explain("e0200").map(|explanation| explanation.code), Some("E0200"));
+	assert_eq!(explain("E9999"), None);
+}
+
+#[test]
+fn every_reported_code_is_in_the_catalog() {
+	let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("bootstrap");
+	let mut used = BTreeSet::new();
+	scan(&root, &mut used);
+
+	let catalog: BTreeSet<String> = CATALOG.iter().map(|explanation| explanation.code.to_string()).collect();
+	let missing: Vec<&String> = used.difference(&catalog).collect();
+	assert!(missing.is_empty(), "reported but not in the catalog: {:?}", missing);
+	let unused: Vec<&String> = catalog.difference(&used).collect();
+	assert!(unused.is_empty(), "in the catalog but never reported: {:?}", unused);
+}
+
+// Runs with a main thread's 8 MiB of stack, like the parser's fuzz test, for
+// the example of code nested too deeply.
+#[test]
+fn examples_get_their_diagnostic() {
+	let worker = thread::Builder::new().stack_size(8 << 20).spawn(|| {
+		let mut failures = Vec::new();
+		for explanation in CATALOG {
+			let diagnostics = compile(explanation.example);
+			if !diagnostics.iter().any(|diagnostic| diagnostic.code == Some(explanation.code)) {
+				let got: Vec<String> = diagnostics.iter().map(|diagnostic| format!("{:?}: {}", diagnostic.code, diagnostic)).collect();
+				failures.push(format!("{}\n  got {:?}", explanation.code, got));
+			}
+		}
+		assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+	});
+	worker.unwrap().join().unwrap();
+}
+
+// Codes in string literals in the compiler's source, except the catalog's own.
+fn scan(path: &Path, used: &mut BTreeSet<String>) {
+	if path.is_dir() {
+		for entry in fs::read_dir(path).unwrap() {
+			scan(&entry.unwrap().path(), used);
+		}
+		return;
+	}
+	if path.extension().is_none_or(|extension| extension != "rs") || path.ends_with("diagnostics/codes.rs") {
+		return;
+	}
+	let source = fs::read_to_string(path).unwrap();
+	let bytes = source.as_bytes();
+	for (index, _) in source.match_indices('"') {
+		let Some(code) = bytes.get(index + 1..index + 7) else { continue };
+		if matches!(code[0], b'E' | b'W') && code[1..5].iter().all(u8::is_ascii_digit) && code[5] == b'"' {
+			used.insert(String::from_utf8(code[..5].to_vec()).unwrap());
+		}
+	}
+}
+
+// Every diagnostic the example gets, from whichever stage stops it.
+fn compile(example: &str) -> Vec<Diagnostic> {
+	let mut files = Vec::new();
+	for (path, source) in split(example) {
+		let tokens = match Lexer::new(source).lex() {
+			Ok(tokens) => tokens.clone(),
+			Err(error) => return vec![*error]
+		};
+		match Parser::new(tokens).parse() {
+			Ok(program) => files.push(SourceFile { path, program, package: None }),
+			Err(errors) => return errors
+		}
+	}
+	match check(&files) {
+		Ok(program) => program.warnings,
+		Err(errors) => errors
+	}
+}
+
+// The files of an example, which starts each with a `// file: name.gl` line
+// when it has more than one.
+fn split(example: &str) -> Vec<(String, String)> {
+	let mut files: Vec<(String, String)> = Vec::new();
+	for line in example.lines() {
+		match line.strip_prefix("// file: ") {
+			Some(path) => files.push((path.to_string(), String::new())),
+			None => {
+				if files.is_empty() {
+					files.push(("example.gl".to_string(), String::new()));
+				}
+				let text = &mut files.last_mut().unwrap().1;
+				text.push_str(line);
+				text.push('\n');
+			}
+		}
+	}
+	files
+}
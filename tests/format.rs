@@ -0,0 +1,84 @@
+// The layout `dotfun fmt` gives code, written down as source and what it must
+// become, and the promises it keeps for any file that parses: the tokens stay
+// the same, formatting twice changes nothing, and no line it can wrap is left
+// longer than `MAX_WIDTH`.
+
+use glee::format::{MAX_WIDTH, format};
+use glee::lexer::lexer::Lexer;
+
+const LAYOUTS: &[(&str, &str)] = &[
+	// Indentation is a tab a level, and spacing is canonical
+	("fn add(a:Int,b:Int):Int{\nreturn a+b\n}", "fn add(a: Int, b: Int): Int {\n\treturn a + b\n}\n"),
+	("fn f() {\n        if(x>0){\n  g()\n }\n}", "fn f() {\n\tif (x>0) {\n\t\tg()\n\t}\n}\n"),
+	// Blank lines collapse to one, and go just inside braces
+	("fn f() {\n\n\tval a = 1\n\n\n\tval b = 2\n\n}", "fn f() {\n\tval a = 1\n\n\tval b = 2\n}\n"),
+	// Comments stay where they are
+	("// top\nfn f() { // trailing\n/* block */ g()\n}", "// top\nfn f() { // trailing\n\t/* block */ g()\n}\n"),
+	("fn f() {\n\tg(/* none */)\n}", "fn f() {\n\tg(/* none */)\n}\n"),
+	// Minus is unary where no value comes before it
+	("val a = b-1\nval c = -b\nval d = f(-1)*-2", "val a = b - 1\nval c = -b\nval d = f(-1) * -2\n"),
+	("fn f() {\n\ti ++\n\t++ i\n}", "fn f() {\n\ti++\n\t++i\n}\n"),
+	// Type arguments hug, and `<` and `>` otherwise keep their spacing
+	("val a: Map<String, List<Int>>? = null\nval b = a < c", "val a: Map<String, List<Int>>? = null\nval b = a < c\n"),
+	("val a: Int[] = [1,2]\nval b = a [0]!!", "val a: Int[] = [1, 2]\nval b = a[0]!!\n"),
+	// Colons are tight in annotations and spaced in type headers
+	("class Dog : Animal {\n\tval name : String\n}", "class Dog : Animal {\n\tval name: String\n}\n"),
+	// Strings, and what is interpolated into them, stay as written
+	("val s = \"a ${ b+1 }  $c\"", "val s = \"a ${ b+1 }  $c\"\n"),
+	// Case bodies go a level in from their cases
+	(
+		"switch (x) {\ncase 1:\nf()\ng()\ndefault: h()\n}",
+		"switch (x) {\n\tcase 1:\n\t\tf()\n\t\tg()\n\tdefault: h()\n}\n"
+	),
+	// Lines carried on by an operator go a level in
+	("val s = a +\nb +\nc", "val s = a +\n\tb +\n\tc\n"),
+	("val s = list\n.map(f)\n.first()", "val s = list\n\t.map(f)\n\t.first()\n")
+];
+
+#[test]
+fn code_gets_the_documented_layout() {
+	for (source, expected) in LAYOUTS {
+		let formatted = format(source).unwrap_or_else(|errors| panic!("{:?} does not parse: {:?}", source, errors));
+		assert_eq!(formatted, *expected, "formatting {:?}", source);
+	}
+}
+
+#[test]
+fn long_argument_lists_go_one_per_line() {
+	let arguments: Vec<String> = (0..8).map(|index| format!("argument_number_{}", index)).collect();
+	let source = format!("fn f() {{\n\tval result = call({})\n}}", arguments.join(", "));
+	let formatted = format(&source).unwrap();
+	let mut expected = "fn f() {\n\tval result = call(\n".to_string();
+	for (index, argument) in arguments.iter().enumerate() {
+		let comma = if index + 1 < arguments.len() { "," } else { "" };
+		expected.push_str(&format!("\t\t{}{}\n", argument, comma));
+	}
+	expected.push_str("\t)\n}\n");
+	assert_eq!(formatted, expected);
+
+	// Arguments that are too long themselves are wrapped in turn
+	let inner = format!("inner({})", arguments.join(", "));
+	let formatted = format(&format!("val x = outer({}, 1)", inner)).unwrap();
+	assert!(formatted.lines().all(|line| line.replace('\t', "    ").len() <= MAX_WIDTH), "{}", formatted);
+	assert!(formatted.contains("\tinner(\n\t\targument_number_0,\n"), "{}", formatted);
+}
+
+#[test]
+fn formatting_keeps_tokens_and_is_idempotent() {
+	for (source, _) in LAYOUTS {
+		let formatted = format(source).unwrap();
+		assert_eq!(tokens(&formatted), tokens(source), "tokens of {:?}", source);
+		assert_eq!(format(&formatted).unwrap(), formatted, "formatting {:?} twice", source);
+	}
+}
+
+#[test]
+fn files_that_do_not_parse_are_not_formatted() {
+	assert!(format("fn f() {\n\tg(\n}").is_err());
+	assert!(format("val s = \"open").is_err());
+}
+
+fn tokens(source: &str) -> Vec<String> {
+	let tokens = Lexer::new(source.to_string()).lex().unwrap().clone();
+	tokens.iter().map(|token| source[token.span.start..token.span.end].to_string()).collect()
+}
@@ -0,0 +1,105 @@
+// Edits reparsed in part must leave the same tree, spans and all, as parsing
+// the edited text afresh would.
+
+use glee::ast::Program;
+use glee::lexer::lexer::Lexer;
+use glee::parser::incremental::{IncrementalParser, Reparse, TextEdit};
+use glee::parser::parser::Parser;
+
+const SOURCE: &str = "/// The area of a rectangle
+fn area(w: Int, h: Int): Int {
+	val product = w * h
+	return product
+}
+
+class Box {
+	val width = 1
+	fn grow(by: Int): Int {
+		return width + by
+	}
+}
+
+val total = area(2, 3)
+
+fn after() {
+	println(total)
+}
+";
+
+// The tree as text, without node ids, which an incremental parse hands out
+// from where the last one left off.
+fn tree(program: &Program) -> String {
+	let text = format!("{:#?}", program.stmts);
+	let mut tree = String::new();
+	let mut rest = text.as_str();
+	while let Some(at) = rest.find("NodeId(") {
+		tree.push_str(&rest[..at]);
+		rest = &rest[at..];
+		rest = &rest[rest.find(')').unwrap() + 1..];
+	}
+	tree.push_str(rest);
+	tree
+}
+
+fn fresh(text: &str) -> Option<String> {
+	let tokens = Lexer::new(text.to_string()).lex().ok()?.clone();
+	Parser::new(tokens).parse().ok().map(|program| tree(&program))
+}
+
+// Replaces the first `old` after `after` with `new`, checking the edit is
+// reparsed as `expected` and that every span after it moved as it should.
+fn edit(parser: &mut IncrementalParser, after: &str, old: &str, new: &str, expected: Reparse) {
+	let source = parser.source().to_string();
+	let from = source.find(after).unwrap();
+	let start = from + source[from..].find(old).unwrap();
+	let edit = TextEdit { start, end: start + old.len(), text: new.to_string() };
+	assert_eq!(parser.apply(edit), Ok(expected), "replacing {:?} with {:?}", old, new);
+	let mut edited = source.clone();
+	edited.replace_range(start..start + old.len(), new);
+	assert_eq!(parser.source(), edited);
+	assert_eq!(parser.program().map(tree), fresh(&edited), "replacing {:?} with {:?}", old, new);
+}
+
+#[test]
+fn edits_inside_a_body_reparse_only_the_body() {
+	let mut parser = IncrementalParser::new(SOURCE.to_string());
+	edit(&mut parser, "val product", "w * h", "w * h\n\tprintln(product)\n\tval more = 2", Reparse::Body);
+	edit(&mut parser, "fn grow", "width + by", "width +\n\t\tby * 2", Reparse::Body);
+	edit(&mut parser, "fn after", "total", "total + 1", Reparse::Body);
+}
+
+#[test]
+fn edits_inside_a_member_reparse_only_the_member() {
+	let mut parser = IncrementalParser::new(SOURCE.to_string());
+	edit(&mut parser, "fn grow", "by: Int", "by: Int,\n\t\ttimes: Int", Reparse::Member);
+	edit(&mut parser, "val width", "=", "= 4 +", Reparse::Member);
+}
+
+#[test]
+fn edits_inside_a_declaration_reparse_only_the_declaration() {
+	let mut parser = IncrementalParser::new(SOURCE.to_string());
+	edit(&mut parser, "fn area", "h: Int", "h: Int, d: Int", Reparse::Declaration);
+	edit(&mut parser, "val total", "2, 3", "20,\n\t3", Reparse::Declaration);
+	edit(&mut parser, "class Box", "Box", "Crate", Reparse::Declaration);
+}
+
+#[test]
+fn edits_that_change_the_construct_reparse_the_file() {
+	let mut parser = IncrementalParser::new(SOURCE.to_string());
+	// Across two declarations
+	edit(&mut parser, "return product", "}\n\nclass Box {", "}\n\nclass Bag {", Reparse::Full);
+	// Inside a body, but closing it and starting another function
+	edit(&mut parser, "val product", "w * h", "w * h\n}\n\nfn extra() {", Reparse::Full);
+	// Into and back out of a syntax error
+	edit(&mut parser, "val total", "area(", "area((", Reparse::Full);
+	assert!(parser.program().is_none() && !parser.errors().is_empty());
+	edit(&mut parser, "val total", "area((", "area(", Reparse::Full);
+	assert!(parser.errors().is_empty());
+}
+
+#[test]
+fn edits_outside_the_source_are_refused() {
+	let mut parser = IncrementalParser::new(SOURCE.to_string());
+	assert!(parser.apply(TextEdit { start: 5, end: SOURCE.len() + 1, text: String::new() }).is_err());
+	assert_eq!(parser.source(), SOURCE);
+}
@@ -0,0 +1,223 @@
+// What programs print when run, and how they stop when something goes wrong.
+
+mod common;
+
+use common::checked;
+use glee::interpreter::native::Runtime;
+use glee::interpreter::{self, Uncaught};
+
+// Checks and runs `source`, returning what it printed and what stopped it.
+fn run(source: &str) -> (String, Option<Uncaught>) {
+	let program = checked(source);
+	let mut out = Vec::new();
+	let uncaught = interpreter::run(&program, &Runtime::new(), None, &[], &mut out).err();
+	(String::from_utf8(out).unwrap(), uncaught)
+}
+
+fn output(source: &str) -> String {
+	let (out, uncaught) = run(source);
+	assert_eq!(uncaught, None, "{}", out);
+	out
+}
+
+#[test]
+fn top_level_code_runs_before_main() {
+	let source = "val limit = 3
+println(\"starting\")
+
+fn fib(n: Int): Int = if n < 2 { n } else { fib(n - 1) + fib(n - 2) }
+
+fn main() {
+	mut total = 0
+	outer: for i in 0..10 {
+		if i == limit { break outer }
+		total += fib(i + 10)
+	}
+	println(\"total: ${total}, ${7 / 2}, ${7 / 2.0}\")
+	val words = [\"a\", \"b\"]
+	for (i, word) of words {
+		print(word + i)
+	}
+	println(\"\")
+	println(words)
+}";
+	assert_eq!(output(source), "starting\ntotal: 288, 3, 3.5\na0b1\n[a, b]\n");
+}
+
+#[test]
+fn closures_keep_the_variables_they_use() {
+	let source = "fn counter(): () -> Int {
+	mut count = 0
+	return () => {
+		count += 1
+		return count
+	}
+}
+
+fn main() {
+	val next = counter()
+	next()
+	println(next())
+	println([1, 2, 3].size)
+}";
+	assert_eq!(output(source), "2\n3\n");
+}
+
+#[test]
+fn closures_made_in_a_loop_keep_that_iteration() {
+	let source = "fn main() {
+	val doubles: (() -> Int)[] = [() => 0, () => 0, () => 0]
+	val counters: (() -> Int)[] = [() => 0, () => 0]
+	for i in 0..3 {
+		val doubled = i * 2
+		doubles[i] = () => doubled
+		mut count = i * 10
+		if i < 2 {
+			counters[i] = () => {
+				count += 1
+				return count
+			}
+		}
+	}
+	print(doubles[0]() + \" \" + doubles[2]() + \" \")
+	counters[0]()
+	print(counters[0]() + \" \" + counters[1]())
+	fn even(n: Int): Bool = if n == 0 { true } else { odd(n - 1) }
+	fn odd(n: Int): Bool = if n == 0 { false } else { even(n - 1) }
+	println(\" \" + even(4))
+}";
+	assert_eq!(output(source), "0 4 2 11 true\n");
+}
+
+#[test]
+fn methods_are_dispatched_on_the_class_of_the_object() {
+	let source = "abstract class Animal {
+	val name: String
+	constructor(name: String) {
+		this.name = name
+	}
+	fn speak(): String = \"...\"
+	fn describe(): String = name + \" says \" + speak()
+}
+
+class Dog : Animal {
+	constructor(name: String) {
+		super(name)
+	}
+	override fn speak(): String = \"woof\"
+}
+
+struct Size {
+	width: Int
+	height: Int = 1
+}
+
+enum Color { Red, Green }
+
+fn main() {
+	val animal: Animal = Dog(\"Rex\")
+	println(animal.describe())
+	println(animal is Dog)
+	println(Size(2))
+	println(Size(2) == Size(2, 1))
+	println(Color.Green)
+}";
+	assert_eq!(output(source), "Rex says woof\ntrue\nSize(width=2, height=1)\ntrue\nGreen\n");
+}
+
+#[test]
+fn inherited_methods_come_from_the_nearest_class_or_interface() {
+	let source = "interface Shape {
+	fn area(): Int
+	fn describe(): String = \"area \" + area()
+}
+
+class Rect : Shape {
+	val width: Int
+	val height: Int
+	constructor(width: Int, height: Int) {
+		this.width = width
+		this.height = height
+	}
+	override fn area(): Int = width * height
+}
+
+class Square : Rect {
+	constructor(side: Int) {
+		super(side, side)
+	}
+	override fn describe(): String = \"square, \" + super.describe()
+}
+
+fn main() {
+	val shapes: Shape[] = [Rect(2, 3), Square(4)]
+	for shape in shapes {
+		println(shape.describe() + \" \" + (shape is Shape) + \" \" + (shape is Square))
+	}
+}";
+	assert_eq!(output(source), "area 6 true false\nsquare, area 16 true true\n");
+}
+
+#[test]
+fn errors_can_be_caught_or_stop_the_program() {
+	let source = "fn divide(a: Int, b: Int): Int = a / b
+
+fn main() {
+	try {
+		divide(1, 0)
+	} catch (e) {
+		println(\"caught: \" + e)
+	} finally {
+		println(\"finally\")
+	}
+	throw \"gave up\"
+}";
+	let (out, uncaught) = run(source);
+	assert_eq!(out, "caught: Division by zero\nfinally\n");
+	let uncaught = uncaught.unwrap();
+	assert_eq!(uncaught.message, "Uncaught exception: gave up");
+	assert_eq!((uncaught.span.line, uncaught.span.column), (11, 2));
+}
+
+#[test]
+fn uncaught_exceptions_carry_the_calls_they_went_through() {
+	let source = "class Account {
+	mut balance = 0
+	fn withdraw(amount: Int) {
+		if amount > balance {
+			throw \"insufficient funds\"
+		}
+		balance -= amount
+	}
+}
+
+fn pay(account: Account) {
+	account.withdraw(10)
+}
+
+fn main() {
+	try {
+		pay(Account())
+	} catch (e: Int) {
+		println(\"not this one\")
+	} finally {
+		println(\"finally\")
+	}
+}";
+	let (out, uncaught) = run(source);
+	assert_eq!(out, "finally\n");
+	let trace: Vec<(String, i64)> = uncaught.unwrap().trace.into_iter().map(|frame| (frame.function, frame.span.line)).collect();
+	assert_eq!(trace, [("Account.withdraw".to_string(), 5), ("pay".to_string(), 12), ("main".to_string(), 17)]);
+}
+
+#[test]
+fn stack_traces_name_each_call_and_where_it_is() {
+	let source = "fn countdown(n: Int): Int = if n == 0 { 1 / n } else { countdown(n - 1) }
+
+fn main() {
+	countdown(3)
+}";
+	let program = checked(source);
+	let uncaught = interpreter::run(&program, &Runtime::new(), None, &[], &mut Vec::new()).unwrap_err();
+	assert_eq!(uncaught.diagnostic(&["main.gl".to_string()]).notes, ["at countdown (main.gl:1:41)", "at countdown (main.gl:1:56), 3 times", "at main (main.gl:4:2)"]);
+}
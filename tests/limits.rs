@@ -0,0 +1,174 @@
+// What hosts running programs they do not trust limit them to, and how a
+// program that goes over a limit is stopped.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::checked_with;
+use glee::bytecode::compiler::compile;
+use glee::bytecode::vm;
+use glee::driver::runtime;
+use glee::interpreter::gc::Settings;
+use glee::interpreter::limits::{Limit, RuntimeLimits};
+use glee::interpreter::native::Runtime;
+use glee::interpreter::{self, Uncaught};
+use glee::sema::hir::Program;
+
+fn checked(source: &str) -> Program {
+	checked_with(&runtime(), source).unwrap()
+}
+
+fn limited(limits: RuntimeLimits) -> Runtime {
+	let mut runtime = runtime();
+	runtime.limits = limits;
+	runtime
+}
+
+// How the program stopped on each engine, and what it printed first.
+fn run(source: &str, limits: RuntimeLimits) -> [(Uncaught, String); 2] {
+	interpreter::with_stack(|| {
+		let program = checked(source);
+		let runtime = limited(limits);
+		let mut out = Vec::new();
+		let interpreted = interpreter::run(&program, &runtime, None, &[], &mut out).unwrap_err();
+		let interpreted = (interpreted, String::from_utf8(out).unwrap());
+		let mut out = Vec::new();
+		let compiled = vm::run(&compile(&program, None), &runtime, &[], Settings::default(), &mut out).unwrap_err();
+		[interpreted, (compiled, String::from_utf8(out).unwrap())]
+	})
+}
+
+#[test]
+fn endless_loops_run_out_of_instructions_and_cannot_catch_that() {
+	let source = "fn main() {
+	mut val n = 0
+	try {
+		while (true) {
+			n += 1
+		}
+	} catch (e) {
+		println(\"caught \" + e)
+	} finally {
+		println(\"finally\")
+	}
+}";
+	for (uncaught, out) in run(source, RuntimeLimits { max_instructions: Some(10_000), ..Default::default() }) {
+		assert_eq!(uncaught.message, "Limit exceeded: more than 10000 instructions");
+		assert_eq!(uncaught.limit, Some(Limit::Instructions(10_000)));
+		assert_eq!(uncaught.trace[0].function, "main");
+		assert_eq!(out, "");
+	}
+}
+
+#[test]
+fn calls_can_nest_only_as_deep_as_allowed() {
+	let source = "fn down(n: Int): Int {
+	return down(n + 1) + 1
+}
+
+fn main() {
+	println(down(0))
+}";
+	for (uncaught, _) in run(source, RuntimeLimits { max_call_depth: Some(50), ..Default::default() }) {
+		assert_eq!(uncaught.message, "Limit exceeded: more than 50 calls deep");
+		assert_eq!(uncaught.limit, Some(Limit::CallDepth(50)));
+	}
+
+	// Within the limit, calls run as they would without it
+	let program = checked("fn down(n: Int): Int {\n\treturn if (n == 0) 0 else down(n - 1) + 1\n}\n\nfn main() {\n\tprintln(down(40))\n}");
+	let mut out = Vec::new();
+	let runtime = limited(RuntimeLimits { max_call_depth: Some(50), ..Default::default() });
+	vm::run(&compile(&program, None), &runtime, &[], Settings::default(), &mut out).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), "40\n");
+}
+
+#[test]
+fn programs_are_stopped_when_their_time_is_up_even_while_they_wait() {
+	let limits = RuntimeLimits { wall_clock: Some(Duration::from_millis(50)), ..Default::default() };
+	let started = Instant::now();
+	// The VM's event loop has nothing to run until the timer is up, and the
+	// interpreter sleeps
+	for (uncaught, out) in run("async fn main() {\n\tprintln(\"waiting\")\n\tawait sleep(60000)\n\tprintln(\"woke\")\n}", limits) {
+		assert_eq!(uncaught.message, "Limit exceeded: running for more than 50ms");
+		assert_eq!(uncaught.limit, Some(Limit::WallClock(Duration::from_millis(50))));
+		assert_eq!(out, "waiting\n");
+	}
+	assert!(started.elapsed() < Duration::from_secs(10), "{:?}", started.elapsed());
+}
+
+#[test]
+fn the_vm_measures_the_heap_the_program_can_reach() {
+	let source = "fn main() {
+	mut val kept: Any = []
+	while (true) {
+		kept = [kept, \"more\", 1, 2]
+	}
+}";
+	let program = compile(&checked(source), None);
+	let runtime = limited(RuntimeLimits { max_heap_bytes: Some(1_000_000), ..Default::default() });
+	// Freeing what it kept goes as deep as the arrays nest
+	let uncaught = interpreter::with_stack(|| vm::run(&program, &runtime, &[], Settings::default(), &mut Vec::new()).unwrap_err());
+	assert_eq!(uncaught.message, "Limit exceeded: more than 1000000 bytes of memory");
+	assert_eq!(uncaught.limit, Some(Limit::HeapBytes(1_000_000)));
+
+	// What it makes and drops again does not count
+	let source = "fn main() {
+	for (i in 0..100000) {
+		val dropped = [i, i + 1, \"gone\"]
+	}
+	println(\"done\")
+}";
+	let mut out = Vec::new();
+	vm::run(&compile(&checked(source), None), &runtime, &[], Settings::default(), &mut out).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), "done\n");
+}
+
+#[test]
+fn regex_searches_count_what_they_keep_against_the_memory_limit() {
+	let source = "fn main() {
+	val text = \"ab\".repeat(5000)
+	// Searches from one place after another let go of the places behind them
+	println(regex.findAll(\"ba\", text).size)
+	try {
+		println(regex.test(\"(?:[ab]{1,50})*c\", text))
+	} catch (e) {
+		println(\"caught \" + e)
+	}
+}";
+	for (uncaught, out) in run(source, RuntimeLimits { max_heap_bytes: Some(100_000), ..Default::default() }) {
+		assert_eq!(uncaught.message, "Limit exceeded: more than 100000 bytes of memory");
+		assert_eq!(uncaught.limit, Some(Limit::HeapBytes(100_000)));
+		assert_eq!(out, "4999\n");
+	}
+
+	// Without a limit, the search goes on to the end
+	let mut out = Vec::new();
+	interpreter::run(&checked(source), &runtime(), None, &[], &mut out).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), "4999\nfalse\n");
+}
+
+#[test]
+fn strings_and_arrays_that_grow_at_once_are_counted_as_they_are_made() {
+	let doubled = "fn main() {
+	mut s = \"x\"
+	try {
+		for (i in 0..30) {
+			s = s + s
+		}
+	} catch (e) {
+		println(\"caught \" + e)
+	}
+	println(s.length)
+}";
+	let made = ["\"x\".repeat(1000000000).length", "\"x\".padStart(1000000000, \"ab\").length", "(0..1000000000).toArray().size"]
+		.map(|made| format!("fn main() {{\n\ttry {{\n\t\tprintln({})\n\t}} catch (e) {{\n\t\tprintln(\"caught \" + e)\n\t}}\n}}", made));
+	let limits = RuntimeLimits { max_heap_bytes: Some(1_000_000), ..Default::default() };
+	for source in made.iter().map(String::as_str).chain([doubled]) {
+		for (uncaught, out) in run(source, limits) {
+			assert_eq!(uncaught.message, "Limit exceeded: more than 1000000 bytes of memory", "{}", source);
+			assert_eq!(uncaught.limit, Some(Limit::HeapBytes(1_000_000)));
+			assert_eq!(out, "");
+		}
+	}
+}
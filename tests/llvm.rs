@@ -0,0 +1,107 @@
+// The native target: programs lowered to LLVM IR and built into executables
+// that run as the interpreter runs them, where `llc` and a C compiler are
+// there to build them with.
+
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use common::checked;
+use glee::interpreter;
+use glee::interpreter::native::Runtime;
+use glee::llvm;
+use glee::llvm::lower::lower;
+
+// Builds `source` into an executable and runs it, returning what it printed
+// to stdout and stderr and whether it succeeded, or None without `llc`.
+fn native(name: &str, source: &str) -> Option<(String, String, bool)> {
+	Command::new("llc").arg("--version").output().ok()?;
+	let path = std::env::temp_dir().join(format!("dotfun-test-{}-{}", name, std::process::id()));
+	llvm::build(&lower(&checked(source), None).unwrap(), &path).unwrap();
+	let output = Command::new(&path).output().unwrap();
+	fs::remove_file(&path).unwrap();
+	Some((String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap(), output.status.success()))
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn executables_run_as_the_interpreter_runs_programs() {
+	let source = "extern \"libm.so.6\" fn pow(x: Float, y: Float): Float
+extern \"libc.so.6\" fn strchr(text: String, c: Int): String?
+
+val greeting = \"hello\"
+mut val total = 0
+
+fn fib(n: Int): Int = if (n < 2) n else fib(n - 1) + fib(n - 2)
+
+fn first(): Int {
+	try {
+		return 1
+	} finally {
+		println(\"finally\")
+	}
+}
+
+fn describe(x: Int | String | Bool): String {
+	switch (x) {
+		case Int:
+			return \"int\"
+		case \"hi\":
+			return \"greeting\"
+		case String:
+			return \"string\"
+		case Bool:
+			return \"bool\"
+	}
+}
+
+fn main() {
+	println(greeting + \", \" + fib(15))
+	println(1.0 / 3.0)
+	println(10000000000000000.0)
+	println(0.00001)
+	println(pow(2.0, 10.0))
+	println(strchr(greeting, 108))
+	println(first())
+	for (i in 0..10) {
+		if (i % 2 == 0) {
+			continue
+		}
+		total += i
+	}
+	println(total)
+	mut val text = \"\"
+	while (text.length < 20000) {
+		text = text + \"ab\"
+	}
+	println(text.length)
+	println(describe(1) + \" \" + describe(\"hi\") + \" \" + describe(\"yo\") + \" \" + describe(false))
+	try {
+		println(total / 0)
+	} catch (e) {
+		println(\"caught \" + e)
+	}
+	val maybe: String? = null
+	println(maybe ?? \"none\")
+	println(\"ell\" in greeting && 3 in 0..10)
+	throw \"boom\"
+}";
+	let program = checked(source);
+	let mut out = Vec::new();
+	let uncaught = interpreter::run(&program, &Runtime::new(), None, &[], &mut out).unwrap_err();
+	let Some((stdout, stderr, success)) = native("run", source) else {
+		return;
+	};
+	assert_eq!(stdout, String::from_utf8(out).unwrap());
+	assert_eq!(stderr, format!("error: {}\n", uncaught.message));
+	assert!(!success);
+}
+
+#[test]
+fn what_the_native_target_cannot_compile_is_reported_where_it_is_used() {
+	let program = checked("fn unused() {\n\tprintln([1])\n}\n\nfn main() {\n\tval f = (x: Int) => x\n\tprintln(f(1))\n}");
+	let errors = lower(&program, None).err().unwrap();
+	let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+	assert_eq!(messages, ["The native target cannot compile lambdas yet", "The native target cannot compile calls to local functions and lambdas yet"]);
+}
@@ -0,0 +1,231 @@
+// What `dotfun-lsp` tells an editor about the documents it has open.
+
+use std::fs;
+use std::io::Cursor;
+
+use glee::driver::json::{self, Json};
+use glee::driver::lsp::serve;
+
+// Frames each message as the protocol does, numbering the requests, the
+// ones with a method other than a notification's, from 1.
+fn messages_to(messages: &[(&str, Json)]) -> Vec<u8> {
+	let mut input = Vec::new();
+	let mut id = 0i64;
+	for (method, params) in messages {
+		let mut message = Json::object([("jsonrpc", "2.0".into()), ("method", (*method).into()), ("params", params.clone())]);
+		if !method.starts_with("textDocument/did") && !matches!(*method, "initialized" | "exit") {
+			id += 1;
+			if let Json::Object(object) = &mut message {
+				object.insert("id".to_string(), id.into());
+			}
+		}
+		let text = message.to_string();
+		input.extend(format!("Content-Length: {}\r\n\r\n{}", text.len(), text).into_bytes());
+	}
+	input
+}
+
+// The messages in what was sent back.
+fn messages(output: &[u8]) -> Vec<Json> {
+	let mut text = String::from_utf8(output.to_vec()).unwrap();
+	let mut messages = Vec::new();
+	while let Some((header, rest)) = text.split_once("\r\n\r\n") {
+		let length: usize = header.trim_start_matches("Content-Length: ").parse().unwrap();
+		messages.push(json::parse(&rest[..length]).unwrap());
+		text = rest[length..].to_string();
+	}
+	messages
+}
+
+// Serves a session of `script` between `initialize` and `shutdown`, and
+// what it exited with and sent back.
+fn session(script: &[(&str, Json)]) -> (u8, Vec<Json>) {
+	let mut all = vec![("initialize", Json::object([("capabilities", Json::object([]))])), ("initialized", Json::object([]))];
+	all.extend(script.iter().cloned());
+	all.extend([("shutdown", Json::Null), ("exit", Json::Null)]);
+	let mut output = Vec::new();
+	let code = serve(Box::new(Cursor::new(messages_to(&all))), Box::new(&mut output));
+	(code, messages(&output))
+}
+
+fn uri(name: &str) -> String {
+	format!("file://{}", std::env::temp_dir().join(format!("dotfun-lsp-{}-{}.gl", name, std::process::id())).display())
+}
+
+fn open(uri: &str, text: &str) -> (&'static str, Json) {
+	let document = Json::object([("uri", uri.into()), ("languageId", "dotfun".into()), ("version", 1i64.into()), ("text", text.into())]);
+	("textDocument/didOpen", Json::object([("textDocument", document)]))
+}
+
+fn position(line: i64, character: i64) -> Json {
+	Json::object([("line", line.into()), ("character", character.into())])
+}
+
+fn change(uri: &str, start: Json, end: Json, text: &str) -> (&'static str, Json) {
+	let change = Json::object([("range", Json::object([("start", start), ("end", end)])), ("text", text.into())]);
+	let document = Json::object([("uri", uri.into()), ("version", 2i64.into())]);
+	("textDocument/didChange", Json::object([("textDocument", document), ("contentChanges", vec![change].into())]))
+}
+
+// The diagnostics published, in order, each as the messages of them.
+fn published(messages: &[Json]) -> Vec<Vec<&Json>> {
+	messages
+		.iter()
+		.filter(|message| message.get("method").as_str() == Some("textDocument/publishDiagnostics"))
+		.map(|message| message.get("params").get("diagnostics").items().iter().collect())
+		.collect()
+}
+
+#[test]
+fn editors_are_told_what_they_can_ask() {
+	let (code, messages) = session(&[]);
+	assert_eq!(code, 0);
+	let initialized = messages.iter().find(|message| message.get("id").as_i64() == Some(1)).unwrap();
+	let capabilities = initialized.get("result").get("capabilities");
+	assert_eq!(capabilities.get("textDocumentSync").get("change").as_i64(), Some(2));
+	assert_eq!(initialized.get("result").get("serverInfo").get("name").as_str(), Some("dotfun-lsp"));
+}
+
+#[test]
+fn errors_are_published_as_documents_change() {
+	let uri = uri("changes");
+	let (code, messages) = session(&[
+		open(&uri, "fn main() {\n\tval word = \"é\"\n\tval n: Int = word\n\tprintln(n)\n}\n"),
+		change(&uri, position(2, 14), position(2, 18), "word.length"),
+		change(&uri, position(1, 12), position(1, 15), "1")
+	]);
+	assert_eq!(code, 0);
+	let published = published(&messages);
+	assert_eq!(published.len(), 3);
+	let error = published[0][0];
+	assert_eq!(error.get("severity").as_i64(), Some(1));
+	assert_eq!(error.get("source").as_str(), Some("dotfun"));
+	let range = error.get("range");
+	assert_eq!((range.get("start").get("line").as_i64(), range.get("start").get("character").as_i64()), (Some(2), Some(14)));
+	assert!(published[1].is_empty());
+	assert!(published[2][0].get("message").as_str().unwrap().contains("length"));
+}
+
+#[test]
+fn names_are_highlighted_by_what_they_refer_to() {
+	let uri = uri("tokens");
+	let text = "class Counter {\n\tmut count = 0\n\tfn add(n: Int) {\n\t\tcount += n\n\t}\n}\n\nval counter = Counter()\ncounter.add(2)\n";
+	let tokens = ("textDocument/semanticTokens/full", Json::object([("textDocument", Json::object([("uri", uri.as_str().into())]))]));
+	let (_, messages) = session(&[open(&uri, text), tokens]);
+	let legend = messages[0].get("result").get("capabilities").get("semanticTokensProvider").get("legend");
+	let data = messages.iter().find(|message| message.get("id").as_i64() == Some(2)).unwrap().get("result").get("data").items();
+	let lines: Vec<&str> = text.lines().collect();
+	let (mut line, mut character) = (0, 0);
+	let mut tokens = Vec::new();
+	for token in data.chunks(5) {
+		let [delta, start, length, ty, modifiers] = [0, 1, 2, 3, 4].map(|at| token[at].as_i64().unwrap() as usize);
+		character = if delta == 0 { character + start } else { start };
+		line += delta;
+		let names = legend.get("tokenModifiers").items();
+		let modifiers: Vec<&str> = (0..names.len()).filter(|bit| modifiers >> bit & 1 == 1).map(|bit| names[bit].as_str().unwrap()).collect();
+		let ty = legend.get("tokenTypes").items()[ty].as_str().unwrap();
+		tokens.push((&lines[line][character..character + length], ty, modifiers.join(" ")));
+	}
+	assert_eq!(tokens, [
+		("class", "keyword", String::new()),
+		("Counter", "class", "declaration".to_string()),
+		("mut", "keyword", String::new()),
+		("count", "property", "declaration".to_string()),
+		("fn", "keyword", String::new()),
+		("add", "method", "declaration".to_string()),
+		("n", "parameter", "declaration readonly".to_string()),
+		("count", "property", String::new()),
+		("n", "parameter", "readonly".to_string()),
+		("val", "keyword", String::new()),
+		("counter", "variable", "declaration readonly".to_string()),
+		("Counter", "class", String::new()),
+		("counter", "variable", "readonly".to_string()),
+		("add", "method", String::new())
+	]);
+}
+
+// Where the locations in an answer are, as the file each is in and the
+// line and character of its start.
+fn locations(answer: &Json) -> Vec<(String, i64, i64)> {
+	answer
+		.get("result")
+		.items()
+		.iter()
+		.map(|location| {
+			let file = location.get("uri").as_str().unwrap().rsplit('/').next().unwrap().to_string();
+			let start = location.get("range").get("start");
+			(file, start.get("line").as_i64().unwrap(), start.get("character").as_i64().unwrap())
+		})
+		.collect()
+}
+
+#[test]
+fn names_lead_to_their_declarations_and_uses_across_files() {
+	let root = std::env::temp_dir().join(format!("dotfun-lsp-project-{}", std::process::id()));
+	fs::create_dir_all(root.join("src/shapes")).unwrap();
+	fs::write(root.join("dotfun.toml"), "[package]\nname = \"shapes\"\nversion = \"0.1.0\"\n").unwrap();
+	fs::write(root.join("src/shapes/area.gl"), "package shapes\n\nfn area(side: Int): Int = side * side\nfn area(side: Float): Float = side * side\n").unwrap();
+	let main = "import shapes.area\n\nfn main() {\n\tprintln(area(2))\n\tprintln(area(1.5))\n\tprintln(area(3))\n}\n";
+	fs::write(root.join("src/main.gl"), main).unwrap();
+	let uri = format!("file://{}", root.join("src/main.gl").display());
+	let at = |line: i64, character: i64, declaration: bool| {
+		let context = Json::object([("includeDeclaration", declaration.into())]);
+		Json::object([("textDocument", Json::object([("uri", uri.as_str().into())])), ("position", position(line, character)), ("context", context)])
+	};
+	let (_, messages) = session(&[
+		open(&uri, main),
+		("textDocument/definition", at(0, 15, false)),
+		("textDocument/definition", at(4, 10, false)),
+		("textDocument/references", at(3, 10, false)),
+		("textDocument/references", at(3, 10, true))
+	]);
+	fs::remove_dir_all(&root).unwrap();
+	let answer = |id: i64| messages.iter().find(|message| message.get("id").as_i64() == Some(id)).unwrap();
+	let place = |file: &str, line: i64, character: i64| (file.to_string(), line, character);
+	// Imports name every overload, and calls the one they call
+	assert_eq!(locations(answer(2)), [place("area.gl", 2, 3), place("area.gl", 3, 3)]);
+	assert_eq!(locations(answer(3)), [place("area.gl", 3, 3)]);
+	assert_eq!(locations(answer(4)), [place("main.gl", 0, 14), place("main.gl", 3, 9), place("main.gl", 5, 9)]);
+	assert_eq!(locations(answer(5))[0], place("area.gl", 2, 3));
+}
+
+#[test]
+fn hovering_shows_signatures_docs_and_types() {
+	let uri = uri("hover");
+	let text = "/// How far apart `a` and `b` are.\nfn distance(a: Int, b: Int): Int = b - a\n\n";
+	let text = format!("{}fn main() {{\n\tval gap = distance(1, 4)\n\tprintln(gap * 2.5)\n}}\n", text);
+	let at = |line: i64, character: i64| Json::object([("textDocument", Json::object([("uri", uri.as_str().into())])), ("position", position(line, character))]);
+	let (_, messages) = session(&[
+		open(&uri, &text),
+		("textDocument/hover", at(4, 13)),
+		("textDocument/hover", at(4, 6)),
+		("textDocument/hover", at(5, 13)),
+		("textDocument/hover", at(3, 0))
+	]);
+	let hover = |id: i64| {
+		let result = messages.iter().find(|message| message.get("id").as_i64() == Some(id)).unwrap().get("result");
+		let start = result.get("range").get("start");
+		(result.get("contents").get("value").as_str().unwrap().to_string(), start.get("line").as_i64().unwrap(), start.get("character").as_i64().unwrap())
+	};
+	assert_eq!(hover(2), ("```dotfun\nfn distance(a: Int, b: Int): Int\n```\n\nHow far apart `a` and `b` are.".to_string(), 4, 11));
+	assert_eq!(hover(3), ("```dotfun\nval gap: Int\n```".to_string(), 4, 5));
+	// Away from names, the innermost expression
+	assert_eq!(hover(4), ("```dotfun\nFloat\n```".to_string(), 5, 9));
+	let nothing = messages.iter().find(|message| message.get("id").as_i64() == Some(5)).unwrap();
+	assert!(nothing.get("result").is_null());
+}
+
+#[test]
+fn requests_it_cannot_answer_are_errors() {
+	let (code, messages) = session(&[("workspace/symbol", Json::object([("query", "main".into())]))]);
+	assert_eq!(code, 0);
+	let answer = messages.iter().find(|message| message.get("id").as_i64() == Some(2)).unwrap();
+	assert_eq!(answer.get("error").get("code").as_i64(), Some(-32601));
+}
+
+#[test]
+fn leaving_without_shutting_down_is_a_failure() {
+	let input = messages_to(&[("initialize", Json::object([])), ("exit", Json::Null)]);
+	let mut output = Vec::new();
+	assert_eq!(serve(Box::new(Cursor::new(input)), Box::new(&mut output)), 1);
+}
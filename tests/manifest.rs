@@ -0,0 +1,154 @@
+// What `dotfun.toml` and `dotfun.lock` files mean, the errors the ones that
+// make no sense get, how dependencies' files join the program, and the
+// projects `dotfun new` makes.
+
+use std::path::PathBuf;
+use std::{env, fs, process};
+
+use glee::driver::manifest::{Dependency, Manifest, Project, Source};
+use glee::driver::packages::{Lock, Locked, Resolver};
+use glee::driver::scaffold::{self, Template};
+use glee::lexer::lexer::Lexer;
+use glee::parser::parser::Parser;
+use glee::sema::check::check;
+use glee::sema::modules::SourceFile;
+
+#[test]
+fn manifests_fill_in_defaults() {
+	let manifest = Manifest::parse("[package]\nname = \"hello\"\nversion = \"0.1.0\"\n").unwrap();
+	assert_eq!(manifest.name, "hello");
+	assert_eq!(manifest.version, "0.1.0");
+	assert_eq!(manifest.entry, None);
+	assert_eq!(manifest.std, None);
+	assert_eq!(manifest.sources, ["src"]);
+	assert!(manifest.dependencies.is_empty());
+}
+
+#[test]
+fn manifests_read_every_field() {
+	let text = r#"
+		# A project with everything
+		[package]
+		name = "app"
+		version = "1.2.3"
+		entry = "code/start.gl"
+		sources = [
+			"code",
+			'generated', # made by a script
+		]
+		std = 1
+
+		[dependencies]
+		util = { path = "../util" }
+		json = { git = "https://example.com/json.git", rev = "v1.2" }
+		"dotted.name" = { git = "https://example.com/dotted.git" }
+	"#;
+	let manifest = Manifest::parse(text).unwrap();
+	assert_eq!(manifest.entry.as_deref(), Some("code/start.gl"));
+	assert_eq!(manifest.sources, ["code", "generated"]);
+	assert_eq!(manifest.std, Some(1));
+	let git = |url: &str, rev: Option<&str>| Source::Git { url: url.to_string(), rev: rev.map(str::to_string) };
+	assert_eq!(manifest.dependencies, [
+		Dependency { name: "dotted.name".to_string(), source: git("https://example.com/dotted.git", None) },
+		Dependency { name: "json".to_string(), source: git("https://example.com/json.git", Some("v1.2")) },
+		Dependency { name: "util".to_string(), source: Source::Path("../util".to_string()) }
+	]);
+}
+
+#[test]
+fn mistakes_in_manifests_are_explained() {
+	let cases: &[(&str, &str)] = &[
+		("name = \"a\"", "Unknown key 'name'"),
+		("[dependencies]", "Missing the [package] table"),
+		("[package]\nversion = \"1\"", "Missing 'package.name'"),
+		("[package]\nname = \"a b\"\nversion = \"1\"", "'a b' is not a package name; use letters, digits, '_' and '-'"),
+		("[package]\nname = \"a\"\nversion = 1", "'package.version' must be a string, not an integer"),
+		("[package]\nname = \"a\"\nversion = \"1\"\nsource = [\"src\"]", "Unknown key 'package.source'"),
+		("[package]\nname = \"a\"\nversion = \"1\"\nstd = \"1\"", "'package.std' must be an integer, not a string"),
+		("[package]\nname = \"a\"\nversion = \"1\"\nstd = 0", "'package.std' must be a version from 1 up, not 0"),
+		("[package]\nname = \"a\"\nversion = \"1\"\n[dependencies]\nb = \"1.0\"", "Dependency 'b' must be a table like { path = \"../b\" }"),
+		("[package]\nname = \"a\"\nversion = \"1\"\n[dependencies]\nb = {}", "Dependency 'b' needs a 'path' or a 'git'"),
+		("[package]\nname = \"a\"\nversion = \"1\"\n[dependencies]\nb = { path = \"b\", rev = \"x\" }", "'dependencies.b.rev' only goes with 'git'"),
+		("[package]\nname = \"a\"\nname = \"b\"", "line 3: 'name' is set twice"),
+		("[package]\n[package]", "line 2: Table 'package' is defined twice"),
+		("[package]\nname = \"a\" version = \"1\"", "line 2: Expected a line break, found 'v'"),
+		("[package]\nname = \"a", "line 2: Unterminated string"),
+		("[package]\nname = 1.5.2", "line 2: '1.5.2' is not a value dotfun.toml can have")
+	];
+	for (text, expected) in cases {
+		assert_eq!(Manifest::parse(text).unwrap_err(), *expected, "parsing {:?}", text);
+	}
+}
+
+#[test]
+fn lockfiles_read_back_what_was_written() {
+	let mut lock = Lock::default();
+	let locked = |url: &str, rev: Option<&str>, commit: &str| {
+		Locked { url: url.to_string(), rev: rev.map(str::to_string), commit: commit.to_string() }
+	};
+	lock.packages.insert("json-lib".to_string(), locked("https://example.com/json.git", Some("v1"), "608fde2c3a71a4e1bd2c4a5e4c6f0e9a8d7b6c5d"));
+	lock.packages.insert("quoted".to_string(), locked("C:\\repos\\\"q\"", None, "0123abcd0123abcd0123abcd0123abcd0123abcd"));
+	assert_eq!(Lock::parse(&lock.to_string()), Ok(lock));
+	assert_eq!(Lock::parse("[a]\ngit = \"x\""), Err("Missing 'a.commit'".to_string()));
+	// What git would take for an option, or what is not a commit at all
+	for commit in ["--upload-pack=touch", "608fde2c3a71", "é".repeat(20).as_str()] {
+		let expected = format!("'a.commit' must be the 40 hex digits of a commit, not '{}'", commit);
+		assert_eq!(Lock::parse(&format!("[a]\ngit = \"x\"\ncommit = \"{}\"", commit)), Err(expected));
+	}
+}
+
+#[test]
+fn git_is_not_given_options_in_place_of_urls_and_revisions() {
+	let root = env::temp_dir().join(format!("dotfun-resolver-{}", process::id()));
+	fs::create_dir_all(&root).unwrap();
+	let resolver = Resolver::new(&root).unwrap();
+	let url = "--upload-pack=touch /tmp/pwned";
+	assert_eq!(resolver.fetch(url, None), Err(format!("'{}' is not a git URL", url)));
+	let rev = "--output=/tmp/pwned";
+	assert_eq!(resolver.fetch("https://example.com/json.git", Some(rev)), Err(format!("'{}' is not a branch, tag or commit", rev)));
+	fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn dependency_files_join_the_package_named_after_it() {
+	let file = |path: &str, source: &str, package: Option<&str>| {
+		let tokens = Lexer::new(source.to_string()).lex().unwrap().clone();
+		let program = Parser::new(tokens).parse().unwrap();
+		SourceFile { path: path.to_string(), program, package: package.map(str::to_string) }
+	};
+	let files = [
+		file("src/main.gl", "import util.double\nimport util.more.triple\nfn main() {\n\tprintln(double(triple(1)))\n}", None),
+		file("util/src/lib.gl", "fn double(x: Int): Int = x * 2", Some("util")),
+		file("util/src/more.gl", "package util.more\nfn triple(x: Int): Int = x * 3", Some("util"))
+	];
+	let program = check(&files).unwrap();
+	assert_eq!(program.files.iter().map(|file| file.package.as_str()).collect::<Vec<_>>(), ["", "util", "util.more"]);
+	// Imported packages are initialized first
+	assert_eq!(program.init_order, [1, 2, 0]);
+}
+
+#[test]
+fn new_projects_check_and_have_tests() {
+	for template in [Template::Bin, Template::Lib] {
+		let dir = env::temp_dir().join(format!("dotfun-new-{}-{:?}", process::id(), template));
+		let _ = fs::remove_dir_all(&dir);
+		scaffold::new(&dir, template).unwrap();
+		let project = Project::load(&dir).unwrap();
+		assert_eq!(project.manifest.name, dir.file_name().unwrap().to_str().unwrap());
+		assert_eq!(project.entry().is_some(), template == Template::Bin);
+		assert!(scaffold::new(&dir, template).unwrap_err().ends_with("already exists; use 'dotfun init' to make a project of a directory"));
+		assert_eq!(scaffold::init(&dir, template), Err(format!("'{}' is already a project", dir.display())));
+
+		let source = |path: PathBuf| {
+			let tokens = Lexer::new(fs::read_to_string(&path).unwrap()).lex().unwrap().clone();
+			SourceFile { path: path.display().to_string(), program: Parser::new(tokens).parse().unwrap(), package: None }
+		};
+		let files: Vec<SourceFile> = [project.root.join("src"), project.tests().unwrap()]
+			.iter()
+			.flat_map(|dir| fs::read_dir(dir).unwrap().map(|entry| source(entry.unwrap().path())))
+			.collect();
+		let program = check(&files).unwrap();
+		assert_eq!(program.tests.len(), 1);
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -0,0 +1,217 @@
+// Functions programs call that are not written in dotFun: Rust ones hosts
+// register, C ones `extern` declares, and how their arguments, results and
+// errors cross over.
+
+mod common;
+
+use std::cell::RefCell;
+
+use common::checked_with;
+use glee::bytecode::compiler::compile;
+use glee::bytecode::disassemble::disassemble;
+use glee::bytecode::file;
+use glee::bytecode::vm;
+use glee::interpreter::{self, Interpreter};
+use glee::interpreter::gc::{self, Settings};
+use glee::interpreter::native::Runtime;
+use glee::interpreter::value::Value;
+
+fn runtime() -> Runtime {
+	let mut runtime = Runtime::new();
+	runtime
+		.register("math.sqrt", |x: f64| x.sqrt())
+		.register("greet", |name: String| if name.is_empty() { Err("no one to greet".to_string()) } else { Ok(format!("hi {}", name)) })
+		.register("sum", |items: Vec<i64>| items.iter().sum::<i64>())
+		.register("find", |items: Vec<String>, item: String| items.iter().position(|known| *known == item).map(|index| index as i64));
+	runtime
+}
+
+#[test]
+fn programs_call_natives_on_both_engines() {
+	let runtime = runtime();
+	let program = checked_with(
+		&runtime,
+		"fn main() {
+	println(math.sqrt(16.0))
+	println(greet(\"ada\"))
+	try {
+		greet(\"\")
+	} catch (e) {
+		println(\"caught \" + e)
+	}
+	println(sum([1, 2, 3]))
+	println(find([\"a\", \"b\"], \"b\"))
+	println(find([\"a\", \"b\"], \"c\"))
+	val f = math.sqrt
+	println(f(9.0))
+}"
+	)
+	.unwrap();
+	let expected = "4.0\nhi ada\ncaught no one to greet\n6\n1\nnull\n3.0\n";
+	let mut out = Vec::new();
+	interpreter::run(&program, &runtime, None, &[], &mut out).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), expected);
+	let mut out = Vec::new();
+	vm::run(&compile(&program, None), &runtime, &[], Settings::default(), &mut out).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), expected);
+}
+
+#[test]
+fn calls_are_checked_against_what_natives_take() {
+	let runtime = runtime();
+	let errors = checked_with(&runtime, "fn main() {\n\tmath.sqrt(\"four\")\n\tsqrt(4.0)\n}").unwrap_err();
+	let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+	assert_eq!(messages, ["Type mismatch: expected 'Float', found 'String'", "Cannot find 'sqrt' in this scope"]);
+
+	// Called with what it does not take, a native says why
+	let sqrt = runtime.native("math.sqrt").unwrap();
+	assert_eq!(sqrt.arity, Some(1));
+	assert_eq!((sqrt.function)(&[Value::string("four")]).unwrap_err(), "'math.sqrt' takes a 'Float' as argument 1, not a 'String'");
+}
+
+#[test]
+fn programs_run_with_a_runtime_that_lacks_a_native_throw_when_they_call_it() {
+	let program = checked_with(&runtime(), "fn main() {\n\tprintln(\"before\")\n\tprintln(sum([1]))\n}").unwrap();
+	let mut out = Vec::new();
+	let uncaught = vm::run(&compile(&program, None), &Runtime::new(), &[], Settings::default(), &mut out).unwrap_err();
+	assert_eq!(String::from_utf8(out).unwrap(), "before\n");
+	assert_eq!(uncaught.message, "'sum' is a native function this runtime does not have");
+	let mut out = Vec::new();
+	let expected = interpreter::run(&program, &Runtime::new(), None, &[], &mut out).unwrap_err();
+	assert_eq!(uncaught, expected);
+}
+
+thread_local! {
+	static KEPT: RefCell<Vec<Value>> = const { RefCell::new(Vec::new()) };
+}
+
+#[test]
+fn values_only_natives_hold_survive_collecting() {
+	let mut runtime = Runtime::new();
+	runtime.register("keep", |value: Value| KEPT.with_borrow_mut(|kept| kept.push(value)));
+	let program = checked_with(
+		&runtime,
+		"class Node {
+	mut next: Node? = null
+	mut label = \"\"
+}
+
+fn main() {
+	val a = Node()
+	val b = Node()
+	a.next = b
+	b.next = a
+	b.label = \"held\"
+	keep(a)
+	for i in 0..100 {
+		val garbage = Node()
+		garbage.next = garbage
+	}
+}"
+	)
+	.unwrap();
+	// Only the cycle's two nodes, by way of the first's field
+	let label = || match KEPT.with_borrow_mut(|kept| kept.pop()) {
+		Some(Value::Object(a)) => match &a.fields.borrow()[0] {
+			Value::Object(b) => b.fields.borrow()[1].clone(),
+			next => next.clone()
+		},
+		_ => Value::Null
+	};
+	let stress = Settings { stress: true, ..Settings::default() };
+	vm::run(&compile(&program, None), &runtime, &[], stress, &mut Vec::new()).unwrap();
+	assert_eq!(label(), Value::string("held"));
+	let mut out = Vec::new();
+	let mut interpreter = Interpreter::new(&program, &runtime, stress, &mut out);
+	interpreter.init().unwrap();
+	interpreter.call(interpreter::main(&program, None).unwrap(), Vec::new()).unwrap();
+	let stats = gc::stats();
+	drop(interpreter);
+	assert_eq!(label(), Value::string("held"));
+	assert!(stats.collections > 0 && stats.freed > 90, "{:?}", stats);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn extern_functions_call_into_shared_libraries() {
+	let program = checked_with(
+		&Runtime::new(),
+		"extern \"libm.so.6\" fn pow(x: Float, y: Float): Float
+extern \"libc.so.6\" fn labs(n: Int): Int
+extern \"libc.so.6\" fn strlen(text: String): Int
+extern \"libc.so.6\" fn strchr(text: String, c: Int): String?
+extern \"libc.so.6\" fn isdigit(c: Int): Bool
+extern \"libnothing.so\" fn missing(): Void
+
+fn main() {
+	println(pow(2.0, 10.0))
+	println(labs(-42) + strlen(\"hello\"))
+	println(strchr(\"hello\", 108))
+	println(strchr(\"hello\", 122))
+	println(isdigit(55))
+	try {
+		missing()
+	} catch (e) {
+		println(e)
+	}
+}"
+	)
+	.unwrap();
+	let mut out = Vec::new();
+	interpreter::run(&program, &Runtime::new(), None, &[], &mut out).unwrap();
+	let expected = String::from_utf8(out).unwrap();
+	assert!(expected.starts_with("1024.0\n47\nllo\nnull\ntrue\nCannot load 'libnothing.so': "), "{}", expected);
+
+	// Compiled, they are called the same, also once saved and loaded
+	let module = file::load(&file::save(&compile(&program, None))).unwrap();
+	assert!(disassemble(&module).contains("extern \"libc.so.6\" fn labs"));
+	let mut out = Vec::new();
+	vm::run(&module, &Runtime::new(), &[], Settings::default(), &mut out).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), expected);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn extern_functions_hand_out_pointers_for_others_to_take() {
+	let program = checked_with(
+		&Runtime::new(),
+		"extern \"libc.so.6\" fn strdup(text: String): Pointer
+extern \"libc.so.6\" fn atoi(text: Pointer): Int
+extern \"libc.so.6\" fn free(pointer: Pointer?): Void
+extern \"libc.so.6\" fn getenv(name: String): Pointer?
+extern \"libc.so.6\" fn secure_getenv(name: String): Pointer
+
+fn main() {
+	val number = strdup(\"42\")
+	println(atoi(number) + 1)
+	println([number == number, number is Pointer, number is Int, typeof number])
+	println(\"{}\".format([number]).startsWith(\"<pointer 0x\"))
+	free(number)
+	free(null)
+	println(getenv(\"GLEE_TEST_NO_SUCH_VARIABLE\"))
+	try {
+		secure_getenv(\"GLEE_TEST_NO_SUCH_VARIABLE\")
+	} catch (e) {
+		println(e)
+	}
+}"
+	)
+	.unwrap();
+	let expected = "43\n[true, true, false, Pointer]\ntrue\nnull\n'secure_getenv' returned NULL for a Pointer\n";
+	let mut out = Vec::new();
+	interpreter::run(&program, &Runtime::new(), None, &[], &mut out).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), expected);
+	let module = file::load(&file::save(&compile(&program, None))).unwrap();
+	let mut out = Vec::new();
+	vm::run(&module, &Runtime::new(), &[], Settings::default(), &mut out).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), expected);
+
+	// Only C makes them, and they are not numbers
+	let errors = checked_with(&Runtime::new(), "extern \"libc.so.6\" fn atoi(text: Pointer): Int
+
+fn main() {
+	atoi(42)
+}")
+	.unwrap_err();
+	assert_eq!(errors[0].message, "Type mismatch: expected 'Pointer', found 'Int'");
+}
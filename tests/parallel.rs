@@ -0,0 +1,117 @@
+// Work spread over threads comes back in order, and panics on any of them are
+// internal compiler errors like panics anywhere else. Packages checked on
+// threads of their own make the same program and errors however many threads
+// there are, and are only checked again once something they depend on changes.
+
+use glee::diagnostics::ice::{self, Phase};
+use glee::driver::parallel;
+use glee::interpreter;
+use glee::interpreter::native::Runtime;
+use glee::lexer::lexer::Lexer;
+use glee::parser::parser::Parser;
+use glee::sema::check::{Bodies, check};
+use glee::sema::modules::SourceFile;
+
+#[test]
+fn results_keep_the_order_of_the_items() {
+	let items: Vec<u64> = (0..1000).collect();
+	let squares = parallel::map(&items, |&item| {
+		// Later items finish first
+		std::thread::sleep(std::time::Duration::from_micros(1000 - item));
+		item * item
+	});
+	assert_eq!(squares, items.iter().map(|item| item * item).collect::<Vec<_>>());
+}
+
+#[test]
+fn panics_on_other_threads_are_caught() {
+	let items: Vec<usize> = (0..64).collect();
+	let ice = ice::catch(|| {
+		parallel::map(&items, |&item| {
+			ice::enter(Phase::Parsing, format!("file{}.gl", item));
+			assert!(item != 40, "broke on {}", item);
+			item
+		})
+	})
+	.unwrap_err();
+	assert_eq!(ice.message, "broke on 40");
+	assert_eq!((ice.phase, ice.file.as_deref()), (Some(Phase::Parsing), Some("file40.gl")));
+}
+
+fn files(sources: &[(&str, &str)]) -> Vec<SourceFile> {
+	sources
+		.iter()
+		.map(|(path, text)| {
+			let tokens = Lexer::new(text.to_string()).lex().unwrap().clone();
+			SourceFile { path: path.to_string(), program: Parser::new(tokens).parse().unwrap(), package: None }
+		})
+		.collect()
+}
+
+// The root package imports the other two, which are checked side by side
+// before it, each adding lambdas and locals of its own.
+const PACKAGES: &[(&str, &str)] = &[
+	("main.gl", "import shapes.area\nimport text.shout\n\nfn main() {\n\tval twice = (n: Int) => n * 2\n\tprintln(shout(\"area \" + twice(area(3))))\n}\n"),
+	("shapes.gl", "package shapes\n\nfn area(side: Int): Int {\n\tval by = (x: Int) => x * side\n\treturn by(side)\n}\n"),
+	(
+		"text.gl",
+		concat!(
+			"package text\n\nfn shout(s: String): String {\n\tmut loud = s\n",
+			"\tfor each in [s].map((each) => each + \"!\") {\n\t\tloud = each\n\t}\n\treturn loud\n}\n"
+		)
+	)
+];
+
+#[test]
+fn packages_check_to_the_same_program_on_any_number_of_threads() {
+	let files = files(PACKAGES);
+	parallel::set_threads(1);
+	let alone = check(&files).unwrap();
+	parallel::set_threads(8);
+	let together = check(&files).unwrap();
+	parallel::set_threads(0);
+	assert_eq!(format!("{:?}", alone), format!("{:?}", together));
+
+	let mut out = Vec::new();
+	interpreter::run(&together, &Runtime::new(), None, &[], &mut out).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), "area 18!\n");
+}
+
+#[test]
+fn errors_of_packages_checked_apart_are_in_the_order_of_the_files() {
+	let files = files(&[
+		("main.gl", "import shapes.area\n\nfn main() {\n\tval n: String = area(2)\n}\n"),
+		("shapes.gl", "package shapes\n\nfn area(side: Int): Int {\n\tval f = (x: Int) => x * side\n\treturn f(\"no\")\n}\n"),
+		("text.gl", "package text\n\nfn shout(s: String): String {\n\tval n: Int = s\n\treturn s\n}\n")
+	]);
+	let errors = |threads: usize| {
+		parallel::set_threads(threads);
+		let errors = check(&files).unwrap_err();
+		parallel::set_threads(0);
+		errors.into_iter().map(|error| (error.file, error.primary_span.line, error.message)).collect::<Vec<_>>()
+	};
+	let alone = errors(1);
+	assert_eq!(alone.iter().map(|(file, line, _)| (*file, *line)).collect::<Vec<_>>(), [(0, 4), (1, 5), (2, 4)]);
+	assert_eq!(errors(8), alone);
+}
+
+#[test]
+fn packages_are_checked_again_only_once_what_they_depend_on_changes() {
+	let runtime = Runtime::new();
+	let mut kept = Bodies::new();
+	let first = runtime.recheck(&files(PACKAGES), &mut kept).unwrap();
+	assert!(kept.reused().is_empty());
+	let again = runtime.recheck(&files(PACKAGES), &mut kept).unwrap();
+	assert_eq!(kept.reused(), ["shapes", "text", ""]);
+	assert_eq!(format!("{:?}", again), format!("{:?}", first));
+
+	// The root package imports `text`, and `shapes` does not
+	let text = PACKAGES[2].1.replace("\"!\"", "\"!!\"");
+	let edited = files(&[PACKAGES[0], PACKAGES[1], ("text.gl", &text)]);
+	let again = runtime.recheck(&edited, &mut kept).unwrap();
+	assert_eq!(kept.reused(), ["shapes"]);
+	assert_eq!(format!("{:?}", again), format!("{:?}", runtime.check(&edited).unwrap()));
+	let mut out = Vec::new();
+	interpreter::run(&again, &runtime, None, &[], &mut out).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), "area 18!!\n");
+}
@@ -0,0 +1,42 @@
+// Declarations as the parser reads them, where one part ends and the next
+// starts depending on what follows it.
+
+use glee::ast::stmt::{EnumDecl, Member, StmtKind};
+use glee::lexer::lexer::Lexer;
+use glee::parser::parser::Parser;
+
+fn enum_decl(source: &str) -> EnumDecl {
+	let tokens = Lexer::new(source.to_string()).lex().unwrap().clone();
+	let program = Parser::new(tokens).parse().unwrap_or_else(|errors| panic!("{}", errors[0].message));
+	match program.stmts.into_iter().next().map(|stmt| stmt.kind) {
+		Some(StmtKind::Enum(decl)) => decl,
+		kind => panic!("{:?}", kind)
+	}
+}
+
+// Each variant's name, then each member's, with `static` before the static
+// ones.
+fn parts(decl: &EnumDecl) -> (Vec<&str>, Vec<String>) {
+	let variants = decl.variants.iter().map(|variant| variant.name.as_str()).collect();
+	let members = decl.members.iter().map(|member| match member {
+		Member::Field(field) => format!("{}{}", if field.modifiers.is_static { "static " } else { "" }, field.name),
+		Member::Method(method) => format!("{}{}", if method.modifiers.is_static { "static " } else { "" }, method.name),
+		Member::Constructor(_) => "constructor".to_string()
+	});
+	(variants, members.collect())
+}
+
+#[test]
+fn enum_variants_end_where_the_members_start() {
+	for (source, members) in [
+		("enum C {\n\tRed,\n\tGreen\n\tfn f(): Int = 1\n}", vec!["f"]),
+		("enum C {\n\tRed,\n\tGreen\n\tstatic fn f(): Int = 1\n}", vec!["static f"]),
+		("enum C {\n\tRed,\n\tGreen\n\tval n: Int = 1\n}", vec!["n"]),
+		("enum C {\n\tRed\n\tGreen\n\tstatic val n: Int = 1\n\tfn f(): Int = n\n}", vec!["static n", "f"]),
+		("enum C {\n\tRed, Green;\n\tprivate fn f(): Int = 1\n}", vec!["f"]),
+		("enum C { Red, Green }", vec![])
+	] {
+		let decl = enum_decl(source);
+		assert_eq!(parts(&decl), (vec!["Red", "Green"], members.iter().map(|member| member.to_string()).collect()), "{}", source);
+	}
+}
@@ -0,0 +1,306 @@
+// Runs the lexer and parser over random and mutated input, checking that they
+// never panic or hang and that every error points at a real place in the
+// source. `GLEE_FUZZ_ITERATIONS` and `GLEE_FUZZ_SEED` make longer or
+// reproducible runs possible:
+//
+//   GLEE_FUZZ_ITERATIONS=200000 cargo test --release --test parser_fuzz
+
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use glee::ast::spans;
+use glee::lexer::lexer::Lexer;
+use glee::lexer::token::{Span, Token};
+use glee::parser::parser::Parser;
+
+const CORPUS: &[&str] = &[
+	include_str!("../preview.gl"),
+	include_str!("../test.gl"),
+	"fn <T> List<T>.first(): T? = if (this.size > 0) this[0] else null",
+	"class A<T : B> : C(), D { mut val x: Int = 0 get() = field set(v) { field = v } }",
+	"enum E { A(1), B(2); fn f() {} }",
+	"for (k, v) of m { print(\"${k}: $v\") }",
+	"switch (x) { case 1, 2 -> a case 3 { b } default -> c }",
+	"try { throw E() } catch (e: E) { } finally { }",
+	"val f = (a, b) => a + b; val g = x => { x }; val h = fn (a) { return a }",
+	"type Pair<A, B> = Map<A, (B) -> A?>[]",
+	"@Anno(1) private async fn f() throws E { await g()!! ?? 0..10 }",
+	"/// doc\n/** more */ struct S { a: Int, b: String = \"\" }",
+	"retrun}",
+	"interfac}e Sized {"
+];
+
+const FRAGMENTS: &[&str] = &[
+	"(", ")", "{", "}", "[", "]", "<", ">", ">>", ",", ".", "..", ":", ";", "=", "=>", "->", "?", "??", "!!",
+	"\"", "'", "${", "$", "\\", "/*", "*/", "//", "///", "@", "\n", " ", "fn", "class", "val", "mut", "if",
+	"else", "for", "in", "of", "is", "!is", "type", "get", "set", "x", "0", "1.5", "\"s\"", "'c'", "\u{e9}"
+];
+
+struct Rng(u64);
+
+impl Rng {
+	fn next(&mut self) -> u64 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		self.0
+	}
+
+	fn below(&mut self, n: usize) -> usize {
+		(self.next() % n.max(1) as u64) as usize
+	}
+
+	fn pick(&mut self, items: &[&'static str]) -> &'static str {
+		items[self.below(items.len())]
+	}
+}
+
+enum Case {
+	Source(String),
+	Tokens(Vec<Token>)
+}
+
+impl Case {
+	fn describe(&self) -> String {
+		match self {
+			Case::Source(source) => format!("source {:?}", source),
+			Case::Tokens(tokens) => {
+				let lexemes: Vec<&str> = tokens.iter().map(|t| t.lexeme.as_str()).collect();
+				format!("tokens {:?}", lexemes)
+			}
+		}
+	}
+}
+
+fn random_source(rng: &mut Rng) -> String {
+	let mut source = String::new();
+	for _ in 0..rng.below(40) {
+		source.push_str(rng.pick(FRAGMENTS));
+		if rng.below(3) == 0 {
+			source.push(' ');
+		}
+	}
+	source
+}
+
+fn mutate(rng: &mut Rng, source: &str) -> String {
+	let mut source = source.to_string();
+	for _ in 0..1 + rng.below(4) {
+		let at = boundary(&source, rng.below(source.len() + 1));
+		let to = boundary(&source, at + rng.below(20));
+		match rng.below(4) {
+			0 => source.replace_range(at..to, ""),
+			1 => source.insert_str(at, rng.pick(FRAGMENTS)),
+			2 => {
+				let copy = source[at..to].to_string();
+				source.insert_str(to, &copy);
+			}
+			// Cutting files short is where most end-of-file bugs hide
+			_ => source.truncate(at)
+		}
+	}
+	source
+}
+
+fn permute(rng: &mut Rng, mut tokens: Vec<Token>) -> Vec<Token> {
+	tokens.pop();
+	for _ in 0..1 + rng.below(6) {
+		if tokens.is_empty() {
+			break;
+		}
+		let i = rng.below(tokens.len());
+		let j = rng.below(tokens.len());
+		match rng.below(4) {
+			0 => tokens.swap(i, j),
+			1 => {
+				tokens.remove(i);
+			}
+			2 => {
+				let token = tokens[i].clone();
+				tokens.insert(j, token);
+			}
+			_ => tokens.truncate(i)
+		}
+	}
+	tokens
+}
+
+fn boundary(source: &str, mut at: usize) -> usize {
+	at = at.min(source.len());
+	while !source.is_char_boundary(at) {
+		at -= 1;
+	}
+	at
+}
+
+fn lex(source: &str) -> Result<Vec<Token>, Span> {
+	let mut lexer = Lexer::new(source.to_string());
+	lexer.lex().cloned().map_err(|err| err.primary_span)
+}
+
+fn generate(rng: &mut Rng) -> Case {
+	match rng.below(4) {
+		0 => Case::Source(random_source(rng)),
+		1 | 2 => {
+			let source = rng.pick(CORPUS);
+			Case::Source(mutate(rng, source))
+		}
+		_ => {
+			let tokens = lex(CORPUS[rng.below(CORPUS.len())]).unwrap();
+			Case::Tokens(permute(rng, tokens))
+		}
+	}
+}
+
+// Byte offsets must be in bounds and on character boundaries, and for real
+// source the line and column must be where that offset actually is.
+fn check_span(span: Span, source: Option<&str>, limit: usize) -> Result<(), String> {
+	if span.start > span.end || span.end > limit || span.line < 1 || span.column < 1 {
+		return Err(format!("invalid span {:?}", span));
+	}
+	if let Some(source) = source {
+		if !source.is_char_boundary(span.start) || !source.is_char_boundary(span.end) {
+			return Err(format!("span {:?} splits a character", span));
+		}
+		let before = &source[..span.start];
+		let line = before.matches('\n').count() as i64 + 1;
+		let column = (span.start - before.rfind('\n').map_or(0, |i| i + 1)) as i64 + 1;
+		if (line, column) != (span.line, span.column) {
+			return Err(format!("span {:?} is really at line {} column {}", span, line, column));
+		}
+	}
+	Ok(())
+}
+
+fn run(case: &Case) -> Result<(), String> {
+	let (tokens, source) = match case {
+		Case::Source(source) => match lex(source) {
+			Ok(tokens) => (tokens, Some(source.as_str())),
+			Err(span) => return check_span(span, Some(source), source.len()),
+		},
+		Case::Tokens(tokens) => (tokens.clone(), None)
+	};
+
+	let limit = source.map_or_else(|| tokens.iter().map(|t| t.span.end).max().unwrap_or(0), str::len);
+	if let Some(source) = source {
+		for token in &tokens {
+			check_span(token.span, Some(source), limit)?;
+		}
+	}
+
+	match Parser::new(tokens).parse() {
+		Ok(mut program) => {
+			let mut result = Ok(());
+			spans::for_each_span_mut(&mut program, &mut |span| {
+				if result.is_ok() {
+					result = check_span(*span, source, limit);
+				}
+			});
+			result
+		}
+		Err(errors) => {
+			if errors.is_empty() {
+				return Err("parse failed without reporting an error".to_string());
+			}
+			errors.iter().try_for_each(|err| check_span(err.primary_span, source, limit))
+		}
+	}
+}
+
+#[test]
+fn parser_survives_arbitrary_input() {
+	let iterations = std::env::var("GLEE_FUZZ_ITERATIONS").ok().and_then(|v| v.parse().ok()).unwrap_or(3000);
+	let seed = std::env::var("GLEE_FUZZ_SEED").ok().and_then(|v| v.parse().ok()).unwrap_or(0x9e3779b97f4a7c15);
+
+	// The worker publishes each case before running it so a hang or panic can
+	// be reported with the input that caused it.
+	let progress = Arc::new(AtomicUsize::new(0));
+	let current = Arc::new(Mutex::new(String::new()));
+	let failures = Arc::new(Mutex::new(Vec::new()));
+
+	let worker = {
+		let (progress, current, failures) = (progress.clone(), current.clone(), failures.clone());
+		thread::spawn(move || {
+			panic::set_hook(Box::new(|_| {}));
+			let mut rng = Rng(seed);
+			for _ in 0..iterations {
+				let case = generate(&mut rng);
+				*current.lock().unwrap() = case.describe();
+				let outcome = panic::catch_unwind(|| run(&case)).unwrap_or_else(|payload| {
+					let message = payload
+						.downcast_ref::<String>()
+						.cloned()
+						.or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+						.unwrap_or_default();
+					Err(format!("panicked: {}", message))
+				});
+				if let Err(message) = outcome {
+					failures.lock().unwrap().push(format!("{}\n  on {}", message, case.describe()));
+				}
+				progress.fetch_add(1, Ordering::SeqCst);
+			}
+		})
+	};
+
+	let mut last = (0, Instant::now());
+	while !worker.is_finished() {
+		thread::sleep(Duration::from_millis(20));
+		let done = progress.load(Ordering::SeqCst);
+		if done != last.0 {
+			last = (done, Instant::now());
+		} else if last.1.elapsed() > Duration::from_secs(5) {
+			panic!("parser hung on {}", current.lock().unwrap());
+		}
+	}
+	let _ = panic::take_hook();
+
+	let failures = failures.lock().unwrap();
+	if !failures.is_empty() {
+		panic!("{} of {} cases failed, first:\n{}", failures.len(), iterations, failures[0]);
+	}
+}
+
+// Recursion depth is bounded, so absurd nesting is a parse error rather than
+// a stack overflow. Debug builds use a lot of stack per level, so this runs
+// with the 8 MiB a main thread gets rather than a test thread's default.
+#[test]
+fn deep_nesting_is_an_error() {
+	let units = ["(", "[", "{", "-", "x => ", "if (a) b else ", "val x: (", "if (a) {} elif (b) {} else ", "\"${"];
+	let worker = thread::Builder::new().stack_size(8 << 20).spawn(move || {
+		for unit in units {
+			let source = unit.repeat(20000);
+			match lex(&source) {
+				Ok(tokens) => {
+					let errors = Parser::new(tokens).parse().expect_err(unit);
+					for err in errors {
+						check_span(err.primary_span, Some(&source), source.len()).unwrap();
+					}
+				}
+				Err(span) => check_span(span, Some(&source), source.len()).unwrap()
+			}
+		}
+	});
+	worker.unwrap().join().unwrap();
+}
+
+// A misspelled keyword skips the rest of its line, which must still move past
+// a `}` that ends the line straight after it.
+#[test]
+fn misspelled_keywords_before_a_brace_are_skipped() {
+	let sources = ["retrun}", "interfac}e Sized {", "fn main() {\n\tretrun}\n}", "clas}\nfn f() {}"];
+	let (done, finished) = std::sync::mpsc::channel();
+	thread::spawn(move || {
+		for source in sources {
+			let errors = Parser::new(lex(source).unwrap()).parse().map(|_| Vec::new()).unwrap_or_else(|errors| errors);
+			assert!(!errors.is_empty(), "{:?}", source);
+			for err in errors {
+				check_span(err.primary_span, Some(source), source.len()).unwrap();
+			}
+		}
+		done.send(()).unwrap();
+	});
+	finished.recv_timeout(Duration::from_secs(5)).expect("the parser hung");
+}
@@ -0,0 +1,224 @@
+// Operator precedence and associativity, written down as the trees the parser
+// must build. Each shape is an s-expression: `(op left right)` for infix
+// operators, `(op operand)` for prefix and postfix ones.
+
+use glee::ast::expr::{Expr, ExprKind, InterpolationPart, LambdaBody, Literal, PostfixOp, UnaryOp};
+use glee::ast::stmt::StmtKind;
+use glee::lexer::lexer::Lexer;
+use glee::parser::parser::Parser;
+use glee::parser::precedence::{self, Assoc, Fixity, OPERATORS, Precedence};
+
+const SHAPES: &[(&str, &str)] = &[
+	// Arithmetic and grouping
+	("a + b * c", "(+ a (* b c))"),
+	("a * b + c", "(+ (* a b) c)"),
+	("a - b - c", "(- (- a b) c)"),
+	("a / b % c", "(% (/ a b) c)"),
+	("(a + b) * c", "(* (+ a b) c)"),
+	("a * -b", "(* a (- b))"),
+	("-a * b", "(* (- a) b)"),
+	("a << b + c", "(<< a (+ b c))"),
+	// Bitwise sits between logic and equality
+	("a | b ^ c & d", "(| a (^ b (& c d)))"),
+	("a & b == c", "(& a (== b c))"),
+	("a || b && c", "(|| a (&& b c))"),
+	("a or b and c", "(|| a (&& b c))"),
+	// Comparison, type tests and membership
+	("a < b == c < d", "(== (< a b) (< c d))"),
+	("a is T && b", "(&& (is a T) b)"),
+	("a !is T", "(!is a T)"),
+	("!a is T", "(is (! a) T)"),
+	("a in b == c", "(== (in a b) c)"),
+	// Ranges bind tighter than comparison but looser than arithmetic
+	("a..b + 1", "(.. a (+ b 1))"),
+	("a < b..c", "(< a (.. b c))"),
+	("x in 0..n", "(in x (.. 0 n))"),
+	// Null coalescing is right associative and `?:` is the same operator
+	("a ?? b ?? c", "(?? a (?? b c))"),
+	("a ?? b ?: c", "(?? a (?? b c))"),
+	("a ?: b ?? c", "(?? a (?? b c))"),
+	("a ?? b || c", "(?? a (|| b c))"),
+	("a!! ?? b", "(?? (!! a) b)"),
+	// Assignment is the loosest and right associative
+	("a = b = c", "(= a (= b c))"),
+	("x = a ?? b", "(= x (?? a b))"),
+	("a.b[c] = d + e", "(= ([] (. a b) c) (+ d e))"),
+	("a += b * c", "(+= a (* b c))"),
+	("a -= b -= c", "(-= a (-= b c))"),
+	("a.b %= c ?? d", "(%= (. a b) (?? c d))"),
+	// `if` branches stop before anything looser than `??`
+	("x = if (c) a else b ?? d", "(= x (if c a (?? b d)))"),
+	("x = if (c) a else b", "(= x (if c a b))"),
+	// Prefix operators bind tighter than every infix one
+	("typeof a == b", "(== (typeof a) b)"),
+	("await a + b", "(+ (await a) b)"),
+	("await spawn { a } + b", "(+ (await (spawn 1)) b)"),
+	("not a && b", "(&& (! a) b)"),
+	("- - a", "(- (- a))"),
+	// Postfix binds tightest
+	("-a.b()", "(- (call (. a b)))"),
+	("a!!.b", "(. (!! a) b)"),
+	("a.b!!", "(!! (. a b))"),
+	("a++ + b", "(+ (++ a) b)"),
+	("++a.b", "(++ (. a b))"),
+	("a[b](c)", "(call ([] a b) c)"),
+	("-a.b()? ?? c", "(?? (- (? (call (. a b)))) c)"),
+	("a?.b?", "(? (?. a b))"),
+	// Lambdas take everything to their right
+	("x => x + 1", "(=> (x) (+ x 1))"),
+	("f = (a, b) => a ?? b", "(= f (=> (a b) (?? a b)))")
+];
+
+// Combinations the grammar rejects outright.
+const ERRORS: &[(&str, &str)] = &[
+	("a..b..c", "'..'"),
+	("a + b = c", "assign"),
+	("1 = a", "assign"),
+	("a + b += c", "assign"),
+	("++1", "Increment"),
+	("a ?? ", "Expected expression")
+];
+
+#[test]
+fn operators_build_the_documented_shapes() {
+	let mut failures = Vec::new();
+	for &(source, expected) in SHAPES {
+		match parse(source) {
+			Ok(expr) if shape(&expr) == expected => {}
+			Ok(expr) => failures.push(format!("{}\n  expected {}\n  got      {}", source, expected, shape(&expr))),
+			Err(message) => failures.push(format!("{}\n  expected {}\n  got error {}", source, expected, message))
+		}
+	}
+	assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn ambiguous_combinations_are_rejected() {
+	for &(source, fragment) in ERRORS {
+		match parse(source) {
+			Ok(expr) => panic!("{} parsed as {}", source, shape(&expr)),
+			Err(message) => assert!(message.contains(fragment), "{}: unexpected error {}", source, message)
+		}
+	}
+}
+
+// The parser's binding table must match the one the formatter and docs use.
+#[test]
+fn parser_agrees_with_operator_table() {
+	let mut levels: Vec<Precedence> = Vec::new();
+	for operator in OPERATORS {
+		levels.push(operator.precedence);
+		if operator.fixity != Fixity::Infix || operator.symbol == "!is" {
+			continue;
+		}
+		let mut lexer = Lexer::new(operator.symbol.to_string());
+		let tokens = lexer.lex().unwrap();
+		assert_eq!(tokens.len(), 2, "'{}' should be a single token", operator.symbol);
+		let binding = precedence::infix(tokens[0].token_type);
+		assert_eq!(binding, Some((operator.precedence, operator.assoc)), "'{}'", operator.symbol);
+		if !matches!(operator.symbol, "=" | "is" | "..") {
+			let op = precedence::binary_op(tokens[0].token_type).or(precedence::compound_op(tokens[0].token_type));
+			assert!(op.is_some(), "'{}' has no BinaryOp", operator.symbol);
+		}
+	}
+	assert!(levels.windows(2).all(|pair| pair[0] <= pair[1]), "OPERATORS must be listed weakest first");
+	assert!(OPERATORS.iter().all(|op| op.fixity != Fixity::Prefix || op.assoc == Assoc::Right));
+}
+
+fn parse(source: &str) -> Result<Expr, String> {
+	let mut lexer = Lexer::new(source.to_string());
+	let tokens = lexer.lex().map_err(|err| err.to_string())?.clone();
+	let mut program = Parser::new(tokens).parse().map_err(|errors| {
+		errors.iter().map(|err| err.to_string()).collect::<Vec<_>>().join("; ")
+	})?;
+	match program.stmts.pop().map(|stmt| stmt.kind) {
+		Some(StmtKind::Expr(expr)) if program.stmts.is_empty() => Ok(expr),
+		_ => Err("not a single expression".to_string())
+	}
+}
+
+fn shape(expr: &Expr) -> String {
+	match &expr.kind {
+		ExprKind::Literal(Literal::Int(value)) => value.to_string(),
+		ExprKind::Literal(literal) => format!("{:?}", literal),
+		ExprKind::Identifier(name) => name.clone(),
+		ExprKind::This => "this".to_string(),
+		ExprKind::Super => "super".to_string(),
+		ExprKind::Interpolation(parts) => {
+			let parts: Vec<String> = parts
+				.iter()
+				.map(|part| match part {
+					InterpolationPart::Text(text) => format!("{:?}", text),
+					InterpolationPart::Expr(expr) => shape(expr)
+				})
+				.collect();
+			format!("(str {})", parts.join(" "))
+		}
+		ExprKind::Unary { op, operand } => {
+			let symbol = match op {
+				UnaryOp::Negate => "-",
+				UnaryOp::Not => "!",
+				UnaryOp::PreIncrement => "++",
+				UnaryOp::PreDecrement => "--",
+				UnaryOp::Typeof => "typeof"
+			};
+			format!("({} {})", symbol, shape(operand))
+		}
+		ExprKind::Postfix { op, operand } => {
+			let symbol = match op {
+				PostfixOp::Increment => "++",
+				PostfixOp::Decrement => "--",
+				PostfixOp::NonNull => "!!",
+				PostfixOp::Propagate => "?"
+			};
+			format!("({} {})", symbol, shape(operand))
+		}
+		ExprKind::Binary { op, left, right } => format!("({} {} {})", op.symbol(), shape(left), shape(right)),
+		ExprKind::Assign { target, op: None, value } => format!("(= {} {})", shape(target), shape(value)),
+		ExprKind::Assign { target, op: Some(op), value } => format!("({}= {} {})", op.symbol(), shape(target), shape(value)),
+		ExprKind::Is { operand, ty, negated } => {
+			format!("({} {} {})", if *negated { "!is" } else { "is" }, shape(operand), ty)
+		}
+		ExprKind::If { cond, then_branch, else_branch } => {
+			format!("(if {} {} {})", shape(cond), shape(then_branch), shape(else_branch))
+		}
+		ExprKind::Block(block) => format!("(block {})", block.stmts.len()),
+		ExprKind::Call { callee, args } => {
+			let mut parts = vec![shape(callee)];
+			parts.extend(args.iter().map(shape));
+			format!("(call {})", parts.join(" "))
+		}
+		ExprKind::Member { object, name, safe, .. } => {
+			format!("({} {} {})", if *safe { "?." } else { "." }, shape(object), name)
+		}
+		ExprKind::Index { object, index } => format!("([] {} {})", shape(object), shape(index)),
+		ExprKind::New { class, args } => {
+			let args: Vec<String> = args.iter().map(shape).collect();
+			format!("(new {} {})", class, args.join(" "))
+		}
+		ExprKind::Range { start, end } => format!("(.. {} {})", shape(start), shape(end)),
+		ExprKind::Array(items) => {
+			let items: Vec<String> = items.iter().map(shape).collect();
+			format!("[{}]", items.join(" "))
+		}
+		ExprKind::Map(entries) => {
+			let entries: Vec<String> = entries.iter().map(|(key, value)| format!("{}: {}", shape(key), shape(value))).collect();
+			format!("[{}]", entries.join(" "))
+		}
+		ExprKind::Lambda { params, body } => {
+			let params: Vec<&str> = params.iter().map(|param| param.name.as_str()).collect();
+			let body = match body {
+				LambdaBody::Expr(body) => shape(body),
+				LambdaBody::Block(block) => format!("(block {})", block.stmts.len())
+			};
+			format!("(=> ({}) {})", params.join(" "), body)
+		}
+		ExprKind::Await(operand) => format!("(await {})", shape(operand)),
+		ExprKind::Spawn(block) => format!("(spawn {})", block.stmts.len()),
+		ExprKind::Return(value) => match value {
+			Some(value) => format!("(return {})", shape(value)),
+			None => "(return)".to_string()
+		},
+		ExprKind::Throw(value) => format!("(throw {})", shape(value))
+	}
+}
@@ -0,0 +1,57 @@
+// What sampling a program finds of where it spends its time, and what each
+// function makes.
+
+mod common;
+
+use std::time::Duration;
+
+use common::checked;
+use glee::bytecode::compiler::compile;
+use glee::bytecode::profiler::Profiler;
+use glee::bytecode::vm;
+use glee::interpreter::gc::Settings;
+use glee::interpreter::native::Runtime;
+
+const PROGRAM: &str = "fn square(n: Int): Int {
+	return n * n
+}
+
+fn pair(n: Int): Any {
+	return [n, n]
+}
+
+fn main() {
+	mut val total = 0
+	for (i in 0..100000) {
+		total += square(i)
+	}
+	for (i in 0..100) {
+		pair(i)
+	}
+	println(total)
+}";
+
+#[test]
+fn samples_find_the_calls_running_and_count_what_each_makes() {
+	let module = compile(&checked(PROGRAM), None);
+	let mut profiler = Profiler::new(Duration::from_micros(100));
+	let mut out = Vec::new();
+	vm::profile(&module, &Runtime::new(), &[], Settings::default(), &mut profiler, &mut out).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), "333328333350000\n");
+
+	// Every stack is of calls the program makes, outermost first
+	let folded = profiler.folded();
+	assert!(!folded.is_empty());
+	for line in folded.lines() {
+		let (stack, samples) = line.rsplit_once(' ').unwrap();
+		assert!(["main", "main;square", "main;pair"].contains(&stack), "{}", folded);
+		assert!(samples.parse::<u64>().unwrap() > 0);
+	}
+
+	let functions = profiler.functions();
+	let main = functions.iter().find(|function| function.name == "main").unwrap();
+	assert!(functions.iter().all(|function| function.total <= main.total && function.own <= function.total));
+	let pair = functions.iter().find(|function| function.name == "pair").unwrap();
+	assert_eq!((pair.made, main.made), (100, 0));
+	assert!(profiler.table().starts_with("function"));
+}
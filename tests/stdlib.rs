@@ -0,0 +1,812 @@
+// What the standard library gives every program, on both engines.
+
+mod common;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use common::{checked_with, run_both};
+use glee::bytecode::compiler::compile;
+use glee::bytecode::vm;
+use glee::driver::{running, runtime};
+use glee::interpreter::gc::Settings;
+use glee::interpreter::Uncaught;
+use glee::sema::hir::Program;
+
+fn checked(source: &str) -> Program {
+	checked_with(&runtime(), source).unwrap()
+}
+
+// Runs `source` on the interpreter and the VM, checks they agree, and
+// returns what it printed and what stopped it.
+fn run(source: &str) -> (String, Option<Uncaught>) {
+	run_both(&checked(source), &runtime(), Settings::default())
+}
+
+#[test]
+fn the_prelude_reads_numbers_and_asserts() {
+	let (out, uncaught) = run("fn main() {
+	println(toInt(\" 42 \") + 1)
+	println(toFloat(\"2.5\") * 2)
+	try {
+		toInt(\"4x\")
+	} catch (e) {
+		println(e)
+	}
+	assert(1 + 1 == 2)
+	assert(1 + 1 == 3)
+	println(\"not reached\")
+}");
+	assert_eq!(out, "43\n5.0\nCannot read '4x' as an Int\n");
+	assert_eq!(uncaught.unwrap().message, "Assertion failed");
+}
+
+#[test]
+fn std_modules_are_imported_like_packages_and_the_prelude_needs_no_import() {
+	let (out, uncaught) = run("import std.json as j
+import std.math.sqrt
+import std.regex.*
+import std.testing.*
+
+fn main() {
+	println(j.stringify(std.json.parse(\"[1, 2]\")))
+	println(sqrt(16.0) + \" \" + math.abs(-2) + \" \" + toInt(\"7\"))
+	println(test(\"[0-9]+\", \"abc 123\"))
+	assertEquals(1 + 1, 2)
+	assertNotEquals(\"a\", \"b\")
+	println(assertThrows(() => toInt(\"x\")))
+	println(assertThrows(() => assertThrows(() => 1)))
+	println(assertThrows(() => assertEquals(\"say \\\"hi\\\"\", \"say\\thi\")))
+	println(assertThrows(() => assertEquals(\"one\\n2\\nthree\\nfour\", \"one\\ntwo\\nthree\")))
+	assertEquals([1, 2], [2, 1])
+}");
+	assert_eq!(out, "\
+[1,2]
+4.0 2.0 7
+true
+Cannot read 'x' as an Int
+Expected it to throw, but it returned
+Expected \"say\\thi\" but got \"say \\\"hi\\\"\"
+Expected \"one\\ntwo\\nthree\" but got \"one\\n2\\nthree\\nfour\"
+  one
+- two
++ 2
+  three
++ four
+");
+	assert_eq!(uncaught.unwrap().message, "Uncaught exception: Expected [2, 1] but got [1, 2]");
+
+	let codes = |source: &str| {
+		let errors = checked_with(&runtime(), source).unwrap_err();
+		errors.iter().map(|error| (error.code, error.message.clone())).collect::<Vec<_>>()
+	};
+	assert_eq!(codes("import std.jsn\n"), [(Some("E0202"), "Cannot find package 'std.jsn'".to_string())]);
+	assert_eq!(codes("package std.mine\n"), [(Some("E0212"), "'std.mine' is the standard library's package; name this one something else".to_string())]);
+}
+
+#[test]
+fn strings_are_worked_on_by_character() {
+	let (out, _) = run("class Point {
+	val x: Int
+	val y: Int
+	constructor(x: Int, y: Int) {
+		this.x = x
+		this.y = y
+	}
+	fn toString(): String = \"({}, {})\".format([x, y])
+}
+
+fn main() {
+	val text = \"  héllo, wörld  \".trim()
+	println(text.length)
+	println(text.slice(1, 5) + \"|\" + text.toUpperCase() + \"|\" + \"ÉÀ\".toLowerCase())
+	println(text.split(\", \"))
+	println(\"añb\".split(\"\") + \" \" + \"añb\".chars().size)
+	println(text.contains(\"wö\") + \" \" + text.startsWith(\"hé\") + \" \" + text.endsWith(\"x\"))
+	println(text.indexOf(\"w\") + \" \" + text.indexOf(\"z\"))
+	println(text.replace(\"l\", \"L\"))
+	println(\"7\".padStart(3, \"0\") + \" \" + \"ab\".padEnd(5, \"-=\") + \" \" + \"long\".padStart(2, \" \"))
+	println(\"ab\".repeat(3) + \" [\" + \"  x \".trimStart() + \"] [\" + \"  x \".trimEnd() + \"]\")
+	println(\"{} + {} = {}, {{literally}}\".format([1, 2.5, Point(1, 2)]))
+	for template in [\"{} {}\", \"{}\", \"{\"] {
+		try {
+			println(template.format([1, 2]))
+		} catch (e) {
+			println(e)
+		}
+	}
+	try {
+		text.slice(3, 20)
+	} catch (e) {
+		println(e)
+	}
+	for times in [-1, math.intMax()] {
+		try {
+			\"ab\".repeat(times)
+		} catch (e) {
+			println(e)
+		}
+	}
+	try {
+		\"x\".padStart(math.intMax(), \"ab\")
+	} catch (e) {
+		println(e)
+	}
+}");
+	assert_eq!(out, "\
+12
+éllo|HÉLLO, WÖRLD|éà
+[héllo, wörld]
+[a, ñ, b] 3
+true true false
+7 -1
+héLLo, wörLd
+007 ab-=- long
+ababab [x ] [  x]
+1 + 2.5 = (1, 2), {literally}
+1 2
+The format String has 1 '{}', but 2 arguments were given
+The format String has a lone '{'; write '{{' for one
+Cannot slice 3..20 of a String of length 12
+Cannot repeat a String -1 times
+Cannot make a String of 18446744073709551614 bytes
+Cannot make a String of 9223372036854775807 bytes
+");
+}
+
+#[test]
+fn lists_grow_and_maps_and_sets_look_up_by_value() {
+	let (out, _) = run("fn main() {
+	mut val list = [3, 1]
+	list.push(4)
+	list.insert(0, 9)
+	list.insert(list.size, 5)
+	println(list + \" \" + list.pop() + \" \" + list.removeAt(1) + \" \" + list)
+	println(list.remove(4) + \" \" + list.remove(7) + \" \" + list.contains(9) + \" \" + list.indexOf(1) + \" \" + list.indexOf(8))
+	val ages: Map<String, Int> = [\"ann\": 31, \"bob\": 27]
+	ages[\"cy\"] = 40
+	ages.set(\"ann\", 32)
+	println(ages + \" \" + ages.size + \" \" + ages[\"bob\"] + \" \" + ages.get(\"dee\") + \" \" + (\"cy\" in ages))
+	println(ages.remove(\"bob\") + \" \" + ages.remove(\"bob\") + \" \" + ages.keys() + \" \" + ages.values())
+	for (name, age) of ages {
+		println(name + \" is \" + (age + 1))
+	}
+	val empty: Map<Int, Bool> = [:]
+	println(empty.isEmpty() + \" \" + empty.containsKey(1))
+	val seen: Set<Int> = [1, 2, 2, 3]
+	println(seen + \" \" + seen.add(2) + \" \" + seen.add(4) + \" \" + seen.remove(1) + \" \" + (3 in seen) + \" \" + seen.contains(1))
+	mut val total = 0
+	for n in seen {
+		total += n
+	}
+	println(total + \" \" + seen.toArray() + \" \" + (seen is Set<Int>))
+	seen.clear()
+	list.clear()
+	println(seen.size + \" \" + list.isEmpty())
+	try {
+		list.removeAt(0)
+	} catch (e) {
+		println(e)
+	}
+}");
+	assert_eq!(out, "\
+[9, 3, 1, 4, 5] 5 3 [9, 1, 4]
+true false true 1 -1
+{ann: 32, bob: 27, cy: 40} 3 27 null true
+27 null [ann, cy] [32, 40]
+ann is 33
+cy is 41
+true false
+{1, 2, 3} false true true true false
+9 [2, 3, 4] true
+0 true
+Index 0 is out of bounds for length 0
+");
+}
+
+#[test]
+fn maps_and_sets_are_typed_by_their_keys_and_elements() {
+	let source = "fn main() {
+	val ages = [\"ann\": 31]
+	ages[1] = 2
+	val ids: Set<Int> = [\"x\"]
+	val wrong: Map<Int> = [:]
+	ages.set(\"bob\", \"old\")
+}";
+	let errors: Vec<String> = checked_with(&runtime(), source).unwrap_err().iter().map(|error| error.message.clone()).collect();
+	assert_eq!(errors, [
+		"Key must be 'String', found 'Int'",
+		"Type mismatch: expected 'Int', found 'String'",
+		"'Map' expects 2 type argument(s) but 1 were given",
+		"Type mismatch: expected 'Int', found 'String'"
+	]);
+}
+
+#[test]
+fn sequences_work_out_their_elements_when_asked() {
+	let (out, _) = run("class Naturals {
+	fn iterator(): Counter = Counter()
+}
+
+class Counter {
+	mut at: Int = 0
+	fn hasNext(): Bool = true
+	fn next(): Int {
+		at += 1
+		return at - 1
+	}
+}
+
+class Letters {
+	fn iterator(): Iterator<String> = [\"a\", \"b\", \"c\"].iterator()
+}
+
+fn main() {
+	val squares = [1, 2, 3, 4].map(n => {
+		println(\"squaring \" + n)
+		return n * n
+	})
+	println(\"nothing squared yet\")
+	for square in squares.filter(n => n % 2 == 0) {
+		println(square)
+	}
+	println(squares.hasNext())
+	println(Naturals().filter(n => n % 7 == 3).any(n => n > 20))
+	println(Naturals().map(n => n * 2).all(n => n < 10))
+	println((1..5).reduce(0, (total, n) => total + n) + \" \" + (1..4).reduce(\"\", (text, n) => text + n))
+	val ages: Map<String, Int> = [\"ann\": 31, \"bob\": 27]
+	println(ages.map(name => name.toUpperCase()).toArray() + \" \" + ages.any(name => ages[name] == 27))
+	for pair in Letters().enumerate() {
+		println(pair[0] + \" \" + pair[1])
+	}
+	println(Letters().zip(Naturals()).toArray() + \" \" + [1, 2, 3].zip(Letters().filter(l => l != \"b\")).toArray())
+	val lengths: Iterator<Int> = Letters().map(letter => letter.length)
+	println(lengths.next() + lengths.toArray().size)
+	try {
+		lengths.next()
+	} catch (e) {
+		println(e)
+	}
+}");
+	assert_eq!(out, "\
+nothing squared yet
+squaring 1
+squaring 2
+4
+squaring 3
+squaring 4
+16
+false
+true
+false
+10 123
+[ANN, BOB] true
+0 a
+1 b
+2 c
+[[a, 0], [b, 1], [c, 2]] [[1, a], [2, c]]
+3
+The iterator has no elements left
+");
+}
+
+#[test]
+fn math_rounds_and_checks_what_overflows() {
+	let (out, _) = run("fn main() {
+	println(math.sqrt(16) + \" \" + math.pow(2, 10) + \" \" + math.powInt(3, 4) + \" \" + math.abs(-2.5) + \" \" + math.absInt(-7))
+	println(math.floor(2.7) + \" \" + math.ceil(2.1) + \" \" + math.round(-2.5) + \" \" + math.truncate(-2.7) + \" \" + math.toFloat(3))
+	println(math.min(1, 2.5) + \" \" + math.maxInt(4, 9) + \" \" + math.clampInt(12, 0, 10) + \" \" + math.sign(-3))
+	println(math.round(math.sin(math.pi() / 2) * 1000) + \" \" + math.round(math.toDegrees(math.atan2(1, 1))) + \" \" + math.round(math.e() * 100))
+	println(math.isNaN(math.nan()) + \" \" + math.isFinite(math.infinity()))
+	println(math.checkedAdd(math.intMax(), 1) + \" \" + math.checkedMul(6, 7) + \" \" + math.checkedDiv(1, 0))
+	println(math.wrappingAdd(math.intMax(), 1) == math.intMin())
+	println(math.wrappingMul(math.intMax(), 2) + \" \" + math.wrappingNeg(math.intMin()))
+	for attempt in [() => math.round(math.infinity()), () => math.absInt(math.intMin()), () => math.clamp(1, 2, 0)] {
+		try {
+			attempt()
+		} catch (e) {
+			println(e)
+		}
+	}
+}");
+	assert_eq!(out, "\
+4.0 1024.0 81 2.5 7
+2 3 -3 -2 3.0
+1.0 9 10 -1.0
+1000 45 272
+true false
+null 42 null
+true
+-2 -9223372036854775808
+inf is out of range for an Int
+Integer overflow in 'math.absInt'
+Cannot clamp between 2 and 0; the lower bound is greater
+");
+}
+
+#[test]
+fn files_are_read_written_and_listed_and_what_fails_is_thrown() {
+	let dir = std::env::temp_dir().join(format!("dotfun-fs-{}", std::process::id()));
+	let (out, _) = run(&"fn main() {
+	val dir = \"DIR\"
+	fs.createDir(dir + \"/sub\")
+	fs.writeText(dir + \"/notes.txt\", \"one\\r\\ntwo\\n\")
+	fs.appendText(dir + \"/notes.txt\", \"three\")
+	println(fs.readText(dir + \"/notes.txt\").length + \" \" + fs.exists(dir + \"/notes.txt\") + \" \" + fs.isDir(dir + \"/sub\"))
+	for line in fs.lines(dir + \"/notes.txt\") {
+		println(\"[\" + line + \"]\")
+	}
+	println(fs.listDir(dir))
+	fs.remove(dir + \"/notes.txt\")
+	fs.remove(dir + \"/sub\")
+	println(fs.exists(dir + \"/notes.txt\") + \" \" + fs.listDir(dir).size)
+	for attempt in [() => fs.readText(dir + \"/notes.txt\"), () => fs.lines(dir + \"/gone\"), () => fs.remove(dir + \"/sub\")] {
+		try {
+			attempt()
+		} catch (e) {
+			println(e)
+		}
+	}
+	fs.remove(dir)
+}".replace("DIR", dir.to_str().unwrap()));
+	assert_eq!(out.replace(dir.to_str().unwrap(), "DIR"), "\
+14 true true
+[one]
+[two]
+[three]
+[notes.txt, sub]
+false 0
+Cannot read 'DIR/notes.txt': No such file or directory (os error 2)
+Cannot read 'DIR/gone': No such file or directory (os error 2)
+Cannot remove 'DIR/sub': No such file or directory (os error 2)
+");
+	assert!(!dir.exists());
+}
+
+#[test]
+fn json_is_read_into_maps_and_arrays_and_written_back() {
+	let (out, _) = run("enum Color { Red, Green }
+
+class Pet {
+	val name: String
+	val legs: Int
+	constructor(name: String, legs: Int) {
+		this.name = name
+		this.legs = legs
+	}
+}
+
+fn main() {
+	val doc = json.parse(\"{\\\"name\\\": \\\"dot\\\", \\\"tags\\\": [\\\"a\\\", 1, 2.5, true, null], \\\"big\\\": 1e300}\")
+	println(doc)
+	println(doc[\"tags\"][1] + 1)
+	println(json.stringify(doc))
+	val ages: Map<String, Int> = [\"ann\": 31]
+	println(json.stringify([Pet(\"rex\", 4), ages, Color.Green, \"say \\\"hi\\\"\\n\"]))
+	println(json.pretty([\"list\": [1, 2], \"empty\": [:], \"set\": json.parse(\"[]\")], 2))
+	for attempt in [() => json.parse(\"[1,\"), () => json.stringify([1: 2]), () => json.stringify(x => x), () => json.stringify(0.0 / 0.0)] {
+		try {
+			attempt()
+		} catch (e) {
+			println(e)
+		}
+	}
+}");
+	assert_eq!(out, "\
+{big: 1e300, name: dot, tags: [a, 1, 2.5, true, null]}
+2
+{\"big\":1e300,\"name\":\"dot\",\"tags\":[\"a\",1,2.5,true,null]}
+[{\"name\":\"rex\",\"legs\":4},{\"ann\":31},\"Green\",\"say \\\"hi\\\"\\n\"]
+{
+  \"list\": [
+    1,
+    2
+  ],
+  \"empty\": {},
+  \"set\": []
+}
+Cannot parse JSON: Expected a value, found the end of the JSON
+Cannot stringify a Map with 'Int' keys; JSON keys are Strings
+Cannot stringify a 'Function' as JSON
+Cannot stringify NaN; JSON numbers are finite
+");
+}
+
+#[test]
+fn results_and_options_are_combined_and_returned_early_with_a_question_mark() {
+	let (out, uncaught) = run("fn parse(text: String): Result<Int, String> {
+	try {
+		return Ok(toInt(text))
+	} catch (e) {
+		return Err(\"not a number: \" + text)
+	}
+}
+
+fn half(n: Int): Option<Int> = if (n % 2 == 0) Some(n / 2) else None
+
+fn sum(a: String, b: String): Result<Int, String> = Ok(parse(a)? + parse(b)?)
+
+fn quarter(n: Int): Option<Int> = half(half(n)?)
+
+fn main() {
+	println(sum(\"1\", \"2\"))
+	println(sum(\"1\", \"x\"))
+	println([quarter(12), quarter(6), quarter(5)])
+	println(parse(\"4\").map(n => n * 10).unwrapOr(0))
+	println(parse(\"y\").map(n => n * 10).unwrapOr(0))
+	println(parse(\"y\").mapErr(e => e.length).orElse(n => if (n > 3) Ok(n) else Err(\"short\")))
+	println(half(8).andThen(half).orElse(() => Some(-1)))
+	println(half(3).andThen(half).orElse(() => Some(-1)))
+	println([half(4).okOr(\"odd\"), half(3).okOr(\"odd\"), half(3).orNull()])
+	println([parse(\"5\").ok(), parse(\"5\").err(), parse(\"5\").isOk(), None.isNone()])
+	println([Ok(1) == Ok(1), Some(1) == Some(2), half(4) is Option<Int>, parse(\"1\") is Option<Int>])
+	println(parse(\"z\").unwrap())
+}");
+	assert_eq!(out, "\
+Ok(3)
+Err(not a number: x)
+[Some(3), None, None]
+40
+0
+Ok(15)
+Some(2)
+Some(-1)
+[Ok(2), Err(odd), null]
+[Some(5), None, true, true]
+[true, false, true, false]
+");
+	assert_eq!(uncaught.unwrap().message, "Called 'unwrap()' on Err(not a number: z)");
+}
+
+#[test]
+fn times_are_written_and_read_in_iso_8601_and_durations_are_milliseconds() {
+	let (out, _) = run("async fn main() {
+	println(time.format(0))
+	val leap = time.date(2024, 2, 29)
+	println([leap, time.format(leap + time.hours(13) + time.minutes(5) + 250)])
+	println(time.format(time.date(1969, 12, 31) - 1))
+	println(time.parse(\"2024-02-29T13:05:00.25Z\") == leap + time.hours(13) + time.minutes(5) + 250)
+	println(time.format(time.parse(\"2024-03-01T01:30+02:00\")))
+	println(time.format(time.parse(\"1999-12-31 23:59:59.999-00:30\")))
+	println([time.toSeconds(time.days(1)), time.format(time.date(10000, 1, 1))])
+	println(time.now() > time.parse(\"2024-01-01\"))
+	val start = time.monotonic()
+	await sleep(time.seconds(0) + 5)
+	println(time.monotonic() - start >= 5)
+	for attempt in [() => time.parse(\"2023-02-29\"), () => time.parse(\"2024-01-01T24:00\"), () => time.date(2024, 13, 1), () => time.days(math.intMax())] {
+		try {
+			attempt()
+		} catch (e) {
+			println(e)
+		}
+	}
+}");
+	assert_eq!(out, "\
+1970-01-01T00:00:00.000Z
+[1709164800000, 2024-02-29T13:05:00.250Z]
+1969-12-30T23:59:59.999Z
+true
+2024-02-29T23:30:00.000Z
+2000-01-01T00:29:59.999Z
+[86400.0, +10000-01-01T00:00:00.000Z]
+true
+true
+Cannot read '2023-02-29' as an ISO 8601 time
+Cannot read '2024-01-01T24:00' as an ISO 8601 time
+2024-13-1 is not a date
+Integer overflow in 'time.days'
+");
+}
+
+#[test]
+fn random_numbers_repeat_for_the_same_seed() {
+	let (out, _) = run("fn draw(random: Random): Int[] {
+	val rolls: Int[] = []
+	for (i in 0..20) {
+		rolls.push(random.int(1, 6))
+	}
+	return rolls
+}
+
+fn main() {
+	println(random.generator(0).int(math.intMin(), math.intMax()))
+	val first = draw(random.generator(7))
+	println(first == draw(random.generator(7)))
+	println(first.toString() == draw(random.generator(7)).toString())
+	println([first.any(n => n < 1 || n > 6), first.contains(1) && first.contains(6)])
+	val generator = random.generator(3)
+	val name: String = generator.choice([\"ann\", \"bob\"])
+	println([name.length, generator is Random, generator.float() < 1.0])
+	val deck = [1, 2, 3, 4, 5, 6, 7, 8]
+	generator.shuffle(deck)
+	random.shuffle(deck)
+	println(deck.reduce(0, (sum, n) => sum + n))
+	random.seed(11)
+	val once = [random.int(0, 1000), random.choice(deck)]
+	random.seed(11)
+	println(once.toString() == [random.int(0, 1000), random.choice(deck)].toString())
+	for attempt in [() => random.int(2, 1), () => generator.choice([])] {
+		try {
+			attempt()
+		} catch (e) {
+			println(e)
+		}
+	}
+}");
+	assert_eq!(out, "\
+7070836379803831727
+false
+true
+[false, true]
+[3, true, true]
+36
+true
+Cannot pick an Int from 2 to 1; the lower bound is greater
+Cannot choose from an empty array
+");
+}
+
+#[test]
+fn patterns_match_find_replace_and_split_and_bad_ones_say_where() {
+	let (out, _) = run("fn main() {
+	println(regex.test(\"^\\\\d{3}-\\\\d{4}$\", \"555-1234\"))
+	println(regex.test(\"^\\\\d{3}-\\\\d{4}$\", \"555-12345\"))
+	println(regex.match(\"(\\\\w+)@(\\\\w+)\\\\.com\", \"mail ann@example.com today\"))
+	println(regex.match(\"(a)|(b)\", \"b\"))
+	println(regex.match(\"x\", \"abc\"))
+	println(regex.findAll(\"(\\\\d+)([a-z]?)\", \"10a 2 33zz\"))
+	println(regex.findAll(\"a*\", \"baa\"))
+	println(regex.findAll(\"<.+?>\", \"<b>bold</b>\"))
+	println(regex.replace(\"(\\\\w+) (\\\\w+)\", \"hello world, good day\", \"$2 $1 $$\"))
+	println(regex.replace(\"\\\\bcat\\\\b\", \"cat concat cat.\", \"dog\"))
+	println(regex.split(\"\\\\s*,\\\\s*\", \"a , b,c,, d\"))
+	println(regex.split(\"[^a-zàé]+\", \"déjà-vu 2x\"))
+	println(regex.escape(\"1+1=2?\"))
+	println(regex.test(\"(a|aa)*b\", \"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaac\"))
+	for pattern in [\"a(b\", \"ab)\", \"*a\", \"[z-a]\", \"[abc\", \"a{2,1}\", \"\\\\q\", \"a\\\\\"] {
+		try {
+			regex.test(pattern, \"\")
+		} catch (e) {
+			println(e)
+		}
+	}
+	try {
+		regex.replace(\"(a)\", \"a\", \"$2\")
+	} catch (e) {
+		println(e)
+	}
+}");
+	assert_eq!(out, "\
+true
+false
+[ann@example.com, ann, example]
+[b, null, b]
+null
+[[10a, 10, a], [2, 2, ], [33z, 33, z]]
+[[], [aa], []]
+[[<b>], [</b>]]
+world hello $, day good $
+dog concat dog.
+[a, b, c, , d]
+[déjà, vu, x]
+1\\+1=2\\?
+false
+Invalid pattern 'a(b': '(' is not closed, at 1
+Invalid pattern 'ab)': unmatched ')', at 2
+Invalid pattern '*a': nothing to repeat before '*', at 0
+Invalid pattern '[z-a]': the range 'z-a' is out of order, at 1
+Invalid pattern '[abc': '[' is not closed, at 0
+Invalid pattern 'a{2,1}': the repetition's maximum is less than its minimum, at 1
+Invalid pattern '\\q': unknown escape '\\q', at 0
+Invalid pattern 'a\\': the pattern ends in '\\', at 1
+The replacement uses group 2, but the pattern has 1
+");
+}
+
+#[test]
+fn the_environment_is_read_and_changed_and_programs_run_with_it() {
+	let (out, _) = run("fn main() {
+	println(env.get(\"GLEE_TEST_UNSET\"))
+	env.set(\"GLEE_TEST_GREETING\", \"hello\")
+	println([env.get(\"GLEE_TEST_GREETING\"), env.vars()[\"GLEE_TEST_GREETING\"]])
+	val result = process.run(\"sh\", [\"-c\", \"echo \\$GLEE_TEST_GREETING; echo oops >&2; exit 3\"])
+	println([result[\"status\"], result[\"stdout\"], result[\"stderr\"]])
+	env.remove(\"GLEE_TEST_GREETING\")
+	println([env.get(\"GLEE_TEST_GREETING\"), env.vars().containsKey(\"GLEE_TEST_GREETING\")])
+	println(process.run(\"sh\", [\"-c\", \"echo [\\$GLEE_TEST_GREETING]\"])[\"stdout\"])
+	println(os.args())
+	try {
+		process.run(\"glee-test-no-such-program\", [])
+	} catch (e) {
+		println(e.startsWith(\"Cannot run 'glee-test-no-such-program': \"))
+	}
+}");
+	assert_eq!(out, "\
+null
+[hello, hello]
+[3, hello\n, oops\n]
+[null, false]
+[]
+
+[]
+true
+");
+	// Each run starts from the environment of the process
+	for _ in 0..2 {
+		let (out, _) = run("fn main() {
+	println(env.get(\"GLEE_TEST_LEFT\"))
+	env.set(\"GLEE_TEST_LEFT\", \"left\")
+}");
+		assert_eq!(out, "null\n");
+	}
+}
+
+#[test]
+fn programs_are_given_their_arguments() {
+	let program = checked("fn main() {
+	println(os.args())
+}");
+	let args = ["one".to_string(), "two words".to_string()];
+	let mut out = Vec::new();
+	vm::run(&compile(&program, None), &running(&args), &args, Settings::default(), &mut out).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), "[one, two words]\n");
+}
+
+// A server on a port of its own that answers `/missing` with a 404,
+// `/moved` with a redirect to `/echo`, `/near` and `/away` with redirects to
+// `/secrets` on the same host and on `localhost`, which answers with the
+// credentials it was sent, `/chunked` in chunks and `/slow` after a second,
+// and anything else with what it was sent, as JSON.
+fn serve() -> String {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let address = listener.local_addr().unwrap();
+	thread::spawn(move || {
+		for stream in listener.incoming().flatten() {
+			thread::spawn(move || answer(stream));
+		}
+	});
+	format!("http://{}", address)
+}
+
+fn answer(mut stream: TcpStream) {
+	let mut reader = BufReader::new(stream.try_clone().unwrap());
+	let mut head = Vec::new();
+	loop {
+		let mut line = String::new();
+		reader.read_line(&mut line).unwrap();
+		if line.trim().is_empty() {
+			break;
+		}
+		head.push(line.trim().to_string());
+	}
+	let header = |name: &str| head.iter().find_map(|line| line.strip_prefix(&format!("{}: ", name))).unwrap_or("").to_string();
+	let mut body = vec![0; header("Content-Length").parse().unwrap_or(0)];
+	reader.read_exact(&mut body).unwrap();
+	let (method, path) = head[0].split_once(' ').map(|(method, rest)| (method, rest.split(' ').next().unwrap())).unwrap();
+	let response = match path {
+		"/missing" => "HTTP/1.1 404 Not Found\r\nContent-Length: 4\r\n\r\nnope".to_string(),
+		"/moved" => "HTTP/1.1 302 Found\r\nLocation: echo?from=moved\r\nContent-Length: 0\r\n\r\n".to_string(),
+		"/chunked" => "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n7\r\n, world\r\n0\r\n\r\n".to_string(),
+		"/near" => "HTTP/1.1 302 Found\r\nLocation: /secrets\r\nContent-Length: 0\r\n\r\n".to_string(),
+		"/away" => {
+			let port = header("Host").rsplit(':').next().unwrap().to_string();
+			format!("HTTP/1.1 307 Temporary Redirect\r\nLocation: http://localhost:{}/secrets\r\nContent-Length: 0\r\n\r\n", port)
+		}
+		"/secrets" => {
+			let host = header("Host").rsplit_once(':').unwrap().0.to_string();
+			let secrets = format!("{} got '{}' and '{}'", host, header("Authorization"), header("Cookie"));
+			format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", secrets.len(), secrets)
+		}
+		_ => {
+			if path == "/slow" {
+				thread::sleep(Duration::from_secs(1));
+			}
+			let echo = format!(
+				"{{\"method\": {:?}, \"path\": {:?}, \"agent\": {:?}, \"type\": {:?}, \"body\": {:?}}}",
+				method,
+				path,
+				header("User-Agent"),
+				header("Content-Type"),
+				String::from_utf8(body).unwrap()
+			);
+			format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nX-Twice: a\r\nX-Twice: b\r\n\r\n{}", echo)
+		}
+	};
+	let _ = stream.write_all(response.as_bytes());
+}
+
+#[test]
+fn spawned_blocks_are_joined_and_send_over_channels() {
+	let (out, uncaught) = run("async fn produce(out: Channel<Int>, n: Int) {
+	for (i in 0..n) {
+		out.send(i * 10)
+	}
+	out.close()
+}
+
+async fn main() {
+	val numbers: Channel<Int> = channel()
+	val producer = spawn {
+		await produce(numbers, 4)
+		4
+	}
+	val consumer = spawn {
+		mut total = 0
+		mut next = await numbers.receive()
+		while (next != null) {
+			total += next
+			next = await numbers.receive()
+		}
+		total
+	}
+	println(await join([producer, consumer]))
+	println(await join([spawn { 1 + 1 }, spawn { 2 * 3 }]))
+	println([numbers.isClosed(), numbers])
+	numbers.send(1)
+}");
+	assert_eq!(out, "[4, 60]\n[2, 6]\n[true, <channel>]\n");
+	assert_eq!(uncaught.unwrap().message, "Cannot send on a closed channel");
+}
+
+#[test]
+fn web_apis_are_called_over_http_with_and_without_waiting() {
+	let url = serve();
+	let source = "async fn main() {
+	val response = http.get(\"URL/echo?x=1\")
+	println([response[\"status\"], response[\"headers\"][\"x-twice\"]])
+	println(response[\"body\"])
+	println(http.post(\"URL/echo\", \"hi\")[\"body\"])
+	println(http.postJson(\"URL/echo\", [\"n\": [1, 2]]))
+	println(http.getJson(\"URL/moved\")[\"path\"])
+	println(http.get(\"URL/chunked\")[\"body\"])
+	val put = http.request(\"put\", \"URL/put\", [\"headers\": [\"User-Agent\": \"tester\"], \"body\": \"x\", \"timeout\": 2000])
+	println(put[\"body\"])
+	val slow = http.getJsonAsync(\"URL/slow\")
+	val fast = http.getAsync(\"URL/missing\")
+	println((await fast)[\"status\"])
+	println((await slow)[\"path\"])
+	val credentials = [\"headers\": [\"Authorization\": \"Bearer t\", \"Cookie\": \"id=1\"]]
+	println(http.request(\"GET\", \"URL/near\", credentials)[\"body\"])
+	println(http.request(\"GET\", \"URL/away\", credentials)[\"body\"])
+	for attempt in [
+		() => http.getJson(\"URL/missing\"),
+		() => http.get(\"https://example.com\"),
+		() => http.get(\"URL/echo HTTP/1.1\\r\\nX-Injected: 1\\r\\n\\r\\nGET /echo\"),
+		() => http.get(\"URL/echo?q=a b\"),
+		() => http.request(\"GET\", \"URL/\", [\"retries\": 3]),
+		() => http.request(\"GET\", \"URL/slow\", [\"timeout\": 100])
+	] {
+		try {
+			attempt()
+		} catch (e) {
+			println(e)
+		}
+	}
+}"
+	.replace("URL", &url);
+	let (out, _) = run(&source);
+	assert_eq!(
+		out.replace(&url, "URL"),
+		"\
+[200, a, b]
+{\"method\": \"GET\", \"path\": \"/echo?x=1\", \"agent\": \"dotfun\", \"type\": \"\", \"body\": \"\"}
+{\"method\": \"POST\", \"path\": \"/echo\", \"agent\": \"dotfun\", \"type\": \"text/plain; charset=utf-8\", \"body\": \"hi\"}
+{agent: dotfun, body: {\"n\":[1,2]}, method: POST, path: /echo, type: application/json}
+/echo?from=moved
+hello, world
+{\"method\": \"PUT\", \"path\": \"/put\", \"agent\": \"tester\", \"type\": \"\", \"body\": \"x\"}
+404
+/slow
+127.0.0.1 got 'Bearer t' and 'id=1'
+localhost got '' and ''
+GET 'URL/missing' failed with status 404
+Cannot GET 'https://example.com': there is no TLS for 'https://', only plain 'http://'
+Cannot GET 'URL/echo HTTP/1.1\r\nX-Injected: 1\r\n\r\nGET /echo': a URL has no spaces or control characters in it
+Cannot GET 'URL/echo?q=a b': a URL has no spaces or control characters in it
+A request has no option 'retries'; it takes 'headers', 'body' and 'timeout'
+Cannot GET 'URL/slow': it took longer than 100 ms
+"
+	);
+}
@@ -0,0 +1,43 @@
+// What `--timings` reports about the phases of a build.
+
+use std::time::Duration;
+
+use glee::diagnostics::timings::{self, Event};
+
+#[test]
+fn phases_are_timed_once_started() {
+	assert_eq!(timings::time("lex", "a.gl", || 1), 1);
+	assert!(timings::take().0.is_empty());
+
+	timings::start();
+	timings::time("lex", "a.gl", || ());
+	timings::time("parse", "a.gl", || ());
+	timings::time("lex", "b.gl", || ());
+	let (events, _) = timings::take();
+	let timed: Vec<(&str, &str)> = events.iter().map(|event| (event.phase, event.file.as_str())).collect();
+	assert_eq!(timed, [("lex", "a.gl"), ("parse", "a.gl"), ("lex", "b.gl")]);
+
+	let event = |phase, file: &str, start, duration, thread, allocated| Event {
+		phase,
+		file: file.to_string(),
+		start: Duration::from_micros(start),
+		duration: Duration::from_micros(duration),
+		thread,
+		allocated
+	};
+	let events = [event("lex", "a.gl", 0, 250, 0, 512), event("lex", "b.gl", 10, 750, 1, 2048), event("check", "", 800, 1000, 0, 3 << 20)];
+	assert_eq!(timings::table(&events, Duration::from_millis(2)), [
+		"phase        runs       time   share   allocated",
+		"lex             2     1.00ms   50.0%     2.5 KiB",
+		"check           1     1.00ms   50.0%     3.0 MiB",
+		"total                 2.00ms",
+		"on 2 threads"
+	].join("\n"));
+	let trace = timings::trace(&events[2..]);
+	assert_eq!(trace, [
+		"{\"traceEvents\":[",
+		"{\"name\":\"check\",\"cat\":\"compiler\",\"ph\":\"X\",\"ts\":800,\"dur\":1000,\"pid\":1,\"tid\":0,\"args\":{\"file\":\"\",\"allocated\":3145728}},",
+		"{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":0,\"args\":{\"name\":\"thread 0\"}}",
+		"]}\n"
+	].join("\n"));
+}
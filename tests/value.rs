@@ -0,0 +1,67 @@
+// The equality and hashing every part of the runtime shares, and maps built
+// on them.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use glee::interpreter::value::{Class, Key, Map, Object, Value};
+use glee::sema::types::TypeId;
+
+fn hash(value: &Value) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	Key(value.clone()).hash(&mut hasher);
+	hasher.finish()
+}
+
+fn object(class: &Rc<Class>, fields: Vec<Value>) -> Value {
+	Value::Object(Rc::new(Object { class: class.clone(), fields: RefCell::new(fields), variant: None }))
+}
+
+fn class(name: &str, by_value: bool) -> Rc<Class> {
+	let fields = vec!["x".to_string(), "y".to_string()];
+	Rc::new(Class { id: TypeId(0), name: name.to_string(), by_value, fields, variants: Vec::new(), supertypes: Vec::new(), methods: HashMap::new() })
+}
+
+#[test]
+fn numbers_strings_and_ranges_are_equal_by_value() {
+	assert_eq!(Value::Int(1), Value::Float(1.0));
+	assert_eq!(hash(&Value::Int(1)), hash(&Value::Float(1.0)));
+	assert_ne!(Value::Int(i64::MAX), Value::Float(i64::MAX as f64));
+	assert_ne!(Value::Float(f64::NAN), Value::Float(f64::NAN));
+	assert_eq!(Key(Value::Float(f64::NAN)), Key(Value::Float(-f64::NAN)));
+	assert_eq!(Value::string("a"), Value::string(String::from("a")));
+	assert_eq!(Value::Range(0, 2), Value::Range(0, 2));
+	assert_ne!(Value::Null, Value::Bool(false));
+}
+
+#[test]
+fn only_structs_and_data_classes_are_equal_by_their_fields() {
+	let point = class("Point", true);
+	let (a, b) = (object(&point, vec![Value::Int(1), Value::Int(2)]), object(&point, vec![Value::Int(1), Value::Float(2.0)]));
+	assert_eq!(a, b);
+	assert_eq!(hash(&a), hash(&b));
+	assert_ne!(a, object(&point, vec![Value::Int(1), Value::Int(3)]));
+
+	let node = class("Node", false);
+	let c = object(&node, vec![Value::Null, Value::Null]);
+	assert_ne!(c, object(&node, vec![Value::Null, Value::Null]));
+	assert_eq!(c, c.clone());
+	assert_ne!(Value::array(Vec::new()), Value::array(Vec::new()));
+}
+
+#[test]
+fn maps_keep_the_order_keys_were_added_in() {
+	let mut map = Map::new();
+	map.insert(Value::string("b"), Value::Int(1));
+	map.insert(Value::Int(2), Value::Int(2));
+	map.insert(Value::string("a"), Value::Int(3));
+	assert_eq!(map.insert(Value::Float(2.0), Value::Int(4)), Some(Value::Int(2)));
+	assert_eq!(map.remove(&Value::string("b")), Some(Value::Int(1)));
+	assert_eq!(map.get(&Value::string("a")), Some(&Value::Int(3)));
+	let keys: Vec<Value> = map.iter().map(|(key, _)| key.clone()).collect();
+	assert_eq!(keys, [Value::Int(2), Value::string("a")]);
+	assert_eq!(map.len(), 2);
+}